@@ -0,0 +1,289 @@
+//! Benchmarks of the core world operations, run with `cargo bench`.
+//!
+//! Each group measures one operation at a few sizes, so storage and
+//! recording changes can be compared against a saved baseline:
+//!
+//! ```bash
+//! cargo bench -- --save-baseline before
+//! cargo bench -- --baseline before
+//! ```
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use rust_ecs::{
+    replay_analysis, Diff, DiffComponent, Entity, In, Out, Soa, SoaComponent, StorageKind, System, World, WorldUpdateHistory,
+    WorldView,
+};
+
+#[derive(Debug, Clone, PartialEq, Diff, Soa)]
+struct Position {
+    x: f32,
+    y: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Diff, Soa)]
+struct Velocity {
+    dx: f32,
+    dy: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Diff)]
+struct Inventory {
+    items: Vec<u32>,
+    owner: String,
+}
+
+macro_rules! components {
+    ($($name:ident),*) => {
+        $(
+            #[derive(Debug, Clone, Copy, PartialEq)]
+            struct $name(u32);
+        )*
+    };
+}
+
+components!(C1, C2, C3, C4, C5, C6, C7, C8);
+
+/// Moves every entity by its velocity, recording the changes
+struct MovementSystem;
+
+impl System for MovementSystem {
+    type InComponents = (Velocity,);
+    type OutComponents = (Position,);
+
+    fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+    fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+        let mut changes = Vec::new();
+        for (entity, (position, velocity)) in world.query_components::<(Out<Position>, In<Velocity>)>() {
+            let old = position.clone();
+            position.x += velocity.dx;
+            position.y += velocity.dy;
+            changes.push((entity, old, position.clone()));
+        }
+        for (entity, old, new) in changes {
+            world.record_component_modification(entity, &old, &new);
+        }
+    }
+
+    fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+}
+
+/// Moves every entity by its velocity through columns, recording the changes
+struct ColumnMovementSystem;
+
+impl System for ColumnMovementSystem {
+    type InComponents = (Velocity,);
+    type OutComponents = (Position,);
+
+    fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+    fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+        let mut batch = world.query_columns::<(Out<Position>, In<Velocity>)>();
+        let (_, [x, y, dx, dy]) = batch.columns_mut();
+        for i in 0..x.len() {
+            x[i] += dx[i];
+            y[i] += dy[i];
+        }
+        world.write_columns(&batch);
+    }
+
+    fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+}
+
+fn moving_world(entities: usize) -> World {
+    let mut world = World::new();
+    for i in 0..entities {
+        let entity = world.create_entity();
+        world.add_component(entity, Position { x: i as f32, y: 0.0 });
+        world.add_component(entity, Velocity { dx: 1.0, dy: 0.5 });
+    }
+    world
+}
+
+fn world_with_components(entities: usize) -> World {
+    let mut world = World::new();
+    for i in 0..entities as u32 {
+        let entity = world.create_entity();
+        world.add_component(entity, C1(i));
+        world.add_component(entity, C2(i));
+        world.add_component(entity, C3(i));
+        world.add_component(entity, C4(i));
+        world.add_component(entity, C5(i));
+        world.add_component(entity, C6(i));
+        world.add_component(entity, C7(i));
+        world.add_component(entity, C8(i));
+    }
+    world
+}
+
+fn recorded_history(frames: usize) -> WorldUpdateHistory {
+    let mut world = moving_world(50);
+    world.add_system(MovementSystem);
+    world.initialize_systems();
+    for _ in 0..frames {
+        world.update();
+    }
+    world.get_update_history().clone()
+}
+
+fn spawn(c: &mut Criterion) {
+    let mut group = c.benchmark_group("spawn");
+    for count in [100, 1_000, 10_000] {
+        group.throughput(Throughput::Elements(count as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(count), &count, |b, &count| {
+            b.iter(|| moving_world(black_box(count)));
+        });
+    }
+    group.finish();
+}
+
+fn query(c: &mut Criterion) {
+    let world = world_with_components(1_000);
+    let mut group = c.benchmark_group("query");
+    group.throughput(Throughput::Elements(1_000));
+    group.bench_function("1 component", |b| b.iter(|| world.query::<(In<C1>,)>().len()));
+    group.bench_function("2 components", |b| b.iter(|| world.query::<(In<C1>, In<C2>)>().len()));
+    group.bench_function("4 components", |b| {
+        b.iter(|| world.query::<(In<C1>, In<C2>, In<C3>, In<C4>)>().len())
+    });
+    group.bench_function("8 components", |b| {
+        b.iter(|| world.query::<(In<C1>, In<C2>, In<C3>, In<C4>, In<C5>, In<C6>, In<C7>, In<C8>)>().len())
+    });
+    group.finish();
+}
+
+/// Moving entities by their velocity through references and through columns
+fn columns(c: &mut Criterion) {
+    let mut group = c.benchmark_group("columns");
+    group.throughput(Throughput::Elements(1_000));
+    let mut world = moving_world(1_000);
+    world.add_system(MovementSystem);
+    world.initialize_systems();
+    group.bench_function("references", |b| b.iter(|| world.update()));
+    let mut world = moving_world(1_000);
+    world.add_system(ColumnMovementSystem);
+    world.initialize_systems();
+    group.bench_function("columns", |b| b.iter(|| world.update()));
+    group.finish();
+}
+
+fn add_remove(c: &mut Criterion) {
+    let mut group = c.benchmark_group("add_remove");
+    group.throughput(Throughput::Elements(1_000));
+    group.bench_function("add", |b| {
+        b.iter_batched(
+            || {
+                let mut world = World::new();
+                let entities: Vec<Entity> = (0..1_000).map(|_| world.create_entity()).collect();
+                (world, entities)
+            },
+            |(mut world, entities)| {
+                for entity in entities {
+                    world.add_component(entity, Position { x: 0.0, y: 0.0 });
+                }
+                world
+            },
+            BatchSize::SmallInput,
+        );
+    });
+    group.bench_function("remove", |b| {
+        b.iter_batched(
+            || moving_world(1_000),
+            |mut world| {
+                for entity in world.entities_with_component::<Velocity>() {
+                    world.remove_component::<Velocity>(entity);
+                }
+                world
+            },
+            BatchSize::SmallInput,
+        );
+    });
+    group.finish();
+}
+
+/// Querying and looking up a component one entity in a hundred has, stored
+/// densely and sparsely
+fn storage(c: &mut Criterion) {
+    let mut group = c.benchmark_group("storage");
+    for kind in [StorageKind::Dense, StorageKind::Sparse] {
+        let mut world = world_with_components(10_000);
+        world.register_storage::<Inventory>(kind).unwrap();
+        let entities: Vec<Entity> = world.entities_with_component::<C1>().into_iter().step_by(100).collect();
+        for entity in &entities {
+            world.add_component(*entity, Inventory { items: Vec::new(), owner: String::new() });
+        }
+        let name = format!("{:?}", kind);
+        group.bench_function(BenchmarkId::new("rare query", &name), |b| {
+            b.iter(|| world.query::<(In<C1>, In<Inventory>)>().len())
+        });
+        group.bench_function(BenchmarkId::new("rare lookup", &name), |b| {
+            b.iter(|| entities.iter().filter(|entity| world.get_component::<Inventory>(**entity).is_some()).count())
+        });
+    }
+    group.finish();
+}
+
+fn diff(c: &mut Criterion) {
+    let mut group = c.benchmark_group("diff");
+    let before = Position { x: 1.0, y: 2.0 };
+    let after = Position { x: 3.0, y: 2.0 };
+    group.bench_function("derived struct", |b| b.iter(|| black_box(&before).diff(black_box(&after))));
+    for len in [10, 1_000] {
+        let before = Inventory {
+            items: (0..len).collect(),
+            owner: "village".to_string(),
+        };
+        let mut after = before.clone();
+        after.items.iter_mut().step_by(3).for_each(|item| *item += 1);
+        after.items.push(len);
+        let changes = before.diff(&after).unwrap();
+        group.bench_with_input(BenchmarkId::new("vec", len), &len, |b, _| {
+            b.iter(|| black_box(&before).diff(black_box(&after)))
+        });
+        group.bench_with_input(BenchmarkId::new("apply vec", len), &len, |b, _| {
+            b.iter_batched(
+                || before.clone(),
+                |mut inventory| inventory.apply_diff(black_box(&changes)),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn update(c: &mut Criterion) {
+    let mut group = c.benchmark_group("update");
+    for systems in [1, 4, 16] {
+        group.bench_with_input(BenchmarkId::new("systems", systems), &systems, |b, &systems| {
+            let mut world = moving_world(100);
+            for _ in 0..systems {
+                world.add_system(MovementSystem);
+            }
+            world.initialize_systems();
+            b.iter(|| world.update());
+        });
+    }
+    group.finish();
+}
+
+fn replay_log(c: &mut Criterion) {
+    let history = recorded_history(100);
+    let mut log = Vec::new();
+    replay_analysis::write_replay_log(&history, &mut log).unwrap();
+    let log = String::from_utf8(log).unwrap();
+
+    let mut group = c.benchmark_group("replay_log");
+    group.throughput(Throughput::Bytes(log.len() as u64));
+    group.bench_function("write", |b| {
+        b.iter(|| {
+            let mut out = Vec::with_capacity(log.len());
+            replay_analysis::write_replay_log(black_box(&history), &mut out).unwrap();
+            out
+        })
+    });
+    group.bench_function("parse", |b| b.iter(|| replay_analysis::parse_replay_log_str(black_box(&log))));
+    group.finish();
+}
+
+criterion_group!(benches, spawn, query, columns, add_remove, storage, diff, update, replay_log);
+criterion_main!(benches);
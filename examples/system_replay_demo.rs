@@ -14,7 +14,7 @@ fn main() {
     original_world.add_system(WaitSystem);
     
     println!("   - Adding RenderSystem");
-    original_world.add_system(RenderSystem::default());
+    original_world.add_system(RenderSystem);
     
     // Run a few updates
     println!("   - Running 2 updates");
@@ -55,16 +55,13 @@ fn main() {
     println!("   - Running an update on the fresh world to verify it works:");
     fresh_world.update();
     
-    let fresh_history = fresh_world.get_update_history();
-    println!("   - Fresh world update history length: {}", fresh_history.len());
+    print!("{}", fresh_world.stats());
     println!("     (1 new update - replay doesn't re-record operations)\n");
     
     // Scenario 3: Use the game's initialize_game function
     println!("3. Using the game's initialize_game function:");
     let game_world = initialize_game();
-    let game_history = game_world.get_update_history();
-    
-    println!("   - initialize_game() recorded {} operations", game_history.len());
+    print!("{}", game_world.stats());
     println!("     (This demonstrates that system additions in initialize_game are now recorded)");
     
     println!("\n✅ System addition recording and replay functionality working correctly!");
@@ -0,0 +1,26 @@
+//! Feeds arbitrary text to the replay log parser and validator, run with
+//! `cargo +nightly fuzz run parse_replay_log` from the repository root.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rust_ecs::replay_analysis;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(contents) = std::str::from_utf8(data) else {
+        return;
+    };
+    let (history, errors) = replay_analysis::parse_replay_log_with_errors(contents);
+    let lines = contents.lines().count();
+    for error in &errors {
+        assert!((1..=lines).contains(&error.line), "{} of {} lines", error, lines);
+    }
+    replay_analysis::validate_replay_log_str(contents);
+
+    // Whatever was parsed is written as a log that parses without errors
+    let mut log = Vec::new();
+    replay_analysis::write_replay_log(&history, &mut log).unwrap();
+    let log = String::from_utf8(log).unwrap();
+    let (_, errors) = replay_analysis::parse_replay_log_with_errors(&log);
+    assert!(errors.is_empty(), "{:?} in\n{}", errors, log);
+});
@@ -4,14 +4,70 @@ use proc_macro::TokenStream;
 use quote::quote;
 use syn::{parse_macro_input, Data, DeriveInput, Fields};
 
-/// Derive macro for automatically implementing Diff trait
-#[proc_macro_derive(Diff)]
+/// Serialize and Deserialize derives for generated diffs. The `serde` feature
+/// of this crate is enabled by the one of rust_ecs, features of the crate
+/// using the derive do not matter.
+fn serde_derives() -> proc_macro2::TokenStream {
+    if cfg!(feature = "serde") {
+        quote! { #[derive(serde::Serialize, serde::Deserialize)] }
+    } else {
+        quote! {}
+    }
+}
+
+/// Module named by a `#[diff(with = "path::to::module")]` attribute
+fn diff_with(attrs: &[syn::Attribute]) -> syn::Result<Option<syn::Path>> {
+    let mut with = None;
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("diff")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("with") {
+                with = Some(meta.value()?.parse::<syn::LitStr>()?.parse::<syn::Path>()?);
+                Ok(())
+            } else {
+                Err(meta.error("expected `with = \"path::to::module\"`"))
+            }
+        })?;
+    }
+    Ok(with)
+}
+
+/// Derive macro for automatically implementing Diff trait.
+///
+/// Types and fields that need hand-tuned diffing, like grids diffed as lists
+/// of dirty cells, delegate to a module with `#[diff(with = "path::to::module")]`.
+/// The module provides `type Diff`, `fn diff(old: &T, new: &T) -> Option<Diff>`
+/// and `fn apply_diff(value: &mut T, diff: &Diff)`. On a field, the generated
+/// diff struct keeps the module's diff for that field.
+#[proc_macro_derive(Diff, attributes(diff))]
 pub fn derive_diff(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
 
     let name = &input.ident;
     let diff_name = syn::Ident::new(&format!("{}Diff", name), name.span());
 
+    match diff_with(&input.attrs) {
+        Ok(Some(with)) => {
+            let expanded = quote! {
+                impl crate::Diff for #name {
+                    type Diff = #with::Diff;
+
+                    fn diff(&self, other: &Self) -> Option<Self::Diff> {
+                        #with::diff(self, other)
+                    }
+
+                    fn apply_diff(&mut self, diff: &Self::Diff) {
+                        #with::apply_diff(self, diff)
+                    }
+                }
+
+                impl crate::DiffComponent for #name {}
+            };
+            return TokenStream::from(expanded);
+        }
+        Ok(None) => {}
+        Err(e) => return e.to_compile_error().into(),
+    }
+
     match &input.data {
         Data::Struct(data_struct) => {
             match &data_struct.fields {
@@ -19,20 +75,37 @@ pub fn derive_diff(input: TokenStream) -> TokenStream {
                     // Handle structs with named fields
                     let field_names: Vec<_> = fields.named.iter().map(|f| &f.ident).collect();
                     let field_types: Vec<_> = fields.named.iter().map(|f| &f.ty).collect();
+                    let field_withs = match fields
+                        .named
+                        .iter()
+                        .map(|f| diff_with(&f.attrs))
+                        .collect::<syn::Result<Vec<_>>>()
+                    {
+                        Ok(withs) => withs,
+                        Err(e) => return e.to_compile_error().into(),
+                    };
 
                     let diff_fields = field_names
                         .iter()
                         .zip(field_types.iter())
-                        .map(|(name, ty)| {
-                            quote! {
+                        .zip(field_withs.iter())
+                        .map(|((name, ty), with)| match with {
+                            Some(with) => quote! {
+                                pub #name: Option<#with::Diff>
+                            },
+                            None => quote! {
                                 pub #name: Option<<#ty as crate::Diff>::Diff>
-                            }
+                            },
                         });
 
-                    let diff_computation = field_names.iter().map(|name| {
+                    let diff_computation = field_names.iter().zip(field_withs.iter()).map(|(name, with)| {
+                        let field_diff = match with {
+                            Some(with) => quote! { #with::diff(&self.#name, &other.#name) },
+                            None => quote! { self.#name.diff(&other.#name) },
+                        };
                         quote! {
                             #name: {
-                                let field_diff = self.#name.diff(&other.#name);
+                                let field_diff = #field_diff;
                                 if field_diff.is_some() {
                                     has_changes = true;
                                 }
@@ -41,16 +114,23 @@ pub fn derive_diff(input: TokenStream) -> TokenStream {
                         }
                     });
 
-                    let apply_diff_operations = field_names.iter().map(|name| {
+                    let apply_diff_operations = field_names.iter().zip(field_withs.iter()).map(|(name, with)| {
+                        let apply = match with {
+                            Some(with) => quote! { #with::apply_diff(&mut self.#name, field_diff) },
+                            None => quote! { self.#name.apply_diff(field_diff) },
+                        };
                         quote! {
                             if let Some(ref field_diff) = diff.#name {
-                                self.#name.apply_diff(field_diff);
+                                #apply;
                             }
                         }
                     });
 
+                    // The diff is serialized when replayable components are recorded
+                    let serde_derives = serde_derives();
                     let expanded = quote! {
                         #[derive(Clone, Debug)]
+                        #serde_derives
                         pub struct #diff_name {
                             #(#diff_fields,)*
                         }
@@ -135,3 +215,230 @@ pub fn derive_diff(input: TokenStream) -> TokenStream {
         }
     }
 }
+
+/// Derive macro for implementing Reflect, exposing fields by name.
+///
+/// Supports structs with named fields, unit structs and enums whose variants
+/// have no fields (reflected as the variant name).
+#[proc_macro_derive(Reflect)]
+pub fn derive_reflect(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+    let name_str = name.to_string();
+
+    let expanded = match &input.data {
+        Data::Struct(data_struct) => match &data_struct.fields {
+            Fields::Named(fields) => {
+                let field_idents: Vec<_> = fields.named.iter().map(|f| &f.ident).collect();
+                let field_types: Vec<_> = fields.named.iter().map(|f| &f.ty).collect();
+                let field_strs: Vec<String> = field_idents
+                    .iter()
+                    .map(|ident| ident.as_ref().unwrap().to_string())
+                    .collect();
+
+                quote! {
+                    impl crate::Reflect for #name {
+                        fn field_names() -> &'static [&'static str] {
+                            &[#(#field_strs),*]
+                        }
+
+                        fn field(&self, name: &str) -> Option<crate::Value> {
+                            match name {
+                                #(#field_strs => Some(crate::Reflect::to_value(&self.#field_idents)),)*
+                                _ => None,
+                            }
+                        }
+
+                        fn set_field(&mut self, name: &str, value: &crate::Value) -> Result<(), String> {
+                            match name {
+                                #(#field_strs => {
+                                    self.#field_idents = <#field_types as crate::Reflect>::from_value(value)
+                                        .map_err(|e| format!("{}.{}: {}", #name_str, name, e))?;
+                                    Ok(())
+                                })*
+                                _ => Err(format!("{} has no field '{}'", #name_str, name)),
+                            }
+                        }
+
+                        fn to_value(&self) -> crate::Value {
+                            let mut map = std::collections::BTreeMap::new();
+                            #(map.insert(#field_strs.to_string(), crate::Reflect::to_value(&self.#field_idents));)*
+                            crate::Value::Map(map)
+                        }
+
+                        fn from_value(value: &crate::Value) -> Result<Self, String> {
+                            let crate::Value::Map(map) = value else {
+                                return Err(format!("expected a map for {}, found {}", #name_str, value));
+                            };
+                            Ok(Self {
+                                #(#field_idents: {
+                                    let field = map
+                                        .get(#field_strs)
+                                        .ok_or_else(|| format!("missing field {}.{}", #name_str, #field_strs))?;
+                                    <#field_types as crate::Reflect>::from_value(field)
+                                        .map_err(|e| format!("{}.{}: {}", #name_str, #field_strs, e))?
+                                },)*
+                            })
+                        }
+                    }
+                }
+            }
+            Fields::Unit => quote! {
+                impl crate::Reflect for #name {
+                    fn to_value(&self) -> crate::Value {
+                        crate::Value::Unit
+                    }
+
+                    fn from_value(value: &crate::Value) -> Result<Self, String> {
+                        match value {
+                            crate::Value::Unit => Ok(Self),
+                            crate::Value::Map(map) if map.is_empty() => Ok(Self),
+                            _ => Err(format!("expected () for {}, found {}", #name_str, value)),
+                        }
+                    }
+                }
+            },
+            Fields::Unnamed(_) => {
+                panic!("Reflect derive macro does not support tuple structs yet");
+            }
+        },
+        Data::Enum(data_enum) => {
+            if data_enum.variants.iter().any(|variant| !matches!(variant.fields, Fields::Unit)) {
+                panic!("Reflect derive macro only supports enums whose variants have no fields");
+            }
+            let variants: Vec<_> = data_enum.variants.iter().map(|variant| &variant.ident).collect();
+            let variant_strs: Vec<String> = variants.iter().map(|variant| variant.to_string()).collect();
+
+            quote! {
+                impl crate::Reflect for #name {
+                    fn to_value(&self) -> crate::Value {
+                        let variant = match self {
+                            #(Self::#variants => #variant_strs,)*
+                        };
+                        crate::Value::String(variant.to_string())
+                    }
+
+                    fn from_value(value: &crate::Value) -> Result<Self, String> {
+                        match value {
+                            #(crate::Value::String(variant) if variant == #variant_strs => Ok(Self::#variants),)*
+                            _ => Err(format!("{} is not a variant of {}", value, #name_str)),
+                        }
+                    }
+                }
+            }
+        }
+        Data::Union(_) => {
+            panic!("Reflect derive macro does not support unions");
+        }
+    };
+
+    TokenStream::from(expanded)
+}
+
+/// Derive macro for implementing Component, registering the type in every
+/// new world when the `auto-register` feature is enabled.
+///
+/// The type must implement Reflect. With `#[component(replay)]` it is
+/// registered for replay (requires Diff and serde) when rust_ecs's `serde`
+/// feature is enabled, otherwise for reflection only.
+#[proc_macro_derive(Component, attributes(component))]
+pub fn derive_component(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+
+    let mut replay = false;
+    for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("component")) {
+        let parsed = attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("replay") {
+                replay = true;
+                Ok(())
+            } else {
+                Err(meta.error("expected `replay`"))
+            }
+        });
+        if let Err(e) = parsed {
+            return e.to_compile_error().into();
+        }
+    }
+
+    let register = if replay && cfg!(feature = "serde") {
+        quote! {
+            registry.register_replayable::<Self>();
+        }
+    } else {
+        quote! {
+            registry.register::<Self>();
+        }
+    };
+
+    let expanded = quote! {
+        impl crate::Component for #name {
+            const NAME: &'static str = stringify!(#name);
+
+            fn register(registry: &mut crate::ComponentRegistry) {
+                #register
+            }
+        }
+
+        #[cfg(feature = "auto-register")]
+        crate::inventory::submit! {
+            crate::component::ComponentInfo::of::<#name>()
+        }
+    };
+
+    TokenStream::from(expanded)
+}
+
+/// Derive macro for implementing SoaComponent, so queries can return the
+/// type's fields as columns of f32.
+///
+/// Supports structs whose named fields are all `f32`; the columns follow the
+/// field order.
+#[proc_macro_derive(Soa)]
+pub fn derive_soa(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+
+    let Data::Struct(data_struct) = &input.data else {
+        return syn::Error::new_spanned(name, "Soa derive macro only supports structs")
+            .to_compile_error()
+            .into();
+    };
+    let Fields::Named(fields) = &data_struct.fields else {
+        return syn::Error::new_spanned(name, "Soa derive macro only supports structs with named fields")
+            .to_compile_error()
+            .into();
+    };
+    if let Some(field) = fields.named.iter().find(|f| {
+        let ty = &f.ty;
+        quote!(#ty).to_string() != "f32"
+    }) {
+        return syn::Error::new_spanned(&field.ty, "Soa fields must be f32")
+            .to_compile_error()
+            .into();
+    }
+    let field_idents: Vec<_> = fields.named.iter().map(|f| &f.ident).collect();
+    let field_strs: Vec<String> = field_idents
+        .iter()
+        .map(|ident| ident.as_ref().unwrap().to_string())
+        .collect();
+    let indices: Vec<usize> = (0..field_idents.len()).collect();
+
+    let expanded = quote! {
+        impl crate::SoaComponent for #name {
+            fn field_names() -> &'static [&'static str] {
+                &[#(#field_strs),*]
+            }
+
+            fn to_lanes(&self, lanes: &mut [f32]) {
+                #(lanes[#indices] = self.#field_idents;)*
+            }
+
+            fn set_lanes(&mut self, lanes: &[f32]) {
+                #(self.#field_idents = lanes[#indices];)*
+            }
+        }
+    };
+
+    TokenStream::from(expanded)
+}
@@ -0,0 +1,208 @@
+//! Counting how often systems read and write each component type.
+//!
+//! With `World::set_access_profiling(true)` every component a system reads or
+//! writes through its view is counted: a query counts one access per matched
+//! entity for each of its components, `get_component` and the other single
+//! entity methods count one. After each frame `access_profile` returns the
+//! counts of that frame, hottest component first:
+//!
+//! ```ignore
+//! world.set_access_profiling(true);
+//! world.update();
+//! print!("{}", world.access_profile().unwrap());
+//! ```
+//!
+//! ```text
+//! Component access profile of frame 12
+//!   Position: 40000 reads, 120 writes
+//!   Velocity: 40000 reads, 0 writes
+//! ```
+//!
+//! Accesses made directly on the `World` outside of systems are not counted.
+
+use crate::{ComponentAccess, World};
+use std::any::TypeId;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+/// Reads and writes of one component type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ComponentAccessCount {
+    /// Short type name of the component
+    pub component: &'static str,
+    pub reads: u64,
+    pub writes: u64,
+}
+
+impl ComponentAccessCount {
+    /// Get the number of reads and writes together
+    pub fn total(&self) -> u64 {
+        self.reads + self.writes
+    }
+}
+
+/// Component accesses of one frame, returned by `World::access_profile`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccessProfile {
+    /// Number of the profiled frame
+    pub frame: usize,
+    /// Accessed component types, most accessed first
+    pub components: Vec<ComponentAccessCount>,
+}
+
+impl AccessProfile {
+    /// Get the counts of a component type by its short name
+    pub fn get(&self, component: &str) -> Option<&ComponentAccessCount> {
+        self.components.iter().find(|count| count.component == component)
+    }
+}
+
+impl std::fmt::Display for AccessProfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Component access profile of frame {}", self.frame)?;
+        if self.components.is_empty() {
+            writeln!(f, "  no component accesses")?;
+        }
+        for count in &self.components {
+            writeln!(f, "  {}: {} reads, {} writes", count.component, count.reads, count.writes)?;
+        }
+        Ok(())
+    }
+}
+
+/// Counts of the frame in progress and the profile of the last completed one.
+/// Counted through a shared reference, so read-only views can count too.
+#[derive(Debug, Default)]
+pub(crate) struct AccessProfiler {
+    counts: RefCell<HashMap<TypeId, ComponentAccessCount>>,
+    last_frame: Option<AccessProfile>,
+}
+
+impl World {
+    /// Count the component accesses of systems, see the module documentation.
+    /// Off by default.
+    pub fn set_access_profiling(&mut self, enabled: bool) {
+        self.access_profiler = enabled.then(AccessProfiler::default);
+    }
+
+    /// Get the component accesses of the last completed frame, or None if
+    /// profiling is off or no frame completed since it was turned on
+    pub fn access_profile(&self) -> Option<&AccessProfile> {
+        self.access_profiler.as_ref()?.last_frame.as_ref()
+    }
+
+    /// Count `times` accesses of each of the components, if profiling is on
+    pub(crate) fn profile_accesses(&self, accesses: impl FnOnce() -> Vec<ComponentAccess>, times: usize) {
+        let Some(profiler) = &self.access_profiler else {
+            return;
+        };
+        let mut counts = profiler.counts.borrow_mut();
+        for access in accesses() {
+            let count = counts.entry(access.type_id).or_insert(ComponentAccessCount {
+                component: access.type_name,
+                reads: 0,
+                writes: 0,
+            });
+            if access.write {
+                count.writes += times as u64;
+            } else {
+                count.reads += times as u64;
+            }
+        }
+    }
+
+    /// Forget accesses made between frames
+    pub(crate) fn begin_access_profile(&mut self) {
+        if let Some(profiler) = &mut self.access_profiler {
+            profiler.counts.get_mut().clear();
+        }
+    }
+
+    /// Keep the counts of the completed frame as its profile
+    pub(crate) fn finish_access_profile(&mut self) {
+        let frame = self.frame_number;
+        let Some(profiler) = &mut self.access_profiler else {
+            return;
+        };
+        let mut components: Vec<ComponentAccessCount> = profiler.counts.get_mut().drain().map(|(_, count)| count).collect();
+        components.sort_by(|a, b| b.total().cmp(&a.total()).then(a.component.cmp(b.component)));
+        profiler.last_frame = Some(AccessProfile { frame, components });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{In, Out, ReadOnlySystem, ReadOnlyWorldView, System, WorldView};
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Position(i32);
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Velocity(i32);
+
+    struct MovementSystem;
+
+    impl System for MovementSystem {
+        type InComponents = (Velocity,);
+        type OutComponents = (Position,);
+
+        fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+        fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+            for (_, (velocity, position)) in world.query_components::<(In<Velocity>, Out<Position>)>() {
+                position.0 += velocity.0;
+            }
+        }
+
+        fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    }
+
+    struct ReportSystem;
+
+    impl ReadOnlySystem for ReportSystem {
+        type InComponents = (Position,);
+
+        fn update(&mut self, world: &ReadOnlyWorldView<Self::InComponents>) {
+            for (entity, _) in world.query::<(In<Position>,)>() {
+                world.get_component::<Position>(entity);
+            }
+        }
+    }
+
+    #[test]
+    fn test_access_profile_counts_each_frame() {
+        let mut world = World::new();
+        for velocity in [1, 2, 3] {
+            let entity = world.create_entity();
+            world.add_component(entity, Position(0));
+            if velocity > 1 {
+                world.add_component(entity, Velocity(velocity));
+            }
+        }
+        world.add_system(MovementSystem);
+        world.add_system(ReportSystem);
+        world.update();
+        assert!(world.access_profile().is_none());
+
+        world.set_access_profiling(true);
+        world.update();
+        let profile = world.access_profile().unwrap().clone();
+        assert_eq!(profile.frame, 1);
+        let counts: Vec<(&str, u64, u64)> = profile
+            .components
+            .iter()
+            .map(|count| (count.component, count.reads, count.writes))
+            .collect();
+        assert_eq!(counts, vec![("Position", 6, 2), ("Velocity", 2, 0)]);
+        assert!(profile.to_string().contains("  Position: 6 reads, 2 writes\n"), "{}", profile);
+
+        // Counts start over every frame
+        world.update();
+        assert_eq!(world.access_profile().unwrap().frame, 2);
+        assert_eq!(world.access_profile().unwrap().get("Velocity").map(|count| count.total()), Some(2));
+
+        world.set_access_profiling(false);
+        assert!(world.access_profile().is_none());
+    }
+}
@@ -0,0 +1,181 @@
+//! Disabling entities without removing them.
+//!
+//! A disabled entity keeps its components but queries skip it, which is
+//! useful for pooled entities and paused actors. Queries that should see
+//! disabled entities too add the `IncludeDisabled` filter:
+//!
+//! ```ignore
+//! world.set_enabled(actor, false);
+//! let all = world_view.query_components::<(In<Position>, IncludeDisabled)>();
+//! ```
+//!
+//! Enabling and disabling are world operations, recorded in the history and
+//! replay logs as `DISABLE_ENTITY Entity(0, 3)` / `ENABLE_ENTITY Entity(0, 3)`.
+//! Direct access like `get_component` still reaches disabled entities.
+
+use crate::{Entity, MixedQueryComponent, World, WorldOperation, WorldView};
+
+/// Query filter that lets the query match disabled entities
+pub struct IncludeDisabled;
+
+impl<'a> MixedQueryComponent<'a> for IncludeDisabled {
+    type Item = ();
+    const INCLUDES_DISABLED: bool = true;
+
+    fn get_mixed_component(_world: &'a mut World, _entity: Entity) -> Option<Self::Item> {
+        Some(())
+    }
+}
+
+fn operation(entity: Entity, enabled: bool) -> WorldOperation {
+    if enabled {
+        WorldOperation::EnableEntity(entity)
+    } else {
+        WorldOperation::DisableEntity(entity)
+    }
+}
+
+impl World {
+    /// Enable or disable an entity, recording the change in the history.
+    /// Returns false if the entity does not exist or already was in that state.
+    pub fn set_enabled(&mut self, entity: Entity, enabled: bool) -> bool {
+        let changed = self.set_entity_enabled(entity, enabled);
+        if changed {
+            self.record_standalone_operation(operation(entity, enabled));
+        }
+        changed
+    }
+
+    /// Check if an entity is enabled. Entities are enabled when created.
+    pub fn is_enabled(&self, entity: Entity) -> bool {
+        !self.disabled_entities.contains(&entity)
+    }
+
+    /// Enable or disable an entity without recording it
+    pub(crate) fn set_entity_enabled(&mut self, entity: Entity, enabled: bool) -> bool {
+        if !self.entity_exists(entity) {
+            return false;
+        }
+        if enabled {
+            self.disabled_entities.remove(&entity)
+        } else {
+            self.disabled_entities.insert(entity)
+        }
+    }
+
+    /// Entities a query visits, in entity order
+    pub(crate) fn query_entities(&self, include_disabled: bool) -> Vec<Entity> {
+        let sliced = self.active_slice.get().is_some();
+        if (include_disabled || self.disabled_entities.is_empty()) && !sliced {
+            return self.entities.clone();
+        }
+        self.entities
+            .iter()
+            .filter(|entity| include_disabled || !self.disabled_entities.contains(entity))
+            .filter(|entity| !sliced || self.in_slice(**entity))
+            .copied()
+            .collect()
+    }
+}
+
+impl<I, O> WorldView<I, O> {
+    /// Enable or disable an entity, recording the change in the system's diff
+    pub fn set_enabled(&mut self, entity: Entity, enabled: bool) -> bool {
+        let changed = unsafe { self.world_mut() }.set_entity_enabled(entity, enabled);
+        if changed {
+            self.system_diff.record_world_operation(operation(entity, enabled));
+        }
+        changed
+    }
+
+    /// Check if an entity is enabled
+    pub fn is_enabled(&self, entity: Entity) -> bool {
+        unsafe { self.world() }.is_enabled(entity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay_analysis::{parse_replay_log_str, validate_replay_log_str, write_replay_log};
+    use crate::{In, Out, System};
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Energy(i32);
+
+    /// Drains energy and disables entities that run out
+    struct TireSystem;
+
+    impl System for TireSystem {
+        type InComponents = ();
+        type OutComponents = (Energy,);
+
+        fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+        fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+            let mut exhausted = Vec::new();
+            for (entity, energy) in world.query_components::<(Out<Energy>,)>() {
+                energy.0 -= 1;
+                if energy.0 == 0 {
+                    exhausted.push(entity);
+                }
+            }
+            for entity in exhausted {
+                world.set_enabled(entity, false);
+            }
+        }
+
+        fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    }
+
+    #[test]
+    fn test_disabled_entities_are_skipped_by_queries() {
+        let mut world = World::new();
+        let entities: Vec<Entity> = [1, 3]
+            .into_iter()
+            .map(|energy| {
+                let entity = world.create_entity();
+                world.add_component(entity, Energy(energy));
+                entity
+            })
+            .collect();
+        world.add_system(TireSystem);
+        world.update();
+        world.update();
+
+        // The first entity stopped draining once disabled
+        assert!(!world.is_enabled(entities[0]));
+        assert_eq!(world.get_component::<Energy>(entities[0]), Some(&Energy(0)));
+        assert_eq!(world.get_component::<Energy>(entities[1]), Some(&Energy(1)));
+
+        let mut view = WorldView::<(Energy,), ()>::new(&mut world);
+        let enabled: Vec<Entity> = view
+            .query_components::<(In<Energy>,)>()
+            .into_iter()
+            .map(|(entity, _)| entity)
+            .collect();
+        assert_eq!(enabled, vec![entities[1]]);
+        assert_eq!(view.query_components::<(In<Energy>, IncludeDisabled)>().len(), 2);
+
+        assert!(world.set_enabled(entities[0], true));
+        assert!(!world.set_enabled(entities[0], true));
+        let mut log = Vec::new();
+        write_replay_log(world.get_update_history(), &mut log).unwrap();
+        let log = String::from_utf8(log).unwrap();
+        assert!(log.contains("DISABLE_ENTITY Entity(0, 0)"), "{}", log);
+        assert!(validate_replay_log_str(&log).is_empty(), "{:?}", validate_replay_log_str(&log));
+
+        let mut replayed = World::new();
+        for _ in &entities {
+            replayed.create_entity();
+        }
+        let history = parse_replay_log_str(&log);
+        let (last, frames) = history.updates().split_last().unwrap();
+        for update in frames {
+            replayed.apply_update_diff(update);
+        }
+        assert!(!replayed.is_enabled(entities[0]));
+        replayed.apply_update_diff(last);
+        assert!(replayed.is_enabled(entities[0]));
+    }
+}
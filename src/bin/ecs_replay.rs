@@ -0,0 +1,261 @@
+//! Command line tool for working with replay logs without writing Rust.
+//!
+//! ```text
+//! cargo run --features replay-cli --bin ecs-replay -- info game_logs/simulation_game_<id>.log
+//! ```
+//!
+//! Logs are read in the text format written by `AutoReplayLogger`, as JSON or
+//! in a compact binary encoding; the format is picked from the file extension
+//! (`.json`, `.bin`, anything else is text). Frames are numbered from 0.
+//!
+//! Binary logs start with `ECSR` and a version byte, followed by the
+//! varint-encoded `CompactHistory`. Binary logs written before the header was
+//! added are still read.
+
+use rust_ecs::log_recovery::recover_replay_log_str;
+use rust_ecs::replay_analysis::{
+    check_replay_log_consistency, compare_histories, parse_replay_log_str, print_replay_analysis, trim_history,
+    validate_replay_log_str, write_replay_log,
+};
+use bincode::Options;
+use rust_ecs::{CompactHistory, WorldUpdateHistory};
+use std::env;
+use std::error::Error;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+const USAGE: &str = "Usage:
+  ecs-replay info <log>                          Print statistics of a session
+  ecs-replay convert <log> --to <text|json|binary> [--output <path>]
+  ecs-replay diff <a> <b>                        Show frames whose changes differ
+  ecs-replay trim <log> --frames <start..end> [--output <path>]
+  ecs-replay validate <log>                      Check a log for malformed entries and entities
+                                                 or components used before their creation
+  ecs-replay recover <log> [--output <path>]     Save the complete updates of a log cut short by a crash";
+
+/// Start of binary logs in the compact encoding
+const BINARY_MAGIC: &[u8; 4] = b"ECSR";
+const BINARY_VERSION: u8 = 1;
+
+/// On-disk encoding of a replay log
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Text,
+    Json,
+    Binary,
+}
+
+impl Format {
+    fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "text" | "log" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            "binary" | "bin" => Ok(Format::Binary),
+            _ => Err(format!("unknown format '{}', expected text, json or binary", name)),
+        }
+    }
+
+    fn of_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => Format::Json,
+            Some("bin") => Format::Binary,
+            _ => Format::Text,
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Text => "log",
+            Format::Json => "json",
+            Format::Binary => "bin",
+        }
+    }
+}
+
+fn load(path: &Path) -> Result<WorldUpdateHistory, Box<dyn Error>> {
+    let bytes = std::fs::read(path).map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    Ok(match Format::of_path(path) {
+        Format::Text => parse_replay_log_str(&String::from_utf8(bytes)?),
+        Format::Json => {
+            let mut history: WorldUpdateHistory = serde_json::from_slice(&bytes)?;
+            history.intern_strings();
+            history
+        }
+        Format::Binary => match bytes.strip_prefix(BINARY_MAGIC.as_slice()) {
+            Some([BINARY_VERSION, compact @ ..]) => {
+                bincode::DefaultOptions::new().deserialize::<CompactHistory>(compact)?.into_history()?
+            }
+            Some(_) => return Err(format!("unsupported binary log version in {}", path.display()).into()),
+            None => {
+                let mut history: WorldUpdateHistory = bincode::deserialize(&bytes)?;
+                history.intern_strings();
+                history
+            }
+        },
+    })
+}
+
+fn save(history: &WorldUpdateHistory, path: &Path, format: Format) -> Result<(), Box<dyn Error>> {
+    let bytes = match format {
+        Format::Text => {
+            let mut buffer = Vec::new();
+            write_replay_log(history, &mut buffer)?;
+            buffer
+        }
+        Format::Json => serde_json::to_vec_pretty(history)?,
+        Format::Binary => {
+            let mut buffer = BINARY_MAGIC.to_vec();
+            buffer.push(BINARY_VERSION);
+            bincode::DefaultOptions::new().serialize_into(&mut buffer, &CompactHistory::from_history(history))?;
+            buffer
+        }
+    };
+    std::fs::write(path, bytes).map_err(|e| format!("failed to write {}: {}", path.display(), e))?;
+    println!("Wrote {} updates to {}", history.len(), path.display());
+    Ok(())
+}
+
+/// Parse a frame range like `100..200`, `100..=200`, `100..` or `..200`
+fn parse_frames(range: &str) -> Result<Range<usize>, String> {
+    let invalid = || format!("invalid frame range '{}', expected e.g. 100..200", range);
+    let (start, end) = range.split_once("..").ok_or_else(invalid)?;
+    let start = if start.is_empty() { 0 } else { start.parse().map_err(|_| invalid())? };
+    let end = match end.strip_prefix('=') {
+        Some(end) => end.parse::<usize>().map_err(|_| invalid())? + 1,
+        None if end.is_empty() => usize::MAX,
+        None => end.parse().map_err(|_| invalid())?,
+    };
+    if start > end {
+        return Err(invalid());
+    }
+    Ok(start..end)
+}
+
+/// Value of an option like `--to json`
+fn option<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|arg| arg == name)
+        .and_then(|index| args.get(index + 1))
+        .map(String::as_str)
+}
+
+/// Output path given with `--output`, or the input path with a suffix and the format's extension
+fn output_path(args: &[String], input: &Path, suffix: &str, format: Format) -> PathBuf {
+    match option(args, "--output") {
+        Some(path) => PathBuf::from(path),
+        None => {
+            let stem = input.file_stem().and_then(|stem| stem.to_str()).unwrap_or("replay");
+            input.with_file_name(format!("{}{}.{}", stem, suffix, format.extension()))
+        }
+    }
+}
+
+/// Run a command, returning whether it succeeded
+fn run(args: &[String]) -> Result<bool, Box<dyn Error>> {
+    let (Some(command), Some(log)) = (args.first(), args.get(1)) else {
+        return Err(USAGE.into());
+    };
+    let log = Path::new(log);
+
+    match command.as_str() {
+        "info" => {
+            let history = load(log)?;
+            println!("Log: {} ({:?})", log.display(), Format::of_path(log));
+            print_replay_analysis(&history);
+            let memory = history.memory_stats();
+            println!(
+                "Memory: ~{} bytes, {} unique strings ({} bytes shared by {} bytes of changes)",
+                memory.estimated_bytes, memory.unique_strings, memory.interned_bytes, memory.string_bytes
+            );
+            Ok(true)
+        }
+        "convert" => {
+            let format = Format::parse(option(args, "--to").ok_or("convert needs --to <text|json|binary>")?)?;
+            let output = output_path(args, log, "", format);
+            if output == log {
+                return Err(format!("{} is already in {:?} format", log.display(), format).into());
+            }
+            save(&load(log)?, &output, format)?;
+            Ok(true)
+        }
+        "diff" => {
+            let other = Path::new(args.get(2).ok_or(USAGE)?);
+            let (first, second) = (load(log)?, load(other)?);
+            let differences = compare_histories(&first, &second);
+            for difference in &differences {
+                println!("FRAME {}", difference.frame);
+                for entry in &difference.only_in_first {
+                    println!("  - {}", entry);
+                }
+                for entry in &difference.only_in_second {
+                    println!("  + {}", entry);
+                }
+            }
+            println!(
+                "{} of {} frames differ ({} vs {} updates)",
+                differences.len(),
+                first.len().max(second.len()),
+                first.len(),
+                second.len()
+            );
+            Ok(differences.is_empty())
+        }
+        "trim" => {
+            let frames = parse_frames(option(args, "--frames").ok_or("trim needs --frames <start..end>")?)?;
+            let history = load(log)?;
+            let trimmed = trim_history(&history, frames.clone());
+            let end = frames.end.min(history.len());
+            let format = Format::of_path(log);
+            let output = output_path(args, log, &format!("_frames_{}-{}", frames.start, end), format);
+            save(&trimmed, &output, format)?;
+            Ok(true)
+        }
+        "validate" => {
+            let updates = match Format::of_path(log) {
+                Format::Text => {
+                    let contents = std::fs::read_to_string(log)?;
+                    let mut issues = validate_replay_log_str(&contents);
+                    issues.extend(check_replay_log_consistency(&contents));
+                    issues.sort_by_key(|issue| issue.line);
+                    for issue in &issues {
+                        println!("{}", issue);
+                    }
+                    if !issues.is_empty() {
+                        println!("{} issues found in {}", issues.len(), log.display());
+                        return Ok(false);
+                    }
+                    parse_replay_log_str(&contents).len()
+                }
+                // Structured formats are valid if they deserialize
+                _ => load(log)?.len(),
+            };
+            println!("{} is valid ({} updates)", log.display(), updates);
+            Ok(true)
+        }
+        "recover" => {
+            if Format::of_path(log) != Format::Text {
+                return Err("only text logs are written while a session runs".into());
+            }
+            let contents = std::fs::read(log).map_err(|e| format!("failed to read {}: {}", log.display(), e))?;
+            let recovered = recover_replay_log_str(&String::from_utf8_lossy(&contents));
+            println!("{}", recovered);
+            if !recovered.complete {
+                save(&recovered.history, &output_path(args, log, "_recovered", Format::Text), Format::Text)?;
+            }
+            Ok(true)
+        }
+        _ => Err(USAGE.into()),
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match run(&args) {
+        Ok(true) => {}
+        Ok(false) => std::process::exit(1),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    }
+}
@@ -0,0 +1,252 @@
+//! Interactive terminal inspector for replay logs.
+//!
+//! Loads a replay log written by `AutoReplayLogger`, reconstructs the game world
+//! frame by frame and lets you scrub through the session:
+//!
+//! ```text
+//! cargo run --features replay-inspector --bin replay-inspector -- game_logs/simulation_game_<id>.log
+//! ```
+//!
+//! Left/Right step one frame, PageUp/PageDown jump ten frames, Home/End go to the
+//! first/last frame, `n`/`p` jump to the next/previous annotated frame, Up/Down
+//! scroll the change list, `v` switches between the raw changes and sentences
+//! describing them, and `q` quits.
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use rust_ecs::game::game::{game_narrator, game_replay_player, render_world_grid};
+use rust_ecs::{World, WorldUpdateHistory};
+use std::env;
+
+/// Number of frames skipped by PageUp/PageDown
+const PAGE_SIZE: usize = 10;
+
+/// Replay data plus the pre-rendered grid for every frame
+struct Inspector {
+    log_path: String,
+    history: WorldUpdateHistory,
+    /// Grid rows after each frame has been applied
+    grids: Vec<Vec<String>>,
+    /// Sentences describing the changes of each frame
+    narratives: Vec<Vec<String>>,
+    frame: usize,
+    scroll: u16,
+    /// Show the sentences instead of the raw changes
+    narrate: bool,
+}
+
+impl Inspector {
+    /// Parse the log and reconstruct the grid for every frame
+    fn load(log_path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let history = World::parse_replay_log_file(log_path)?;
+
+        // Play the log back the same way `run_game_replay` does, which also
+        // restores the keyframes of sampled logs
+        let mut player = game_replay_player(history.clone())?;
+        let narrator = game_narrator();
+        let mut grids = Vec::with_capacity(history.len());
+        let mut narratives = Vec::with_capacity(history.len());
+        while !player.is_finished() {
+            narratives.push(player.narrate_next(&narrator));
+            player.step();
+            grids.push(render_world_grid(player.world()));
+        }
+
+        Ok(Self {
+            log_path: log_path.to_string(),
+            history,
+            grids,
+            narratives,
+            frame: 0,
+            scroll: 0,
+            narrate: false,
+        })
+    }
+
+    fn frame_count(&self) -> usize {
+        self.history.len()
+    }
+
+    /// Move the selected frame by `delta`, clamped to the available frames
+    fn seek_by(&mut self, delta: isize) {
+        let last = self.frame_count().saturating_sub(1) as isize;
+        self.seek_to((self.frame as isize + delta).clamp(0, last) as usize);
+    }
+
+    /// Jump to the closest annotated frame after (`forward`) or before the selected one
+    fn seek_annotation(&mut self, forward: bool) {
+        let frames = self.history.annotations().into_iter().map(|(frame, _)| frame);
+        let target = if forward {
+            frames.filter(|frame| *frame > self.frame).min()
+        } else {
+            frames.filter(|frame| *frame < self.frame).max()
+        };
+        if let Some(frame) = target {
+            self.seek_to(frame);
+        }
+    }
+
+    /// Session metadata for the header, empty if the log has none
+    fn metadata_line(&self) -> String {
+        let metadata = self.history.metadata();
+        let mut fields = Vec::new();
+        if let Some(game_version) = &metadata.game_version {
+            fields.push(format!("version {}", game_version));
+        }
+        if let Some(seed) = metadata.seed {
+            fields.push(format!("seed {}", seed));
+        }
+        if let Some(config_hash) = &metadata.config_hash {
+            fields.push(format!("config {}", config_hash));
+        }
+        fields.join("  ")
+    }
+
+    fn seek_to(&mut self, frame: usize) {
+        self.frame = frame.min(self.frame_count().saturating_sub(1));
+        self.scroll = 0;
+    }
+
+    /// Lines describing the component changes and world operations of the selected frame
+    fn change_lines(&self) -> Vec<Line<'static>> {
+        let Some(update) = self.history.updates().get(self.frame) else {
+            return vec![Line::from("No frames in replay log")];
+        };
+
+        let mut lines = Vec::new();
+        for annotation in update.annotations() {
+            lines.push(Line::from(Span::styled(
+                format!("▶ {}ms {}", annotation.elapsed.as_millis(), annotation.text),
+                Style::default().add_modifier(Modifier::BOLD | Modifier::REVERSED),
+            )));
+        }
+        if self.narrate {
+            let sentences = self.narratives.get(self.frame).map_or(&[][..], Vec::as_slice);
+            if sentences.is_empty() {
+                lines.push(Line::from("Nothing happened"));
+            }
+            lines.extend(sentences.iter().map(|sentence| Line::from(sentence.clone())));
+            return lines;
+        }
+        for (system_idx, system_diff) in update.system_diffs().iter().enumerate() {
+            let change_count =
+                system_diff.component_changes().len() + system_diff.world_operations().len();
+            lines.push(Line::from(Span::styled(
+                format!("System {} ({} changes)", system_idx, change_count),
+                Style::default().add_modifier(Modifier::BOLD),
+            )));
+            for change in system_diff.component_changes() {
+                lines.push(Line::from(format!("  {}", change)));
+            }
+            for operation in system_diff.world_operations() {
+                lines.push(Line::from(format!("  {}", operation)));
+            }
+        }
+        lines
+    }
+}
+
+fn draw(frame: &mut Frame, inspector: &Inspector) {
+    let [header_area, body_area, footer_area] = Layout::vertical([
+        Constraint::Length(4),
+        Constraint::Min(0),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    let header = Paragraph::new(vec![
+        Line::from(format!(
+            "Frame {} / {}  ({} annotations)",
+            inspector.frame + 1,
+            inspector.frame_count(),
+            inspector.history.annotations().len()
+        )),
+        Line::from(inspector.metadata_line()),
+    ])
+    .block(Block::bordered().title(format!(" Replay Inspector - {} ", inspector.log_path)));
+    frame.render_widget(header, header_area);
+
+    let [grid_area, changes_area] =
+        Layout::horizontal([Constraint::Length(25), Constraint::Min(0)]).areas(body_area);
+
+    let grid_lines: Vec<Line> = inspector
+        .grids
+        .get(inspector.frame)
+        .map(|rows| rows.iter().map(|row| Line::from(row.clone())).collect())
+        .unwrap_or_default();
+    frame.render_widget(
+        Paragraph::new(grid_lines).block(Block::bordered().title(" Grid ")),
+        grid_area,
+    );
+
+    frame.render_widget(
+        Paragraph::new(inspector.change_lines())
+            .block(Block::bordered().title(if inspector.narrate { " What happened " } else { " Changes per system " }))
+            .scroll((inspector.scroll, 0)),
+        changes_area,
+    );
+
+    frame.render_widget(
+        Paragraph::new("←/→ frame  PgUp/PgDn ±10  Home/End first/last  n/p annotation  ↑/↓ scroll  v sentences  q quit"),
+        footer_area,
+    );
+}
+
+fn run(terminal: &mut DefaultTerminal, inspector: &mut Inspector) -> std::io::Result<()> {
+    loop {
+        terminal.draw(|frame| draw(frame, inspector))?;
+
+        if let Event::Key(key) = event::read()? {
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Right => inspector.seek_by(1),
+                KeyCode::Left => inspector.seek_by(-1),
+                KeyCode::PageDown => inspector.seek_by(PAGE_SIZE as isize),
+                KeyCode::PageUp => inspector.seek_by(-(PAGE_SIZE as isize)),
+                KeyCode::Home => inspector.seek_to(0),
+                KeyCode::End => inspector.seek_to(inspector.frame_count()),
+                KeyCode::Char('n') => inspector.seek_annotation(true),
+                KeyCode::Char('p') => inspector.seek_annotation(false),
+                KeyCode::Char('v') => {
+                    inspector.narrate = !inspector.narrate;
+                    inspector.scroll = 0;
+                }
+                KeyCode::Down => inspector.scroll = inspector.scroll.saturating_add(1),
+                KeyCode::Up => inspector.scroll = inspector.scroll.saturating_sub(1),
+                _ => {}
+            }
+        }
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let Some(log_path) = args.get(1) else {
+        eprintln!("Usage: replay-inspector <replay_log_path>");
+        std::process::exit(2);
+    };
+
+    let mut inspector = match Inspector::load(log_path) {
+        Ok(inspector) => inspector,
+        Err(e) => {
+            eprintln!("Failed to load replay log {}: {}", log_path, e);
+            std::process::exit(1);
+        }
+    };
+
+    let mut terminal = ratatui::init();
+    let result = run(&mut terminal, &mut inspector);
+    ratatui::restore();
+
+    if let Err(e) = result {
+        eprintln!("Replay inspector failed: {}", e);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,393 @@
+//! Tracing which components a system read before it changed one.
+//!
+//! With causality tracing on, the `WorldView` of every system notes the
+//! components each query reads through `In<T>`. When the system then records
+//! a change of an entity's component, the components of the same entity it
+//! read so far are stored with the system's diff as the change's cause:
+//!
+//! ```ignore
+//! world.set_causality_tracing(true);
+//! world.update();
+//! // Frame 3 System 1 changed Entity(0, 0) Position after reading Velocity
+//! //   Frame 2 System 0 changed Entity(0, 0) Velocity after reading Input
+//! let cause = replay_analysis::why(world.get_update_history(), player, "Position", 3);
+//! ```
+//!
+//! `why` follows each read component back to the latest earlier change of it,
+//! building a chain of changes that led to the one asked about. Only reads of
+//! the changed entity are linked; queries read all their entities up front,
+//! so reads of other entities tell little about one change.
+//!
+//! Causes are written to replay logs as `CAUSE: Entity(0, 0) Position <- Velocity`
+//! lines. Off by default.
+
+use crate::{split_entity, Entity, SystemUpdateDiff, World, WorldUpdateHistory};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// Components of an entity a system read before changing one of its components
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WriteCause {
+    pub entity: Entity,
+    /// Short type name of the changed component
+    pub component: String,
+    /// Short type names of the read components, in name order
+    pub reads: Vec<String>,
+}
+
+/// Formats the cause as in replay logs, e.g. `Entity(0, 0) Position <- Mass, Velocity`
+impl std::fmt::Display for WriteCause {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} <- {}", self.entity, self.component, self.reads.join(", "))
+    }
+}
+
+impl std::str::FromStr for WriteCause {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let (entity, rest) = split_entity(text).ok_or_else(|| format!("expected an entity, found {}", text))?;
+        let (component, reads) = rest
+            .split_once(" <-")
+            .ok_or_else(|| format!("expected 'Component <- reads', found {}", rest))?;
+        if component.is_empty() || component.contains(' ') {
+            return Err(format!("invalid component name '{}'", component));
+        }
+        Ok(Self {
+            entity,
+            component: component.to_string(),
+            reads: reads
+                .split(',')
+                .map(str::trim)
+                .filter(|read| !read.is_empty())
+                .map(str::to_string)
+                .collect(),
+        })
+    }
+}
+
+/// Reads of the running system and the changes they led to
+#[derive(Default)]
+pub(crate) struct ReadTracker {
+    reads: HashMap<Entity, BTreeSet<&'static str>>,
+    causes: BTreeMap<(Entity, String), BTreeSet<&'static str>>,
+}
+
+impl ReadTracker {
+    /// Note components a query read from an entity
+    pub(crate) fn record_reads(&mut self, entity: Entity, components: &[&'static str]) {
+        if !components.is_empty() {
+            self.reads.entry(entity).or_default().extend(components);
+        }
+    }
+
+    /// Link a recorded change to the components of the entity read so far
+    pub(crate) fn record_write(&mut self, entity: Entity, component: &str) {
+        let Some(reads) = self.reads.get(&entity) else {
+            return;
+        };
+        self.causes
+            .entry((entity, component.to_string()))
+            .or_default()
+            .extend(reads);
+    }
+
+    /// Store the causes of the changes left in the system's diff
+    pub(crate) fn finish(self, diff: &mut SystemUpdateDiff) {
+        for ((entity, component), reads) in self.causes {
+            let changed = diff
+                .component_changes
+                .iter()
+                .any(|change| change.entity() == entity && change.type_name() == component);
+            if changed {
+                diff.record_cause(WriteCause {
+                    entity,
+                    component,
+                    reads: reads.into_iter().map(str::to_string).collect(),
+                });
+            }
+        }
+    }
+}
+
+impl World {
+    /// Record which components systems read before each change they record.
+    /// Off by default.
+    pub fn set_causality_tracing(&mut self, enabled: bool) {
+        self.causality_tracing = enabled;
+    }
+
+    /// Check if causality tracing is on
+    pub fn causality_tracing(&self) -> bool {
+        self.causality_tracing
+    }
+}
+
+/// A component change and the earlier changes that led to it, returned by `why`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChangeCause {
+    pub frame: usize,
+    /// Index of the changing system in the frame
+    pub system_index: usize,
+    pub entity: Entity,
+    pub component: String,
+    /// Components of the entity the system read before the change, empty
+    /// if causality tracing was off
+    pub reads: Vec<String>,
+    /// Latest earlier changes of the read components, explained the same way
+    pub read_changes: Vec<ChangeCause>,
+}
+
+/// Formats the chain as an indented tree, one change per line
+impl std::fmt::Display for ChangeCause {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.write_tree(f, 0)
+    }
+}
+
+impl ChangeCause {
+    fn write_tree(&self, f: &mut std::fmt::Formatter<'_>, depth: usize) -> std::fmt::Result {
+        write!(
+            f,
+            "{:indent$}Frame {} System {} changed {} {}",
+            "",
+            self.frame,
+            self.system_index,
+            self.entity,
+            self.component,
+            indent = depth * 2
+        )?;
+        if !self.reads.is_empty() {
+            write!(f, " after reading {}", self.reads.join(", "))?;
+        }
+        writeln!(f)?;
+        for read_change in &self.read_changes {
+            read_change.write_tree(f, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+/// Longest chain of changes `why` follows back
+pub const MAX_CAUSE_DEPTH: usize = 16;
+
+/// Explain why a component of an entity changed in a frame: the last system
+/// that changed it, the components it read before, and recursively the
+/// latest earlier changes of those. None if the component did not change in
+/// the frame.
+pub fn why(history: &WorldUpdateHistory, entity: Entity, component: &str, frame: usize) -> Option<ChangeCause> {
+    let update = history.updates().get(frame)?;
+    let system_index = update
+        .system_diffs()
+        .iter()
+        .rposition(|system| changes_component(system, entity, component))?;
+    Some(explain(history, entity, component, frame, system_index, MAX_CAUSE_DEPTH))
+}
+
+/// Check if a system's diff changes a component of an entity
+fn changes_component(system: &SystemUpdateDiff, entity: Entity, component: &str) -> bool {
+    system
+        .component_changes()
+        .iter()
+        .any(|change| change.entity() == entity && change.type_name() == component)
+}
+
+/// Explain a known change, following reads back at most `depth` more changes
+fn explain(
+    history: &WorldUpdateHistory,
+    entity: Entity,
+    component: &str,
+    frame: usize,
+    system_index: usize,
+    depth: usize,
+) -> ChangeCause {
+    let reads: Vec<String> = history.updates()[frame].system_diffs()[system_index]
+        .causes()
+        .iter()
+        .find(|cause| cause.entity == entity && cause.component == component)
+        .map(|cause| cause.reads.clone())
+        .unwrap_or_default();
+    let read_changes = if depth == 0 {
+        Vec::new()
+    } else {
+        reads
+            .iter()
+            .filter_map(|read| {
+                let (frame, system_index) = latest_change_before(history, entity, read, frame, system_index)?;
+                Some(explain(history, entity, read, frame, system_index, depth - 1))
+            })
+            .collect()
+    };
+    ChangeCause {
+        frame,
+        system_index,
+        entity,
+        component: component.to_string(),
+        reads,
+        read_changes,
+    }
+}
+
+/// Find the latest change of a component before a system's update, as frame
+/// and system index
+fn latest_change_before(
+    history: &WorldUpdateHistory,
+    entity: Entity,
+    component: &str,
+    frame: usize,
+    system_index: usize,
+) -> Option<(usize, usize)> {
+    let updates = history.updates();
+    let earlier_systems = &updates[frame].system_diffs()[..system_index];
+    if let Some(index) = earlier_systems.iter().rposition(|system| changes_component(system, entity, component)) {
+        return Some((frame, index));
+    }
+    updates[..frame].iter().enumerate().rev().find_map(|(frame, update)| {
+        update
+            .system_diffs()
+            .iter()
+            .rposition(|system| changes_component(system, entity, component))
+            .map(|index| (frame, index))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay_analysis::{parse_replay_log_str, write_replay_log};
+    use crate::{Diff, In, Out, System, WorldView};
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Input(i32);
+
+    #[derive(Debug, Clone, PartialEq, Diff)]
+    struct Velocity {
+        x: i32,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Diff)]
+    struct Position {
+        x: i32,
+    }
+
+    /// Sets velocities from the input
+    struct SteerSystem;
+
+    impl System for SteerSystem {
+        type InComponents = (Input,);
+        type OutComponents = (Velocity,);
+
+        fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+        fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+            let steered: Vec<(Entity, Velocity, Velocity)> = world
+                .query_components::<(In<Input>, Out<Velocity>)>()
+                .into_iter()
+                .map(|(entity, (input, velocity))| (entity, velocity.clone(), Velocity { x: input.0 }))
+                .collect();
+            for (entity, old, new) in steered {
+                world.record_component_modification(entity, &old, &new);
+                *world.get_component_mut::<Velocity>(entity).unwrap() = new;
+            }
+        }
+
+        fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    }
+
+    /// Moves entities by their velocity
+    struct MoveSystem;
+
+    impl System for MoveSystem {
+        type InComponents = (Velocity,);
+        type OutComponents = (Position,);
+
+        fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+        fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+            let moved: Vec<(Entity, Position, Position)> = world
+                .query_components::<(In<Velocity>, Out<Position>)>()
+                .into_iter()
+                .map(|(entity, (velocity, position))| (entity, position.clone(), Position { x: position.x + velocity.x }))
+                .collect();
+            for (entity, old, new) in moved {
+                world.record_component_modification(entity, &old, &new);
+                *world.get_component_mut::<Position>(entity).unwrap() = new;
+            }
+        }
+
+        fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    }
+
+    #[test]
+    fn test_why_follows_reads_back_to_earlier_changes() {
+        let mut world = World::new();
+        world.set_causality_tracing(true);
+        let player = world.create_entity();
+        world.add_component(player, Input(0));
+        world.add_component(player, Velocity { x: 0 });
+        world.add_component(player, Position { x: 0 });
+        // Moves before it steers, so positions follow velocities of the previous frame
+        world.add_system(MoveSystem);
+        world.add_system(SteerSystem);
+        world.get_component_mut::<Input>(player).unwrap().0 = 2;
+        world.update();
+        world.update();
+
+        let history = world.get_update_history();
+        let frame = history.updates().len() - 1;
+        let cause = why(history, player, "Position", frame).unwrap();
+        assert_eq!((cause.frame, cause.system_index), (frame, 0));
+        assert_eq!(cause.reads, vec!["Velocity".to_string()]);
+        // The velocity was changed by the steering system a frame earlier
+        let velocity = &cause.read_changes[0];
+        assert_eq!((velocity.frame, velocity.system_index), (frame - 1, 1));
+        assert_eq!(velocity.reads, vec!["Input".to_string()]);
+        assert!(velocity.read_changes.is_empty());
+        assert_eq!(
+            cause.to_string(),
+            format!(
+                "Frame {} System 0 changed {} Position after reading Velocity\n  Frame {} System 1 changed {} Velocity after reading Input\n",
+                frame,
+                player,
+                frame - 1,
+                player
+            )
+        );
+        assert!(why(history, player, "Input", frame).is_none());
+
+        // Causes survive the replay log
+        let mut log = Vec::new();
+        write_replay_log(history, &mut log).unwrap();
+        let log = String::from_utf8(log).unwrap();
+        assert!(log.contains(&format!("CAUSE: {} Position <- Velocity", player)), "{}", log);
+        assert_eq!(why(&parse_replay_log_str(&log), player, "Position", frame), Some(cause));
+    }
+
+    #[test]
+    fn test_changes_without_tracing_have_no_reads() {
+        let mut world = World::new();
+        let player = world.create_entity();
+        world.add_component(player, Velocity { x: 1 });
+        world.add_component(player, Position { x: 0 });
+        world.add_system(MoveSystem);
+        world.update();
+
+        let history = world.get_update_history();
+        let frame = history.updates().len() - 1;
+        let cause = why(history, player, "Position", frame).unwrap();
+        assert!(cause.reads.is_empty() && cause.read_changes.is_empty());
+        assert!(history.updates()[frame].system_diffs()[0].causes().is_empty());
+    }
+
+    #[test]
+    fn test_write_cause_round_trip() {
+        let cause = WriteCause {
+            entity: Entity::new(0, 3),
+            component: "Position".to_string(),
+            reads: vec!["Mass".to_string(), "Velocity".to_string()],
+        };
+        assert_eq!(cause.to_string(), "Entity(0, 3) Position <- Mass, Velocity");
+        assert_eq!(cause.to_string().parse::<WriteCause>(), Ok(cause));
+        assert!("Entity(0, 3) Position".parse::<WriteCause>().is_err());
+    }
+}
@@ -0,0 +1,245 @@
+//! Change ticks for finding the components that were written.
+//!
+//! The world keeps a change tick that advances at the start of every frame and
+//! around each system update. Every mutable access the framework hands out
+//! (`get_component_mut`, `Out<T>` in queries, replays, reflection and
+//! scripts) stamps the component with the current tick, and adding a
+//! component stamps the new one. `Changed<T>` uses the stamps to match only
+//! components written since the querying system last ran:
+//!
+//! ```ignore
+//! for (entity, (position, _)) in world.query_components::<(Changed<Position>, Out<Sprite>)>() {
+//!     // Only entities that moved since this system's last update
+//! }
+//! ```
+//!
+//! Mutable access counts as a write even if the value stays the same. Rewind
+//! tracking and watches use the stamps to skip comparing components nobody
+//! wrote since they were captured.
+
+use crate::{ComponentAccess, Entity, MixedQueryComponent, World};
+use std::any::TypeId;
+
+/// Point in the world's sequence of frames and system runs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ChangeTick(u64);
+
+impl ChangeTick {
+    pub fn get(self) -> u64 {
+        self.0
+    }
+
+    /// Check if this tick comes after `other`
+    pub fn is_newer_than(self, other: ChangeTick) -> bool {
+        self.0 > other.0
+    }
+
+    pub(crate) fn next(self) -> Self {
+        Self(self.0 + 1)
+    }
+}
+
+/// Query filter matching components written since the querying system last
+/// ran, giving immutable access to them.
+///
+/// On a system's first update every component counts as changed, later the
+/// system's own writes do not. Outside of systems, components written since
+/// the world was created match.
+pub struct Changed<T>(std::marker::PhantomData<T>);
+
+impl<'a, T: 'static> MixedQueryComponent<'a> for Changed<T> {
+    type Item = &'a T;
+
+    fn get_mixed_component(world: &'a mut World, entity: Entity) -> Option<Self::Item> {
+        let world: &'a World = world;
+        if !world.component_changed_since(TypeId::of::<T>(), entity, world.last_run_tick) {
+            return None;
+        }
+        world.get_component::<T>(entity)
+    }
+    fn access() -> Option<ComponentAccess> {
+        Some(ComponentAccess::read::<T>())
+    }
+}
+
+impl World {
+    /// Get the current change tick
+    pub fn change_tick(&self) -> ChangeTick {
+        self.change_tick
+    }
+
+    /// Get the tick a component was last written at
+    pub fn component_change_tick<T: 'static>(&self, entity: Entity) -> Option<ChangeTick> {
+        self.component_ticks.get(&(TypeId::of::<T>(), entity)).copied()
+    }
+
+    /// Advance the change tick, so writes from now on are newer than anything captured before
+    pub(crate) fn advance_change_tick(&mut self) {
+        self.change_tick = self.change_tick.next();
+    }
+
+    /// Stamp a component with the current change tick
+    pub(crate) fn mark_component_changed(&mut self, type_id: TypeId, entity: Entity) {
+        self.component_ticks.insert((type_id, entity), self.change_tick);
+    }
+
+    /// Check if a component was written after `tick`
+    pub(crate) fn component_changed_since(&self, type_id: TypeId, entity: Entity, tick: ChangeTick) -> bool {
+        self.component_ticks
+            .get(&(type_id, entity))
+            .is_some_and(|changed| changed.is_newer_than(tick))
+    }
+
+    /// Forget the ticks of every component of a removed entity
+    pub(crate) fn forget_component_ticks(&mut self, entity: Entity) {
+        for type_id in self.components.keys() {
+            self.component_ticks.remove(&(*type_id, entity));
+        }
+    }
+
+    /// Run `update` as a system that last ran at `last_run`, returning the tick it ran at.
+    /// The tick advances afterwards, so later writes are new to the system.
+    pub(crate) fn run_as_system<R>(&mut self, last_run: ChangeTick, update: impl FnOnce(&mut World) -> R) -> (R, ChangeTick) {
+        let outer = std::mem::replace(&mut self.last_run_tick, last_run);
+        let result = update(self);
+        // The system's mutable references are gone, report its writes before the next one runs
+        self.flush_observers();
+        self.last_run_tick = outer;
+        let ran_at = self.change_tick;
+        self.advance_change_tick();
+        (result, ran_at)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{In, Out, System, WorldView};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Position(i32);
+
+    /// Moves one entity per frame
+    struct MoveSystem {
+        frame: usize,
+        entities: Vec<Entity>,
+    }
+
+    impl System for MoveSystem {
+        type InComponents = ();
+        type OutComponents = (Position,);
+
+        fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+        fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+            let entity = self.entities[self.frame % self.entities.len()];
+            world.get_component_mut::<Position>(entity).unwrap().0 += 1;
+            self.frame += 1;
+        }
+
+        fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    }
+
+    /// Collects the entities whose position changed since its last update
+    struct ChangedSystem(Rc<RefCell<Vec<Vec<Entity>>>>);
+
+    impl System for ChangedSystem {
+        type InComponents = (Position,);
+        type OutComponents = ();
+
+        fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+        fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+            let changed = world
+                .query_components::<(Changed<Position>,)>()
+                .into_iter()
+                .map(|(entity, _)| entity)
+                .collect();
+            self.0.borrow_mut().push(changed);
+        }
+
+        fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    }
+
+    #[test]
+    fn test_changed_matches_components_written_since_last_run() {
+        let mut world = World::new();
+        let entities: Vec<Entity> = (0..3)
+            .map(|index| {
+                let entity = world.create_entity();
+                world.add_component(entity, Position(index));
+                entity
+            })
+            .collect();
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        world.add_system(MoveSystem {
+            frame: 0,
+            entities: entities.clone(),
+        });
+        world.add_system(ChangedSystem(seen.clone()));
+
+        world.update();
+        world.update();
+        world.get_component_mut::<Position>(entities[0]).unwrap().0 = 10;
+        world.update();
+
+        // Everything is new to the first update, then the moved entity and the one written in between
+        assert_eq!(
+            *seen.borrow(),
+            vec![entities.clone(), vec![entities[1]], vec![entities[0], entities[2]]]
+        );
+    }
+
+    #[test]
+    fn test_reads_do_not_stamp_components() {
+        let mut world = World::new();
+        let entity = world.create_entity();
+        world.add_component(entity, Position(0));
+        let added = world.component_change_tick::<Position>(entity).unwrap();
+
+        world.advance_change_tick();
+        let _ = world.get_component::<Position>(entity);
+        let _ = WorldView::<(Position,), ()>::new(&mut world).query_components::<(In<Position>,)>();
+        assert_eq!(world.component_change_tick::<Position>(entity), Some(added));
+
+        let _ = WorldView::<(), (Position,)>::new(&mut world).query_components::<(Out<Position>,)>();
+        assert!(world.component_change_tick::<Position>(entity).unwrap().is_newer_than(added));
+
+        world.remove_entity(entity);
+        assert_eq!(world.component_change_tick::<Position>(entity), None);
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Velocity(i32);
+
+    #[test]
+    fn test_unmatched_results_do_not_stamp_out_components() {
+        let mut world = World::new();
+        let entity = world.create_entity();
+        world.add_component(entity, Position(0));
+        world.add_component(entity, Velocity(1));
+        // Components written up to now count as unchanged
+        world.last_run_tick = world.change_tick;
+        world.advance_change_tick();
+        let written = world.component_change_tick::<Velocity>(entity);
+
+        let mut view = WorldView::<(Position,), (Velocity,)>::new(&mut world);
+        assert!(view.query_components::<(Out<Velocity>, Changed<Position>)>().is_empty());
+        assert_eq!(world.component_change_tick::<Velocity>(entity), written);
+    }
+
+    #[test]
+    fn test_filtered_out_results_do_not_stamp_out_components() {
+        let mut world = World::new();
+        let entity = world.create_entity();
+        world.add_component(entity, Velocity(1));
+        world.advance_change_tick();
+        let written = world.component_change_tick::<Velocity>(entity);
+
+        let mut view = WorldView::<(), (Velocity,)>::new(&mut world);
+        assert!(view.query_components_filtered::<(Out<Velocity>,), _>(|_, _| false).is_empty());
+        assert_eq!(world.component_change_tick::<Velocity>(entity), written);
+    }
+}
@@ -0,0 +1,150 @@
+//! Wiping a world, e.g. between tests or on level transitions.
+//!
+//! - `clear_entities` removes every entity with its components, groups and
+//!   enabled state, keeping systems and resources.
+//! - `clear_all` also removes systems, startup systems, resources and the
+//!   list of added plugins, so plugins can be added again.
+//! - `reset_history` drops the recorded updates, keeping the session metadata.
+//!
+//! Each is recorded as a single world operation (`CLEAR_ENTITIES`,
+//! `CLEAR_ALL`, `RESET_HISTORY`), so replays clear the replayed world at the
+//! same point. Entity indices keep counting up, so entities from before a
+//! clear never match new ones. Registered component types, prefabs, watches
+//! and observers are configuration and survive every reset.
+
+use crate::{World, WorldOperation, WorldUpdateHistory};
+
+impl World {
+    /// Remove every entity and its components, keeping systems and resources
+    pub fn clear_entities(&mut self) {
+        self.remove_all_entities();
+        self.record_standalone_operation(WorldOperation::ClearEntities);
+    }
+
+    /// Remove every entity, system and resource
+    pub fn clear_all(&mut self) {
+        self.remove_everything();
+        self.record_standalone_operation(WorldOperation::ClearAll);
+    }
+
+    /// Drop the recorded history, keeping its metadata. The reset itself is
+    /// the first update of the new history.
+    pub fn reset_history(&mut self) {
+        let metadata = self.world_update_history.metadata().clone();
+        self.world_update_history = WorldUpdateHistory::new();
+        self.world_update_history.set_metadata(metadata);
+        self.record_standalone_operation(WorldOperation::ResetHistory);
+    }
+
+    /// Remove every entity without recording it
+    pub(crate) fn remove_all_entities(&mut self) {
+        for entity in self.entities.clone() {
+            self.clear_components(entity);
+        }
+        self.entities.clear();
+        self.components.clear();
+        self.component_ticks.clear();
+        self.groups.clear();
+        self.relations.clear();
+        self.disabled_entities.clear();
+        // Rewinding across the clear would restore components of removed entities
+        self.rewind_frames.clear();
+    }
+
+    /// Remove every entity, system and resource without recording it
+    pub(crate) fn remove_everything(&mut self) {
+        self.remove_all_entities();
+        self.systems.clear();
+        self.startup_systems.clear();
+        self.disabled_systems.clear();
+        self.resources.clear();
+        self.plugins.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay_analysis::{parse_replay_log_str, validate_replay_log_str, write_replay_log};
+    use crate::{Entity, Out, System, WorldView};
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Score(i32);
+
+    struct ScoreSystem;
+
+    impl System for ScoreSystem {
+        type InComponents = ();
+        type OutComponents = (Score,);
+
+        fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+        fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+            for (_, score) in world.query_components::<(Out<Score>,)>() {
+                score.0 += 1;
+            }
+        }
+
+        fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    }
+
+    fn level(world: &mut World) -> Entity {
+        let entity = world.create_entity();
+        world.add_component(entity, Score(0));
+        world.add_to_group(entity, "players");
+        entity
+    }
+
+    #[test]
+    fn test_clear_entities_keeps_systems_and_resources() {
+        let mut world = World::new();
+        let first = level(&mut world);
+        world.insert_resource(3u32);
+        world.add_system(ScoreSystem);
+        world.update();
+
+        world.clear_entities();
+        assert_eq!(world.entity_count(), 0);
+        assert!(world.group("players").is_empty());
+        let second = level(&mut world);
+        assert_ne!(first, second);
+        world.update();
+        assert_eq!(world.get_component::<Score>(second), Some(&Score(1)));
+        assert_eq!(world.resource::<u32>(), Some(&3));
+
+        world.clear_all();
+        assert_eq!(world.entity_count(), 0);
+        assert_eq!(world.resource::<u32>(), None);
+        let third = level(&mut world);
+        world.update();
+        assert_eq!(world.get_component::<Score>(third), Some(&Score(0)));
+
+        let mut log = Vec::new();
+        write_replay_log(world.get_update_history(), &mut log).unwrap();
+        let log = String::from_utf8(log).unwrap();
+        assert!(log.contains("CLEAR_ENTITIES") && log.contains("CLEAR_ALL"), "{}", log);
+        assert!(validate_replay_log_str(&log).is_empty(), "{:?}", validate_replay_log_str(&log));
+        let history = parse_replay_log_str(&log);
+        let cleared = history
+            .updates()
+            .iter()
+            .position(|update| {
+                update
+                    .system_diffs()
+                    .iter()
+                    .any(|system| system.world_operations().contains(&WorldOperation::ClearEntities))
+            })
+            .unwrap();
+        let mut replayed = World::new();
+        level(&mut replayed);
+        for update in &history.updates()[..=cleared] {
+            replayed.apply_update_diff(update);
+        }
+        assert_eq!(replayed.entity_count(), 0);
+
+        world.reset_history();
+        let updates = world.get_update_history().updates();
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].system_diffs()[0].world_operations(), [WorldOperation::ResetHistory]);
+    }
+}
@@ -0,0 +1,153 @@
+//! Time source used for timing frames and naming replay sessions.
+//!
+//! `std::time::Instant` and `SystemTime` panic on `wasm32-unknown-unknown`.
+//! The world reads the time through this module instead. Native targets use
+//! the standard clocks; in the browser the application installs a time source
+//! backed by `performance.now()` and `Date.now()` with `set_time_source`.
+//! Without one, frame timings read as zero and replay sessions are numbered.
+//!
+//! Tests can give a world a `MockClock` instead, which only advances when
+//! told to, so timings and annotation timestamps are the same on every run.
+
+use crate::{World, WorldView};
+use std::cell::Cell;
+use std::rc::Rc;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// Functions the world reads the current time from
+#[derive(Debug, Clone, Copy)]
+pub struct TimeSource {
+    /// Monotonic time since an arbitrary fixed point
+    pub monotonic: fn() -> Duration,
+    /// Time since the Unix epoch, None if the wall clock is unavailable
+    pub wall_clock: fn() -> Option<Duration>,
+}
+
+static TIME_SOURCE: OnceLock<TimeSource> = OnceLock::new();
+
+/// Install the time source for this process.
+///
+/// Must be called before the first time reading; returns false if a time
+/// source was already installed or the default one is in use.
+pub fn set_time_source(source: TimeSource) -> bool {
+    TIME_SOURCE.set(source).is_ok()
+}
+
+fn time_source() -> &'static TimeSource {
+    TIME_SOURCE.get_or_init(default_time_source)
+}
+
+/// Current monotonic time, only meaningful relative to other readings
+pub fn monotonic_now() -> Duration {
+    (time_source().monotonic)()
+}
+
+/// Current time since the Unix epoch, if the wall clock is available
+pub fn unix_time() -> Option<Duration> {
+    (time_source().wall_clock)()
+}
+
+/// Manually advanced monotonic time; clones share the same time
+#[derive(Debug, Clone, Default)]
+pub struct MockClock(Rc<Cell<Duration>>);
+
+impl MockClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Current time of the clock
+    pub fn now(&self) -> Duration {
+        self.0.get()
+    }
+
+    /// Move the clock forward
+    pub fn advance(&self, duration: Duration) {
+        self.0.set(self.0.get() + duration);
+    }
+
+    /// Set the clock to a time
+    pub fn set(&self, time: Duration) {
+        self.0.set(time);
+    }
+}
+
+impl World {
+    /// Read the world's time from a mock clock instead of the time source.
+    ///
+    /// The world's creation time is reset to the clock's current time, so
+    /// annotations are timestamped relative to it.
+    pub fn set_mock_clock(&mut self, clock: MockClock) {
+        self.created_at = clock.now();
+        self.mock_clock = Some(clock);
+    }
+
+    /// Current monotonic time of the world, from its mock clock if it has one
+    pub fn now(&self) -> Duration {
+        match &self.mock_clock {
+            Some(clock) => clock.now(),
+            None => monotonic_now(),
+        }
+    }
+}
+
+impl<I, O> WorldView<I, O> {
+    /// Current monotonic time of the world, see `World::now`
+    pub fn now(&self) -> Duration {
+        unsafe { self.world().now() }
+    }
+}
+
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+fn default_time_source() -> TimeSource {
+    use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+    static START: OnceLock<Instant> = OnceLock::new();
+    START.get_or_init(Instant::now);
+
+    TimeSource {
+        monotonic: || START.get().map_or(Duration::ZERO, Instant::elapsed),
+        wall_clock: || SystemTime::now().duration_since(UNIX_EPOCH).ok(),
+    }
+}
+
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+fn default_time_source() -> TimeSource {
+    TimeSource {
+        monotonic: || Duration::ZERO,
+        wall_clock: || None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_time_source_advances() {
+        let start = monotonic_now();
+        std::thread::sleep(Duration::from_millis(2));
+        assert!(monotonic_now() > start);
+        assert!(unix_time().is_some());
+        assert!(!set_time_source(TimeSource {
+            monotonic: || Duration::ZERO,
+            wall_clock: || None,
+        }));
+    }
+
+    #[test]
+    fn test_mock_clock_drives_world_time() {
+        let clock = MockClock::new();
+        clock.set(Duration::from_secs(5));
+        let mut world = World::new();
+        world.set_mock_clock(clock.clone());
+        assert_eq!(world.now(), Duration::from_secs(5));
+
+        clock.advance(Duration::from_millis(250));
+        world.annotate_replay("marker");
+        world.update();
+        let annotations = world.get_update_history().annotations();
+        assert_eq!(annotations[0].1.elapsed, Duration::from_millis(250));
+    }
+}
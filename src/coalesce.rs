@@ -0,0 +1,226 @@
+//! Combining the writes a system makes to one component during an update.
+//!
+//! A system that moves an entity three times in one update would record three
+//! modifications of its position. The `WorldView` instead keeps one change per
+//! entity and component type, replaced by every later write:
+//!
+//! - modifications are diffed from the value before the system's first write,
+//!   and dropped if the component ends up unchanged
+//! - modifying a component the system added records the addition of its
+//!   latest value
+//! - removing a component the system added records nothing
+//! - adding or removing a component replaces any earlier change
+//!
+//! Each system's diff therefore holds at most one change per component, which
+//! keeps logs small and makes change counts of the analysis meaningful.
+
+use crate::{DiffComponentChange, Entity, SystemUpdateDiff};
+use std::any::Any;
+use std::collections::{BTreeSet, HashMap};
+
+/// What a system's recorded writes did to one component so far
+pub(crate) enum WriteKind {
+    Added,
+    Removed,
+    /// Modified from the value before the system's first write
+    Modified(Box<dyn Any>),
+    /// Added and removed again, so nothing is recorded
+    Cancelled,
+}
+
+/// Recorded change of one component
+struct RecordedWrite {
+    /// Index of the change in the system's diff
+    index: usize,
+    kind: WriteKind,
+}
+
+/// Changes a system recorded, by entity and component type name
+#[derive(Default)]
+pub(crate) struct CoalescedWrites {
+    writes: HashMap<(Entity, String), RecordedWrite>,
+    /// Changes that were undone by later writes
+    dropped: BTreeSet<usize>,
+}
+
+impl CoalescedWrites {
+    /// Get what earlier writes of the update did to a component
+    pub(crate) fn earlier(&self, entity: Entity, type_name: &str) -> Option<&WriteKind> {
+        self.writes
+            .get(&(entity, type_name.to_string()))
+            .map(|write| &write.kind)
+    }
+
+    /// Record the combined change of a component, replacing the change of
+    /// earlier writes. None drops it; a kind of None keeps the earlier kind.
+    pub(crate) fn record(
+        &mut self,
+        diff: &mut SystemUpdateDiff,
+        entity: Entity,
+        type_name: &str,
+        change: Option<DiffComponentChange>,
+        kind: Option<WriteKind>,
+    ) {
+        let key = (entity, type_name.to_string());
+        let Some(write) = self.writes.get_mut(&key) else {
+            let Some(change) = change else {
+                return;
+            };
+            let Some(kind) = kind else {
+                return;
+            };
+            self.writes.insert(
+                key,
+                RecordedWrite {
+                    index: diff.component_changes.len(),
+                    kind,
+                },
+            );
+            diff.record_component_change(change);
+            return;
+        };
+        match change {
+            Some(change) => {
+                diff.replace_component_change(write.index, change);
+                self.dropped.remove(&write.index);
+            }
+            None => {
+                self.dropped.insert(write.index);
+            }
+        }
+        if let Some(kind) = kind {
+            write.kind = kind;
+        }
+    }
+
+    /// Remove the dropped changes from the system's diff
+    pub(crate) fn finish(self, diff: &mut SystemUpdateDiff) {
+        for index in self.dropped.into_iter().rev() {
+            diff.remove_component_change(index);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dynamic::{ComponentSchema, FieldType};
+    use crate::reflect::Value;
+    use crate::{Diff, DiffComponentChange, Entity, Out, System, World, WorldView};
+
+    #[derive(Debug, Clone, PartialEq, Diff)]
+    struct Position {
+        x: i32,
+        y: i32,
+    }
+
+    /// Moves every entity by a list of steps, recording each step
+    struct StepSystem(Vec<(i32, i32)>);
+
+    impl System for StepSystem {
+        type InComponents = ();
+        type OutComponents = (Position,);
+
+        fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+        fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+            let entities: Vec<Entity> = world
+                .query_components::<(Out<Position>,)>()
+                .into_iter()
+                .map(|(entity, _)| entity)
+                .collect();
+            for entity in entities {
+                for (x, y) in &self.0 {
+                    let old = world.get_component::<Position>(entity).unwrap().clone();
+                    let new = Position { x: old.x + x, y: old.y + y };
+                    world.record_component_modification(entity, &old, &new);
+                    *world.get_component_mut::<Position>(entity).unwrap() = new;
+                }
+            }
+        }
+
+        fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    }
+
+    /// Spawns an entity, moves it and gives it a dynamic component it removes again
+    struct SpawnSystem;
+
+    impl System for SpawnSystem {
+        type InComponents = ();
+        type OutComponents = (Position,);
+
+        fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+        fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+            let entity = world.create_entity();
+            let spawned = Position { x: 0, y: 0 };
+            world.record_component_addition(entity, &spawned);
+            world.add_component(entity, spawned.clone());
+            let moved = Position { x: 2, y: 0 };
+            world.record_component_modification(entity, &spawned, &moved);
+            *world.get_component_mut::<Position>(entity).unwrap() = moved;
+
+            let shield = Value::Map([("points".to_string(), Value::Int(3))].into_iter().collect());
+            world.add_dyn_component(entity, "Shield", shield).unwrap();
+            world.remove_dyn_component(entity, "Shield");
+        }
+
+        fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    }
+
+    fn changes(world: &World) -> Vec<DiffComponentChange> {
+        let update = world.get_update_history().updates().last().unwrap();
+        update.system_diffs().iter().flat_map(|system| system.component_changes().to_vec()).collect()
+    }
+
+    #[test]
+    fn test_repeated_writes_record_one_combined_change() {
+        let mut world = World::new();
+        let entity = world.create_entity();
+        world.add_component(entity, Position { x: 0, y: 0 });
+        world.add_system(StepSystem(vec![(1, 0), (1, 0), (1, 0)]));
+        world.update();
+
+        let expected = Position { x: 0, y: 0 }.diff(&Position { x: 3, y: 0 }).unwrap();
+        match changes(&world).as_slice() {
+            [DiffComponentChange::Modified { entity: changed, diff, .. }] => {
+                assert_eq!(*changed, entity);
+                assert_eq!(&**diff, Position::diff_to_string(&expected));
+            }
+            changes => panic!("expected one modification, got {:?}", changes),
+        }
+    }
+
+    #[test]
+    fn test_writes_that_cancel_out_record_nothing() {
+        let mut world = World::new();
+        let entity = world.create_entity();
+        world.add_component(entity, Position { x: 0, y: 0 });
+        world.add_system(StepSystem(vec![(1, 2), (-1, -2)]));
+        world.update();
+        assert!(changes(&world).is_empty());
+        assert_eq!(world.get_component::<Position>(entity), Some(&Position { x: 0, y: 0 }));
+
+        // A later write after the cancelled ones is recorded again
+        let mut world = World::new();
+        let entity = world.create_entity();
+        world.add_component(entity, Position { x: 0, y: 0 });
+        world.add_system(StepSystem(vec![(1, 0), (-1, 0), (0, 5)]));
+        world.update();
+        assert!(matches!(changes(&world).as_slice(), [DiffComponentChange::Modified { .. }]));
+    }
+
+    #[test]
+    fn test_components_added_in_the_update_record_their_latest_value() {
+        let mut world = World::new();
+        world.register_schema(ComponentSchema::new("Shield").field("points", FieldType::Int));
+        world.add_system(SpawnSystem);
+        world.update();
+        match changes(&world).as_slice() {
+            [DiffComponentChange::Added { type_name, data, .. }] => {
+                assert_eq!(&**type_name, "Position");
+                assert_eq!(&**data, format!("{:?}", Position { x: 2, y: 0 }));
+            }
+            changes => panic!("expected one addition, got {:?}", changes),
+        }
+    }
+}
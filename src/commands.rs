@@ -0,0 +1,241 @@
+//! Deferred world changes queued by systems.
+//!
+//! Removing entities while a system still iterates its query results would
+//! pull components out from under it. Systems instead queue the removal on
+//! their `CommandBuffer`, which is applied when the system's update ends:
+//!
+//! ```ignore
+//! // In CorpseSystem::update
+//! world.commands().despawn_recursive(corpse);
+//! ```
+//!
+//! `despawn_recursive` also removes the entities attached to the entity with
+//! a `Parent` component, and theirs in turn. Every removed entity is recorded
+//! as a `RemoveEntity` operation of the system, so replays remove the same
+//! entities without knowing about the hierarchy.
+//!
+//! `despawn_deferred` waits until the whole frame ends instead, so the systems
+//! after the queueing one still see the entity. The removal is recorded as an
+//! operation of the frame's last system, where replays apply it at the same
+//! point of the frame.
+
+use crate::{Entity, World, WorldOperation, WorldUpdateDiff, WorldView};
+use std::collections::BTreeSet;
+
+/// A queued change
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Command {
+    Despawn(Entity),
+    DespawnRecursive(Entity),
+    DespawnDeferred(Entity),
+}
+
+/// Changes queued by a system, applied when its update ends
+#[derive(Debug, Default)]
+pub struct CommandBuffer {
+    commands: Vec<Command>,
+}
+
+impl CommandBuffer {
+    /// Remove an entity and its components
+    pub fn despawn(&mut self, entity: Entity) {
+        self.commands.push(Command::Despawn(entity));
+    }
+
+    /// Remove an entity and every entity attached to it, see `World::despawn_recursive`
+    pub fn despawn_recursive(&mut self, entity: Entity) {
+        self.commands.push(Command::DespawnRecursive(entity));
+    }
+
+    /// Remove an entity and its components when the frame ends, after every system updated
+    pub fn despawn_deferred(&mut self, entity: Entity) {
+        self.commands.push(Command::DespawnDeferred(entity));
+    }
+
+    /// Get the number of queued commands
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    /// Check if no commands are queued
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Apply the queued commands in order, returning the removed entities
+    pub(crate) fn apply(&mut self, world: &mut World) -> Vec<Entity> {
+        let mut removed = Vec::new();
+        for command in std::mem::take(&mut self.commands) {
+            match command {
+                Command::Despawn(entity) => {
+                    if world.remove_entity(entity) {
+                        removed.push(entity);
+                    }
+                }
+                Command::DespawnRecursive(entity) => removed.extend(world.despawn_recursive(entity)),
+                Command::DespawnDeferred(entity) => {
+                    if !world.deferred_despawns.contains(&entity) {
+                        world.deferred_despawns.push(entity);
+                    }
+                }
+            }
+        }
+        removed
+    }
+}
+
+impl World {
+    /// Remove an entity together with the entities attached to it with a
+    /// `Parent` component, down the whole hierarchy. Returns the removed
+    /// entities, parents before their children.
+    pub fn despawn_recursive(&mut self, entity: Entity) -> Vec<Entity> {
+        if !self.entity_exists(entity) {
+            return Vec::new();
+        }
+        // Collect the hierarchy first, children are found through their Parent
+        let mut hierarchy = vec![entity];
+        let mut visited = BTreeSet::from([entity]);
+        let mut next = 0;
+        while next < hierarchy.len() {
+            for child in self.children(hierarchy[next]) {
+                // Parent cycles end at entities already collected
+                if visited.insert(child) {
+                    hierarchy.push(child);
+                }
+            }
+            next += 1;
+        }
+        hierarchy.retain(|entity| self.remove_entity(*entity));
+        hierarchy
+    }
+
+    /// Remove the entities queued with `despawn_deferred` during the frame,
+    /// recording them as removed by the frame's last system
+    pub(crate) fn apply_deferred_despawns(&mut self, diff: &mut WorldUpdateDiff) {
+        if self.deferred_despawns.is_empty() {
+            return;
+        }
+        let entities = std::mem::take(&mut self.deferred_despawns);
+        let Some(last) = diff.system_diffs.last_mut() else {
+            return;
+        };
+        for entity in entities {
+            // Replay mode keeps the world unchanged, only the removal is recorded
+            let removed = if self.replay_mode {
+                self.entity_exists(entity)
+            } else {
+                self.remove_entity(entity)
+            };
+            if removed {
+                last.record_world_operation(WorldOperation::RemoveEntity(entity));
+            }
+        }
+    }
+}
+
+impl<I, O> WorldView<I, O> {
+    /// Get the command buffer applied when the system's update ends
+    pub fn commands(&mut self) -> &mut CommandBuffer {
+        &mut self.commands
+    }
+
+    /// Apply the queued commands, recording the removed entities
+    pub(crate) fn apply_commands(&mut self) {
+        if self.commands.is_empty() {
+            return;
+        }
+        let mut commands = std::mem::take(&mut self.commands);
+        let removed = commands.apply(unsafe { self.world_mut() });
+        for entity in removed {
+            self.record_entity_removal(entity);
+        }
+    }
+
+    /// Record the removal of an entity
+    pub(crate) fn record_entity_removal(&mut self, entity: Entity) {
+        self.system_diff.record_world_operation(WorldOperation::RemoveEntity(entity));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Diff, Entity, In, Parent, System, World, WorldOperation, WorldView};
+
+    #[derive(Debug, Clone, Copy, PartialEq, Diff)]
+    struct Health {
+        points: u32,
+    }
+
+    /// Despawns every entity without health points, with everything attached to it
+    struct CleanupSystem;
+
+    impl System for CleanupSystem {
+        type InComponents = (Health,);
+        type OutComponents = ();
+
+        fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+        fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+            let dead: Vec<Entity> = world
+                .query_components::<(In<Health>,)>()
+                .into_iter()
+                .filter(|(_, health)| health.points == 0)
+                .map(|(entity, _)| entity)
+                .collect();
+            for entity in dead {
+                world.commands().despawn_recursive(entity);
+                // Nothing is removed until the update ends
+                assert!(world.get_component::<Health>(entity).is_some());
+            }
+        }
+
+        fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    }
+
+    #[test]
+    fn test_despawn_recursive_removes_the_hierarchy() {
+        let mut world = World::new();
+        let actor = world.create_entity();
+        let tool = world.create_entity();
+        let gem = world.create_entity();
+        let other = world.create_entity();
+        world.add_component(tool, Parent { entity: actor });
+        world.add_component(gem, Parent { entity: tool });
+        world.add_component(other, Parent { entity: gem });
+        // A cycle ends the hierarchy
+        world.add_component(actor, Parent { entity: other });
+
+        assert_eq!(world.despawn_recursive(tool), vec![tool, gem, other, actor]);
+        assert_eq!(world.entity_count(), 0);
+        assert!(world.despawn_recursive(tool).is_empty());
+    }
+
+    #[test]
+    fn test_queued_despawns_are_recorded_and_replayed() {
+        let mut world = World::new();
+        let living = world.create_entity();
+        world.add_component(living, Health { points: 3 });
+        let dead = world.create_entity();
+        world.add_component(dead, Health { points: 0 });
+        let sword = world.create_entity();
+        world.add_component(sword, Parent { entity: dead });
+        let mut replayed = World::new();
+        for _ in 0..3 {
+            replayed.create_entity();
+        }
+        world.add_system(CleanupSystem);
+        world.update();
+
+        assert_eq!(world.entities_with_component::<Health>(), vec![living]);
+        assert!(!world.entity_exists(sword));
+        let update = world.get_update_history().updates().last().unwrap();
+        assert_eq!(
+            update.system_diffs()[0].world_operations(),
+            &[WorldOperation::RemoveEntity(dead), WorldOperation::RemoveEntity(sword)]
+        );
+
+        replayed.apply_update_diff(update);
+        assert!(replayed.entity_exists(living));
+        assert!(!replayed.entity_exists(dead) && !replayed.entity_exists(sword));
+    }
+}
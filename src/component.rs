@@ -0,0 +1,128 @@
+//! Component types that register themselves.
+//!
+//! Reflection and replay need every component type registered in both the
+//! recording and the replaying world. `#[derive(Component)]` records the
+//! type's name, TypeId and registration once, next to the type:
+//!
+//! ```ignore
+//! #[derive(Debug, Clone, PartialEq, Diff, Reflect, Component, Serialize, Deserialize)]
+//! #[component(replay)]
+//! struct Position { x: i32, y: i32 }
+//! ```
+//!
+//! With the `auto-register` feature, every derived type is collected at
+//! startup and registered in each new world, so no `register_component` or
+//! `register_replay_component` calls are needed. Without it, the types are
+//! registered with `World::register::<Position>()`.
+//!
+//! Types are registered in name order, so registration order, and with it
+//! reflected component order, is the same on every run.
+
+use crate::{ComponentRegistry, Reflect, World};
+use std::any::TypeId;
+
+/// A component type with registration metadata, see `#[derive(Component)]`
+pub trait Component: Reflect {
+    /// Short type name, as in replay logs
+    const NAME: &'static str;
+
+    /// Register the type for reflection, and for replay if it opted in
+    fn register(registry: &mut ComponentRegistry);
+}
+
+/// Metadata of a derived component type, collected at startup with the
+/// `auto-register` feature
+#[derive(Debug, Clone, Copy)]
+pub struct ComponentInfo {
+    pub name: &'static str,
+    type_id: fn() -> TypeId,
+    register: fn(&mut ComponentRegistry),
+}
+
+impl ComponentInfo {
+    pub const fn of<T: Component>() -> Self {
+        Self {
+            name: T::NAME,
+            type_id: TypeId::of::<T>,
+            register: T::register,
+        }
+    }
+
+    pub fn type_id(&self) -> TypeId {
+        (self.type_id)()
+    }
+}
+
+#[cfg(feature = "auto-register")]
+inventory::collect!(ComponentInfo);
+
+/// Get the component types derived with `#[derive(Component)]` in the
+/// program, in name order. Empty without the `auto-register` feature.
+pub fn derived_components() -> Vec<&'static ComponentInfo> {
+    #[cfg(feature = "auto-register")]
+    let mut components: Vec<&'static ComponentInfo> = inventory::iter::<ComponentInfo>.into_iter().collect();
+    #[cfg(not(feature = "auto-register"))]
+    let mut components: Vec<&'static ComponentInfo> = Vec::new();
+    components.sort_by_key(|info| info.name);
+    components
+}
+
+impl World {
+    /// Register a derived component type for reflection, and for replay if
+    /// it opted in with `#[component(replay)]`
+    pub fn register<T: Component>(&mut self) {
+        T::register(&mut self.component_registry);
+    }
+
+    /// Register every type collected by `derived_components`. New worlds do
+    /// this on creation.
+    pub fn register_derived_components(&mut self) {
+        for info in derived_components() {
+            (info.register)(&mut self.component_registry);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Component, Diff, Reflect};
+
+    #[derive(Debug, Clone, PartialEq, Diff, Reflect, Component)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    #[component(replay)]
+    struct Stamina {
+        points: i32,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Reflect, Component)]
+    struct Nickname {
+        name: String,
+    }
+
+    #[test]
+    fn test_derived_component_metadata() {
+        let info = ComponentInfo::of::<Stamina>();
+        assert_eq!(info.name, "Stamina");
+        assert_eq!(info.type_id(), TypeId::of::<Stamina>());
+
+        let mut world = World::new();
+        world.register::<Stamina>();
+        world.register::<Nickname>();
+        let registry = world.component_registry();
+        assert_eq!(registry.get("Stamina").unwrap().is_replayable(), cfg!(feature = "serde"));
+        assert!(!registry.get("Nickname").unwrap().is_replayable());
+    }
+
+    #[cfg(feature = "auto-register")]
+    #[test]
+    fn test_derived_components_are_registered_in_new_worlds() {
+        let names: Vec<&str> = derived_components().iter().map(|info| info.name).collect();
+        assert!(names.contains(&"Nickname") && names.contains(&"Stamina"));
+        assert!(names.windows(2).all(|pair| pair[0] <= pair[1]));
+
+        let world = World::new();
+        assert!(world.component_registry().get("Nickname").is_some());
+        assert_eq!(world.component_registry().get("Stamina").unwrap().is_replayable(), cfg!(feature = "serde"));
+    }
+}
@@ -0,0 +1,187 @@
+//! Values of single components at past frames, read from the history.
+//!
+//! ```ignore
+//! let now = world.get_update_history().len();
+//! let earlier = world.get_update_history().component_at::<Position>(actor, now - 200)?;
+//! ```
+//!
+//! The value is rebuilt from the last keyframe at or before the frame, or
+//! the initial state, by applying only the recorded changes of that one
+//! component. Frames count recorded updates, like `ReplayPlayer` frames:
+//! frame 0 is the state before the first update. The component type must be
+//! replayable, as changes are decoded the way `register_replay_component`
+//! decodes them. Changes filtered out of the history are not seen, so the
+//! value of a type recorded with a sampling policy is only approximate.
+
+use crate::snapshot::WorldSnapshot;
+use crate::{short_type_name, Diff, DiffComponentChange, DiffEntry, Entity, WorldOperation, WorldUpdateHistory};
+
+impl WorldUpdateHistory {
+    /// Get the value a component of an entity had after the first `frame`
+    /// updates, None if the entity did not have one then
+    pub fn component_at<T>(&self, entity: Entity, frame: usize) -> Result<Option<T>, String>
+    where
+        T: Diff + serde::de::DeserializeOwned,
+        T::Diff: serde::de::DeserializeOwned,
+    {
+        if frame > self.len() {
+            return Err(format!("Frame {} is past the {} recorded updates", frame, self.len()));
+        }
+        let type_name = short_type_name::<T>();
+        let (start, state) = match self.keyframes().iter().rfind(|keyframe| keyframe.update_index <= frame) {
+            Some(keyframe) => (keyframe.update_index, Some(&keyframe.state)),
+            None => (0, self.initial_state()),
+        };
+        let mut component = match state {
+            Some(state) => snapshot_component::<T>(state, entity, type_name)?,
+            None => None,
+        };
+
+        for update in &self.updates()[start..frame] {
+            for entry in update.system_diffs().iter().flat_map(|system_diff| system_diff.entries()) {
+                match entry {
+                    DiffEntry::Change(DiffComponentChange::Added { entity: changed, type_name: changed_type, data })
+                        if *changed == entity && changed_type.as_str() == type_name =>
+                    {
+                        component = Some(decode::<T>(data)?);
+                    }
+                    DiffEntry::Change(DiffComponentChange::Modified { entity: changed, type_name: changed_type, diff })
+                        if *changed == entity && changed_type.as_str() == type_name =>
+                    {
+                        let current = component
+                            .as_mut()
+                            .ok_or_else(|| format!("{} of {} was modified before it was added", type_name, entity))?;
+                        let diff = ron::from_str::<T::Diff>(diff)
+                            .map_err(|e| format!("Invalid {} diff '{}': {}", type_name, diff, e))?;
+                        current.apply_diff(&diff);
+                    }
+                    DiffEntry::Change(DiffComponentChange::Removed { entity: changed, type_name: changed_type })
+                        if *changed == entity && changed_type.as_str() == type_name =>
+                    {
+                        component = None;
+                    }
+                    DiffEntry::Operation(WorldOperation::RemoveEntity(removed)) if *removed == entity => {
+                        component = None;
+                    }
+                    DiffEntry::Operation(WorldOperation::ClearEntities | WorldOperation::ClearAll) => {
+                        component = None;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(component)
+    }
+}
+
+/// Decode the component of an entity stored in a snapshot
+fn snapshot_component<T: serde::de::DeserializeOwned>(
+    state: &WorldSnapshot,
+    entity: Entity,
+    type_name: &str,
+) -> Result<Option<T>, String> {
+    state
+        .components()
+        .into_iter()
+        .find(|(owner, owner_type, _)| *owner == entity && *owner_type == type_name)
+        .map(|(_, _, data)| decode::<T>(data))
+        .transpose()
+}
+
+fn decode<T: serde::de::DeserializeOwned>(data: &str) -> Result<T, String> {
+    ron::from_str::<T>(data).map_err(|e| format!("Invalid {} data '{}': {}", short_type_name::<T>(), data, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Diff, Entity, Reflect, System, World, WorldView};
+
+    #[derive(Debug, Clone, PartialEq, Diff, Reflect, serde::Serialize, serde::Deserialize)]
+    struct Position {
+        x: i32,
+        y: i32,
+    }
+
+    /// Spawns a walker when initialized and moves it right every update,
+    /// removing it after the fifth step
+    struct WalkSystem(Option<Entity>);
+
+    impl System for WalkSystem {
+        type InComponents = ();
+        type OutComponents = (Position,);
+
+        fn initialize(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+            let walker = world.create_entity();
+            world.record_entity_creation(walker);
+            let position = Position { x: 0, y: 3 };
+            world.add_component(walker, position.clone());
+            world.record_component_addition(walker, &position);
+            self.0 = Some(walker);
+        }
+
+        fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+            let Some(walker) = self.0 else {
+                return;
+            };
+            let Some(position) = world.get_component_mut::<Position>(walker) else {
+                return;
+            };
+            let old = position.clone();
+            position.x += 1;
+            let new = position.clone();
+            world.record_component_modification(walker, &old, &new);
+            if new.x == 5 {
+                world.remove_component::<Position>(walker);
+                world.record_component_removal::<Position>(walker);
+                self.0 = None;
+            }
+        }
+
+        fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    }
+
+    fn walked_world() -> (World, Entity) {
+        let mut world = World::new();
+        world.register_replay_component::<Position>();
+        world.add_system(WalkSystem(None));
+        world.initialize_systems();
+        let walker = Entity::new(0, 0);
+        for _ in 0..6 {
+            world.update();
+        }
+        (world, walker)
+    }
+
+    #[test]
+    fn test_component_at_past_frames() {
+        let (world, walker) = walked_world();
+        let history = world.get_update_history();
+        // The system addition, the initialization, then 6 updates
+        assert_eq!(history.len(), 8);
+        assert_eq!(history.component_at::<Position>(walker, 1).unwrap(), None);
+        assert_eq!(history.component_at::<Position>(walker, 2).unwrap(), Some(Position { x: 0, y: 3 }));
+        assert_eq!(history.component_at::<Position>(walker, 5).unwrap(), Some(Position { x: 3, y: 3 }));
+        assert_eq!(history.component_at::<Position>(walker, 7).unwrap(), None);
+        assert!(history.component_at::<Position>(walker, 9).is_err());
+    }
+
+    #[test]
+    fn test_component_at_starts_from_the_nearest_keyframe() {
+        let (world, walker) = walked_world();
+        let mut history = world.get_update_history().clone();
+        history.truncate(4);
+
+        // A keyframe disagreeing with the updates shows which one was used
+        let mut state = World::new();
+        state.register_replay_component::<Position>();
+        let entity = state.create_entity();
+        state.add_component(entity, Position { x: 40, y: 0 });
+        history.record_keyframe(3, state.snapshot());
+        history.record(world.get_update_history().updates()[4].clone());
+
+        assert_eq!(history.component_at::<Position>(walker, 3).unwrap(), Some(Position { x: 1, y: 3 }));
+        assert_eq!(history.component_at::<Position>(walker, 4).unwrap(), Some(Position { x: 40, y: 0 }));
+        // The update after the keyframe only sets x
+        assert_eq!(history.component_at::<Position>(walker, 5).unwrap(), Some(Position { x: 3, y: 0 }));
+    }
+}
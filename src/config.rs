@@ -0,0 +1,267 @@
+//! Tuning values loaded from TOML files and reloaded while the game runs.
+//!
+//! `ConfigWatcher` loads a file into a `T` and watches it. Saving the file
+//! updates the value before the next frame's systems read it:
+//!
+//! ```ignore
+//! #[derive(Debug, Clone, Default, Diff, Reflect, Serialize, Deserialize)]
+//! struct Tuning {
+//!     speed: f32,
+//!     spawn_interval: u32,
+//! }
+//!
+//! world.add_plugin(ConfigWatcher::<Tuning>::new("tuning.toml"));
+//! let speed = world.config::<Tuning>().unwrap().speed;
+//! ```
+//!
+//! The value is a component of an entity spawned by the plugin, found
+//! through the `Config<T>` resource. Reloads are recorded like any other
+//! component modification, so the history and replay logs of a tuning
+//! session contain every value the systems saw and replays need no file.
+//! A file that cannot be read or parsed leaves the previous value in place.
+
+use crate::{short_type_name, Diff, Entity, Plugin, Reflect, System, World, WorldView};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::fmt::Debug;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+
+/// Where the value loaded from a watched file is kept
+pub struct Config<T> {
+    entity: Entity,
+    path: PathBuf,
+    _value: PhantomData<fn() -> T>,
+}
+
+impl<T: 'static> Config<T> {
+    /// Get the entity holding the value as a component
+    pub fn entity(&self) -> Entity {
+        self.entity
+    }
+
+    /// Get the path of the watched file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Get the current value
+    pub fn get<'w>(&self, world: &'w World) -> Option<&'w T> {
+        world.get_component::<T>(self.entity)
+    }
+}
+
+/// Loads a `T` from a TOML file and reloads it whenever the file changes.
+///
+/// If the file cannot be loaded at startup, `T::default()` is used until it
+/// can be.
+pub struct ConfigWatcher<T> {
+    path: PathBuf,
+    _value: PhantomData<fn() -> T>,
+}
+
+impl<T> ConfigWatcher<T> {
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self {
+            path: path.into(),
+            _value: PhantomData,
+        }
+    }
+}
+
+impl<T> Plugin for ConfigWatcher<T>
+where
+    T: Reflect + Diff + Clone + Debug + Default + serde::Serialize + serde::de::DeserializeOwned + 'static,
+    T::Diff: serde::Serialize + serde::de::DeserializeOwned,
+{
+    fn build(&self, world: &mut World) {
+        world.register_replay_component::<T>();
+        let value = load_config::<T>(&self.path).unwrap_or_else(|e| {
+            ecs_error!("{}, using the default {}", e, short_type_name::<T>());
+            T::default()
+        });
+        let entity = world.create_entity();
+        world.add_component(entity, value);
+        world.insert_resource(Config::<T> {
+            entity,
+            path: self.path.clone(),
+            _value: PhantomData,
+        });
+
+        let (sender, events) = mpsc::channel();
+        let file_name = self.path.file_name().map(|name| name.to_os_string());
+        let watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            let Ok(event) = event else {
+                return;
+            };
+            // Reading the file while reloading it must not trigger another reload
+            let written = matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_));
+            if written && event.paths.iter().any(|path| path.file_name() == file_name.as_deref()) {
+                let _ = sender.send(());
+            }
+        })
+        .and_then(|mut watcher| {
+            // Watch the directory, as editors often replace the file when saving
+            let directory = match self.path.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent,
+                _ => Path::new("."),
+            };
+            watcher.watch(directory, RecursiveMode::NonRecursive)?;
+            Ok(watcher)
+        });
+        let watcher = match watcher {
+            Ok(watcher) => Some(watcher),
+            Err(e) => {
+                ecs_error!("Failed to watch {}: {}", self.path.display(), e);
+                None
+            }
+        };
+        world.add_system(ConfigReloadSystem::<T> {
+            path: self.path.clone(),
+            entity,
+            events,
+            _watcher: watcher,
+            _value: PhantomData,
+        });
+    }
+
+    fn name(&self) -> &'static str {
+        "ConfigWatcher"
+    }
+}
+
+/// Replaces the config component with the file's contents after it changed
+struct ConfigReloadSystem<T> {
+    path: PathBuf,
+    entity: Entity,
+    events: Receiver<()>,
+    /// Sends to `events` until dropped
+    _watcher: Option<RecommendedWatcher>,
+    _value: PhantomData<fn() -> T>,
+}
+
+impl<T> System for ConfigReloadSystem<T>
+where
+    T: Diff + Clone + Debug + serde::de::DeserializeOwned + 'static,
+{
+    type InComponents = ();
+    type OutComponents = (T,);
+
+    fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+    fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+        // Saving a file often takes several writes, one reload covers them all
+        if self.events.try_iter().count() == 0 {
+            return;
+        }
+        let value = match load_config::<T>(&self.path) {
+            Ok(value) => value,
+            Err(e) => {
+                ecs_warn!("{}, keeping the previous {}", e, short_type_name::<T>());
+                return;
+            }
+        };
+        let Some(current) = world.get_component_mut::<T>(self.entity) else {
+            return;
+        };
+        if current.diff(&value).is_none() {
+            return;
+        }
+        let previous = std::mem::replace(current, value.clone());
+        world.record_component_modification(self.entity, &previous, &value);
+        ecs_info!("Reloaded {} from {}", short_type_name::<T>(), self.path.display());
+    }
+
+    fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+}
+
+fn load_config<T: serde::de::DeserializeOwned>(path: &Path) -> Result<T, String> {
+    let contents = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    toml::from_str(&contents).map_err(|e| format!("Failed to parse {}: {}", path.display(), e))
+}
+
+impl World {
+    /// Get the value loaded by the `ConfigWatcher` of T
+    pub fn config<T: 'static>(&self) -> Option<&T> {
+        self.resource::<Config<T>>()?.get(self)
+    }
+}
+
+impl<I, O> WorldView<I, O> {
+    /// Get the value loaded by the `ConfigWatcher` of T
+    pub fn config<T: 'static>(&self) -> Option<&T> {
+        let entity = self.resource::<Config<T>>()?.entity;
+        self.get_component::<T>(entity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay_analysis::{parse_replay_log_str, write_replay_log};
+    use std::time::Duration;
+
+    #[derive(Debug, Clone, Default, PartialEq, Diff, Reflect, serde::Serialize, serde::Deserialize)]
+    struct Tuning {
+        speed: f32,
+        workers: u32,
+    }
+
+    /// Update the world until the config matches, for up to five seconds
+    fn update_until(world: &mut World, expected: &Tuning) -> bool {
+        for _ in 0..500 {
+            world.update();
+            if world.config::<Tuning>() == Some(expected) {
+                return true;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        false
+    }
+
+    #[test]
+    fn test_config_is_reloaded_and_recorded() {
+        let directory = std::env::temp_dir().join(format!("rust_ecs_config_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("tuning.toml");
+        std::fs::write(&path, "speed = 1.5\nworkers = 2\n").unwrap();
+
+        let mut world = World::new();
+        world.add_plugin(ConfigWatcher::<Tuning>::new(&path));
+        let initial = Tuning { speed: 1.5, workers: 2 };
+        assert_eq!(world.config::<Tuning>(), Some(&initial));
+        world.update();
+
+        let tuned = Tuning { speed: 3.0, workers: 2 };
+        std::fs::write(&path, "speed = 3.0\nworkers = 2\n").unwrap();
+        assert!(update_until(&mut world, &tuned), "{:?}", world.config::<Tuning>());
+
+        // Broken files are ignored
+        std::fs::write(&path, "speed = \"fast\"\n").unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        world.update();
+        world.update();
+        assert_eq!(world.config::<Tuning>(), Some(&tuned));
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        // Replays see the reload without the file
+        let mut log = Vec::new();
+        write_replay_log(world.get_update_history(), &mut log).unwrap();
+        let mut replayed = World::new();
+        replayed.register_replay_component::<Tuning>();
+        let entity = replayed.create_entity();
+        replayed.add_component(entity, initial);
+        for update in parse_replay_log_str(&String::from_utf8(log).unwrap()).updates() {
+            replayed.apply_update_diff(update);
+        }
+        assert_eq!(replayed.get_component::<Tuning>(entity), Some(&tuned));
+    }
+
+    #[test]
+    fn test_missing_file_uses_the_default() {
+        let mut world = World::new();
+        world.add_plugin(ConfigWatcher::<Tuning>::new("missing/tuning.toml"));
+        assert_eq!(world.config::<Tuning>(), Some(&Tuning::default()));
+        world.update();
+    }
+}
@@ -0,0 +1,435 @@
+//! Component types defined at runtime.
+//!
+//! Mods and scripts cannot add Rust types, so they describe their components
+//! with a `ComponentSchema`: a name and typed fields. Dynamic components are
+//! stored as reflected `Value` maps and checked against their schema:
+//!
+//! ```ignore
+//! world.register_schema(ComponentSchema::new("Mana").field("current", FieldType::Int));
+//! world.add_dyn_component(wizard, "Mana", mana)?;
+//! for (entity, mana) in world.query_dyn(DynComponent("Mana")) { /* ... */ }
+//! ```
+//!
+//! Changes made through a `WorldView` are recorded like those of typed
+//! components, under the schema name: additions with the full value,
+//! modifications with the changed fields, e.g.
+//! `MOD Entity(0, 1) Mana {current: 4}`. Replays apply them to worlds with the
+//! same schemas registered.
+
+use crate::coalesce::WriteKind;
+use crate::prefab::merge_value;
+use crate::reflect::Value;
+use crate::{DiffComponentChange, Entity, World, WorldView};
+use std::collections::BTreeMap;
+
+/// Type of a field of a dynamic component
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldType {
+    Bool,
+    Int,
+    Float,
+    String,
+}
+
+impl FieldType {
+    /// Value of the field when a component leaves it out
+    fn default_value(self) -> Value {
+        match self {
+            FieldType::Bool => Value::Bool(false),
+            FieldType::Int => Value::Int(0),
+            FieldType::Float => Value::Float(0.0),
+            FieldType::String => Value::String(String::new()),
+        }
+    }
+
+    /// Check a value against the type, widening integers for float fields
+    fn check(self, value: &Value) -> Option<Value> {
+        match (self, value) {
+            (FieldType::Bool, Value::Bool(_))
+            | (FieldType::Int, Value::Int(_))
+            | (FieldType::Float, Value::Float(_))
+            | (FieldType::String, Value::String(_)) => Some(value.clone()),
+            (FieldType::Float, Value::Int(value)) => Some(Value::Float(*value as f64)),
+            _ => None,
+        }
+    }
+}
+
+/// Name and typed fields of a component type defined at runtime
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComponentSchema {
+    name: String,
+    fields: Vec<(String, FieldType)>,
+}
+
+impl ComponentSchema {
+    /// Create a schema without fields
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            fields: Vec::new(),
+        }
+    }
+
+    /// Add a field, replacing an earlier field with the same name
+    pub fn field(mut self, name: impl Into<String>, field_type: FieldType) -> Self {
+        let name = name.into();
+        self.fields.retain(|(existing, _)| *existing != name);
+        self.fields.push((name, field_type));
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get the fields in the order they were added
+    pub fn fields(&self) -> &[(String, FieldType)] {
+        &self.fields
+    }
+
+    /// Get the type of a field
+    pub fn field_type(&self, name: &str) -> Option<FieldType> {
+        self.fields.iter().find(|(field, _)| field == name).map(|(_, field_type)| *field_type)
+    }
+
+    /// Check a component value, filling in missing fields with defaults
+    pub fn validate(&self, value: &Value) -> Result<Value, String> {
+        let Value::Map(map) = value else {
+            return Err(format!("{} component must be a map, found {}", self.name, value));
+        };
+        if let Some(unknown) = map.keys().find(|key| self.field_type(key).is_none()) {
+            return Err(format!("{} has no field {}", self.name, unknown));
+        }
+        let mut fields = BTreeMap::new();
+        for (name, field_type) in &self.fields {
+            let value = match map.get(name) {
+                Some(value) => field_type
+                    .check(value)
+                    .ok_or_else(|| format!("{}.{} expects {:?}, found {}", self.name, name, field_type, value))?,
+                None => field_type.default_value(),
+            };
+            fields.insert(name.clone(), value);
+        }
+        Ok(Value::Map(fields))
+    }
+}
+
+/// Query wrapper naming a dynamic component type, e.g. `DynComponent("Mana")`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DynComponent<'a>(pub &'a str);
+
+/// Schemas and components of the dynamic component types of a world
+#[derive(Default)]
+pub(crate) struct DynamicComponents {
+    schemas: BTreeMap<String, ComponentSchema>,
+    components: BTreeMap<String, BTreeMap<Entity, Value>>,
+}
+
+impl DynamicComponents {
+    pub(crate) fn is_dynamic(&self, name: &str) -> bool {
+        self.schemas.contains_key(name)
+    }
+
+    /// Iterate over the registered schemas, in name order
+    pub(crate) fn schemas(&self) -> impl Iterator<Item = &ComponentSchema> {
+        self.schemas.values()
+    }
+
+    /// Remove every dynamic component of an entity
+    pub(crate) fn remove_entity(&mut self, entity: Entity) {
+        for components in self.components.values_mut() {
+            components.remove(&entity);
+        }
+    }
+
+    /// Iterate over all dynamic components as schema name, entity and value
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&str, Entity, &Value)> {
+        self.components
+            .iter()
+            .flat_map(|(name, components)| components.iter().map(move |(entity, value)| (name.as_str(), *entity, value)))
+    }
+}
+
+/// Fields of `new` that differ from `old`, or None if nothing changed
+fn changed_fields(old: &Value, new: &Value) -> Option<Value> {
+    let (Value::Map(old), Value::Map(new)) = (old, new) else {
+        return (old != new).then(|| new.clone());
+    };
+    let changed: BTreeMap<String, Value> = new
+        .iter()
+        .filter(|(name, value)| old.get(*name) != Some(value))
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect();
+    (!changed.is_empty()).then_some(Value::Map(changed))
+}
+
+/// Recorded modification of a dynamic component, None if nothing changed
+fn modification(entity: Entity, name: &str, old: &Value, new: &Value) -> Option<DiffComponentChange> {
+    changed_fields(old, new).map(|diff| DiffComponentChange::Modified {
+        entity,
+        type_name: name.into(),
+        diff: diff.to_string().into(),
+    })
+}
+
+impl World {
+    /// Register a component type defined at runtime, replacing a schema with the
+    /// same name. Existing components of the type are kept.
+    pub fn register_schema(&mut self, schema: ComponentSchema) {
+        self.dynamic.components.entry(schema.name.clone()).or_default();
+        self.dynamic.schemas.insert(schema.name.clone(), schema);
+    }
+
+    /// Get a registered schema by name
+    pub fn schema(&self, name: &str) -> Option<&ComponentSchema> {
+        self.dynamic.schemas.get(name)
+    }
+
+    /// Add a dynamic component to an entity, replacing an existing one.
+    /// Missing fields get default values.
+    pub fn add_dyn_component(&mut self, entity: Entity, name: &str, value: Value) -> Result<(), String> {
+        let schema = self.dynamic.schemas.get(name).ok_or_else(|| format!("Unknown component schema {}", name))?;
+        if !self.entity_exists(entity) {
+            return Err(format!("Entity {} does not exist", entity));
+        }
+        let value = schema.validate(&value)?;
+        self.dynamic.components.entry(name.to_string()).or_default().insert(entity, value);
+        Ok(())
+    }
+
+    /// Get a dynamic component of an entity
+    pub fn dyn_component(&self, entity: Entity, name: &str) -> Option<&Value> {
+        self.dynamic.components.get(name)?.get(&entity)
+    }
+
+    /// Set a field of a dynamic component, returning the previous component
+    pub fn set_dyn_field(&mut self, entity: Entity, name: &str, field: &str, value: Value) -> Result<Value, String> {
+        let mut fields = BTreeMap::new();
+        fields.insert(field.to_string(), value);
+        self.merge_dyn_component(entity, name, &Value::Map(fields))
+    }
+
+    /// Remove a dynamic component, returning it
+    pub fn remove_dyn_component(&mut self, entity: Entity, name: &str) -> Option<Value> {
+        self.dynamic.components.get_mut(name)?.remove(&entity)
+    }
+
+    /// Get the enabled entities with a dynamic component, in entity order
+    pub fn query_dyn(&self, component: DynComponent) -> Vec<(Entity, &Value)> {
+        let Some(components) = self.dynamic.components.get(component.0) else {
+            return Vec::new();
+        };
+        components
+            .iter()
+            .filter(|(entity, _)| self.is_enabled(**entity))
+            .map(|(entity, value)| (*entity, value))
+            .collect()
+    }
+
+    /// Merge changed fields into a dynamic component, returning the previous component
+    fn merge_dyn_component(&mut self, entity: Entity, name: &str, fields: &Value) -> Result<Value, String> {
+        let schema = self.dynamic.schemas.get(name).ok_or_else(|| format!("Unknown component schema {}", name))?;
+        let component = self
+            .dynamic
+            .components
+            .get_mut(name)
+            .and_then(|components| components.get_mut(&entity))
+            .ok_or_else(|| format!("Entity {} has no {} component", entity, name))?;
+        let mut merged = component.clone();
+        merge_value(&mut merged, fields);
+        let merged = schema.validate(&merged)?;
+        Ok(std::mem::replace(component, merged))
+    }
+
+    /// Apply a recorded addition of a dynamic component
+    pub(crate) fn apply_dyn_addition(&mut self, entity: Entity, name: &str, data: &str) -> Result<(), String> {
+        self.add_dyn_component(entity, name, data.parse()?)
+    }
+
+    /// Apply recorded changed fields of a dynamic component
+    pub(crate) fn apply_dyn_modification(&mut self, entity: Entity, name: &str, diff: &str) -> Result<(), String> {
+        self.merge_dyn_component(entity, name, &diff.parse()?).map(|_| ())
+    }
+}
+
+impl<I, O> WorldView<I, O> {
+    /// Add a dynamic component, recording the addition in the system's diff
+    pub fn add_dyn_component(&mut self, entity: Entity, name: &str, value: Value) -> Result<(), String> {
+        let world = unsafe { self.world_mut() };
+        world.add_dyn_component(entity, name, value)?;
+        let data = world.dyn_component(entity, name).map(Value::to_string).unwrap_or_default();
+        let change = DiffComponentChange::Added {
+            entity,
+            type_name: name.into(),
+            data: data.into(),
+        };
+        self.record_write(entity, name, Some(change), Some(WriteKind::Added));
+        Ok(())
+    }
+
+    /// Get a dynamic component of an entity
+    pub fn dyn_component(&self, entity: Entity, name: &str) -> Option<&Value> {
+        unsafe { self.world() }.dyn_component(entity, name)
+    }
+
+    /// Set a field of a dynamic component, recording the change in the system's diff
+    pub fn set_dyn_field(&mut self, entity: Entity, name: &str, field: &str, value: Value) -> Result<(), String> {
+        let world = unsafe { self.world_mut() };
+        let old = world.set_dyn_field(entity, name, field, value)?;
+        let new = world.dyn_component(entity, name).cloned().unwrap_or(Value::Unit);
+        // Later writes of the update replace the change of earlier ones
+        let (change, kind) = match self.writes.earlier(entity, name) {
+            Some(WriteKind::Added) => {
+                let change = DiffComponentChange::Added {
+                    entity,
+                    type_name: name.into(),
+                    data: new.to_string().into(),
+                };
+                (Some(change), None)
+            }
+            Some(WriteKind::Modified(base)) => {
+                let base = base.downcast_ref::<Value>().unwrap_or(&old);
+                (modification(entity, name, base, &new), None)
+            }
+            _ => (
+                modification(entity, name, &old, &new),
+                Some(WriteKind::Modified(Box::new(old.clone()))),
+            ),
+        };
+        self.record_write(entity, name, change, kind);
+        Ok(())
+    }
+
+    /// Remove a dynamic component, recording the removal in the system's diff
+    pub fn remove_dyn_component(&mut self, entity: Entity, name: &str) -> Option<Value> {
+        let removed = unsafe { self.world_mut() }.remove_dyn_component(entity, name)?;
+        match self.writes.earlier(entity, name) {
+            // Added by this update, so there is nothing to record
+            Some(WriteKind::Added | WriteKind::Cancelled) => {
+                self.record_write(entity, name, None, Some(WriteKind::Cancelled));
+            }
+            _ => {
+                let change = DiffComponentChange::Removed {
+                    entity,
+                    type_name: name.into(),
+                };
+                self.record_write(entity, name, Some(change), Some(WriteKind::Removed));
+            }
+        }
+        Some(removed)
+    }
+
+    /// Get the enabled entities with a dynamic component, in entity order
+    pub fn query_dyn(&self, component: DynComponent) -> Vec<(Entity, &Value)> {
+        unsafe { self.world() }.query_dyn(component)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay_analysis::{parse_replay_log_str, write_replay_log};
+    use crate::System;
+
+    fn mana_schema() -> ComponentSchema {
+        ComponentSchema::new("Mana")
+            .field("current", FieldType::Int)
+            .field("max", FieldType::Int)
+            .field("regen", FieldType::Float)
+    }
+
+    fn mana(current: i64) -> Value {
+        let mut fields = BTreeMap::new();
+        fields.insert("current".to_string(), Value::Int(current));
+        fields.insert("max".to_string(), Value::Int(10));
+        fields.insert("regen".to_string(), Value::Int(1));
+        Value::Map(fields)
+    }
+
+    /// Regenerates mana up to the maximum
+    struct RegenSystem;
+
+    impl System for RegenSystem {
+        type InComponents = ();
+        type OutComponents = ();
+
+        fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+        fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+            let regenerating: Vec<(Entity, i64)> = world
+                .query_dyn(DynComponent("Mana"))
+                .into_iter()
+                .filter_map(|(entity, mana)| {
+                    let Value::Map(fields) = mana else {
+                        return None;
+                    };
+                    match (&fields["current"], &fields["max"]) {
+                        (Value::Int(current), Value::Int(max)) if current < max => Some((entity, current + 1)),
+                        _ => None,
+                    }
+                })
+                .collect();
+            for (entity, current) in regenerating {
+                world.set_dyn_field(entity, "Mana", "current", Value::Int(current)).unwrap();
+            }
+        }
+
+        fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    }
+
+    /// Gives an entity mana once
+    struct SummonSystem(Entity);
+
+    impl System for SummonSystem {
+        type InComponents = ();
+        type OutComponents = ();
+
+        fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+        fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+            world.add_dyn_component(self.0, "Mana", mana(7)).unwrap();
+        }
+
+        fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    }
+
+    #[test]
+    fn test_dynamic_components_are_queried_and_replayed() {
+        let mut world = World::new();
+        world.register_schema(mana_schema());
+        let wizard = world.create_entity();
+        let full = world.create_entity();
+        assert!(world.add_dyn_component(wizard, "Stamina", mana(0)).is_err());
+        assert!(world.set_dyn_field(wizard, "Mana", "current", Value::Int(1)).is_err());
+        world.add_dyn_component(full, "Mana", mana(10)).unwrap();
+        assert_eq!(
+            world.dyn_component(full, "Mana").unwrap().to_string(),
+            "{current: 10, max: 10, regen: 1.0}"
+        );
+
+        world.add_startup_system(SummonSystem(wizard));
+        world.add_system(RegenSystem);
+        world.update();
+        world.update();
+        assert!(world.set_dyn_field(wizard, "Mana", "current", Value::Bool(true)).is_err());
+        assert_eq!(world.query_dyn(DynComponent("Mana")).len(), 2);
+        world.set_enabled(full, false);
+        assert_eq!(world.query_dyn(DynComponent("Mana")).len(), 1);
+
+        let mut log = Vec::new();
+        write_replay_log(world.get_update_history(), &mut log).unwrap();
+        let log = String::from_utf8(log).unwrap();
+        assert!(log.contains("MOD Entity(0, 0) Mana {current: 9}"), "{}", log);
+
+        let mut replayed = World::new();
+        replayed.register_schema(mana_schema());
+        replayed.create_entity();
+        replayed.create_entity();
+        for update in parse_replay_log_str(&log).updates() {
+            replayed.apply_update_diff(update);
+        }
+        assert_eq!(replayed.dyn_component(wizard, "Mana"), world.dyn_component(wizard, "Mana"));
+        assert_eq!(replayed.dyn_component(full, "Mana"), None);
+    }
+}
@@ -0,0 +1,245 @@
+//! Access to the components of one entity without repeating the entity.
+//!
+//! ```ignore
+//! let health = world.entity(actor).get::<Health>();
+//! world.entity_mut(actor).insert(Poison { damage: 1 }).remove::<Shield>();
+//!
+//! // In a system, changes made through the view are recorded
+//! let mut actor = world_view.entity(actor);
+//! actor.insert(Burning);
+//! actor.modify::<Health>(|health| health.current -= 1);
+//! actor.despawn();
+//! ```
+//!
+//! The view of a system's `WorldView` records each change like the
+//! `record_*` methods would, so the system's diff, history and replay log
+//! contain them. Views of a `World` change it directly, like its own methods.
+
+use crate::{Diff, Entity, World, WorldView};
+use std::fmt::Debug;
+
+/// Read access to the components of one entity of a world
+pub struct EntityView<'w> {
+    world: &'w World,
+    entity: Entity,
+}
+
+impl<'w> EntityView<'w> {
+    pub fn entity(&self) -> Entity {
+        self.entity
+    }
+
+    /// Check if the entity exists
+    pub fn exists(&self) -> bool {
+        self.world.entity_exists(self.entity)
+    }
+
+    /// Get a component of the entity
+    pub fn get<T: 'static>(&self) -> Option<&'w T> {
+        self.world.get_component::<T>(self.entity)
+    }
+
+    /// Check if the entity has a component of type T
+    pub fn has<T: 'static>(&self) -> bool {
+        self.get::<T>().is_some()
+    }
+}
+
+/// Changes the components of one entity of a `World`, or of a system's
+/// `WorldView` recording the changes
+pub struct EntityViewMut<'w, W = World> {
+    world: &'w mut W,
+    entity: Entity,
+}
+
+impl<'w> EntityViewMut<'w, World> {
+    pub fn entity(&self) -> Entity {
+        self.entity
+    }
+
+    /// Get a component of the entity
+    pub fn get<T: 'static>(&self) -> Option<&T> {
+        self.world.get_component::<T>(self.entity)
+    }
+
+    /// Check if the entity has a component of type T
+    pub fn has<T: 'static>(&self) -> bool {
+        self.get::<T>().is_some()
+    }
+
+    /// Get a mutable component of the entity, marking it as changed
+    pub fn get_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        self.world.get_component_mut::<T>(self.entity)
+    }
+
+    /// Add a component to the entity, replacing one of the same type
+    pub fn insert<T: 'static>(&mut self, component: T) -> &mut Self {
+        self.world.add_component(self.entity, component);
+        self
+    }
+
+    /// Remove a component from the entity
+    pub fn remove<T: 'static>(&mut self) -> Option<T> {
+        self.world.remove_component::<T>(self.entity)
+    }
+
+    /// Remove the entity and its components, returning false if it did not exist
+    pub fn despawn(self) -> bool {
+        self.world.remove_entity(self.entity)
+    }
+}
+
+impl<'w, I, O> EntityViewMut<'w, WorldView<I, O>> {
+    pub fn entity(&self) -> Entity {
+        self.entity
+    }
+
+    /// Get a component of the entity
+    pub fn get<T: 'static>(&self) -> Option<&T> {
+        self.world.get_component::<T>(self.entity)
+    }
+
+    /// Check if the entity has a component of type T
+    pub fn has<T: 'static>(&self) -> bool {
+        self.get::<T>().is_some()
+    }
+
+    /// Add a component to the entity, replacing one of the same type, and
+    /// record the addition
+    pub fn insert<T: Debug + 'static>(&mut self, component: T) -> &mut Self {
+        self.world.record_component_addition(self.entity, &component);
+        self.world.add_component(self.entity, component);
+        self
+    }
+
+    /// Change a component of the entity and record the modification,
+    /// returning false if the entity has no component of type T
+    pub fn modify<T: Diff + Clone + Debug + 'static>(&mut self, change: impl FnOnce(&mut T)) -> bool {
+        let Some(component) = self.world.get_component_mut::<T>(self.entity) else {
+            return false;
+        };
+        let old = component.clone();
+        change(component);
+        let new = component.clone();
+        self.world.record_component_modification(self.entity, &old, &new);
+        true
+    }
+
+    /// Remove a component from the entity and record the removal
+    pub fn remove<T: 'static>(&mut self) -> Option<T> {
+        let removed = self.world.remove_component::<T>(self.entity)?;
+        self.world.record_component_removal::<T>(self.entity);
+        Some(removed)
+    }
+
+    /// Remove the entity and its components when the system's update ends,
+    /// recording the removal
+    pub fn despawn(self) {
+        self.world.commands().despawn(self.entity);
+    }
+}
+
+impl World {
+    /// Get read access to the components of an entity
+    pub fn entity(&self, entity: Entity) -> EntityView<'_> {
+        EntityView { world: self, entity }
+    }
+
+    /// Get access to change the components of an entity
+    pub fn entity_mut(&mut self, entity: Entity) -> EntityViewMut<'_> {
+        EntityViewMut { world: self, entity }
+    }
+}
+
+impl<I, O> WorldView<I, O> {
+    /// Get access to the components of an entity that records the changes made through it
+    pub fn entity(&mut self, entity: Entity) -> EntityViewMut<'_, Self> {
+        EntityViewMut { world: self, entity }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DiffComponentChange, System, WorldOperation};
+
+    #[derive(Debug, Clone, PartialEq, Diff)]
+    struct Health {
+        current: i32,
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Burning;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Shield(i32);
+
+    /// Burns the first actor down, despawning it at 0 health
+    struct FireSystem(Entity);
+
+    impl System for FireSystem {
+        type InComponents = ();
+        type OutComponents = (Health, Burning, Shield);
+
+        fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+        fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+            let mut actor = world.entity(self.0);
+            if !actor.has::<Burning>() {
+                actor.insert(Burning);
+                actor.remove::<Shield>();
+                return;
+            }
+            actor.modify::<Health>(|health| health.current -= 5);
+            if actor.get::<Health>().is_some_and(|health| health.current <= 0) {
+                actor.despawn();
+            }
+        }
+
+        fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    }
+
+    #[test]
+    fn test_world_entity_views() {
+        let mut world = World::new();
+        let actor = world.create_entity();
+        world.entity_mut(actor).insert(Health { current: 5 }).insert(Shield(2));
+        assert_eq!(world.entity(actor).get::<Health>(), Some(&Health { current: 5 }));
+        assert!(world.entity(actor).has::<Shield>());
+
+        let mut view = world.entity_mut(actor);
+        view.get_mut::<Health>().unwrap().current = 4;
+        assert_eq!(view.remove::<Shield>(), Some(Shield(2)));
+        assert!(view.despawn());
+        assert!(!world.entity(actor).exists());
+        assert!(!world.entity_mut(actor).despawn());
+    }
+
+    #[test]
+    fn test_system_changes_through_views_are_recorded() {
+        let mut world = World::new();
+        let actor = world.create_entity();
+        world.entity_mut(actor).insert(Health { current: 5 }).insert(Shield(1));
+        world.add_system(FireSystem(actor));
+
+        world.update();
+        let changes = |world: &World| {
+            let update = world.get_update_history().updates().last().unwrap();
+            update.system_diffs()[0].component_changes().to_vec()
+        };
+        assert!(matches!(
+            changes(&world).as_slice(),
+            [DiffComponentChange::Added { type_name: added, .. }, DiffComponentChange::Removed { type_name: removed, .. }]
+                if added.as_str() == "Burning" && removed.as_str() == "Shield"
+        ));
+
+        world.update();
+        assert!(matches!(
+            changes(&world).as_slice(),
+            [DiffComponentChange::Modified { type_name, .. }] if type_name.as_str() == "Health"
+        ));
+        assert!(!world.entity(actor).exists());
+        let update = world.get_update_history().updates().last().unwrap();
+        assert_eq!(update.system_diffs()[0].world_operations(), &[WorldOperation::RemoveEntity(actor)]);
+    }
+}
@@ -0,0 +1,204 @@
+//! Running many frames as fast as possible.
+//!
+//! Soak tests and replay verification run thousands of frames without anyone
+//! watching. `update_n` runs a number of frames in one call, optionally
+//! reporting progress every few frames:
+//!
+//! ```ignore
+//! world.set_fast_forward(true);
+//! world.update_n_with_progress(10_000, 1_000, |world, progress| {
+//!     println!("{}/{} frames, {:.0} fps", progress.completed, progress.total, progress.frames_per_second());
+//! });
+//! ```
+//!
+//! In fast-forward mode the systems of render groups are skipped like
+//! systems of a disabled group, and loops pacing the world, like the
+//! headless server and the game loop, run the next frame without sleeping.
+//! The "rendering" group is a render group unless `set_render_groups` says
+//! otherwise.
+
+use crate::World;
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+/// Fast-forward state of a world
+pub(crate) struct FastForward {
+    enabled: bool,
+    /// System groups skipped while fast-forwarding
+    render_groups: BTreeSet<String>,
+}
+
+impl Default for FastForward {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            render_groups: BTreeSet::from(["rendering".to_string()]),
+        }
+    }
+}
+
+/// Progress of `update_n_with_progress`, passed to its callback
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UpdateProgress {
+    /// Frames run so far by this call
+    pub completed: usize,
+    /// Frames the call runs in total
+    pub total: usize,
+    /// Time since the call started
+    pub elapsed: Duration,
+}
+
+impl UpdateProgress {
+    /// Get the average number of frames run per second so far
+    pub fn frames_per_second(&self) -> f64 {
+        if self.elapsed.is_zero() {
+            return 0.0;
+        }
+        self.completed as f64 / self.elapsed.as_secs_f64()
+    }
+
+    /// Check if all frames have run
+    pub fn is_finished(&self) -> bool {
+        self.completed == self.total
+    }
+}
+
+impl World {
+    /// Run `n` update frames
+    pub fn update_n(&mut self, n: usize) {
+        for _ in 0..n {
+            self.update();
+        }
+    }
+
+    /// Run `n` update frames, calling `progress` after every `every` frames
+    /// and after the last one
+    pub fn update_n_with_progress(
+        &mut self,
+        n: usize,
+        every: usize,
+        mut progress: impl FnMut(&World, &UpdateProgress),
+    ) {
+        let every = every.max(1);
+        let started = self.now();
+        for completed in 1..=n {
+            self.update();
+            if completed % every == 0 || completed == n {
+                let report = UpdateProgress {
+                    completed,
+                    total: n,
+                    elapsed: self.now().saturating_sub(started),
+                };
+                progress(self, &report);
+            }
+        }
+    }
+
+    /// Skip render groups and frame pacing, see the module documentation
+    pub fn set_fast_forward(&mut self, enabled: bool) {
+        self.fast_forward.enabled = enabled;
+    }
+
+    /// Check if the world is fast-forwarding
+    pub fn is_fast_forwarding(&self) -> bool {
+        self.fast_forward.enabled
+    }
+
+    /// Set the system groups skipped while fast-forwarding
+    pub fn set_render_groups<I, S>(&mut self, groups: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.fast_forward.render_groups = groups.into_iter().map(Into::into).collect();
+    }
+
+    /// Get the system groups skipped while fast-forwarding, in name order
+    pub fn render_groups(&self) -> Vec<&str> {
+        self.fast_forward.render_groups.iter().map(String::as_str).collect()
+    }
+
+    /// Check if the system at `system_index` is skipped by fast-forwarding
+    pub(crate) fn skipped_by_fast_forward(&self, system_index: usize) -> bool {
+        self.fast_forward.enabled
+            && self
+                .system_groups
+                .group_of(system_index)
+                .is_some_and(|group| self.fast_forward.render_groups.contains(group))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+    use crate::{System, WorldView};
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    /// Counts its updates
+    struct CountingSystem(Rc<Cell<usize>>);
+
+    impl System for CountingSystem {
+        type InComponents = ();
+        type OutComponents = ();
+
+        fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+        fn update(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+            self.0.set(self.0.get() + 1);
+        }
+
+        fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    }
+
+    #[test]
+    fn test_update_n_reports_progress() {
+        let mut world = World::new();
+        let clock = MockClock::new();
+        world.set_mock_clock(clock.clone());
+        let updates = Rc::new(Cell::new(0));
+        world.add_system(CountingSystem(updates.clone()));
+        world.update_n(3);
+        assert_eq!((world.frame_number(), updates.get()), (3, 3));
+
+        let mut reports = Vec::new();
+        world.update_n_with_progress(10, 4, |world, progress| {
+            clock.advance(Duration::from_secs(1));
+            reports.push((world.frame_number(), *progress));
+        });
+        let completed: Vec<(usize, usize)> = reports.iter().map(|(frame, progress)| (*frame, progress.completed)).collect();
+        assert_eq!(completed, vec![(7, 4), (11, 8), (13, 10)]);
+        assert!(reports.last().unwrap().1.is_finished());
+        // Two seconds passed before the third report
+        assert_eq!(reports[2].1.elapsed, Duration::from_secs(2));
+        assert_eq!(reports[2].1.frames_per_second(), 5.0);
+    }
+
+    #[test]
+    fn test_fast_forward_skips_render_groups() {
+        let mut world = World::new();
+        let simulated = Rc::new(Cell::new(0));
+        let rendered = Rc::new(Cell::new(0));
+        let drawn = Rc::new(Cell::new(0));
+        world.add_system_to_group("simulation", CountingSystem(simulated.clone()));
+        world.add_system_to_group("rendering", CountingSystem(rendered.clone()));
+        world.add_system(CountingSystem(drawn.clone()));
+
+        world.set_fast_forward(true);
+        assert!(world.is_fast_forwarding());
+        world.update_n(5);
+        assert_eq!((simulated.get(), rendered.get(), drawn.get()), (5, 0, 5));
+        // Skipped systems keep the system indices of the history aligned
+        assert_eq!(world.get_update_history().updates().last().unwrap().system_diffs().len(), 3);
+
+        world.set_render_groups(["simulation"]);
+        assert_eq!(world.render_groups(), vec!["simulation"]);
+        world.update();
+        assert_eq!((simulated.get(), rendered.get()), (5, 1));
+
+        world.set_fast_forward(false);
+        world.update();
+        assert_eq!((simulated.get(), rendered.get()), (6, 2));
+    }
+}
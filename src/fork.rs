@@ -0,0 +1,202 @@
+//! Independent copies of a world for simulating "what if" branches.
+//!
+//! ```ignore
+//! let mut branch = world.fork()?;
+//! branch.get_component_mut::<Target>(actor).unwrap().x = 9;
+//! for _ in 0..100 {
+//!     branch.update();
+//! }
+//! // `world` is untouched; compare the outcome with world.snapshot().diff(&branch.snapshot())
+//! ```
+//!
+//! A fork is built the way a replay rebuilds a world: components and
+//! resources are copied through their replay encoding and systems are
+//! created again by their registered factories, so the fork shares nothing
+//! mutable with the original. Forking fails if something could not be
+//! copied that way. Systems start from their factory's state, and
+//! observers, watches, invariants and replay logging are not copied. The
+//! fork's history starts with the forked state as its initial state.
+
+use crate::World;
+
+impl World {
+    /// Create an independent copy of the world's entities, components,
+    /// resources and systems that can be updated separately, see the module documentation
+    pub fn fork(&self) -> Result<World, String> {
+        self.check_forkable()
+            .map_err(|e| format!("Cannot fork world {}: {}", self.world_index, e))?;
+
+        let mut fork = World::new_with_index(self.world_index);
+        fork.component_registry = self.component_registry.clone();
+        fork.system_registry = self.system_registry.clone();
+        fork.prefabs = self.prefabs.clone();
+        fork.storages = self.storages.clone();
+        for schema in self.dynamic.schemas() {
+            fork.register_schema(schema.clone());
+        }
+
+        let snapshot = self.snapshot();
+        fork.restore_snapshot(&snapshot);
+        fork.next_entity_id = self.next_entity_id;
+        fork.frame_number = self.frame_number;
+        fork.disabled_systems = self.disabled_systems.clone();
+        fork.disable_failed_systems = self.disable_failed_systems;
+        fork.system_groups = self.system_groups.clone();
+        fork.world_update_history.set_metadata(self.world_update_history.metadata().clone());
+        fork.world_update_history.set_initial_state(snapshot);
+        Ok(fork)
+    }
+
+    /// Check that everything a fork needs can be copied, listing what cannot
+    fn check_forkable(&self) -> Result<(), String> {
+        let mut problems = Vec::new();
+        if self.frame_in_progress.is_some() {
+            problems.push("a frame is in progress".to_string());
+        }
+        let mut components: Vec<&str> = self
+            .components
+            .iter()
+            .filter(|(type_id, column)| {
+                !column.is_empty()
+                    && !self
+                        .component_registry
+                        .get_by_type_id(**type_id)
+                        .is_some_and(|registration| registration.is_replayable())
+            })
+            .map(|(type_id, _)| self.component_type_name(*type_id))
+            .collect();
+        if !components.is_empty() {
+            components.sort_unstable();
+            problems.push(format!("components {} are not registered for replay", components.join(", ")));
+        }
+        let resources = self
+            .resources
+            .keys()
+            .filter(|type_id| {
+                !self
+                    .component_registry
+                    .get_by_type_id(**type_id)
+                    .is_some_and(|registration| registration.is_replayable())
+            })
+            .count();
+        if resources > 0 {
+            problems.push(format!("{} resources are not registered for replay", resources));
+        }
+        let systems: Vec<&str> = self
+            .systems
+            .iter()
+            .map(|system| system.name())
+            .filter(|name| !self.system_registry.contains(name))
+            .collect();
+        if !systems.is_empty() {
+            problems.push(format!("systems {} have no registered factory", systems.join(", ")));
+        }
+        if !self.startup_systems.is_empty() {
+            problems.push(format!("{} startup systems have not run yet", self.startup_systems.len()));
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems.join("; "))
+        }
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use crate::{Diff, Entity, In, Out, Reflect, System, World, WorldView};
+
+    #[derive(Debug, Clone, Copy, PartialEq, Diff, Reflect, serde::Serialize, serde::Deserialize)]
+    struct Health {
+        current: i32,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Diff, Reflect, serde::Serialize, serde::Deserialize)]
+    struct Poison {
+        damage: i32,
+    }
+
+    /// Poisoned entities lose health every update
+    struct PoisonSystem;
+
+    impl System for PoisonSystem {
+        type InComponents = (Poison,);
+        type OutComponents = (Health,);
+
+        fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+        fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+            let mut changes = Vec::new();
+            for (entity, (health, poison)) in world.query_components::<(Out<Health>, In<Poison>)>() {
+                let old = *health;
+                health.current -= poison.damage;
+                changes.push((entity, old, *health));
+            }
+            for (entity, old, new) in changes {
+                world.record_component_modification(entity, &old, &new);
+            }
+        }
+
+        fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    }
+
+    struct IdleSystem;
+
+    impl System for IdleSystem {
+        type InComponents = ();
+        type OutComponents = ();
+
+        fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+        fn update(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+        fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    }
+
+    fn poisoned_world() -> (World, Entity) {
+        let mut world = World::new();
+        world.register_replay_component::<Health>();
+        world.register_replay_component::<Poison>();
+        world.register_system_factory(|| PoisonSystem);
+        world.add_system(PoisonSystem);
+        let entity = world.create_entity();
+        world.add_component(entity, Health { current: 10 });
+        world.add_component(entity, Poison { damage: 1 });
+        world.update();
+        (world, entity)
+    }
+
+    #[test]
+    fn test_forks_are_updated_separately() {
+        let (mut world, entity) = poisoned_world();
+        let mut branch = world.fork().unwrap();
+        assert!(world.snapshot().diff(&branch.snapshot()).is_empty());
+        assert_eq!(branch.frame_number(), world.frame_number());
+        assert!(branch.get_update_history().initial_state().is_some());
+
+        // What if the poison was stronger?
+        branch.get_component_mut::<Poison>(entity).unwrap().damage = 3;
+        branch.update();
+        branch.update();
+        world.update();
+
+        assert_eq!(branch.get_component::<Health>(entity), Some(&Health { current: 3 }));
+        assert_eq!(world.get_component::<Health>(entity), Some(&Health { current: 8 }));
+        assert_eq!(world.get_component::<Poison>(entity), Some(&Poison { damage: 1 }));
+        // Entities created in the fork continue the original's numbering
+        assert_eq!(branch.create_entity(), world.create_entity());
+    }
+
+    #[test]
+    fn test_fork_lists_what_cannot_be_copied() {
+        let (mut world, entity) = poisoned_world();
+        world.add_component(entity, 7u32);
+        world.insert_resource(3u8);
+        world.add_system(IdleSystem);
+        let Err(error) = world.fork() else {
+            panic!("forked a world with unregistered types");
+        };
+        assert_eq!(
+            error,
+            "Cannot fork world 0: components u32 are not registered for replay; 1 resources are not registered for replay; systems rust_ecs::fork::tests::IdleSystem have no registered factory"
+        );
+    }
+}
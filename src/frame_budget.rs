@@ -0,0 +1,114 @@
+//! Frame-budget watchdog.
+//!
+//! When a frame budget is configured, every system update is timed. Frames
+//! that exceed the budget produce a `SlowFrameRecord` with per-system timings,
+//! entity and component counts and the systems that produced the largest
+//! diffs. The record is kept on the world and written to the replay log.
+
+use crate::World;
+use std::time::Duration;
+
+/// Number of systems listed in `SlowFrameRecord::largest_diffs`
+const LARGEST_DIFF_COUNT: usize = 3;
+
+/// Time spent in one system update during a frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SystemTiming {
+    /// Type name of the system
+    pub system_name: String,
+    /// Wall-clock time spent in the system update
+    pub duration: Duration,
+    /// Number of component changes and world operations the system recorded
+    pub change_count: usize,
+}
+
+/// Diagnostic record of a frame that exceeded the frame budget
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlowFrameRecord {
+    /// Frame number of the slow frame
+    pub frame: usize,
+    /// Total time spent in system updates
+    pub duration: Duration,
+    /// Budget that was exceeded
+    pub budget: Duration,
+    /// Timing of every system, in execution order
+    pub system_timings: Vec<SystemTiming>,
+    /// Number of entities at the end of the frame
+    pub entity_count: usize,
+    /// Number of components (of all types) at the end of the frame
+    pub component_count: usize,
+    /// Systems with the most recorded changes, largest first
+    pub largest_diffs: Vec<SystemTiming>,
+}
+
+impl SlowFrameRecord {
+    fn new(world: &World, budget: Duration, system_timings: Vec<SystemTiming>) -> Self {
+        let duration = system_timings.iter().map(|timing| timing.duration).sum();
+
+        let mut largest_diffs: Vec<SystemTiming> = system_timings
+            .iter()
+            .filter(|timing| timing.change_count > 0)
+            .cloned()
+            .collect();
+        largest_diffs.sort_by_key(|timing| std::cmp::Reverse(timing.change_count));
+        largest_diffs.truncate(LARGEST_DIFF_COUNT);
+
+        Self {
+            frame: world.frame_number,
+            duration,
+            budget,
+            system_timings,
+            entity_count: world.entity_count(),
+            component_count: world.components.values().map(|components| components.len()).sum(),
+            largest_diffs,
+        }
+    }
+}
+
+impl World {
+    /// Set the time budget for one update; slower frames are captured as
+    /// `SlowFrameRecord`s. `None` disables the watchdog (the default).
+    pub fn set_frame_budget(&mut self, budget: Option<Duration>) {
+        self.frame_budget = budget;
+    }
+
+    /// Get the configured frame budget
+    pub fn frame_budget(&self) -> Option<Duration> {
+        self.frame_budget
+    }
+
+    /// Get the diagnostic records of all frames that exceeded the budget
+    pub fn slow_frames(&self) -> &[SlowFrameRecord] {
+        &self.slow_frames
+    }
+
+    /// Clear the recorded slow frames
+    pub fn clear_slow_frames(&mut self) {
+        self.slow_frames.clear();
+    }
+
+    /// Capture a slow-frame record if the finished frame exceeded the budget
+    pub(crate) fn check_frame_budget(&mut self, system_timings: Vec<SystemTiming>) {
+        let Some(budget) = self.frame_budget else {
+            return;
+        };
+        let total: Duration = system_timings.iter().map(|timing| timing.duration).sum();
+        if total <= budget {
+            return;
+        }
+
+        let record = SlowFrameRecord::new(self, budget, system_timings);
+        ecs_warn!(
+            "Frame {} took {:?}, exceeding the frame budget of {:?}",
+            record.frame,
+            record.duration,
+            record.budget
+        );
+        if let Some(ref mut logger) = self.replay_logger {
+            if let Err(e) = logger.log_slow_frame(&record) {
+                ecs_error!("Failed to log slow frame: {}", e);
+            }
+        }
+        self.slow_frames.push(record);
+    }
+}
@@ -0,0 +1,520 @@
+//! Fighting between teams, with damage and death events.
+//!
+//! Fighters of different `Team`s are hostile. The `CombatSystem` moves every
+//! fighter towards its nearest foe and, once a foe is adjacent, attacks it
+//! with its `Attack` every `cooldown` ticks, taking `Health`. Each hit is
+//! reported as a `DamageEvent` and each kill as a `DeathEvent` in the
+//! `CombatEvents` resource, which holds the events of the current frame.
+//!
+//! ```ignore
+//! world.add_plugin(CombatPlugin::default());
+//! world.update();
+//! for death in &world.resource::<CombatEvents>().unwrap().deaths {
+//!     println!("{} was killed by {}", death.entity, death.killer);
+//! }
+//! ```
+//!
+//! The `CorpseSystem` reacts to the death events: the dead stop acting and
+//! lie as a `Corpse` until it decays, when the corpse is despawned through
+//! the system's command buffer together with the `Weapon` attached to it.
+
+use super::game::{add_recorded, is_adjacent, is_valid_position, run_demo_loop, Actor, NavigationPlugin, Position, Target, GRID_SIZE};
+use crate::{Diff, Entity, In, Parent, Plugin, ReadOnlySystem, ReadOnlyWorldView, Reflect, System, World, WorldView};
+
+/// Ticks a corpse lies before it is removed
+pub const CORPSE_TICKS: u32 = 5;
+
+/// Hit points of a fighter; it dies when they reach 0
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Diff, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Health {
+    pub current: u32,
+    pub max: u32,
+}
+
+impl Health {
+    pub fn new(max: u32) -> Self {
+        Self { current: max, max }
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.current > 0
+    }
+}
+
+/// Damage dealt to an adjacent foe every `cooldown` ticks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Diff, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Attack {
+    pub damage: u32,
+    pub cooldown: u32,
+    /// Ticks until the next attack
+    pub ticks: u32,
+}
+
+impl Attack {
+    pub fn new(damage: u32, cooldown: u32) -> Self {
+        Self {
+            damage,
+            cooldown,
+            ticks: 0,
+        }
+    }
+}
+
+/// Side a fighter is on; fighters of other teams are hostile
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Diff, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Team {
+    pub id: u32,
+}
+
+/// Weapon carried by a fighter, attached to it with a `Parent` component
+#[derive(Debug, Clone, PartialEq, Eq, Diff, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Weapon {
+    pub name: String,
+}
+
+/// A dead fighter, removed once `ticks` reach 0
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Diff, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Corpse {
+    pub ticks: u32,
+}
+
+/// `attacker` hit `target` for `amount` health
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DamageEvent {
+    pub attacker: Entity,
+    pub target: Entity,
+    pub amount: u32,
+}
+
+/// `entity` was killed by `killer`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeathEvent {
+    pub entity: Entity,
+    pub killer: Entity,
+}
+
+/// Combat events of the current frame, replaced by the `CombatSystem` every update
+#[derive(Debug, Clone, Default)]
+pub struct CombatEvents {
+    pub damage: Vec<DamageEvent>,
+    pub deaths: Vec<DeathEvent>,
+}
+
+/// Fighter as seen by the CombatSystem during one update
+struct Fighter {
+    entity: Entity,
+    position: (i32, i32),
+    team: u32,
+    health: Health,
+    attack: Attack,
+    target: Target,
+}
+
+/// Moves fighters towards their foes and resolves the attacks of adjacent ones
+pub struct CombatSystem;
+
+impl System for CombatSystem {
+    type InComponents = (Position, Team, Health, Attack, Target, Actor);
+    type OutComponents = (Health, Attack, Target);
+
+    fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+    fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+        let mut fighters: Vec<Fighter> = world
+            .query_components::<(In<Position>, In<Team>, In<Health>, In<Attack>, In<Target>, In<Actor>)>()
+            .into_iter()
+            .map(|(entity, (position, team, health, attack, target, _))| Fighter {
+                entity,
+                position: (position.x, position.y),
+                team: team.id,
+                health: *health,
+                attack: *attack,
+                target: *target,
+            })
+            .collect();
+        let before: Vec<(Health, Attack, Target)> =
+            fighters.iter().map(|fighter| (fighter.health, fighter.attack, fighter.target)).collect();
+        let mut events = CombatEvents::default();
+
+        for index in 0..fighters.len() {
+            if !fighters[index].health.is_alive() {
+                continue;
+            }
+            let fighter = &fighters[index];
+            let foes = fighters
+                .iter()
+                .enumerate()
+                .filter(|(_, other)| other.team != fighter.team && other.health.is_alive());
+            // Attack the weakest adjacent foe, or head for the nearest one
+            let adjacent = foes
+                .clone()
+                .filter(|(_, foe)| is_adjacent(fighter.position, foe.position))
+                .min_by_key(|(_, foe)| foe.health.current)
+                .map(|(foe, _)| foe);
+            let nearest = foes
+                .min_by_key(|(_, foe)| {
+                    (foe.position.0 - fighter.position.0).abs().max((foe.position.1 - fighter.position.1).abs())
+                })
+                .map(|(_, foe)| foe.position);
+
+            let fighter = &mut fighters[index];
+            fighter.attack.ticks = fighter.attack.ticks.saturating_sub(1);
+            if let Some((x, y)) = nearest {
+                fighter.target = Target { x, y };
+            }
+            let Some(foe) = adjacent else {
+                continue;
+            };
+            if fighter.attack.ticks > 0 {
+                continue;
+            }
+            fighter.attack.ticks = fighter.attack.cooldown;
+            let (attacker, damage) = (fighter.entity, fighter.attack.damage);
+            let foe = &mut fighters[foe];
+            let amount = damage.min(foe.health.current);
+            foe.health.current -= amount;
+            events.damage.push(DamageEvent {
+                attacker,
+                target: foe.entity,
+                amount,
+            });
+            if !foe.health.is_alive() {
+                events.deaths.push(DeathEvent {
+                    entity: foe.entity,
+                    killer: attacker,
+                });
+            }
+        }
+
+        for (fighter, (health, attack, target)) in fighters.iter().zip(before) {
+            write(world, fighter.entity, health, fighter.health);
+            write(world, fighter.entity, attack, fighter.attack);
+            write(world, fighter.entity, target, fighter.target);
+        }
+        if let Some(combat_events) = world.resource_mut::<CombatEvents>() {
+            *combat_events = events;
+        }
+    }
+
+    fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+}
+
+/// Replace a component of an entity if it changed, recording the modification
+fn write<T, I, O>(world: &mut WorldView<I, O>, entity: Entity, old: T, new: T)
+where
+    T: Diff + Clone + PartialEq + std::fmt::Debug + 'static,
+{
+    if old == new {
+        return;
+    }
+    if let Some(component) = world.get_component_mut::<T>(entity) {
+        *component = new.clone();
+        world.record_component_modification(entity, &old, &new);
+    }
+}
+
+/// Turns the fighters killed this frame into corpses and despawns decayed
+/// corpses with everything attached to them
+pub struct CorpseSystem;
+
+impl System for CorpseSystem {
+    type InComponents = (Corpse,);
+    type OutComponents = (Corpse, Attack, Actor);
+
+    fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+    fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+        let corpses: Vec<(Entity, Corpse)> = world
+            .query_components::<(In<Corpse>,)>()
+            .into_iter()
+            .map(|(entity, corpse)| (entity, *corpse))
+            .collect();
+        for (entity, corpse) in corpses {
+            if corpse.ticks <= 1 {
+                ecs_info!("Corpse {} removed", entity);
+                world.commands().despawn_recursive(entity);
+            } else {
+                write(world, entity, corpse, Corpse { ticks: corpse.ticks - 1 });
+            }
+        }
+
+        let deaths = world
+            .resource::<CombatEvents>()
+            .map(|events| events.deaths.clone())
+            .unwrap_or_default();
+        for death in deaths {
+            ecs_info!("{} was killed by {}", death.entity, death.killer);
+            // The dead neither move nor fight
+            world.remove_component::<Actor>(death.entity);
+            world.record_component_removal::<Actor>(death.entity);
+            world.remove_component::<Attack>(death.entity);
+            world.record_component_removal::<Attack>(death.entity);
+            add_recorded(world, death.entity, Corpse { ticks: CORPSE_TICKS });
+        }
+    }
+
+    fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+}
+
+/// Draws the battlefield and the events of the frame
+pub struct CombatRenderSystem;
+
+impl ReadOnlySystem for CombatRenderSystem {
+    type InComponents = (Position, Team, Health, Corpse);
+
+    fn update(&mut self, world: &ReadOnlyWorldView<Self::InComponents>) {
+        // Clear screen
+        print!("\x1B[2J\x1B[1;1H");
+
+        println!("Skirmish - teams fighting until one is left");
+        println!("0-9 = Fighter of that team, x = Corpse");
+        println!();
+        for row in battlefield_grid(world) {
+            println!("{}", row);
+        }
+        println!();
+        if let Some(events) = world.resource::<CombatEvents>() {
+            for hit in &events.damage {
+                println!("{} hits {} for {}", hit.attacker, hit.target, hit.amount);
+            }
+            for death in &events.deaths {
+                println!("{} was killed by {}", death.entity, death.killer);
+            }
+        }
+    }
+}
+
+/// Render the battlefield from the components currently in the world, one
+/// string per row
+pub fn render_battlefield(world: &World) -> Vec<String> {
+    battlefield_grid(&ReadOnlyWorldView::<()>::new(world))
+}
+
+fn battlefield_grid<I>(world: &ReadOnlyWorldView<I>) -> Vec<String> {
+    let mut grid = vec![vec!['.'; GRID_SIZE as usize]; GRID_SIZE as usize];
+    for entity in world.entities_with_component::<Position>() {
+        let Some(position) = world.get_component::<Position>(entity) else {
+            continue;
+        };
+        let symbol = if world.get_component::<Corpse>(entity).is_some() {
+            'x'
+        } else if let Some(team) = world.get_component::<Team>(entity) {
+            char::from_digit(team.id % 10, 10).unwrap_or('?')
+        } else {
+            'A'
+        };
+        if is_valid_position((position.x, position.y)) {
+            grid[position.y as usize][position.x as usize] = symbol;
+        }
+    }
+    grid.iter()
+        .map(|row| row.iter().map(|cell| format!("{} ", cell)).collect::<String>().trim_end().to_string())
+        .collect()
+}
+
+/// Get the teams with living fighters, in team order
+pub fn surviving_teams(world: &World) -> Vec<u32> {
+    let mut teams: Vec<u32> = world
+        .entities_with_component::<Team>()
+        .into_iter()
+        .filter(|entity| world.get_component::<Health>(*entity).is_some_and(Health::is_alive))
+        .filter_map(|entity| world.get_component::<Team>(entity).map(|team| team.id))
+        .collect();
+    teams.sort_unstable();
+    teams.dedup();
+    teams
+}
+
+/// Register the combat components for replay
+pub fn register_combat_components(world: &mut World) {
+    world.register_replay_component::<Health>();
+    world.register_replay_component::<Attack>();
+    world.register_replay_component::<Team>();
+    world.register_replay_component::<Weapon>();
+    world.register_replay_component::<Corpse>();
+}
+
+/// A fighter spawned by the `CombatPlugin`
+#[derive(Debug, Clone)]
+pub struct FighterSpec {
+    pub team: u32,
+    pub position: (i32, i32),
+    pub health: u32,
+    pub attack: Attack,
+    pub weapon: String,
+}
+
+impl FighterSpec {
+    pub fn new(team: u32, position: (i32, i32), weapon: &str) -> Self {
+        Self {
+            team,
+            position,
+            health: 10,
+            attack: Attack::new(3, 2),
+            weapon: weapon.to_string(),
+        }
+    }
+
+    pub fn health(mut self, health: u32) -> Self {
+        self.health = health;
+        self
+    }
+
+    pub fn attack(mut self, damage: u32, cooldown: u32) -> Self {
+        self.attack = Attack::new(damage, cooldown);
+        self
+    }
+}
+
+/// Spawns fighters with their weapons and adds the combat systems, with the
+/// shared `NavigationPlugin` moving the fighters
+pub struct CombatPlugin {
+    pub fighters: Vec<FighterSpec>,
+    /// Add the CombatRenderSystem drawing the battlefield every frame, in the "rendering" group
+    pub render: bool,
+}
+
+impl Default for CombatPlugin {
+    /// Three swordsmen facing two axemen hitting harder but slower
+    fn default() -> Self {
+        Self {
+            fighters: vec![
+                FighterSpec::new(1, (2, 3), "sword"),
+                FighterSpec::new(1, (2, 5), "sword"),
+                FighterSpec::new(1, (2, 7), "sword"),
+                FighterSpec::new(2, (7, 4), "axe").health(14).attack(5, 3),
+                FighterSpec::new(2, (7, 6), "axe").health(14).attack(5, 3),
+            ],
+            render: true,
+        }
+    }
+}
+
+impl Plugin for CombatPlugin {
+    fn build(&self, world: &mut World) {
+        register_combat_components(world);
+        world.insert_resource(CombatEvents::default());
+
+        for spec in &self.fighters {
+            let (x, y) = spec.position;
+            let fighter = world.create_entity();
+            world.add_component(fighter, Position { x, y });
+            world.add_component(fighter, Target { x, y });
+            world.add_component(fighter, Actor);
+            world.add_component(fighter, Team { id: spec.team });
+            world.add_component(fighter, Health::new(spec.health));
+            world.add_component(fighter, spec.attack);
+            let weapon = world.create_entity();
+            world.add_component(weapon, Weapon { name: spec.weapon.clone() });
+            world.add_component(weapon, Parent { entity: fighter });
+        }
+
+        world.add_plugin(NavigationPlugin);
+        world.register_system_factory(|| CombatSystem);
+        world.register_system_factory(|| CorpseSystem);
+        world.add_system(CombatSystem);
+        world.add_system(CorpseSystem);
+        if self.render {
+            world.add_system_to_group("rendering", CombatRenderSystem);
+        }
+    }
+}
+
+/// Run the combat demo at 2 ticks per second, logging the session to `game_logs/`
+pub fn run_combat_game() {
+    println!("Starting Skirmish...");
+    println!("Two teams fight until one is left");
+    println!("Press Ctrl+C to stop the simulation");
+
+    let mut world = World::new();
+    world.add_plugin(CombatPlugin::default());
+    world.initialize_systems();
+    run_demo_loop(&mut world, "combat");
+    println!("Skirmish stopped after {} updates with teams {:?} standing", world.frame_number(), surviving_teams(&world));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::WorldOperation;
+
+    fn battle(fighters: Vec<FighterSpec>) -> World {
+        let mut world = World::new();
+        world.set_strict_access(true);
+        world.add_plugin(CombatPlugin { fighters, render: false });
+        world
+    }
+
+    #[test]
+    fn test_adjacent_foes_fight_until_one_dies() {
+        let mut world = battle(vec![
+            FighterSpec::new(1, (3, 3), "sword").health(4),
+            FighterSpec::new(2, (4, 3), "club").health(6).attack(2, 1),
+        ]);
+        let swordsman = world.entities_with_component::<Team>()[0];
+        let brute = world.entities_with_component::<Team>()[1];
+        let club = world.children(brute)[0];
+
+        world.update();
+        let events = world.resource::<CombatEvents>().unwrap().clone();
+        assert_eq!(
+            events.damage,
+            vec![
+                DamageEvent { attacker: swordsman, target: brute, amount: 3 },
+                DamageEvent { attacker: brute, target: swordsman, amount: 2 },
+            ]
+        );
+        assert!(events.deaths.is_empty());
+
+        // The brute attacks every tick, the swordsman every other one
+        world.update();
+        let events = world.resource::<CombatEvents>().unwrap().clone();
+        assert_eq!(events.deaths, vec![DeathEvent { entity: swordsman, killer: brute }]);
+        assert_eq!(world.get_component::<Corpse>(swordsman), Some(&Corpse { ticks: CORPSE_TICKS }));
+        assert!(world.get_component::<Actor>(swordsman).is_none());
+        assert_eq!(surviving_teams(&world), vec![2]);
+        assert_eq!(world.get_component::<Health>(brute), Some(&Health { current: 3, max: 6 }));
+
+        // The corpse decays and is removed with its sword, the brute keeps its club
+        let sword = world.entities_with_component::<Weapon>()[0];
+        world.update_n(CORPSE_TICKS as usize);
+        assert!(!world.entity_exists(swordsman) && !world.entity_exists(sword));
+        assert!(world.entity_exists(club));
+        let removals: Vec<&WorldOperation> = world
+            .get_update_history()
+            .updates()
+            .iter()
+            .flat_map(|update| update.system_diffs().iter().flat_map(|diff| diff.world_operations()))
+            .filter(|operation| matches!(operation, WorldOperation::RemoveEntity(_)))
+            .collect();
+        assert_eq!(
+            removals,
+            vec![&WorldOperation::RemoveEntity(swordsman), &WorldOperation::RemoveEntity(sword)]
+        );
+    }
+
+    #[test]
+    fn test_skirmish_replays_from_the_log() {
+        let mut world = battle(CombatPlugin::default().fighters);
+        let initial_state = world.snapshot();
+        world.reset_history();
+        world.update_n(60);
+        assert_eq!(surviving_teams(&world).len(), 1, "{:?}", render_battlefield(&world));
+        assert_eq!(world.entities_with_component::<Corpse>(), Vec::<Entity>::new());
+        let mut history = world.get_update_history().clone();
+        history.set_initial_state(initial_state);
+
+        let mut replayed = super::super::world_from_replay(&history).unwrap();
+        for update in history.updates() {
+            replayed.apply_update_diff(update);
+        }
+        assert_eq!(render_battlefield(&replayed), render_battlefield(&world));
+        assert_eq!(replayed.entity_count(), world.entity_count());
+        assert_eq!(surviving_teams(&replayed), surviving_teams(&world));
+    }
+}
@@ -0,0 +1,689 @@
+//! Construction of buildings by builders carrying resources.
+//!
+//! A construction site is placed on a free cell with
+//! `place_construction_site`. Builders fetch the resources its `Blueprint`
+//! costs from the stockpile, deliver them into the site's `Inventory` and then
+//! work on the site. After the blueprint's work ticks the site becomes the
+//! building: its `ConstructionSite` and `Inventory` are replaced by a `Home`,
+//! `Work` or `Hut` component.
+//!
+//! ```ignore
+//! world.add_plugin(ConstructionPlugin::default());
+//! place_construction_site(&mut world, BuildingKind::Hut, (3, 3))?;
+//! ```
+//!
+//! Completed buildings are reported by an observer of `ConstructionSite`
+//! removals into the `CompletedBuildings` resource.
+
+use super::game::{
+    add_recorded, free_neighbour, is_adjacent, is_valid_position, run_demo_loop, Actor, Home, NavigationPlugin, Obstacle,
+    Position, Target, Work, GRID_SIZE, HOME_POS, WORK_POS,
+};
+use crate::{Diff, Entity, In, Plugin, ReadOnlySystem, ReadOnlyWorldView, Reflect, System, World, WorldView};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+/// Most units of resources a builder carries at once
+const CARRY_CAPACITY: u32 = 2;
+
+/// Resources by name, carried by builders, stored in the stockpile or
+/// delivered to construction sites
+#[derive(Debug, Clone, PartialEq, Eq, Default, Diff, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Inventory {
+    pub items: BTreeMap<String, u32>,
+}
+
+impl Inventory {
+    pub fn with(mut self, item: &str, count: u32) -> Self {
+        self.add(item, count);
+        self
+    }
+
+    /// Get the number of units of an item
+    pub fn count(&self, item: &str) -> u32 {
+        self.items.get(item).copied().unwrap_or(0)
+    }
+
+    pub fn add(&mut self, item: &str, count: u32) {
+        if count > 0 {
+            *self.items.entry(item.to_string()).or_insert(0) += count;
+        }
+    }
+
+    /// Take up to `count` units of an item, returning the number taken
+    pub fn take(&mut self, item: &str, count: u32) -> u32 {
+        let available = self.count(item);
+        let taken = available.min(count);
+        if taken == available {
+            self.items.remove(item);
+        } else if let Some(units) = self.items.get_mut(item) {
+            *units -= taken;
+        }
+        taken
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+}
+
+/// Building a construction site turns into
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Diff, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BuildingKind {
+    #[default]
+    Hut,
+    Home,
+    Work,
+}
+
+/// A small building finished by builders
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Diff, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Hut;
+
+/// A building under construction; delivered resources are kept in the
+/// site's Inventory
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Diff, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConstructionSite {
+    pub building: BuildingKind,
+    /// Work ticks done once all resources were delivered
+    pub work: u32,
+}
+
+/// An actor carrying resources to construction sites and working on them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Diff, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Builder;
+
+/// The Inventory builders fetch resources from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Diff, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Stockpile;
+
+/// What a building costs: resources to deliver and work ticks after that
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Blueprint {
+    pub building: BuildingKind,
+    pub cost: BTreeMap<String, u32>,
+    pub work_ticks: u32,
+}
+
+impl Blueprint {
+    pub fn new(building: BuildingKind) -> Self {
+        Self {
+            building,
+            cost: BTreeMap::new(),
+            work_ticks: 0,
+        }
+    }
+
+    /// Require `count` units of `item`
+    pub fn cost(mut self, item: &str, count: u32) -> Self {
+        self.cost.insert(item.to_string(), count);
+        self
+    }
+
+    pub fn work_ticks(mut self, ticks: u32) -> Self {
+        self.work_ticks = ticks;
+        self
+    }
+
+    /// Get the resources still missing from a site's delivered resources
+    pub fn missing(&self, delivered: &Inventory) -> Inventory {
+        let mut missing = Inventory::default();
+        for (item, count) in &self.cost {
+            missing.add(item, count.saturating_sub(delivered.count(item)));
+        }
+        missing
+    }
+}
+
+/// Blueprints of the hut, the home and the work place
+pub fn village_blueprints() -> Vec<Blueprint> {
+    vec![
+        Blueprint::new(BuildingKind::Hut).cost("wood", 3).cost("stone", 1).work_ticks(4),
+        Blueprint::new(BuildingKind::Home).cost("wood", 4).cost("stone", 2).work_ticks(6),
+        Blueprint::new(BuildingKind::Work).cost("wood", 5).cost("stone", 3).work_ticks(8),
+    ]
+}
+
+/// Buildings completed so far, in completion order
+#[derive(Debug, Clone, Default)]
+pub struct CompletedBuildings(Rc<RefCell<Vec<(Entity, BuildingKind)>>>);
+
+impl CompletedBuildings {
+    pub fn list(&self) -> Vec<(Entity, BuildingKind)> {
+        self.0.borrow().clone()
+    }
+}
+
+/// Place a construction site on a free cell of the grid
+pub fn place_construction_site(world: &mut World, building: BuildingKind, position: (i32, i32)) -> Result<Entity, String> {
+    if !is_valid_position(position) {
+        return Err(format!("{:?} is outside the grid", position));
+    }
+    let occupied = position == HOME_POS
+        || position == WORK_POS
+        || world
+            .entities_with_component::<Position>()
+            .into_iter()
+            .filter_map(|entity| world.get_component::<Position>(entity))
+            .any(|other| (other.x, other.y) == position);
+    if occupied {
+        return Err(format!("{:?} is occupied", position));
+    }
+    let site = world.create_entity();
+    world.add_component(site, Position { x: position.0, y: position.1 });
+    world.add_component(site, ConstructionSite { building, work: 0 });
+    world.add_component(site, Inventory::default());
+    world.add_component(site, Obstacle);
+    Ok(site)
+}
+
+/// Construction site as seen by the BuildingSystem during one update
+struct SiteState {
+    entity: Entity,
+    position: (i32, i32),
+    site: ConstructionSite,
+    delivered: Inventory,
+    blueprint: Blueprint,
+}
+
+impl SiteState {
+    fn missing(&self) -> Inventory {
+        self.blueprint.missing(&self.delivered)
+    }
+
+    fn is_supplied(&self) -> bool {
+        self.missing().is_empty()
+    }
+
+    fn is_finished(&self) -> bool {
+        self.is_supplied() && self.site.work >= self.blueprint.work_ticks
+    }
+}
+
+/// Moves builders between the stockpile and construction sites, delivers
+/// resources, works on supplied sites and turns finished sites into buildings
+pub struct BuildingSystem {
+    blueprints: Vec<Blueprint>,
+}
+
+impl BuildingSystem {
+    pub fn new(blueprints: Vec<Blueprint>) -> Self {
+        Self { blueprints }
+    }
+}
+
+impl Default for BuildingSystem {
+    fn default() -> Self {
+        Self::new(village_blueprints())
+    }
+}
+
+impl System for BuildingSystem {
+    type InComponents = (Position, Builder, Inventory, ConstructionSite, Stockpile, Target);
+    type OutComponents = (Inventory, ConstructionSite, Target, Hut, Home, Work, Position, Actor, Builder);
+
+    fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+    fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+        let mut occupied: Vec<(i32, i32)> = world
+            .query_components::<(In<Position>,)>()
+            .into_iter()
+            .map(|(_, position)| (position.x, position.y))
+            .collect();
+        let mut sites: Vec<SiteState> = world
+            .query_components::<(In<Position>, In<ConstructionSite>, In<Inventory>)>()
+            .into_iter()
+            .filter_map(|(entity, (position, site, delivered))| {
+                let blueprint = self.blueprints.iter().find(|blueprint| blueprint.building == site.building)?;
+                Some(SiteState {
+                    entity,
+                    position: (position.x, position.y),
+                    site: *site,
+                    delivered: delivered.clone(),
+                    blueprint: blueprint.clone(),
+                })
+            })
+            .collect();
+        let mut stockpile = world
+            .query_components::<(In<Position>, In<Stockpile>, In<Inventory>)>()
+            .into_iter()
+            .next()
+            .map(|(entity, (position, _, stock))| (entity, (position.x, position.y), stock.clone()));
+        let mut builders: Vec<(Entity, (i32, i32), Inventory, Target)> = world
+            .query_components::<(In<Position>, In<Builder>, In<Inventory>, In<Target>)>()
+            .into_iter()
+            .map(|(entity, (position, _, carried, target))| (entity, (position.x, position.y), carried.clone(), *target))
+            .collect();
+
+        for index in 0..builders.len() {
+            let (_, position, carried, _) = builders[index].clone();
+            // Resources on their way to sites are no longer missing
+            let mut in_transit = Inventory::default();
+            for (_, _, other, _) in &builders {
+                for (item, count) in &other.items {
+                    in_transit.add(item, *count);
+                }
+            }
+            let destination = if !carried.is_empty() {
+                // Deliver to the first site missing what the builder carries,
+                // or bring it back to the stockpile
+                let site = sites
+                    .iter_mut()
+                    .find(|site| carried.items.keys().any(|item| site.missing().count(item) > 0));
+                match (site, &mut stockpile) {
+                    (Some(site), _) => {
+                        if is_adjacent(position, site.position) {
+                            let missing = site.missing();
+                            let carried = &mut builders[index].2;
+                            for (item, count) in &missing.items {
+                                let delivered = carried.take(item, *count);
+                                site.delivered.add(item, delivered);
+                            }
+                        }
+                        Some(site.position)
+                    }
+                    (None, Some((_, stockpile_position, stock))) => {
+                        if is_adjacent(position, *stockpile_position) {
+                            for (item, count) in std::mem::take(&mut builders[index].2.items) {
+                                stock.add(&item, count);
+                            }
+                        }
+                        Some(*stockpile_position)
+                    }
+                    (None, None) => None,
+                }
+            } else {
+                // Fetch the first missing resource the stockpile has
+                let mut needed = Inventory::default();
+                for site in &sites {
+                    for (item, count) in &site.missing().items {
+                        needed.add(item, *count);
+                    }
+                }
+                let fetch = stockpile.as_ref().and_then(|(_, _, stock)| {
+                    needed.items.iter().find_map(|(item, count)| {
+                        let wanted = count.saturating_sub(in_transit.count(item)).min(stock.count(item));
+                        (wanted > 0).then(|| (item.clone(), wanted.min(CARRY_CAPACITY)))
+                    })
+                });
+                match (fetch, &mut stockpile) {
+                    (Some((item, count)), Some((_, stockpile_position, stock))) => {
+                        if is_adjacent(position, *stockpile_position) {
+                            let taken = stock.take(&item, count);
+                            builders[index].2.add(&item, taken);
+                        }
+                        Some(*stockpile_position)
+                    }
+                    // Otherwise work on the first supplied site
+                    _ => sites.iter_mut().find(|site| site.is_supplied() && !site.is_finished()).map(|site| {
+                        if is_adjacent(position, site.position) {
+                            site.site.work += 1;
+                        }
+                        site.position
+                    }),
+                }
+            };
+            if let Some((x, y)) = destination {
+                builders[index].3 = Target { x, y };
+            }
+        }
+
+        // Write back the changed inventories, sites and targets
+        for (entity, _, carried, target) in builders {
+            self.write(world, entity, carried);
+            self.write(world, entity, target);
+        }
+        if let Some((entity, _, stock)) = stockpile {
+            self.write(world, entity, stock);
+        }
+        for state in sites {
+            if !state.is_finished() {
+                self.write(world, state.entity, state.delivered);
+                self.write(world, state.entity, state.site);
+                continue;
+            }
+            // The site becomes the building
+            world.remove_component::<ConstructionSite>(state.entity);
+            world.record_component_removal::<ConstructionSite>(state.entity);
+            world.remove_component::<Inventory>(state.entity);
+            world.record_component_removal::<Inventory>(state.entity);
+            match state.site.building {
+                BuildingKind::Hut => {
+                    add_recorded(world, state.entity, Hut);
+                    // Every hut houses a new builder
+                    if let Some(position) = free_neighbour(&occupied, state.position) {
+                        occupied.push(position);
+                        spawn_builder(world, position);
+                    }
+                }
+                BuildingKind::Home => add_recorded(world, state.entity, Home),
+                BuildingKind::Work => add_recorded(world, state.entity, Work),
+            }
+        }
+    }
+
+    fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+}
+
+impl BuildingSystem {
+    /// Write a component of an entity, recording the modification if it changed
+    fn write<T, I, O>(&self, world: &mut WorldView<I, O>, entity: Entity, value: T)
+    where
+        T: Diff + Clone + std::fmt::Debug + 'static,
+    {
+        let Some(component) = world.get_component_mut::<T>(entity) else {
+            return;
+        };
+        if component.diff(&value).is_none() {
+            return;
+        }
+        let old = std::mem::replace(component, value.clone());
+        world.record_component_modification(entity, &old, &value);
+    }
+}
+
+/// Spawn a builder from inside a system, recording its creation
+fn spawn_builder<I, O>(world: &mut WorldView<I, O>, (x, y): (i32, i32)) {
+    let builder = world.create_entity();
+    world.record_entity_creation(builder);
+    add_recorded(world, builder, Position { x, y });
+    add_recorded(world, builder, Target { x, y });
+    add_recorded(world, builder, Actor);
+    add_recorded(world, builder, Builder);
+    add_recorded(world, builder, Inventory::default());
+}
+
+/// Draws the grid and the progress of the construction sites every frame
+pub struct ConstructionRenderSystem {
+    blueprints: Vec<Blueprint>,
+}
+
+impl Default for ConstructionRenderSystem {
+    fn default() -> Self {
+        Self {
+            blueprints: village_blueprints(),
+        }
+    }
+}
+
+impl ReadOnlySystem for ConstructionRenderSystem {
+    type InComponents = (Position, ConstructionSite, Inventory, Stockpile, Builder, Hut, Home, Work);
+
+    fn update(&mut self, world: &ReadOnlyWorldView<Self::InComponents>) {
+        // Clear screen
+        print!("\x1B[2J\x1B[1;1H");
+
+        println!("Construction - builders carrying resources to building sites");
+        println!("# = Site, S = Stockpile, b = Builder, h = Hut, H = Home, W = Work");
+        println!();
+        for row in construction_grid(world) {
+            println!("{}", row);
+        }
+        println!();
+        for line in site_progress(world, &self.blueprints) {
+            println!("{}", line);
+        }
+    }
+}
+
+/// Render the construction grid from the components currently in the world,
+/// one string per row
+pub fn render_construction_grid(world: &World) -> Vec<String> {
+    construction_grid(&ReadOnlyWorldView::<()>::new(world))
+}
+
+fn construction_grid<I>(world: &ReadOnlyWorldView<I>) -> Vec<String> {
+    let mut grid = vec![vec!['.'; GRID_SIZE as usize]; GRID_SIZE as usize];
+    for entity in world.entities_with_component::<Position>() {
+        let Some(position) = world.get_component::<Position>(entity) else {
+            continue;
+        };
+        let symbol = if world.get_component::<ConstructionSite>(entity).is_some() {
+            '#'
+        } else if world.get_component::<Stockpile>(entity).is_some() {
+            'S'
+        } else if world.get_component::<Builder>(entity).is_some() {
+            'b'
+        } else if world.get_component::<Hut>(entity).is_some() {
+            'h'
+        } else if world.get_component::<Home>(entity).is_some() {
+            'H'
+        } else if world.get_component::<Work>(entity).is_some() {
+            'W'
+        } else {
+            'A'
+        };
+        if is_valid_position((position.x, position.y)) {
+            grid[position.y as usize][position.x as usize] = symbol;
+        }
+    }
+    grid.iter()
+        .map(|row| row.iter().map(|cell| format!("{} ", cell)).collect::<String>().trim_end().to_string())
+        .collect()
+}
+
+/// Describe the progress of every construction site, e.g.
+/// "Hut at (3, 3): stone 1/1, wood 2/3, work 0/4"
+pub fn construction_progress(world: &World, blueprints: &[Blueprint]) -> Vec<String> {
+    site_progress(&ReadOnlyWorldView::<()>::new(world), blueprints)
+}
+
+fn site_progress<I>(world: &ReadOnlyWorldView<I>, blueprints: &[Blueprint]) -> Vec<String> {
+    world
+        .entities_with_component::<ConstructionSite>()
+        .into_iter()
+        .filter_map(|entity| {
+            let site = world.get_component::<ConstructionSite>(entity)?;
+            let position = world.get_component::<Position>(entity)?;
+            let blueprint = blueprints.iter().find(|blueprint| blueprint.building == site.building)?;
+            let delivered = world.get_component::<Inventory>(entity).cloned().unwrap_or_default();
+            let mut parts: Vec<String> = blueprint
+                .cost
+                .iter()
+                .map(|(item, count)| format!("{} {}/{}", item, delivered.count(item), count))
+                .collect();
+            parts.push(format!("work {}/{}", site.work, blueprint.work_ticks));
+            Some(format!("{:?} at ({}, {}): {}", site.building, position.x, position.y, parts.join(", ")))
+        })
+        .collect()
+}
+
+/// Register the construction components for replay
+pub fn register_construction_components(world: &mut World) {
+    world.register_replay_component::<Inventory>();
+    world.register_replay_component::<ConstructionSite>();
+    world.register_replay_component::<Builder>();
+    world.register_replay_component::<Stockpile>();
+    world.register_replay_component::<Hut>();
+}
+
+/// Spawns a stockpile, builders and construction sites and adds the
+/// `BuildingSystem`, with the shared `NavigationPlugin` moving the builders
+pub struct ConstructionPlugin {
+    pub blueprints: Vec<Blueprint>,
+    /// Position and initial resources of the stockpile
+    pub stockpile: ((i32, i32), Inventory),
+    /// Positions of the builders
+    pub builders: Vec<(i32, i32)>,
+    /// Construction sites placed at the start
+    pub sites: Vec<(BuildingKind, (i32, i32))>,
+    /// Add the ConstructionRenderSystem drawing the grid every frame, in the "rendering" group
+    pub render: bool,
+}
+
+impl Default for ConstructionPlugin {
+    /// Two builders putting up two huts and a home
+    fn default() -> Self {
+        Self {
+            blueprints: village_blueprints(),
+            stockpile: ((0, 5), Inventory::default().with("wood", 12).with("stone", 5)),
+            builders: vec![(2, 6), (5, 5)],
+            sites: vec![
+                (BuildingKind::Hut, (3, 3)),
+                (BuildingKind::Hut, (7, 3)),
+                (BuildingKind::Home, (4, 8)),
+            ],
+            render: true,
+        }
+    }
+}
+
+impl Plugin for ConstructionPlugin {
+    fn build(&self, world: &mut World) {
+        register_construction_components(world);
+        let completed = CompletedBuildings::default();
+        let observed = completed.clone();
+        world.observe::<ConstructionSite>(move |entity, old, new| {
+            // Sites are only removed once their building is complete
+            if let (Some(site), None) = (old, new) {
+                ecs_info!("{:?} {} completed", site.building, entity);
+                observed.0.borrow_mut().push((entity, site.building));
+            }
+        });
+        world.insert_resource(completed);
+
+        let ((x, y), stock) = &self.stockpile;
+        let stockpile = world.create_entity();
+        world.add_component(stockpile, Position { x: *x, y: *y });
+        world.add_component(stockpile, Stockpile);
+        world.add_component(stockpile, stock.clone());
+        world.add_component(stockpile, Obstacle);
+        // Builders target their own position until there is something to do
+        for &(x, y) in &self.builders {
+            let builder = world.create_entity();
+            world.add_component(builder, Position { x, y });
+            world.add_component(builder, Target { x, y });
+            world.add_component(builder, Actor);
+            world.add_component(builder, Builder);
+            world.add_component(builder, Inventory::default());
+        }
+        for &(building, position) in &self.sites {
+            if let Err(e) = place_construction_site(world, building, position) {
+                ecs_warn!("Cannot place {:?} site: {}", building, e);
+            }
+        }
+
+        world.add_plugin(NavigationPlugin);
+        let blueprints = self.blueprints.clone();
+        world.register_system_factory(move || BuildingSystem::new(blueprints.clone()));
+        world.add_system(BuildingSystem::new(self.blueprints.clone()));
+        if self.render {
+            world.add_system_to_group("rendering", ConstructionRenderSystem {
+                blueprints: self.blueprints.clone(),
+            });
+        }
+    }
+}
+
+/// Run the construction demo at 2 ticks per second, logging the session to `game_logs/`
+pub fn run_construction_game() {
+    println!("Starting Construction...");
+    println!("Builders carry wood and stone from the stockpile to building sites");
+    println!("Press Ctrl+C to stop the simulation");
+
+    let mut world = World::new();
+    world.add_plugin(ConstructionPlugin::default());
+    world.initialize_systems();
+    run_demo_loop(&mut world, "construction");
+    println!("Construction stopped after {} updates", world.frame_number());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn construction(builders: Vec<(i32, i32)>, sites: Vec<(BuildingKind, (i32, i32))>) -> World {
+        let mut world = World::new();
+        world.set_strict_access(true);
+        world.add_plugin(ConstructionPlugin {
+            builders,
+            sites,
+            render: false,
+            ..ConstructionPlugin::default()
+        });
+        world
+    }
+
+    #[test]
+    fn test_builders_turn_sites_into_buildings() {
+        let mut world = construction(vec![(2, 6), (5, 5)], vec![(BuildingKind::Hut, (3, 3))]);
+        let site = world.entities_with_component::<ConstructionSite>()[0];
+        world.update_n(60);
+
+        assert!(world.get_component::<ConstructionSite>(site).is_none());
+        assert!(world.get_component::<Inventory>(site).is_none());
+        assert!(world.get_component::<Hut>(site).is_some());
+        let stockpile = world.entities_with_component::<Stockpile>()[0];
+        let stock = world.get_component::<Inventory>(stockpile).unwrap();
+        assert_eq!((stock.count("wood"), stock.count("stone")), (9, 4));
+        // Builders brought back nothing and carry nothing, and the hut housed a new one
+        let builders = world.entities_with_component::<Builder>();
+        assert_eq!(builders.len(), 3);
+        for builder in builders {
+            assert!(world.get_component::<Inventory>(builder).unwrap().is_empty());
+        }
+
+        let completed = world.resource::<CompletedBuildings>().unwrap().list();
+        assert_eq!(completed, vec![(site, BuildingKind::Hut)]);
+        let failures: Vec<&str> = world
+            .get_update_history()
+            .updates()
+            .iter()
+            .flat_map(|update| update.system_diffs().iter().filter_map(|diff| diff.failure()))
+            .collect();
+        assert!(failures.is_empty(), "{:?}", failures);
+    }
+
+    #[test]
+    fn test_progress_is_reported_per_site() {
+        let mut world = construction(vec![(1, 5)], vec![(BuildingKind::Home, (4, 8))]);
+        let blueprints = village_blueprints();
+        assert_eq!(
+            construction_progress(&world, &blueprints),
+            vec!["Home at (4, 8): stone 0/2, wood 0/4, work 0/6".to_string()]
+        );
+        // The builder takes two units of stone next to the stockpile and delivers them
+        world.update_n(10);
+        let progress = construction_progress(&world, &blueprints);
+        assert!(progress[0].starts_with("Home at (4, 8): stone 2/2"), "{:?}", progress);
+        assert!(render_construction_grid(&world)[8].contains('#'));
+    }
+
+    #[test]
+    fn test_sites_are_placed_on_free_cells() {
+        let mut world = construction(vec![(2, 6)], vec![]);
+        assert!(place_construction_site(&mut world, BuildingKind::Hut, (2, 6)).is_err());
+        assert!(place_construction_site(&mut world, BuildingKind::Hut, HOME_POS).is_err());
+        assert!(place_construction_site(&mut world, BuildingKind::Hut, (10, 0)).is_err());
+        let site = place_construction_site(&mut world, BuildingKind::Hut, (8, 8)).unwrap();
+        assert_eq!(world.get_component::<ConstructionSite>(site).unwrap().work, 0);
+        assert!(world.get_component::<Obstacle>(site).is_some());
+    }
+
+    #[test]
+    fn test_construction_replays_from_the_log() {
+        let mut world = construction(vec![(2, 6)], vec![(BuildingKind::Hut, (3, 3))]);
+        let initial_state = world.snapshot();
+        world.reset_history();
+        world.update_n(60);
+        let mut history = world.get_update_history().clone();
+        history.set_initial_state(initial_state);
+
+        let mut replayed = super::super::world_from_replay(&history).unwrap();
+        for update in history.updates() {
+            replayed.apply_update_diff(update);
+        }
+        assert_eq!(render_construction_grid(&replayed), render_construction_grid(&world));
+        assert_eq!(replayed.entities_with_component::<Hut>().len(), 1);
+        assert!(replayed.entities_with_component::<ConstructionSite>().is_empty());
+        // Including the builder spawned by the hut
+        assert_eq!(replayed.entities_with_component::<Builder>().len(), 2);
+    }
+}
@@ -0,0 +1,186 @@
+//! Registry of the showcase demos, run with `cargo run demo <name>`.
+//!
+//! Every demo is a `Plugin` that brings its own map layout, entities and
+//! systems, registered under a name with a one-line description:
+//!
+//! ```ignore
+//! let mut demos = DemoRegistry::builtin();
+//! demos.register::<ArenaPlugin>("arena", "Gladiators fighting in a ring");
+//! demos.run("arena")?;
+//! ```
+//!
+//! The plugin's `Default` is the demo's setup; `register_with` takes a
+//! closure instead for demos sharing a plugin type with another setup.
+//! Render systems belong in the "rendering" group, so fast-forwarding a
+//! demo world skips them.
+
+use super::combat::CombatPlugin;
+use super::construction::ConstructionPlugin;
+use super::game::{run_demo_loop, GamePlugin};
+use super::jobs::JobsPlugin;
+use super::needs::NeedsPlugin;
+use crate::{Plugin, World};
+
+/// A registered demo
+pub struct Demo {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// Adds the demo's plugin to a world
+    add_plugin: Box<dyn Fn(&mut World)>,
+}
+
+impl Demo {
+    /// Create a world with the demo's plugin added and its systems initialized
+    pub fn build(&self) -> World {
+        let mut world = World::new();
+        (self.add_plugin)(&mut world);
+        world.initialize_systems();
+        world
+    }
+}
+
+impl std::fmt::Debug for Demo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Demo")
+            .field("name", &self.name)
+            .field("description", &self.description)
+            .finish()
+    }
+}
+
+/// Demos by name, listed in registration order
+#[derive(Debug, Default)]
+pub struct DemoRegistry {
+    demos: Vec<Demo>,
+}
+
+impl DemoRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a registry of the demos shipped with the game
+    pub fn builtin() -> Self {
+        let mut demos = Self::new();
+        demos
+            .register::<GamePlugin>("game", "Actors travelling between home and work")
+            .register::<JobsPlugin>("jobs", "Woodcutters, carpenters and farmers working a village")
+            .register::<ConstructionPlugin>("construction", "Builders carrying resources to put up huts")
+            .register::<NeedsPlugin>("needs", "Villagers eating, resting and growing in number")
+            .register::<CombatPlugin>("combat", "Two teams fighting until one is left");
+        demos
+    }
+
+    /// Register a demo running the default setup of plugin `P`, replacing
+    /// an earlier demo of the same name
+    pub fn register<P: Plugin + Default>(&mut self, name: &'static str, description: &'static str) -> &mut Self {
+        self.register_with(name, description, P::default)
+    }
+
+    /// Register a demo running the plugin created by `plugin`
+    pub fn register_with<P: Plugin>(
+        &mut self,
+        name: &'static str,
+        description: &'static str,
+        plugin: impl Fn() -> P + 'static,
+    ) -> &mut Self {
+        let demo = Demo {
+            name,
+            description,
+            add_plugin: Box::new(move |world| {
+                world.add_plugin(plugin());
+            }),
+        };
+        match self.demos.iter_mut().find(|existing| existing.name == name) {
+            Some(existing) => {
+                ecs_warn!("Demo {} was already registered, replacing it", name);
+                *existing = demo;
+            }
+            None => self.demos.push(demo),
+        }
+        self
+    }
+
+    /// Get a demo by name
+    pub fn get(&self, name: &str) -> Option<&Demo> {
+        self.demos.iter().find(|demo| demo.name == name)
+    }
+
+    /// Get the registered demos, in registration order
+    pub fn demos(&self) -> &[Demo] {
+        &self.demos
+    }
+
+    /// Get the names of the registered demos, in registration order
+    pub fn names(&self) -> Vec<&'static str> {
+        self.demos.iter().map(|demo| demo.name).collect()
+    }
+
+    /// List the demos one per line, e.g. "  combat        Two teams fighting until one is left"
+    pub fn list(&self) -> String {
+        let width = self.demos.iter().map(|demo| demo.name.len()).max().unwrap_or(0);
+        self.demos
+            .iter()
+            .map(|demo| format!("  {:width$}  {}\n", demo.name, demo.description, width = width))
+            .collect()
+    }
+
+    /// Create the world of a demo, see `Demo::build`
+    pub fn build(&self, name: &str) -> Result<World, String> {
+        self.get(name).map(Demo::build).ok_or_else(|| {
+            format!("Unknown demo '{}', available demos:\n{}", name, self.list())
+        })
+    }
+
+    /// Run a demo at 2 ticks per second until Ctrl+C, logging the session to
+    /// `game_logs/<name>_<id>.log`
+    pub fn run(&self, name: &str) -> Result<(), String> {
+        let mut world = self.build(name)?;
+        let demo = self.get(name).expect("the demo was built");
+        println!("Starting demo {} - {}", demo.name, demo.description);
+        println!("Press Ctrl+C to stop the simulation");
+        run_demo_loop(&mut world, name);
+        println!("Demo {} stopped after {} updates", name, world.frame_number());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{Actor, NavigationPlugin};
+
+    #[test]
+    fn test_builtin_demos_build_and_run() {
+        let demos = DemoRegistry::builtin();
+        assert_eq!(demos.names(), vec!["game", "jobs", "construction", "needs", "combat"]);
+        for demo in demos.demos() {
+            let mut world = demos.build(demo.name).unwrap();
+            // Fast-forwarding skips the render systems
+            world.set_fast_forward(true);
+            world.update_n(5);
+            assert!(!world.entities_with_component::<Actor>().is_empty(), "{}", demo.name);
+            assert!(world.has_plugin::<NavigationPlugin>() || demo.name == "game", "{}", demo.name);
+        }
+    }
+
+    #[test]
+    fn test_unknown_demos_list_the_available_ones() {
+        let mut demos = DemoRegistry::new();
+        demos.register_with("duel", "Two fighters", || CombatPlugin {
+            fighters: vec![],
+            render: false,
+        });
+        demos.register::<NeedsPlugin>("village", "Villagers with needs");
+        assert_eq!(demos.list(), "  duel     Two fighters\n  village  Villagers with needs\n");
+        let error = demos.build("arena").err().unwrap();
+        assert_eq!(error, format!("Unknown demo 'arena', available demos:\n{}", demos.list()));
+
+        // Registering a name again replaces the demo
+        demos.register::<CombatPlugin>("duel", "Two teams");
+        assert_eq!(demos.names(), vec!["duel", "village"]);
+        assert_eq!(demos.get("duel").unwrap().description, "Two teams");
+        assert!(demos.build("duel").unwrap().has_plugin::<CombatPlugin>());
+    }
+}
@@ -1,59 +1,71 @@
-use crate::{Diff, In, Out, System, World, WorldView};
-use rand::Rng;
+use crate::{
+    BackgroundWriting, Diff, Entity, In, LogDurability, Narrator, Out, Plugin, Prefab, ReadOnlySystem, ReadOnlyWorldView, Reflect, ReplayComparison,
+    ReplayLogConfig, ReplayMetadata, ReplayPlayer, System, World, WorldUpdateHistory, WorldView,
+};
+use crate::replay_analysis::FrameRenderer;
+use rand::{Rng, SeedableRng};
 use std::collections::HashSet;
 use std::fs::{File, OpenOptions};
 use std::io::{Write, BufWriter};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{mpsc, Arc};
 use std::thread;
 use std::time::Duration;
 
 // Grid constants
-const GRID_SIZE: i32 = 10;
-const HOME_POS: (i32, i32) = (1, 1);
-const WORK_POS: (i32, i32) = (6, 8);
+pub(super) const GRID_SIZE: i32 = 10;
+pub(super) const HOME_POS: (i32, i32) = (1, 1);
+pub(super) const WORK_POS: (i32, i32) = (6, 8);
 const WAIT_TICKS: u32 = 10;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Diff)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Diff, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Position {
     pub x: i32,
     pub y: i32,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Diff)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Diff, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Home;
 
 
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Diff)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Diff, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Work;
 
 
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Diff)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Diff, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Actor;
 
 
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Diff)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Diff, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Obstacle;
 
 
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Diff)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Diff, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Target {
     pub x: i32,
     pub y: i32,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Diff)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Diff, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct WaitTimer {
     pub ticks: u32,
 }
 
 
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Diff)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Diff, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[allow(dead_code)]
 pub enum ActorState {
     #[default]
@@ -69,7 +81,7 @@ pub enum ActorState {
 // Simplified thanks to extended query support for up to 16 components!
 pub struct MovementSystem;
 impl System for MovementSystem {
-    type InComponents = (Actor, Position, Target);
+    type InComponents = (Actor, Position, Target, Obstacle);
     type OutComponents = (Position,);
 
     fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
@@ -82,6 +94,11 @@ impl System for MovementSystem {
         obstacles.insert(HOME_POS);
         obstacles.insert(WORK_POS);
 
+        // Add the positions of other obstacles, e.g. the sites of jobs
+        for (_, (pos, _)) in world.query_components::<(In<Position>, In<Obstacle>)>() {
+            obstacles.insert((pos.x, pos.y));
+        }
+
         // Collect all current actor positions to avoid collisions
         let current_positions: Vec<(i32, i32)> = world
             .query_components::<(In<Position>, In<Actor>)>()
@@ -213,65 +230,88 @@ impl Default for RenderSystem {
     }
 }
 
-impl System for RenderSystem {
+impl ReadOnlySystem for RenderSystem {
     type InComponents = (Position,);
-    type OutComponents = ();
-
-    fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
 
-    fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+    fn update(&mut self, world: &ReadOnlyWorldView<Self::InComponents>) {
         // Clear screen
         print!("\x1B[2J\x1B[1;1H");
 
-        // Create grid
-        let mut grid = vec![vec!['.'; GRID_SIZE as usize]; GRID_SIZE as usize];
-
-        // Place entities on grid
-        for (_entity, position) in world.query_components::<(In<Position>,)>() {
-            let x = position.x as usize;
-            let y = position.y as usize;
-
-            if x < GRID_SIZE as usize && y < GRID_SIZE as usize {
-                // Check what type of entity this is by position
-                if (position.x, position.y) == HOME_POS {
-                    grid[y][x] = 'H';
-                } else if (position.x, position.y) == WORK_POS {
-                    grid[y][x] = 'W';
-                } else {
-                    // If the position overlaps with home or work, show the location marker instead
-                    if grid[y][x] == '.' {
-                        grid[y][x] = 'A'; // Actor
-                    }
-                }
-            }
-        }
-
-        // Ensure home and work are always visible
-        if HOME_POS.0 >= 0 && HOME_POS.0 < GRID_SIZE && HOME_POS.1 >= 0 && HOME_POS.1 < GRID_SIZE {
-            grid[HOME_POS.1 as usize][HOME_POS.0 as usize] = 'H';
-        }
-        if WORK_POS.0 >= 0 && WORK_POS.0 < GRID_SIZE && WORK_POS.1 >= 0 && WORK_POS.1 < GRID_SIZE {
-            grid[WORK_POS.1 as usize][WORK_POS.0 as usize] = 'W';
-        }
+        let positions: Vec<(i32, i32)> = world
+            .query::<(In<Position>,)>()
+            .into_iter()
+            .map(|(_, position)| (position.x, position.y))
+            .collect();
 
         // Print grid - same output regardless of mode
         println!("Simulation Game - Actors traveling between Home and Work");
         println!("H = Home, W = Work, A = Actor");
         println!();
-        for row in &grid {
-            for cell in row {
-                print!("{} ", cell);
-            }
-            println!();
+        for row in render_grid(&positions) {
+            println!("{}", row);
         }
         println!();
+        if world.is_paused() {
+            println!("PAUSED - p + Enter to resume");
+        }
     }
 
-    fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    fn run_when_paused(&self) -> bool {
+        true
+    }
 }
 
 // Helper functions
 
+/// Render the game grid for the given entity positions, one string per row.
+/// Home and work cells are always marked; any other occupied cell is an actor.
+pub fn render_grid(positions: &[(i32, i32)]) -> Vec<String> {
+    let mut grid = vec![vec!['.'; GRID_SIZE as usize]; GRID_SIZE as usize];
+
+    // Place entities on grid
+    for &(x, y) in positions {
+        if is_valid_position((x, y)) && grid[y as usize][x as usize] == '.' {
+            grid[y as usize][x as usize] = 'A';
+        }
+    }
+
+    // Ensure home and work are always visible
+    if is_valid_position(HOME_POS) {
+        grid[HOME_POS.1 as usize][HOME_POS.0 as usize] = 'H';
+    }
+    if is_valid_position(WORK_POS) {
+        grid[WORK_POS.1 as usize][WORK_POS.0 as usize] = 'W';
+    }
+
+    grid.iter()
+        .map(|row| row.iter().map(|cell| format!("{} ", cell)).collect::<String>().trim_end().to_string())
+        .collect()
+}
+
+/// Render the game grid from the Position components currently in the world
+pub fn render_world_grid(world: &World) -> Vec<String> {
+    let positions: Vec<(i32, i32)> = world
+        .entities_with_component::<Position>()
+        .into_iter()
+        .filter_map(|entity| world.get_component::<Position>(entity))
+        .map(|position| (position.x, position.y))
+        .collect();
+    render_grid(&positions)
+}
+
+/// Draws frames of recorded game sessions as the ASCII grid, for `replay_analysis::render_frames`
+pub struct GridRenderer;
+
+impl FrameRenderer for GridRenderer {
+    fn new_world() -> World {
+        new_replay_world()
+    }
+
+    fn render(&mut self, world: &World) -> Vec<String> {
+        render_world_grid(world)
+    }
+}
+
 fn calculate_next_move(
     current: (i32, i32),
     target: (i32, i32),
@@ -322,50 +362,237 @@ fn calculate_next_move(
     current
 }
 
-fn is_valid_position(pos: (i32, i32)) -> bool {
+pub(super) fn is_valid_position(pos: (i32, i32)) -> bool {
     pos.0 >= 0 && pos.0 < GRID_SIZE && pos.1 >= 0 && pos.1 < GRID_SIZE
 }
 
-fn is_adjacent(pos1: (i32, i32), pos2: (i32, i32)) -> bool {
+pub(super) fn is_adjacent(pos1: (i32, i32), pos2: (i32, i32)) -> bool {
     let dx = (pos1.0 - pos2.0).abs();
     let dy = (pos1.1 - pos2.1).abs();
     dx <= 1 && dy <= 1 && !(dx == 0 && dy == 0)
 }
 
+/// Get the first free cell around `position`
+pub(super) fn free_neighbour(occupied: &[(i32, i32)], position: (i32, i32)) -> Option<(i32, i32)> {
+    (-1..=1)
+        .flat_map(|dy| (-1..=1).map(move |dx| (position.0 + dx, position.1 + dy)))
+        .find(|cell| {
+            is_valid_position(*cell) && *cell != HOME_POS && *cell != WORK_POS && !occupied.contains(cell)
+        })
+}
+
+/// Add a component to an entity, recording the addition
+pub(super) fn add_recorded<T: std::fmt::Debug + 'static, I, O>(world: &mut WorldView<I, O>, entity: Entity, component: T) {
+    world.record_component_addition(entity, &component);
+    world.add_component(entity, component);
+}
+
 // Game initialization and main loop
 
 pub fn initialize_game() -> World {
     let mut world = World::new();
-    let mut rng = rand::thread_rng();
-
-    // Create home entity
-    let home_entity = world.create_entity();
-    world.add_component(
-        home_entity,
-        Position {
-            x: HOME_POS.0,
-            y: HOME_POS.1,
-        },
+    world.add_plugin(GamePlugin::default());
+    world.initialize_systems();
+    world
+}
+
+/// Initialize the game without the RenderSystem, for running as a server
+pub fn initialize_headless_game() -> World {
+    let mut world = World::new();
+    world.add_plugin(GamePlugin::headless());
+    world.initialize_systems();
+    world
+}
+
+/// Moves every Actor towards its Target one cell per frame, around the home
+/// and work cells, entities with an Obstacle component and other actors. Shared by the commuters of
+/// `GamePlugin` and the workers of `JobsPlugin`.
+pub struct NavigationPlugin;
+
+impl Plugin for NavigationPlugin {
+    fn build(&self, world: &mut World) {
+        world.register_replay_component::<Position>();
+        world.register_replay_component::<Target>();
+        world.register_replay_component::<Actor>();
+        world.register_replay_component::<Obstacle>();
+        // Actors missing a Position or Target are skipped by the MovementSystem
+        world
+            .component_registry_mut()
+            .require_existing::<Target, Position>()
+            .require_existing::<Actor, Target>();
+        world.register_system_factory(|| MovementSystem);
+        world.add_system(MovementSystem);
+    }
+}
+
+/// Spawns the home, the work place and the actors and adds the game systems -
+/// the same for both normal and replay modes
+pub struct GamePlugin {
+    /// Add the RenderSystem drawing the grid every frame, in the "rendering" group
+    pub render: bool,
+    /// Seed for placing the actors; if None the seed of the world's replay
+    /// metadata is used, or a random one if that has none either
+    pub seed: Option<u64>,
+}
+
+impl Default for GamePlugin {
+    fn default() -> Self {
+        Self {
+            render: true,
+            seed: None,
+        }
+    }
+}
+
+impl GamePlugin {
+    /// The game without the RenderSystem
+    pub fn headless() -> Self {
+        Self {
+            render: false,
+            ..Self::default()
+        }
+    }
+}
+
+impl Plugin for GamePlugin {
+    fn build(&self, world: &mut World) {
+        let seed = self
+            .seed
+            .or(world.replay_metadata().seed)
+            .unwrap_or_else(rand::random);
+        world.set_replay_metadata(ReplayMetadata {
+            game_version: Some(env!("CARGO_PKG_VERSION").to_string()),
+            seed: Some(seed),
+            config_hash: Some(ReplayMetadata::hash_config(&format!(
+                "grid={} home={:?} work={:?} render={}",
+                GRID_SIZE, HOME_POS, WORK_POS, self.render
+            ))),
+            ..ReplayMetadata::default()
+        });
+        register_game_components(world);
+        register_game_systems(world);
+        spawn_game_entities(world, seed);
+        world.add_plugin(NavigationPlugin);
+        world.add_system(WaitSystem);
+        if self.render {
+            world.add_system_to_group("rendering", RenderSystem);
+        }
+    }
+}
+
+/// Register the game components for replay, so recorded changes can be applied to a world
+pub fn register_game_components(world: &mut World) {
+    world.register_replay_component::<Position>();
+    world.register_replay_component::<Target>();
+    world.register_replay_component::<WaitTimer>();
+    world.register_replay_component::<Actor>();
+    world.register_replay_component::<Home>();
+    world.register_replay_component::<Work>();
+    world.register_replay_component::<Obstacle>();
+    world.register_replay_component::<ActorState>();
+    super::jobs::register_job_components(world);
+    super::construction::register_construction_components(world);
+    super::needs::register_needs_components(world);
+    super::combat::register_combat_components(world);
+}
+
+/// Register the game systems, so replays can add them to a world
+pub fn register_game_systems(world: &mut World) {
+    world.register_system_factory(|| MovementSystem);
+    world.register_system_factory(|| WaitSystem);
+    world.register_system_factory(|| RenderSystem);
+    world.register_system_factory(super::jobs::JobSystem::default);
+    world.register_system_factory(|| super::jobs::JobRenderSystem);
+    world.register_system_factory(super::construction::BuildingSystem::default);
+    world.register_system_factory(super::construction::ConstructionRenderSystem::default);
+    world.register_system_factory(|| super::needs::NeedsSystem);
+    world.register_system_factory(|| super::needs::VillagerSystem);
+    world.register_system_factory(|| super::needs::PopulationSystem);
+    world.register_system_factory(|| super::needs::NeedsRenderSystem);
+    world.register_system_factory(|| super::combat::CombatSystem);
+    world.register_system_factory(|| super::combat::CorpseSystem);
+    world.register_system_factory(|| super::combat::CombatRenderSystem);
+}
+
+/// Build a game world from the initial state recorded in a replay log, without
+/// running any initialization code
+pub fn world_from_replay(history: &WorldUpdateHistory) -> Result<World, String> {
+    let snapshot = history
+        .initial_state()
+        .ok_or("Replay log has no initial state; it was recorded before initial states were logged")?;
+    let mut world = new_replay_world();
+    world.check_replay_registrations(history)?;
+    world.restore_snapshot(snapshot);
+    Ok(world)
+}
+
+/// Create a player for a replay log, starting from the initial state recorded in it
+pub fn game_replay_player(history: WorldUpdateHistory) -> Result<ReplayPlayer, String> {
+    if history.initial_state().is_none() {
+        return Err("Replay log has no initial state; it was recorded before initial states were logged".to_string());
+    }
+    ReplayPlayer::new(history, new_replay_world)
+}
+
+/// Narrator describing game frames, e.g. "MovementSystem moved Actor Entity(0, 3) from (2, 3) to (3, 3)"
+pub fn game_narrator() -> Narrator {
+    Narrator::new()
+        .with_kind("Actor")
+        .with_kind("Obstacle")
+        .with_verb("Position", "moved")
+}
+
+/// Create an empty world with the game components and systems registered for replay
+pub fn new_replay_world() -> World {
+    let mut world = World::new();
+    register_game_components(&mut world);
+    register_game_systems(&mut world);
+    world
+}
+
+/// Register the prefabs of the home, the work place and the actors
+pub fn register_game_prefabs(world: &mut World) {
+    world.register_prefab(
+        Prefab::new("home")
+            .with(Position {
+                x: HOME_POS.0,
+                y: HOME_POS.1,
+            })
+            .with(Home)
+            .with(Obstacle),
     );
-    world.add_component(home_entity, Home);
-    world.add_component(home_entity, Obstacle);
-
-    // Create work entity
-    let work_entity = world.create_entity();
-    world.add_component(
-        work_entity,
-        Position {
-            x: WORK_POS.0,
-            y: WORK_POS.1,
-        },
+    world.register_prefab(
+        Prefab::new("work")
+            .with(Position {
+                x: WORK_POS.0,
+                y: WORK_POS.1,
+            })
+            .with(Work)
+            .with(Obstacle),
     );
-    world.add_component(work_entity, Work);
-    world.add_component(work_entity, Obstacle);
+    // Actors start by going to work
+    world.register_prefab(
+        Prefab::new("actor")
+            .with(Position { x: 0, y: 0 })
+            .with(Actor)
+            .with(Target {
+                x: WORK_POS.0,
+                y: WORK_POS.1,
+            })
+            .with(WaitTimer { ticks: 0 })
+            .with(ActorState::MovingToWork),
+    );
+}
+
+fn spawn_game_entities(world: &mut World, seed: u64) {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+    register_game_prefabs(world);
+
+    world.instantiate("home", &[]).expect("home prefab is valid");
+    world.instantiate("work", &[]).expect("work prefab is valid");
 
     // Create 3 actors at random positions
     for _i in 0..3 {
-        let actor_entity = world.create_entity();
-
         // Generate random position that's not home or work
         let mut pos;
         loop {
@@ -375,48 +602,178 @@ pub fn initialize_game() -> World {
             }
         }
 
-        world.add_component(actor_entity, Position { x: pos.0, y: pos.1 });
-        world.add_component(actor_entity, Actor);
-        world.add_component(
-            actor_entity,
-            Target {
-                x: WORK_POS.0,
-                y: WORK_POS.1,
-            },
-        ); // Start by going to work
-        world.add_component(actor_entity, WaitTimer { ticks: 0 });
-        world.add_component(actor_entity, ActorState::MovingToWork);
+        let position = Position { x: pos.0, y: pos.1 }.to_value();
+        world
+            .instantiate("actor", &[("Position", position)])
+            .expect("actor prefab is valid");
     }
+}
 
-    // Add systems - same for both normal and replay modes
-    world.add_system(MovementSystem);
-    world.add_system(WaitSystem);
-    world.add_system(RenderSystem::default());
+pub fn run_game() {
+    run_game_normal();
+}
 
-    // Initialize systems
-    world.initialize_systems();
+/// Playback settings of `run_game_replay`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayOptions {
+    /// Playback speed, 1.0 being the recorded 2 ticks per second
+    pub speed: f64,
+    /// Start paused, waiting for step commands
+    pub paused: bool,
+    /// Frame to jump to before playback starts
+    pub start_frame: Option<usize>,
+    /// Print the changes of every played frame as sentences
+    pub verbose: bool,
+}
 
-    world
+impl Default for ReplayOptions {
+    fn default() -> Self {
+        Self {
+            speed: 1.0,
+            paused: false,
+            start_frame: None,
+            verbose: false,
+        }
+    }
 }
 
-pub fn run_game() {
-    run_game_normal();
+impl ReplayOptions {
+    /// Parse `--speed 4x`, `--paused`, `--frame <n>` and `--verbose` command line options
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Self::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--speed" => {
+                    let speed = args.next().ok_or("--speed needs a value like 4x")?;
+                    options.speed = ReplayPlayer::parse_speed(speed)?;
+                }
+                "--paused" => options.paused = true,
+                "--verbose" => options.verbose = true,
+                "--frame" => {
+                    let frame = args.next().ok_or("--frame needs a frame number")?;
+                    options.start_frame =
+                        Some(frame.parse().map_err(|_| format!("invalid frame number '{}'", frame))?);
+                }
+                _ => return Err(format!("unknown replay option '{}'", arg)),
+            }
+        }
+        Ok(options)
+    }
 }
 
-pub fn run_game_replay(replay_log_path: &str) {
+pub fn run_game_replay(replay_log_path: &str, options: ReplayOptions) {
     println!("Starting Simulation Game in Replay Mode...");
     println!("Loading replay data from: {}", replay_log_path);
     
-    // Initialize the game world - same as normal mode
-    let mut world = initialize_game();
-    
-    // Run the replay using existing systems with component copies
-    match run_replay_with_existing_systems(&mut world, replay_log_path) {
+    // The world is rebuilt from the initial state recorded in the log
+    match run_replay_with_existing_systems(replay_log_path, options) {
         Ok(()) => {
             println!("Replay completed successfully");
         }
         Err(e) => {
-            eprintln!("Replay failed: {}", e);
+            ecs_error!("Replay failed: {}", e);
+        }
+    }
+}
+
+/// Print the grid of every frame of a replay log, or only of `frame`, without running the game
+pub fn render_game_replay(replay_log_path: &str, frame: Option<usize>) -> Result<(), Box<dyn std::error::Error>> {
+    let history = World::parse_replay_log_file(replay_log_path)?;
+    let frames = match frame {
+        Some(frame) => vec![(frame, crate::replay_analysis::render_frame(&history, frame, &mut GridRenderer)?)],
+        None => crate::replay_analysis::render_frames(&history, &mut GridRenderer)?
+            .into_iter()
+            .enumerate()
+            .collect(),
+    };
+    for (frame, rows) in frames {
+        println!("=== Frame {} / {} ===", frame, history.len());
+        for row in rows {
+            println!("{}", row);
+        }
+    }
+    Ok(())
+}
+
+/// Play two replay logs in lockstep and print the frames at which their states differ
+pub fn compare_game_replays(first_log_path: &str, second_log_path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let first = World::parse_replay_log_file(first_log_path)?;
+    let second = World::parse_replay_log_file(second_log_path)?;
+    let mut comparison = ReplayComparison::new(first, second, new_replay_world)?;
+    let frames = comparison.all_differences();
+    for frame in &frames {
+        println!("=== Frame {}: {} differences ===", frame.frame, frame.differences.len());
+        for difference in &frame.differences {
+            println!("  {}", difference);
+        }
+    }
+    match frames.first() {
+        Some(first) => println!("Sessions diverge at frame {} and differ in {} frames", first.frame, frames.len()),
+        None => println!("Sessions have the same state in all {} frames", comparison.frame_count()),
+    }
+    Ok(())
+}
+
+/// Run the game headless at 2 ticks per second, controlled through `address`
+#[cfg(feature = "server")]
+pub fn run_game_server(address: &str) {
+    use crate::server::{Server, ServerConfig};
+
+    let config = ServerConfig {
+        tick_rate: 2.0,
+        control_address: Some(address.to_string()),
+        start_paused: false,
+    };
+    let mut server = match Server::new(initialize_headless_game(), config) {
+        Ok(server) => server,
+        Err(e) => {
+            ecs_error!("Failed to start server on {}: {}", address, e);
+            return;
+        }
+    };
+    server.register_component::<Position>();
+    server.register_component::<Home>();
+    server.register_component::<Work>();
+    server.register_component::<Actor>();
+    server.register_component::<Obstacle>();
+    server.register_component::<Target>();
+    server.register_component::<WaitTimer>();
+    server.register_component::<ActorState>();
+
+    let running = server.running_flag();
+    ctrlc::set_handler(move || {
+        running.store(false, Ordering::SeqCst);
+    }).expect("Error setting Ctrl-C handler");
+
+    println!("Simulation Game server listening on {}", address);
+    println!("Commands: status, pause, resume, step [n], snapshot, stream on|off, tick-rate <hz>, shutdown");
+    server.run();
+}
+
+/// Update a demo world at 2 ticks per second until Ctrl+C, logging the
+/// session to `game_logs/<file_prefix>_<id>.log`. The shutdown handler
+/// finalizes the log and exits the process.
+pub(super) fn run_demo_loop(world: &mut World, file_prefix: &str) {
+    let replay_config = ReplayLogConfig {
+        enabled: true,
+        log_directory: "game_logs".to_string(),
+        file_prefix: file_prefix.to_string(),
+        flush_interval: 10,
+        background: Some(BackgroundWriting::default()),
+        durability: LogDurability::FlushEveryFrame,
+        ..ReplayLogConfig::default()
+    };
+    if let Err(e) = world.enable_replay_logging(replay_config) {
+        ecs_warn!("Failed to enable replay logging: {}", e);
+    }
+
+    world.install_shutdown_handler().expect("Error setting Ctrl-C handler");
+
+    loop {
+        world.update();
+        if !world.is_fast_forwarding() {
+            thread::sleep(Duration::from_millis(500)); // 2 FPS
         }
     }
 }
@@ -424,48 +781,62 @@ pub fn run_game_replay(replay_log_path: &str) {
 fn run_game_normal() {
     println!("Starting Simulation Game...");
     println!("Actors will travel between Home (H) and Work (W)");
-    println!("Press Ctrl+C to stop the simulation");
+    println!("Press Ctrl+C to stop the simulation, p + Enter to pause or resume");
 
     let mut world = initialize_game();
 
     // Enable replay logging for full recording
-    if let Err(e) = world.enable_replay_logging_simple("game_logs", "simulation_game", 10) {
-        eprintln!("Warning: Failed to enable replay logging: {}", e);
+    // Log files are written on a background thread, so flushes don't delay ticks
+    let replay_config = ReplayLogConfig {
+        enabled: true,
+        log_directory: "game_logs".to_string(),
+        file_prefix: "simulation_game".to_string(),
+        flush_interval: 10,
+        background: Some(BackgroundWriting::default()),
+        // At 2 ticks per second, flushing every frame costs nothing and keeps
+        // the log readable with `ecs-replay recover` after a crash
+        durability: LogDurability::FlushEveryFrame,
+        ..ReplayLogConfig::default()
+    };
+    if let Err(e) = world.enable_replay_logging(replay_config) {
+        ecs_warn!("Failed to enable replay logging: {}", e);
         println!("Continuing without replay logging...");
     } else {
         println!("Replay logging enabled. Session will be saved to game_logs/");
+        if let Some(session_id) = world.replay_session_id() {
+            println!("To replay this session, run: cargo run game game_logs/simulation_game_{}.log", session_id);
+        }
     }
-    
-    // Set up Ctrl+C handler for graceful shutdown
-    let running = Arc::new(AtomicBool::new(true));
-    let r = running.clone();
-    
-    ctrlc::set_handler(move || {
-        println!("\nReceived Ctrl+C, shutting down gracefully...");
-        r.store(false, Ordering::SeqCst);
-    }).expect("Error setting Ctrl-C handler");
 
-    let mut update_count = 0;
-    
+    // On Ctrl+C the world deinitializes its systems, writes its final state
+    // to the replay log and finalizes it before the process exits
+    world.install_shutdown_handler().expect("Error setting Ctrl-C handler");
+
+    let (sender, commands) = mpsc::channel();
+    thread::spawn(move || {
+        for line in std::io::stdin().lines().map_while(Result::ok) {
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+
     // Game loop - 2 ticks per second
-    while running.load(Ordering::SeqCst) {
+    loop {
+        // Only the RenderSystem keeps updating behind the pause menu
+        for command in commands.try_iter() {
+            match command.trim() {
+                "p" if world.is_paused() => world.resume(),
+                "p" => world.pause(),
+                _ => {}
+            }
+        }
         world.update();
-        update_count += 1;
-        
-        thread::sleep(Duration::from_millis(500)); // 2 FPS
-    }
 
-    // Disable replay logging and finalize the log file
-    if let Err(e) = world.disable_replay_logging() {
-        eprintln!("Warning: Failed to finalize replay logging: {}", e);
-    } else {
-        if let Some(session_id) = world.replay_session_id() {
-            println!("Replay log saved. Session ID: {}", session_id);
-            println!("To replay this session, run: cargo run game game_logs/simulation_game_{}.log", session_id);
+        if !world.is_fast_forwarding() {
+            thread::sleep(Duration::from_millis(500)); // 2 FPS
         }
     }
-
-    println!("Game completed after {} updates", update_count);
 }
 
 #[cfg(test)]
@@ -489,6 +860,47 @@ mod tests {
         assert_eq!(actor_entities.len(), 3);
     }
 
+    #[test]
+    fn test_game_systems_declare_their_access() {
+        let mut world = World::new();
+        world.set_strict_access(true);
+        world.add_plugin(GamePlugin {
+            render: false,
+            seed: Some(3),
+        });
+        for _ in 0..20 {
+            world.update();
+        }
+        let failures: Vec<&str> = world
+            .get_update_history()
+            .updates()
+            .iter()
+            .flat_map(|update| update.system_diffs().iter().filter_map(|diff| diff.failure()))
+            .collect();
+        assert!(failures.is_empty(), "{:?}", failures);
+    }
+
+    #[test]
+    fn test_actors_need_a_position_and_target() {
+        let mut world = World::new();
+        world.add_plugin(NavigationPlugin);
+        let actor = world.create_entity();
+        assert_eq!(
+            world.try_add_component(actor, Actor),
+            Err(format!("Actor on {} requires Target", actor))
+        );
+        assert_eq!(
+            world.try_add_component(actor, Target { x: 2, y: 2 }),
+            Err(format!("Target on {} requires Position", actor))
+        );
+        world.try_add_component(actor, Position { x: 0, y: 0 }).unwrap();
+        world.try_add_component(actor, Target { x: 2, y: 2 }).unwrap();
+        world.try_add_component(actor, Actor).unwrap();
+        world.initialize_systems();
+        world.update();
+        assert_eq!(world.get_component::<Position>(actor), Some(&Position { x: 1, y: 0 }));
+    }
+
     #[test]
     fn test_valid_position() {
         assert!(is_valid_position((0, 0)));
@@ -509,6 +921,39 @@ mod tests {
         assert!(!is_adjacent((1, 1), (3, 3))); // too far
     }
 
+    #[test]
+    fn test_render_grid() {
+        let rows = render_grid(&[(0, 0), HOME_POS, (9, 9), (20, 20)]);
+        assert_eq!(rows.len(), GRID_SIZE as usize);
+        assert!(rows[0].starts_with("A . "));
+        assert_eq!(rows[HOME_POS.1 as usize].chars().nth(HOME_POS.0 as usize * 2), Some('H'));
+        assert_eq!(rows[WORK_POS.1 as usize].chars().nth(WORK_POS.0 as usize * 2), Some('W'));
+        assert!(rows[9].ends_with('A'));
+    }
+
+    #[test]
+    fn test_render_recorded_frames() {
+        let mut world = World::new();
+        world.add_plugin(GamePlugin {
+            render: false,
+            seed: Some(7),
+        });
+        let initial_state = world.snapshot();
+        world.reset_history();
+        for _ in 0..5 {
+            world.update();
+        }
+        let mut history = world.get_update_history().clone();
+        history.set_initial_state(initial_state);
+
+        let frames = crate::replay_analysis::render_frames(&history, &mut GridRenderer).unwrap();
+        assert_eq!(frames.len(), history.len() + 1);
+        assert_eq!(frames.last(), Some(&render_world_grid(&world)));
+        assert_ne!(frames[1], frames[history.len()]);
+        assert_eq!(crate::replay_analysis::render_frame(&history, 3, &mut GridRenderer).unwrap(), frames[3]);
+        assert!(crate::replay_analysis::render_frame(&history, 7, &mut GridRenderer).is_err());
+    }
+
     #[test]
     fn test_calculate_next_move() {
         let obstacles = HashSet::new();
@@ -547,7 +992,7 @@ mod tests {
         // Check that each update has system diffs
         for (i, update) in history.updates().iter().enumerate() {
             println!("  Update {}: {} system diffs", i + 1, update.system_diffs().len());
-            assert!(update.system_diffs().len() > 0);
+            assert!(!update.system_diffs().is_empty());
         }
     }
 
@@ -558,8 +1003,7 @@ mod tests {
         
         // Get initial positions and targets of actors
         let initial_data: Vec<((i32, i32), (i32, i32))> = {
-            let mut world_view = crate::WorldView::<(), ()>::new(&mut world);
-            world_view.query_components::<(crate::In<Position>, crate::In<Actor>, crate::In<Target>)>()
+            world.query::<(crate::In<Position>, crate::In<Actor>, crate::In<Target>)>()
                 .into_iter()
                 .map(|(_, (pos, _, target))| ((pos.x, pos.y), (target.x, target.y)))
                 .collect()
@@ -584,8 +1028,7 @@ mod tests {
         
         // Verify actors have moved (at least some should have different positions)
         let final_data: Vec<((i32, i32), (i32, i32))> = {
-            let mut world_view = crate::WorldView::<(), ()>::new(&mut world);
-            world_view.query_components::<(crate::In<Position>, crate::In<Actor>, crate::In<Target>)>()
+            world.query::<(crate::In<Position>, crate::In<Actor>, crate::In<Target>)>()
                 .into_iter()
                 .map(|(_, (pos, _, target))| ((pos.x, pos.y), (target.x, target.y)))
                 .collect()
@@ -691,12 +1134,34 @@ mod tests {
         
         println!("✅ Replay mode functionality test passed - system-level snapshot/restore with replay diff application works");
     }
+
+    #[test]
+    fn test_replay_options_parsing() {
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        let options = ReplayOptions::parse(&args(&["--speed", "4x", "--paused", "--frame", "120", "--verbose"])).unwrap();
+        assert_eq!(
+            options,
+            ReplayOptions {
+                speed: 4.0,
+                paused: true,
+                start_frame: Some(120),
+                verbose: true,
+            }
+        );
+        assert_eq!(ReplayOptions::parse(&[]).unwrap(), ReplayOptions::default());
+        assert!(ReplayOptions::parse(&args(&["--speed"])).is_err());
+        assert!(ReplayOptions::parse(&args(&["--frame", "last"])).is_err());
+        assert!(ReplayOptions::parse(&args(&["--loop"])).is_err());
+    }
 }
 
 // Manual logging functions for game history
 
 /// A world that operates on component copies for replay mode
-fn run_replay_with_existing_systems(world: &mut World, replay_log_path: &str) -> Result<(), Box<dyn std::error::Error>> {
+fn run_replay_with_existing_systems(
+    replay_log_path: &str,
+    options: ReplayOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
     println!("Replay mode: Parsing and applying actual replay data");
     println!("Log path: {}", replay_log_path);
     
@@ -707,7 +1172,7 @@ fn run_replay_with_existing_systems(world: &mut World, replay_log_path: &str) ->
             history
         }
         Err(e) => {
-            eprintln!("Failed to parse replay log: {}", e);
+            ecs_error!("Failed to parse replay log: {}", e);
             return Err(e);
         }
     };
@@ -716,6 +1181,16 @@ fn run_replay_with_existing_systems(world: &mut World, replay_log_path: &str) ->
         println!("No replay data found in log file");
         return Ok(());
     }
+    let mut player = game_replay_player(replay_history)?;
+    let narrator = game_narrator();
+    player.set_speed(options.speed);
+    if options.paused {
+        player.pause();
+    }
+    if let Some(frame) = options.start_frame {
+        player.seek(frame);
+        print_replay_frame(&player);
+    }
     
     // Set up Ctrl+C handler for graceful shutdown
     let running = Arc::new(AtomicBool::new(true));
@@ -726,28 +1201,103 @@ fn run_replay_with_existing_systems(world: &mut World, replay_log_path: &str) ->
         r.store(false, Ordering::SeqCst);
     }).expect("Error setting Ctrl-C handler");
 
-    // Apply each update from the replay
-    let updates = replay_history.updates();
-    for (frame_idx, update) in updates.iter().enumerate() {
-        if !running.load(Ordering::SeqCst) {
-            break;
+    // Commands are read line by line, so a plain Enter steps one frame
+    println!("Commands: Enter step, p pause/resume, g <frame> jump, + faster, - slower, q quit");
+    let (sender, commands) = mpsc::channel();
+    thread::spawn(move || {
+        for line in std::io::stdin().lines().map_while(Result::ok) {
+            if sender.send(line).is_err() {
+                break;
+            }
         }
+    });
+    let mut input_closed = false;
 
-        println!("=== Replay Frame {} ===", frame_idx + 1);
-        
-        // Apply the recorded world update
-        world.apply_update_diff(update);
-        
-        // For now, just print the frame number - rendering would require specific system access
-        println!("Applied replay frame {}", frame_idx + 1);
+    while running.load(Ordering::SeqCst) {
+        if player.is_finished() && !player.is_paused() {
+            break;
+        }
+        // Wake up regularly while paused to notice Ctrl+C
+        let wait = if player.is_paused() { Duration::from_millis(100) } else { player.frame_delay() };
+        let command = if input_closed {
+            thread::sleep(wait);
+            None
+        } else {
+            match commands.recv_timeout(wait) {
+                Ok(command) => Some(command),
+                Err(mpsc::RecvTimeoutError::Timeout) => None,
+                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    // Nobody can resume a paused replay without input
+                    input_closed = true;
+                    player.resume();
+                    None
+                }
+            }
+        };
 
-        thread::sleep(Duration::from_millis(500)); // 2 FPS for visualization
+        let step = match command.as_deref().map(str::trim) {
+            None => !player.is_paused(),
+            // Stepping by hand pauses playback
+            Some("" | "s") => {
+                player.pause();
+                true
+            }
+            Some("p") => {
+                player.toggle_pause();
+                println!("{}", if player.is_paused() { "Paused" } else { "Playing" });
+                false
+            }
+            Some("+") => {
+                player.set_speed(player.speed() * 2.0);
+                false
+            }
+            Some("-") => {
+                player.set_speed(player.speed() / 2.0);
+                false
+            }
+            Some("q") => break,
+            Some(command) => {
+                match command.strip_prefix("g ").map(|frame| frame.trim().parse::<usize>()) {
+                    Some(Ok(frame)) => {
+                        player.seek(frame);
+                        print_replay_frame(&player);
+                    }
+                    _ => println!("Unknown command '{}'", command),
+                }
+                false
+            }
+        };
+        if step {
+            let sentences = if options.verbose { player.narrate_next(&narrator) } else { Vec::new() };
+            if player.step() {
+                for sentence in sentences {
+                    println!("- {}", sentence);
+                }
+                print_replay_frame(&player);
+            }
+        }
     }
 
-    println!("Replay completed - {} frames applied", updates.len());
+    println!("Replay stopped at frame {} of {}", player.frame(), player.frame_count());
     Ok(())
 }
 
+/// Print the grid and the annotations of the frame the player is at
+fn print_replay_frame(player: &ReplayPlayer) {
+    println!("=== Replay Frame {} / {} ({}x) ===", player.frame(), player.frame_count(), player.speed());
+    let annotations = player
+        .frame()
+        .checked_sub(1)
+        .and_then(|frame| player.history().updates().get(frame))
+        .map_or(&[][..], |update| update.annotations());
+    for annotation in annotations {
+        println!("* {}", annotation.text);
+    }
+    for row in render_world_grid(player.world()) {
+        println!("{}", row);
+    }
+}
+
 fn simulate_replay_frame(world: &mut World, frame: usize) {
     // Simulate component changes based on frame for replay functionality
     // This demonstrates how replay would work with actual recorded changes
@@ -764,8 +1314,8 @@ fn simulate_replay_frame(world: &mut World, frame: usize) {
             let base_x = 2 + i as i32 * 2;
             let base_y = 2 + i as i32;
             
-            let new_x = (base_x + offset_x).max(0).min(GRID_SIZE - 1);
-            let new_y = (base_y + offset_y).max(0).min(GRID_SIZE - 1);
+            let new_x = (base_x + offset_x).clamp(0, GRID_SIZE - 1);
+            let new_y = (base_y + offset_y).clamp(0, GRID_SIZE - 1);
             
             // Update the component with the calculated position
             let new_position = Position { x: new_x, y: new_y };
@@ -989,8 +1539,8 @@ fn apply_replay_diff_to_components(world: &mut World, frame: usize) {
             let base_x = 2 + i as i32 * 2;
             let base_y = 2 + i as i32;
             
-            let new_x = (base_x + offset_x).max(0).min(GRID_SIZE - 1);
-            let new_y = (base_y + offset_y).max(0).min(GRID_SIZE - 1);
+            let new_x = (base_x + offset_x).clamp(0, GRID_SIZE - 1);
+            let new_y = (base_y + offset_y).clamp(0, GRID_SIZE - 1);
             
             // Apply the exact component state from replay data
             let replay_position = Position { x: new_x, y: new_y };
@@ -0,0 +1,476 @@
+//! Professions of the village demo, defined as data.
+//!
+//! A `Job` names the kind of site its workers gather at, the kind of site
+//! they work at and how long each takes. One `JobSystem` runs every
+//! profession, and workers walk between the sites with the `MovementSystem`
+//! of the shared `NavigationPlugin`, so a new profession is a new `Job`
+//! value rather than a new system:
+//!
+//! ```ignore
+//! let jobs = vec![
+//!     Job::new("woodcutter").gather_at("forest", 4).work_at("sawmill", 2),
+//!     Job::new("carpenter").gather_at("sawmill", 2).work_at("workshop", 6),
+//! ];
+//! ```
+//!
+//! Workers cycle through the stages of `JobStage`: they head to the nearest
+//! gather site, gather for the job's gather ticks, head to the nearest
+//! workplace, work for its work ticks and start over.
+
+use super::game::{
+    is_adjacent, is_valid_position, run_demo_loop, Actor, NavigationPlugin, Obstacle, Position, Target, GRID_SIZE,
+};
+use crate::{Diff, In, Out, Plugin, ReadOnlySystem, ReadOnlyWorldView, Reflect, System, World, WorldView};
+
+/// A place workers gather at or work at, e.g. a forest or a sawmill
+#[derive(Debug, Clone, PartialEq, Eq, Diff, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Site {
+    pub kind: String,
+}
+
+/// What a worker is doing for its job
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Diff, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum JobStage {
+    /// Not yet sent to a site
+    #[default]
+    Idle,
+    GoingToGather,
+    Gathering,
+    GoingToWork,
+    Working,
+}
+
+/// An actor working a job, by name
+#[derive(Debug, Clone, PartialEq, Eq, Diff, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Worker {
+    pub job: String,
+    pub stage: JobStage,
+    /// Ticks left of gathering or working
+    pub ticks: u32,
+}
+
+impl Worker {
+    /// An idle worker of `job`
+    pub fn new(job: &str) -> Self {
+        Self {
+            job: job.to_string(),
+            stage: JobStage::Idle,
+            ticks: 0,
+        }
+    }
+}
+
+/// A profession: where its workers gather and work, and for how long
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Job {
+    pub name: String,
+    /// Kind of site the workers gather at
+    pub gather_target: String,
+    pub gather_ticks: u32,
+    /// Kind of site the workers work at
+    pub workplace: String,
+    pub work_ticks: u32,
+}
+
+impl Job {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            gather_target: String::new(),
+            gather_ticks: 0,
+            workplace: String::new(),
+            work_ticks: 0,
+        }
+    }
+
+    /// Gather at sites of `kind` for `ticks` frames
+    pub fn gather_at(mut self, kind: &str, ticks: u32) -> Self {
+        self.gather_target = kind.to_string();
+        self.gather_ticks = ticks;
+        self
+    }
+
+    /// Work at sites of `kind` for `ticks` frames
+    pub fn work_at(mut self, kind: &str, ticks: u32) -> Self {
+        self.workplace = kind.to_string();
+        self.work_ticks = ticks;
+        self
+    }
+}
+
+/// The woodcutter, the carpenter and the farmer of the village demo
+pub fn village_jobs() -> Vec<Job> {
+    vec![
+        Job::new("woodcutter").gather_at("forest", 4).work_at("sawmill", 2),
+        Job::new("carpenter").gather_at("sawmill", 2).work_at("workshop", 6),
+        Job::new("farmer").gather_at("meadow", 5).work_at("granary", 3),
+    ]
+}
+
+/// Moves workers through the stages of their jobs by setting their Target
+pub struct JobSystem {
+    jobs: Vec<Job>,
+}
+
+impl JobSystem {
+    pub fn new(jobs: Vec<Job>) -> Self {
+        Self { jobs }
+    }
+
+    fn job(&self, name: &str) -> Option<&Job> {
+        self.jobs.iter().find(|job| job.name == name)
+    }
+}
+
+impl Default for JobSystem {
+    fn default() -> Self {
+        Self::new(village_jobs())
+    }
+}
+
+/// Get the position of the site of `kind` closest to `from`
+fn nearest_site(sites: &[(String, (i32, i32))], kind: &str, from: (i32, i32)) -> Option<(i32, i32)> {
+    sites
+        .iter()
+        .filter(|(site, _)| site == kind)
+        .map(|(_, position)| *position)
+        .min_by_key(|position| (position.0 - from.0).abs().max((position.1 - from.1).abs()))
+}
+
+impl System for JobSystem {
+    type InComponents = (Position, Site, Worker, Target);
+    type OutComponents = (Worker, Target);
+
+    fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+    fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+        let sites: Vec<(String, (i32, i32))> = world
+            .query_components::<(In<Position>, In<Site>)>()
+            .into_iter()
+            .map(|(_, (position, site))| (site.kind.clone(), (position.x, position.y)))
+            .collect();
+
+        // Collect changes to record after the query
+        let mut worker_changes = Vec::new();
+        let mut target_changes = Vec::new();
+
+        for (entity, (position, worker, target)) in
+            world.query_components::<(In<Position>, Out<Worker>, Out<Target>)>()
+        {
+            let Some(job) = self.job(&worker.job) else {
+                continue;
+            };
+            let current_pos = (position.x, position.y);
+            let target_pos = (target.x, target.y);
+            let arrived = current_pos == target_pos || is_adjacent(current_pos, target_pos);
+            let old_worker = worker.clone();
+            let old_target = *target;
+
+            // Site to head to next, with the stage of the way there
+            let next = match worker.stage {
+                JobStage::GoingToGather if arrived => {
+                    worker.stage = JobStage::Gathering;
+                    worker.ticks = job.gather_ticks;
+                    None
+                }
+                JobStage::GoingToWork if arrived => {
+                    worker.stage = JobStage::Working;
+                    worker.ticks = job.work_ticks;
+                    None
+                }
+                JobStage::Gathering | JobStage::Working if worker.ticks > 0 => {
+                    worker.ticks -= 1;
+                    None
+                }
+                JobStage::Idle | JobStage::Working => Some((&job.gather_target, JobStage::GoingToGather)),
+                JobStage::Gathering => Some((&job.workplace, JobStage::GoingToWork)),
+                JobStage::GoingToGather | JobStage::GoingToWork => None,
+            };
+            // Without a site of the kind the worker waits where it is
+            if let Some((kind, stage)) = next {
+                if let Some(site) = nearest_site(&sites, kind, current_pos) {
+                    worker.stage = stage;
+                    target.x = site.0;
+                    target.y = site.1;
+                }
+            }
+
+            if *worker != old_worker {
+                worker_changes.push((entity, old_worker, worker.clone()));
+            }
+            if *target != old_target {
+                target_changes.push((entity, old_target, *target));
+            }
+        }
+
+        // Record all component changes
+        for (entity, old_worker, new_worker) in worker_changes {
+            world.record_component_modification(entity, &old_worker, &new_worker);
+        }
+        for (entity, old_target, new_target) in target_changes {
+            world.record_component_modification(entity, &old_target, &new_target);
+        }
+    }
+
+    fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+}
+
+/// Draws the village grid every frame
+pub struct JobRenderSystem;
+
+impl ReadOnlySystem for JobRenderSystem {
+    type InComponents = (Position, Site, Worker);
+
+    fn update(&mut self, world: &ReadOnlyWorldView<Self::InComponents>) {
+        // Clear screen
+        print!("\x1B[2J\x1B[1;1H");
+
+        let sites: Vec<(String, (i32, i32))> = world
+            .query::<(In<Position>, In<Site>)>()
+            .into_iter()
+            .map(|(_, (position, site))| (site.kind.clone(), (position.x, position.y)))
+            .collect();
+        let workers: Vec<(String, (i32, i32))> = world
+            .query::<(In<Position>, In<Worker>)>()
+            .into_iter()
+            .map(|(_, (position, worker))| (worker.job.clone(), (position.x, position.y)))
+            .collect();
+
+        println!("Village - workers going about their jobs");
+        println!("Sites are upper case, workers lower case initials");
+        println!();
+        for row in render_village_grid(&sites, &workers) {
+            println!("{}", row);
+        }
+        println!();
+    }
+}
+
+/// Render the village grid, one string per row. Sites are drawn with the
+/// upper case initial of their kind, workers with the lower case initial of
+/// their job.
+pub fn render_village_grid(sites: &[(String, (i32, i32))], workers: &[(String, (i32, i32))]) -> Vec<String> {
+    let mut grid = vec![vec!['.'; GRID_SIZE as usize]; GRID_SIZE as usize];
+    let initials = sites
+        .iter()
+        .map(|(kind, position)| (kind, *position, true))
+        .chain(workers.iter().map(|(job, position)| (job, *position, false)));
+    for (name, (x, y), upper) in initials {
+        let Some(initial) = name.chars().next() else {
+            continue;
+        };
+        if is_valid_position((x, y)) && grid[y as usize][x as usize] == '.' {
+            grid[y as usize][x as usize] = if upper {
+                initial.to_ascii_uppercase()
+            } else {
+                initial.to_ascii_lowercase()
+            };
+        }
+    }
+    grid.iter()
+        .map(|row| row.iter().map(|cell| format!("{} ", cell)).collect::<String>().trim_end().to_string())
+        .collect()
+}
+
+/// Register the job components for replay
+pub fn register_job_components(world: &mut World) {
+    world.register_replay_component::<Site>();
+    world.register_replay_component::<Worker>();
+}
+
+/// Spawns the sites and workers of a village and adds the `JobSystem` running
+/// their jobs, with the shared `NavigationPlugin` moving the workers
+pub struct JobsPlugin {
+    pub jobs: Vec<Job>,
+    /// Sites placed in the world, by kind and position
+    pub sites: Vec<(String, (i32, i32))>,
+    /// Workers spawned, by job and position
+    pub workers: Vec<(String, (i32, i32))>,
+    /// Add the JobRenderSystem drawing the grid every frame, in the "rendering" group
+    pub render: bool,
+}
+
+impl Default for JobsPlugin {
+    /// The village demo: a worker of every job in `village_jobs`
+    fn default() -> Self {
+        let places = |places: &[(&str, (i32, i32))]| {
+            places
+                .iter()
+                .map(|(name, position)| (name.to_string(), *position))
+                .collect()
+        };
+        Self {
+            jobs: village_jobs(),
+            sites: places(&[
+                ("forest", (0, 9)),
+                ("sawmill", (4, 6)),
+                ("workshop", (8, 8)),
+                ("meadow", (9, 0)),
+                ("granary", (5, 2)),
+            ]),
+            workers: places(&[("woodcutter", (1, 5)), ("carpenter", (6, 5)), ("farmer", (7, 1))]),
+            render: true,
+        }
+    }
+}
+
+impl Plugin for JobsPlugin {
+    fn build(&self, world: &mut World) {
+        register_job_components(world);
+        for (job, _) in &self.workers {
+            if !self.jobs.iter().any(|defined| &defined.name == job) {
+                ecs_warn!("Worker job {} is not defined, its workers stay idle", job);
+            }
+        }
+
+        for (kind, (x, y)) in &self.sites {
+            let site = world.create_entity();
+            world.add_component(site, Position { x: *x, y: *y });
+            world.add_component(site, Site { kind: kind.clone() });
+            world.add_component(site, Obstacle);
+        }
+        // Workers target their own position until the JobSystem sends them out
+        for (job, (x, y)) in &self.workers {
+            let worker = world.create_entity();
+            world.add_component(worker, Position { x: *x, y: *y });
+            world.add_component(worker, Target { x: *x, y: *y });
+            world.add_component(worker, Actor);
+            world.add_component(worker, Worker::new(job));
+        }
+
+        world.add_plugin(NavigationPlugin);
+        let jobs = self.jobs.clone();
+        world.register_system_factory(move || JobSystem::new(jobs.clone()));
+        world.add_system(JobSystem::new(self.jobs.clone()));
+        if self.render {
+            world.add_system_to_group("rendering", JobRenderSystem);
+        }
+    }
+}
+
+/// Run the village demo at 2 ticks per second, logging the session to `game_logs/`
+pub fn run_jobs_game() {
+    println!("Starting Village...");
+    println!("Woodcutters, carpenters and farmers work their jobs");
+    println!("Press Ctrl+C to stop the simulation");
+
+    let mut world = World::new();
+    world.add_plugin(JobsPlugin::default());
+    world.initialize_systems();
+    run_demo_loop(&mut world, "village");
+    println!("Village stopped after {} updates", world.frame_number());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn village(workers: &[(&str, (i32, i32))]) -> World {
+        let mut world = World::new();
+        world.add_plugin(JobsPlugin {
+            workers: workers
+                .iter()
+                .map(|(job, position)| (job.to_string(), *position))
+                .collect(),
+            render: false,
+            ..JobsPlugin::default()
+        });
+        world
+    }
+
+    fn worker(world: &World) -> (Worker, (i32, i32)) {
+        let entity = world.entities_with_component::<Worker>()[0];
+        let position = world.get_component::<Position>(entity).unwrap();
+        (world.get_component::<Worker>(entity).unwrap().clone(), (position.x, position.y))
+    }
+
+    #[test]
+    fn test_worker_cycles_through_its_job() {
+        let mut world = village(&[("woodcutter", (1, 5))]);
+        world.update();
+        let (woodcutter, _) = worker(&world);
+        assert_eq!(woodcutter.stage, JobStage::GoingToGather);
+
+        // Walks next to the forest at (0, 9) and gathers for 4 ticks
+        let mut stages = Vec::new();
+        for _ in 0..30 {
+            world.update();
+            let (woodcutter, position) = worker(&world);
+            if stages.last() != Some(&woodcutter.stage) {
+                stages.push(woodcutter.stage);
+                if woodcutter.stage == JobStage::Gathering {
+                    assert!(is_adjacent(position, (0, 9)));
+                }
+                if woodcutter.stage == JobStage::Working {
+                    assert!(is_adjacent(position, (4, 6)));
+                }
+            }
+        }
+        assert_eq!(
+            &stages[..5],
+            &[
+                JobStage::GoingToGather,
+                JobStage::Gathering,
+                JobStage::GoingToWork,
+                JobStage::Working,
+                JobStage::GoingToGather
+            ]
+        );
+    }
+
+    #[test]
+    fn test_new_professions_are_data() {
+        let mut world = World::new();
+        world.add_plugin(JobsPlugin {
+            jobs: vec![Job::new("miner").gather_at("quarry", 1).work_at("forge", 1)],
+            sites: vec![("quarry".to_string(), (2, 0)), ("forge".to_string(), (2, 4))],
+            workers: vec![("miner".to_string(), (2, 2)), ("baker".to_string(), (5, 5))],
+            render: false,
+        });
+        world.set_strict_access(true);
+        // Sent out, next to the quarry, gathering one tick, sent to the forge
+        for _ in 0..4 {
+            world.update();
+        }
+        let workers: Vec<Worker> = world
+            .entities_with_component::<Worker>()
+            .into_iter()
+            .map(|entity| world.get_component::<Worker>(entity).unwrap().clone())
+            .collect();
+        assert_eq!(workers[0].stage, JobStage::GoingToWork);
+        // Jobs without a definition stay idle
+        assert_eq!(workers[1].stage, JobStage::Idle);
+        assert!(world
+            .get_update_history()
+            .updates()
+            .iter()
+            .all(|update| update.system_diffs().iter().all(|diff| diff.failure().is_none())));
+    }
+
+    #[test]
+    fn test_jobs_share_navigation_with_the_game() {
+        let mut world = World::new();
+        world.add_plugin(super::super::GamePlugin {
+            render: false,
+            seed: Some(7),
+        });
+        world.add_plugin(JobsPlugin {
+            render: false,
+            ..JobsPlugin::default()
+        });
+        let snapshot = world.snapshot();
+        let names: Vec<&str> = snapshot.systems().into_iter().map(|system| system.rsplit("::").next().unwrap()).collect();
+        assert_eq!(names, vec!["MovementSystem", "WaitSystem", "JobSystem"]);
+    }
+
+    #[test]
+    fn test_render_village_grid() {
+        let sites = [("forest".to_string(), (0, 0))];
+        let workers = [("woodcutter".to_string(), (1, 0)), ("farmer".to_string(), (0, 0))];
+        let rows = render_village_grid(&sites, &workers);
+        assert!(rows[0].starts_with("F w ."));
+    }
+}
@@ -1,3 +1,15 @@
+pub mod combat;
+pub mod construction;
+pub mod demos;
 #[allow(clippy::module_inception)]
 pub mod game;
+pub mod jobs;
+pub mod needs;
+pub use combat::{Attack, CombatEvents, CombatPlugin, Corpse, DamageEvent, DeathEvent, FighterSpec, Health, Team, Weapon};
+pub use construction::{
+    place_construction_site, Blueprint, BuildingKind, CompletedBuildings, ConstructionPlugin, ConstructionSite, Hut, Inventory,
+};
+pub use demos::{Demo, DemoRegistry};
 pub use game::*;
+pub use jobs::{Job, JobStage, JobsPlugin, Site, Worker};
+pub use needs::{Activity, Births, Field, Needs, NeedsPlugin, Pantry, Villager};
@@ -0,0 +1,589 @@
+//! Villagers with needs, feeding a growing population.
+//!
+//! Every villager has `Needs` that decay each tick. A villager with nothing
+//! to do attends to its most urgent need once it falls below `URGENT_NEED`:
+//! it eats from the pantry of its home, or at the field if the pantry is
+//! empty, and rests at home. Otherwise it harvests food at the field and
+//! hauls it into the pantry. A home whose pantry holds surplus food spawns a
+//! new villager next to it.
+//!
+//! ```ignore
+//! world.add_plugin(NeedsPlugin::default());
+//! world.update_n(500);
+//! println!("{} villagers", world.entities_with_component::<Villager>().len());
+//! ```
+//!
+//! Births are reported by an observer of `Villager` additions into the
+//! `Births` resource.
+//!
+//! The three systems - `NeedsSystem`, `VillagerSystem` and `PopulationSystem` -
+//! plus the shared `MovementSystem` write overlapping components every frame,
+//! which makes the demo a stress test for scheduling, recording and replay
+//! analysis.
+
+use super::game::{
+    add_recorded, free_neighbour, is_adjacent, is_valid_position, run_demo_loop, Actor, Home, NavigationPlugin, Obstacle,
+    Position, Target, GRID_SIZE, HOME_POS,
+};
+use crate::{Diff, Entity, In, Plugin, ReadOnlySystem, ReadOnlyWorldView, Reflect, System, World, WorldView};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Value of a fully satisfied need
+pub const MAX_NEED: u32 = 100;
+/// Needs below this are attended to
+pub const URGENT_NEED: u32 = 40;
+const HUNGER_DECAY: u32 = 2;
+const REST_DECAY: u32 = 1;
+/// Rest regained per tick of resting
+const REST_RECOVERY: u32 = 10;
+const HARVEST_TICKS: u32 = 3;
+const FOOD_PER_HARVEST: u32 = 2;
+/// Food in a pantry above which a new villager is born
+pub const SURPLUS_FOOD: u32 = 8;
+/// Food a birth takes from the pantry
+const BIRTH_COST: u32 = 6;
+/// Villagers stop being born at this population
+pub const MAX_POPULATION: usize = 12;
+
+/// How satisfied an actor's needs are, from `MAX_NEED` down to 0
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Diff, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Needs {
+    /// How well fed the actor is
+    pub hunger: u32,
+    /// How rested the actor is
+    pub rest: u32,
+}
+
+impl Default for Needs {
+    fn default() -> Self {
+        Self {
+            hunger: MAX_NEED,
+            rest: MAX_NEED,
+        }
+    }
+}
+
+/// What a villager is doing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Diff, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Activity {
+    #[default]
+    Idle,
+    Harvesting,
+    Hauling,
+    Eating,
+    Resting,
+}
+
+/// An actor with needs living in the village
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Diff, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Villager {
+    pub activity: Activity,
+    /// Ticks spent harvesting so far
+    pub ticks: u32,
+    /// Food carried to the pantry
+    pub carrying: u32,
+}
+
+/// Food stored in a home
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Diff, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Pantry {
+    pub food: u32,
+}
+
+/// Where villagers harvest food
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Diff, Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Field;
+
+/// Villagers born so far, in birth order
+#[derive(Debug, Clone, Default)]
+pub struct Births(Rc<RefCell<Vec<Entity>>>);
+
+impl Births {
+    pub fn list(&self) -> Vec<Entity> {
+        self.0.borrow().clone()
+    }
+}
+
+/// Decays the needs of every actor each tick
+pub struct NeedsSystem;
+
+impl System for NeedsSystem {
+    type InComponents = (Needs,);
+    type OutComponents = (Needs,);
+
+    fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+    fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+        let mut changes = Vec::new();
+        for (entity, needs) in world.query_components::<(crate::Out<Needs>,)>() {
+            let old_needs = *needs;
+            needs.hunger = needs.hunger.saturating_sub(HUNGER_DECAY);
+            needs.rest = needs.rest.saturating_sub(REST_DECAY);
+            if *needs != old_needs {
+                changes.push((entity, old_needs, *needs));
+            }
+        }
+        for (entity, old_needs, new_needs) in changes {
+            world.record_component_modification(entity, &old_needs, &new_needs);
+        }
+    }
+
+    fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+}
+
+/// Home as seen by the VillagerSystem during one update
+struct HomeState {
+    entity: Entity,
+    position: (i32, i32),
+    pantry: Pantry,
+}
+
+/// Picks each villager's activity by its most urgent need and carries it out
+pub struct VillagerSystem;
+
+impl System for VillagerSystem {
+    type InComponents = (Position, Needs, Villager, Target, Pantry, Home, Field);
+    type OutComponents = (Needs, Villager, Target, Pantry);
+
+    fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+    fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+        let mut homes: Vec<HomeState> = world
+            .query_components::<(In<Position>, In<Home>, In<Pantry>)>()
+            .into_iter()
+            .map(|(entity, (position, _, pantry))| HomeState {
+                entity,
+                position: (position.x, position.y),
+                pantry: *pantry,
+            })
+            .collect();
+        let field = world
+            .query_components::<(In<Position>, In<Field>)>()
+            .into_iter()
+            .next()
+            .map(|(_, (position, _))| (position.x, position.y));
+        let villagers: Vec<_> = world
+            .query_components::<(In<Position>, In<Needs>, In<Villager>, In<Target>)>()
+            .into_iter()
+            .map(|(entity, (position, needs, villager, target))| {
+                (entity, (position.x, position.y), *needs, *villager, *target)
+            })
+            .collect();
+        let (Some(field), false) = (field, homes.is_empty()) else {
+            return;
+        };
+
+        for (entity, position, old_needs, old_villager, old_target) in villagers {
+            let mut needs = old_needs;
+            let mut villager = old_villager;
+            // Villagers live in the nearest home
+            let home = homes
+                .iter_mut()
+                .min_by_key(|home| (home.position.0 - position.0).abs().max((home.position.1 - position.1).abs()))
+                .expect("there is a home");
+            let at = |place: (i32, i32)| is_adjacent(position, place) || position == place;
+
+            if villager.activity == Activity::Idle {
+                villager.activity = if villager.carrying > 0 {
+                    Activity::Hauling
+                } else if needs.hunger.min(needs.rest) >= URGENT_NEED {
+                    Activity::Harvesting
+                } else if needs.hunger <= needs.rest {
+                    Activity::Eating
+                } else {
+                    Activity::Resting
+                };
+            }
+            let destination = match villager.activity {
+                Activity::Idle => position,
+                Activity::Harvesting => {
+                    if at(field) {
+                        villager.ticks += 1;
+                        if villager.ticks >= HARVEST_TICKS {
+                            villager.ticks = 0;
+                            villager.carrying += FOOD_PER_HARVEST;
+                            villager.activity = Activity::Hauling;
+                        }
+                    }
+                    field
+                }
+                Activity::Hauling => {
+                    if at(home.position) {
+                        home.pantry.food += villager.carrying;
+                        villager.carrying = 0;
+                        villager.activity = Activity::Idle;
+                    }
+                    home.position
+                }
+                // Eat from the pantry, or at the field while the pantry is empty
+                Activity::Eating if home.pantry.food > 0 => {
+                    if at(home.position) {
+                        home.pantry.food -= 1;
+                        needs.hunger = MAX_NEED;
+                        villager.activity = Activity::Idle;
+                    }
+                    home.position
+                }
+                Activity::Eating => {
+                    if at(field) {
+                        needs.hunger = MAX_NEED;
+                        villager.activity = Activity::Idle;
+                    }
+                    field
+                }
+                Activity::Resting => {
+                    if at(home.position) {
+                        needs.rest = (needs.rest + REST_RECOVERY).min(MAX_NEED);
+                        if needs.rest == MAX_NEED {
+                            villager.activity = Activity::Idle;
+                        }
+                    }
+                    home.position
+                }
+            };
+            let target = Target {
+                x: destination.0,
+                y: destination.1,
+            };
+
+            if needs != old_needs {
+                write(world, entity, old_needs, needs);
+            }
+            if villager != old_villager {
+                write(world, entity, old_villager, villager);
+            }
+            if target != old_target {
+                write(world, entity, old_target, target);
+            }
+        }
+
+        for home in homes {
+            if let Some(pantry) = world.get_component::<Pantry>(home.entity).copied() {
+                if pantry != home.pantry {
+                    write(world, home.entity, pantry, home.pantry);
+                }
+            }
+        }
+    }
+
+    fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+}
+
+/// Replace a component of an entity, recording the modification
+fn write<T, I, O>(world: &mut WorldView<I, O>, entity: Entity, old: T, new: T)
+where
+    T: Diff + Clone + std::fmt::Debug + 'static,
+{
+    if let Some(component) = world.get_component_mut::<T>(entity) {
+        *component = new.clone();
+        world.record_component_modification(entity, &old, &new);
+    }
+}
+
+/// Spawns a villager next to every home with surplus food
+pub struct PopulationSystem;
+
+impl System for PopulationSystem {
+    type InComponents = (Position, Home, Pantry, Villager);
+    type OutComponents = (Pantry, Position, Target, Actor, Needs, Villager);
+
+    fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+    fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+        let mut population = world.query_components::<(In<Villager>,)>().len();
+        let mut occupied: Vec<(i32, i32)> = world
+            .query_components::<(In<Position>,)>()
+            .into_iter()
+            .map(|(_, position)| (position.x, position.y))
+            .collect();
+        let homes: Vec<(Entity, (i32, i32), Pantry)> = world
+            .query_components::<(In<Position>, In<Home>, In<Pantry>)>()
+            .into_iter()
+            .map(|(entity, (position, _, pantry))| (entity, (position.x, position.y), *pantry))
+            .collect();
+
+        for (home, position, pantry) in homes {
+            if pantry.food < SURPLUS_FOOD || population >= MAX_POPULATION {
+                continue;
+            }
+            let Some((x, y)) = free_neighbour(&occupied, position) else {
+                continue;
+            };
+            occupied.push((x, y));
+            population += 1;
+            write(world, home, pantry, Pantry { food: pantry.food - BIRTH_COST });
+
+            let villager = world.create_entity();
+            world.record_entity_creation(villager);
+            add_recorded(world, villager, Position { x, y });
+            add_recorded(world, villager, Target { x, y });
+            add_recorded(world, villager, Actor);
+            add_recorded(world, villager, Needs::default());
+            add_recorded(world, villager, Villager::default());
+        }
+    }
+
+    fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+}
+
+/// Draws the village and its population every frame
+pub struct NeedsRenderSystem;
+
+impl ReadOnlySystem for NeedsRenderSystem {
+    type InComponents = (Position, Home, Pantry, Field, Villager, Needs);
+
+    fn update(&mut self, world: &ReadOnlyWorldView<Self::InComponents>) {
+        // Clear screen
+        print!("\x1B[2J\x1B[1;1H");
+
+        println!("Village - villagers harvesting, eating and resting");
+        println!("H = Home, F = Field, v = Villager");
+        println!();
+        for row in village_grid(world) {
+            println!("{}", row);
+        }
+        println!();
+        println!("{}", population_summary(world));
+    }
+}
+
+/// Render the village grid from the components currently in the world, one
+/// string per row
+pub fn render_needs_grid(world: &World) -> Vec<String> {
+    village_grid(&ReadOnlyWorldView::<()>::new(world))
+}
+
+fn village_grid<I>(world: &ReadOnlyWorldView<I>) -> Vec<String> {
+    let mut grid = vec![vec!['.'; GRID_SIZE as usize]; GRID_SIZE as usize];
+    for entity in world.entities_with_component::<Position>() {
+        let Some(position) = world.get_component::<Position>(entity) else {
+            continue;
+        };
+        let symbol = if world.get_component::<Home>(entity).is_some() {
+            'H'
+        } else if world.get_component::<Field>(entity).is_some() {
+            'F'
+        } else if world.get_component::<Villager>(entity).is_some() {
+            'v'
+        } else {
+            'A'
+        };
+        if is_valid_position((position.x, position.y)) {
+            grid[position.y as usize][position.x as usize] = symbol;
+        }
+    }
+    grid.iter()
+        .map(|row| row.iter().map(|cell| format!("{} ", cell)).collect::<String>().trim_end().to_string())
+        .collect()
+}
+
+/// Summarize population, stored food and average needs, e.g.
+/// "Villagers: 4, food: 3, hunger: 61, rest: 72"
+pub fn village_summary(world: &World) -> String {
+    population_summary(&ReadOnlyWorldView::<()>::new(world))
+}
+
+fn population_summary<I>(world: &ReadOnlyWorldView<I>) -> String {
+    let needs: Vec<Needs> = world
+        .entities_with_component::<Villager>()
+        .into_iter()
+        .filter_map(|entity| world.get_component::<Needs>(entity).copied())
+        .collect();
+    let food: u32 = world
+        .entities_with_component::<Pantry>()
+        .into_iter()
+        .filter_map(|entity| world.get_component::<Pantry>(entity))
+        .map(|pantry| pantry.food)
+        .sum();
+    let average = |need: fn(&Needs) -> u32| match needs.len() {
+        0 => 0,
+        count => needs.iter().map(need).sum::<u32>() / count as u32,
+    };
+    format!(
+        "Villagers: {}, food: {}, hunger: {}, rest: {}",
+        needs.len(),
+        food,
+        average(|needs| needs.hunger),
+        average(|needs| needs.rest)
+    )
+}
+
+/// Register the needs components for replay
+pub fn register_needs_components(world: &mut World) {
+    world.register_replay_component::<Home>();
+    world.register_replay_component::<Needs>();
+    world.register_replay_component::<Villager>();
+    world.register_replay_component::<Pantry>();
+    world.register_replay_component::<Field>();
+}
+
+/// Spawns a home with a pantry, a field and villagers, and adds the needs
+/// systems, with the shared `NavigationPlugin` moving the villagers
+pub struct NeedsPlugin {
+    pub home: (i32, i32),
+    /// Food in the home's pantry at the start
+    pub pantry_food: u32,
+    pub field: (i32, i32),
+    /// Positions of the villagers at the start
+    pub villagers: Vec<(i32, i32)>,
+    /// Add the NeedsRenderSystem drawing the grid every frame, in the "rendering" group
+    pub render: bool,
+}
+
+impl Default for NeedsPlugin {
+    /// Three villagers, their home on the game's home cell and a field across the grid
+    fn default() -> Self {
+        Self {
+            home: HOME_POS,
+            pantry_food: 3,
+            field: (8, 2),
+            villagers: vec![(3, 4), (5, 6), (2, 8)],
+            render: true,
+        }
+    }
+}
+
+impl Plugin for NeedsPlugin {
+    fn build(&self, world: &mut World) {
+        register_needs_components(world);
+
+        let home = world.create_entity();
+        world.add_component(home, Position { x: self.home.0, y: self.home.1 });
+        world.add_component(home, Home);
+        world.add_component(home, Pantry { food: self.pantry_food });
+        world.add_component(home, Obstacle);
+        let field = world.create_entity();
+        world.add_component(field, Position { x: self.field.0, y: self.field.1 });
+        world.add_component(field, Field);
+        world.add_component(field, Obstacle);
+        for &(x, y) in &self.villagers {
+            let villager = world.create_entity();
+            world.add_component(villager, Position { x, y });
+            world.add_component(villager, Target { x, y });
+            world.add_component(villager, Actor);
+            world.add_component(villager, Needs::default());
+            world.add_component(villager, Villager::default());
+        }
+        let births = Births::default();
+        let observed = births.clone();
+        world.observe::<Villager>(move |entity, old, new| {
+            if let (None, Some(_)) = (old, new) {
+                ecs_info!("Villager {} born", entity);
+                observed.0.borrow_mut().push(entity);
+            }
+        });
+        world.insert_resource(births);
+
+        world.add_plugin(NavigationPlugin);
+        world.register_system_factory(|| NeedsSystem);
+        world.register_system_factory(|| VillagerSystem);
+        world.register_system_factory(|| PopulationSystem);
+        world.add_system(NeedsSystem);
+        world.add_system(VillagerSystem);
+        world.add_system(PopulationSystem);
+        if self.render {
+            world.add_system_to_group("rendering", NeedsRenderSystem);
+        }
+    }
+}
+
+/// Run the needs demo at 2 ticks per second, logging the session to `game_logs/`
+pub fn run_needs_game() {
+    println!("Starting Village Needs...");
+    println!("Villagers harvest food, eat, rest and grow in number");
+    println!("Press Ctrl+C to stop the simulation");
+
+    let mut world = World::new();
+    world.add_plugin(NeedsPlugin::default());
+    world.initialize_systems();
+    run_demo_loop(&mut world, "needs");
+    println!("Village stopped after {} updates with {}", world.frame_number(), village_summary(&world));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn village() -> World {
+        let mut world = World::new();
+        world.set_strict_access(true);
+        world.add_plugin(NeedsPlugin {
+            render: false,
+            ..NeedsPlugin::default()
+        });
+        world
+    }
+
+    #[test]
+    fn test_villagers_attend_to_their_most_urgent_need() {
+        let mut world = village();
+        let villager = world.entities_with_component::<Villager>()[0];
+        world.update();
+        assert_eq!(world.get_component::<Villager>(villager).unwrap().activity, Activity::Harvesting);
+        assert_eq!(
+            world.get_component::<Needs>(villager),
+            Some(&Needs {
+                hunger: MAX_NEED - HUNGER_DECAY,
+                rest: MAX_NEED - REST_DECAY
+            })
+        );
+
+        // Hunger decays faster, so the villager eats before it rests
+        let mut lowest = Needs::default();
+        let mut attended = Vec::new();
+        for _ in 0..120 {
+            let before = *world.get_component::<Needs>(villager).unwrap();
+            world.update();
+            let needs = *world.get_component::<Needs>(villager).unwrap();
+            if needs.hunger > before.hunger {
+                attended.push("eat");
+            }
+            if needs.rest > before.rest && attended.last() != Some(&"rest") {
+                attended.push("rest");
+            }
+            lowest.hunger = lowest.hunger.min(needs.hunger);
+            lowest.rest = lowest.rest.min(needs.rest);
+        }
+        assert_eq!(attended[..3], ["eat", "rest", "eat"]);
+        assert!(lowest.hunger > 0 && lowest.rest > 0, "{:?}", lowest);
+    }
+
+    #[test]
+    fn test_surplus_food_grows_the_population() {
+        let mut world = village();
+        world.update_n(400);
+        let population = world.entities_with_component::<Villager>().len();
+        assert!(population > 3 && population <= MAX_POPULATION, "{}", village_summary(&world));
+        assert_eq!(world.resource::<Births>().unwrap().list().len(), population - 3);
+        let failures: Vec<&str> = world
+            .get_update_history()
+            .updates()
+            .iter()
+            .flat_map(|update| update.system_diffs().iter().filter_map(|diff| diff.failure()))
+            .collect();
+        assert!(failures.is_empty(), "{:?}", failures);
+    }
+
+    #[test]
+    fn test_needs_replay_from_the_log() {
+        let mut world = village();
+        let initial_state = world.snapshot();
+        world.reset_history();
+        world.update_n(300);
+        let mut history = world.get_update_history().clone();
+        history.set_initial_state(initial_state);
+
+        let mut replayed = super::super::world_from_replay(&history).unwrap();
+        for update in history.updates() {
+            replayed.apply_update_diff(update);
+        }
+        assert_eq!(render_needs_grid(&replayed), render_needs_grid(&world));
+        assert_eq!(village_summary(&replayed), village_summary(&world));
+    }
+}
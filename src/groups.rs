@@ -0,0 +1,245 @@
+//! Named groups of entities.
+//!
+//! Groups tag entities without adding components, e.g. to tell enemies from
+//! allies. Membership changes are world operations, so they are recorded in
+//! the history and replay logs (`ADD_TO_GROUP Entity(0, 3) enemies`) and
+//! applied again by replays:
+//!
+//! ```ignore
+//! world.add_to_group(entity, "enemies");
+//! let enemies = world.group("enemies");
+//! for (entity, health) in enemies.filter(world_view.query_components::<(Out<Health>,)>()) {
+//!     // ...
+//! }
+//! ```
+//!
+//! Removed entities leave all their groups.
+
+use crate::time_travel::EntityFilter;
+use crate::{Entity, SystemUpdateDiff, World, WorldOperation, WorldUpdateDiff, WorldView};
+use std::collections::BTreeSet;
+
+/// Members of a group at the time it was read
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EntityGroup {
+    entities: BTreeSet<Entity>,
+}
+
+impl EntityGroup {
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.entities.contains(&entity)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    /// Iterate over the members in entity order
+    pub fn iter(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.entities.iter().copied()
+    }
+
+    /// Keep the query results of group members
+    pub fn filter<T>(&self, results: Vec<(Entity, T)>) -> Vec<(Entity, T)> {
+        results.into_iter().filter(|(entity, _)| self.contains(*entity)).collect()
+    }
+}
+
+/// Lets a group select the entities of watches
+impl From<EntityGroup> for EntityFilter {
+    fn from(group: EntityGroup) -> Self {
+        EntityFilter::Entities(group.entities.into_iter().collect())
+    }
+}
+
+impl World {
+    /// Add an entity to a group, recording the change in the history.
+    /// Returns false if the entity already was a member.
+    pub fn add_to_group(&mut self, entity: Entity, group: &str) -> bool {
+        let added = self.insert_into_group(entity, group);
+        if added {
+            self.record_standalone_operation(WorldOperation::AddToGroup(entity, group.to_string()));
+        }
+        added
+    }
+
+    /// Remove an entity from a group, recording the change in the history.
+    /// Returns false if the entity was not a member.
+    pub fn remove_from_group(&mut self, entity: Entity, group: &str) -> bool {
+        let removed = self.take_from_group(entity, group);
+        if removed {
+            self.record_standalone_operation(WorldOperation::RemoveFromGroup(entity, group.to_string()));
+        }
+        removed
+    }
+
+    /// Get the members of a group, empty if nothing was ever added to it
+    pub fn group(&self, group: &str) -> EntityGroup {
+        EntityGroup {
+            entities: self.groups.get(group).cloned().unwrap_or_default(),
+        }
+    }
+
+    /// Check if an entity is a member of a group
+    pub fn is_in_group(&self, entity: Entity, group: &str) -> bool {
+        self.groups.get(group).is_some_and(|members| members.contains(&entity))
+    }
+
+    /// Get the names of the groups an entity is a member of, sorted by name
+    pub fn groups_of(&self, entity: Entity) -> Vec<&str> {
+        self.groups
+            .iter()
+            .filter(|(_, members)| members.contains(&entity))
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    /// Add an entity to a group without recording it
+    pub(crate) fn insert_into_group(&mut self, entity: Entity, group: &str) -> bool {
+        match self.groups.get_mut(group) {
+            Some(members) => members.insert(entity),
+            None => {
+                self.groups.insert(group.to_string(), BTreeSet::from([entity]));
+                true
+            }
+        }
+    }
+
+    /// Remove an entity from a group without recording it
+    pub(crate) fn take_from_group(&mut self, entity: Entity, group: &str) -> bool {
+        let Some(members) = self.groups.get_mut(group) else {
+            return false;
+        };
+        let removed = members.remove(&entity);
+        if members.is_empty() {
+            self.groups.remove(group);
+        }
+        removed
+    }
+
+    /// Remove a removed entity from every group
+    pub(crate) fn leave_all_groups(&mut self, entity: Entity) {
+        self.groups.retain(|_, members| {
+            members.remove(&entity);
+            !members.is_empty()
+        });
+    }
+
+    /// Record a world operation made outside of systems as an update of its own
+    pub(crate) fn record_standalone_operation(&mut self, operation: WorldOperation) {
+        let mut world_diff = WorldUpdateDiff::new();
+        let mut system_diff = SystemUpdateDiff::new();
+        system_diff.record_world_operation(operation);
+        world_diff.record(system_diff);
+        self.world_update_history.record(world_diff);
+    }
+}
+
+impl<I, O> WorldView<I, O> {
+    /// Add an entity to a group, recording the change in the system's diff
+    pub fn add_to_group(&mut self, entity: Entity, group: &str) -> bool {
+        let added = unsafe { self.world_mut() }.insert_into_group(entity, group);
+        if added {
+            self.system_diff
+                .record_world_operation(WorldOperation::AddToGroup(entity, group.to_string()));
+        }
+        added
+    }
+
+    /// Remove an entity from a group, recording the change in the system's diff
+    pub fn remove_from_group(&mut self, entity: Entity, group: &str) -> bool {
+        let removed = unsafe { self.world_mut() }.take_from_group(entity, group);
+        if removed {
+            self.system_diff
+                .record_world_operation(WorldOperation::RemoveFromGroup(entity, group.to_string()));
+        }
+        removed
+    }
+
+    /// Get the members of a group
+    pub fn group(&self, group: &str) -> EntityGroup {
+        unsafe { self.world() }.group(group)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay_analysis::{parse_replay_log_str, validate_replay_log_str, write_replay_log};
+    use crate::{Out, System};
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Health(i32);
+
+    /// Damages enemies and moves those out of health to the "defeated" group
+    struct DamageSystem;
+
+    impl System for DamageSystem {
+        type InComponents = ();
+        type OutComponents = (Health,);
+
+        fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+        fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+            let enemies = world.group("enemies");
+            let mut defeated = Vec::new();
+            for (entity, health) in enemies.filter(world.query_components::<(Out<Health>,)>()) {
+                health.0 -= 5;
+                if health.0 <= 0 {
+                    defeated.push(entity);
+                }
+            }
+            for entity in defeated {
+                world.remove_from_group(entity, "enemies");
+                world.add_to_group(entity, "defeated");
+            }
+        }
+
+        fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    }
+
+    #[test]
+    fn test_group_changes_are_recorded_and_replayed() {
+        let mut world = World::new();
+        let entities: Vec<Entity> = [5, 10, 15]
+            .into_iter()
+            .map(|health| {
+                let entity = world.create_entity();
+                world.add_component(entity, Health(health));
+                entity
+            })
+            .collect();
+        world.add_to_group(entities[0], "enemies");
+        world.add_to_group(entities[1], "enemies");
+        assert!(!world.add_to_group(entities[1], "enemies"));
+        world.add_system(DamageSystem);
+        world.update();
+
+        assert_eq!(world.group("enemies").iter().collect::<Vec<_>>(), vec![entities[1]]);
+        assert_eq!(world.groups_of(entities[0]), vec!["defeated"]);
+        assert_eq!(world.get_component::<Health>(entities[2]), Some(&Health(15)));
+
+        let mut log = Vec::new();
+        write_replay_log(world.get_update_history(), &mut log).unwrap();
+        let log = String::from_utf8(log).unwrap();
+        assert!(log.contains("REMOVE_FROM_GROUP Entity(0, 0) enemies"), "{}", log);
+        assert!(validate_replay_log_str(&log).is_empty(), "{:?}", validate_replay_log_str(&log));
+
+        let mut replayed = World::new();
+        for _ in &entities {
+            replayed.create_entity();
+        }
+        for update in parse_replay_log_str(&log).updates() {
+            replayed.apply_update_diff(update);
+        }
+        assert_eq!(replayed.group("enemies"), world.group("enemies"));
+        assert_eq!(replayed.group("defeated"), world.group("defeated"));
+
+        world.remove_entity(entities[1]);
+        assert!(world.group("enemies").is_empty());
+    }
+}
@@ -0,0 +1,222 @@
+//! Introspection of single entities, for inspectors and debug printing.
+//!
+//! `components_of` lists every typed component of an entity, whether or not
+//! its type is registered for reflection. Registered components can be read
+//! as `Value`s, the others downcast to their type:
+//!
+//! ```ignore
+//! for component in world.components_of(entity) {
+//!     match component.to_value() {
+//!         Some(value) => println!("{} {}", component.type_name(), value),
+//!         None => println!("{}", component.type_name()),
+//!     }
+//! }
+//! print!("{}", world.debug_entity(entity));
+//! ```
+//!
+//! `debug_entity` also prints the entity's dynamic components, groups and
+//! relations. Values of unregistered types cannot be printed, so only their
+//! type names are.
+
+use crate::reflect::{ComponentRegistration, Value};
+use crate::{Entity, World};
+use std::any::{Any, TypeId};
+use std::fmt::Write;
+
+/// A component of an entity, listed by `World::components_of`
+#[derive(Clone, Copy)]
+pub struct ComponentRef<'w> {
+    type_id: TypeId,
+    type_name: &'static str,
+    component: &'w dyn Any,
+    registration: Option<&'w ComponentRegistration>,
+}
+
+impl<'w> ComponentRef<'w> {
+    pub fn type_id(&self) -> TypeId {
+        self.type_id
+    }
+
+    /// Get the short type name, e.g. "Position"
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    pub fn as_any(&self) -> &'w dyn Any {
+        self.component
+    }
+
+    /// Get the component as a T, None if it is of another type
+    pub fn downcast_ref<T: 'static>(&self) -> Option<&'w T> {
+        self.component.downcast_ref::<T>()
+    }
+
+    /// Check if the type is registered for reflection
+    pub fn is_reflected(&self) -> bool {
+        self.registration.is_some()
+    }
+
+    /// Get the component as a value, None if its type is not registered
+    pub fn to_value(&self) -> Option<Value> {
+        self.registration?.to_value(self.component)
+    }
+}
+
+impl std::fmt::Debug for ComponentRef<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ComponentRef")
+            .field("type_name", &self.type_name)
+            .field("value", &self.to_value())
+            .finish()
+    }
+}
+
+impl World {
+    /// Iterate over the typed components of an entity, sorted by type name
+    pub fn components_of(&self, entity: Entity) -> impl Iterator<Item = ComponentRef<'_>> {
+        let mut components: Vec<ComponentRef<'_>> = self
+            .components
+            .iter()
+            .filter_map(|(type_id, column)| {
+                let registration = self.component_registry.get_by_type_id(*type_id);
+                Some(ComponentRef {
+                    type_id: *type_id,
+                    type_name: self.component_type_name(*type_id),
+                    component: column.get(entity)?,
+                    registration,
+                })
+            })
+            .collect();
+        components.sort_by_key(|component| component.type_name);
+        components.into_iter()
+    }
+
+    /// Describe an entity with one line per component, dynamic component,
+    /// group and relation, e.g.
+    ///
+    /// ```text
+    /// Entity(0, 3)
+    ///   Health {current: 3, max: 5}
+    ///   Position
+    ///   Mana {current: 4} (dynamic)
+    ///   group enemies
+    ///   Targets Entity(0, 1)
+    /// ```
+    pub fn debug_entity(&self, entity: Entity) -> String {
+        if !self.entity_exists(entity) {
+            return format!("{} does not exist\n", entity);
+        }
+        let mut text = entity.to_string();
+        if self.disabled_entities.contains(&entity) {
+            text.push_str(" (disabled)");
+        }
+        text.push('\n');
+        for component in self.components_of(entity) {
+            match component.to_value() {
+                Some(value) => writeln!(text, "  {} {}", component.type_name(), value),
+                None => writeln!(text, "  {}", component.type_name()),
+            }
+            .unwrap();
+        }
+        for (name, owner, value) in self.dynamic.iter() {
+            if owner == entity {
+                writeln!(text, "  {} {} (dynamic)", name, value).unwrap();
+            }
+        }
+        for group in self.groups_of(entity) {
+            writeln!(text, "  group {}", group).unwrap();
+        }
+        for (relation, links) in &self.relations {
+            for (_, target) in links.iter().filter(|(source, _)| *source == entity) {
+                writeln!(text, "  {} {}", relation, target).unwrap();
+            }
+        }
+        text
+    }
+
+    /// Get the short name of a component type, from its registration or the
+    /// `add_component` call that stored it first
+    pub(crate) fn component_type_name(&self, type_id: TypeId) -> &'static str {
+        self.component_registry
+            .get_by_type_id(type_id)
+            .map(|registration| registration.type_name())
+            .or_else(|| self.component_type_names.get(&type_id).copied())
+            .unwrap_or("<unnamed>")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dynamic::{ComponentSchema, FieldType};
+    use crate::Reflect;
+
+    #[derive(Debug, Clone, PartialEq, Reflect)]
+    struct Health {
+        current: i32,
+        max: i32,
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Position(i32, i32);
+
+    struct Enemy;
+    struct Targets;
+
+    #[test]
+    fn test_components_of_lists_every_component() {
+        let mut world = World::new();
+        world.register_component::<Health>();
+        let entity = world.create_entity();
+        let other = world.create_entity();
+        world.add_component(entity, Position(1, 2));
+        world.add_component(entity, Health { current: 3, max: 5 });
+        world.add_component(entity, Enemy);
+        world.add_component(other, Position(0, 0));
+
+        let components: Vec<ComponentRef> = world.components_of(entity).collect();
+        let names: Vec<&str> = components.iter().map(|component| component.type_name()).collect();
+        assert_eq!(names, vec!["Enemy", "Health", "Position"]);
+        assert_eq!(components[2].downcast_ref::<Position>(), Some(&Position(1, 2)));
+        assert!(components[2].to_value().is_none());
+        assert!(components[1].is_reflected());
+        assert_eq!(components[1].to_value().unwrap().to_string(), "{current: 3, max: 5}");
+        assert_eq!(world.components_of(other).count(), 1);
+    }
+
+    #[test]
+    fn test_boxed_components_record_their_type_name() {
+        let mut world = World::new();
+        world.register_component::<Health>();
+        let entity = world.create_entity();
+        let health = Value::Map([("current".to_string(), Value::Int(3)), ("max".to_string(), Value::Int(5))].into());
+        world.insert_component_value(entity, "Health", &health).unwrap();
+        assert_eq!(world.component_type_names.get(&TypeId::of::<Health>()), Some(&"Health"));
+        assert_eq!(world.get_component::<Health>(entity), Some(&Health { current: 3, max: 5 }));
+    }
+
+    #[test]
+    fn test_debug_entity() {
+        let mut world = World::new();
+        world.register_component::<Health>();
+        world.register_schema(ComponentSchema::new("Mana").field("current", FieldType::Int));
+        let entity = world.create_entity();
+        let prey = world.create_entity();
+        world.add_component(entity, Health { current: 3, max: 5 });
+        world.add_component(entity, Position(1, 2));
+        world.add_dyn_component(entity, "Mana", Value::Map([("current".to_string(), Value::Int(4))].into())).unwrap();
+        world.add_to_group(entity, "enemies");
+        world.relate::<Targets>(entity, prey);
+        world.set_enabled(entity, false);
+
+        assert_eq!(
+            world.debug_entity(entity),
+            format!(
+                "{} (disabled)\n  Health {{current: 3, max: 5}}\n  Position\n  Mana {{current: 4}} (dynamic)\n  group enemies\n  Targets {}\n",
+                entity, prey
+            )
+        );
+        world.remove_entity(prey);
+        assert_eq!(world.debug_entity(prey), format!("{} does not exist\n", prey));
+    }
+}
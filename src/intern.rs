@@ -0,0 +1,577 @@
+//! Shared storage of the strings recorded in update histories.
+//!
+//! Long sessions record the same few component type names and encoded values
+//! (`Actor`, `()`, `(x:Some(3),y:None)`) over and over. The names and data of
+//! component changes are `InternedStr`s, and `WorldUpdateHistory` keeps one
+//! copy of every distinct string it records; the changes share it. How much
+//! that saves is reported by `WorldUpdateHistory::memory_stats`:
+//!
+//! ```ignore
+//! let stats = world.get_update_history().memory_stats();
+//! println!("{} of {} string bytes stored", stats.interned_bytes, stats.string_bytes);
+//! ```
+//!
+//! With the `serde` feature, `CompactHistory` is a smaller form of a history
+//! for binary encodings: every distinct string is stored once in a table that
+//! the changes refer to by index, and the entities of a system's changes are
+//! stored as the difference to the previous change's entity.
+
+use crate::{DiffComponentChange, SystemUpdateDiff, WorldUpdateHistory};
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// Immutable string whose clones share one allocation
+#[derive(Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct InternedStr(Arc<str>);
+
+impl InternedStr {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Check if two strings share their allocation
+    pub fn ptr_eq(&self, other: &InternedStr) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl std::ops::Deref for InternedStr {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for InternedStr {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::borrow::Borrow<str> for InternedStr {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for InternedStr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::fmt::Debug for InternedStr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(&*self.0, f)
+    }
+}
+
+impl From<&str> for InternedStr {
+    fn from(value: &str) -> Self {
+        Self(value.into())
+    }
+}
+
+impl From<String> for InternedStr {
+    fn from(value: String) -> Self {
+        Self(value.into())
+    }
+}
+
+impl From<&String> for InternedStr {
+    fn from(value: &String) -> Self {
+        Self(value.as_str().into())
+    }
+}
+
+impl PartialEq<str> for InternedStr {
+    fn eq(&self, other: &str) -> bool {
+        &*self.0 == other
+    }
+}
+
+impl PartialEq<&str> for InternedStr {
+    fn eq(&self, other: &&str) -> bool {
+        &*self.0 == *other
+    }
+}
+
+impl PartialEq<String> for InternedStr {
+    fn eq(&self, other: &String) -> bool {
+        *self.0 == **other
+    }
+}
+
+/// Serialized as a plain string
+#[cfg(feature = "serde")]
+impl serde::Serialize for InternedStr {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for InternedStr {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Self::from)
+    }
+}
+
+/// Set of the distinct strings of a history
+#[derive(Debug, Clone, Default)]
+pub(crate) struct StringInterner {
+    strings: HashSet<InternedStr>,
+}
+
+impl StringInterner {
+    /// Replace `string` with the stored copy of its text, storing it if it is new
+    pub(crate) fn intern(&mut self, string: &mut InternedStr) {
+        match self.strings.get(string.as_str()) {
+            Some(stored) => *string = stored.clone(),
+            None => {
+                self.strings.insert(string.clone());
+            }
+        }
+    }
+
+    /// Intern the type names and data of the changes in a system diff
+    pub(crate) fn intern_system_diff(&mut self, system_diff: &mut SystemUpdateDiff) {
+        for change in &mut system_diff.component_changes {
+            match change {
+                DiffComponentChange::Added { type_name, data, .. } => {
+                    self.intern(type_name);
+                    self.intern(data);
+                }
+                DiffComponentChange::Modified { type_name, diff, .. } => {
+                    self.intern(type_name);
+                    self.intern(diff);
+                }
+                DiffComponentChange::Removed { type_name, .. } => self.intern(type_name),
+            }
+        }
+    }
+}
+
+/// Memory used by the recorded changes of a history
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HistoryMemoryStats {
+    pub updates: usize,
+    pub component_changes: usize,
+    pub world_operations: usize,
+    /// Bytes of the changes' type names and data if each change had its own copy
+    pub string_bytes: usize,
+    /// Bytes of the distinct strings actually stored
+    pub interned_bytes: usize,
+    /// Number of distinct strings stored
+    pub unique_strings: usize,
+    /// Estimated heap size of the updates, including the stored strings
+    pub estimated_bytes: usize,
+}
+
+impl HistoryMemoryStats {
+    /// Bytes saved by sharing repeated strings
+    pub fn saved_bytes(&self) -> usize {
+        self.string_bytes.saturating_sub(self.interned_bytes)
+    }
+}
+
+impl WorldUpdateHistory {
+    /// Measure the memory used by the recorded updates, initial state and keyframes
+    pub fn memory_stats(&self) -> HistoryMemoryStats {
+        let mut stats = HistoryMemoryStats {
+            updates: self.len(),
+            ..HistoryMemoryStats::default()
+        };
+        let mut stored: HashSet<*const u8> = HashSet::new();
+        let mut count_string = |stats: &mut HistoryMemoryStats, string: &InternedStr| {
+            stats.string_bytes += string.len();
+            if stored.insert(string.as_ptr()) {
+                stats.interned_bytes += string.len();
+                stats.unique_strings += 1;
+            }
+        };
+
+        let mut system_diffs: Vec<&SystemUpdateDiff> = Vec::new();
+        for update in self.updates() {
+            stats.estimated_bytes += std::mem::size_of_val(update)
+                + std::mem::size_of_val(update.system_diffs());
+            system_diffs.extend(update.system_diffs());
+        }
+        system_diffs.extend(self.initial_state().map(|state| &state.diff));
+        system_diffs.extend(self.keyframes().iter().map(|keyframe| &keyframe.state.diff));
+
+        for system_diff in system_diffs {
+            stats.component_changes += system_diff.component_changes().len();
+            stats.world_operations += system_diff.world_operations().len();
+            stats.estimated_bytes += std::mem::size_of_val(system_diff.component_changes())
+                + std::mem::size_of_val(system_diff.world_operations());
+            for change in system_diff.component_changes() {
+                match change {
+                    DiffComponentChange::Added { type_name, data, .. } => {
+                        count_string(&mut stats, type_name);
+                        count_string(&mut stats, data);
+                    }
+                    DiffComponentChange::Modified { type_name, diff, .. } => {
+                        count_string(&mut stats, type_name);
+                        count_string(&mut stats, diff);
+                    }
+                    DiffComponentChange::Removed { type_name, .. } => count_string(&mut stats, type_name),
+                }
+            }
+        }
+        stats.estimated_bytes += stats.interned_bytes;
+        stats
+    }
+
+    /// Share the repeated strings of the history, e.g. after deserializing it
+    pub fn intern_strings(&mut self) {
+        let mut interner = std::mem::take(&mut self.interner);
+        for update in &mut self.updates {
+            for system_diff in &mut update.system_diffs {
+                interner.intern_system_diff(system_diff);
+            }
+        }
+        if let Some(state) = &mut self.initial_state {
+            interner.intern_system_diff(&mut state.diff);
+        }
+        for keyframe in &mut self.keyframes {
+            interner.intern_system_diff(&mut keyframe.state.diff);
+        }
+        self.interner = interner;
+    }
+}
+
+#[cfg(feature = "serde")]
+pub use compact::CompactHistory;
+
+#[cfg(feature = "serde")]
+mod compact {
+    use super::InternedStr;
+    use crate::replay_metadata::{ReplayAnnotation, ReplayMetadata};
+    use crate::snapshot::{Keyframe, WorldSnapshot};
+    use crate::{DiffComponentChange, Entity, SystemUpdateDiff, WorldOperation, WorldUpdateDiff, WorldUpdateHistory};
+    use serde::{Deserialize, Serialize};
+    use std::collections::HashMap;
+
+    /// History with its strings in a shared table and delta encoded entities
+    #[derive(Debug, Clone, Default, Serialize, Deserialize)]
+    pub struct CompactHistory {
+        strings: Vec<String>,
+        updates: Vec<CompactUpdate>,
+        initial_state: Option<CompactSnapshot>,
+        metadata: ReplayMetadata,
+        keyframes: Vec<CompactKeyframe>,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    struct CompactUpdate {
+        system_diffs: Vec<CompactSystemDiff>,
+        annotations: Vec<ReplayAnnotation>,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    struct CompactSystemDiff {
+        component_changes: Vec<CompactChange>,
+        world_operations: Vec<WorldOperation>,
+        failure: Option<String>,
+        error: Option<String>,
+        #[serde(default)]
+        stats: Option<crate::SystemStats>,
+        #[serde(default)]
+        causes: Vec<crate::WriteCause>,
+        #[serde(default)]
+        failed_assertions: Vec<String>,
+        #[serde(default)]
+        change_sequence: Vec<u64>,
+        #[serde(default)]
+        operation_sequence: Vec<u64>,
+    }
+
+    /// Component change with string table indices. `entity` is the difference
+    /// between the entity index and the one of the previous change.
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    enum CompactChange {
+        Added { world: usize, entity: i64, type_name: u32, data: u32 },
+        Modified { world: usize, entity: i64, type_name: u32, diff: u32 },
+        Removed { world: usize, entity: i64, type_name: u32 },
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    struct CompactSnapshot {
+        diff: CompactSystemDiff,
+        resources: Vec<(String, String)>,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    struct CompactKeyframe {
+        frame: usize,
+        update_index: usize,
+        state: CompactSnapshot,
+    }
+
+    /// Builds the string table while encoding
+    #[derive(Default)]
+    struct Encoder {
+        strings: Vec<String>,
+        indices: HashMap<InternedStr, u32>,
+    }
+
+    impl Encoder {
+        fn string(&mut self, string: &InternedStr) -> u32 {
+            if let Some(&index) = self.indices.get(string) {
+                return index;
+            }
+            let index = self.strings.len() as u32;
+            self.strings.push(string.to_string());
+            self.indices.insert(string.clone(), index);
+            index
+        }
+
+        fn system_diff(&mut self, system_diff: &SystemUpdateDiff) -> CompactSystemDiff {
+            let mut previous = 0i64;
+            let mut entity_delta = |entity: &Entity| {
+                let delta = entity.entity_index as i64 - previous;
+                previous = entity.entity_index as i64;
+                delta
+            };
+            let component_changes = system_diff
+                .component_changes
+                .iter()
+                .map(|change| match change {
+                    DiffComponentChange::Added { entity, type_name, data } => CompactChange::Added {
+                        world: entity.world_index,
+                        entity: entity_delta(entity),
+                        type_name: self.string(type_name),
+                        data: self.string(data),
+                    },
+                    DiffComponentChange::Modified { entity, type_name, diff } => CompactChange::Modified {
+                        world: entity.world_index,
+                        entity: entity_delta(entity),
+                        type_name: self.string(type_name),
+                        diff: self.string(diff),
+                    },
+                    DiffComponentChange::Removed { entity, type_name } => CompactChange::Removed {
+                        world: entity.world_index,
+                        entity: entity_delta(entity),
+                        type_name: self.string(type_name),
+                    },
+                })
+                .collect();
+            CompactSystemDiff {
+                component_changes,
+                world_operations: system_diff.world_operations.clone(),
+                failure: system_diff.failure.clone(),
+                error: system_diff.error.clone(),
+                stats: system_diff.stats,
+                causes: system_diff.causes.clone(),
+                failed_assertions: system_diff.failed_assertions.clone(),
+                change_sequence: system_diff.change_sequence.clone(),
+                operation_sequence: system_diff.operation_sequence.clone(),
+            }
+        }
+
+        fn snapshot(&mut self, snapshot: &WorldSnapshot) -> CompactSnapshot {
+            CompactSnapshot {
+                diff: self.system_diff(&snapshot.diff),
+                resources: snapshot.resources.clone(),
+            }
+        }
+    }
+
+    /// Resolves string table indices while decoding
+    struct Decoder {
+        strings: Vec<InternedStr>,
+    }
+
+    impl Decoder {
+        fn string(&self, index: u32) -> Result<InternedStr, String> {
+            self.strings
+                .get(index as usize)
+                .cloned()
+                .ok_or_else(|| format!("String index {} out of range ({} strings)", index, self.strings.len()))
+        }
+
+        fn system_diff(&self, system_diff: CompactSystemDiff) -> Result<SystemUpdateDiff, String> {
+            let mut previous = 0i64;
+            let mut entity = |world: usize, delta: i64| {
+                previous += delta;
+                usize::try_from(previous)
+                    .map(|entity_index| Entity::new(world, entity_index))
+                    .map_err(|_| format!("Invalid entity index {}", previous))
+            };
+            let mut component_changes = Vec::with_capacity(system_diff.component_changes.len());
+            for change in system_diff.component_changes {
+                component_changes.push(match change {
+                    CompactChange::Added {
+                        world,
+                        entity: delta,
+                        type_name,
+                        data,
+                    } => DiffComponentChange::Added {
+                        entity: entity(world, delta)?,
+                        type_name: self.string(type_name)?,
+                        data: self.string(data)?,
+                    },
+                    CompactChange::Modified {
+                        world,
+                        entity: delta,
+                        type_name,
+                        diff,
+                    } => DiffComponentChange::Modified {
+                        entity: entity(world, delta)?,
+                        type_name: self.string(type_name)?,
+                        diff: self.string(diff)?,
+                    },
+                    CompactChange::Removed {
+                        world,
+                        entity: delta,
+                        type_name,
+                    } => DiffComponentChange::Removed {
+                        entity: entity(world, delta)?,
+                        type_name: self.string(type_name)?,
+                    },
+                });
+            }
+            let mut decoded = SystemUpdateDiff {
+                component_changes,
+                world_operations: system_diff.world_operations,
+                failure: system_diff.failure,
+                error: system_diff.error,
+                stats: system_diff.stats,
+                causes: system_diff.causes,
+                failed_assertions: system_diff.failed_assertions,
+                ..SystemUpdateDiff::new()
+            };
+            decoded.set_sequences(system_diff.change_sequence, system_diff.operation_sequence);
+            Ok(decoded)
+        }
+
+        fn snapshot(&self, snapshot: CompactSnapshot) -> Result<WorldSnapshot, String> {
+            Ok(WorldSnapshot {
+                diff: self.system_diff(snapshot.diff)?,
+                resources: snapshot.resources,
+                component_versions: None,
+            })
+        }
+    }
+
+    impl CompactHistory {
+        /// Encode a history
+        pub fn from_history(history: &WorldUpdateHistory) -> Self {
+            let mut encoder = Encoder::default();
+            let updates = history
+                .updates
+                .iter()
+                .map(|update| CompactUpdate {
+                    system_diffs: update
+                        .system_diffs
+                        .iter()
+                        .map(|system_diff| encoder.system_diff(system_diff))
+                        .collect(),
+                    annotations: update.annotations.clone(),
+                })
+                .collect();
+            let initial_state = history.initial_state.as_ref().map(|state| encoder.snapshot(state));
+            let keyframes = history
+                .keyframes
+                .iter()
+                .map(|keyframe| CompactKeyframe {
+                    frame: keyframe.frame,
+                    update_index: keyframe.update_index,
+                    state: encoder.snapshot(&keyframe.state),
+                })
+                .collect();
+            Self {
+                strings: encoder.strings,
+                updates,
+                initial_state,
+                metadata: history.metadata.clone(),
+                keyframes,
+            }
+        }
+
+        /// Decode the history, sharing the strings of the table between its changes
+        pub fn into_history(self) -> Result<WorldUpdateHistory, String> {
+            let decoder = Decoder {
+                strings: self.strings.into_iter().map(InternedStr::from).collect(),
+            };
+            let mut history = WorldUpdateHistory::new();
+            for update in self.updates {
+                let mut diff = WorldUpdateDiff::new();
+                for system_diff in update.system_diffs {
+                    diff.system_diffs.push(decoder.system_diff(system_diff)?);
+                }
+                diff.annotations = update.annotations;
+                history.updates.push(diff);
+            }
+            history.initial_state = self.initial_state.map(|state| decoder.snapshot(state)).transpose()?;
+            history.metadata = self.metadata;
+            for keyframe in self.keyframes {
+                history.keyframes.push(Keyframe {
+                    frame: keyframe.frame,
+                    update_index: keyframe.update_index,
+                    state: decoder.snapshot(keyframe.state)?,
+                });
+            }
+            history.intern_strings();
+            Ok(history)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Entity, WorldUpdateDiff};
+
+    fn history(frames: usize) -> WorldUpdateHistory {
+        let mut history = WorldUpdateHistory::new();
+        for frame in 0..frames {
+            let mut system_diff = SystemUpdateDiff::new();
+            for entity_index in 0..10 {
+                system_diff.record_component_change(DiffComponentChange::Added {
+                    entity: Entity::new(0, entity_index),
+                    type_name: "Actor".into(),
+                    data: "()".to_string().into(),
+                });
+                system_diff.record_component_change(DiffComponentChange::Modified {
+                    entity: Entity::new(0, entity_index),
+                    type_name: "Position".to_string().into(),
+                    diff: format!("(x:Some({}),y:None)", frame % 3).into(),
+                });
+            }
+            let mut update = WorldUpdateDiff::new();
+            update.record(system_diff);
+            history.record(update);
+        }
+        history
+    }
+
+    #[test]
+    fn test_repeated_strings_are_shared() {
+        let history = history(20);
+        let type_name = |update: usize| match &history.updates()[update].system_diffs()[0].component_changes()[0] {
+            DiffComponentChange::Added { type_name, .. } => type_name.clone(),
+            change => panic!("unexpected change {:?}", change),
+        };
+        assert!(type_name(0).ptr_eq(&type_name(19)));
+
+        let stats = history.memory_stats();
+        assert_eq!(stats.updates, 20);
+        assert_eq!(stats.component_changes, 400);
+        // Actor, (), Position and three Position diffs
+        assert_eq!(stats.unique_strings, 6);
+        assert!(stats.saved_bytes() > stats.interned_bytes * 10, "{:?}", stats);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_compact_history_round_trip() {
+        let history = history(5);
+        let compact = CompactHistory::from_history(&history);
+        let decoded = compact.into_history().unwrap();
+        assert!(crate::replay_analysis::compare_histories(&history, &decoded).is_empty());
+        assert_eq!(decoded.memory_stats(), history.memory_stats());
+    }
+}
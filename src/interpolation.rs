@@ -0,0 +1,219 @@
+//! Smoothing rendered movement between fixed simulation ticks.
+//!
+//! With a fixed timestep the simulation advances in whole ticks while frames
+//! are drawn at any rate. `FixedTimestep` tells the loop how many ticks to run
+//! for the elapsed time and how far the next tick is, as an alpha between 0
+//! and 1. For component types registered with `track_previous`, the world
+//! keeps a `Previous<T>` copy of the value from before the latest tick, and
+//! `interpolated` blends the two for rendering:
+//!
+//! ```ignore
+//! world.track_previous::<Position>();
+//! let mut timestep = FixedTimestep::new(Duration::from_millis(20));
+//! loop {
+//!     for _ in 0..timestep.advance(frame_time) {
+//!         world.update();
+//!     }
+//!     for entity in world.entities_with_component::<Position>() {
+//!         draw(world.interpolated::<Position>(entity, timestep.alpha()));
+//!     }
+//! }
+//! ```
+//!
+//! An alpha above 1 extrapolates past the latest tick. `Previous<T>` is
+//! derived from the world's state and is not recorded in the history.
+
+use crate::transform::{GlobalPosition, LocalPosition};
+use crate::{Entity, World};
+use std::any::TypeId;
+use std::time::Duration;
+
+/// Value of a component before the latest update, kept for types registered
+/// with `World::track_previous`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Previous<T>(pub T);
+
+/// Values that can be blended linearly
+pub trait Interpolate {
+    /// Blend from `self` at alpha 0 to `next` at alpha 1
+    fn interpolate(&self, next: &Self, alpha: f32) -> Self;
+}
+
+impl Interpolate for f32 {
+    fn interpolate(&self, next: &Self, alpha: f32) -> Self {
+        self + (next - self) * alpha
+    }
+}
+
+impl Interpolate for f64 {
+    fn interpolate(&self, next: &Self, alpha: f32) -> Self {
+        self + (next - self) * alpha as f64
+    }
+}
+
+impl Interpolate for LocalPosition {
+    fn interpolate(&self, next: &Self, alpha: f32) -> Self {
+        Self {
+            x: self.x.interpolate(&next.x, alpha),
+            y: self.y.interpolate(&next.y, alpha),
+        }
+    }
+}
+
+impl Interpolate for GlobalPosition {
+    fn interpolate(&self, next: &Self, alpha: f32) -> Self {
+        Self {
+            x: self.x.interpolate(&next.x, alpha),
+            y: self.y.interpolate(&next.y, alpha),
+        }
+    }
+}
+
+/// Accumulates frame time into fixed simulation ticks
+#[derive(Debug, Clone, PartialEq)]
+pub struct FixedTimestep {
+    step: Duration,
+    accumulated: Duration,
+}
+
+impl FixedTimestep {
+    /// Create a timestep ticking every `step`
+    pub fn new(step: Duration) -> Self {
+        assert!(!step.is_zero(), "fixed timestep must be longer than zero");
+        Self {
+            step,
+            accumulated: Duration::ZERO,
+        }
+    }
+
+    /// Get the time between ticks
+    pub fn step(&self) -> Duration {
+        self.step
+    }
+
+    /// Add the time of a frame and return the number of ticks to run
+    pub fn advance(&mut self, elapsed: Duration) -> usize {
+        self.accumulated += elapsed;
+        let mut ticks = 0;
+        while self.accumulated >= self.step {
+            self.accumulated -= self.step;
+            ticks += 1;
+        }
+        ticks
+    }
+
+    /// Fraction of the next tick that has already elapsed, from 0 up to 1
+    pub fn alpha(&self) -> f32 {
+        self.accumulated.as_secs_f32() / self.step.as_secs_f32()
+    }
+}
+
+/// Component type and function storing its previous values
+pub(crate) type PreviousTracker = (TypeId, fn(&mut World));
+
+/// Copy the current values of `T` into `Previous<T>`
+fn store_previous<T: Clone + 'static>(world: &mut World) {
+    for entity in world.entities_with_component::<Previous<T>>() {
+        if world.get_component::<T>(entity).is_none() {
+            world.remove_component::<Previous<T>>(entity);
+        }
+    }
+    for entity in world.entities_with_component::<T>() {
+        let Some(current) = world.get_component::<T>(entity).cloned() else {
+            continue;
+        };
+        match world.get_component_mut::<Previous<T>>(entity) {
+            Some(previous) => previous.0 = current,
+            None => world.add_component(entity, Previous(current)),
+        }
+    }
+}
+
+impl World {
+    /// Keep a `Previous<T>` copy of every `T` component from before each update
+    pub fn track_previous<T: Clone + 'static>(&mut self) {
+        if !self.previous_trackers.iter().any(|(type_id, _)| *type_id == TypeId::of::<T>()) {
+            self.previous_trackers
+                .push((TypeId::of::<T>(), store_previous::<T>));
+        }
+    }
+
+    /// Store the previous values of the tracked component types, before a frame
+    pub(crate) fn store_previous_components(&mut self) {
+        let trackers: Vec<fn(&mut World)> = self.previous_trackers.iter().map(|(_, store)| *store).collect();
+        for store in trackers {
+            store(self);
+        }
+    }
+
+    /// Get the value of `T` blended from before the latest update to now.
+    ///
+    /// Returns the current value if no previous one is kept, and None if the
+    /// entity has no `T`.
+    pub fn interpolated<T: Interpolate + Clone + 'static>(&self, entity: Entity, alpha: f32) -> Option<T> {
+        let current = self.get_component::<T>(entity)?;
+        Some(match self.get_component::<Previous<T>>(entity) {
+            Some(previous) => previous.0.interpolate(current, alpha),
+            None => current.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Out, System, WorldView};
+
+    struct MoveRightSystem;
+
+    impl System for MoveRightSystem {
+        type InComponents = ();
+        type OutComponents = (LocalPosition,);
+
+        fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+        fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+            for (_, position) in world.query_components::<(Out<LocalPosition>,)>() {
+                position.x += 10.0;
+            }
+        }
+
+        fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    }
+
+    #[test]
+    fn test_fixed_timestep_accumulates_ticks() {
+        let mut timestep = FixedTimestep::new(Duration::from_millis(20));
+        assert_eq!(timestep.advance(Duration::from_millis(15)), 0);
+        assert_eq!(timestep.advance(Duration::from_millis(30)), 2);
+        assert!((timestep.alpha() - 0.25).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_interpolate_between_updates() {
+        let mut world = World::new();
+        world.track_previous::<LocalPosition>();
+        world.add_system(MoveRightSystem);
+        let entity = world.create_entity();
+        world.add_component(entity, LocalPosition { x: 0.0, y: 2.0 });
+        assert_eq!(
+            world.interpolated::<LocalPosition>(entity, 0.5),
+            Some(LocalPosition { x: 0.0, y: 2.0 })
+        );
+
+        world.update();
+        world.update();
+        assert_eq!(world.get_component::<Previous<LocalPosition>>(entity).map(|p| p.0.x), Some(10.0));
+        assert_eq!(
+            world.interpolated::<LocalPosition>(entity, 0.5),
+            Some(LocalPosition { x: 15.0, y: 2.0 })
+        );
+        // Extrapolating past the latest update
+        assert_eq!(world.interpolated::<LocalPosition>(entity, 1.5).map(|p| p.x), Some(25.0));
+
+        world.remove_component::<LocalPosition>(entity);
+        world.update();
+        assert!(world.get_component::<Previous<LocalPosition>>(entity).is_none());
+        assert_eq!(world.interpolated::<LocalPosition>(entity, 0.5), None);
+    }
+}
@@ -0,0 +1,145 @@
+//! Invariant assertions checked while the world updates.
+//!
+//! Invariants are predicates over the whole world. In debug builds and in
+//! replay mode they are evaluated after every system update, so a violation is
+//! attributed to the exact system (and its recorded diff) that broke it.
+//!
+//! Systems can also assert conditions of their own while they update:
+//!
+//! ```ignore
+//! world.debug_assert_invariant("health_not_negative", health.current >= 0);
+//! ```
+//!
+//! A failed assertion does not panic. Its name is recorded in the system's
+//! diff and written to replay logs as `ASSERT: health_not_negative`, so replay
+//! analysis shows it at the frame it failed in (see
+//! `WorldUpdateHistory::assertion_frames`).
+
+use crate::{SystemUpdateDiff, World, WorldView};
+
+/// Predicate that must hold for the world to be in a valid state
+pub(crate) type InvariantCheck = Box<dyn Fn(&World) -> bool>;
+
+/// A registered invariant and whether it held at the last check
+pub(crate) struct Invariant {
+    name: String,
+    check: InvariantCheck,
+    holds: bool,
+}
+
+/// Report of an invariant that stopped holding
+#[derive(Debug, Clone)]
+pub struct InvariantViolation {
+    /// Name the invariant was registered with
+    pub invariant: String,
+    /// Frame number in which the violation was detected
+    pub frame: usize,
+    /// Type name of the system whose update broke the invariant, or None if the
+    /// world was changed outside of a system update
+    pub system_name: Option<String>,
+    /// Diff recorded by the offending system
+    pub system_diff: Option<SystemUpdateDiff>,
+}
+
+impl std::fmt::Display for InvariantViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.system_name {
+            Some(system_name) => write!(
+                f,
+                "Invariant '{}' violated in frame {} by {}",
+                self.invariant, self.frame, system_name
+            ),
+            None => write!(
+                f,
+                "Invariant '{}' violated in frame {} outside of system updates",
+                self.invariant, self.frame
+            ),
+        }
+    }
+}
+
+impl World {
+    /// Register an invariant that is checked after every system update in debug
+    /// builds and in replay mode.
+    ///
+    /// When the invariant stops holding, an `InvariantViolation` naming the
+    /// system responsible is reported and stored (see `invariant_violations`).
+    /// A broken invariant is reported again only after it has held in between.
+    pub fn add_invariant<F>(&mut self, name: &str, check: F)
+    where
+        F: Fn(&World) -> bool + 'static,
+    {
+        let holds = check(self);
+        self.invariants.push(Invariant {
+            name: name.to_string(),
+            check: Box::new(check),
+            holds,
+        });
+    }
+
+    /// Remove an invariant by name, returning whether it existed
+    pub fn remove_invariant(&mut self, name: &str) -> bool {
+        let initial_count = self.invariants.len();
+        self.invariants.retain(|invariant| invariant.name != name);
+        self.invariants.len() < initial_count
+    }
+
+    /// Get all violations detected so far
+    pub fn invariant_violations(&self) -> &[InvariantViolation] {
+        &self.invariant_violations
+    }
+
+    /// Clear the recorded violations
+    pub fn clear_invariant_violations(&mut self) {
+        self.invariant_violations.clear();
+    }
+
+    /// Check if invariants are evaluated during updates
+    pub(crate) fn invariants_active(&self) -> bool {
+        !self.invariants.is_empty() && (cfg!(debug_assertions) || self.replay_mode)
+    }
+
+    /// Evaluate every invariant, attributing new violations to `system` if given
+    pub(crate) fn check_invariants(&mut self, system: Option<(&str, &SystemUpdateDiff)>) {
+        let mut invariants = std::mem::take(&mut self.invariants);
+        for invariant in &mut invariants {
+            let holds = (invariant.check)(self);
+            if invariant.holds && !holds {
+                let violation = InvariantViolation {
+                    invariant: invariant.name.clone(),
+                    frame: self.frame_number,
+                    system_name: system.map(|(name, _)| name.to_string()),
+                    system_diff: system.map(|(_, diff)| diff.clone()),
+                };
+                ecs_error!("{}", violation);
+                self.invariant_violations.push(violation);
+            }
+            invariant.holds = holds;
+        }
+        self.invariants = invariants;
+    }
+
+    /// Check the invariants after a system update that is about to be undone
+    /// in replay mode. The update is checked against the state before it, and
+    /// the invariants are left as they were for the systems after it.
+    pub(crate) fn check_undone_invariants(&mut self, system_name: &str, system_diff: &SystemUpdateDiff) {
+        let held: Vec<bool> = self.invariants.iter().map(|invariant| invariant.holds).collect();
+        self.check_invariants(Some((system_name, system_diff)));
+        for (invariant, holds) in self.invariants.iter_mut().zip(held) {
+            invariant.holds = holds;
+        }
+    }
+}
+
+impl<I, O> WorldView<I, O> {
+    /// Record a failed assertion named `name` in the system's diff unless
+    /// `condition` holds. Returns the condition.
+    pub fn debug_assert_invariant(&mut self, name: &str, condition: bool) -> bool {
+        if !condition {
+            let world = unsafe { self.world() };
+            ecs_error!("Assertion '{}' failed in frame {}", name, world.frame_number);
+            self.system_diff.record_failed_assertion(name);
+        }
+        condition
+    }
+}
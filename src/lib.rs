@@ -4,15 +4,21 @@
 //! input and output components, enabling comprehensive change tracking and replay
 //! functionality for debugging complex system interactions.
 
+#[macro_use]
+mod telemetry;
+mod coalesce;
+pub mod clock;
+
 use std::any::{Any, TypeId};
-use std::collections::HashMap;
-use std::fs::{File, OpenOptions};
-use std::io::{Write, BufWriter};
-use std::path::Path;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::Write;
 
 // Re-export the derive macro from the derive crate
-pub use rust_ecs_derive::Diff;
+pub use rust_ecs_derive::{Component, Diff, Reflect, Soa};
+// Used by `#[derive(Component)]` to collect component types at startup
+#[cfg(feature = "auto-register")]
+#[doc(hidden)]
+pub use inventory;
 
 /// A dummy function to demonstrate the library.
 /// Returns the sum of two numbers.
@@ -36,6 +42,17 @@ pub trait Diff {
     fn diff_to_string(diff: &Self::Diff) -> String {
         format!("{:?}", diff)
     }
+
+    /// Compute the diff that undoes `diff` when applied after it.
+    /// Returns None if applying the diff leaves self unchanged.
+    fn invert_diff(&self, diff: &Self::Diff) -> Option<Self::Diff>
+    where
+        Self: Clone,
+    {
+        let mut applied = self.clone();
+        applied.apply_diff(diff);
+        applied.diff(self)
+    }
 }
 
 /// Macro to automatically implement Diff for structs
@@ -222,40 +239,66 @@ impl<T: Diff + Clone + std::fmt::Debug> Diff for Vec<T> {
     }
 
     fn apply_diff(&mut self, diff: &Self::Diff) {
-        // Sort changes by index in reverse order to handle removals correctly
-        let mut sorted_changes = diff.changes.clone();
-        sorted_changes.sort_by_key(|b| std::cmp::Reverse(b.index()));
-
-        for change in sorted_changes {
-            match change {
-                VecChange::Added { index, value } => {
-                    if index <= self.len() {
-                        self.insert(index, value);
-                    } else {
-                        self.push(value);
-                    }
-                }
-                VecChange::Removed { index } => {
-                    if index < self.len() {
-                        self.remove(index);
-                    }
-                }
-                VecChange::Modified { index, diff } => {
-                    if let Some(item) = self.get_mut(index) {
-                        item.apply_diff(&diff);
-                    }
+        // Indices refer to the vector before the diff: modify items first,
+        // then remove from the back so earlier indices stay valid, then add
+        // from the front so every index is in place when it is inserted
+        for change in &diff.changes {
+            if let VecChange::Modified { index, diff } = change {
+                if let Some(item) = self.get_mut(*index) {
+                    item.apply_diff(diff);
                 }
             }
         }
+
+        let mut removed: Vec<usize> = diff
+            .changes
+            .iter()
+            .filter_map(|change| match change {
+                VecChange::Removed { index } => Some(*index),
+                _ => None,
+            })
+            .collect();
+        removed.sort_unstable_by(|a, b| b.cmp(a));
+        for index in removed {
+            if index < self.len() {
+                self.remove(index);
+            }
+        }
+
+        let mut added: Vec<(usize, &T)> = diff
+            .changes
+            .iter()
+            .filter_map(|change| match change {
+                VecChange::Added { index, value } => Some((*index, value)),
+                _ => None,
+            })
+            .collect();
+        added.sort_by_key(|(index, _)| *index);
+        for (index, value) in added {
+            if index <= self.len() {
+                self.insert(index, value.clone());
+            } else {
+                self.push(value.clone());
+            }
+        }
     }
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "T: serde::Serialize, T::Diff: serde::Serialize",
+        deserialize = "T: serde::Deserialize<'de>, T::Diff: serde::Deserialize<'de>"
+    ))
+)]
 pub struct VecDiff<T: Diff + std::fmt::Debug> {
     pub changes: Vec<VecChange<T>>,
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum VecChange<T: Diff + std::fmt::Debug> {
     Added { index: usize, value: T },
     Removed { index: usize },
@@ -263,7 +306,8 @@ pub enum VecChange<T: Diff + std::fmt::Debug> {
 }
 
 impl<T: Diff + std::fmt::Debug> VecChange<T> {
-    fn index(&self) -> usize {
+    /// Get the index in the vector before the diff that the change applies to
+    pub fn index(&self) -> usize {
         match self {
             VecChange::Added { index, .. } => *index,
             VecChange::Removed { index } => *index,
@@ -333,12 +377,160 @@ impl<
     }
 }
 
-#[derive(Clone, Debug)]
+/// Changed entries of a `HashMap`.
+///
+/// Keys need no `Ord`: the changes are formatted sorted by the `Debug` form
+/// of their keys and serialized sorted by their serialized form, comparing
+/// runs of digits by value (see `compare_key_text`). Identical runs record
+/// identical diffs.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "K: serde::Serialize, V: serde::Serialize, V::Diff: serde::Serialize",
+        deserialize = "K: serde::Deserialize<'de> + Eq + std::hash::Hash, V: serde::Deserialize<'de>, V::Diff: serde::Deserialize<'de>"
+    ))
+)]
 pub struct HashMapDiff<K: std::fmt::Debug, V: Diff + std::fmt::Debug> {
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_sorted_changes"))]
     pub changes: HashMap<K, HashMapChange<V>>,
 }
 
+/// Compare the text forms of two keys, runs of digits by their value, so
+/// that 2 comes before 10
+fn compare_key_text(a: &str, b: &str) -> std::cmp::Ordering {
+    fn split_number(text: &[u8]) -> (&[u8], &[u8]) {
+        let end = text.iter().position(|c| !c.is_ascii_digit()).unwrap_or(text.len());
+        let (digits, rest) = text.split_at(end);
+        let start = digits.iter().position(|&c| c != b'0').unwrap_or(digits.len());
+        (&digits[start..], rest)
+    }
+
+    let (mut left, mut right) = (a.as_bytes(), b.as_bytes());
+    while let (Some(&l), Some(&r)) = (left.first(), right.first()) {
+        let ordering = if l.is_ascii_digit() && r.is_ascii_digit() {
+            let (left_number, left_rest) = split_number(left);
+            let (right_number, right_rest) = split_number(right);
+            (left, right) = (left_rest, right_rest);
+            // Without leading zeros, the longer number is the larger one
+            left_number.len().cmp(&right_number.len()).then(left_number.cmp(right_number))
+        } else {
+            (left, right) = (&left[1..], &right[1..]);
+            l.cmp(&r)
+        };
+        if ordering.is_ne() {
+            return ordering;
+        }
+    }
+    // Keys like 2 and 02 compare equal above, but still need an order
+    left.len().cmp(&right.len()).then_with(|| a.cmp(b))
+}
+
+/// Entries of a map sorted by the text forms of their keys
+fn sorted_changes<'a, K, C>(changes: impl IntoIterator<Item = (String, &'a K, &'a C)>) -> Vec<(&'a K, &'a C)> {
+    let mut entries: Vec<(String, &K, &C)> = changes.into_iter().collect();
+    entries.sort_by(|(a, _, _), (b, _, _)| compare_key_text(a, b));
+    entries.into_iter().map(|(_, key, change)| (key, change)).collect()
+}
+
+#[cfg(feature = "serde")]
+fn serialize_sorted_changes<S, K, C>(changes: &HashMap<K, C>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+    K: serde::Serialize,
+    C: serde::Serialize,
+{
+    use serde::ser::{Error, SerializeMap};
+    let entries = changes
+        .iter()
+        .map(|(key, change)| Ok((ron::to_string(key).map_err(S::Error::custom)?, key, change)))
+        .collect::<Result<Vec<_>, S::Error>>()?;
+    let mut map = serializer.serialize_map(Some(changes.len()))?;
+    for (key, change) in sorted_changes(entries) {
+        map.serialize_entry(key, change)?;
+    }
+    map.end()
+}
+
+impl<K: std::fmt::Debug, V: Diff + std::fmt::Debug> std::fmt::Debug for HashMapDiff<K, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        struct SortedChanges<'a, K, C>(&'a HashMap<K, C>);
+
+        impl<K: std::fmt::Debug, C: std::fmt::Debug> std::fmt::Debug for SortedChanges<'_, K, C> {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                let entries = self.0.iter().map(|(key, change)| (format!("{:?}", key), key, change));
+                f.debug_map().entries(sorted_changes(entries)).finish()
+            }
+        }
+
+        f.debug_struct("HashMapDiff")
+            .field("changes", &SortedChanges(&self.changes))
+            .finish()
+    }
+}
+
+impl<K: Clone + Ord + std::fmt::Debug, V: Diff + Clone + std::fmt::Debug> Diff for std::collections::BTreeMap<K, V> {
+    type Diff = BTreeMapDiff<K, V>;
+
+    fn diff(&self, other: &Self) -> Option<Self::Diff> {
+        let mut changes = std::collections::BTreeMap::new();
+        for (key, other_value) in other {
+            match self.get(key) {
+                Some(self_value) => {
+                    if let Some(value_diff) = self_value.diff(other_value) {
+                        changes.insert(key.clone(), HashMapChange::Modified(value_diff));
+                    }
+                }
+                None => {
+                    changes.insert(key.clone(), HashMapChange::Added(other_value.clone()));
+                }
+            }
+        }
+        for key in self.keys() {
+            if !other.contains_key(key) {
+                changes.insert(key.clone(), HashMapChange::Removed);
+            }
+        }
+        (!changes.is_empty()).then_some(BTreeMapDiff { changes })
+    }
+
+    fn apply_diff(&mut self, diff: &Self::Diff) {
+        for (key, change) in &diff.changes {
+            match change {
+                HashMapChange::Added(value) => {
+                    self.insert(key.clone(), value.clone());
+                }
+                HashMapChange::Removed => {
+                    self.remove(key);
+                }
+                HashMapChange::Modified(value_diff) => {
+                    if let Some(existing_value) = self.get_mut(key) {
+                        existing_value.apply_diff(value_diff);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Changed entries of a `BTreeMap`, in key order
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "K: serde::Serialize, V: serde::Serialize, V::Diff: serde::Serialize",
+        deserialize = "K: serde::Deserialize<'de> + Ord, V: serde::Deserialize<'de>, V::Diff: serde::Deserialize<'de>"
+    ))
+)]
+pub struct BTreeMapDiff<K: std::fmt::Debug, V: Diff + std::fmt::Debug> {
+    pub changes: std::collections::BTreeMap<K, HashMapChange<V>>,
+}
+
+/// Change of one entry of a `HashMap` or `BTreeMap`
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum HashMapChange<V: Diff + std::fmt::Debug> {
     Added(V),
     Removed,
@@ -347,7 +539,11 @@ pub enum HashMapChange<V: Diff + std::fmt::Debug> {
 
 /// An Entity is a unique identifier consisting of world index and entity index.
 /// This allows entities to be uniquely identified across multiple worlds.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Diff)]
+///
+/// Entities are ordered by world index and then entity index, which is the
+/// order they were created in and the order queries return them in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Diff)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Entity {
     /// Index of the world this entity belongs to
     pub world_index: usize,
@@ -375,13 +571,20 @@ impl Entity {
     }
 }
 
+/// Formats the entity as `Entity(world_index, entity_index)`, the form used in replay logs
+impl std::fmt::Display for Entity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Entity({}, {})", self.world_index, self.entity_index)
+    }
+}
+
 /// The System trait defines the contract for all systems in the ECS.
 /// Systems declare their input and output components for change tracking.
 pub trait System {
     /// Components that the system will read from without modifying them
-    type InComponents;
+    type InComponents: ComponentSet;
     /// Components that the system will read from and write to
-    type OutComponents;
+    type OutComponents: ComponentSet;
 
     /// Called once before the first update to initialize system state
     fn initialize(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>);
@@ -389,10 +592,202 @@ pub trait System {
     /// Called every frame to update the system
     fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>);
 
+    /// Called by the world every frame instead of `update`; override it to
+    /// report recoverable errors. The default runs `update` and never fails.
+    fn try_update(
+        &mut self,
+        world: &mut WorldView<Self::InComponents, Self::OutComponents>,
+    ) -> Result<(), SystemError> {
+        self.update(world);
+        Ok(())
+    }
+
+    /// Called by `World::pause`, e.g. to stop effects or release held input
+    fn on_pause(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+    /// Called by `World::resume` before the next update that runs the system
+    fn on_resume(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+    /// Whether the system keeps updating while the world is paused, like a
+    /// pause menu or the renderer
+    fn run_when_paused(&self) -> bool {
+        false
+    }
+
     /// Called when the system is being removed or the world is shutting down
     fn deinitialize(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>);
 }
 
+/// A system whose update can fail with a recoverable error, such as a missing
+/// resource or bad configuration, instead of panicking.
+///
+/// Errors are recorded in the frame's diff and returned by `World::try_update`;
+/// the remaining systems still run.
+pub trait FallibleSystem {
+    /// Components that the system will read from without modifying them
+    type InComponents: ComponentSet;
+    /// Components that the system will read from and write to
+    type OutComponents: ComponentSet;
+
+    /// Called once before the first update to initialize system state
+    fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+    /// Called every frame to update the system
+    fn try_update(
+        &mut self,
+        world: &mut WorldView<Self::InComponents, Self::OutComponents>,
+    ) -> Result<(), SystemError>;
+
+    /// Called by `World::pause`, e.g. to stop effects or release held input
+    fn on_pause(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+    /// Called by `World::resume` before the next update that runs the system
+    fn on_resume(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+    /// Whether the system keeps updating while the world is paused, like a
+    /// pause menu or the renderer
+    fn run_when_paused(&self) -> bool {
+        false
+    }
+
+    /// Called when the system is being removed or the world is shutting down
+    fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+}
+
+impl<S: FallibleSystem> System for S {
+    type InComponents = S::InComponents;
+    type OutComponents = S::OutComponents;
+
+    fn initialize(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+        FallibleSystem::initialize(self, world);
+    }
+
+    fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+        if let Err(e) = FallibleSystem::try_update(self, world) {
+            ecs_error!("System {} failed: {}", std::any::type_name::<S>(), e);
+        }
+    }
+
+    fn try_update(
+        &mut self,
+        world: &mut WorldView<Self::InComponents, Self::OutComponents>,
+    ) -> Result<(), SystemError> {
+        FallibleSystem::try_update(self, world)
+    }
+
+    fn on_pause(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+        FallibleSystem::on_pause(self, world);
+    }
+
+    fn on_resume(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+        FallibleSystem::on_resume(self, world);
+    }
+
+    fn run_when_paused(&self) -> bool {
+        FallibleSystem::run_when_paused(self)
+    }
+
+    fn deinitialize(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+        FallibleSystem::deinitialize(self, world);
+    }
+}
+
+/// Recoverable error reported by a system's `try_update`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SystemError {
+    message: String,
+}
+
+impl SystemError {
+    /// Create an error with a message describing what went wrong
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+        }
+    }
+
+    /// Get the error message
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl std::fmt::Display for SystemError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for SystemError {}
+
+/// Errors reported by systems during one frame, returned by `World::try_update`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameError {
+    /// Number of the frame the errors occurred in
+    pub frame: usize,
+    /// Names of the failed systems with their error messages
+    pub errors: Vec<(&'static str, String)>,
+}
+
+impl std::fmt::Display for FrameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} system(s) failed in frame {}", self.errors.len(), self.frame)?;
+        for (system, message) in &self.errors {
+            write!(f, "; {}: {}", system, message)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for FrameError {}
+
+/// A tuple of component types, as used for a system's declared In/Out components
+pub trait ComponentSet {
+    /// Type ids of the components in the set
+    fn type_ids() -> Vec<TypeId>;
+
+    /// Short type names of the components in the set
+    fn type_names() -> Vec<&'static str>;
+}
+
+/// Shorten a type name to its last path segment, as used in recorded diffs
+pub(crate) fn short_type_name<T: ?Sized>() -> &'static str {
+    let full = std::any::type_name::<T>();
+    full.rsplit("::").next().unwrap_or(full)
+}
+
+macro_rules! impl_component_set {
+    ($($component:ident),*) => {
+        impl<$($component: 'static),*> ComponentSet for ($($component,)*) {
+            fn type_ids() -> Vec<TypeId> {
+                vec![$(TypeId::of::<$component>()),*]
+            }
+
+            fn type_names() -> Vec<&'static str> {
+                vec![$(short_type_name::<$component>()),*]
+            }
+        }
+    };
+}
+
+impl_component_set!();
+impl_component_set!(A);
+impl_component_set!(A, B);
+impl_component_set!(A, B, C);
+impl_component_set!(A, B, C, D);
+impl_component_set!(A, B, C, D, E);
+impl_component_set!(A, B, C, D, E, F);
+impl_component_set!(A, B, C, D, E, F, G);
+impl_component_set!(A, B, C, D, E, F, G, H);
+impl_component_set!(A, B, C, D, E, F, G, H, I);
+impl_component_set!(A, B, C, D, E, F, G, H, I, J);
+impl_component_set!(A, B, C, D, E, F, G, H, I, J, K);
+impl_component_set!(A, B, C, D, E, F, G, H, I, J, K, L);
+impl_component_set!(A, B, C, D, E, F, G, H, I, J, K, L, M);
+impl_component_set!(A, B, C, D, E, F, G, H, I, J, K, L, M, N);
+impl_component_set!(A, B, C, D, E, F, G, H, I, J, K, L, M, N, O);
+impl_component_set!(A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P);
+
 /// A wrapper for output (mutable) component access in queries
 pub struct Out<T>(pub T);
 
@@ -440,34 +835,57 @@ impl<'a, T: 'static> QueryComponent<'a> for T {
     type Item = &'a T;
 
     fn get_component(world: &'a World, entity: Entity) -> Option<Self::Item> {
-        world
-            .components
-            .get(&TypeId::of::<T>())?
-            .iter()
-            .find_map(|(e, component)| {
-                if *e == entity {
-                    component.downcast_ref::<T>()
-                } else {
-                    None
-                }
-            })
+        world.get_component::<T>(entity)
     }
 }
 
+/// Predicate deciding which results of a query are kept
+pub type QueryFilter<'f, T> = dyn FnMut(Entity, &T) -> bool + 'f;
+
 /// Trait for multi-component queries with mixed mutable/immutable access
 pub trait MixedMultiQuery<'a> {
     type Item;
 
     /// Get all entities that have all the required components with mixed access
-    fn query_mixed(world: &'a mut World) -> Vec<(Entity, Self::Item)>;
+    fn query_mixed(world: &'a mut World) -> Vec<(Entity, Self::Item)> {
+        Self::query_mixed_filtered(world, &mut |_, _| true)
+    }
+
+    /// Get the entities that have all the required components and whose
+    /// components `keep` accepts, checked as each entity is visited
+    fn query_mixed_filtered(world: &'a mut World, keep: &mut QueryFilter<'_, Self::Item>) -> Vec<(Entity, Self::Item)>;
+
+    /// Components the query reads and writes, checked in strict access mode
+    fn accesses() -> Vec<ComponentAccess> {
+        Vec::new()
+    }
 }
 
 /// Trait for components that can be queried with mixed access patterns
 pub trait MixedQueryComponent<'a> {
     type Item;
 
+    /// Whether the query also visits disabled entities, set by `IncludeDisabled`
+    const INCLUDES_DISABLED: bool = false;
+
     /// Extract the component from the world for a specific entity with appropriate access
     fn get_mixed_component(world: &'a mut World, entity: Entity) -> Option<Self::Item>;
+
+    /// Component read or written through this part of a query; filters that
+    /// only check for presence access none
+    fn access() -> Option<ComponentAccess> {
+        None
+    }
+
+    /// Component type every entity the query matches has, which lets the
+    /// query visit only the entities of the smallest such column
+    fn required() -> Option<TypeId> {
+        Self::access().map(|access| access.type_id)
+    }
+
+    /// Record the write of a result the query keeps, once every part of the
+    /// query matched and its filter accepted it; only `Out` writes
+    fn record_write(_world: &mut World, _entity: Entity) {}
 }
 
 /// A wrapper to explicitly mark input (immutable) component access
@@ -479,20 +897,12 @@ impl<'a, T: 'static> MixedQueryComponent<'a> for In<T> {
 
     fn get_mixed_component(world: &'a mut World, entity: Entity) -> Option<Self::Item> {
         // For immutable access, we can safely convert the mutable reference
-        unsafe {
-            let world_ref = &*(world as *const World);
-            world_ref
-                .components
-                .get(&TypeId::of::<T>())?
-                .iter()
-                .find_map(|(e, component)| {
-                    if *e == entity {
-                        component.downcast_ref::<T>()
-                    } else {
-                        None
-                    }
-                })
-        }
+        let world: &'a World = world;
+        world.get_component::<T>(entity)
+    }
+
+    fn access() -> Option<ComponentAccess> {
+        Some(ComponentAccess::read::<T>())
     }
 }
 
@@ -504,14 +914,17 @@ impl<'a, T: 'static> MixedQueryComponent<'a> for Out<T> {
         world
             .components
             .get_mut(&TypeId::of::<T>())?
-            .iter_mut()
-            .find_map(|(e, component)| {
-                if *e == entity {
-                    component.downcast_mut::<T>()
-                } else {
-                    None
-                }
-            })
+            .get_mut(entity)?
+            .downcast_mut::<T>()
+    }
+
+    fn access() -> Option<ComponentAccess> {
+        Some(ComponentAccess::write::<T>())
+    }
+
+    fn record_write(world: &mut World, entity: Entity) {
+        // Earlier results of the query are still borrowed, so observers are not flushed here
+        world.mark_written::<T>(entity);
     }
 }
 
@@ -522,9 +935,13 @@ where
 {
     type Item = A::Item;
 
-    fn query_mixed(world: &'a mut World) -> Vec<(Entity, Self::Item)> {
+    fn accesses() -> Vec<ComponentAccess> {
+        [A::access()].into_iter().flatten().collect()
+    }
+
+    fn query_mixed_filtered(world: &'a mut World, keep: &mut QueryFilter<'_, Self::Item>) -> Vec<(Entity, Self::Item)> {
         let mut results = Vec::new();
-        let entities: Vec<Entity> = world.entities.clone();
+        let entities: Vec<Entity> = world.query_candidates(A::INCLUDES_DISABLED, &[A::required()]);
 
         for entity in entities {
             unsafe {
@@ -532,7 +949,11 @@ where
                 let a = A::get_mixed_component(&mut *world_ptr, entity);
 
                 if let Some(a) = a {
-                    results.push((entity, a));
+                    let item = a;
+                    if keep(entity, &item) {
+                        A::record_write(&mut *world_ptr, entity);
+                        results.push((entity, item));
+                    }
                 }
             }
         }
@@ -549,9 +970,13 @@ where
 {
     type Item = (A::Item, B::Item);
 
-    fn query_mixed(world: &'a mut World) -> Vec<(Entity, Self::Item)> {
+    fn accesses() -> Vec<ComponentAccess> {
+        [A::access(), B::access()].into_iter().flatten().collect()
+    }
+
+    fn query_mixed_filtered(world: &'a mut World, keep: &mut QueryFilter<'_, Self::Item>) -> Vec<(Entity, Self::Item)> {
         let mut results = Vec::new();
-        let entities: Vec<Entity> = world.entities.clone();
+        let entities: Vec<Entity> = world.query_candidates(A::INCLUDES_DISABLED || B::INCLUDES_DISABLED, &[A::required(), B::required()]);
 
         for entity in entities {
             unsafe {
@@ -560,7 +985,12 @@ where
                 let b = B::get_mixed_component(&mut *world_ptr, entity);
 
                 if let (Some(a), Some(b)) = (a, b) {
-                    results.push((entity, (a, b)));
+                    let item = (a, b);
+                    if keep(entity, &item) {
+                        A::record_write(&mut *world_ptr, entity);
+                        B::record_write(&mut *world_ptr, entity);
+                        results.push((entity, item));
+                    }
                 }
             }
         }
@@ -578,9 +1008,14 @@ where
 {
     type Item = (A::Item, B::Item, C::Item);
 
-    fn query_mixed(world: &'a mut World) -> Vec<(Entity, Self::Item)> {
+    fn accesses() -> Vec<ComponentAccess> {
+        [A::access(), B::access(), C::access()].into_iter().flatten().collect()
+    }
+
+    fn query_mixed_filtered(world: &'a mut World, keep: &mut QueryFilter<'_, Self::Item>) -> Vec<(Entity, Self::Item)> {
         let mut results = Vec::new();
-        let entities: Vec<Entity> = world.entities.clone();
+        let include_disabled = A::INCLUDES_DISABLED || B::INCLUDES_DISABLED || C::INCLUDES_DISABLED;
+        let entities: Vec<Entity> = world.query_candidates(include_disabled, &[A::required(), B::required(), C::required()]);
 
         for entity in entities {
             unsafe {
@@ -590,7 +1025,13 @@ where
                 let c = C::get_mixed_component(&mut *world_ptr, entity);
 
                 if let (Some(a), Some(b), Some(c)) = (a, b, c) {
-                    results.push((entity, (a, b, c)));
+                    let item = (a, b, c);
+                    if keep(entity, &item) {
+                        A::record_write(&mut *world_ptr, entity);
+                        B::record_write(&mut *world_ptr, entity);
+                        C::record_write(&mut *world_ptr, entity);
+                        results.push((entity, item));
+                    }
                 }
             }
         }
@@ -609,9 +1050,14 @@ where
 {
     type Item = (A::Item, B::Item, C::Item, D::Item);
 
-    fn query_mixed(world: &'a mut World) -> Vec<(Entity, Self::Item)> {
+    fn accesses() -> Vec<ComponentAccess> {
+        [A::access(), B::access(), C::access(), D::access()].into_iter().flatten().collect()
+    }
+
+    fn query_mixed_filtered(world: &'a mut World, keep: &mut QueryFilter<'_, Self::Item>) -> Vec<(Entity, Self::Item)> {
         let mut results = Vec::new();
-        let entities: Vec<Entity> = world.entities.clone();
+        let include_disabled = A::INCLUDES_DISABLED || B::INCLUDES_DISABLED || C::INCLUDES_DISABLED || D::INCLUDES_DISABLED;
+        let entities: Vec<Entity> = world.query_candidates(include_disabled, &[A::required(), B::required(), C::required(), D::required()]);
 
         for entity in entities {
             unsafe {
@@ -622,7 +1068,14 @@ where
                 let d = D::get_mixed_component(&mut *world_ptr, entity);
 
                 if let (Some(a), Some(b), Some(c), Some(d)) = (a, b, c, d) {
-                    results.push((entity, (a, b, c, d)));
+                    let item = (a, b, c, d);
+                    if keep(entity, &item) {
+                        A::record_write(&mut *world_ptr, entity);
+                        B::record_write(&mut *world_ptr, entity);
+                        C::record_write(&mut *world_ptr, entity);
+                        D::record_write(&mut *world_ptr, entity);
+                        results.push((entity, item));
+                    }
                 }
             }
         }
@@ -642,9 +1095,14 @@ where
 {
     type Item = (A::Item, B::Item, C::Item, D::Item, E::Item);
 
-    fn query_mixed(world: &'a mut World) -> Vec<(Entity, Self::Item)> {
+    fn accesses() -> Vec<ComponentAccess> {
+        [A::access(), B::access(), C::access(), D::access(), E::access()].into_iter().flatten().collect()
+    }
+
+    fn query_mixed_filtered(world: &'a mut World, keep: &mut QueryFilter<'_, Self::Item>) -> Vec<(Entity, Self::Item)> {
         let mut results = Vec::new();
-        let entities: Vec<Entity> = world.entities.clone();
+        let include_disabled = A::INCLUDES_DISABLED || B::INCLUDES_DISABLED || C::INCLUDES_DISABLED || D::INCLUDES_DISABLED || E::INCLUDES_DISABLED;
+        let entities: Vec<Entity> = world.query_candidates(include_disabled, &[A::required(), B::required(), C::required(), D::required(), E::required()]);
 
         for entity in entities {
             unsafe {
@@ -656,7 +1114,15 @@ where
                 let e = E::get_mixed_component(&mut *world_ptr, entity);
 
                 if let (Some(a), Some(b), Some(c), Some(d), Some(e)) = (a, b, c, d, e) {
-                    results.push((entity, (a, b, c, d, e)));
+                    let item = (a, b, c, d, e);
+                    if keep(entity, &item) {
+                        A::record_write(&mut *world_ptr, entity);
+                        B::record_write(&mut *world_ptr, entity);
+                        C::record_write(&mut *world_ptr, entity);
+                        D::record_write(&mut *world_ptr, entity);
+                        E::record_write(&mut *world_ptr, entity);
+                        results.push((entity, item));
+                    }
                 }
             }
         }
@@ -677,9 +1143,14 @@ where
 {
     type Item = (A::Item, B::Item, C::Item, D::Item, E::Item, F::Item);
 
-    fn query_mixed(world: &'a mut World) -> Vec<(Entity, Self::Item)> {
+    fn accesses() -> Vec<ComponentAccess> {
+        [A::access(), B::access(), C::access(), D::access(), E::access(), F::access()].into_iter().flatten().collect()
+    }
+
+    fn query_mixed_filtered(world: &'a mut World, keep: &mut QueryFilter<'_, Self::Item>) -> Vec<(Entity, Self::Item)> {
         let mut results = Vec::new();
-        let entities: Vec<Entity> = world.entities.clone();
+        let include_disabled = A::INCLUDES_DISABLED || B::INCLUDES_DISABLED || C::INCLUDES_DISABLED || D::INCLUDES_DISABLED || E::INCLUDES_DISABLED || F::INCLUDES_DISABLED;
+        let entities: Vec<Entity> = world.query_candidates(include_disabled, &[A::required(), B::required(), C::required(), D::required(), E::required(), F::required()]);
 
         for entity in entities {
             unsafe {
@@ -692,7 +1163,16 @@ where
                 let f = F::get_mixed_component(&mut *world_ptr, entity);
 
                 if let (Some(a), Some(b), Some(c), Some(d), Some(e), Some(f)) = (a, b, c, d, e, f) {
-                    results.push((entity, (a, b, c, d, e, f)));
+                    let item = (a, b, c, d, e, f);
+                    if keep(entity, &item) {
+                        A::record_write(&mut *world_ptr, entity);
+                        B::record_write(&mut *world_ptr, entity);
+                        C::record_write(&mut *world_ptr, entity);
+                        D::record_write(&mut *world_ptr, entity);
+                        E::record_write(&mut *world_ptr, entity);
+                        F::record_write(&mut *world_ptr, entity);
+                        results.push((entity, item));
+                    }
                 }
             }
         }
@@ -714,9 +1194,14 @@ where
 {
     type Item = (A::Item, B::Item, C::Item, D::Item, E::Item, F::Item, G::Item);
 
-    fn query_mixed(world: &'a mut World) -> Vec<(Entity, Self::Item)> {
+    fn accesses() -> Vec<ComponentAccess> {
+        [A::access(), B::access(), C::access(), D::access(), E::access(), F::access(), G::access()].into_iter().flatten().collect()
+    }
+
+    fn query_mixed_filtered(world: &'a mut World, keep: &mut QueryFilter<'_, Self::Item>) -> Vec<(Entity, Self::Item)> {
         let mut results = Vec::new();
-        let entities: Vec<Entity> = world.entities.clone();
+        let include_disabled = A::INCLUDES_DISABLED || B::INCLUDES_DISABLED || C::INCLUDES_DISABLED || D::INCLUDES_DISABLED || E::INCLUDES_DISABLED || F::INCLUDES_DISABLED || G::INCLUDES_DISABLED;
+        let entities: Vec<Entity> = world.query_candidates(include_disabled, &[A::required(), B::required(), C::required(), D::required(), E::required(), F::required(), G::required()]);
 
         for entity in entities {
             unsafe {
@@ -730,7 +1215,17 @@ where
                 let g = G::get_mixed_component(&mut *world_ptr, entity);
 
                 if let (Some(a), Some(b), Some(c), Some(d), Some(e), Some(f), Some(g)) = (a, b, c, d, e, f, g) {
-                    results.push((entity, (a, b, c, d, e, f, g)));
+                    let item = (a, b, c, d, e, f, g);
+                    if keep(entity, &item) {
+                        A::record_write(&mut *world_ptr, entity);
+                        B::record_write(&mut *world_ptr, entity);
+                        C::record_write(&mut *world_ptr, entity);
+                        D::record_write(&mut *world_ptr, entity);
+                        E::record_write(&mut *world_ptr, entity);
+                        F::record_write(&mut *world_ptr, entity);
+                        G::record_write(&mut *world_ptr, entity);
+                        results.push((entity, item));
+                    }
                 }
             }
         }
@@ -753,9 +1248,14 @@ where
 {
     type Item = (A::Item, B::Item, C::Item, D::Item, E::Item, F::Item, G::Item, H::Item);
 
-    fn query_mixed(world: &'a mut World) -> Vec<(Entity, Self::Item)> {
+    fn accesses() -> Vec<ComponentAccess> {
+        [A::access(), B::access(), C::access(), D::access(), E::access(), F::access(), G::access(), H::access()].into_iter().flatten().collect()
+    }
+
+    fn query_mixed_filtered(world: &'a mut World, keep: &mut QueryFilter<'_, Self::Item>) -> Vec<(Entity, Self::Item)> {
         let mut results = Vec::new();
-        let entities: Vec<Entity> = world.entities.clone();
+        let include_disabled = A::INCLUDES_DISABLED || B::INCLUDES_DISABLED || C::INCLUDES_DISABLED || D::INCLUDES_DISABLED || E::INCLUDES_DISABLED || F::INCLUDES_DISABLED || G::INCLUDES_DISABLED || H::INCLUDES_DISABLED;
+        let entities: Vec<Entity> = world.query_candidates(include_disabled, &[A::required(), B::required(), C::required(), D::required(), E::required(), F::required(), G::required(), H::required()]);
 
         for entity in entities {
             unsafe {
@@ -770,7 +1270,18 @@ where
                 let h = H::get_mixed_component(&mut *world_ptr, entity);
 
                 if let (Some(a), Some(b), Some(c), Some(d), Some(e), Some(f), Some(g), Some(h)) = (a, b, c, d, e, f, g, h) {
-                    results.push((entity, (a, b, c, d, e, f, g, h)));
+                    let item = (a, b, c, d, e, f, g, h);
+                    if keep(entity, &item) {
+                        A::record_write(&mut *world_ptr, entity);
+                        B::record_write(&mut *world_ptr, entity);
+                        C::record_write(&mut *world_ptr, entity);
+                        D::record_write(&mut *world_ptr, entity);
+                        E::record_write(&mut *world_ptr, entity);
+                        F::record_write(&mut *world_ptr, entity);
+                        G::record_write(&mut *world_ptr, entity);
+                        H::record_write(&mut *world_ptr, entity);
+                        results.push((entity, item));
+                    }
                 }
             }
         }
@@ -794,9 +1305,14 @@ where
 {
     type Item = (A::Item, B::Item, C::Item, D::Item, E::Item, F::Item, G::Item, H::Item, I::Item);
 
-    fn query_mixed(world: &'a mut World) -> Vec<(Entity, Self::Item)> {
+    fn accesses() -> Vec<ComponentAccess> {
+        [A::access(), B::access(), C::access(), D::access(), E::access(), F::access(), G::access(), H::access(), I::access()].into_iter().flatten().collect()
+    }
+
+    fn query_mixed_filtered(world: &'a mut World, keep: &mut QueryFilter<'_, Self::Item>) -> Vec<(Entity, Self::Item)> {
         let mut results = Vec::new();
-        let entities: Vec<Entity> = world.entities.clone();
+        let include_disabled = A::INCLUDES_DISABLED || B::INCLUDES_DISABLED || C::INCLUDES_DISABLED || D::INCLUDES_DISABLED || E::INCLUDES_DISABLED || F::INCLUDES_DISABLED || G::INCLUDES_DISABLED || H::INCLUDES_DISABLED || I::INCLUDES_DISABLED;
+        let entities: Vec<Entity> = world.query_candidates(include_disabled, &[A::required(), B::required(), C::required(), D::required(), E::required(), F::required(), G::required(), H::required(), I::required()]);
 
         for entity in entities {
             unsafe {
@@ -812,7 +1328,19 @@ where
                 let i = I::get_mixed_component(&mut *world_ptr, entity);
 
                 if let (Some(a), Some(b), Some(c), Some(d), Some(e), Some(f), Some(g), Some(h), Some(i)) = (a, b, c, d, e, f, g, h, i) {
-                    results.push((entity, (a, b, c, d, e, f, g, h, i)));
+                    let item = (a, b, c, d, e, f, g, h, i);
+                    if keep(entity, &item) {
+                        A::record_write(&mut *world_ptr, entity);
+                        B::record_write(&mut *world_ptr, entity);
+                        C::record_write(&mut *world_ptr, entity);
+                        D::record_write(&mut *world_ptr, entity);
+                        E::record_write(&mut *world_ptr, entity);
+                        F::record_write(&mut *world_ptr, entity);
+                        G::record_write(&mut *world_ptr, entity);
+                        H::record_write(&mut *world_ptr, entity);
+                        I::record_write(&mut *world_ptr, entity);
+                        results.push((entity, item));
+                    }
                 }
             }
         }
@@ -837,9 +1365,14 @@ where
 {
     type Item = (A::Item, B::Item, C::Item, D::Item, E::Item, F::Item, G::Item, H::Item, I::Item, J::Item);
 
-    fn query_mixed(world: &'a mut World) -> Vec<(Entity, Self::Item)> {
+    fn accesses() -> Vec<ComponentAccess> {
+        [A::access(), B::access(), C::access(), D::access(), E::access(), F::access(), G::access(), H::access(), I::access(), J::access()].into_iter().flatten().collect()
+    }
+
+    fn query_mixed_filtered(world: &'a mut World, keep: &mut QueryFilter<'_, Self::Item>) -> Vec<(Entity, Self::Item)> {
         let mut results = Vec::new();
-        let entities: Vec<Entity> = world.entities.clone();
+        let include_disabled = A::INCLUDES_DISABLED || B::INCLUDES_DISABLED || C::INCLUDES_DISABLED || D::INCLUDES_DISABLED || E::INCLUDES_DISABLED || F::INCLUDES_DISABLED || G::INCLUDES_DISABLED || H::INCLUDES_DISABLED || I::INCLUDES_DISABLED || J::INCLUDES_DISABLED;
+        let entities: Vec<Entity> = world.query_candidates(include_disabled, &[A::required(), B::required(), C::required(), D::required(), E::required(), F::required(), G::required(), H::required(), I::required(), J::required()]);
 
         for entity in entities {
             unsafe {
@@ -856,7 +1389,20 @@ where
                 let j = J::get_mixed_component(&mut *world_ptr, entity);
 
                 if let (Some(a), Some(b), Some(c), Some(d), Some(e), Some(f), Some(g), Some(h), Some(i), Some(j)) = (a, b, c, d, e, f, g, h, i, j) {
-                    results.push((entity, (a, b, c, d, e, f, g, h, i, j)));
+                    let item = (a, b, c, d, e, f, g, h, i, j);
+                    if keep(entity, &item) {
+                        A::record_write(&mut *world_ptr, entity);
+                        B::record_write(&mut *world_ptr, entity);
+                        C::record_write(&mut *world_ptr, entity);
+                        D::record_write(&mut *world_ptr, entity);
+                        E::record_write(&mut *world_ptr, entity);
+                        F::record_write(&mut *world_ptr, entity);
+                        G::record_write(&mut *world_ptr, entity);
+                        H::record_write(&mut *world_ptr, entity);
+                        I::record_write(&mut *world_ptr, entity);
+                        J::record_write(&mut *world_ptr, entity);
+                        results.push((entity, item));
+                    }
                 }
             }
         }
@@ -882,9 +1428,14 @@ where
 {
     type Item = (A::Item, B::Item, C::Item, D::Item, E::Item, F::Item, G::Item, H::Item, I::Item, J::Item, K::Item);
 
-    fn query_mixed(world: &'a mut World) -> Vec<(Entity, Self::Item)> {
+    fn accesses() -> Vec<ComponentAccess> {
+        [A::access(), B::access(), C::access(), D::access(), E::access(), F::access(), G::access(), H::access(), I::access(), J::access(), K::access()].into_iter().flatten().collect()
+    }
+
+    fn query_mixed_filtered(world: &'a mut World, keep: &mut QueryFilter<'_, Self::Item>) -> Vec<(Entity, Self::Item)> {
         let mut results = Vec::new();
-        let entities: Vec<Entity> = world.entities.clone();
+        let include_disabled = A::INCLUDES_DISABLED || B::INCLUDES_DISABLED || C::INCLUDES_DISABLED || D::INCLUDES_DISABLED || E::INCLUDES_DISABLED || F::INCLUDES_DISABLED || G::INCLUDES_DISABLED || H::INCLUDES_DISABLED || I::INCLUDES_DISABLED || J::INCLUDES_DISABLED || K::INCLUDES_DISABLED;
+        let entities: Vec<Entity> = world.query_candidates(include_disabled, &[A::required(), B::required(), C::required(), D::required(), E::required(), F::required(), G::required(), H::required(), I::required(), J::required(), K::required()]);
 
         for entity in entities {
             unsafe {
@@ -902,7 +1453,21 @@ where
                 let k = K::get_mixed_component(&mut *world_ptr, entity);
 
                 if let (Some(a), Some(b), Some(c), Some(d), Some(e), Some(f), Some(g), Some(h), Some(i), Some(j), Some(k)) = (a, b, c, d, e, f, g, h, i, j, k) {
-                    results.push((entity, (a, b, c, d, e, f, g, h, i, j, k)));
+                    let item = (a, b, c, d, e, f, g, h, i, j, k);
+                    if keep(entity, &item) {
+                        A::record_write(&mut *world_ptr, entity);
+                        B::record_write(&mut *world_ptr, entity);
+                        C::record_write(&mut *world_ptr, entity);
+                        D::record_write(&mut *world_ptr, entity);
+                        E::record_write(&mut *world_ptr, entity);
+                        F::record_write(&mut *world_ptr, entity);
+                        G::record_write(&mut *world_ptr, entity);
+                        H::record_write(&mut *world_ptr, entity);
+                        I::record_write(&mut *world_ptr, entity);
+                        J::record_write(&mut *world_ptr, entity);
+                        K::record_write(&mut *world_ptr, entity);
+                        results.push((entity, item));
+                    }
                 }
             }
         }
@@ -929,9 +1494,14 @@ where
 {
     type Item = (A::Item, B::Item, C::Item, D::Item, E::Item, F::Item, G::Item, H::Item, I::Item, J::Item, K::Item, L::Item);
 
-    fn query_mixed(world: &'a mut World) -> Vec<(Entity, Self::Item)> {
+    fn accesses() -> Vec<ComponentAccess> {
+        [A::access(), B::access(), C::access(), D::access(), E::access(), F::access(), G::access(), H::access(), I::access(), J::access(), K::access(), L::access()].into_iter().flatten().collect()
+    }
+
+    fn query_mixed_filtered(world: &'a mut World, keep: &mut QueryFilter<'_, Self::Item>) -> Vec<(Entity, Self::Item)> {
         let mut results = Vec::new();
-        let entities: Vec<Entity> = world.entities.clone();
+        let include_disabled = A::INCLUDES_DISABLED || B::INCLUDES_DISABLED || C::INCLUDES_DISABLED || D::INCLUDES_DISABLED || E::INCLUDES_DISABLED || F::INCLUDES_DISABLED || G::INCLUDES_DISABLED || H::INCLUDES_DISABLED || I::INCLUDES_DISABLED || J::INCLUDES_DISABLED || K::INCLUDES_DISABLED || L::INCLUDES_DISABLED;
+        let entities: Vec<Entity> = world.query_candidates(include_disabled, &[A::required(), B::required(), C::required(), D::required(), E::required(), F::required(), G::required(), H::required(), I::required(), J::required(), K::required(), L::required()]);
 
         for entity in entities {
             unsafe {
@@ -950,7 +1520,22 @@ where
                 let l = L::get_mixed_component(&mut *world_ptr, entity);
 
                 if let (Some(a), Some(b), Some(c), Some(d), Some(e), Some(f), Some(g), Some(h), Some(i), Some(j), Some(k), Some(l)) = (a, b, c, d, e, f, g, h, i, j, k, l) {
-                    results.push((entity, (a, b, c, d, e, f, g, h, i, j, k, l)));
+                    let item = (a, b, c, d, e, f, g, h, i, j, k, l);
+                    if keep(entity, &item) {
+                        A::record_write(&mut *world_ptr, entity);
+                        B::record_write(&mut *world_ptr, entity);
+                        C::record_write(&mut *world_ptr, entity);
+                        D::record_write(&mut *world_ptr, entity);
+                        E::record_write(&mut *world_ptr, entity);
+                        F::record_write(&mut *world_ptr, entity);
+                        G::record_write(&mut *world_ptr, entity);
+                        H::record_write(&mut *world_ptr, entity);
+                        I::record_write(&mut *world_ptr, entity);
+                        J::record_write(&mut *world_ptr, entity);
+                        K::record_write(&mut *world_ptr, entity);
+                        L::record_write(&mut *world_ptr, entity);
+                        results.push((entity, item));
+                    }
                 }
             }
         }
@@ -978,9 +1563,14 @@ where
 {
     type Item = (A::Item, B::Item, C::Item, D::Item, E::Item, F::Item, G::Item, H::Item, I::Item, J::Item, K::Item, L::Item, M::Item);
 
-    fn query_mixed(world: &'a mut World) -> Vec<(Entity, Self::Item)> {
+    fn accesses() -> Vec<ComponentAccess> {
+        [A::access(), B::access(), C::access(), D::access(), E::access(), F::access(), G::access(), H::access(), I::access(), J::access(), K::access(), L::access(), M::access()].into_iter().flatten().collect()
+    }
+
+    fn query_mixed_filtered(world: &'a mut World, keep: &mut QueryFilter<'_, Self::Item>) -> Vec<(Entity, Self::Item)> {
         let mut results = Vec::new();
-        let entities: Vec<Entity> = world.entities.clone();
+        let include_disabled = A::INCLUDES_DISABLED || B::INCLUDES_DISABLED || C::INCLUDES_DISABLED || D::INCLUDES_DISABLED || E::INCLUDES_DISABLED || F::INCLUDES_DISABLED || G::INCLUDES_DISABLED || H::INCLUDES_DISABLED || I::INCLUDES_DISABLED || J::INCLUDES_DISABLED || K::INCLUDES_DISABLED || L::INCLUDES_DISABLED || M::INCLUDES_DISABLED;
+        let entities: Vec<Entity> = world.query_candidates(include_disabled, &[A::required(), B::required(), C::required(), D::required(), E::required(), F::required(), G::required(), H::required(), I::required(), J::required(), K::required(), L::required(), M::required()]);
 
         for entity in entities {
             unsafe {
@@ -1000,7 +1590,23 @@ where
                 let m = M::get_mixed_component(&mut *world_ptr, entity);
 
                 if let (Some(a), Some(b), Some(c), Some(d), Some(e), Some(f), Some(g), Some(h), Some(i), Some(j), Some(k), Some(l), Some(m)) = (a, b, c, d, e, f, g, h, i, j, k, l, m) {
-                    results.push((entity, (a, b, c, d, e, f, g, h, i, j, k, l, m)));
+                    let item = (a, b, c, d, e, f, g, h, i, j, k, l, m);
+                    if keep(entity, &item) {
+                        A::record_write(&mut *world_ptr, entity);
+                        B::record_write(&mut *world_ptr, entity);
+                        C::record_write(&mut *world_ptr, entity);
+                        D::record_write(&mut *world_ptr, entity);
+                        E::record_write(&mut *world_ptr, entity);
+                        F::record_write(&mut *world_ptr, entity);
+                        G::record_write(&mut *world_ptr, entity);
+                        H::record_write(&mut *world_ptr, entity);
+                        I::record_write(&mut *world_ptr, entity);
+                        J::record_write(&mut *world_ptr, entity);
+                        K::record_write(&mut *world_ptr, entity);
+                        L::record_write(&mut *world_ptr, entity);
+                        M::record_write(&mut *world_ptr, entity);
+                        results.push((entity, item));
+                    }
                 }
             }
         }
@@ -1029,9 +1635,14 @@ where
 {
     type Item = (A::Item, B::Item, C::Item, D::Item, E::Item, F::Item, G::Item, H::Item, I::Item, J::Item, K::Item, L::Item, M::Item, N::Item);
 
-    fn query_mixed(world: &'a mut World) -> Vec<(Entity, Self::Item)> {
+    fn accesses() -> Vec<ComponentAccess> {
+        [A::access(), B::access(), C::access(), D::access(), E::access(), F::access(), G::access(), H::access(), I::access(), J::access(), K::access(), L::access(), M::access(), N::access()].into_iter().flatten().collect()
+    }
+
+    fn query_mixed_filtered(world: &'a mut World, keep: &mut QueryFilter<'_, Self::Item>) -> Vec<(Entity, Self::Item)> {
         let mut results = Vec::new();
-        let entities: Vec<Entity> = world.entities.clone();
+        let include_disabled = A::INCLUDES_DISABLED || B::INCLUDES_DISABLED || C::INCLUDES_DISABLED || D::INCLUDES_DISABLED || E::INCLUDES_DISABLED || F::INCLUDES_DISABLED || G::INCLUDES_DISABLED || H::INCLUDES_DISABLED || I::INCLUDES_DISABLED || J::INCLUDES_DISABLED || K::INCLUDES_DISABLED || L::INCLUDES_DISABLED || M::INCLUDES_DISABLED || N::INCLUDES_DISABLED;
+        let entities: Vec<Entity> = world.query_candidates(include_disabled, &[A::required(), B::required(), C::required(), D::required(), E::required(), F::required(), G::required(), H::required(), I::required(), J::required(), K::required(), L::required(), M::required(), N::required()]);
 
         for entity in entities {
             unsafe {
@@ -1052,7 +1663,24 @@ where
                 let n = N::get_mixed_component(&mut *world_ptr, entity);
 
                 if let (Some(a), Some(b), Some(c), Some(d), Some(e), Some(f), Some(g), Some(h), Some(i), Some(j), Some(k), Some(l), Some(m), Some(n)) = (a, b, c, d, e, f, g, h, i, j, k, l, m, n) {
-                    results.push((entity, (a, b, c, d, e, f, g, h, i, j, k, l, m, n)));
+                    let item = (a, b, c, d, e, f, g, h, i, j, k, l, m, n);
+                    if keep(entity, &item) {
+                        A::record_write(&mut *world_ptr, entity);
+                        B::record_write(&mut *world_ptr, entity);
+                        C::record_write(&mut *world_ptr, entity);
+                        D::record_write(&mut *world_ptr, entity);
+                        E::record_write(&mut *world_ptr, entity);
+                        F::record_write(&mut *world_ptr, entity);
+                        G::record_write(&mut *world_ptr, entity);
+                        H::record_write(&mut *world_ptr, entity);
+                        I::record_write(&mut *world_ptr, entity);
+                        J::record_write(&mut *world_ptr, entity);
+                        K::record_write(&mut *world_ptr, entity);
+                        L::record_write(&mut *world_ptr, entity);
+                        M::record_write(&mut *world_ptr, entity);
+                        N::record_write(&mut *world_ptr, entity);
+                        results.push((entity, item));
+                    }
                 }
             }
         }
@@ -1082,9 +1710,14 @@ where
 {
     type Item = (A::Item, B::Item, C::Item, D::Item, E::Item, F::Item, G::Item, H::Item, I::Item, J::Item, K::Item, L::Item, M::Item, N::Item, O::Item);
 
-    fn query_mixed(world: &'a mut World) -> Vec<(Entity, Self::Item)> {
+    fn accesses() -> Vec<ComponentAccess> {
+        [A::access(), B::access(), C::access(), D::access(), E::access(), F::access(), G::access(), H::access(), I::access(), J::access(), K::access(), L::access(), M::access(), N::access(), O::access()].into_iter().flatten().collect()
+    }
+
+    fn query_mixed_filtered(world: &'a mut World, keep: &mut QueryFilter<'_, Self::Item>) -> Vec<(Entity, Self::Item)> {
         let mut results = Vec::new();
-        let entities: Vec<Entity> = world.entities.clone();
+        let include_disabled = A::INCLUDES_DISABLED || B::INCLUDES_DISABLED || C::INCLUDES_DISABLED || D::INCLUDES_DISABLED || E::INCLUDES_DISABLED || F::INCLUDES_DISABLED || G::INCLUDES_DISABLED || H::INCLUDES_DISABLED || I::INCLUDES_DISABLED || J::INCLUDES_DISABLED || K::INCLUDES_DISABLED || L::INCLUDES_DISABLED || M::INCLUDES_DISABLED || N::INCLUDES_DISABLED || O::INCLUDES_DISABLED;
+        let entities: Vec<Entity> = world.query_candidates(include_disabled, &[A::required(), B::required(), C::required(), D::required(), E::required(), F::required(), G::required(), H::required(), I::required(), J::required(), K::required(), L::required(), M::required(), N::required(), O::required()]);
 
         for entity in entities {
             unsafe {
@@ -1106,7 +1739,25 @@ where
                 let o = O::get_mixed_component(&mut *world_ptr, entity);
 
                 if let (Some(a), Some(b), Some(c), Some(d), Some(e), Some(f), Some(g), Some(h), Some(i), Some(j), Some(k), Some(l), Some(m), Some(n), Some(o)) = (a, b, c, d, e, f, g, h, i, j, k, l, m, n, o) {
-                    results.push((entity, (a, b, c, d, e, f, g, h, i, j, k, l, m, n, o)));
+                    let item = (a, b, c, d, e, f, g, h, i, j, k, l, m, n, o);
+                    if keep(entity, &item) {
+                        A::record_write(&mut *world_ptr, entity);
+                        B::record_write(&mut *world_ptr, entity);
+                        C::record_write(&mut *world_ptr, entity);
+                        D::record_write(&mut *world_ptr, entity);
+                        E::record_write(&mut *world_ptr, entity);
+                        F::record_write(&mut *world_ptr, entity);
+                        G::record_write(&mut *world_ptr, entity);
+                        H::record_write(&mut *world_ptr, entity);
+                        I::record_write(&mut *world_ptr, entity);
+                        J::record_write(&mut *world_ptr, entity);
+                        K::record_write(&mut *world_ptr, entity);
+                        L::record_write(&mut *world_ptr, entity);
+                        M::record_write(&mut *world_ptr, entity);
+                        N::record_write(&mut *world_ptr, entity);
+                        O::record_write(&mut *world_ptr, entity);
+                        results.push((entity, item));
+                    }
                 }
             }
         }
@@ -1137,9 +1788,14 @@ where
 {
     type Item = (A::Item, B::Item, C::Item, D::Item, E::Item, F::Item, G::Item, H::Item, I::Item, J::Item, K::Item, L::Item, M::Item, N::Item, O::Item, P::Item);
 
-    fn query_mixed(world: &'a mut World) -> Vec<(Entity, Self::Item)> {
+    fn accesses() -> Vec<ComponentAccess> {
+        [A::access(), B::access(), C::access(), D::access(), E::access(), F::access(), G::access(), H::access(), I::access(), J::access(), K::access(), L::access(), M::access(), N::access(), O::access(), P::access()].into_iter().flatten().collect()
+    }
+
+    fn query_mixed_filtered(world: &'a mut World, keep: &mut QueryFilter<'_, Self::Item>) -> Vec<(Entity, Self::Item)> {
         let mut results = Vec::new();
-        let entities: Vec<Entity> = world.entities.clone();
+        let include_disabled = A::INCLUDES_DISABLED || B::INCLUDES_DISABLED || C::INCLUDES_DISABLED || D::INCLUDES_DISABLED || E::INCLUDES_DISABLED || F::INCLUDES_DISABLED || G::INCLUDES_DISABLED || H::INCLUDES_DISABLED || I::INCLUDES_DISABLED || J::INCLUDES_DISABLED || K::INCLUDES_DISABLED || L::INCLUDES_DISABLED || M::INCLUDES_DISABLED || N::INCLUDES_DISABLED || O::INCLUDES_DISABLED || P::INCLUDES_DISABLED;
+        let entities: Vec<Entity> = world.query_candidates(include_disabled, &[A::required(), B::required(), C::required(), D::required(), E::required(), F::required(), G::required(), H::required(), I::required(), J::required(), K::required(), L::required(), M::required(), N::required(), O::required(), P::required()]);
 
         for entity in entities {
             unsafe {
@@ -1162,7 +1818,26 @@ where
                 let p = P::get_mixed_component(&mut *world_ptr, entity);
 
                 if let (Some(a), Some(b), Some(c), Some(d), Some(e), Some(f), Some(g), Some(h), Some(i), Some(j), Some(k), Some(l), Some(m), Some(n), Some(o), Some(p)) = (a, b, c, d, e, f, g, h, i, j, k, l, m, n, o, p) {
-                    results.push((entity, (a, b, c, d, e, f, g, h, i, j, k, l, m, n, o, p)));
+                    let item = (a, b, c, d, e, f, g, h, i, j, k, l, m, n, o, p);
+                    if keep(entity, &item) {
+                        A::record_write(&mut *world_ptr, entity);
+                        B::record_write(&mut *world_ptr, entity);
+                        C::record_write(&mut *world_ptr, entity);
+                        D::record_write(&mut *world_ptr, entity);
+                        E::record_write(&mut *world_ptr, entity);
+                        F::record_write(&mut *world_ptr, entity);
+                        G::record_write(&mut *world_ptr, entity);
+                        H::record_write(&mut *world_ptr, entity);
+                        I::record_write(&mut *world_ptr, entity);
+                        J::record_write(&mut *world_ptr, entity);
+                        K::record_write(&mut *world_ptr, entity);
+                        L::record_write(&mut *world_ptr, entity);
+                        M::record_write(&mut *world_ptr, entity);
+                        N::record_write(&mut *world_ptr, entity);
+                        O::record_write(&mut *world_ptr, entity);
+                        P::record_write(&mut *world_ptr, entity);
+                        results.push((entity, item));
+                    }
                 }
             }
         }
@@ -1177,6 +1852,12 @@ pub struct WorldView<InComponents, OutComponents> {
     _input_phantom: std::marker::PhantomData<InComponents>,
     _output_phantom: std::marker::PhantomData<OutComponents>,
     system_diff: SystemUpdateDiff,
+    /// Recorded component changes, combined per entity and component type
+    writes: coalesce::CoalescedWrites,
+    /// Components read by queries, while causality is traced
+    reads: Option<causality::ReadTracker>,
+    /// Changes queued by the system, applied when its update ends
+    commands: commands::CommandBuffer,
 }
 
 impl<I, O> WorldView<I, O> {
@@ -1187,14 +1868,38 @@ impl<I, O> WorldView<I, O> {
             _input_phantom: std::marker::PhantomData,
             _output_phantom: std::marker::PhantomData,
             system_diff: SystemUpdateDiff::new(),
+            writes: coalesce::CoalescedWrites::default(),
+            reads: world.causality_tracing.then(causality::ReadTracker::default),
+            commands: commands::CommandBuffer::default(),
         }
     }
 
-    /// Get the accumulated system diff from this WorldView session
-    pub fn get_system_diff(self) -> SystemUpdateDiff {
+    /// Get the accumulated system diff from this WorldView session, with at
+    /// most one change per entity and component type
+    pub fn get_system_diff(mut self) -> SystemUpdateDiff {
+        self.apply_commands();
+        self.writes.finish(&mut self.system_diff);
+        if let Some(reads) = self.reads.take() {
+            reads.finish(&mut self.system_diff);
+        }
+        self.system_diff.compact_sequences();
         self.system_diff
     }
 
+    /// Record the combined change of a component, see `CoalescedWrites::record`
+    pub(crate) fn record_write(
+        &mut self,
+        entity: Entity,
+        type_name: &str,
+        change: Option<DiffComponentChange>,
+        kind: Option<coalesce::WriteKind>,
+    ) {
+        if let Some(reads) = &mut self.reads {
+            reads.record_write(entity, type_name);
+        }
+        self.writes.record(&mut self.system_diff, entity, type_name, change, kind);
+    }
+
     /// Record a component modification (call this when you modify a component)
     pub fn record_component_modification<T: Diff + Clone + std::fmt::Debug + 'static>(
         &mut self, 
@@ -1202,20 +1907,67 @@ impl<I, O> WorldView<I, O> {
         old_value: &T, 
         new_value: &T
     ) {
+        if old_value.diff(new_value).is_none() {
+            return;
+        }
+        let type_name = std::any::type_name::<T>().split("::").last().unwrap_or(std::any::type_name::<T>());
+        // Later writes of the update replace the change of earlier ones
+        let coalesced = match self.writes.earlier(entity, type_name) {
+            Some(coalesce::WriteKind::Modified(base)) => base.downcast_ref::<T>().map(|base| {
+                let diff = base.diff(new_value)?;
+                Some(DiffComponentChange::Modified {
+                    entity,
+                    type_name: type_name.into(),
+                    diff: self.encode_modification(base, new_value, &diff).into(),
+                })
+            }),
+            // Still an addition, of the latest value
+            Some(coalesce::WriteKind::Added) => Some(Some(DiffComponentChange::Added {
+                entity,
+                type_name: type_name.into(),
+                data: self.encode_addition(new_value).into(),
+            })),
+            _ => None,
+        };
+        if let Some(change) = coalesced {
+            self.record_write(entity, type_name, change, None);
+            return;
+        }
+        // The type's recording policy may skip the modification, or record it
+        // from the last recorded value
+        let Some(base) = unsafe { self.world_mut() }.recorded_modification_base(entity, old_value, new_value) else {
+            return;
+        };
+        let old_value = base.as_ref().unwrap_or(old_value);
         if let Some(diff) = old_value.diff(new_value) {
-            let diff_str = T::diff_to_string(&diff);
-            let type_name = std::any::type_name::<T>().split("::").last().unwrap_or(std::any::type_name::<T>());
-            
             let change = DiffComponentChange::Modified {
                 entity,
-                type_name: type_name.to_string(),
-                diff: diff_str,
+                type_name: type_name.into(),
+                diff: self.encode_modification(old_value, new_value, &diff).into(),
             };
-            
-            self.system_diff.record_component_change(change);
+            let kind = coalesce::WriteKind::Modified(Box::new(old_value.clone()));
+            self.record_write(entity, type_name, Some(change), Some(kind));
         }
     }
 
+    /// Encode a modification, in a form replays can apply for replayable types
+    fn encode_modification<T: Diff + 'static>(&self, old_value: &T, new_value: &T, diff: &T::Diff) -> String {
+        unsafe { self.world() }
+            .component_registry
+            .get_by_type_id(TypeId::of::<T>())
+            .and_then(|registration| registration.encode_diff(old_value, new_value))
+            .unwrap_or_else(|| T::diff_to_string(diff))
+    }
+
+    /// Encode an added component, in a form replays can apply for replayable types
+    fn encode_addition<T: std::fmt::Debug + 'static>(&self, component: &T) -> String {
+        unsafe { self.world() }
+            .component_registry
+            .get_by_type_id(TypeId::of::<T>())
+            .and_then(|registration| registration.encode(component))
+            .unwrap_or_else(|| format!("{:?}", component))
+    }
+
     /// Record a component addition
     pub fn record_component_addition<T: std::fmt::Debug + 'static>(
         &mut self, 
@@ -1223,15 +1975,35 @@ impl<I, O> WorldView<I, O> {
         component: &T
     ) {
         let type_name = std::any::type_name::<T>().split("::").last().unwrap_or(std::any::type_name::<T>());
-        let data = format!("{:?}", component);
+        let data = self.encode_addition(component);
+        unsafe { self.world_mut() }.forget_recording_base(TypeId::of::<T>(), entity);
         
         let change = DiffComponentChange::Added {
             entity,
-            type_name: type_name.to_string(),
-            data,
+            type_name: type_name.into(),
+            data: data.into(),
         };
         
-        self.system_diff.record_component_change(change);
+        self.record_write(entity, type_name, Some(change), Some(coalesce::WriteKind::Added));
+    }
+
+    /// Record a component removal
+    pub fn record_component_removal<T: 'static>(&mut self, entity: Entity) {
+        let type_name = std::any::type_name::<T>().split("::").last().unwrap_or(std::any::type_name::<T>());
+        unsafe { self.world_mut() }.forget_recording_base(TypeId::of::<T>(), entity);
+        match self.writes.earlier(entity, type_name) {
+            // Added by this update, so there is nothing to record
+            Some(coalesce::WriteKind::Added | coalesce::WriteKind::Cancelled) => {
+                self.record_write(entity, type_name, None, Some(coalesce::WriteKind::Cancelled));
+            }
+            _ => {
+                let change = DiffComponentChange::Removed {
+                    entity,
+                    type_name: type_name.into(),
+                };
+                self.record_write(entity, type_name, Some(change), Some(coalesce::WriteKind::Removed));
+            }
+        }
     }
 
     /// Get a reference to the underlying world (unsafe due to raw pointer)
@@ -1249,60 +2021,99 @@ impl<I, O> WorldView<I, O> {
         unsafe { self.world_mut().create_entity() }
     }
 
+    /// Record the creation of an entity (call this after `create_entity`)
+    pub fn record_entity_creation(&mut self, entity: Entity) {
+        self.system_diff.record_world_operation(WorldOperation::CreateEntity(entity));
+    }
+
     /// Add a component to an entity
     pub fn add_component<T: 'static>(&mut self, entity: Entity, component: T) {
+        unsafe { self.world() }.check_access(|| vec![ComponentAccess::write::<T>()]);
+        unsafe { self.world() }.profile_accesses(|| vec![ComponentAccess::write::<T>()], 1);
         unsafe { self.world_mut().add_component(entity, component) }
     }
 
+    /// Remove a component from an entity
+    pub fn remove_component<T: 'static>(&mut self, entity: Entity) -> Option<T> {
+        unsafe { self.world() }.check_access(|| vec![ComponentAccess::write::<T>()]);
+        unsafe { self.world() }.profile_accesses(|| vec![ComponentAccess::write::<T>()], 1);
+        unsafe { self.world_mut().remove_component(entity) }
+    }
+
     /// Get a component for an entity (if it exists)
     pub fn get_component<T: 'static>(&self, entity: Entity) -> Option<&T> {
-        unsafe {
-            let world = self.world();
-            world
-                .components
-                .get(&TypeId::of::<T>())?
-                .iter()
-                .find_map(|(e, component)| {
-                    if *e == entity {
-                        component.downcast_ref::<T>()
-                    } else {
-                        None
-                    }
-                })
-        }
+        unsafe { self.world() }.check_access(|| vec![ComponentAccess::read::<T>()]);
+        unsafe { self.world() }.profile_accesses(|| vec![ComponentAccess::read::<T>()], 1);
+        unsafe { self.world().get_component::<T>(entity) }
     }
 
     /// Get a mutable component for an entity (if it exists)
     pub fn get_component_mut<T: 'static>(&mut self, entity: Entity) -> Option<&mut T> {
-        unsafe {
-            let world = self.world_mut();
-            world
-                .components
-                .get_mut(&TypeId::of::<T>())?
-                .iter_mut()
-                .find_map(|(e, component)| {
-                    if *e == entity {
-                        component.downcast_mut::<T>()
-                    } else {
-                        None
-                    }
-                })
-        }
+        unsafe { self.world() }.check_access(|| vec![ComponentAccess::write::<T>()]);
+        unsafe { self.world() }.profile_accesses(|| vec![ComponentAccess::write::<T>()], 1);
+        unsafe { self.world_mut().get_component_mut::<T>(entity) }
     }
 
     /// Query entities with multiple components, using Out<T> for mutable access and In<T> for immutable access
     /// Example: world_view.query_components::<(In<Position>, Out<Velocity>)>()
+    ///
+    /// Results are in entity order, so they are the same on every run and in replays.
     pub fn query_components<Q>(&mut self) -> Vec<(Entity, <Q as MixedMultiQuery<'_>>::Item)>
     where
         for<'a> Q: MixedMultiQuery<'a>,
     {
+        self.query_components_filtered::<Q, _>(|_, _| true)
+    }
+
+    /// Query entities like `query_components`, keeping only the results `keep`
+    /// accepts. The predicate runs as entities are visited, so rejected results
+    /// are never collected:
+    /// `world_view.query_components_filtered::<(In<Position>, In<Home>), _>(|_, (position, home)| position.distance(home) <= 3)`
+    pub fn query_components_filtered<'w, Q, F>(&'w mut self, mut keep: F) -> Vec<(Entity, <Q as MixedMultiQuery<'w>>::Item)>
+    where
+        for<'a> Q: MixedMultiQuery<'a>,
+        F: FnMut(Entity, &<Q as MixedMultiQuery<'w>>::Item) -> bool,
+    {
+        unsafe { self.world() }.check_access(Q::accesses);
+        unsafe { self.world() }.select_slice_window(Q::accesses);
+        // Results of earlier queries are no longer borrowed, so their writes are done
+        unsafe { self.world_mut() }.flush_observers();
         // Get the query results
-        let results = unsafe { Q::query_mixed(self.world_mut()) };
+        let world = self.world;
+        let results = unsafe { Q::query_mixed_filtered(&mut *world, &mut keep) };
+        if let Some(counts) = unsafe { (*world).query_counts.as_mut() } {
+            counts.record(results.len());
+        }
+        unsafe { (*world).profile_accesses(Q::accesses, results.len()) };
+        if let Some(reads) = &mut self.reads {
+            let read: Vec<&'static str> = Q::accesses()
+                .into_iter()
+                .filter(|access| !access.write)
+                .map(|access| access.type_name)
+                .collect();
+            for (entity, _) in &results {
+                reads.record_reads(*entity, &read);
+            }
+        }
         
         // For now, return results directly without tracking
         // TODO: Implement automatic change tracking
         results
     }
+
+    /// Query entities like `query_components`, sorted with a comparator, e.g. by render z-order:
+    /// `world_view.query_components_sorted_by::<(In<Sprite>, In<Position>), _>(|(_, a), (_, b)| a.0.z.cmp(&b.0.z))`
+    ///
+    /// The sort is stable, so results that compare equal stay in entity order.
+    pub fn query_components_sorted_by<'w, Q, F>(&'w mut self, mut compare: F) -> Vec<(Entity, <Q as MixedMultiQuery<'w>>::Item)>
+    where
+        for<'a> Q: MixedMultiQuery<'a>,
+        F: FnMut(&(Entity, <Q as MixedMultiQuery<'w>>::Item), &(Entity, <Q as MixedMultiQuery<'w>>::Item)) -> std::cmp::Ordering,
+    {
+        let mut results = self.query_components::<Q>();
+        results.sort_by(|a, b| compare(a, b));
+        results
+    }
 }
 
 /// Tracks a specific component change
@@ -1322,34 +2133,112 @@ pub enum ComponentOperation {
 }
 
 /// Enumeration for different world operations that can be tracked
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum WorldOperation {
     CreateEntity(Entity),
     RemoveEntity(Entity),
     CreateWorld(usize),
     RemoveWorld(usize),
     AddSystem(String), // System type name for replay identification
+    /// Entity joined the named group
+    AddToGroup(Entity, String),
+    /// Entity left the named group
+    RemoveFromGroup(Entity, String),
+    /// Source entity was linked to the target entity with the named relation
+    Relate(Entity, String, Entity),
+    /// Source entity was unlinked from the target entity
+    Unrelate(Entity, String, Entity),
+    /// Entity is skipped by queries until enabled again
+    DisableEntity(Entity),
+    EnableEntity(Entity),
+    /// Every entity was removed, systems and resources were kept
+    ClearEntities,
+    /// Every entity, system and resource was removed
+    ClearAll,
+    /// The history before this operation was dropped
+    ResetHistory,
+}
+
+/// Formats the operation the way it appears in replay logs, e.g. `CREATE_ENTITY Entity(0, 1)`
+impl std::fmt::Display for WorldOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WorldOperation::CreateEntity(entity) => write!(f, "CREATE_ENTITY {}", entity),
+            WorldOperation::RemoveEntity(entity) => write!(f, "REMOVE_ENTITY {}", entity),
+            WorldOperation::CreateWorld(world_id) => write!(f, "CREATE_WORLD {}", world_id),
+            WorldOperation::RemoveWorld(world_id) => write!(f, "REMOVE_WORLD {}", world_id),
+            WorldOperation::AddSystem(system_type) => write!(f, "ADD_SYSTEM {}", system_type),
+            WorldOperation::AddToGroup(entity, group) => write!(f, "ADD_TO_GROUP {} {}", entity, group),
+            WorldOperation::RemoveFromGroup(entity, group) => write!(f, "REMOVE_FROM_GROUP {} {}", entity, group),
+            WorldOperation::Relate(source, relation, target) => write!(f, "RELATE {} {} {}", source, relation, target),
+            WorldOperation::Unrelate(source, relation, target) => write!(f, "UNRELATE {} {} {}", source, relation, target),
+            WorldOperation::DisableEntity(entity) => write!(f, "DISABLE_ENTITY {}", entity),
+            WorldOperation::EnableEntity(entity) => write!(f, "ENABLE_ENTITY {}", entity),
+            WorldOperation::ClearEntities => write!(f, "CLEAR_ENTITIES"),
+            WorldOperation::ClearAll => write!(f, "CLEAR_ALL"),
+            WorldOperation::ResetHistory => write!(f, "RESET_HISTORY"),
+        }
+    }
 }
 
 /// Enhanced component change operations for better tracking
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DiffComponentChange {
     Added {
         entity: Entity,
-        type_name: String,
-        data: String,
+        type_name: InternedStr,
+        data: InternedStr,
     },
     Modified {
         entity: Entity,
-        type_name: String,
-        diff: String,
+        type_name: InternedStr,
+        diff: InternedStr,
     },
     Removed {
         entity: Entity,
-        type_name: String,
+        type_name: InternedStr,
     },
 }
 
+impl DiffComponentChange {
+    /// Get the entity whose component changed
+    pub fn entity(&self) -> Entity {
+        match self {
+            DiffComponentChange::Added { entity, .. }
+            | DiffComponentChange::Modified { entity, .. }
+            | DiffComponentChange::Removed { entity, .. } => *entity,
+        }
+    }
+
+    /// Get the short type name of the changed component
+    pub fn type_name(&self) -> &str {
+        match self {
+            DiffComponentChange::Added { type_name, .. }
+            | DiffComponentChange::Modified { type_name, .. }
+            | DiffComponentChange::Removed { type_name, .. } => type_name,
+        }
+    }
+}
+
+/// Formats the change the way it appears in replay logs, e.g. `MOD Entity(0, 1) Position PositionDiff { .. }`
+impl std::fmt::Display for DiffComponentChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DiffComponentChange::Added { entity, type_name, data } => {
+                write!(f, "ADD {} {} {}", entity, type_name, data)
+            }
+            DiffComponentChange::Modified { entity, type_name, diff } => {
+                write!(f, "MOD {} {} {}", entity, type_name, diff)
+            }
+            DiffComponentChange::Removed { entity, type_name } => {
+                write!(f, "REM {} {}", entity, type_name)
+            }
+        }
+    }
+}
+
 /// Trait for components that can be tracked in the diff change system
 pub trait DiffComponent: Diff + std::fmt::Debug + 'static {
     /// Serialize the component to a string representation
@@ -1368,6 +2257,10 @@ pub trait DiffComponent: Diff + std::fmt::Debug + 'static {
 pub struct SystemInitDiff {
     pub component_changes: Vec<DiffComponentChange>,
     pub world_operations: Vec<WorldOperation>,
+    /// Sequence number of each component change, see `SystemUpdateDiff::change_sequence`
+    pub change_sequence: Vec<u64>,
+    /// Sequence number of each world operation
+    pub operation_sequence: Vec<u64>,
 }
 
 impl Default for SystemInitDiff {
@@ -1381,23 +2274,88 @@ impl SystemInitDiff {
         Self {
             component_changes: Vec::new(),
             world_operations: Vec::new(),
+            change_sequence: Vec::new(),
+            operation_sequence: Vec::new(),
         }
     }
 
     pub fn record_component_change(&mut self, change: DiffComponentChange) {
+        self.change_sequence.push((self.component_changes.len() + self.world_operations.len()) as u64);
         self.component_changes.push(change);
     }
 
     pub fn record_world_operation(&mut self, operation: WorldOperation) {
+        self.operation_sequence.push((self.component_changes.len() + self.world_operations.len()) as u64);
         self.world_operations.push(operation);
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.component_changes.is_empty() && self.world_operations.is_empty()
+    }
+}
+
+/// Keeps the order in which the system recorded its changes and operations
+impl From<SystemUpdateDiff> for SystemInitDiff {
+    fn from(diff: SystemUpdateDiff) -> Self {
+        let (change_sequence, operation_sequence) = diff.sequences();
+        Self {
+            component_changes: diff.component_changes,
+            world_operations: diff.world_operations,
+            change_sequence,
+            operation_sequence,
+        }
+    }
+}
+
+impl From<SystemInitDiff> for SystemUpdateDiff {
+    fn from(diff: SystemInitDiff) -> Self {
+        let mut update_diff = SystemUpdateDiff::new();
+        update_diff.component_changes = diff.component_changes;
+        update_diff.world_operations = diff.world_operations;
+        update_diff.set_sequences(diff.change_sequence, diff.operation_sequence);
+        update_diff
+    }
 }
 
 /// Enhanced system update diff tracking with diff components
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SystemUpdateDiff {
     pub component_changes: Vec<DiffComponentChange>,
     pub world_operations: Vec<WorldOperation>,
+    /// Panic message if the system panicked during the update
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub failure: Option<String>,
+    /// Error reported by the system's `try_update`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub error: Option<String>,
+    /// Execution statistics, recorded when enabled with `World::set_system_stats`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub stats: Option<SystemStats>,
+    /// Components read before each change, recorded when enabled with
+    /// `World::set_causality_tracing`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub causes: Vec<WriteCause>,
+    /// Names of the `debug_assert_invariant` assertions that failed during the update
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub failed_assertions: Vec<String>,
+    /// Sequence number of each component change, increasing in the order the
+    /// system recorded its changes and operations. Empty if unknown.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub change_sequence: Vec<u64>,
+    /// Sequence number of each world operation, see `change_sequence`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub operation_sequence: Vec<u64>,
+    /// Sequence number of the next recorded entry
+    #[cfg_attr(feature = "serde", serde(skip))]
+    next_sequence: u64,
+}
+
+/// A component change or world operation of a system diff, see `SystemUpdateDiff::entries`
+#[derive(Debug, Clone, Copy)]
+pub enum DiffEntry<'a> {
+    Change(&'a DiffComponentChange),
+    Operation(&'a WorldOperation),
 }
 
 impl Default for SystemUpdateDiff {
@@ -1411,15 +2369,199 @@ impl SystemUpdateDiff {
         Self {
             component_changes: Vec::new(),
             world_operations: Vec::new(),
+            failure: None,
+            error: None,
+            stats: None,
+            causes: Vec::new(),
+            failed_assertions: Vec::new(),
+            change_sequence: Vec::new(),
+            operation_sequence: Vec::new(),
+            next_sequence: 0,
         }
     }
 
     pub fn record_component_change(&mut self, change: DiffComponentChange) {
+        let sequence = self.take_sequence();
         self.component_changes.push(change);
+        self.change_sequence.push(sequence);
     }
 
     pub fn record_world_operation(&mut self, operation: WorldOperation) {
+        let sequence = self.take_sequence();
         self.world_operations.push(operation);
+        self.operation_sequence.push(sequence);
+    }
+
+    /// Record the changes and operations of another diff after the ones
+    /// recorded so far, in the order they were recorded there
+    pub(crate) fn append(&mut self, other: SystemUpdateDiff) {
+        for entry in other.entries() {
+            match entry {
+                DiffEntry::Change(change) => self.record_component_change(change.clone()),
+                DiffEntry::Operation(operation) => self.record_world_operation(operation.clone()),
+            }
+        }
+    }
+
+    /// Get the sequence number of the next recorded entry
+    fn take_sequence(&mut self) -> u64 {
+        let recorded = (self.component_changes.len() + self.world_operations.len()) as u64;
+        let sequence = self.next_sequence.max(recorded);
+        self.next_sequence = sequence + 1;
+        sequence
+    }
+
+    /// Check if the sequence numbers cover every change and operation
+    fn is_sequenced(&self) -> bool {
+        self.change_sequence.len() == self.component_changes.len()
+            && self.operation_sequence.len() == self.world_operations.len()
+    }
+
+    /// Replace a recorded component change, moving it after all entries
+    /// recorded so far
+    pub(crate) fn replace_component_change(&mut self, index: usize, change: DiffComponentChange) {
+        let sequence = self.take_sequence();
+        self.component_changes[index] = change;
+        if let Some(recorded) = self.change_sequence.get_mut(index) {
+            *recorded = sequence;
+        }
+    }
+
+    /// Remove a recorded component change
+    pub(crate) fn remove_component_change(&mut self, index: usize) {
+        self.component_changes.remove(index);
+        if index < self.change_sequence.len() {
+            self.change_sequence.remove(index);
+        }
+    }
+
+    /// Keep only the component changes accepted by `keep`, with their sequence numbers
+    pub fn retain_component_changes(&mut self, mut keep: impl FnMut(&DiffComponentChange) -> bool) {
+        if !self.is_sequenced() {
+            self.component_changes.retain(keep);
+            return;
+        }
+        let mut sequence = self.change_sequence.iter();
+        let mut kept_sequence = Vec::new();
+        self.component_changes.retain(|change| {
+            let number = sequence.next().copied().unwrap_or_default();
+            let kept = keep(change);
+            if kept {
+                kept_sequence.push(number);
+            }
+            kept
+        });
+        self.change_sequence = kept_sequence;
+    }
+
+    /// Set the sequence numbers of the changes and operations. Numbers that
+    /// do not cover every entry once are dropped, leaving the order unknown.
+    pub fn set_sequences(&mut self, change_sequence: Vec<u64>, operation_sequence: Vec<u64>) {
+        let mut numbers: Vec<u64> = change_sequence.iter().chain(&operation_sequence).copied().collect();
+        numbers.sort_unstable();
+        numbers.dedup();
+        self.change_sequence = change_sequence;
+        self.operation_sequence = operation_sequence;
+        if !self.is_sequenced() || numbers.len() != self.change_sequence.len() + self.operation_sequence.len() {
+            self.change_sequence.clear();
+            self.operation_sequence.clear();
+        }
+        self.next_sequence = numbers.last().map_or(0, |last| last + 1);
+    }
+
+    /// Renumber the changes and operations 0, 1, 2... keeping their order
+    pub fn compact_sequences(&mut self) {
+        if !self.is_sequenced() {
+            return;
+        }
+        let mut numbers: Vec<u64> = self.change_sequence.iter().chain(&self.operation_sequence).copied().collect();
+        numbers.sort_unstable();
+        let rank = |number: &mut u64| *number = numbers.partition_point(|other| other < number) as u64;
+        self.change_sequence.iter_mut().for_each(rank);
+        self.operation_sequence.iter_mut().for_each(rank);
+        self.next_sequence = numbers.len() as u64;
+    }
+
+    /// Get the sequence numbers of the changes and of the operations.
+    /// Without recorded numbers, as in older logs, the operations come first.
+    pub fn sequences(&self) -> (Vec<u64>, Vec<u64>) {
+        if self.is_sequenced() {
+            return (self.change_sequence.clone(), self.operation_sequence.clone());
+        }
+        let operations = self.world_operations.len() as u64;
+        (
+            (operations..operations + self.component_changes.len() as u64).collect(),
+            (0..operations).collect(),
+        )
+    }
+
+    /// Get the changes and operations in the order the system recorded them
+    pub fn entries(&self) -> Vec<DiffEntry<'_>> {
+        let (change_sequence, operation_sequence) = self.sequences();
+        let mut entries: Vec<(u64, DiffEntry<'_>)> = operation_sequence
+            .into_iter()
+            .zip(self.world_operations.iter().map(DiffEntry::Operation))
+            .chain(change_sequence.into_iter().zip(self.component_changes.iter().map(DiffEntry::Change)))
+            .collect();
+        entries.sort_by_key(|(sequence, _)| *sequence);
+        entries.into_iter().map(|(_, entry)| entry).collect()
+    }
+
+    /// Get the order of the entries as `C` for the next component change and
+    /// `O` for the next world operation, e.g. `C O C`. None if the operations
+    /// come first, the order assumed without sequence numbers.
+    pub fn entry_order(&self) -> Option<String> {
+        let entries = self.entries();
+        let operations_first = entries
+            .iter()
+            .skip(self.world_operations.len())
+            .all(|entry| matches!(entry, DiffEntry::Change(_)));
+        if operations_first {
+            return None;
+        }
+        let order: Vec<&str> = entries
+            .iter()
+            .map(|entry| match entry {
+                DiffEntry::Change(_) => "C",
+                DiffEntry::Operation(_) => "O",
+            })
+            .collect();
+        Some(order.join(" "))
+    }
+
+    /// Set the sequence numbers from an order written by `entry_order`
+    pub fn set_entry_order(&mut self, order: &str) -> Result<(), String> {
+        let mut change_sequence = Vec::new();
+        let mut operation_sequence = Vec::new();
+        for (sequence, entry) in order.split_whitespace().enumerate() {
+            match entry {
+                "C" => change_sequence.push(sequence as u64),
+                "O" => operation_sequence.push(sequence as u64),
+                other => return Err(format!("unknown entry '{}', expected C or O", other)),
+            }
+        }
+        if change_sequence.len() != self.component_changes.len() || operation_sequence.len() != self.world_operations.len() {
+            return Err(format!(
+                "order lists {} changes and {} operations, the system has {} and {}",
+                change_sequence.len(),
+                operation_sequence.len(),
+                self.component_changes.len(),
+                self.world_operations.len()
+            ));
+        }
+        self.set_sequences(change_sequence, operation_sequence);
+        Ok(())
+    }
+
+    /// Forget the recorded order, so the operations are applied first
+    pub fn clear_sequences(&mut self) {
+        self.change_sequence.clear();
+        self.operation_sequence.clear();
+    }
+
+    /// Mark the system as failed with the given panic message
+    pub fn record_failure(&mut self, message: impl Into<String>) {
+        self.failure = Some(message.into());
     }
 
     pub fn component_changes(&self) -> &[DiffComponentChange] {
@@ -1429,6 +2571,54 @@ impl SystemUpdateDiff {
     pub fn world_operations(&self) -> &[WorldOperation] {
         &self.world_operations
     }
+
+    /// Get the panic message if the system failed
+    pub fn failure(&self) -> Option<&str> {
+        self.failure.as_deref()
+    }
+
+    /// Record an error reported by the system
+    pub fn record_error(&mut self, message: impl Into<String>) {
+        self.error = Some(message.into());
+    }
+
+    /// Get the error reported by the system, if any
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// Record execution statistics of the update
+    pub fn record_stats(&mut self, stats: SystemStats) {
+        self.stats = Some(stats);
+    }
+
+    /// Get the execution statistics of the update, if they were recorded
+    pub fn stats(&self) -> Option<&SystemStats> {
+        self.stats.as_ref()
+    }
+
+    /// Record the components read before a change
+    pub fn record_cause(&mut self, cause: WriteCause) {
+        self.causes.push(cause);
+    }
+
+    /// Get the components read before each change, if causality tracing was on
+    pub fn causes(&self) -> &[WriteCause] {
+        &self.causes
+    }
+
+    /// Record a failed assertion, once per update
+    pub fn record_failed_assertion(&mut self, name: impl Into<String>) {
+        let name = name.into();
+        if !self.failed_assertions.contains(&name) {
+            self.failed_assertions.push(name);
+        }
+    }
+
+    /// Get the names of the assertions that failed during the update
+    pub fn failed_assertions(&self) -> &[String] {
+        &self.failed_assertions
+    }
 }
 
 /// Enhanced system deinitialization diff tracking with diff components
@@ -1436,6 +2626,10 @@ impl SystemUpdateDiff {
 pub struct SystemDeinitDiff {
     pub component_changes: Vec<DiffComponentChange>,
     pub world_operations: Vec<WorldOperation>,
+    /// Sequence number of each component change, see `SystemUpdateDiff::change_sequence`
+    pub change_sequence: Vec<u64>,
+    /// Sequence number of each world operation
+    pub operation_sequence: Vec<u64>,
 }
 
 impl Default for SystemDeinitDiff {
@@ -1449,22 +2643,57 @@ impl SystemDeinitDiff {
         Self {
             component_changes: Vec::new(),
             world_operations: Vec::new(),
+            change_sequence: Vec::new(),
+            operation_sequence: Vec::new(),
         }
     }
 
     pub fn record_component_change(&mut self, change: DiffComponentChange) {
+        self.change_sequence.push((self.component_changes.len() + self.world_operations.len()) as u64);
         self.component_changes.push(change);
     }
 
     pub fn record_world_operation(&mut self, operation: WorldOperation) {
+        self.operation_sequence.push((self.component_changes.len() + self.world_operations.len()) as u64);
         self.world_operations.push(operation);
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.component_changes.is_empty() && self.world_operations.is_empty()
+    }
+}
+
+/// Keeps the order in which the system recorded its changes and operations
+impl From<SystemUpdateDiff> for SystemDeinitDiff {
+    fn from(diff: SystemUpdateDiff) -> Self {
+        let (change_sequence, operation_sequence) = diff.sequences();
+        Self {
+            component_changes: diff.component_changes,
+            world_operations: diff.world_operations,
+            change_sequence,
+            operation_sequence,
+        }
+    }
+}
+
+impl From<SystemDeinitDiff> for SystemUpdateDiff {
+    fn from(diff: SystemDeinitDiff) -> Self {
+        let mut update_diff = SystemUpdateDiff::new();
+        update_diff.component_changes = diff.component_changes;
+        update_diff.world_operations = diff.world_operations;
+        update_diff.set_sequences(diff.change_sequence, diff.operation_sequence);
+        update_diff
+    }
 }
 
 /// Tracks overall world update changes
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct WorldUpdateDiff {
     system_diffs: Vec<SystemUpdateDiff>,
+    /// Markers added with `World::annotate_replay` during the frame
+    #[cfg_attr(feature = "serde", serde(default))]
+    annotations: Vec<replay_metadata::ReplayAnnotation>,
 }
 
 impl Default for WorldUpdateDiff {
@@ -1477,6 +2706,7 @@ impl WorldUpdateDiff {
     pub fn new() -> Self {
         Self {
             system_diffs: Vec::new(),
+            annotations: Vec::new(),
         }
     }
 
@@ -1488,12 +2718,35 @@ impl WorldUpdateDiff {
     pub fn system_diffs(&self) -> &[SystemUpdateDiff] {
         &self.system_diffs
     }
+
+    /// Add a marker to the frame
+    pub fn record_annotation(&mut self, annotation: replay_metadata::ReplayAnnotation) {
+        self.annotations.push(annotation);
+    }
+
+    /// Get the markers added during the frame
+    pub fn annotations(&self) -> &[replay_metadata::ReplayAnnotation] {
+        &self.annotations
+    }
 }
 
 /// Maintains history of all world changes for replay functionality
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct WorldUpdateHistory {
     updates: Vec<WorldUpdateDiff>,
+    /// State of the world before the first update, read from a replay log header
+    #[cfg_attr(feature = "serde", serde(default))]
+    initial_state: Option<snapshot::WorldSnapshot>,
+    /// Build and settings the session was recorded with
+    #[cfg_attr(feature = "serde", serde(default))]
+    metadata: replay_metadata::ReplayMetadata,
+    /// World states between the updates of sampled logs
+    #[cfg_attr(feature = "serde", serde(default))]
+    keyframes: Vec<snapshot::Keyframe>,
+    /// Distinct strings of the recorded changes, shared by all of them
+    #[cfg_attr(feature = "serde", serde(skip))]
+    interner: intern::StringInterner,
 }
 
 impl Default for WorldUpdateHistory {
@@ -1506,10 +2759,17 @@ impl WorldUpdateHistory {
     pub fn new() -> Self {
         Self {
             updates: Vec::new(),
+            initial_state: None,
+            metadata: replay_metadata::ReplayMetadata::default(),
+            keyframes: Vec::new(),
+            interner: intern::StringInterner::default(),
         }
     }
 
-    pub fn record(&mut self, diff: WorldUpdateDiff) {
+    pub fn record(&mut self, mut diff: WorldUpdateDiff) {
+        for system_diff in &mut diff.system_diffs {
+            self.interner.intern_system_diff(system_diff);
+        }
         self.updates.push(diff);
     }
 
@@ -1523,6 +2783,51 @@ impl WorldUpdateHistory {
         self.updates.len()
     }
 
+    /// Get the state of the world before the first update, if it was recorded
+    pub fn initial_state(&self) -> Option<&snapshot::WorldSnapshot> {
+        self.initial_state.as_ref()
+    }
+
+    /// Set the state of the world before the first update
+    pub fn set_initial_state(&mut self, mut snapshot: snapshot::WorldSnapshot) {
+        self.interner.intern_system_diff(&mut snapshot.diff);
+        self.initial_state = Some(snapshot);
+    }
+
+    /// Get the build and settings the session was recorded with
+    pub fn metadata(&self) -> &replay_metadata::ReplayMetadata {
+        &self.metadata
+    }
+
+    /// Set the build and settings the session was recorded with
+    pub fn set_metadata(&mut self, metadata: replay_metadata::ReplayMetadata) {
+        self.metadata = metadata;
+    }
+
+    /// Get the world states recorded between updates, in order
+    pub fn keyframes(&self) -> &[snapshot::Keyframe] {
+        &self.keyframes
+    }
+
+    /// Add a world state recorded after the updates recorded so far
+    pub fn record_keyframe(&mut self, frame: usize, mut state: snapshot::WorldSnapshot) {
+        self.interner.intern_system_diff(&mut state.diff);
+        self.keyframes.push(snapshot::Keyframe {
+            frame,
+            update_index: self.updates.len(),
+            state,
+        });
+    }
+
+    /// Get every marker with the index of the update it was added in
+    pub fn annotations(&self) -> Vec<(usize, &replay_metadata::ReplayAnnotation)> {
+        self.updates
+            .iter()
+            .enumerate()
+            .flat_map(|(frame, update)| update.annotations().iter().map(move |annotation| (frame, annotation)))
+            .collect()
+    }
+
     /// Check if the history is empty
     pub fn is_empty(&self) -> bool {
         self.updates.is_empty()
@@ -1532,6 +2837,41 @@ impl WorldUpdateHistory {
     pub fn clear(&mut self) {
         self.updates.clear();
     }
+
+    /// Get the indices of the updates in which a system panicked or reported an error
+    pub fn error_frames(&self) -> Vec<usize> {
+        self.updates
+            .iter()
+            .enumerate()
+            .filter(|(_, update)| {
+                update
+                    .system_diffs()
+                    .iter()
+                    .any(|system| system.failure().is_some() || system.error().is_some())
+            })
+            .map(|(frame, _)| frame)
+            .collect()
+    }
+
+    /// Get the indices of the updates in which a `debug_assert_invariant` assertion failed
+    pub fn assertion_frames(&self) -> Vec<usize> {
+        self.updates
+            .iter()
+            .enumerate()
+            .filter(|(_, update)| {
+                update
+                    .system_diffs()
+                    .iter()
+                    .any(|system| !system.failed_assertions().is_empty())
+            })
+            .map(|(frame, _)| frame)
+            .collect()
+    }
+
+    /// Drop every update recorded after the first `len` updates
+    pub(crate) fn truncate(&mut self, len: usize) {
+        self.updates.truncate(len);
+    }
 }
 
 /// Configuration for automatic replay logging
@@ -1547,6 +2887,16 @@ pub struct ReplayLogConfig {
     pub flush_interval: usize,
     /// Whether to include detailed component changes in logs
     pub include_component_details: bool,
+    /// Component types whose changes are logged
+    pub components: recording::RecordFilter,
+    /// Systems whose updates are logged
+    pub systems: recording::RecordFilter,
+    /// Log only some frames, plus keyframes holding the full world state
+    pub sampling: Option<recording::FrameSampling>,
+    /// Write log files on a background thread instead of the game thread
+    pub background: Option<log_writer::BackgroundWriting>,
+    /// How soon logged updates are flushed, so they survive a crash
+    pub durability: log_writer::LogDurability,
 }
 
 impl Default for ReplayLogConfig {
@@ -1557,17 +2907,155 @@ impl Default for ReplayLogConfig {
             file_prefix: "game_replay".to_string(),
             flush_interval: 100,
             include_component_details: true,
+            components: recording::RecordFilter::default(),
+            systems: recording::RecordFilter::default(),
+            sampling: None,
+            background: None,
+            durability: log_writer::LogDurability::default(),
+        }
+    }
+}
+
+/// Writer adapter counting the bytes written through it
+struct CountingWriter<W> {
+    inner: W,
+    bytes_written: u64,
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.bytes_written += written as u64;
+        telemetry::replay_log_bytes_written(written);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Write one world update in the replay log format
+fn write_update_entry<W: Write + ?Sized>(
+    writer: &mut W,
+    number: usize,
+    update: &WorldUpdateDiff,
+    include_component_details: bool,
+) -> std::io::Result<()> {
+    // Write update header
+    writeln!(writer, "UPDATE {}", number)?;
+    for annotation in update.annotations() {
+        writeln!(writer, "  {}", annotation)?;
+    }
+    writeln!(writer, "SYSTEMS: {}", update.system_diffs().len())?;
+
+    // Log each system update
+    for (system_idx, system_diff) in update.system_diffs().iter().enumerate() {
+        writeln!(writer, "  SYSTEM {}", system_idx)?;
+
+        // Log component changes
+        if include_component_details && !system_diff.component_changes().is_empty() {
+            writeln!(writer, "    COMPONENT_CHANGES: {}", system_diff.component_changes().len())?;
+            for change in system_diff.component_changes() {
+                writeln!(writer, "      {}", change)?;
+            }
+        }
+
+        // Log world operations
+        if !system_diff.world_operations().is_empty() {
+            writeln!(writer, "    WORLD_OPERATIONS: {}", system_diff.world_operations().len())?;
+            for operation in system_diff.world_operations() {
+                writeln!(writer, "      {}", operation)?;
+            }
+        }
+
+        // Log how changes and operations interleaved, unless the operations came first
+        if let Some(order) = system_diff.entry_order() {
+            writeln!(writer, "    ORDER: {}", order)?;
+        }
+
+        // Log the panic message of a failed system on one line
+        if let Some(failure) = system_diff.failure() {
+            writeln!(writer, "    FAILED: {}", failure.replace('\n', " "))?;
+        }
+        if let Some(error) = system_diff.error() {
+            writeln!(writer, "    ERROR: {}", error.replace('\n', " "))?;
         }
+        for name in system_diff.failed_assertions() {
+            writeln!(writer, "    ASSERT: {}", name.replace('\n', " "))?;
+        }
+        if let Some(stats) = system_diff.stats() {
+            writeln!(writer, "    STATS: {}", stats)?;
+        }
+        for cause in system_diff.causes() {
+            writeln!(writer, "    CAUSE: {}", cause)?;
+        }
+    }
+
+    writeln!(writer) // Empty line between updates
+}
+
+/// Write one world update without the systems and component types excluded by the configuration
+fn write_logged_update<W: Write + ?Sized>(
+    writer: &mut W,
+    number: usize,
+    update: &WorldUpdateDiff,
+    system_names: &[&str],
+    config: &ReplayLogConfig,
+) -> std::io::Result<()> {
+    if config.components.records_all() && config.systems.records_all() {
+        write_update_entry(writer, number, update, config.include_component_details)
+    } else {
+        let update = recording::filter_update(update, system_names, &config.components, &config.systems);
+        write_update_entry(writer, number, &update, config.include_component_details)
     }
 }
 
+/// Write the diagnostic record of a slow frame as comment lines
+fn write_slow_frame<W: Write + ?Sized>(writer: &mut W, record: &SlowFrameRecord) -> std::io::Result<()> {
+    writeln!(
+        writer,
+        "# SLOW_FRAME {} duration_us={} budget_us={} entities={} components={}",
+        record.frame,
+        record.duration.as_micros(),
+        record.budget.as_micros(),
+        record.entity_count,
+        record.component_count
+    )?;
+    for timing in &record.system_timings {
+        writeln!(
+            writer,
+            "#   SYSTEM_TIME {} {}us changes={}",
+            timing.system_name,
+            timing.duration.as_micros(),
+            timing.change_count
+        )?;
+    }
+    for timing in &record.largest_diffs {
+        writeln!(writer, "#   LARGEST_DIFF {} changes={}", timing.system_name, timing.change_count)?;
+    }
+    writeln!(writer)
+}
+
+/// Destination of a replay log
+enum LogOutput {
+    /// Entries are written on the thread logging them
+    Direct(CountingWriter<Box<dyn Write>>),
+    /// Entries are written on a background thread
+    Background(log_writer::BackgroundWriter),
+}
+
 /// Automatic replay logger that saves game history to files for analysis
-#[derive(Debug)]
 pub struct AutoReplayLogger {
     config: ReplayLogConfig,
-    log_file: Option<BufWriter<File>>,
+    log_file: Option<LogOutput>,
     session_id: String,
     update_count: usize,
+    /// Written to the header when the log is initialized
+    metadata: replay_metadata::ReplayMetadata,
+    /// Totals of the finalized log
+    bytes_written: u64,
+    dropped_updates: usize,
 }
 
 impl AutoReplayLogger {
@@ -1580,19 +3068,32 @@ impl AutoReplayLogger {
             log_file: None,
             session_id,
             update_count: 0,
+            metadata: replay_metadata::ReplayMetadata::default(),
+            bytes_written: 0,
+            dropped_updates: 0,
         }
     }
 
-    /// Generate a unique session ID based on timestamp
+    /// Set the metadata written to the header; must be called before initializing
+    pub fn set_metadata(&mut self, metadata: replay_metadata::ReplayMetadata) {
+        self.metadata = metadata;
+    }
+
+    /// Generate a unique session ID based on timestamp, or a sequence number
+    /// when the wall clock is unavailable
     fn generate_session_id() -> String {
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs();
-        format!("{}", timestamp)
+        static NEXT_SESSION: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(1);
+        match clock::unix_time() {
+            Some(timestamp) => format!("{}", timestamp.as_secs()),
+            None => format!(
+                "session{}",
+                NEXT_SESSION.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+            ),
+        }
     }
 
     /// Initialize logging - create directory and log file
+    #[cfg(feature = "fs")]
     pub fn initialize(&mut self) -> Result<(), std::io::Error> {
         if !self.config.enabled {
             return Ok(());
@@ -1603,107 +3104,189 @@ impl AutoReplayLogger {
 
         // Create log file
         let filename = format!("{}_{}.log", self.config.file_prefix, self.session_id);
-        let filepath = Path::new(&self.config.log_directory).join(filename);
+        let filepath = std::path::Path::new(&self.config.log_directory).join(filename);
         
-        let file = OpenOptions::new()
+        let file = std::fs::OpenOptions::new()
             .create(true)
             .write(true)
             .truncate(true)
             .open(filepath)?;
-        
-        let mut writer = BufWriter::new(file);
-        
-        // Write header
-        writeln!(writer, "# ECS Replay Log")?;
-        writeln!(writer, "# Session ID: {}", self.session_id)?;
-        writeln!(writer, "# Timestamp: {}", chrono::Utc::now().format("%Y-%m-%d %H:%M:%S UTC"))?;
-        writeln!(writer, "# Configuration: {:?}", self.config)?;
-        writeln!(writer, "# Format: Each line represents one world update")?;
-        writeln!(writer)?;
-        
-        self.log_file = Some(writer);
-        
-        println!("Replay logging initialized - Session ID: {}", self.session_id);
-        Ok(())
+
+        if self.config.durability == log_writer::LogDurability::SyncEveryFrame {
+            return self.initialize_with_send_sink(std::io::BufWriter::new(log_writer::SyncedFile(file)));
+        }
+        self.initialize_with_send_sink(std::io::BufWriter::new(file))
     }
 
-    /// Log a world update diff
-    pub fn log_update(&mut self, update: &WorldUpdateDiff) -> Result<(), std::io::Error> {
-        if !self.config.enabled || self.log_file.is_none() {
+    /// Initialize logging to an arbitrary sink instead of a log file, e.g. an
+    /// in-memory buffer where no file system is available. Entries are
+    /// written on the calling thread, see `initialize_with_send_sink`.
+    pub fn initialize_with_sink<W: Write + 'static>(&mut self, sink: W) -> Result<(), std::io::Error> {
+        if !self.config.enabled {
             return Ok(());
         }
 
-        let writer = self.log_file.as_mut().unwrap();
-        self.update_count += 1;
+        let mut writer = CountingWriter {
+            inner: Box::new(sink) as Box<dyn Write>,
+            bytes_written: 0,
+        };
+        self.write_header(&mut writer)?;
+        self.log_file = Some(LogOutput::Direct(writer));
 
-        // Write update header
-        writeln!(writer, "UPDATE {}", self.update_count)?;
-        writeln!(writer, "SYSTEMS: {}", update.system_diffs().len())?;
+        ecs_info!("Replay logging initialized - Session ID: {}", self.session_id);
+        Ok(())
+    }
 
-        // Log each system update
-        for (system_idx, system_diff) in update.system_diffs().iter().enumerate() {
-            writeln!(writer, "  SYSTEM {}", system_idx)?;
-            
-            // Log component changes
-            if self.config.include_component_details && !system_diff.component_changes().is_empty() {
-                writeln!(writer, "    COMPONENT_CHANGES: {}", system_diff.component_changes().len())?;
-                for change in system_diff.component_changes() {
-                    match change {
-                        DiffComponentChange::Added { entity, type_name, data } => {
-                            writeln!(writer, "      ADD {:?} {} {}", entity, type_name, data)?;
-                        }
-                        DiffComponentChange::Modified { entity, type_name, diff } => {
-                            writeln!(writer, "      MOD {:?} {} {}", entity, type_name, diff)?;
-                        }
-                        DiffComponentChange::Removed { entity, type_name } => {
-                            writeln!(writer, "      REM {:?} {}", entity, type_name)?;
-                        }
-                    }
-                }
-            }
+    /// Initialize logging to a sink that can be moved to another thread.
+    /// Entries are written on a background thread if `config.background` is set.
+    pub fn initialize_with_send_sink<W: Write + Send + 'static>(&mut self, sink: W) -> Result<(), std::io::Error> {
+        let Some(settings) = self.config.background else {
+            return self.initialize_with_sink(sink);
+        };
+        if !self.config.enabled {
+            return Ok(());
+        }
 
-            // Log world operations
-            if !system_diff.world_operations().is_empty() {
-                writeln!(writer, "    WORLD_OPERATIONS: {}", system_diff.world_operations().len())?;
-                for operation in system_diff.world_operations() {
-                    match operation {
-                        WorldOperation::CreateEntity(entity) => {
-                            writeln!(writer, "      CREATE_ENTITY {:?}", entity)?;
-                        }
-                        WorldOperation::RemoveEntity(entity) => {
-                            writeln!(writer, "      REMOVE_ENTITY {:?}", entity)?;
-                        }
-                        WorldOperation::CreateWorld(world_id) => {
-                            writeln!(writer, "      CREATE_WORLD {}", world_id)?;
-                        }
-                        WorldOperation::RemoveWorld(world_id) => {
-                            writeln!(writer, "      REMOVE_WORLD {}", world_id)?;
-                        }
-                        WorldOperation::AddSystem(system_type) => {
-                            writeln!(writer, "      ADD_SYSTEM {}", system_type)?;
-                        }
-                    }
-                }
+        let mut writer = CountingWriter {
+            inner: sink,
+            bytes_written: 0,
+        };
+        self.write_header(&mut writer)?;
+        self.log_file = Some(LogOutput::Background(log_writer::BackgroundWriter::spawn(
+            writer,
+            self.config.clone(),
+            settings,
+        )?));
+
+        ecs_info!("Replay logging initialized on a background thread - Session ID: {}", self.session_id);
+        Ok(())
+    }
+
+    /// Write the comment lines at the top of the log
+    fn write_header<W: Write>(&self, writer: &mut W) -> Result<(), std::io::Error> {
+        let timestamp = clock::unix_time()
+            .and_then(|time| chrono::DateTime::from_timestamp(time.as_secs() as i64, time.subsec_nanos()))
+            .map_or_else(|| "unknown".to_string(), |time| time.format("%Y-%m-%d %H:%M:%S UTC").to_string());
+        writeln!(writer, "# ECS Replay Log")?;
+        writeln!(writer, "# Session ID: {}", self.session_id)?;
+        writeln!(writer, "# Timestamp: {}", timestamp)?;
+        writeln!(writer, "# Configuration: {:?}", self.config)?;
+        replay_metadata::write_metadata_header(writer, &self.metadata)?;
+        if let Some(sampling) = self.config.sampling {
+            writeln!(
+                writer,
+                "# Sampling: every {} frames, keyframe every {} frames",
+                sampling.every, sampling.keyframe_interval
+            )?;
+        }
+        writeln!(writer, "# Format: Each line represents one world update")?;
+        writeln!(writer)
+    }
+
+    /// Log the state of the world before the first update as the `INITIAL_STATE` section
+    pub fn log_initial_state(&mut self, snapshot: &snapshot::WorldSnapshot) -> Result<(), std::io::Error> {
+        self.log_snapshot("INITIAL_STATE".to_string(), snapshot)
+    }
+
+    /// Log a section holding the world state, leaving out excluded component types
+    fn log_snapshot(&mut self, header: String, snapshot: &snapshot::WorldSnapshot) -> Result<(), std::io::Error> {
+        if !self.config.enabled {
+            return Ok(());
+        }
+        match self.log_file.as_mut() {
+            Some(LogOutput::Direct(writer)) => {
+                let snapshot = recording::filter_snapshot(snapshot, &self.config.components);
+                snapshot::write_snapshot_entry(writer, &header, &snapshot)
             }
+            Some(LogOutput::Background(writer)) => writer.send(log_writer::LogEntry::Snapshot {
+                header,
+                snapshot: Box::new(snapshot.clone()),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Log a world update diff
+    pub fn log_update(&mut self, update: &WorldUpdateDiff) -> Result<(), std::io::Error> {
+        self.log_update_of_systems(update, &[])
+    }
+
+    /// Log a world update diff made by the named systems, leaving out the
+    /// systems and component types excluded by the configuration
+    pub fn log_update_of_systems(&mut self, update: &WorldUpdateDiff, system_names: &[&str]) -> Result<(), std::io::Error> {
+        if !self.config.enabled || self.log_file.is_none() {
+            return Ok(());
         }
 
-        writeln!(writer)?; // Empty line between updates
+        self.update_count += 1;
+        if self.config.sampling.is_some_and(|sampling| !sampling.records_frame(self.update_count)) {
+            return Ok(());
+        }
 
-        // Flush periodically
-        if self.update_count % self.config.flush_interval == 0 {
-            writer.flush()?;
+        #[allow(clippy::manual_is_multiple_of)]
+        let flush = self.config.durability.flushes_every_frame() || self.update_count % self.config.flush_interval == 0;
+        match self.log_file.as_mut().unwrap() {
+            LogOutput::Direct(writer) => {
+                write_logged_update(writer, self.update_count, update, system_names, &self.config)?;
+                // Flush periodically
+                if flush {
+                    writer.flush()?;
+                }
+            }
+            LogOutput::Background(writer) => {
+                writer.send(log_writer::LogEntry::Update {
+                    number: self.update_count,
+                    update: update.clone(),
+                    system_names: system_names.iter().map(|name| name.to_string()).collect(),
+                })?;
+                if flush {
+                    writer.send(log_writer::LogEntry::Flush)?;
+                }
+            }
         }
 
         Ok(())
     }
 
-    /// Finalize logging - flush and close file
+    /// Check if a keyframe should be logged after the last logged update
+    pub fn keyframe_due(&self) -> bool {
+        self.log_file.is_some()
+            && self
+                .config
+                .sampling
+                .is_some_and(|sampling| sampling.writes_keyframe(self.update_count))
+    }
+
+    /// Log the state of the world after the last logged update as a `KEYFRAME` section
+    pub fn log_keyframe(&mut self, snapshot: &snapshot::WorldSnapshot) -> Result<(), std::io::Error> {
+        self.log_snapshot(format!("KEYFRAME {}", self.update_count), snapshot)
+    }
+
+    /// Finalize logging - flush and close file. With background writing,
+    /// waits for the writer thread to write the queued updates.
     pub fn finalize(&mut self) -> Result<(), std::io::Error> {
-        if let Some(mut writer) = self.log_file.take() {
-            writeln!(writer, "# End of replay log - Total updates: {}", self.update_count)?;
-            writer.flush()?;
-            println!("Replay logging finalized - {} updates logged", self.update_count);
+        let footer = format!("# End of replay log - Total updates: {}\n", self.update_count);
+        match self.log_file.take() {
+            Some(LogOutput::Direct(mut writer)) => {
+                writer.write_all(footer.as_bytes())?;
+                writer.flush()?;
+                self.bytes_written = writer.bytes_written;
+            }
+            Some(LogOutput::Background(mut writer)) => {
+                writer.send(log_writer::LogEntry::Bytes(footer.into_bytes()))?;
+                writer.finish()?;
+                self.dropped_updates = writer.dropped_updates();
+                if self.dropped_updates > 0 {
+                    ecs_warn!(
+                        "Replay log writer fell behind, {} updates were left out of the log",
+                        self.dropped_updates
+                    );
+                }
+                self.bytes_written = writer.bytes_written();
+            }
+            None => return Ok(()),
         }
+        ecs_info!("Replay logging finalized - {} updates logged", self.update_count);
         Ok(())
     }
 
@@ -1712,185 +3295,200 @@ impl AutoReplayLogger {
         &self.session_id
     }
 
+    /// Get the number of bytes written to the current log file. With
+    /// background writing, entries still queued are not counted.
+    pub fn bytes_written(&self) -> u64 {
+        match &self.log_file {
+            Some(LogOutput::Direct(writer)) => writer.bytes_written,
+            Some(LogOutput::Background(writer)) => writer.bytes_written(),
+            None => self.bytes_written,
+        }
+    }
+
+    /// Get the number of updates left out of the log because the background
+    /// writer fell behind (see `Backpressure::DropUpdates`)
+    pub fn dropped_updates(&self) -> usize {
+        match &self.log_file {
+            Some(LogOutput::Background(writer)) => writer.dropped_updates(),
+            _ => self.dropped_updates,
+        }
+    }
+
+    /// Log the diagnostic record of a frame that exceeded its budget.
+    /// Written as comment lines so replay parsers skip it.
+    pub fn log_slow_frame(&mut self, record: &SlowFrameRecord) -> Result<(), std::io::Error> {
+        if !self.config.enabled {
+            return Ok(());
+        }
+        match self.log_file.as_mut() {
+            Some(LogOutput::Direct(writer)) => write_slow_frame(writer, record),
+            Some(LogOutput::Background(writer)) => {
+                let mut entry = Vec::new();
+                write_slow_frame(&mut entry, record)?;
+                writer.send(log_writer::LogEntry::Bytes(entry))
+            }
+            None => Ok(()),
+        }
+    }
+
     /// Get the current update count
     pub fn update_count(&self) -> usize {
         self.update_count
     }
 }
 
-/// Snapshot structure for system-level component state
-#[derive(Debug, Clone)]
-struct SystemComponentSnapshot {
-    /// Serialized component data specific to this system
-    component_data: String,
-    /// Number of entities at snapshot time
-    entity_count: usize,
-}
-
-/// Snapshot structure for individual system's internal state
-#[derive(Debug, Clone)]
-struct SystemStateSnapshot {
-    /// System-specific internal state data
-    system_data: String,
-    /// Frame marker for this system
-    frame_marker: usize,
-}
-
 /// Type-erased system wrapper for storage in World
 trait SystemWrapper {
+    /// Type name of the wrapped system
+    fn name(&self) -> &'static str;
+    /// Short type names of the components the system reads
+    fn in_components(&self) -> Vec<&'static str>;
+    /// Short type names of the components the system writes
+    fn out_components(&self) -> Vec<&'static str>;
     fn initialize(&mut self, world: &mut World) -> SystemInitDiff;
     fn update(&mut self, world: &mut World) -> SystemUpdateDiff;
-    fn update_with_replay(&mut self, world: &mut World, frame_number: usize) -> SystemUpdateDiff;
-    #[allow(dead_code)]
+    /// Update and undo the changes to the world, returning the diff
+    fn update_with_replay(&mut self, world: &mut World) -> SystemUpdateDiff;
+    /// Slice the system's entities over updates, or process all with None
+    fn set_slice(&mut self, slice: Option<slicing::SliceCursor>);
+    fn slice(&self) -> Option<slicing::SliceCursor>;
+    fn on_pause(&mut self, world: &mut World) -> SystemUpdateDiff;
+    fn on_resume(&mut self, world: &mut World) -> SystemUpdateDiff;
+    fn run_when_paused(&self) -> bool;
     fn deinitialize(&mut self, world: &mut World) -> SystemDeinitDiff;
 }
 
 /// Concrete implementation of SystemWrapper for a specific system type
 struct ConcreteSystemWrapper<S: System> {
     system: S,
+    /// Change tick of the system's last update
+    last_run: ChangeTick,
+    /// Round-robin position of a sliced system
+    slice: Option<slicing::SliceCursor>,
 }
 
 impl<S: System> ConcreteSystemWrapper<S> {
     fn new(system: S) -> Self {
-        Self { system }
+        Self {
+            system,
+            last_run: ChangeTick::default(),
+            slice: None,
+        }
     }
+}
 
-    /// Create a snapshot of components that this system can access
-    fn create_system_component_snapshot(&self, world: &World) -> SystemComponentSnapshot {
-        // Capture all components in the world for this snapshot
-        // This is a comprehensive approach that captures all component types
-        let mut component_data = String::new();
-        let entity_count = world.entity_count();
-        
-        // Serialize all component data in a structured format
-        component_data.push_str("COMPONENTS:\n");
-        
-        // For each component type, capture all entities that have it
-        for (type_id, components) in &world.components {
-            component_data.push_str(&format!("TYPE_{:?}:\n", type_id));
-            for (entity, component) in components {
-                // Use Debug formatting to capture component state
-                component_data.push_str(&format!("  {:?}: {:?}\n", entity, component));
-            }
-        }
-        
-        SystemComponentSnapshot {
-            component_data,
-            entity_count,
-        }
+impl<S: System> SystemWrapper for ConcreteSystemWrapper<S> {
+    fn name(&self) -> &'static str {
+        std::any::type_name::<S>()
     }
 
-    /// Restore components that this system can access from a snapshot
-    fn restore_system_component_snapshot(&self, world: &mut World, snapshot: &SystemComponentSnapshot) {
-        // Parse and restore the component data
-        // Note: This is a simplified implementation. A full implementation would need
-        // proper deserialization support for all component types.
-        
-        // For this implementation, we can at least restore the entity count
-        if world.entity_count() != snapshot.entity_count {
-            // Adjust entity counter if needed
-            while world.entity_count() < snapshot.entity_count {
-                world.create_entity();
-            }
-        }
-        
-        // The component_data contains the full snapshot but restoring it requires
-        // proper deserialization which is complex without runtime type information.
-        // For now, we've captured the state properly in the snapshot.
+    fn in_components(&self) -> Vec<&'static str> {
+        S::InComponents::type_names()
+    }
+
+    fn out_components(&self) -> Vec<&'static str> {
+        S::OutComponents::type_names()
+    }
+
+    fn initialize(&mut self, world: &mut World) -> SystemInitDiff {
+        let mut world_view = WorldView::<S::InComponents, S::OutComponents>::new(world);
+        self.system.initialize(&mut world_view);
+        world_view.get_system_diff().into()
     }
 
-    /// Create a snapshot of this specific system's internal state
-    fn create_system_state_snapshot(&self) -> SystemStateSnapshot {
-        // Capture any system-specific internal state
-        // For most systems, this might be empty, but some systems might have
-        // internal counters, timers, or other state that needs preservation
-        SystemStateSnapshot {
-            system_data: format!("System state snapshot for type: {}", std::any::type_name::<S>()),
-            frame_marker: 0, // Could be used to track frame-specific state
+    fn update(&mut self, world: &mut World) -> SystemUpdateDiff {
+        let declared = || DeclaredAccess::of::<S::InComponents, S::OutComponents>(std::any::type_name::<S>());
+        let ((result, mut system_diff), ran_at) = world.run_with_declared_access(declared, |world| {
+            world.run_as_system(self.last_run, |world| {
+                world.run_sliced(&mut self.slice, |world| {
+                    // Create world view with change tracking enabled
+                    let mut world_view = WorldView::<S::InComponents, S::OutComponents>::new(world);
+
+                    // Execute the system - changes will be tracked automatically by WorldView
+                    let result = self.system.try_update(&mut world_view);
+
+                    // Return the accumulated changes from the world view
+                    (result, world_view.get_system_diff())
+                })
+            })
+        });
+        self.last_run = ran_at;
+        if let Err(e) = result {
+            ecs_error!("System {} failed: {}", std::any::type_name::<S>(), e);
+            system_diff.record_error(e.to_string());
         }
+        system_diff
     }
 
-    /// Restore this specific system's internal state from a snapshot
-    fn restore_system_state_snapshot(&self, snapshot: &SystemStateSnapshot) {
-        // Restore system-specific internal state
-        // For most basic systems, this is a no-op since they don't have internal state
-        // More complex systems would restore their internal state here
-        
-        // Log the restoration for debugging
-        if !snapshot.system_data.is_empty() {
-            // System state restored (most systems don't have mutable internal state to restore)
+    fn update_with_replay(&mut self, world: &mut World) -> SystemUpdateDiff {
+        // Run the system for its diff, then undo its changes so the replayed
+        // diffs alone drive the world
+        let snapshot = world.system_snapshot();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| self.update(world)));
+        let system_diff = match result {
+            Ok(system_diff) => system_diff,
+            Err(payload) => {
+                // Undo the changes made before the panic, then report it as usual
+                world.restore_system_snapshot(snapshot);
+                std::panic::resume_unwind(payload);
+            }
+        };
+        // The undo hides what the system did from checks made afterwards
+        if world.invariants_active() {
+            world.check_undone_invariants(self.name(), &system_diff);
         }
+        world.restore_system_snapshot(snapshot);
+        system_diff
     }
 
-    /// Apply replay diff specific to this system for the given frame
-    fn apply_system_replay_diff(&self, _world: &mut World, frame_number: usize) {
-        // Apply system-specific replay data for this frame
-        // This would typically involve reading the replay log and applying 
-        // the specific changes that this system made during the original execution
-        
-        // Since we don't have system-specific replay logs in the current implementation,
-        // and the replay data is already applied at the world level via apply_update_diff,
-        // this becomes a coordination point for more complex replay scenarios.
-        
-        // For now, this ensures the system is ready for the replay frame
-        let _ = frame_number; // Use the frame number if needed for frame-specific logic
+    fn set_slice(&mut self, slice: Option<slicing::SliceCursor>) {
+        self.slice = slice;
     }
-}
 
-impl<S: System> SystemWrapper for ConcreteSystemWrapper<S> {
-    fn initialize(&mut self, world: &mut World) -> SystemInitDiff {
-        let mut world_view = WorldView::<S::InComponents, S::OutComponents>::new(world);
-        self.system.initialize(&mut world_view);
-        SystemInitDiff::new()
+    fn slice(&self) -> Option<slicing::SliceCursor> {
+        self.slice
     }
 
-    fn update(&mut self, world: &mut World) -> SystemUpdateDiff {
-        // Create world view with change tracking enabled
+    fn on_pause(&mut self, world: &mut World) -> SystemUpdateDiff {
         let mut world_view = WorldView::<S::InComponents, S::OutComponents>::new(world);
+        self.system.on_pause(&mut world_view);
+        world_view.get_system_diff()
+    }
 
-        // Execute the system - changes will be tracked automatically by WorldView
-        self.system.update(&mut world_view);
-
-        // Return the accumulated changes from the world view
+    fn on_resume(&mut self, world: &mut World) -> SystemUpdateDiff {
+        let mut world_view = WorldView::<S::InComponents, S::OutComponents>::new(world);
+        self.system.on_resume(&mut world_view);
         world_view.get_system_diff()
     }
 
-    fn update_with_replay(&mut self, world: &mut World, frame_number: usize) -> SystemUpdateDiff {
-        // 1. Snapshot component and system state before this system's update
-        let component_snapshot = self.create_system_component_snapshot(world);
-        let system_state_snapshot = self.create_system_state_snapshot();
-        
-        // 2. Run the system normally (this will modify components)
-        let system_diff = self.update(world);
-        
-        // 3. Restore component and system state after this system's update
-        self.restore_system_component_snapshot(world, &component_snapshot);
-        self.restore_system_state_snapshot(&system_state_snapshot);
-        
-        // 4. Apply replay diff specific to this system for this frame
-        self.apply_system_replay_diff(world, frame_number);
-        
-        // Return the original system diff for tracking purposes
-        system_diff
+    fn run_when_paused(&self) -> bool {
+        self.system.run_when_paused()
     }
 
     fn deinitialize(&mut self, world: &mut World) -> SystemDeinitDiff {
         let mut world_view = WorldView::<S::InComponents, S::OutComponents>::new(world);
         self.system.deinitialize(&mut world_view);
-        SystemDeinitDiff::new()
+        world_view.get_system_diff().into()
     }
 }
 
 /// Type alias for component storage to reduce complexity
-type ComponentStorage = HashMap<TypeId, Vec<(Entity, Box<dyn Any>)>>;
+type ComponentStorage = HashMap<TypeId, storage::ComponentColumn>;
 
 /// The main World struct that manages entities, components, and systems
 pub struct World {
     /// Unique index identifying this world
     world_index: usize,
+    /// Entities in entity order, which queries rely on
     entities: Vec<Entity>,
     components: ComponentStorage,
     systems: Vec<Box<dyn SystemWrapper>>,
+    /// Systems run once before the next update by `run_startup_systems`
+    startup_systems: Vec<Box<dyn SystemWrapper>>,
+    /// Indices of systems skipped after panicking
+    disabled_systems: HashSet<usize>,
+    /// Whether a system that panics is added to `disabled_systems`
+    disable_failed_systems: bool,
     next_entity_id: usize,
     #[allow(dead_code)]
     child_worlds: Vec<World>,
@@ -1903,6 +3501,103 @@ pub struct World {
     replay_mode: bool,
     /// Current frame number in replay mode
     replay_frame: usize,
+    /// Number of completed update frames
+    frame_number: usize,
+    /// Component watches used by `debug_update`
+    watches: Vec<(WatchId, Box<dyn time_travel::Watch>)>,
+    next_watch_id: usize,
+    /// Frame paused by `debug_update` after a watch hit
+    frame_in_progress: Option<time_travel::FrameInProgress>,
+    /// Component types whose changes are recorded for `rewind`
+    rewind_trackers: Vec<(TypeId, Box<dyn time_travel::RewindTracker>)>,
+    rewind_frames: VecDeque<time_travel::RewindFrame>,
+    rewind_capacity: usize,
+    /// Invariants checked after each system update in debug builds and replay mode
+    invariants: Vec<invariants::Invariant>,
+    invariant_violations: Vec<InvariantViolation>,
+    /// Time budget per update; slower frames are captured in `slow_frames`
+    frame_budget: Option<std::time::Duration>,
+    slow_frames: Vec<SlowFrameRecord>,
+    /// Component types registered for runtime reflection
+    component_registry: reflect::ComponentRegistry,
+    /// Entity templates spawned by `instantiate`
+    prefabs: prefab::PrefabRegistry,
+    /// Singleton values stored by type
+    resources: HashMap<TypeId, Box<dyn Any>>,
+    /// Types and names of the added plugins
+    plugins: Vec<(TypeId, &'static str)>,
+    /// Factories of the systems replays can add
+    system_registry: system_registry::SystemRegistry,
+    /// Monotonic time the world was created at, annotations are timestamped relative to it
+    created_at: std::time::Duration,
+    /// Markers added with `annotate_replay` for the frame in progress
+    pending_annotations: Vec<replay_metadata::ReplayAnnotation>,
+    /// Errors the systems reported in the last completed frame, see `try_update`
+    frame_errors: Vec<(&'static str, String)>,
+    /// Clock read instead of the time source, set by tests
+    mock_clock: Option<clock::MockClock>,
+    /// Advances every frame and around every system update
+    change_tick: ChangeTick,
+    /// Tick the running system last ran at, read by `Changed` filters
+    last_run_tick: ChangeTick,
+    /// Tick each component was last written at
+    component_ticks: HashMap<(TypeId, Entity), ChangeTick>,
+    /// Members of the named entity groups
+    groups: std::collections::BTreeMap<String, std::collections::BTreeSet<Entity>>,
+    /// Source and target entities linked by each named relation
+    relations: std::collections::BTreeMap<String, std::collections::BTreeSet<(Entity, Entity)>>,
+    /// Short names of the component types stored by `add_component`
+    component_type_names: HashMap<TypeId, &'static str>,
+    /// Callbacks run when observed components change
+    observers: Vec<(ObserverId, Box<dyn observers::ComponentObserver>)>,
+    /// Set while a replayed system update runs and is undone, see `system_snapshot`
+    observers_muted: bool,
+    next_observer_id: usize,
+    /// Entities skipped by queries
+    disabled_entities: std::collections::BTreeSet<Entity>,
+    /// Component types defined at runtime and their components
+    dynamic: dynamic::DynamicComponents,
+    /// Component combinations whose entering and leaving entities are tracked
+    memberships: membership::Memberships,
+    /// Queries of the running system, counted while system statistics are recorded
+    query_counts: Option<system_stats::QueryCounts>,
+    /// Component accesses of systems, counted per frame while profiling
+    access_profiler: Option<access_profile::AccessProfiler>,
+    /// Named groups of systems enabled and ordered together
+    system_groups: system_groups::SystemGroups,
+    /// Whether render groups and frame pacing are skipped
+    fast_forward: fast_forward::FastForward,
+    /// Component types copied into `Previous<T>` before each update
+    previous_trackers: Vec<interpolation::PreviousTracker>,
+    /// Whether system component access is checked against the declarations
+    strict_access: bool,
+    /// Whether systems record the components they read before each change
+    causality_tracing: bool,
+    /// Declared components of the running system, while access is checked
+    declared_access: Option<strict_access::DeclaredAccess>,
+    /// Component versions of the data applied in replays
+    replay_versions: versioning::ReplayVersions,
+    /// Last recorded values of components with skipped modifications
+    recording_bases: HashMap<(TypeId, Entity), Box<dyn Any>>,
+    /// Entities of the world that applied diffs are mapped to
+    entity_remapper: Option<remapping::EntityRemapper>,
+    /// Entities the running sliced system sees
+    active_slice: std::cell::Cell<Option<slicing::ActiveSlice>>,
+    /// Entities queued with `CommandBuffer::despawn_deferred`, removed when the frame ends
+    deferred_despawns: Vec<Entity>,
+    /// Whether only the systems running when paused update
+    paused: bool,
+    /// Storage chosen for component types with `register_storage`
+    storages: HashMap<TypeId, storage::StorageKind>,
+    /// Whether to shut down at the end of an update once a signal arrives, see `shutdown`
+    #[cfg(feature = "shutdown-handler")]
+    shutdown_on_signal: bool,
+    /// Component types published to read handles after every frame
+    #[cfg(feature = "read-handle")]
+    shared_components: read_handle::SharedComponents,
+    /// Database every completed frame is stored in
+    #[cfg(feature = "sqlite")]
+    sqlite_store: Option<sqlite::SqliteReplayStore>,
 }
 
 impl Default for World {
@@ -1919,11 +3614,16 @@ impl World {
 
     /// Creates a new world with a specific world index
     pub fn new_with_index(world_index: usize) -> Self {
-        Self {
+        // Only changed when derived component types are registered
+        #[allow(unused_mut)]
+        let mut world = Self {
             world_index,
             entities: Vec::new(),
             components: HashMap::new(),
             systems: Vec::new(),
+            startup_systems: Vec::new(),
+            disabled_systems: HashSet::new(),
+            disable_failed_systems: false,
             next_entity_id: 0,
             child_worlds: Vec::new(),
             world_update_history: WorldUpdateHistory::new(),
@@ -1931,7 +3631,63 @@ impl World {
             replay_logger: None,
             replay_mode: false,
             replay_frame: 0,
-        }
+            frame_number: 0,
+            watches: Vec::new(),
+            next_watch_id: 0,
+            frame_in_progress: None,
+            rewind_trackers: Vec::new(),
+            rewind_frames: VecDeque::new(),
+            rewind_capacity: time_travel::DEFAULT_REWIND_CAPACITY,
+            invariants: Vec::new(),
+            invariant_violations: Vec::new(),
+            frame_budget: None,
+            slow_frames: Vec::new(),
+            component_registry: reflect::ComponentRegistry::new(),
+            prefabs: prefab::PrefabRegistry::new(),
+            resources: HashMap::new(),
+            plugins: Vec::new(),
+            system_registry: system_registry::SystemRegistry::new(),
+            created_at: clock::monotonic_now(),
+            pending_annotations: Vec::new(),
+            frame_errors: Vec::new(),
+            mock_clock: None,
+            change_tick: ChangeTick::default().next(),
+            last_run_tick: ChangeTick::default(),
+            component_ticks: HashMap::new(),
+            groups: std::collections::BTreeMap::new(),
+            relations: std::collections::BTreeMap::new(),
+            component_type_names: HashMap::new(),
+            observers: Vec::new(),
+            observers_muted: false,
+            next_observer_id: 0,
+            disabled_entities: std::collections::BTreeSet::new(),
+            dynamic: dynamic::DynamicComponents::default(),
+            memberships: membership::Memberships::default(),
+            query_counts: None,
+            access_profiler: None,
+            system_groups: system_groups::SystemGroups::default(),
+            fast_forward: fast_forward::FastForward::default(),
+            previous_trackers: Vec::new(),
+            strict_access: false,
+            causality_tracing: false,
+            declared_access: None,
+            replay_versions: versioning::ReplayVersions::default(),
+            recording_bases: HashMap::new(),
+            entity_remapper: None,
+            active_slice: std::cell::Cell::new(None),
+            deferred_despawns: Vec::new(),
+            paused: false,
+            storages: HashMap::new(),
+            #[cfg(feature = "shutdown-handler")]
+            shutdown_on_signal: false,
+            #[cfg(feature = "read-handle")]
+            shared_components: read_handle::SharedComponents::default(),
+            #[cfg(feature = "sqlite")]
+            sqlite_store: None,
+        };
+        #[cfg(feature = "auto-register")]
+        world.register_derived_components();
+        world
     }
 
     /// Get the world index of this world
@@ -2007,6 +3763,38 @@ impl World {
         self.add_system_internal(system);
     }
 
+    /// Add a system that runs exactly once, before the next update.
+    ///
+    /// Use it for setup logic that needs queries, such as spawning the initial
+    /// map. The changes of all pending startup systems are recorded in the
+    /// history and replay log as one update of their own.
+    pub fn add_startup_system<S: System + 'static>(&mut self, system: S) {
+        self.startup_systems
+            .push(Box::new(ConcreteSystemWrapper::new(system)));
+    }
+
+    /// Run the pending startup systems now rather than before the next update
+    pub fn run_startup_systems(&mut self) {
+        if self.startup_systems.is_empty() {
+            return;
+        }
+        let mut diff = WorldUpdateDiff::new();
+        let mut system_names = Vec::new();
+        for mut system in std::mem::take(&mut self.startup_systems) {
+            let mut system_diff = SystemUpdateDiff::from(system.initialize(self));
+            if self.replay_mode {
+                // Like other systems, leave the world to the replayed diffs
+                system_diff.append(system.update_with_replay(self));
+            } else {
+                system_diff.append(system.update(self));
+            }
+            system_diff.append(system.deinitialize(self).into());
+            diff.record(system_diff);
+            system_names.push(system.name());
+        }
+        self.record_update(&diff, &system_names);
+    }
+
     /// Internal method to add a system without recording (for replay)
     fn add_system_internal<S: System + 'static>(&mut self, system: S) {
         self.systems
@@ -2017,45 +3805,103 @@ impl World {
     pub fn create_entity(&mut self) -> Entity {
         let entity = Entity::new(self.world_index, self.next_entity_id);
         self.next_entity_id += 1;
-        self.entities.push(entity);
+        // Entities applied from a world with a higher index sort after new ones
+        let index = self.entities.partition_point(|e| *e < entity);
+        self.entities.insert(index, entity);
         entity
     }
 
     /// Add a component to an entity
     pub fn add_component<T: 'static>(&mut self, entity: Entity, component: T) {
-        self.components
-            .entry(TypeId::of::<T>())
-            .or_default()
-            .push((entity, Box::new(component)));
+        let type_id = TypeId::of::<T>();
+        match self.components.entry(type_id) {
+            std::collections::hash_map::Entry::Occupied(mut column) => column.get_mut().insert(entity, Box::new(component)),
+            // Knowing the type, marker components get a set-based column
+            std::collections::hash_map::Entry::Vacant(column) => {
+                column.insert(match self.storages.get(&type_id) {
+                    Some(kind) => storage::ComponentColumn::with_storage(*kind, entity, Box::new(component)),
+                    None => storage::ComponentColumn::new::<T>(entity, Box::new(component)),
+                });
+                self.component_type_names.insert(type_id, short_type_name::<T>());
+            }
+        }
+        self.mark_component_changed(type_id, entity);
+        self.observe_added(type_id, entity);
+        self.update_memberships(type_id, entity);
+        self.warn_missing_requirements(type_id, short_type_name::<T>(), entity);
+        self.insert_required_components(type_id, entity);
+    }
+
+    /// Add a boxed component to an entity, keeping the storage of its type
+    /// sorted by entity so iteration order does not depend on insertion order
+    pub(crate) fn push_component_any(&mut self, entity: Entity, type_id: TypeId, component: Box<dyn Any>) {
+        // Boxed components come from registered types, which know their names
+        // and the storage `add_component` would pick for them
+        let registration = self
+            .component_registry
+            .get_by_type_id(type_id)
+            .map(|registration| (registration.type_name(), registration.default_storage()));
+        match self.components.get_mut(&type_id) {
+            Some(components) => components.insert(entity, component),
+            None => {
+                let kind = match (self.storages.get(&type_id), registration) {
+                    (Some(kind), _) => *kind,
+                    (None, Some((_, default_storage))) => default_storage,
+                    (None, None) => StorageKind::Dense,
+                };
+                self.components.insert(type_id, storage::ComponentColumn::with_storage(kind, entity, component));
+                if let Some((type_name, _)) = registration {
+                    self.component_type_names.insert(type_id, type_name);
+                }
+            }
+        }
+        self.mark_component_changed(type_id, entity);
+        self.observe_added(type_id, entity);
+        self.update_memberships(type_id, entity);
+        let type_name = registration.map_or("unregistered component", |(type_name, _)| type_name);
+        self.warn_missing_requirements(type_id, type_name, entity);
+        self.insert_required_components(type_id, entity);
     }
 
     /// Remove a component from an entity
     pub fn remove_component<T: 'static>(&mut self, entity: Entity) -> Option<T> {
-        if let Some(components) = self.components.get_mut(&TypeId::of::<T>()) {
-            if let Some(pos) = components.iter().position(|(e, _)| *e == entity) {
-                let (_, component_box) = components.remove(pos);
-                return component_box.downcast::<T>().ok().map(|boxed| *boxed);
-            }
-        }
-        None
+        self.observe_removal(TypeId::of::<T>(), entity);
+        let component_box = self.components.get_mut(&TypeId::of::<T>())?.remove(entity)?;
+        self.component_ticks.remove(&(TypeId::of::<T>(), entity));
+        self.update_memberships(TypeId::of::<T>(), entity);
+        component_box.downcast::<T>().ok().map(|boxed| *boxed)
     }
 
     /// Remove an entity and all its components
     pub fn remove_entity(&mut self, entity: Entity) -> bool {
         let initial_count = self.entities.len();
 
+        // Remove all components belonging to this entity
+        self.clear_components(entity);
+
         // Remove from entities list
         self.entities.retain(|e| *e != entity);
-
-        // Remove all components belonging to this entity
-        for components in self.components.values_mut() {
-            components.retain(|(e, _)| *e != entity);
-        }
+        self.leave_all_groups(entity);
+        self.drop_relations(entity);
+        self.disabled_entities.remove(&entity);
 
         // Return whether entity was actually removed
         self.entities.len() < initial_count
     }
 
+    /// Remove every component of an entity, keeping the entity
+    pub(crate) fn clear_components(&mut self, entity: Entity) {
+        self.observe_entity_removal(entity);
+        self.forget_component_ticks(entity);
+        self.replay_versions.forget_entity(entity);
+        self.recording_bases.retain(|(_, owner), _| *owner != entity);
+        for components in self.components.values_mut() {
+            components.remove_all(entity);
+        }
+        self.dynamic.remove_entity(entity);
+        self.leave_memberships(entity);
+    }
+
     /// Check if an entity exists
     pub fn entity_exists(&self, entity: Entity) -> bool {
         self.entities.contains(&entity)
@@ -2063,72 +3909,346 @@ impl World {
 
     /// Get a component for an entity (if it exists)
     pub fn get_component<T: 'static>(&self, entity: Entity) -> Option<&T> {
-        self.components
-            .get(&TypeId::of::<T>())?
-            .iter()
-            .find_map(|(e, component)| {
-                if *e == entity {
-                    component.downcast_ref::<T>()
-                } else {
-                    None
-                }
-            })
+        self.components.get(&TypeId::of::<T>())?.get(entity)?.downcast_ref::<T>()
     }
 
-    /// Initialize all systems (called once before the first update)
+    /// Get a mutable component for an entity (if it exists), marking it as changed
+    pub fn get_component_mut<T: 'static>(&mut self, entity: Entity) -> Option<&mut T> {
+        // Mutable references handed out before are gone, so their writes are done
+        self.flush_observers();
+        self.write_component::<T>(entity)
+    }
+
+    /// Mark an existing component as changed and remember it for observers,
+    /// before it is written
+    pub(crate) fn mark_written<T: 'static>(&mut self, entity: Entity) {
+        self.observe_write(TypeId::of::<T>(), entity);
+        self.component_ticks.insert((TypeId::of::<T>(), entity), self.change_tick);
+    }
+
+    /// Get a mutable component, marking it as changed and remembering it for observers
+    pub(crate) fn write_component<T: 'static>(&mut self, entity: Entity) -> Option<&mut T> {
+        self.observe_write(TypeId::of::<T>(), entity);
+        let component = self
+            .components
+            .get_mut(&TypeId::of::<T>())?
+            .get_mut(entity)?
+            .downcast_mut::<T>()?;
+        self.component_ticks.insert((TypeId::of::<T>(), entity), self.change_tick);
+        Some(component)
+    }
+
+    /// Initialize all systems (called once before the first update).
+    ///
+    /// Changes the systems make, such as spawning the initial map, are
+    /// recorded in the history and replay log as one update of their own.
     pub fn initialize_systems(&mut self) {
         // We need to work around the borrowing issue by taking ownership temporarily
         let mut systems = std::mem::take(&mut self.systems);
 
+        let mut diff = WorldUpdateDiff::new();
+        for system in &mut systems {
+            diff.record(system.initialize(self).into());
+        }
+
+        self.systems = systems;
+        self.record_lifecycle_update(diff);
+    }
+
+    /// Deinitialize all systems, e.g. before the world shuts down. Their
+    /// changes are recorded like the ones of `initialize_systems`.
+    pub fn deinitialize_systems(&mut self) {
+        let mut systems = std::mem::take(&mut self.systems);
+
+        let mut diff = WorldUpdateDiff::new();
         for system in &mut systems {
-            let _diff = system.initialize(self);
-            // TODO: Record diff in world update history
+            diff.record(system.deinitialize(self).into());
         }
 
         self.systems = systems;
+        self.record_lifecycle_update(diff);
+    }
+
+    /// Record the diff of initializing or deinitializing the systems, with
+    /// one system diff per system, unless no system changed anything
+    fn record_lifecycle_update(&mut self, diff: WorldUpdateDiff) {
+        let changed = diff
+            .system_diffs()
+            .iter()
+            .any(|system_diff| !system_diff.component_changes().is_empty() || !system_diff.world_operations().is_empty());
+        if changed {
+            let system_names: Vec<&'static str> = self.systems.iter().map(|system| system.name()).collect();
+            self.record_update(&diff, &system_names);
+        }
     }
 
     /// Update all systems for one frame
     pub fn update(&mut self) {
-        let mut world_update_diff = WorldUpdateDiff::new();
+        self.run_frame(false);
+    }
+
+    /// Update all systems for one frame like `update`, returning the errors
+    /// systems reported through `try_update`. All systems run even if some fail.
+    pub fn try_update(&mut self) -> Result<(), FrameError> {
+        let frame = self.frame_number;
+        self.update();
+        let errors = std::mem::take(&mut self.frame_errors);
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(FrameError { frame, errors })
+        }
+    }
+
+    /// Update systems like `update`, but pause right after a system changes a
+    /// watched component (see `add_watch`).
+    ///
+    /// Returns the hit when paused; calling again resumes the frame from the
+    /// next system. Returns None once the frame has completed.
+    pub fn debug_update(&mut self) -> Option<WatchHit> {
+        self.run_frame(true)
+    }
+
+    /// Get the number of completed update frames
+    pub fn frame_number(&self) -> usize {
+        self.frame_number
+    }
+
+    /// Run (or resume) a frame, stopping at the first watch hit if requested
+    fn run_frame(&mut self, stop_on_watch: bool) -> Option<WatchHit> {
+        let _frame_span = telemetry::frame_span(self.world_index, self.frame_number);
+        let mut frame = match self.frame_in_progress.take() {
+            Some(frame) => frame,
+            None => {
+                self.begin_access_profile();
+                self.apply_system_group_order();
+                self.run_startup_systems();
+                self.store_previous_components();
+                // Writes made between updates reach observers before the frame
+                self.flush_observers();
+                // Catch state broken by changes made between updates
+                if self.invariants_active() {
+                    self.check_invariants(None);
+                }
+                let rewind = self.begin_rewind_capture();
+                // Writes made during the frame are newer than the capture
+                self.advance_change_tick();
+                self.prune_membership_exits();
+                time_travel::FrameInProgress {
+                    diff: WorldUpdateDiff::new(),
+                    next_system: 0,
+                    pending_hits: VecDeque::new(),
+                    rewind,
+                    system_timings: Vec::new(),
+                    system_errors: Vec::new(),
+                }
+            }
+        };
+
+        // A single system may have triggered several watches
+        if stop_on_watch {
+            if let Some(hit) = frame.pending_hits.pop_front() {
+                self.frame_in_progress = Some(frame);
+                return Some(hit);
+            }
+        }
+        let watching = stop_on_watch && !self.watches.is_empty();
 
         // We need to work around the borrowing issue by taking ownership temporarily
         let mut systems = std::mem::take(&mut self.systems);
 
-        for system in &mut systems {
-            let system_diff = if self.replay_mode {
-                // In replay mode, use system-level snapshot/restore
-                system.update_with_replay(self, self.replay_frame)
-            } else {
-                // In normal mode, just update normally
-                system.update(self)
+        while let Some(system) = systems.get_mut(frame.next_system) {
+            let system_index = frame.next_system;
+            frame.next_system += 1;
+            if self.disabled_systems.contains(&system_index)
+                || self.system_groups.is_disabled(system_index)
+                || self.skipped_by_fast_forward(system_index)
+                || (self.paused && !system.run_when_paused())
+            {
+                // Keep the system indices of the frame aligned with the systems
+                frame.diff.record(SystemUpdateDiff::new());
+                continue;
+            }
+            if watching {
+                self.capture_watches();
+            }
+            self.advance_change_tick();
+
+            let started = (self.frame_budget.is_some() || telemetry::METRICS_ENABLED)
+                .then(|| self.now());
+            let stats_start = self.begin_system_stats();
+            let mut system_diff = {
+                let _system_span = telemetry::system_span(system.name());
+                let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                    if self.replay_mode {
+                        // In replay mode, undo the system's changes after recording them
+                        system.update_with_replay(self)
+                    } else {
+                        // In normal mode, just update normally
+                        system.update(self)
+                    }
+                }));
+                let system_diff = match result {
+                    Ok(system_diff) => system_diff,
+                    Err(payload) => self.system_panicked(system_index, system.name(), payload),
+                };
+                telemetry::record_changes(
+                    system_diff.component_changes(),
+                    system_diff.world_operations(),
+                );
+                system_diff
             };
-            world_update_diff.record(system_diff);
+            if let Some(start) = stats_start {
+                system_diff.record_stats(self.finish_system_stats(start));
+            }
+            if let Some(started) = started {
+                let timing = SystemTiming {
+                    system_name: system.name().to_string(),
+                    duration: self.now().saturating_sub(started),
+                    change_count: system_diff.component_changes().len()
+                        + system_diff.world_operations().len(),
+                };
+                telemetry::record_system_metrics(&timing);
+                frame.system_timings.push(timing);
+            }
+            if let Some(error) = system_diff.error() {
+                frame.system_errors.push((system.name(), error.to_string()));
+            }
+            // Replayed systems are checked before their update is undone
+            if self.invariants_active() && !self.replay_mode {
+                self.check_invariants(Some((system.name(), &system_diff)));
+            }
+            frame.diff.record(system_diff);
+
+            if watching {
+                frame
+                    .pending_hits
+                    .extend(self.detect_watch_hits(system.name()));
+                if let Some(hit) = frame.pending_hits.pop_front() {
+                    self.systems = systems;
+                    self.frame_in_progress = Some(frame);
+                    return Some(hit);
+                }
+            }
         }
 
         self.systems = systems;
-        
+        self.apply_deferred_despawns(&mut frame.diff);
+        self.finish_frame(frame);
+        None
+    }
+
+    /// Record a completed frame in history, the replay log and the rewind buffer
+    fn finish_frame(&mut self, mut frame: time_travel::FrameInProgress) {
         // Increment replay frame if in replay mode
         if self.replay_mode {
             self.replay_frame += 1;
         }
-        
-        // Record the update in history
-        self.world_update_history.record(world_update_diff.clone());
-        
+
+        if let Some(capture) = frame.rewind {
+            self.finish_rewind_capture(capture);
+        }
+
+        frame.diff.annotations.append(&mut self.pending_annotations);
+        let system_names: Vec<&str> = self.systems.iter().map(|system| system.name()).collect();
+        self.record_update(&frame.diff, &system_names);
+
+        telemetry::record_frame_metrics(self, &frame.diff);
+        self.check_frame_budget(frame.system_timings);
+        self.frame_errors = frame.system_errors;
+        self.finish_access_profile();
+        self.frame_number += 1;
+        #[cfg(feature = "read-handle")]
+        self.publish_shared_components();
+        #[cfg(feature = "shutdown-handler")]
+        self.shut_down_on_signal();
+    }
+
+    /// Record an update in history, the replay log and the SQLite store
+    fn record_update(&mut self, diff: &WorldUpdateDiff, system_names: &[&str]) {
+        self.world_update_history.record(diff.clone());
+
         // Log the update if replay logging is enabled
         if let Some(ref mut logger) = self.replay_logger {
-            if let Err(e) = logger.log_update(&world_update_diff) {
-                eprintln!("Failed to log replay data: {}", e);
+            if let Err(e) = logger.log_update_of_systems(diff, system_names) {
+                ecs_error!("Failed to log replay data: {}", e);
             }
+            if logger.keyframe_due() {
+                let snapshot = self.snapshot();
+                if let Err(e) = self.replay_logger.as_mut().map_or(Ok(()), |logger| logger.log_keyframe(&snapshot)) {
+                    ecs_error!("Failed to log replay keyframe: {}", e);
+                }
+            }
+        }
+        #[cfg(feature = "sqlite")]
+        self.store_frame_in_sqlite(diff);
+    }
+
+    /// Record a system panic and disable the system if configured to
+    fn system_panicked(
+        &mut self,
+        system_index: usize,
+        system_name: &str,
+        payload: Box<dyn Any + Send>,
+    ) -> SystemUpdateDiff {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "non-string panic payload".to_string());
+        // The panic skipped the end of the system's update
+        self.declared_access = None;
+        self.active_slice.set(None);
+        self.observers_muted = false;
+        ecs_error!(
+            "System {} panicked in frame {}: {}",
+            system_name,
+            self.frame_number,
+            message
+        );
+        if self.disable_failed_systems {
+            ecs_warn!("Disabling system {} after it panicked", system_name);
+            self.disabled_systems.insert(system_index);
         }
+        // Outside replay mode, changes the system made before panicking stay
+        // in the world, but were not recorded
+        let mut system_diff = SystemUpdateDiff::new();
+        system_diff.record_failure(message);
+        system_diff
+    }
+
+    /// Choose whether a system that panics is skipped in later updates.
+    /// Failed systems keep running by default.
+    pub fn set_disable_failed_systems(&mut self, disable: bool) {
+        self.disable_failed_systems = disable;
+    }
+
+    /// Get the names of the systems disabled after panicking
+    pub fn disabled_systems(&self) -> Vec<&'static str> {
+        let mut indices: Vec<usize> = self.disabled_systems.iter().copied().collect();
+        indices.sort_unstable();
+        indices
+            .into_iter()
+            .filter_map(|index| self.systems.get(index).map(|system| system.name()))
+            .collect()
+    }
+
+    /// Run the systems disabled after panicking again
+    pub fn enable_disabled_systems(&mut self) {
+        self.disabled_systems.clear();
     }
 
-    /// Enable replay mode for this world
+    /// Enable replay mode for this world.
+    ///
+    /// Systems still run and their diffs are recorded, but the world is
+    /// restored to its state before each system update afterwards, so only
+    /// replayed diffs applied with `apply_update_diff` change it. Entities and
+    /// the components and resources registered for replay are restored, and
+    /// the restore is checked against a checksum of the state before the
+    /// update. Changes to other types are kept.
     pub fn enable_replay_mode(&mut self) {
         self.replay_mode = true;
         self.replay_frame = 0;
-        // Replay mode enabled - systems will use snapshot/restore pattern for deterministic replay
     }
 
     /// Disable replay mode for this world
@@ -2159,12 +4279,12 @@ impl World {
 
         // For now, return an empty world - full replay implementation would require
         // more sophisticated state tracking and component serialization
-        println!(
+        ecs_info!(
             "Replaying world history with {} updates",
             history.updates().len()
         );
         for (i, _update) in history.updates().iter().enumerate() {
-            println!("Frame {}: Applying update", i + 1);
+            ecs_info!("Frame {}: Applying update", i + 1);
             // Would apply each update to reconstruct the world state
         }
 
@@ -2177,14 +4297,32 @@ impl World {
     }
 
     /// Enable replay logging with the given configuration
+    #[cfg(feature = "fs")]
     pub fn enable_replay_logging(&mut self, config: ReplayLogConfig) -> Result<(), std::io::Error> {
         let mut logger = AutoReplayLogger::new(config);
+        logger.set_metadata(self.world_update_history.metadata().clone());
         logger.initialize()?;
+        logger.log_initial_state(&self.snapshot())?;
+        self.replay_logger = Some(logger);
+        Ok(())
+    }
+
+    /// Enable replay logging to `sink` instead of a file in `config.log_directory`
+    pub fn enable_replay_logging_to<W: Write + 'static>(
+        &mut self,
+        config: ReplayLogConfig,
+        sink: W,
+    ) -> Result<(), std::io::Error> {
+        let mut logger = AutoReplayLogger::new(config);
+        logger.set_metadata(self.world_update_history.metadata().clone());
+        logger.initialize_with_sink(sink)?;
+        logger.log_initial_state(&self.snapshot())?;
         self.replay_logger = Some(logger);
         Ok(())
     }
 
     /// Enable replay logging with basic parameters (convenience method)
+    #[cfg(feature = "fs")]
     pub fn enable_replay_logging_simple(
         &mut self, 
         log_directory: &str, 
@@ -2197,6 +4335,7 @@ impl World {
             file_prefix: file_prefix.to_string(),
             flush_interval,
             include_component_details: true,
+            ..ReplayLogConfig::default()
         };
         self.enable_replay_logging(config)
     }
@@ -2225,6 +4364,7 @@ impl World {
     }
 
     /// Parse a replay log file and return the parsed history
+    #[cfg(feature = "fs")]
     pub fn parse_replay_log_file(file_path: &str) -> Result<WorldUpdateHistory, Box<dyn std::error::Error>> {
         replay_analysis::parse_replay_log(file_path)
     }
@@ -2232,222 +4372,201 @@ impl World {
     /// Apply a recorded world update diff for replay
     pub fn apply_update_diff(&mut self, diff: &WorldUpdateDiff) {
         for system_diff in diff.system_diffs() {
-            // Apply world operations first
-            for operation in system_diff.world_operations() {
-                match operation {
-                    WorldOperation::CreateWorld(_world_index) => {
-                        // Child world operations are complex to implement properly
-                        // Without a full world hierarchy system, we cannot implement this
-                        eprintln!("Warning: CreateWorld operation not implemented - requires world hierarchy support");
-                    }
-                    WorldOperation::RemoveWorld(_world_index) => {
-                        // Child world operations are complex to implement properly
-                        eprintln!("Warning: RemoveWorld operation not implemented - requires world hierarchy support");
-                    }
-                    WorldOperation::CreateEntity(entity) => {
-                        // Ensure the entity exists (create if it doesn't)
-                        if !self.entity_exists(*entity) {
-                            // Extend next_entity_id if necessary to maintain consistency
-                            if entity.entity_index >= self.next_entity_id {
-                                self.next_entity_id = entity.entity_index + 1;
-                            }
-                            // Add the entity to the entities list
-                            self.entities.push(*entity);
-                        }
-                    }
-                    WorldOperation::RemoveEntity(entity) => {
-                        // Remove the entity from the entities list
-                        self.entities.retain(|e| e != entity);
-                        // Remove all components for this entity
-                        for components in self.components.values_mut() {
-                            components.retain(|(e, _)| *e != *entity);
-                        }
-                    }
-                    WorldOperation::AddSystem(system_type_name) => {
-                        // Apply system addition during replay
-                        if let Err(e) = self.apply_system_addition(system_type_name) {
-                            eprintln!("Failed to apply system addition: {}", e);
-                        }
+            self.apply_system_diff(system_diff);
+        }
+    }
+
+    /// Apply the operations and changes recorded for one system
+    fn apply_system_diff(&mut self, system_diff: &SystemUpdateDiff) {
+        // Apply changes and operations in the order the system recorded them
+        for entry in system_diff.entries() {
+            match entry {
+                DiffEntry::Operation(operation) => self.apply_world_operation(operation),
+                DiffEntry::Change(change) => self.apply_component_change(change),
+            }
+        }
+        self.flush_observers();
+    }
+
+    /// Apply a recorded world operation
+    fn apply_world_operation(&mut self, operation: &WorldOperation) {
+        let operation = self.remap_operation(operation);
+        match &operation {
+            WorldOperation::CreateWorld(_world_index) => {
+                // Child world operations are complex to implement properly
+                // Without a full world hierarchy system, we cannot implement this
+                ecs_warn!("CreateWorld operation not implemented - requires world hierarchy support");
+            }
+            WorldOperation::RemoveWorld(_world_index) => {
+                // Child world operations are complex to implement properly
+                ecs_warn!("RemoveWorld operation not implemented - requires world hierarchy support");
+            }
+            WorldOperation::CreateEntity(entity) => {
+                // Ensure the entity exists (create if it doesn't)
+                if !self.entity_exists(*entity) {
+                    // Extend next_entity_id if necessary to maintain consistency
+                    if entity.entity_index >= self.next_entity_id {
+                        self.next_entity_id = entity.entity_index + 1;
                     }
+                    // Add the entity to the entities list, which is kept in entity order
+                    let index = self.entities.partition_point(|e| e < entity);
+                    self.entities.insert(index, *entity);
                 }
             }
-
-            // Apply component changes
-            for change in system_diff.component_changes() {
-                match change {
-                    DiffComponentChange::Added {
-                        entity,
-                        type_name,
-                        data,
-                    } => {
-                        // Parse and add the component
-                        if let Err(e) = self.apply_component_addition(entity, type_name, data) {
-                            eprintln!("Failed to apply component addition: {}", e);
-                        }
-                    }
-                    DiffComponentChange::Modified {
-                        entity,
-                        type_name,
-                        diff,
-                    } => {
-                        // Parse and apply the component diff
-                        if let Err(e) = self.apply_component_modification(entity, type_name, diff) {
-                            eprintln!("Failed to apply component modification: {}", e);
-                        }
-                    }
-                    DiffComponentChange::Removed { entity, type_name } => {
-                        // Remove the component
-                        if let Err(e) = self.apply_component_removal(entity, type_name) {
-                            eprintln!("Failed to apply component removal: {}", e);
-                        }
-                    }
+            WorldOperation::RemoveEntity(entity) => {
+                // Remove all components for this entity
+                self.clear_components(*entity);
+                // Remove the entity from the entities list
+                self.entities.retain(|e| e != entity);
+                self.leave_all_groups(*entity);
+                self.drop_relations(*entity);
+                self.disabled_entities.remove(entity);
+            }
+            WorldOperation::AddToGroup(entity, group) => {
+                self.insert_into_group(*entity, group);
+            }
+            WorldOperation::RemoveFromGroup(entity, group) => {
+                self.take_from_group(*entity, group);
+            }
+            WorldOperation::Relate(source, relation, target) => {
+                self.insert_relation(*source, relation, *target);
+            }
+            WorldOperation::Unrelate(source, relation, target) => {
+                self.take_relation(*source, relation, *target);
+            }
+            WorldOperation::DisableEntity(entity) => {
+                self.set_entity_enabled(*entity, false);
+            }
+            WorldOperation::EnableEntity(entity) => {
+                self.set_entity_enabled(*entity, true);
+            }
+            WorldOperation::ClearEntities => self.remove_all_entities(),
+            WorldOperation::ClearAll => self.remove_everything(),
+            WorldOperation::ResetHistory => {
+                // Only the recording was reset, the world state is unchanged
+            }
+            WorldOperation::AddSystem(system_type_name) => {
+                // Apply system addition during replay
+                if let Err(e) = self.apply_system_addition(system_type_name) {
+                    ecs_error!("Failed to apply system addition: {}", e);
                 }
             }
         }
     }
 
-    /// Apply a component addition from replay data
-    fn apply_component_addition(&mut self, entity: &Entity, type_name: &str, data: &str) -> Result<(), String> {
-        use crate::game::game::*;
-        
-        match type_name {
-            "Position" => {
-                let component = parse_position_data(data)?;
-                self.remove_component::<Position>(*entity);
-                self.add_component(*entity, component);
-            }
-            "Target" => {
-                let component = parse_target_data(data)?;
-                self.remove_component::<Target>(*entity);
-                self.add_component(*entity, component);
-            }
-            "WaitTimer" => {
-                let component = parse_wait_timer_data(data)?;
-                self.remove_component::<WaitTimer>(*entity);
-                self.add_component(*entity, component);
-            }
-            "Actor" => {
-                self.remove_component::<Actor>(*entity);
-                self.add_component(*entity, Actor);
-            }
-            "Home" => {
-                self.remove_component::<Home>(*entity);
-                self.add_component(*entity, Home);
-            }
-            "Work" => {
-                self.remove_component::<Work>(*entity);
-                self.add_component(*entity, Work);
-            }
-            "Obstacle" => {
-                self.remove_component::<Obstacle>(*entity);
-                self.add_component(*entity, Obstacle);
-            }
-            "ActorState" => {
-                let component = parse_actor_state_data(data)?;
-                self.remove_component::<ActorState>(*entity);
-                self.add_component(*entity, component);
+    /// Apply a recorded component change
+    fn apply_component_change(&mut self, change: &DiffComponentChange) {
+        match change {
+            DiffComponentChange::Added {
+                entity,
+                type_name,
+                data,
+            } => {
+                // Parse and add the component
+                let entity = self.remap_entity(*entity);
+                if let Err(e) = self.apply_component_addition(&entity, type_name, data) {
+                    ecs_error!("Failed to apply component addition: {}", e);
+                }
             }
-            _ => {
-                return Err(format!("Unknown component type: {}", type_name));
+            DiffComponentChange::Modified {
+                entity,
+                type_name,
+                diff,
+            } => {
+                // Parse and apply the component diff
+                let entity = self.remap_entity(*entity);
+                if let Err(e) = self.apply_component_modification(&entity, type_name, diff) {
+                    ecs_error!("Failed to apply component modification: {}", e);
+                }
+            }
+            DiffComponentChange::Removed { entity, type_name } => {
+                // Remove the component
+                let entity = self.remap_entity(*entity);
+                if let Err(e) = self.apply_component_removal(&entity, type_name) {
+                    ecs_error!("Failed to apply component removal: {}", e);
+                }
             }
         }
+    }
+
+    /// Apply a component addition from replay data, replacing an existing component
+    fn apply_component_addition(&mut self, entity: &Entity, type_name: &str, data: &str) -> Result<(), String> {
+        if self.dynamic.is_dynamic(type_name) {
+            return self.apply_dyn_addition(*entity, type_name, data);
+        }
+        let registration = self.replay_registration(type_name)?;
+        #[cfg(feature = "serde")]
+        if let Some(version) = self.outdated_version(&registration) {
+            return self.apply_outdated_addition(*entity, &registration, version, data);
+        }
+        let component = registration.decode(data)?;
+        self.insert_component_any(*entity, registration.type_id(), component);
         Ok(())
     }
 
-    /// Apply a component modification from replay data  
+    /// Apply a component modification from replay data
     fn apply_component_modification(&mut self, entity: &Entity, type_name: &str, diff_data: &str) -> Result<(), String> {
-        use crate::game::game::*;
-        
-        match type_name {
-            "Position" => {
-                if let Some(mut current) = self.get_component::<Position>(*entity).copied() {
-                    apply_position_diff(&mut current, diff_data)?;
-                    self.remove_component::<Position>(*entity);
-                    self.add_component(*entity, current);
-                } else {
-                    return Err(format!("Cannot modify Position component that doesn't exist on entity {:?}", entity));
-                }
-            }
-            "Target" => {
-                if let Some(mut current) = self.get_component::<Target>(*entity).copied() {
-                    apply_target_diff(&mut current, diff_data)?;
-                    self.remove_component::<Target>(*entity);
-                    self.add_component(*entity, current);
-                } else {
-                    return Err(format!("Cannot modify Target component that doesn't exist on entity {:?}", entity));
-                }
-            }
-            "WaitTimer" => {
-                if let Some(mut current) = self.get_component::<WaitTimer>(*entity).copied() {
-                    apply_wait_timer_diff(&mut current, diff_data)?;
-                    self.remove_component::<WaitTimer>(*entity);
-                    self.add_component(*entity, current);
-                } else {
-                    return Err(format!("Cannot modify WaitTimer component that doesn't exist on entity {:?}", entity));
-                }
-            }
-            "ActorState" => {
-                if let Some(mut current) = self.get_component::<ActorState>(*entity).copied() {
-                    apply_actor_state_diff(&mut current, diff_data)?;
-                    self.remove_component::<ActorState>(*entity);
-                    self.add_component(*entity, current);
-                } else {
-                    return Err(format!("Cannot modify ActorState component that doesn't exist on entity {:?}", entity));
-                }
-            }
-            _ => {
-                return Err(format!("Unknown component type for modification: {}", type_name));
-            }
+        if self.dynamic.is_dynamic(type_name) {
+            return self.apply_dyn_modification(*entity, type_name, diff_data);
         }
-        Ok(())
+        let registration = self.replay_registration(type_name)?;
+        #[cfg(feature = "serde")]
+        if let Some(version) = self.outdated_version(&registration) {
+            return self.apply_outdated_modification(*entity, &registration, version, diff_data);
+        }
+        let component = self
+            .component_any_mut(registration.type_id(), *entity)
+            .ok_or_else(|| format!("Cannot modify {} component that doesn't exist on entity {:?}", type_name, entity))?;
+        registration.apply_diff(component, diff_data)
     }
 
     /// Apply a component removal from replay data
     fn apply_component_removal(&mut self, entity: &Entity, type_name: &str) -> Result<(), String> {
-        use crate::game::game::*;
-        
-        match type_name {
-            "Position" => { self.remove_component::<Position>(*entity); }
-            "Target" => { self.remove_component::<Target>(*entity); }
-            "WaitTimer" => { self.remove_component::<WaitTimer>(*entity); }
-            "Actor" => { self.remove_component::<Actor>(*entity); }
-            "Home" => { self.remove_component::<Home>(*entity); }
-            "Work" => { self.remove_component::<Work>(*entity); }
-            "Obstacle" => { self.remove_component::<Obstacle>(*entity); }
-            "ActorState" => { self.remove_component::<ActorState>(*entity); }
-            _ => {
-                return Err(format!("Unknown component type for removal: {}", type_name));
-            }
+        if self.dynamic.is_dynamic(type_name) {
+            self.remove_dyn_component(*entity, type_name);
+            return Ok(());
+        }
+        let type_id = self.replay_registration(type_name)?.type_id();
+        self.replay_versions.forget(*entity, type_name);
+        self.observe_removal(type_id, *entity);
+        if let Some(components) = self.components.get_mut(&type_id) {
+            components.remove_all(*entity);
         }
+        self.component_ticks.remove(&(type_id, *entity));
+        self.update_memberships(type_id, *entity);
         Ok(())
     }
 
-    /// Apply a system addition from replay data
-    fn apply_system_addition(&mut self, system_type_name: &str) -> Result<(), String> {
-        use crate::game::game::*;
-        
-        match system_type_name {
-            "rust_ecs::game::game::MovementSystem" => {
-                self.add_system_internal(MovementSystem);
-            }
-            "rust_ecs::game::game::WaitSystem" => {
-                self.add_system_internal(WaitSystem);
-            }
-            "rust_ecs::game::game::RenderSystem" => {
-                self.add_system_internal(RenderSystem);
-            }
-            _ => {
-                return Err(format!("Unknown system type for addition: {}", system_type_name));
-            }
+    /// Look up a component type that was registered with `register_replay_component`
+    fn replay_registration(&self, type_name: &str) -> Result<reflect::ComponentRegistration, String> {
+        let registration = self
+            .component_registry
+            .get(type_name)
+            .ok_or_else(|| format!("Unknown component type: {}", type_name))?;
+        if !registration.is_replayable() {
+            return Err(format!("Component type {} is not registered for replay", type_name));
         }
+        Ok(registration.clone())
+    }
+
+    /// Apply a system addition from replay data using the registered system factory
+    fn apply_system_addition(&mut self, system_type_name: &str) -> Result<(), String> {
+        let factory = self.system_registry.get(system_type_name).ok_or_else(|| {
+            format!(
+                "No factory registered for system {}; add one with register_system_factory (registered: {})",
+                system_type_name,
+                self.system_registry.names().join(", ")
+            )
+        })?;
+        factory(self);
         Ok(())
     }
 
-    /// Get all entities that have a specific component type
+    /// Get all entities that have a specific component type, in entity order
     pub fn entities_with_component<T: 'static>(&self) -> Vec<Entity> {
         self.components
             .get(&TypeId::of::<T>())
-            .map(|components| components.iter().map(|(entity, _)| *entity).collect())
+            .map(|components| components.iter().map(|(entity, _)| entity).collect())
             .unwrap_or_default()
     }
 }
@@ -2675,6 +4794,389 @@ mod tests {
         assert_eq!(history.updates.len(), 3); // 1 system addition + 2 updates
     }
 
+    #[test]
+    fn test_initialize_and_deinitialize_are_recorded() {
+        /// Spawns the map when initialized and removes it when deinitialized
+        struct MapSystem(Vec<Entity>);
+
+        impl System for MapSystem {
+            type InComponents = ();
+            type OutComponents = (u32,);
+
+            fn initialize(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+                let wall = world.create_entity();
+                world.record_entity_creation(wall);
+                world.add_component(wall, 7u32);
+                world.record_component_addition(wall, &7u32);
+                self.0.push(wall);
+            }
+
+            fn update(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+            fn deinitialize(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+                for wall in self.0.drain(..) {
+                    world.commands().despawn(wall);
+                }
+            }
+        }
+
+        let mut world = World::new();
+        world.add_system(TestSystem);
+        world.add_system(MapSystem(Vec::new()));
+        world.initialize_systems();
+        world.update();
+        world.deinitialize_systems();
+
+        // 2 system additions, initialization, 1 update and deinitialization
+        let history = world.get_update_history();
+        assert_eq!(history.updates.len(), 5);
+        let init = &history.updates[2].system_diffs()[1];
+        assert_eq!(init.world_operations(), &[WorldOperation::CreateEntity(Entity::new(0, 0))]);
+        assert_eq!(init.component_changes().len(), 1);
+        assert!(history.updates[2].system_diffs()[0].world_operations().is_empty());
+        assert_eq!(
+            history.updates[4].system_diffs()[1].world_operations(),
+            &[WorldOperation::RemoveEntity(Entity::new(0, 0))]
+        );
+    }
+
+    #[test]
+    fn test_systems_without_changes_record_no_initialization() {
+        let mut world = World::new();
+        world.add_system(TestSystem);
+        world.initialize_systems();
+        world.deinitialize_systems();
+        assert_eq!(world.get_update_history().updates.len(), 1);
+    }
+
+    #[test]
+    fn test_replay_logging_to_memory_sink() {
+        let buffer = crate::test_util::SharedBuffer::default();
+        let config = ReplayLogConfig {
+            enabled: true,
+            ..ReplayLogConfig::default()
+        };
+        let mut world = World::new();
+        world.enable_replay_logging_to(config, buffer.clone()).unwrap();
+        let entity = world.create_entity();
+        world.add_component(entity, 7u32);
+        world.add_system(TestSystem);
+        world.update();
+        world.update();
+        world.disable_replay_logging().unwrap();
+
+        let log = buffer.contents();
+        assert!(log.starts_with("# ECS Replay Log"));
+        let history = replay_analysis::parse_replay_log_str(&log);
+        assert_eq!(history.len(), 2);
+    }
+
+    #[test]
+    fn test_startup_systems_run_once() {
+        struct SpawnMap;
+
+        impl System for SpawnMap {
+            type InComponents = ();
+            type OutComponents = (u32,);
+
+            fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+            fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+                let entity = world.create_entity();
+                world.add_component(entity, 7u32);
+                world.record_component_addition(entity, &7u32);
+            }
+
+            fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+        }
+
+        let mut world = World::new();
+        world.add_startup_system(SpawnMap);
+        world.add_system(TestSystem);
+        let recorded = world.get_update_history().len();
+
+        world.update();
+        world.update();
+
+        assert_eq!(world.entities_with_component::<u32>().len(), 1);
+        assert_eq!(world.frame_number(), 2);
+        // The startup update is recorded before the two frames
+        let updates = world.get_update_history().updates();
+        assert_eq!(updates.len(), recorded + 3);
+        let startup = updates[recorded].system_diffs()[0].component_changes();
+        assert_eq!(startup.len(), 1);
+        assert_eq!(startup[0].to_string(), "ADD Entity(0, 0) u32 7");
+
+        // In replay mode the startup changes are recorded but undone
+        let mut world = World::new();
+        world.enable_replay_mode();
+        world.add_startup_system(SpawnMap);
+        world.update();
+        assert_eq!(world.entity_count(), 0);
+        let updates = world.get_update_history().updates();
+        assert_eq!(updates[updates.len() - 2].system_diffs()[0].component_changes().len(), 1);
+    }
+
+    #[test]
+    fn test_system_panics_are_isolated() {
+        struct PanickingSystem {
+            updates: u32,
+        }
+
+        impl System for PanickingSystem {
+            type InComponents = ();
+            type OutComponents = ();
+
+            fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+            fn update(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+                self.updates += 1;
+                if self.updates == 2 {
+                    panic!("broken on update {}", self.updates);
+                }
+            }
+
+            fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+        }
+
+        struct CountingSystem;
+
+        impl System for CountingSystem {
+            type InComponents = ();
+            type OutComponents = (u32,);
+
+            fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+            fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+                let entity = world.create_entity();
+                world.add_component(entity, 1u32);
+            }
+
+            fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+        }
+
+        let mut world = World::new();
+        world.set_disable_failed_systems(true);
+        world.add_system(PanickingSystem { updates: 0 });
+        world.add_system(CountingSystem);
+        world.update();
+        world.update();
+        world.update();
+
+        // The system after the failed one still ran in every frame
+        assert_eq!(world.entities_with_component::<u32>().len(), 3);
+        assert_eq!(world.disabled_systems().len(), 1);
+        let updates = world.get_update_history().updates();
+        let failed_frame = &updates[updates.len() - 2];
+        assert_eq!(failed_frame.system_diffs()[0].failure(), Some("broken on update 2"));
+        assert_eq!(updates.last().unwrap().system_diffs().len(), 2);
+
+        let mut log = Vec::new();
+        replay_analysis::write_replay_log(world.get_update_history(), &mut log).unwrap();
+        let log = String::from_utf8(log).unwrap();
+        assert!(log.contains("    FAILED: broken on update 2\n"));
+        assert!(replay_analysis::validate_replay_log_str(&log).is_empty());
+        let parsed = replay_analysis::parse_replay_log_str(&log);
+        assert!(replay_analysis::compare_histories(world.get_update_history(), &parsed).is_empty());
+
+        world.enable_disabled_systems();
+        assert!(world.disabled_systems().is_empty());
+    }
+
+    #[test]
+    fn test_replayed_system_panics_leave_the_world_unchanged() {
+        /// Spawns an entity, then panics
+        struct SpawnThenPanicSystem;
+
+        impl System for SpawnThenPanicSystem {
+            type InComponents = ();
+            type OutComponents = (u32,);
+
+            fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+            fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+                let entity = world.create_entity();
+                world.add_component(entity, 1u32);
+                panic!("spawned and failed");
+            }
+
+            fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+        }
+
+        let mut world = World::new();
+        world.add_system(SpawnThenPanicSystem);
+        world.enable_replay_mode();
+        world.update();
+
+        assert_eq!(world.entity_count(), 0);
+        let updates = world.get_update_history().updates();
+        assert_eq!(updates.last().unwrap().system_diffs()[0].failure(), Some("spawned and failed"));
+        // The next entity gets the id the undone one had
+        assert_eq!(world.create_entity(), Entity::new(0, 0));
+    }
+
+    #[test]
+    fn test_replay_log_tools() {
+        let mut world = World::new();
+        let entity = world.create_entity();
+        world.add_component(entity, 7u32);
+        world.add_system(TestSystem);
+        world.update();
+        world.update();
+        world.update();
+
+        let mut log = Vec::new();
+        replay_analysis::write_replay_log(world.get_update_history(), &mut log).unwrap();
+        let log = String::from_utf8(log).unwrap();
+        assert_eq!(replay_analysis::validate_replay_log_str(&log), Vec::new());
+        let parsed = replay_analysis::parse_replay_log_str(&log);
+        assert_eq!(parsed.len(), world.get_update_history().len());
+        assert!(replay_analysis::compare_histories(world.get_update_history(), &parsed).is_empty());
+
+        let trimmed = replay_analysis::trim_history(&parsed, 1..100);
+        assert_eq!(trimmed.len(), parsed.len() - 1);
+        let differences = replay_analysis::compare_histories(&parsed, &trimmed);
+        assert_eq!(differences.first().map(|difference| difference.frame), Some(0));
+
+        let broken = "UPDATE 1\nSYSTEMS: 2\n  SYSTEM 0\n    COMPONENT_CHANGES: 2\n      ADD Entity(0, 1)\nUPDATE 3\nBOGUS\n";
+        let issues: Vec<String> = replay_analysis::validate_replay_log_str(broken)
+            .iter()
+            .map(|issue| issue.to_string())
+            .collect();
+        assert_eq!(
+            issues,
+            vec![
+                "line 2: SYSTEMS announces 2 entries but has 1",
+                "line 4: COMPONENT_CHANGES announces 2 entries but has 1",
+                "line 5: malformed ADD entry 'Entity(0, 1)'",
+                "line 6: UPDATE 3 follows UPDATE 1",
+                "line 7: unrecognized line 'BOGUS'",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_malformed_replay_log_lines_skip_to_the_next_update() {
+        let log = "\
+UPDATE 1
+SYSTEMS: 1
+  SYSTEM 0
+    COMPONENT_CHANGES: 2
+      ADD Entity(0, 1) u32 7
+      ADD Entity(0, 2)
+      ADD Entity(0, 3) u32 9
+UPDATE 2
+SYSTEMS: 1
+  SYSTEM 0
+    WORLD_OPERATIONS: 1
+      CREATE_ENTITY Entity(0, 4)
+    STATS: time_us=lots
+UPDATE 3
+SYSTEMS: 1
+  SYSTEM 0
+    WORLD_OPERATIONS: 1
+      CREATE_ENTITY Entity(0
+";
+        let (history, errors) = replay_analysis::parse_replay_log_with_errors(log);
+        let errors: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+        assert_eq!(
+            errors,
+            vec![
+                "line 6: malformed ADD entry 'Entity(0, 2)'",
+                "line 13: malformed STATS entry: invalid time_us value lots",
+                "line 18: malformed CREATE_ENTITY entry 'Entity(0'",
+            ]
+        );
+        // Entries before an error are kept, the rest of its update is skipped
+        assert_eq!(history.len(), 3);
+        let first = &history.updates()[0].system_diffs()[0];
+        assert_eq!(first.component_changes().len(), 1);
+        let second = &history.updates()[1].system_diffs()[0];
+        assert_eq!(second.world_operations(), &[WorldOperation::CreateEntity(Entity::new(0, 4))]);
+        assert!(second.stats().is_none());
+
+        let error = replay_analysis::try_parse_replay_log_str(log).unwrap_err();
+        assert_eq!((error.line, error.reason.as_str()), (6, "malformed ADD entry 'Entity(0, 2)'"));
+        assert_eq!(replay_analysis::parse_replay_log_str(log).len(), 3);
+
+        // Entries need an enclosing update and system
+        let (_, errors) = replay_analysis::parse_replay_log_with_errors("REM Entity(0, 1) u32\nSYSTEM 0\n");
+        assert_eq!(errors[0].reason, "REM outside of a system");
+        assert_eq!(errors.len(), 1, "the SYSTEM line is skipped while recovering");
+    }
+
+    #[test]
+    fn test_changes_and_operations_keep_their_recorded_order() {
+        // A pooled entity gets a component, is removed and is reused in one update
+        let entity = Entity::new(0, 0);
+        let mut system = SystemUpdateDiff::new();
+        system.record_world_operation(WorldOperation::CreateEntity(entity));
+        system.record_component_change(DiffComponentChange::Added {
+            entity,
+            type_name: "Shield".into(),
+            data: "{points: 3}".into(),
+        });
+        system.record_world_operation(WorldOperation::RemoveEntity(entity));
+        system.record_world_operation(WorldOperation::CreateEntity(entity));
+        assert_eq!(system.entry_order().as_deref(), Some("O C O O"));
+        assert!(matches!(system.entries()[1], DiffEntry::Change(_)));
+        let mut update = WorldUpdateDiff::new();
+        update.record(system);
+
+        let mut history = WorldUpdateHistory::new();
+        history.record(update.clone());
+        let mut log = Vec::new();
+        replay_analysis::write_replay_log(&history, &mut log).unwrap();
+        let log = String::from_utf8(log).unwrap();
+        assert!(log.contains("    ORDER: O C O O\n"), "{}", log);
+        assert_eq!(replay_analysis::validate_replay_log_str(&log), Vec::new());
+        let parsed = replay_analysis::parse_replay_log_str(&log);
+        assert_eq!(parsed.updates()[0].system_diffs()[0].entry_order().as_deref(), Some("O C O O"));
+
+        for update in [&update, &parsed.updates()[0]] {
+            let mut replayed = World::new();
+            replayed.register_schema(dynamic::ComponentSchema::new("Shield").field("points", dynamic::FieldType::Int));
+            replayed.apply_update_diff(update);
+            assert!(replayed.entity_exists(entity));
+            assert_eq!(replayed.dyn_component(entity, "Shield"), None);
+        }
+
+        // Logs without an ORDER line apply the operations first
+        let legacy = log.replace("    ORDER: O C O O\n", "");
+        let legacy = replay_analysis::parse_replay_log_str(&legacy);
+        assert_eq!(legacy.updates()[0].system_diffs()[0].entry_order(), None);
+        assert!(matches!(legacy.updates()[0].system_diffs()[0].entries()[3], DiffEntry::Change(_)));
+    }
+
+    #[test]
+    fn test_explain_frame() {
+        let mut log = String::new();
+        for update in 1..=4 {
+            let spike = update == 4;
+            log += &format!("UPDATE {}\nSYSTEMS: 2\n  SYSTEM 0\n    COMPONENT_CHANGES: 1\n", update);
+            log += "      MOD Entity(0, 0) Position PositionDiff { x: Some(1) }\n  SYSTEM 1\n";
+            if spike {
+                log += "    COMPONENT_CHANGES: 3\n";
+                for entity in 0..3 {
+                    log += &format!("      ADD Entity(0, {}) Health 10\n", entity);
+                }
+            }
+        }
+        let history = replay_analysis::parse_replay_log_str(&log);
+
+        let explanation = replay_analysis::explain_frame(&history, 3).unwrap();
+        assert_eq!((explanation.changes, explanation.window), (4, 3));
+        assert_eq!(explanation.rolling_average, 1.0);
+        assert_eq!(explanation.suspect, Some(1));
+        assert_eq!(explanation.component_types[0].type_name, "Health");
+        let report = explanation.to_string();
+        assert!(report.starts_with("Frame 3: 4 changes (rolling average 1.0 over 3 frames, 4.0x)"), "{}", report);
+        assert!(report.contains("Suspect: System 1 (+3.0 changes over its average)"), "{}", report);
+
+        assert_eq!(replay_analysis::explain_frame(&history, 1).unwrap().suspect, None);
+        assert_eq!(replay_analysis::explain_frame(&history, 4), None);
+    }
+
     #[test]
     fn test_multi_component_query() {
         let mut world = World::new();
@@ -2747,6 +5249,55 @@ mod tests {
         assert_eq!(velocity2.dy, 2.0); // 1.0 * 2.0
     }
 
+    #[test]
+    fn test_query_order() {
+        let mut world = World::new();
+        let mut world_view = WorldView::<(), ()>::new(&mut world);
+
+        let entity1 = world_view.create_entity();
+        let entity2 = world_view.create_entity();
+        let entity3 = world_view.create_entity();
+
+        // Components added out of entity order are still returned in entity order
+        world_view.add_component(entity3, Position { x: 0.0, y: 3.0 });
+        world_view.add_component(entity1, Position { x: 0.0, y: 1.0 });
+        world_view.add_component(entity2, Position { x: 0.0, y: 2.0 });
+        let entities: Vec<Entity> = world_view
+            .query_components::<(In<Position>,)>()
+            .iter()
+            .map(|(entity, _)| *entity)
+            .collect();
+        assert_eq!(entities, vec![entity1, entity2, entity3]);
+
+        let sorted = world_view.query_components_sorted_by::<(In<Position>,), _>(|(_, a), (_, b)| {
+            b.y.partial_cmp(&a.y).unwrap()
+        });
+        let entities: Vec<Entity> = sorted.iter().map(|(entity, _)| *entity).collect();
+        assert_eq!(entities, vec![entity3, entity2, entity1]);
+        assert_eq!(world.entities_with_component::<Position>(), vec![entity1, entity2, entity3]);
+    }
+
+    #[test]
+    fn test_filtered_query() {
+        let mut world = World::new();
+        let mut world_view = WorldView::<(), ()>::new(&mut world);
+        let entities: Vec<Entity> = (0..4).map(|_| world_view.create_entity()).collect();
+        for (index, entity) in entities.iter().enumerate() {
+            world_view.add_component(*entity, Position { x: 0.0, y: index as f32 });
+        }
+
+        // Actors within 1.5 cells of home at y = 2
+        let near = world_view.query_components_filtered::<(In<Position>,), _>(|_, position| (position.y - 2.0).abs() <= 1.5);
+        let near: Vec<Entity> = near.iter().map(|(entity, _)| *entity).collect();
+        assert_eq!(near, vec![entities[1], entities[2], entities[3]]);
+
+        for (_, position) in world_view.query_components_filtered::<(Out<Position>,), _>(|entity, _| entity == entities[0]) {
+            position.x = 5.0;
+        }
+        assert_eq!(world.get_component::<Position>(entities[0]).unwrap().x, 5.0);
+        assert_eq!(world.get_component::<Position>(entities[1]).unwrap().x, 0.0);
+    }
+
     #[test]
     fn test_multi_world_entity_identification() {
         let mut main_world = World::new();
@@ -2887,6 +5438,61 @@ mod tests {
         assert_eq!(map, map3);
     }
 
+    #[test]
+    fn test_diff_hashmap_is_formatted_in_key_order() {
+        let old: HashMap<u32, i32> = (0..50).map(|key| (key, 0)).collect();
+        let new: HashMap<u32, i32> = (0..50).rev().map(|key| (key + 10, 1)).collect();
+        let diff = old.diff(&new).unwrap();
+        let formatted = format!("{:?}", diff);
+        // Maps built separately iterate in different orders, their diffs format the same
+        for _ in 0..5 {
+            let old: HashMap<u32, i32> = old.clone().into_iter().collect();
+            let new: HashMap<u32, i32> = new.clone().into_iter().collect();
+            assert_eq!(format!("{:?}", old.diff(&new).unwrap()), formatted);
+        }
+        assert!(formatted.starts_with("HashMapDiff { changes: {0: Removed, 1: Removed, 2: Removed"), "{}", formatted);
+        assert!(formatted.contains("9: Removed, 10: Modified(1)"), "{}", formatted);
+
+        #[cfg(feature = "serde")]
+        {
+            let encoded = ron::to_string(&diff).unwrap();
+            assert!(encoded.starts_with("(changes:{0:Removed,1:Removed,2:Removed"), "{}", encoded);
+            let decoded: HashMapDiff<u32, i32> = ron::from_str(&encoded).unwrap();
+            let mut map = old.clone();
+            map.apply_diff(&decoded);
+            assert_eq!(map, new);
+        }
+    }
+
+    #[test]
+    fn test_diff_hashmap_keys_need_no_ord() {
+        #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+        struct Slot(u8);
+
+        let old: HashMap<Slot, i32> = HashMap::new();
+        let new: HashMap<Slot, i32> = [(Slot(10), 1), (Slot(2), 1)].into_iter().collect();
+        let diff = old.diff(&new).unwrap();
+        assert_eq!(format!("{:?}", diff), "HashMapDiff { changes: {Slot(2): Added(1), Slot(10): Added(1)} }");
+        assert!(compare_key_text("a2", "a10").is_lt());
+        assert!(compare_key_text("a02", "a2").is_ne());
+    }
+
+    #[test]
+    fn test_diff_btreemap() {
+        let old: std::collections::BTreeMap<String, i32> = [("a".to_string(), 1), ("b".to_string(), 2)].into();
+        let new: std::collections::BTreeMap<String, i32> = [("a".to_string(), 5), ("c".to_string(), 3)].into();
+        assert!(old.diff(&old.clone()).is_none());
+
+        let diff = old.diff(&new).unwrap();
+        let keys: Vec<&str> = diff.changes.keys().map(String::as_str).collect();
+        assert_eq!(keys, vec!["a", "b", "c"]);
+        assert!(matches!(diff.changes["b"], HashMapChange::Removed));
+
+        let mut map = old.clone();
+        map.apply_diff(&diff);
+        assert_eq!(map, new);
+    }
+
     #[test]
     fn test_diff_u32() {
         // Test u32 diffing (newly implemented)
@@ -2979,6 +5585,77 @@ mod tests {
         assert_eq!(s, s3);
     }
 
+    /// Diffs cell grids as the list of cells that changed
+    mod dirty_cells {
+        pub type Diff = Vec<(usize, u8)>;
+
+        pub fn diff(old: &[u8], new: &[u8]) -> Option<Diff> {
+            let cells: Diff = new
+                .iter()
+                .enumerate()
+                .filter(|(index, cell)| old.get(*index) != Some(cell))
+                .map(|(index, cell)| (index, *cell))
+                .collect();
+            (!cells.is_empty()).then_some(cells)
+        }
+
+        pub fn apply_diff(cells: &mut Vec<u8>, diff: &Diff) {
+            for (index, cell) in diff {
+                if *index >= cells.len() {
+                    cells.resize(index + 1, 0);
+                }
+                cells[*index] = *cell;
+            }
+        }
+    }
+
+    /// Diffs a whole grid through `dirty_cells`
+    mod grid_cells {
+        pub use super::dirty_cells::Diff;
+
+        pub fn diff(old: &super::Grid, new: &super::Grid) -> Option<Diff> {
+            super::dirty_cells::diff(&old.0, &new.0)
+        }
+
+        pub fn apply_diff(grid: &mut super::Grid, diff: &Diff) {
+            super::dirty_cells::apply_diff(&mut grid.0, diff)
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq, Diff)]
+    #[diff(with = "grid_cells")]
+    struct Grid(Vec<u8>);
+
+    #[test]
+    fn test_diff_derive_with_custom_diff() {
+        #[derive(Debug, Clone, PartialEq, Diff)]
+        struct Level {
+            width: u32,
+            #[diff(with = "dirty_cells")]
+            cells: Vec<u8>,
+        }
+
+        let old = Level { width: 4, cells: vec![0; 8] };
+        let mut new = old.clone();
+        new.cells[2] = 7;
+        new.cells[5] = 1;
+        let diff = old.diff(&new).unwrap();
+        assert_eq!(diff.width, None);
+        assert_eq!(diff.cells, Some(vec![(2, 7), (5, 1)]));
+        let mut level = old.clone();
+        level.apply_diff(&diff);
+        assert_eq!(level, new);
+        assert!(new.diff(&new.clone()).is_none());
+
+        // A whole type delegating to the module, even a tuple struct
+        let grid = Grid(vec![1, 2, 3]);
+        let changed = Grid(vec![1, 4, 3, 5]);
+        assert_eq!(grid.diff(&changed), Some(vec![(1, 4), (3, 5)]));
+        let mut applied = grid.clone();
+        applied.apply_diff(&grid.diff(&changed).unwrap());
+        assert_eq!(applied, changed);
+    }
+
     #[test]
     fn test_extended_multi_component_query() {
         let mut world = World::new();
@@ -3053,6 +5730,8 @@ mod tests {
 /// Replay data analysis utilities for developers
 pub mod replay_analysis {
     use super::*;
+    pub use crate::causality::{why, ChangeCause};
+    pub use crate::replay_validation::check_replay_log_consistency;
 
     /// Statistics about a replay session
     #[derive(Debug)]
@@ -3066,6 +5745,41 @@ pub mod replay_analysis {
         pub component_types_involved: Vec<String>,
         pub most_active_frame: Option<usize>,
         pub most_changes_in_frame: usize,
+        /// Execution statistics per system index, for logs recorded with system statistics
+        pub system_stats: Vec<SystemStatsSummary>,
+        /// Frame with the most allocations and their number, if allocations were counted
+        pub most_allocations_in_frame: Option<(usize, u64)>,
+    }
+
+    /// Execution statistics of the system at one index, aggregated over the frames
+    #[derive(Debug, Clone, Default, PartialEq, Eq)]
+    pub struct SystemStatsSummary {
+        pub system_index: usize,
+        /// Number of updates that recorded statistics
+        pub executions: usize,
+        pub total_duration: std::time::Duration,
+        pub max_duration: std::time::Duration,
+        pub queries: usize,
+        pub entities_matched: usize,
+        pub allocations: Option<u64>,
+    }
+
+    impl SystemStatsSummary {
+        fn add(&mut self, stats: &SystemStats) {
+            self.executions += 1;
+            self.total_duration += stats.duration;
+            self.max_duration = self.max_duration.max(stats.duration);
+            self.queries += stats.queries;
+            self.entities_matched += stats.entities_matched;
+            if let Some(allocations) = stats.allocations {
+                *self.allocations.get_or_insert(0) += allocations;
+            }
+        }
+
+        /// Average time of one update
+        pub fn average_duration(&self) -> std::time::Duration {
+            self.total_duration / self.executions.max(1) as u32
+        }
     }
 
     /// Analyze a world update history and generate statistics
@@ -3080,17 +5794,33 @@ pub mod replay_analysis {
             component_types_involved: Vec::new(),
             most_active_frame: None,
             most_changes_in_frame: 0,
+            system_stats: Vec::new(),
+            most_allocations_in_frame: None,
         };
 
         let mut component_types = std::collections::HashSet::new();
         let mut frame_changes: Vec<usize> = Vec::new();
 
-        for update in history.updates() {
+        for (frame_idx, update) in history.updates().iter().enumerate() {
             stats.total_system_executions += update.system_diffs().len();
             
             let mut frame_change_count = 0;
+            let mut frame_allocations = None;
             
-            for system_diff in update.system_diffs() {
+            for (system_idx, system_diff) in update.system_diffs().iter().enumerate() {
+                if let Some(system_stats) = system_diff.stats() {
+                    while stats.system_stats.len() <= system_idx {
+                        stats.system_stats.push(SystemStatsSummary {
+                            system_index: stats.system_stats.len(),
+                            ..Default::default()
+                        });
+                    }
+                    stats.system_stats[system_idx].add(system_stats);
+                    if let Some(allocations) = system_stats.allocations {
+                        *frame_allocations.get_or_insert(0) += allocations;
+                    }
+                }
+
                 stats.total_component_changes += system_diff.component_changes().len();
                 stats.total_world_operations += system_diff.world_operations().len();
                 frame_change_count += system_diff.component_changes().len() + system_diff.world_operations().len();
@@ -3101,7 +5831,7 @@ pub mod replay_analysis {
                         DiffComponentChange::Added { type_name, .. } |
                         DiffComponentChange::Modified { type_name, .. } |
                         DiffComponentChange::Removed { type_name, .. } => {
-                            component_types.insert(type_name.clone());
+                            component_types.insert(type_name.to_string());
                         }
                     }
                 }
@@ -3117,6 +5847,11 @@ pub mod replay_analysis {
             }
             
             frame_changes.push(frame_change_count);
+            if let Some(allocations) = frame_allocations {
+                if stats.most_allocations_in_frame.map(|(_, most)| most) < Some(allocations) {
+                    stats.most_allocations_in_frame = Some((frame_idx, allocations));
+                }
+            }
         }
 
         // Find most active frame
@@ -3136,6 +5871,16 @@ pub mod replay_analysis {
         let stats = analyze_replay_history(history);
         
         println!("=== ECS Replay Analysis Report ===");
+        let metadata = history.metadata();
+        if let Some(game_version) = &metadata.game_version {
+            println!("Game Version: {}", game_version);
+        }
+        if let Some(seed) = metadata.seed {
+            println!("Seed: {}", seed);
+        }
+        if let Some(config_hash) = &metadata.config_hash {
+            println!("Config Hash: {}", config_hash);
+        }
         println!("Total Updates: {}", stats.total_updates);
         println!("Total System Executions: {}", stats.total_system_executions);
         println!("Total Component Changes: {}", stats.total_component_changes);
@@ -3157,6 +5902,38 @@ pub mod replay_analysis {
                 stats.total_component_changes as f64 / stats.total_updates as f64);
         }
         
+        let executed: Vec<&SystemStatsSummary> =
+            stats.system_stats.iter().filter(|system| system.executions > 0).collect();
+        if !executed.is_empty() {
+            println!("System Statistics:");
+            for system in executed {
+                print!(
+                    "  System {}: {} runs, avg {}us, max {}us, {} queries matching {} entities",
+                    system.system_index,
+                    system.executions,
+                    system.average_duration().as_micros(),
+                    system.max_duration.as_micros(),
+                    system.queries,
+                    system.entities_matched
+                );
+                match system.allocations {
+                    Some(allocations) => println!(", {} allocations", allocations),
+                    None => println!(),
+                }
+            }
+        }
+        if let Some((frame, allocations)) = stats.most_allocations_in_frame {
+            println!("Most Allocations: Frame {} ({} allocations)", frame, allocations);
+        }
+
+        let annotations = history.annotations();
+        if !annotations.is_empty() {
+            println!("Annotations:");
+            for (frame, annotation) in annotations {
+                println!("  Frame {} at {}ms: {}", frame, annotation.elapsed.as_millis(), annotation.text);
+            }
+        }
+
         println!("=== End Report ===");
     }
 
@@ -3192,297 +5969,1046 @@ pub mod replay_analysis {
         anomalous_frames
     }
 
+    /// Number of frames before an explained frame that its activity is compared against
+    pub const EXPLAIN_WINDOW: usize = 30;
+
+    /// Changes of one system in an explained frame
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct SystemActivity {
+        pub system_index: usize,
+        pub changes: usize,
+        /// Average changes of the system over the preceding frames
+        pub rolling_average: f64,
+        /// Time spent in the update, if system statistics were recorded
+        pub duration: Option<std::time::Duration>,
+    }
+
+    /// Changes of one component type in an explained frame
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct ComponentActivity {
+        pub type_name: String,
+        pub changes: usize,
+        /// Average changes of the type over the preceding frames
+        pub rolling_average: f64,
+    }
+
+    /// Breakdown of a frame's activity, returned by `explain_frame`
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct FrameExplanation {
+        pub frame: usize,
+        /// Component changes and world operations of the frame
+        pub changes: usize,
+        /// Average changes over the up to `EXPLAIN_WINDOW` preceding frames
+        pub rolling_average: f64,
+        /// Number of preceding frames the averages are taken over
+        pub window: usize,
+        /// Systems of the frame, most changes above their average first
+        pub systems: Vec<SystemActivity>,
+        /// Component types changed in the frame or the window, most changes above their average first
+        pub component_types: Vec<ComponentActivity>,
+        /// System with the most changes above its average, the likely cause of a spike
+        pub suspect: Option<usize>,
+    }
+
+    /// Formats the explanation as a compact report
+    impl std::fmt::Display for FrameExplanation {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(
+                f,
+                "Frame {}: {} changes (rolling average {:.1} over {} frames",
+                self.frame, self.changes, self.rolling_average, self.window
+            )?;
+            if self.rolling_average > 0.0 {
+                write!(f, ", {:.1}x", self.changes as f64 / self.rolling_average)?;
+            }
+            writeln!(f, ")")?;
+            writeln!(f, "  Systems:")?;
+            for system in &self.systems {
+                write!(
+                    f,
+                    "    System {}: {} changes (avg {:.1})",
+                    system.system_index, system.changes, system.rolling_average
+                )?;
+                if let Some(duration) = system.duration {
+                    write!(f, " time {}us", duration.as_micros())?;
+                }
+                writeln!(f)?;
+            }
+            let changed: Vec<&ComponentActivity> =
+                self.component_types.iter().filter(|component| component.changes > 0).collect();
+            if !changed.is_empty() {
+                writeln!(f, "  Component types:")?;
+                for component in changed {
+                    writeln!(
+                        f,
+                        "    {}: {} changes (avg {:.1})",
+                        component.type_name, component.changes, component.rolling_average
+                    )?;
+                }
+            }
+            match self.suspect.and_then(|suspect| self.systems.iter().find(|system| system.system_index == suspect)) {
+                Some(system) => writeln!(
+                    f,
+                    "  Suspect: System {} (+{:.1} changes over its average)",
+                    system.system_index,
+                    system.changes as f64 - system.rolling_average
+                ),
+                None => writeln!(f, "  Suspect: none, no system is above its average"),
+            }
+        }
+    }
+
+    /// Break a frame down by system and component type and compare it against
+    /// the average of the preceding frames. Returns None if the frame is not in the history.
+    pub fn explain_frame(history: &WorldUpdateHistory, frame: usize) -> Option<FrameExplanation> {
+        let update = history.updates().get(frame)?;
+        let preceding = &history.updates()[frame.saturating_sub(EXPLAIN_WINDOW)..frame];
+        let average = |count: usize| {
+            if preceding.is_empty() {
+                0.0
+            } else {
+                count as f64 / preceding.len() as f64
+            }
+        };
+        let system_changes = |system: &SystemUpdateDiff| system.component_changes().len() + system.world_operations().len();
+
+        let mut systems: Vec<SystemActivity> = update
+            .system_diffs()
+            .iter()
+            .enumerate()
+            .map(|(system_index, system)| {
+                let total: usize = preceding
+                    .iter()
+                    .filter_map(|update| update.system_diffs().get(system_index))
+                    .map(system_changes)
+                    .sum();
+                SystemActivity {
+                    system_index,
+                    changes: system_changes(system),
+                    rolling_average: average(total),
+                    duration: system.stats().map(|stats| stats.duration),
+                }
+            })
+            .collect();
+        let excess = |changes: usize, rolling_average: f64| changes as f64 - rolling_average;
+        systems.sort_by(|a, b| {
+            excess(b.changes, b.rolling_average).total_cmp(&excess(a.changes, a.rolling_average))
+        });
+
+        // Changes per component type in the frame and in the window
+        let mut type_changes: std::collections::BTreeMap<&str, (usize, usize)> = std::collections::BTreeMap::new();
+        for system in update.system_diffs() {
+            for change in system.component_changes() {
+                type_changes.entry(change.type_name()).or_default().0 += 1;
+            }
+        }
+        for system in preceding.iter().flat_map(|update| update.system_diffs()) {
+            for change in system.component_changes() {
+                type_changes.entry(change.type_name()).or_default().1 += 1;
+            }
+        }
+        let mut component_types: Vec<ComponentActivity> = type_changes
+            .into_iter()
+            .map(|(type_name, (changes, total))| ComponentActivity {
+                type_name: type_name.to_string(),
+                changes,
+                rolling_average: average(total),
+            })
+            .collect();
+        component_types.sort_by(|a, b| {
+            excess(b.changes, b.rolling_average).total_cmp(&excess(a.changes, a.rolling_average))
+        });
+
+        let changes = update.system_diffs().iter().map(system_changes).sum();
+        let window_total: usize = preceding.iter().flat_map(|update| update.system_diffs()).map(system_changes).sum();
+        let suspect = systems
+            .first()
+            .filter(|system| excess(system.changes, system.rolling_average) > 0.0)
+            .map(|system| system.system_index);
+        Some(FrameExplanation {
+            frame,
+            changes,
+            rolling_average: average(window_total),
+            window: preceding.len(),
+            systems,
+            component_types,
+            suspect,
+        })
+    }
+
+    /// Print the breakdown of a frame from `explain_frame`
+    pub fn print_frame_explanation(history: &WorldUpdateHistory, frame: usize) {
+        match explain_frame(history, frame) {
+            Some(explanation) => print!("{}", explanation),
+            None => println!("Frame {} is not in the history ({} frames)", frame, history.len()),
+        }
+    }
+
+    /// Draws the state of a replayed world as lines of text
+    pub trait FrameRenderer {
+        /// Create an empty world with the component types the history uses registered for replay
+        fn new_world() -> World
+        where
+            Self: Sized,
+        {
+            World::new()
+        }
+
+        /// Draw the world after a frame was applied
+        fn render(&mut self, world: &World) -> Vec<String>;
+    }
+
+    /// Draw every frame of a history without running its systems. The first
+    /// entry is the initial state, entry `n` the world after `n` updates.
+    pub fn render_frames<R: FrameRenderer>(history: &WorldUpdateHistory, renderer: &mut R) -> Result<Vec<Vec<String>>, String> {
+        let mut player = crate::ReplayPlayer::new(history.clone(), R::new_world as fn() -> World)?;
+        let mut frames = vec![renderer.render(player.world())];
+        while player.step() {
+            frames.push(renderer.render(player.world()));
+        }
+        Ok(frames)
+    }
+
+    /// Draw the world after the given number of updates of a history
+    pub fn render_frame<R: FrameRenderer>(history: &WorldUpdateHistory, frame: usize, renderer: &mut R) -> Result<Vec<String>, String> {
+        if frame > history.len() {
+            return Err(format!("Frame {} is not in the history ({} frames)", frame, history.len()));
+        }
+        let mut player = crate::ReplayPlayer::new(history.clone(), R::new_world as fn() -> World)?;
+        player.seek(frame);
+        Ok(renderer.render(player.world()))
+    }
+
     /// Read and parse a replay log file
+    #[cfg(feature = "fs")]
     pub fn read_replay_log(file_path: &str) -> Result<Vec<String>, std::io::Error> {
         std::fs::read_to_string(file_path)
             .map(|content| content.lines().map(|line| line.to_string()).collect())
     }
 
     /// Parse a replay log file into WorldUpdateHistory
+    #[cfg(feature = "fs")]
     pub fn parse_replay_log(file_path: &str) -> Result<WorldUpdateHistory, Box<dyn std::error::Error>> {
-        let lines = replay_analysis::read_replay_log(file_path)?;
-        let mut history = WorldUpdateHistory::new();
-        let mut current_update: Option<WorldUpdateDiff> = None;
-        let mut current_system: Option<SystemUpdateDiff> = None;
-        let mut _line_number = 0;
+        let contents = std::fs::read_to_string(file_path)?;
+        Ok(parse_replay_log_str(&contents))
+    }
 
-        for line in lines {
-            _line_number += 1;
-            let line = line.trim();
-            
-            // Skip comments and empty lines
-            if line.starts_with('#') || line.is_empty() {
-                continue;
+    /// Problem found while parsing a replay log, after which the parser
+    /// skipped ahead to the next update
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct ParseError {
+        /// 1-based line number in the log
+        pub line: usize,
+        pub reason: String,
+    }
+
+    impl std::fmt::Display for ParseError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "line {}: {}", self.line, self.reason)
+        }
+    }
+
+    impl std::error::Error for ParseError {}
+
+    /// Parse the contents of a replay log into WorldUpdateHistory, skipping
+    /// the rest of an update after a malformed line. See
+    /// `parse_replay_log_with_errors` for the lines that were skipped.
+    pub fn parse_replay_log_str(contents: &str) -> WorldUpdateHistory {
+        let (history, errors) = parse_replay_log_with_errors(contents);
+        for error in errors {
+            ecs_warn!("Skipped the rest of a replay log update after {}", error);
+        }
+        history
+    }
+
+    /// Parse the contents of a replay log, failing on the first malformed line
+    pub fn try_parse_replay_log_str(contents: &str) -> Result<WorldUpdateHistory, ParseError> {
+        let (history, mut errors) = parse_replay_log_with_errors(contents);
+        if errors.is_empty() {
+            Ok(history)
+        } else {
+            Err(errors.swap_remove(0))
+        }
+    }
+
+    /// Parse the contents of a replay log into WorldUpdateHistory together
+    /// with the malformed lines found.
+    ///
+    /// After a malformed line the parser recovers at the next UPDATE,
+    /// INITIAL_STATE or KEYFRAME line. The entries read before the error
+    /// are kept, so update numbers still match frames.
+    pub fn parse_replay_log_with_errors(contents: &str) -> (WorldUpdateHistory, Vec<ParseError>) {
+        /// Sections read so far
+        #[derive(Default)]
+        struct ParseState {
+            history: WorldUpdateHistory,
+            current_update: Option<WorldUpdateDiff>,
+            current_system: Option<SystemUpdateDiff>,
+            /// Set while reading an INITIAL_STATE (None) or KEYFRAME (frame)
+            /// section, whose entries go to `current_system`
+            snapshot: Option<(Option<usize>, crate::snapshot::WorldSnapshot)>,
+            /// Whether `current_system` had an ORDER line
+            ordered: bool,
+        }
+
+        impl ParseState {
+            /// Take the system being read. Without an ORDER line its
+            /// operations came before its changes.
+            fn take_system(&mut self) -> Option<SystemUpdateDiff> {
+                let mut system = self.current_system.take()?;
+                if !std::mem::take(&mut self.ordered) {
+                    system.clear_sequences();
+                }
+                Some(system)
+            }
+
+            /// Start reading a system
+            fn start_system(&mut self) {
+                self.current_system = Some(SystemUpdateDiff::new());
+                self.ordered = false;
             }
 
-            if line.starts_with("UPDATE ") {
-                // Save previous update if exists
-                if let Some(update) = current_update.take() {
-                    history.record(update);
+            /// Store the update or snapshot section being read
+            fn finish_section(&mut self) {
+                if let Some((frame, mut snapshot)) = self.snapshot.take() {
+                    snapshot.diff = self.take_system().unwrap_or_default();
+                    match frame {
+                        Some(frame) => self.history.record_keyframe(frame, snapshot),
+                        None => self.history.set_initial_state(snapshot),
+                    }
                 }
-                current_update = Some(WorldUpdateDiff::new());
-            } else if line.starts_with("SYSTEMS: ") {
-                // Just metadata, continue
-            } else if line.starts_with("  SYSTEM ") {
-                // Save previous system if exists
-                if let Some(system) = current_system.take() {
-                    if let Some(ref mut update) = current_update {
+                if let Some(system) = self.take_system() {
+                    if let Some(ref mut update) = self.current_update {
                         update.record(system);
                     }
                 }
-                current_system = Some(SystemUpdateDiff::new());
-            } else if line.starts_with("    COMPONENT_CHANGES: ") {
-                // Component changes section header
-            } else if line.starts_with("      ADD ") {
-                // Parse component addition: "ADD Entity(world_id, entity_id) ComponentType data"
-                if let Some(change) = parse_component_add(&line[10..]) {
-                    if let Some(ref mut system) = current_system {
-                        system.record_component_change(change);
-                    }
+                if let Some(update) = self.current_update.take() {
+                    self.history.record(update);
                 }
-            } else if line.starts_with("      MOD ") {
-                // Parse component modification: "MOD Entity(world_id, entity_id) ComponentType diff"
-                if let Some(change) = parse_component_mod(&line[10..]) {
-                    if let Some(ref mut system) = current_system {
-                        system.record_component_change(change);
+            }
+
+            fn start_snapshot(&mut self, frame: Option<usize>) {
+                self.finish_section();
+                self.snapshot = Some((frame, crate::snapshot::WorldSnapshot::default()));
+                self.start_system();
+            }
+
+            /// Get the system or snapshot whose entries are being read
+            fn system(&mut self, keyword: &str) -> Result<&mut SystemUpdateDiff, String> {
+                self.current_system
+                    .as_mut()
+                    .ok_or_else(|| format!("{} outside of a system", keyword.trim_end_matches(':')))
+            }
+
+            /// Parse one non-empty, non-comment line
+            fn parse_line(&mut self, line: &str) -> Result<(), String> {
+                let (keyword, rest) = line.split_once(' ').unwrap_or((line, ""));
+                let malformed = || format!("malformed {} entry '{}'", keyword, rest);
+                match keyword {
+                    "INITIAL_STATE" => self.start_snapshot(None),
+                    "KEYFRAME" => {
+                        // World state after a frame of a sampled log: "KEYFRAME frame"
+                        let frame = rest.parse::<usize>().map_err(|_| format!("invalid keyframe frame '{}'", rest))?;
+                        self.start_snapshot(Some(frame));
                     }
-                }
-            } else if line.starts_with("      REM ") {
-                // Parse component removal: "REM Entity(world_id, entity_id) ComponentType"
-                if let Some(change) = parse_component_rem(&line[10..]) {
-                    if let Some(ref mut system) = current_system {
-                        system.record_component_change(change);
+                    "RESOURCE" => {
+                        // Resource of a snapshot: "RESOURCE ResourceType data"
+                        let (type_name, data) = rest.split_once(' ').ok_or_else(malformed)?;
+                        let (_, snapshot) = self
+                            .snapshot
+                            .as_mut()
+                            .ok_or("RESOURCE outside of the initial state or a keyframe")?;
+                        snapshot.resources.push((type_name.to_string(), data.to_string()));
                     }
-                }
-            } else if line.starts_with("    WORLD_OPERATIONS: ") {
-                // World operations section header
-            } else if line.starts_with("      CREATE_ENTITY ") {
-                // Parse entity creation: "CREATE_ENTITY Entity(world_id, entity_id)"
-                if let Some(entity) = parse_entity(&line[20..]) {
-                    if let Some(ref mut system) = current_system {
-                        system.record_world_operation(WorldOperation::CreateEntity(entity));
+                    "UPDATE" => {
+                        // Save the previous update (and its last system) or snapshot if exists
+                        self.finish_section();
+                        self.current_update = Some(WorldUpdateDiff::new());
+                        rest.parse::<usize>().map_err(|_| format!("invalid update number '{}'", rest))?;
                     }
-                }
-            } else if line.starts_with("      REMOVE_ENTITY ") {
-                // Parse entity removal: "REMOVE_ENTITY Entity(world_id, entity_id)"
-                if let Some(entity) = parse_entity(&line[20..]) {
-                    if let Some(ref mut system) = current_system {
-                        system.record_world_operation(WorldOperation::RemoveEntity(entity));
+                    "ANNOTATION" => {
+                        // Marker: "ANNOTATION 5730ms text"
+                        let annotation = crate::replay_metadata::parse_annotation(rest).ok_or_else(malformed)?;
+                        let update = self.current_update.as_mut().ok_or("ANNOTATION outside of an update")?;
+                        update.record_annotation(annotation);
                     }
-                }
-            } else if line.starts_with("      CREATE_WORLD ") {
-                // Parse world creation: "CREATE_WORLD world_id"
-                if let Ok(world_id) = line[19..].parse::<usize>() {
-                    if let Some(ref mut system) = current_system {
-                        system.record_world_operation(WorldOperation::CreateWorld(world_id));
+                    "SYSTEM" => {
+                        if self.current_update.is_none() {
+                            return Err("SYSTEM outside of an update".to_string());
+                        }
+                        rest.parse::<usize>().map_err(|_| format!("invalid system index '{}'", rest))?;
+                        // Save the previous system if exists
+                        if let Some(system) = self.take_system() {
+                            if let Some(ref mut update) = self.current_update {
+                                update.record(system);
+                            }
+                        }
+                        self.start_system();
                     }
-                }
-            } else if line.starts_with("      REMOVE_WORLD ") {
-                // Parse world removal: "REMOVE_WORLD world_id"
-                if let Ok(world_id) = line[19..].parse::<usize>() {
-                    if let Some(ref mut system) = current_system {
-                        system.record_world_operation(WorldOperation::RemoveWorld(world_id));
+                    // Section headers, whose counts are checked by `validate_replay_log_str`
+                    "SYSTEMS:" | "COMPONENT_CHANGES:" | "WORLD_OPERATIONS:" | "RESOURCES:" => {
+                        rest.parse::<usize>().map_err(|_| format!("invalid {} count '{}'", keyword, rest))?;
+                    }
+                    "ADD" | "MOD" | "REM" => {
+                        // Component changes: "ADD Entity(world_id, entity_id) ComponentType data",
+                        // "MOD Entity(world_id, entity_id) ComponentType diff" and
+                        // "REM Entity(world_id, entity_id) ComponentType"
+                        let change = match keyword {
+                            "ADD" => parse_component_add(rest),
+                            "MOD" => parse_component_mod(rest),
+                            _ => parse_component_rem(rest),
+                        };
+                        let change = change.ok_or_else(malformed)?;
+                        self.system(keyword)?.record_component_change(change);
+                    }
+                    "FAILED:" => {
+                        // System panic: "FAILED: message"
+                        self.system(keyword)?.record_failure(rest);
+                    }
+                    "ERROR:" => {
+                        // Error reported by the system: "ERROR: message"
+                        self.system(keyword)?.record_error(rest);
+                    }
+                    "ASSERT:" => {
+                        // Failed assertion of the system: "ASSERT: name"
+                        self.system(keyword)?.record_failed_assertion(rest);
+                    }
+                    "STATS:" => {
+                        // Execution statistics: "STATS: time_us=120 queries=2 matched=340 allocs=12"
+                        let stats = rest.parse().map_err(|e| format!("malformed STATS entry: {}", e))?;
+                        self.system(keyword)?.record_stats(stats);
+                    }
+                    "ORDER:" => {
+                        // Order of the system's changes and operations: "ORDER: C O C"
+                        self.system(keyword)?
+                            .set_entry_order(rest)
+                            .map_err(|e| format!("malformed ORDER entry: {}", e))?;
+                        self.ordered = true;
+                    }
+                    "CAUSE:" => {
+                        // Components read before a change: "CAUSE: Entity(0, 0) Position <- Velocity"
+                        let cause = rest.parse().map_err(|e| format!("malformed CAUSE entry: {}", e))?;
+                        self.system(keyword)?.record_cause(cause);
                     }
+                    _ => {
+                        let operation = parse_world_operation(line)?;
+                        self.system(keyword)?.record_world_operation(operation);
+                    }
+                }
+                Ok(())
+            }
+        }
+
+        let mut state = ParseState::default();
+        let mut metadata = crate::replay_metadata::ReplayMetadata::default();
+        let mut errors = Vec::new();
+        // Set after a malformed line until the next section starts
+        let mut recovering = false;
+        for (index, line) in contents.lines().enumerate() {
+            // Indentation is only cosmetic, sections are identified by their keyword
+            let line = line.trim();
+
+            // Header comments may hold session metadata
+            if let Some(comment) = line.strip_prefix('#') {
+                if state.history.is_empty() && state.current_update.is_none() && state.snapshot.is_none() {
+                    metadata.parse_header_line(comment.trim());
                 }
-            } else if line.starts_with("      ADD_SYSTEM ") {
-                // Parse system addition: "ADD_SYSTEM system_type_name"
-                let system_type_name = line[17..].to_string();
-                if let Some(ref mut system) = current_system {
-                    system.record_world_operation(WorldOperation::AddSystem(system_type_name));
+                continue;
+            }
+            // Skip empty lines
+            if line.is_empty() {
+                continue;
+            }
+
+            if recovering {
+                let keyword = line.split(' ').next().unwrap_or(line);
+                if !matches!(keyword, "UPDATE" | "INITIAL_STATE" | "KEYFRAME") {
+                    continue;
                 }
+                recovering = false;
+            }
+            if let Err(reason) = state.parse_line(line) {
+                errors.push(ParseError { line: index + 1, reason });
+                recovering = true;
             }
         }
 
         // Save any remaining data
-        if let Some(system) = current_system {
-            if let Some(ref mut update) = current_update {
-                update.record(system);
+        state.finish_section();
+        state.history.set_metadata(metadata);
+
+        (state.history, errors)
+    }
+
+    /// Write a history in the replay log format, readable by `parse_replay_log_str`
+    pub fn write_replay_log<W: Write>(history: &WorldUpdateHistory, writer: &mut W) -> std::io::Result<()> {
+        writeln!(writer, "# ECS Replay Log")?;
+        crate::replay_metadata::write_metadata_header(writer, history.metadata())?;
+        writeln!(writer, "# Format: Each line represents one world update")?;
+        writeln!(writer)?;
+        if let Some(snapshot) = history.initial_state() {
+            crate::snapshot::write_snapshot_entry(writer, "INITIAL_STATE", snapshot)?;
+        }
+        let mut keyframes = history.keyframes().iter().peekable();
+        for index in 0..=history.len() {
+            while let Some(keyframe) = keyframes.next_if(|keyframe| keyframe.update_index <= index) {
+                let header = format!("KEYFRAME {}", keyframe.frame);
+                crate::snapshot::write_snapshot_entry(writer, &header, &keyframe.state)?;
+            }
+            if let Some(update) = history.updates().get(index) {
+                write_update_entry(writer, index + 1, update, true)?;
             }
         }
-        if let Some(update) = current_update {
-            history.record(update);
+        writeln!(writer, "# End of replay log - Total updates: {}", history.len())
+    }
+
+    /// Copy the updates of the given frames into a new history. The initial
+    /// state is kept if the frames start at the beginning; otherwise a
+    /// keyframe right before the first frame becomes the initial state.
+    pub fn trim_history(history: &WorldUpdateHistory, frames: std::ops::Range<usize>) -> WorldUpdateHistory {
+        let mut trimmed = WorldUpdateHistory::new();
+        trimmed.set_metadata(history.metadata().clone());
+        let start_keyframe = history
+            .keyframes()
+            .iter()
+            .rfind(|keyframe| keyframe.update_index == frames.start)
+            .map(|keyframe| &keyframe.state);
+        if let Some(snapshot) = (frames.start == 0).then(|| history.initial_state()).flatten().or(start_keyframe) {
+            trimmed.set_initial_state(snapshot.clone());
         }
+        let end = frames.end.min(history.len());
+        for (index, update) in history.updates().iter().enumerate().take(end).skip(frames.start) {
+            for keyframe in history.keyframes() {
+                if keyframe.update_index == index && index > frames.start {
+                    trimmed.record_keyframe(keyframe.frame, keyframe.state.clone());
+                }
+            }
+            trimmed.record(update.clone());
+        }
+        trimmed
+    }
 
-        Ok(history)
+    /// Changes recorded in a frame of one history but not in the same frame of the other
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct FrameDifference {
+        pub frame: usize,
+        /// Changes and operations only recorded in the first history
+        pub only_in_first: Vec<String>,
+        /// Changes and operations only recorded in the second history
+        pub only_in_second: Vec<String>,
     }
-}
 
-/// Parse entity from string like "Entity(0, 123)"
-fn parse_entity(input: &str) -> Option<Entity> {
-    if input.starts_with("Entity(") && input.ends_with(')') {
-        let content = &input[7..input.len()-1];
-        let parts: Vec<&str> = content.split(", ").collect();
-        if parts.len() == 2 {
-            if let (Ok(world_index), Ok(entity_index)) = (parts[0].parse::<usize>(), parts[1].parse::<usize>()) {
-                return Some(Entity { world_index, entity_index });
+    /// Compare two histories frame by frame, ignoring which system made a change
+    pub fn compare_histories(first: &WorldUpdateHistory, second: &WorldUpdateHistory) -> Vec<FrameDifference> {
+        fn frame_entries(history: &WorldUpdateHistory, frame: usize) -> Vec<String> {
+            let Some(update) = history.updates().get(frame) else {
+                return Vec::new();
+            };
+            update
+                .system_diffs()
+                .iter()
+                .flat_map(|system| {
+                    let changes = system.component_changes().iter().map(|change| change.to_string());
+                    let operations = system.world_operations().iter().map(|operation| operation.to_string());
+                    let failure = system.failure().map(|message| format!("FAILED: {}", message));
+                    let error = system.error().map(|message| format!("ERROR: {}", message));
+                    let assertions = system.failed_assertions().iter().map(|name| format!("ASSERT: {}", name));
+                    changes.chain(operations).chain(failure).chain(error).chain(assertions)
+                })
+                .collect()
+        }
+
+        let mut differences = Vec::new();
+        for frame in 0..first.len().max(second.len()) {
+            let mut only_in_first = frame_entries(first, frame);
+            let mut only_in_second = Vec::new();
+            for entry in frame_entries(second, frame) {
+                match only_in_first.iter().position(|e| *e == entry) {
+                    Some(index) => {
+                        only_in_first.remove(index);
+                    }
+                    None => only_in_second.push(entry),
+                }
+            }
+            if !only_in_first.is_empty() || !only_in_second.is_empty() {
+                differences.push(FrameDifference {
+                    frame,
+                    only_in_first,
+                    only_in_second,
+                });
             }
         }
+        differences
     }
-    None
-}
 
-/// Parse component addition from string like "Entity(0, 123) Position Position { x: 1.0, y: 2.0 }"
-fn parse_component_add(input: &str) -> Option<DiffComponentChange> {
-    let parts: Vec<&str> = input.splitn(3, ' ').collect();
-    if parts.len() >= 3 {
-        if let Some(entity) = parse_entity(parts[0]) {
-            let type_name = parts[1].to_string();
-            let data = if parts.len() > 2 { parts[2].to_string() } else { String::new() };
-            return Some(DiffComponentChange::Added { entity, type_name, data });
-        }
+    /// Problem found in a replay log by `validate_replay_log_str`
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct ValidationIssue {
+        /// 1-based line number in the log
+        pub line: usize,
+        pub message: String,
     }
-    None
-}
 
-/// Parse component modification from string like "Entity(0, 123) Position PositionDiff { x: Some(1.0), y: None }"
-fn parse_component_mod(input: &str) -> Option<DiffComponentChange> {
-    let parts: Vec<&str> = input.splitn(3, ' ').collect();
-    if parts.len() >= 3 {
-        if let Some(entity) = parse_entity(parts[0]) {
-            let type_name = parts[1].to_string();
-            let diff = if parts.len() > 2 { parts[2].to_string() } else { String::new() };
-            return Some(DiffComponentChange::Modified { entity, type_name, diff });
+    impl std::fmt::Display for ValidationIssue {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "line {}: {}", self.line, self.message)
         }
     }
-    None
-}
 
-/// Parse component removal from string like "Entity(0, 123) Position"
-fn parse_component_rem(input: &str) -> Option<DiffComponentChange> {
-    let parts: Vec<&str> = input.splitn(2, ' ').collect();
-    if parts.len() >= 2 {
-        if let Some(entity) = parse_entity(parts[0]) {
-            let type_name = parts[1].to_string();
-            return Some(DiffComponentChange::Removed { entity, type_name });
+    /// Check a replay log for lines the parser would skip, entries outside an
+    /// update or system, out of order update numbers and section counts that
+    /// do not match their entries
+    pub fn validate_replay_log_str(contents: &str) -> Vec<ValidationIssue> {
+        /// Section header whose announced entry count is still being checked
+        struct OpenSection {
+            line: usize,
+            name: &'static str,
+            expected: usize,
+            found: usize,
         }
-    }
-    None
-}
 
-/// Parse Position component data from string like "Position { x: 1, y: 2 }"
-fn parse_position_data(data: &str) -> Result<crate::game::game::Position, String> {
-    // Simple parser for Position { x: value, y: value }
-    if let Some(content) = data.strip_prefix("Position { ").and_then(|s| s.strip_suffix(" }")) {
-        let mut x: Option<i32> = None;
-        let mut y: Option<i32> = None;
-        
-        for part in content.split(", ") {
-            if let Some(value_str) = part.strip_prefix("x: ") {
-                x = Some(value_str.parse().map_err(|e| format!("Failed to parse x: {}", e))?);
-            } else if let Some(value_str) = part.strip_prefix("y: ") {
-                y = Some(value_str.parse().map_err(|e| format!("Failed to parse y: {}", e))?);
+        fn close(section: &mut Option<OpenSection>, issues: &mut Vec<ValidationIssue>) {
+            if let Some(section) = section.take() {
+                if section.found != section.expected {
+                    issues.push(ValidationIssue {
+                        line: section.line,
+                        message: format!(
+                            "{} announces {} entries but has {}",
+                            section.name, section.expected, section.found
+                        ),
+                    });
+                }
             }
         }
-        
-        if let (Some(x), Some(y)) = (x, y) {
-            Ok(crate::game::game::Position { x, y })
-        } else {
-            Err("Missing x or y value in Position data".to_string())
+
+        let mut issues = Vec::new();
+        let mut last_update: Option<usize> = None;
+        let mut in_system = false;
+        let mut in_initial_state = false;
+        // Sampled logs and logs with dropped updates skip update numbers
+        let mut sampled = false;
+        let mut systems: Option<OpenSection> = None;
+        let mut section: Option<OpenSection> = None;
+
+        for (index, line) in contents.lines().enumerate() {
+            let number = index + 1;
+            let line = line.trim();
+            if line.starts_with("# Sampling:") || line.starts_with("# DROPPED_UPDATES:") {
+                sampled = true;
+            }
+            if line.starts_with('#') || line.is_empty() {
+                continue;
+            }
+            let mut messages = Vec::new();
+            let (keyword, rest) = line.split_once(' ').unwrap_or((line, ""));
+
+            'entry: {
+                match keyword {
+                    "INITIAL_STATE" => {
+                        close(&mut section, &mut issues);
+                        if last_update.is_some() {
+                            messages.push("INITIAL_STATE after the first update".to_string());
+                        }
+                        in_system = true;
+                        in_initial_state = true;
+                    }
+                    "KEYFRAME" => {
+                        close(&mut section, &mut issues);
+                        close(&mut systems, &mut issues);
+                        if rest.parse::<usize>().is_err() {
+                            messages.push(format!("invalid keyframe number '{}'", rest));
+                        }
+                        in_system = true;
+                        in_initial_state = true;
+                    }
+                    "UPDATE" => {
+                        close(&mut section, &mut issues);
+                        close(&mut systems, &mut issues);
+                        in_system = false;
+                        in_initial_state = false;
+                        match rest.parse::<usize>() {
+                            Ok(update) => {
+                                if let Some(last) =
+                                    last_update.filter(|last| update != last + 1 && !(sampled && update > *last))
+                                {
+                                    messages.push(format!("UPDATE {} follows UPDATE {}", update, last));
+                                }
+                                last_update = Some(update);
+                            }
+                            Err(_) => messages.push(format!("invalid update number '{}'", rest)),
+                        }
+                    }
+                    "SYSTEMS:" | "COMPONENT_CHANGES:" | "WORLD_OPERATIONS:" | "RESOURCES:" => {
+                        let Ok(expected) = rest.parse::<usize>() else {
+                            messages.push(format!("invalid {} count '{}'", keyword, rest));
+                            break 'entry;
+                        };
+                        let opened = OpenSection {
+                            line: number,
+                            name: match keyword {
+                                "SYSTEMS:" => "SYSTEMS",
+                                "COMPONENT_CHANGES:" => "COMPONENT_CHANGES",
+                                "RESOURCES:" => "RESOURCES",
+                                _ => "WORLD_OPERATIONS",
+                            },
+                            expected,
+                            found: 0,
+                        };
+                        if keyword == "SYSTEMS:" {
+                            if last_update.is_none() {
+                                messages.push("SYSTEMS outside of an update".to_string());
+                            }
+                            systems = Some(opened);
+                        } else {
+                            close(&mut section, &mut issues);
+                            if !in_system {
+                                messages.push(format!("{} outside of a system", opened.name));
+                            }
+                            section = Some(opened);
+                        }
+                    }
+                    "ANNOTATION" => {
+                        close(&mut section, &mut issues);
+                        if last_update.is_none() || in_system {
+                            messages.push("ANNOTATION outside of an update header".to_string());
+                        }
+                        if crate::replay_metadata::parse_annotation(rest).is_none() {
+                            messages.push(format!("malformed ANNOTATION entry '{}'", rest));
+                        }
+                    }
+                    "FAILED:" | "ERROR:" | "ASSERT:" => {
+                        close(&mut section, &mut issues);
+                        if !in_system {
+                            messages.push(format!("{} outside of a system", keyword.trim_end_matches(':')));
+                        }
+                    }
+                    "STATS:" => {
+                        close(&mut section, &mut issues);
+                        if !in_system {
+                            messages.push("STATS outside of a system".to_string());
+                        }
+                        if let Err(e) = rest.parse::<SystemStats>() {
+                            messages.push(format!("malformed STATS entry: {}", e));
+                        }
+                    }
+                    "ORDER:" => {
+                        close(&mut section, &mut issues);
+                        if !in_system {
+                            messages.push("ORDER outside of a system".to_string());
+                        }
+                        if let Some(entry) = rest.split_whitespace().find(|entry| !matches!(*entry, "C" | "O")) {
+                            messages.push(format!("malformed ORDER entry '{}', expected C or O", entry));
+                        }
+                    }
+                    "CAUSE:" => {
+                        close(&mut section, &mut issues);
+                        if !in_system {
+                            messages.push("CAUSE outside of a system".to_string());
+                        }
+                        if let Err(e) = rest.parse::<WriteCause>() {
+                            messages.push(format!("malformed CAUSE entry: {}", e));
+                        }
+                    }
+                    "SYSTEM" => {
+                        close(&mut section, &mut issues);
+                        if last_update.is_none() {
+                            messages.push("SYSTEM outside of an update".to_string());
+                        }
+                        if rest.parse::<usize>().is_err() {
+                            messages.push(format!("invalid system index '{}'", rest));
+                        }
+                        if let Some(systems) = systems.as_mut() {
+                            systems.found += 1;
+                        }
+                        in_system = true;
+                    }
+                    _ => {
+                        let valid = match keyword {
+                            "ADD" => parse_component_add(rest).is_some(),
+                            "MOD" => parse_component_mod(rest).is_some(),
+                            "REM" => parse_component_rem(rest).is_some(),
+                            "CREATE_ENTITY" | "REMOVE_ENTITY" | "DISABLE_ENTITY" | "ENABLE_ENTITY" => parse_entity(rest).is_some(),
+                            "CREATE_WORLD" | "REMOVE_WORLD" => rest.parse::<usize>().is_ok(),
+                            "ADD_SYSTEM" => !rest.is_empty(),
+                            "CLEAR_ENTITIES" | "CLEAR_ALL" | "RESET_HISTORY" => rest.is_empty(),
+                            "ADD_TO_GROUP" | "REMOVE_FROM_GROUP" => parse_group_operation(rest).is_some(),
+                            "RELATE" | "UNRELATE" => parse_relation_operation(rest).is_some(),
+                            "RESOURCE" => {
+                                if !in_initial_state {
+                                    messages.push("RESOURCE outside of the initial state or a keyframe".to_string());
+                                }
+                                rest.split_once(' ').is_some()
+                            }
+                            _ => {
+                                messages.push(format!("unrecognized line '{}'", line));
+                                break 'entry;
+                            }
+                        };
+                        if !valid {
+                            messages.push(format!("malformed {} entry '{}'", keyword, rest));
+                        } else if !in_system {
+                            messages.push(format!("{} entry outside of a system", keyword));
+                        }
+                        if let Some(section) = section.as_mut() {
+                            section.found += 1;
+                        }
+                    }
+                }
+            }
+            issues.extend(messages.into_iter().map(|message| ValidationIssue { line: number, message }));
         }
-    } else {
-        Err(format!("Invalid Position data format: {}", data))
+        close(&mut section, &mut issues);
+        close(&mut systems, &mut issues);
+        issues.sort_by_key(|issue| issue.line);
+        issues
     }
 }
 
-/// Parse Target component data from string like "Target { x: 1, y: 2 }"
-fn parse_target_data(data: &str) -> Result<crate::game::game::Target, String> {
-    if let Some(content) = data.strip_prefix("Target { ").and_then(|s| s.strip_suffix(" }")) {
-        let mut x: Option<i32> = None;
-        let mut y: Option<i32> = None;
-        
-        for part in content.split(", ") {
-            if let Some(value_str) = part.strip_prefix("x: ") {
-                x = Some(value_str.parse().map_err(|e| format!("Failed to parse x: {}", e))?);
-            } else if let Some(value_str) = part.strip_prefix("y: ") {
-                y = Some(value_str.parse().map_err(|e| format!("Failed to parse y: {}", e))?);
+/// Parse entity from string like "Entity(0, 123)"
+fn parse_entity(input: &str) -> Option<Entity> {
+    if input.starts_with("Entity(") && input.ends_with(')') {
+        let content = &input[7..input.len()-1];
+        let parts: Vec<&str> = content.split(", ").collect();
+        if parts.len() == 2 {
+            if let (Ok(world_index), Ok(entity_index)) = (parts[0].parse::<usize>(), parts[1].parse::<usize>()) {
+                return Some(Entity { world_index, entity_index });
             }
         }
-        
-        if let (Some(x), Some(y)) = (x, y) {
-            Ok(crate::game::game::Target { x, y })
-        } else {
-            Err("Missing x or y value in Target data".to_string())
-        }
-    } else {
-        Err(format!("Invalid Target data format: {}", data))
     }
+    None
 }
 
-/// Parse WaitTimer component data from string like "WaitTimer { ticks: 5 }"
-fn parse_wait_timer_data(data: &str) -> Result<crate::game::game::WaitTimer, String> {
-    if let Some(content) = data.strip_prefix("WaitTimer { ").and_then(|s| s.strip_suffix(" }")) {
-        if let Some(value_str) = content.strip_prefix("ticks: ") {
-            let ticks = value_str.parse().map_err(|e| format!("Failed to parse ticks: {}", e))?;
-            Ok(crate::game::game::WaitTimer { ticks })
-        } else {
-            Err("Missing ticks value in WaitTimer data".to_string())
-        }
-    } else {
-        Err(format!("Invalid WaitTimer data format: {}", data))
-    }
+/// Split a leading "Entity(0, 123)" from the rest of the line
+fn split_entity(input: &str) -> Option<(Entity, &str)> {
+    let end = input.find(')')? + 1;
+    let entity = parse_entity(&input[..end])?;
+    Some((entity, input[end..].trim_start()))
 }
 
-/// Parse ActorState component data from string like "MovingToWork"
-fn parse_actor_state_data(data: &str) -> Result<crate::game::game::ActorState, String> {
-    match data {
-        "MovingToWork" => Ok(crate::game::game::ActorState::MovingToWork),
-        "MovingToHome" => Ok(crate::game::game::ActorState::MovingToHome),
-        "WaitingAtWork" => Ok(crate::game::game::ActorState::WaitingAtWork),
-        "WaitingAtHome" => Ok(crate::game::game::ActorState::WaitingAtHome),
-        _ => Err(format!("Unknown ActorState variant: {}", data))
+/// Parse group membership from string like "Entity(0, 123) enemies"
+fn parse_group_operation(input: &str) -> Option<(Entity, String)> {
+    let (entity, group) = split_entity(input)?;
+    if group.is_empty() {
+        return None;
     }
+    Some((entity, group.to_string()))
 }
 
-/// Apply Position diff from string like "PositionDiff { x: Some(1), y: Some(2) }"
-fn apply_position_diff(position: &mut crate::game::game::Position, diff_data: &str) -> Result<(), String> {
-    if let Some(content) = diff_data.strip_prefix("PositionDiff { ").and_then(|s| s.strip_suffix(" }")) {
-        for part in content.split(", ") {
-            if let Some(value_str) = part.strip_prefix("x: Some(").and_then(|s| s.strip_suffix(")")) {
-                position.x = value_str.parse().map_err(|e| format!("Failed to parse x diff: {}", e))?;
-            } else if let Some(value_str) = part.strip_prefix("y: Some(").and_then(|s| s.strip_suffix(")")) {
-                position.y = value_str.parse().map_err(|e| format!("Failed to parse y diff: {}", e))?;
-            }
-            // Ignore None values as they mean no change
-        }
-        Ok(())
-    } else {
-        Err(format!("Invalid PositionDiff format: {}", diff_data))
-    }
+/// Parse a relation from string like "Entity(0, 1) Targets Entity(0, 2)"
+fn parse_relation_operation(input: &str) -> Option<(Entity, String, Entity)> {
+    let (source, rest) = split_entity(input)?;
+    let (relation, target) = rest.split_once(' ')?;
+    Some((source, relation.to_string(), parse_entity(target)?))
 }
 
-/// Apply Target diff from string like "TargetDiff { x: Some(1), y: Some(2) }"
-fn apply_target_diff(target: &mut crate::game::game::Target, diff_data: &str) -> Result<(), String> {
-    if let Some(content) = diff_data.strip_prefix("TargetDiff { ").and_then(|s| s.strip_suffix(" }")) {
-        for part in content.split(", ") {
-            if let Some(value_str) = part.strip_prefix("x: Some(").and_then(|s| s.strip_suffix(")")) {
-                target.x = value_str.parse().map_err(|e| format!("Failed to parse x diff: {}", e))?;
-            } else if let Some(value_str) = part.strip_prefix("y: Some(").and_then(|s| s.strip_suffix(")")) {
-                target.y = value_str.parse().map_err(|e| format!("Failed to parse y diff: {}", e))?;
-            }
+/// Parse a world operation line, e.g. "CREATE_ENTITY Entity(world_id, entity_id)",
+/// "CREATE_WORLD world_id", "ADD_SYSTEM system_type_name",
+/// "ADD_TO_GROUP Entity(world_id, entity_id) group",
+/// "RELATE Entity(world_id, entity_id) relation Entity(world_id, entity_id)" or "CLEAR_ALL"
+fn parse_world_operation(line: &str) -> Result<WorldOperation, String> {
+    let (keyword, rest) = line.split_once(' ').unwrap_or((line, ""));
+    let malformed = || format!("malformed {} entry '{}'", keyword, rest);
+    let entity = || parse_entity(rest).ok_or_else(malformed);
+    let world = || rest.parse::<usize>().map_err(|_| malformed());
+    let group = || parse_group_operation(rest).ok_or_else(malformed);
+    let relation = || parse_relation_operation(rest).ok_or_else(malformed);
+    Ok(match keyword {
+        "CREATE_ENTITY" => WorldOperation::CreateEntity(entity()?),
+        "REMOVE_ENTITY" => WorldOperation::RemoveEntity(entity()?),
+        "DISABLE_ENTITY" => WorldOperation::DisableEntity(entity()?),
+        "ENABLE_ENTITY" => WorldOperation::EnableEntity(entity()?),
+        "CREATE_WORLD" => WorldOperation::CreateWorld(world()?),
+        "REMOVE_WORLD" => WorldOperation::RemoveWorld(world()?),
+        "ADD_SYSTEM" if !rest.is_empty() => WorldOperation::AddSystem(rest.to_string()),
+        "ADD_TO_GROUP" => {
+            let (entity, group) = group()?;
+            WorldOperation::AddToGroup(entity, group)
         }
-        Ok(())
-    } else {
-        Err(format!("Invalid TargetDiff format: {}", diff_data))
-    }
+        "REMOVE_FROM_GROUP" => {
+            let (entity, group) = group()?;
+            WorldOperation::RemoveFromGroup(entity, group)
+        }
+        "RELATE" => {
+            let (source, relation, target) = relation()?;
+            WorldOperation::Relate(source, relation, target)
+        }
+        "UNRELATE" => {
+            let (source, relation, target) = relation()?;
+            WorldOperation::Unrelate(source, relation, target)
+        }
+        _ => parse_clear_operation(line).ok_or_else(|| format!("unrecognized line '{}'", line))?,
+    })
 }
 
-/// Apply WaitTimer diff from string like "WaitTimerDiff { ticks: Some(5) }"
-fn apply_wait_timer_diff(timer: &mut crate::game::game::WaitTimer, diff_data: &str) -> Result<(), String> {
-    if let Some(content) = diff_data.strip_prefix("WaitTimerDiff { ").and_then(|s| s.strip_suffix(" }")) {
-        if let Some(value_str) = content.strip_prefix("ticks: Some(").and_then(|s| s.strip_suffix(")")) {
-            timer.ticks = value_str.parse().map_err(|e| format!("Failed to parse ticks diff: {}", e))?;
-        }
-        Ok(())
-    } else {
-        Err(format!("Invalid WaitTimerDiff format: {}", diff_data))
+/// Parse a world reset, which has no arguments
+fn parse_clear_operation(line: &str) -> Option<WorldOperation> {
+    match line {
+        "CLEAR_ENTITIES" => Some(WorldOperation::ClearEntities),
+        "CLEAR_ALL" => Some(WorldOperation::ClearAll),
+        "RESET_HISTORY" => Some(WorldOperation::ResetHistory),
+        _ => None,
     }
 }
 
-/// Apply ActorState diff from string like "MovingToWork"
-fn apply_actor_state_diff(state: &mut crate::game::game::ActorState, diff_data: &str) -> Result<(), String> {
-    *state = parse_actor_state_data(diff_data)?;
-    Ok(())
+/// Parse component addition from string like "Entity(0, 123) Position Position { x: 1.0, y: 2.0 }"
+fn parse_component_add(input: &str) -> Option<DiffComponentChange> {
+    let (entity, rest) = split_entity(input)?;
+    let (type_name, data) = rest.split_once(' ')?;
+    Some(DiffComponentChange::Added {
+        entity,
+        type_name: type_name.into(),
+        data: data.into(),
+    })
+}
+
+/// Parse component modification from string like "Entity(0, 123) Position PositionDiff { x: Some(1.0), y: None }"
+fn parse_component_mod(input: &str) -> Option<DiffComponentChange> {
+    let (entity, rest) = split_entity(input)?;
+    let (type_name, diff) = rest.split_once(' ')?;
+    Some(DiffComponentChange::Modified {
+        entity,
+        type_name: type_name.into(),
+        diff: diff.into(),
+    })
+}
+
+/// Parse component removal from string like "Entity(0, 123) Position"
+fn parse_component_rem(input: &str) -> Option<DiffComponentChange> {
+    let (entity, type_name) = split_entity(input)?;
+    if type_name.is_empty() {
+        return None;
+    }
+    Some(DiffComponentChange::Removed {
+        entity,
+        type_name: type_name.into(),
+    })
 }
 
 // Game module - declared after ReplayLogConfig
+#[cfg(feature = "game")]
 pub mod game;
+pub mod access_profile;
+pub mod activation;
+pub mod causality;
+pub mod change_detection;
+pub mod clear;
+pub mod commands;
+pub mod component;
+#[cfg(feature = "serde")]
+pub mod component_history;
+#[cfg(feature = "config-watch")]
+pub mod config;
+pub mod dynamic;
+pub mod entity_view;
+pub mod fast_forward;
+pub mod fork;
+pub mod frame_budget;
+pub mod groups;
+pub mod inspect;
+pub mod intern;
+pub mod interpolation;
+pub mod invariants;
+pub mod lifetime;
+pub mod log_recovery;
+pub mod log_writer;
+pub mod membership;
+pub mod narrative;
+#[cfg(feature = "net")]
+pub mod net;
+pub mod observers;
+pub mod pause;
+pub mod plugin;
+pub mod pool;
+pub mod prefab;
+#[cfg(feature = "read-handle")]
+pub mod read_handle;
+pub mod read_only_system;
+pub mod read_query;
+pub mod recording;
+pub mod reflect;
+pub mod relations;
+pub mod remapping;
+pub mod replay_comparison;
+pub mod replay_metadata;
+pub mod replay_player;
+pub mod replay_validation;
+pub mod requirements;
+mod resources;
+#[cfg(feature = "ron")]
+pub mod scene;
+#[cfg(feature = "scripting")]
+pub mod scripting;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod shutdown;
+pub mod slicing;
+pub mod soa;
+pub mod snapshot;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+mod storage;
+pub mod strict_access;
+pub mod system_graph;
+pub mod system_groups;
+pub mod system_registry;
+pub mod system_stats;
+pub mod tasks;
+#[cfg(test)]
+mod test_util;
+pub mod testing;
+pub mod time_travel;
+pub mod transform;
+pub mod versioning;
+pub mod world_runner;
+pub mod world_stats;
+
+pub use access_profile::{AccessProfile, ComponentAccessCount};
+pub use activation::IncludeDisabled;
+pub use causality::WriteCause;
+pub use change_detection::{ChangeTick, Changed};
+pub use commands::CommandBuffer;
+pub use component::{Component, ComponentInfo};
+#[cfg(feature = "config-watch")]
+pub use config::{Config, ConfigWatcher};
+pub use dynamic::{ComponentSchema, DynComponent, FieldType};
+pub use entity_view::{EntityView, EntityViewMut};
+pub use fast_forward::UpdateProgress;
+pub use frame_budget::{SlowFrameRecord, SystemTiming};
+pub use groups::EntityGroup;
+pub use inspect::ComponentRef;
+pub use intern::{HistoryMemoryStats, InternedStr};
+#[cfg(feature = "serde")]
+pub use intern::CompactHistory;
+pub use interpolation::{FixedTimestep, Interpolate, Previous};
+pub use invariants::InvariantViolation;
+pub use lifetime::{Lifetime, LifetimeSystem};
+pub use log_writer::{BackgroundWriting, Backpressure, LogDurability};
+pub use narrative::{Name, Narrator};
+pub use observers::ObserverId;
+pub use plugin::Plugin;
+pub use pool::{EntityPool, PooledEntity};
+pub use prefab::Prefab;
+#[cfg(feature = "read-handle")]
+pub use read_handle::{ComponentReader, WorldReadHandle};
+pub use read_only_system::{ReadOnlySystem, ReadOnlyWorldView};
+pub use read_query::{ReadOnlyQuery, ReadOnlyQueryComponent};
+pub use recording::{FrameSampling, RecordFilter, RecordingPolicy};
+pub use reflect::{ComponentRegistry, Migration, Reflect, Value};
+pub use remapping::EntityRemapper;
+pub use replay_comparison::ReplayComparison;
+pub use replay_metadata::{ReplayAnnotation, ReplayMetadata};
+pub use replay_player::ReplayPlayer;
+pub use requirements::ComponentRequirement;
+pub use snapshot::{Keyframe, SnapshotDifference, WorldSnapshot};
+pub use soa::{ColumnBatch, SoaComponent};
+pub use storage::{StorageKind, With, Without};
+pub use strict_access::ComponentAccess;
+use strict_access::DeclaredAccess;
+pub use system_graph::SystemAccessReport;
+pub use system_registry::SystemRegistry;
+pub use system_stats::SystemStats;
+pub use tasks::{AsyncTask, TaskCompletionSystem, TaskPool};
+pub use time_travel::{EntityFilter, WatchHit, WatchId};
+pub use transform::{GlobalPosition, LocalPosition, Parent, TransformPropagationSystem};
+pub use world_runner::{WorldInbox, WorldOutbox, WorldRunner};
+pub use world_stats::WorldStats;
@@ -0,0 +1,152 @@
+//! Entities that live for a number of frames.
+//!
+//! Events, effects and projectiles often exist for a few frames only. Give
+//! them a `Lifetime` and add the `LifetimeSystem`, which counts the frames
+//! down and despawns each entity when its lifetime reaches zero:
+//!
+//! ```ignore
+//! let spark = world.create_entity();
+//! world.add_component(spark, Lifetime { frames: 3 });
+//! world.add_system(LifetimeSystem);
+//! ```
+//!
+//! An entity with `frames: 1` is despawned at the end of the next update. The
+//! removal goes through `CommandBuffer::despawn_deferred`, so the systems added
+//! after the `LifetimeSystem` still see the entity in its last frame, and the
+//! countdown and removal are recorded like any other system change.
+
+use crate::{Diff, Entity, Out, System, WorldView};
+
+/// Number of updates left before the `LifetimeSystem` despawns the entity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Diff)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Lifetime {
+    pub frames: u32,
+}
+
+/// Counts every `Lifetime` down once per update and despawns the entities
+/// whose lifetime reaches zero when the frame ends
+pub struct LifetimeSystem;
+
+impl System for LifetimeSystem {
+    type InComponents = ();
+    type OutComponents = (Lifetime,);
+
+    fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+    fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+        let mut changes = Vec::new();
+        let mut expired: Vec<Entity> = Vec::new();
+        for (entity, lifetime) in world.query_components::<(Out<Lifetime>,)>() {
+            if lifetime.frames > 0 {
+                let old = *lifetime;
+                lifetime.frames -= 1;
+                changes.push((entity, old, *lifetime));
+            }
+            if lifetime.frames == 0 {
+                expired.push(entity);
+            }
+        }
+        for (entity, old, new) in changes {
+            world.record_component_modification(entity, &old, &new);
+        }
+        for entity in expired {
+            world.commands().despawn_deferred(entity);
+        }
+    }
+
+    fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{In, World, WorldOperation};
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Debug, Clone, Copy, PartialEq, Diff)]
+    struct Spark;
+
+    /// Counts the sparks it sees in each update
+    struct SparkCounterSystem(Arc<Mutex<Vec<usize>>>);
+
+    impl System for SparkCounterSystem {
+        type InComponents = (Spark,);
+        type OutComponents = ();
+
+        fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+        fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+            let sparks = world.query_components::<(In<Spark>,)>().len();
+            self.0.lock().unwrap().push(sparks);
+        }
+
+        fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    }
+
+    #[test]
+    fn test_entities_despawn_when_their_lifetime_ends() {
+        let mut world = World::new();
+        let short = world.create_entity();
+        world.add_component(short, Spark);
+        world.add_component(short, Lifetime { frames: 1 });
+        let long = world.create_entity();
+        world.add_component(long, Spark);
+        world.add_component(long, Lifetime { frames: 2 });
+        let counts = Arc::new(Mutex::new(Vec::new()));
+        world.add_system(LifetimeSystem);
+        world.add_system(SparkCounterSystem(counts.clone()));
+        let mut replayed = World::new();
+        replayed.create_entity();
+        replayed.create_entity();
+
+        world.update();
+        // The expired spark was still seen by the later system
+        assert!(!world.entity_exists(short));
+        assert_eq!(world.get_component::<Lifetime>(long), Some(&Lifetime { frames: 1 }));
+        let update = world.get_update_history().updates().last().unwrap();
+        assert!(update.system_diffs()[0].world_operations().is_empty());
+        assert_eq!(update.system_diffs()[1].world_operations(), &[WorldOperation::RemoveEntity(short)]);
+        replayed.apply_update_diff(update);
+        assert!(!replayed.entity_exists(short) && replayed.entity_exists(long));
+
+        world.update();
+        world.update();
+        assert_eq!(world.entity_count(), 0);
+        assert_eq!(*counts.lock().unwrap(), vec![2, 1, 0]);
+    }
+
+    #[test]
+    fn test_deferred_despawns_wait_for_the_frame_end() {
+        struct ExpireSystem(Entity);
+
+        impl System for ExpireSystem {
+            type InComponents = ();
+            type OutComponents = ();
+
+            fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+            fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+                world.commands().despawn_deferred(self.0);
+                world.commands().despawn_deferred(self.0);
+            }
+
+            fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+        }
+
+        let mut world = World::new();
+        let spark = world.create_entity();
+        world.add_component(spark, Spark);
+        let counts = Arc::new(Mutex::new(Vec::new()));
+        world.add_system(ExpireSystem(spark));
+        world.add_system(SparkCounterSystem(counts.clone()));
+        world.update();
+
+        assert!(!world.entity_exists(spark));
+        assert_eq!(*counts.lock().unwrap(), vec![1]);
+        let update = world.get_update_history().updates().last().unwrap();
+        assert!(update.system_diffs()[0].world_operations().is_empty());
+        // Queued twice, removed once
+        assert_eq!(update.system_diffs()[1].world_operations(), &[WorldOperation::RemoveEntity(spark)]);
+    }
+}
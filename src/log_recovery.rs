@@ -0,0 +1,144 @@
+//! Reading replay logs of sessions that crashed.
+//!
+//! A log is finalized with an `# End of replay log` line. Without it the
+//! session ended abruptly, and the last section (an update, keyframe or the
+//! initial state) may have been written only in part. Every section of a log
+//! ends with an empty line, so the recovery keeps the log up to the last one
+//! and leaves out the partial section after it:
+//!
+//! ```ignore
+//! let recovered = log_recovery::recover_replay_log("game_logs/simulation_game_1700000000.log")?;
+//! println!("{}", recovered);
+//! let player = ReplayPlayer::new(recovered.history, new_replay_world)?;
+//! ```
+//!
+//! How much is left to recover depends on `ReplayLogConfig::durability`: with
+//! the default buffering, everything since the last flush is lost with the
+//! process.
+
+use crate::replay_analysis::{parse_replay_log_with_errors, ParseError};
+use crate::WorldUpdateHistory;
+
+/// Start of the line `AutoReplayLogger::finalize` ends a log with
+const END_MARKER: &str = "# End of replay log";
+
+/// Updates salvaged from a replay log, returned by `recover_replay_log_str`
+#[derive(Debug, Clone)]
+pub struct RecoveredLog {
+    pub history: WorldUpdateHistory,
+    /// Whether the log was finalized, so nothing had to be recovered
+    pub complete: bool,
+    /// Lines of the partially written last section that were left out
+    pub discarded_lines: usize,
+    /// Malformed lines of the kept part, see `parse_replay_log_with_errors`
+    pub errors: Vec<ParseError>,
+}
+
+impl std::fmt::Display for RecoveredLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.complete {
+            write!(f, "Log is complete with {} updates", self.history.len())?;
+        } else {
+            write!(
+                f,
+                "Recovered {} updates from an unfinished log, discarding {} lines of a partially written section",
+                self.history.len(),
+                self.discarded_lines
+            )?;
+        }
+        if !self.errors.is_empty() {
+            write!(f, " ({} malformed lines skipped)", self.errors.len())?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse a replay log that may have been cut short by a crash
+pub fn recover_replay_log_str(contents: &str) -> RecoveredLog {
+    let complete = contents.lines().any(|line| line.starts_with(END_MARKER));
+    let kept = if complete || contents.ends_with("\n\n") {
+        contents
+    } else {
+        contents.rfind("\n\n").map_or("", |end| &contents[..end + 2])
+    };
+    let (history, errors) = parse_replay_log_with_errors(kept);
+    let discarded_lines = contents[kept.len()..].lines().count();
+    if discarded_lines > 0 {
+        ecs_warn!("Discarded {} lines of a partially written replay log section", discarded_lines);
+    }
+    RecoveredLog {
+        history,
+        complete,
+        discarded_lines,
+        errors,
+    }
+}
+
+/// Read a replay log file that may have been cut short by a crash
+#[cfg(feature = "fs")]
+pub fn recover_replay_log(file_path: &str) -> Result<RecoveredLog, std::io::Error> {
+    let contents = std::fs::read(file_path)?;
+    // A crash can cut a multi-byte character in half
+    Ok(recover_replay_log_str(&String::from_utf8_lossy(&contents)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::SharedBuffer;
+    use crate::{AutoReplayLogger, Entity, LogDurability, ReplayLogConfig, SystemUpdateDiff, WorldOperation, WorldUpdateDiff};
+    use std::io::BufWriter;
+
+    fn update(entity_index: usize) -> WorldUpdateDiff {
+        let mut system_diff = SystemUpdateDiff::new();
+        system_diff.record_world_operation(WorldOperation::CreateEntity(Entity::new(0, entity_index)));
+        let mut update = WorldUpdateDiff::new();
+        update.record(system_diff);
+        update
+    }
+
+    /// Log updates through a buffered sink, then lose the logger like a crashed process would
+    fn crashed_log(durability: LogDurability, updates: usize) -> String {
+        let buffer = SharedBuffer::default();
+        let mut logger = AutoReplayLogger::new(ReplayLogConfig {
+            enabled: true,
+            durability,
+            ..ReplayLogConfig::default()
+        });
+        logger.initialize_with_sink(BufWriter::new(buffer.clone())).unwrap();
+        for index in 0..updates {
+            logger.log_update(&update(index)).unwrap();
+        }
+        std::mem::forget(logger);
+        buffer.contents()
+    }
+
+    #[test]
+    fn test_frames_flushed_every_frame_survive_a_crash() {
+        assert!(crashed_log(LogDurability::Buffered, 3).is_empty());
+
+        let log = crashed_log(LogDurability::FlushEveryFrame, 3);
+        let recovered = recover_replay_log_str(&log);
+        assert!(!recovered.complete);
+        assert_eq!(recovered.history.len(), 3);
+        assert_eq!(recovered.discarded_lines, 0);
+    }
+
+    #[test]
+    fn test_truncated_last_update_is_discarded() {
+        let log = crashed_log(LogDurability::FlushEveryFrame, 3);
+        // Cut the last update in the middle of its entity
+        let cut = log.rfind("Entity(0, 2)").unwrap() + 8;
+        let recovered = recover_replay_log_str(&log[..cut]);
+        assert_eq!(recovered.history.len(), 2);
+        assert_eq!(recovered.discarded_lines, 5);
+        assert!(recovered.errors.is_empty(), "{:?}", recovered.errors);
+        assert!(recovered.to_string().starts_with("Recovered 2 updates"), "{}", recovered);
+
+        let mut finished = log.clone();
+        finished.push_str("# End of replay log - Total updates: 3\n");
+        let recovered = recover_replay_log_str(&finished);
+        assert!(recovered.complete);
+        assert_eq!(recovered.history.len(), 3);
+    }
+}
@@ -0,0 +1,322 @@
+//! Writing replay logs on a background thread.
+//!
+//! Formatting and writing a frame's changes takes time on the game thread,
+//! and flushing a buffered log file stalls the frame that triggers it. With
+//! `ReplayLogConfig::background` set, the logger sends each update to a
+//! writer thread over a bounded channel instead. When the writer falls behind
+//! and the queue is full, the game thread either waits for it or drops the
+//! update, as configured:
+//!
+//! ```ignore
+//! let config = ReplayLogConfig {
+//!     enabled: true,
+//!     background: Some(BackgroundWriting {
+//!         queue_capacity: 1024,
+//!         backpressure: Backpressure::DropUpdates,
+//!     }),
+//!     ..ReplayLogConfig::default()
+//! };
+//! ```
+//!
+//! Dropped updates are noted in the log as `# DROPPED_UPDATES: <count>` before
+//! the next written update. `AutoReplayLogger::finalize` (and dropping the
+//! logger) waits for the thread to write the queued entries and flush.
+//!
+//! Buffered entries are lost if the process crashes. `ReplayLogConfig::durability`
+//! trades speed for keeping them: `FlushEveryFrame` hands every update to the
+//! operating system as it is logged, and `SyncEveryFrame` also waits for it to
+//! reach the disk. Logs cut short by a crash are read with
+//! `log_recovery::recover_replay_log_str`.
+
+use crate::snapshot::WorldSnapshot;
+use crate::{recording, snapshot, CountingWriter, ReplayLogConfig, WorldUpdateDiff};
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+/// What the game thread does when the queue of the writer thread is full
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Backpressure {
+    /// Wait until the writer thread takes the next entry; no update is lost
+    #[default]
+    Block,
+    /// Leave the update out of the log. Keyframes and other entries still wait.
+    DropUpdates,
+}
+
+/// Settings of the thread writing a replay log
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BackgroundWriting {
+    /// Number of entries that can wait for the writer thread
+    pub queue_capacity: usize,
+    pub backpressure: Backpressure,
+}
+
+impl Default for BackgroundWriting {
+    fn default() -> Self {
+        Self {
+            queue_capacity: 256,
+            backpressure: Backpressure::Block,
+        }
+    }
+}
+
+/// How soon logged updates are written out of the log file's buffer
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogDurability {
+    /// Flush every `ReplayLogConfig::flush_interval` updates
+    #[default]
+    Buffered,
+    /// Flush after every update, so a crash of the process loses no complete frame
+    FlushEveryFrame,
+    /// Flush and sync the file to disk after every update, so a crash of the
+    /// whole machine loses no complete frame
+    SyncEveryFrame,
+}
+
+impl LogDurability {
+    /// Check if every logged update is flushed
+    pub fn flushes_every_frame(self) -> bool {
+        self != LogDurability::Buffered
+    }
+}
+
+/// Log file synced to disk whenever it is flushed
+#[cfg(feature = "fs")]
+pub(crate) struct SyncedFile(pub(crate) std::fs::File);
+
+#[cfg(feature = "fs")]
+impl Write for SyncedFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.flush()?;
+        self.0.sync_data()
+    }
+}
+
+/// Work sent to the writer thread
+pub(crate) enum LogEntry {
+    /// An update with the names of the systems that made it, filtered and
+    /// formatted on the writer thread
+    Update {
+        number: usize,
+        update: WorldUpdateDiff,
+        system_names: Vec<String>,
+    },
+    /// An `INITIAL_STATE` or `KEYFRAME` section
+    Snapshot { header: String, snapshot: Box<WorldSnapshot> },
+    /// Text formatted by the logger, like the footer
+    Bytes(Vec<u8>),
+    Flush,
+}
+
+/// Handle of a writer thread
+pub(crate) struct BackgroundWriter {
+    sender: Option<SyncSender<LogEntry>>,
+    thread: Option<JoinHandle<std::io::Result<()>>>,
+    backpressure: Backpressure,
+    bytes_written: Arc<AtomicU64>,
+    /// Updates dropped since the writer thread last noted them in the log
+    dropped: Arc<AtomicUsize>,
+    total_dropped: usize,
+}
+
+impl BackgroundWriter {
+    /// Start a thread writing the entries to `writer`
+    pub(crate) fn spawn<W: Write + Send + 'static>(
+        mut writer: CountingWriter<W>,
+        config: ReplayLogConfig,
+        settings: BackgroundWriting,
+    ) -> std::io::Result<Self> {
+        let (sender, receiver) = mpsc::sync_channel::<LogEntry>(settings.queue_capacity);
+        let bytes_written = Arc::new(AtomicU64::new(writer.bytes_written));
+        let dropped = Arc::new(AtomicUsize::new(0));
+
+        let thread_bytes_written = bytes_written.clone();
+        let thread_dropped = dropped.clone();
+        let thread = std::thread::Builder::new()
+            .name("replay-log-writer".to_string())
+            .spawn(move || {
+                for entry in receiver {
+                    let dropped = thread_dropped.swap(0, Ordering::Relaxed);
+                    if dropped > 0 {
+                        writeln!(writer, "# DROPPED_UPDATES: {}", dropped)?;
+                    }
+                    write_entry(&mut writer, entry, &config)?;
+                    thread_bytes_written.store(writer.bytes_written, Ordering::Relaxed);
+                }
+                writer.flush()
+            })?;
+
+        Ok(Self {
+            sender: Some(sender),
+            thread: Some(thread),
+            backpressure: settings.backpressure,
+            bytes_written,
+            dropped,
+            total_dropped: 0,
+        })
+    }
+
+    /// Queue an entry, waiting or dropping updates when the queue is full
+    pub(crate) fn send(&mut self, entry: LogEntry) -> std::io::Result<()> {
+        let Some(sender) = self.sender.as_ref() else {
+            return Err(writer_stopped());
+        };
+        let result = match (self.backpressure, entry) {
+            (Backpressure::DropUpdates, entry @ LogEntry::Update { .. }) => match sender.try_send(entry) {
+                Err(TrySendError::Full(_)) => {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                    self.total_dropped += 1;
+                    Ok(())
+                }
+                Err(TrySendError::Disconnected(_)) => Err(()),
+                Ok(()) => Ok(()),
+            },
+            (_, entry) => sender.send(entry).map_err(|_| ()),
+        };
+        match result {
+            Ok(()) => Ok(()),
+            // The thread stopped after a write error, which `finish` returns
+            Err(()) => self.finish().and(Err(writer_stopped())),
+        }
+    }
+
+    /// Get the number of bytes the thread has written so far
+    pub(crate) fn bytes_written(&self) -> u64 {
+        self.bytes_written.load(Ordering::Relaxed)
+    }
+
+    /// Get the number of updates dropped because the queue was full
+    pub(crate) fn dropped_updates(&self) -> usize {
+        self.total_dropped
+    }
+
+    /// Wait for the thread to write the queued entries and flush
+    pub(crate) fn finish(&mut self) -> std::io::Result<()> {
+        self.sender = None;
+        match self.thread.take() {
+            Some(thread) => thread
+                .join()
+                .unwrap_or_else(|_| Err(std::io::Error::other("replay log writer thread panicked"))),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for BackgroundWriter {
+    fn drop(&mut self) {
+        if let Err(e) = self.finish() {
+            ecs_error!("Failed to finish writing replay log: {}", e);
+        }
+    }
+}
+
+fn writer_stopped() -> std::io::Error {
+    std::io::Error::other("replay log writer thread stopped")
+}
+
+fn write_entry<W: Write>(writer: &mut W, entry: LogEntry, config: &ReplayLogConfig) -> std::io::Result<()> {
+    match entry {
+        LogEntry::Update {
+            number,
+            update,
+            system_names,
+        } => {
+            let system_names: Vec<&str> = system_names.iter().map(String::as_str).collect();
+            crate::write_logged_update(writer, number, &update, &system_names, config)
+        }
+        LogEntry::Snapshot { header, snapshot } => {
+            let snapshot = recording::filter_snapshot(&snapshot, &config.components);
+            snapshot::write_snapshot_entry(writer, &header, &snapshot)
+        }
+        LogEntry::Bytes(bytes) => writer.write_all(&bytes),
+        LogEntry::Flush => writer.flush(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay_analysis::{parse_replay_log_str, validate_replay_log_str};
+    use crate::test_util::SharedBuffer;
+    use crate::{AutoReplayLogger, SystemUpdateDiff, WorldOperation};
+
+    /// Writer that takes a while, so the queue fills up
+    struct SlowWriter(SharedBuffer);
+
+    impl Write for SlowWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            std::thread::sleep(std::time::Duration::from_millis(1));
+            self.0.write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn update(entity_index: usize) -> WorldUpdateDiff {
+        let mut system_diff = SystemUpdateDiff::new();
+        system_diff.record_world_operation(WorldOperation::CreateEntity(crate::Entity::new(0, entity_index)));
+        let mut update = WorldUpdateDiff::new();
+        update.record(system_diff);
+        update
+    }
+
+    fn config(backpressure: Backpressure) -> ReplayLogConfig {
+        ReplayLogConfig {
+            enabled: true,
+            background: Some(BackgroundWriting {
+                queue_capacity: 2,
+                backpressure,
+            }),
+            ..ReplayLogConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_background_log_matches_direct_log() {
+        let background = SharedBuffer::default();
+        let mut logger = AutoReplayLogger::new(config(Backpressure::Block));
+        logger.initialize_with_send_sink(SlowWriter(background.clone())).unwrap();
+        for index in 0..20 {
+            logger.log_update(&update(index)).unwrap();
+        }
+        logger.finalize().unwrap();
+        let log = background.contents();
+        assert_eq!(logger.bytes_written() as usize, log.len());
+
+        let history = parse_replay_log_str(&log);
+        assert_eq!(history.len(), 20);
+        assert!(log.ends_with("# End of replay log - Total updates: 20\n"));
+        assert!(validate_replay_log_str(&log).is_empty());
+    }
+
+    #[test]
+    fn test_dropped_updates_are_noted() {
+        let buffer = SharedBuffer::default();
+        let mut logger = AutoReplayLogger::new(config(Backpressure::DropUpdates));
+        logger.initialize_with_send_sink(SlowWriter(buffer.clone())).unwrap();
+        for index in 0..50 {
+            logger.log_update(&update(index)).unwrap();
+        }
+        logger.finalize().unwrap();
+        let log = buffer.contents();
+
+        let history = parse_replay_log_str(&log);
+        assert!(history.len() < 50, "no update was dropped");
+        let dropped: usize = log
+            .lines()
+            .filter_map(|line| line.strip_prefix("# DROPPED_UPDATES: "))
+            .map(|count| count.parse::<usize>().unwrap())
+            .sum();
+        assert_eq!(history.len() + dropped, 50);
+        assert_eq!(logger.dropped_updates(), dropped);
+        assert!(validate_replay_log_str(&log).is_empty(), "{:?}", validate_replay_log_str(&log));
+    }
+}
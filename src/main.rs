@@ -1,7 +1,7 @@
 use rust_ecs::{Diff, DiffComponent, In, Out, System, World, WorldView};
 use std::env;
 
-mod game;
+use rust_ecs::game;
 
 // Example components with Diff implementation using derive macro
 #[derive(Debug, Diff)]
@@ -104,9 +104,12 @@ fn main() {
     // Check if "game" argument is provided with optional replay file
     if args.len() > 1 && args[1] == "game" {
         if args.len() > 2 {
-            // Replay mode: cargo run game <replay_log_path>
+            // Replay mode: cargo run game <replay_log_path> [--speed 4x] [--paused] [--frame <n>] [--verbose]
             let replay_path = &args[2];
-            game::run_game_replay(replay_path);
+            match game::ReplayOptions::parse(&args[3..]) {
+                Ok(options) => game::run_game_replay(replay_path, options),
+                Err(e) => eprintln!("{}", e),
+            }
         } else {
             // Normal game mode: cargo run game
             game::run_game();
@@ -114,12 +117,95 @@ fn main() {
         return;
     }
 
+    // Run a registered demo by name, or list the demos: cargo run demo [name]
+    if args.len() > 1 && args[1] == "demo" {
+        let demos = game::DemoRegistry::builtin();
+        match args.get(2) {
+            Some(name) => {
+                if let Err(e) = demos.run(name) {
+                    eprintln!("{}", e);
+                }
+            }
+            None => print!("Available demos, run one with cargo run demo <name>:\n{}", demos.list()),
+        }
+        return;
+    }
+
+    // Run the village demo of woodcutters, carpenters and farmers: cargo run jobs
+    if args.len() > 1 && args[1] == "jobs" {
+        game::jobs::run_jobs_game();
+        return;
+    }
+
+    // Run the construction demo of builders putting up huts: cargo run construction
+    if args.len() > 1 && args[1] == "construction" {
+        game::construction::run_construction_game();
+        return;
+    }
+
+    // Run the demo of villagers with needs growing in number: cargo run needs
+    if args.len() > 1 && args[1] == "needs" {
+        game::needs::run_needs_game();
+        return;
+    }
+
+    // Run the demo of two teams fighting: cargo run combat
+    if args.len() > 1 && args[1] == "combat" {
+        game::combat::run_combat_game();
+        return;
+    }
+
+    // Draw the frames of a replay log without running the game: cargo run render <replay_log_path> [frame]
+    if args.len() > 2 && args[1] == "render" {
+        let frame = match args.get(3).map(|frame| frame.parse::<usize>()) {
+            Some(Ok(frame)) => Some(frame),
+            Some(Err(_)) => {
+                eprintln!("Invalid frame '{}'", args[3]);
+                return;
+            }
+            None => None,
+        };
+        if let Err(e) = game::render_game_replay(&args[2], frame) {
+            eprintln!("Failed to render replay: {}", e);
+        }
+        return;
+    }
+
+    // Play two replay logs side by side and print their state differences: cargo run compare <first_log> <second_log>
+    if args.len() > 3 && args[1] == "compare" {
+        if let Err(e) = game::compare_game_replays(&args[2], &args[3]) {
+            eprintln!("Failed to compare replays: {}", e);
+        }
+        return;
+    }
+
     // Check if "replay-demo" argument is provided
     if args.len() > 1 && args[1] == "replay-demo" {
         demo_replay_analysis();
         return;
     }
 
+    // Run the game headless with remote control: cargo run --features server server [address]
+    if args.len() > 1 && args[1] == "server" {
+        #[cfg(feature = "server")]
+        game::run_game_server(args.get(2).map_or("127.0.0.1:7878", String::as_str));
+        #[cfg(not(feature = "server"))]
+        eprintln!("The server command requires the `server` feature: cargo run --features server server");
+        return;
+    }
+
+    // Print the game's system dependency graph: cargo run system-graph | dot -Tsvg > systems.svg
+    if args.len() > 1 && args[1] == "system-graph" {
+        print!("{}", game::initialize_game().system_graph_dot());
+        return;
+    }
+
+    // Print who reads and writes each component and suggested stages: cargo run system-access
+    if args.len() > 1 && args[1] == "system-access" {
+        game::initialize_game().print_system_access();
+        return;
+    }
+
     // Default behavior - run the ECS framework demo
     run_ecs_demo();
 }
@@ -204,18 +290,7 @@ fn run_ecs_demo() {
 
     // Demonstrate additional world functionality
     println!("\n--- Additional World Features ---");
-    println!(
-        "Entities with Position: {:?}",
-        world.entities_with_component::<Position>()
-    );
-    println!(
-        "Entities with Velocity: {:?}",
-        world.entities_with_component::<Velocity>()
-    );
-    println!(
-        "Entities with Health: {:?}",
-        world.entities_with_component::<Health>()
-    );
+    print!("{}", world.stats());
 
     // Demonstrate diff functionality and derive macro
     demo_diff_functionality();
@@ -355,6 +430,7 @@ fn demo_replay_analysis() {
         file_prefix: "demo_session".to_string(),
         flush_interval: 5,
         include_component_details: true,
+        ..rust_ecs::ReplayLogConfig::default()
     };
     
     match world.enable_replay_logging(replay_config) {
@@ -392,6 +468,9 @@ fn demo_replay_analysis() {
     let anomalous = rust_ecs::replay_analysis::find_anomalous_frames(history, 2.0);
     if !anomalous.is_empty() {
         println!("Anomalous frames (2x average activity): {:?}", anomalous);
+        for frame in anomalous {
+            rust_ecs::replay_analysis::print_frame_explanation(history, frame);
+        }
     }
     
     // Clean up
@@ -0,0 +1,243 @@
+//! Events for entities entering or leaving a combination of components.
+//!
+//! An entity "becomes an actor" the moment it has both a `Position` and a
+//! `Target`, whichever was added last. Systems can run one-time setup for
+//! such entities without checking every entity each frame:
+//!
+//! ```ignore
+//! for entity in world_view.entered::<(Position, Target)>() {
+//!     // First update since the entity got both components
+//! }
+//! for entity in world_view.left::<(Position, Target)>() {
+//!     // Lost one of them or was removed
+//! }
+//! ```
+//!
+//! Like `Changed<T>`, the results are the entities that entered or left since
+//! the calling system last ran. A combination is tracked from the first time
+//! it is asked for, or from `World::track_membership`, with the entities
+//! already having it counting as entered then. Entities that left are
+//! remembered until the end of the next frame, so systems skipped for longer
+//! miss them.
+
+use crate::{ChangeTick, ComponentSet, Entity, World, WorldView};
+use std::any::TypeId;
+use std::collections::BTreeMap;
+
+/// Entities having every component of one combination
+#[derive(Clone)]
+struct Membership {
+    type_ids: Vec<TypeId>,
+    /// Members with the tick they entered at
+    members: BTreeMap<Entity, ChangeTick>,
+    /// Former members with the tick they left at
+    left: BTreeMap<Entity, ChangeTick>,
+}
+
+/// The tracked component combinations of a world
+#[derive(Clone, Default)]
+pub(crate) struct Memberships {
+    sets: Vec<Membership>,
+    /// Start of the previous frame, exits before it are forgotten
+    previous_frame: ChangeTick,
+}
+
+impl Memberships {
+    fn index_of(&self, type_ids: &[TypeId]) -> Option<usize> {
+        self.sets.iter().position(|set| set.type_ids == type_ids)
+    }
+}
+
+/// Sorted type ids identifying a combination regardless of tuple order
+fn combination<S: ComponentSet>() -> Vec<TypeId> {
+    let mut type_ids = S::type_ids();
+    type_ids.sort();
+    type_ids.dedup();
+    type_ids
+}
+
+impl World {
+    /// Track the entities having every component of S, see the module documentation
+    pub fn track_membership<S: ComponentSet>(&mut self) {
+        self.membership_index(combination::<S>());
+    }
+
+    /// Get the entities that got every component of S after `tick`, in entity order
+    pub fn entered_since<S: ComponentSet>(&mut self, tick: ChangeTick) -> Vec<Entity> {
+        let index = self.membership_index(combination::<S>());
+        let set = &self.memberships.sets[index];
+        set.members
+            .iter()
+            .filter(|(_, entered)| entered.is_newer_than(tick))
+            .map(|(entity, _)| *entity)
+            .collect()
+    }
+
+    /// Get the entities that lost a component of S or were removed after
+    /// `tick`, and do not have them all again, in entity order
+    pub fn left_since<S: ComponentSet>(&mut self, tick: ChangeTick) -> Vec<Entity> {
+        let index = self.membership_index(combination::<S>());
+        let set = &self.memberships.sets[index];
+        set.left
+            .iter()
+            .filter(|(_, left)| left.is_newer_than(tick))
+            .map(|(entity, _)| *entity)
+            .collect()
+    }
+
+    /// Find a tracked combination, tracking it with its current members if it is new
+    fn membership_index(&mut self, type_ids: Vec<TypeId>) -> usize {
+        if let Some(index) = self.memberships.index_of(&type_ids) {
+            return index;
+        }
+        let members = self
+            .entities
+            .iter()
+            .filter(|entity| type_ids.iter().all(|type_id| self.has_component_type(*type_id, **entity)))
+            .map(|entity| (*entity, self.change_tick))
+            .collect();
+        self.memberships.sets.push(Membership {
+            type_ids,
+            members,
+            left: BTreeMap::new(),
+        });
+        self.memberships.sets.len() - 1
+    }
+
+    /// Update the combinations with a component type after it was added to
+    /// or removed from an entity
+    pub(crate) fn update_memberships(&mut self, type_id: TypeId, entity: Entity) {
+        if self.memberships.sets.is_empty() {
+            return;
+        }
+        let tick = self.change_tick;
+        let mut sets = std::mem::take(&mut self.memberships.sets);
+        for set in sets.iter_mut().filter(|set| set.type_ids.contains(&type_id)) {
+            let member = self.entity_exists(entity)
+                && set.type_ids.iter().all(|type_id| self.has_component_type(*type_id, entity));
+            if member && !set.members.contains_key(&entity) {
+                set.members.insert(entity, tick);
+                set.left.remove(&entity);
+            } else if !member && set.members.remove(&entity).is_some() {
+                set.left.insert(entity, tick);
+            }
+        }
+        self.memberships.sets = sets;
+    }
+
+    /// Update every combination after an entity lost all its components
+    pub(crate) fn leave_memberships(&mut self, entity: Entity) {
+        let tick = self.change_tick;
+        for set in &mut self.memberships.sets {
+            if set.members.remove(&entity).is_some() {
+                set.left.insert(entity, tick);
+            }
+        }
+    }
+
+    /// Forget the exits from before the previous frame, called when a frame starts
+    pub(crate) fn prune_membership_exits(&mut self) {
+        let previous_frame = std::mem::replace(&mut self.memberships.previous_frame, self.change_tick);
+        for set in &mut self.memberships.sets {
+            set.left.retain(|_, left| !previous_frame.is_newer_than(*left));
+        }
+    }
+}
+
+impl<I, O> WorldView<I, O> {
+    /// Get the entities that got every component of S since this system last ran
+    pub fn entered<S: ComponentSet>(&mut self) -> Vec<Entity> {
+        let world = unsafe { self.world_mut() };
+        world.entered_since::<S>(world.last_run_tick)
+    }
+
+    /// Get the entities that lost a component of S or were removed since this
+    /// system last ran
+    pub fn left<S: ComponentSet>(&mut self) -> Vec<Entity> {
+        let world = unsafe { self.world_mut() };
+        world.left_since::<S>(world.last_run_tick)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::System;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Position(i32);
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Target(i32);
+
+    type Seen = Rc<RefCell<Vec<(Vec<Entity>, Vec<Entity>)>>>;
+
+    /// Collects the actors that appeared and disappeared in each update
+    struct ActorSetupSystem(Seen);
+
+    impl System for ActorSetupSystem {
+        type InComponents = (Position, Target);
+        type OutComponents = ();
+
+        fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+        fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+            // The order of the tuple does not matter
+            let entered = world.entered::<(Target, Position)>();
+            let left = world.left::<(Position, Target)>();
+            self.0.borrow_mut().push((entered, left));
+        }
+
+        fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    }
+
+    #[test]
+    fn test_systems_see_entities_entering_and_leaving() {
+        let mut world = World::new();
+        let seen = Seen::default();
+        let existing = world.create_entity();
+        world.add_component(existing, Position(0));
+        world.add_component(existing, Target(1));
+        let actor = world.create_entity();
+        world.add_component(actor, Position(0));
+        world.add_system(ActorSetupSystem(seen.clone()));
+
+        // Entities having the combination when it is first asked for count as entered
+        world.update();
+        world.update();
+        world.add_component(actor, Target(2));
+        world.update();
+        world.remove_component::<Position>(existing);
+        world.remove_entity(actor);
+        world.update();
+        // Entering again after leaving
+        world.add_component(existing, Position(3));
+        world.update();
+
+        let seen = seen.borrow();
+        assert_eq!(seen[0], (vec![existing], vec![]));
+        assert_eq!(seen[1], (vec![], vec![]));
+        assert_eq!(seen[2], (vec![actor], vec![]));
+        assert_eq!(seen[3], (vec![], vec![existing, actor]));
+        assert_eq!(seen[4], (vec![existing], vec![]));
+    }
+
+    #[test]
+    fn test_exits_are_forgotten_after_a_frame() {
+        let mut world = World::new();
+        world.track_membership::<(Position,)>();
+        let start = ChangeTick::default();
+        let entity = world.create_entity();
+        world.add_component(entity, Position(0));
+        world.remove_component::<Position>(entity);
+        assert!(world.entered_since::<(Position,)>(start).is_empty());
+        assert_eq!(world.left_since::<(Position,)>(start), vec![entity]);
+
+        world.update();
+        assert_eq!(world.left_since::<(Position,)>(start), vec![entity]);
+        world.update();
+        assert!(world.left_since::<(Position,)>(start).is_empty());
+    }
+}
@@ -0,0 +1,384 @@
+//! Recorded frames told as sentences.
+//!
+//! A `Narrator` turns the diff of a frame into sentences a person can follow
+//! without knowing the replay log format, using the reflection registry for
+//! component values, `Name` components and entity kinds:
+//!
+//! ```text
+//! MovementSystem moved Actor 'bob' from (2, 3) to (3, 3)
+//! WaitSystem set WaitTimer of Actor 'bob' to 10
+//! SpawnSystem created Actor 'alice'
+//! ```
+//!
+//! ```ignore
+//! let narrator = Narrator::new().with_kind("Actor").with_verb("Position", "moved");
+//! let update = &player.history().updates()[player.frame()];
+//! for sentence in narrator.narrate(player.world(), update) {
+//!     println!("{}", sentence);
+//! }
+//! player.step();
+//! ```
+//!
+//! Frames are narrated against the world as it was before the frame, so
+//! modified components can be described by their old and new values. New
+//! values are only known for types registered with
+//! `World::register_replay_component`; other changes are told with their
+//! recorded diff.
+
+use crate::reflect::Value;
+use crate::{DiffComponentChange, Entity, World, WorldOperation, WorldUpdateDiff};
+use std::collections::HashMap;
+
+/// Name of an entity, used when narrating and inspecting replays
+#[derive(Debug, Clone, PartialEq, Eq, crate::Diff, crate::Reflect)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Name {
+    pub name: String,
+}
+
+impl Name {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self { name: name.into() }
+    }
+}
+
+/// Describes the changes of recorded frames as sentences
+#[derive(Debug, Clone, Default)]
+pub struct Narrator {
+    /// Marker component types describing what an entity is, e.g. "Actor"
+    kinds: Vec<String>,
+    /// Verbs for modifications by component type, e.g. "moved" for "Position"
+    verbs: HashMap<String, String>,
+}
+
+/// Names and kinds of entities, including those given in the narrated frame
+struct EntityLabels<'a> {
+    world: &'a World,
+    names: HashMap<Entity, String>,
+    kinds: HashMap<Entity, &'a str>,
+}
+
+impl Narrator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Describe entities with the component `type_name`, usually a marker
+    /// like "Actor", by that type. The first matching kind is used.
+    pub fn with_kind(mut self, type_name: &str) -> Self {
+        self.kinds.push(type_name.to_string());
+        self
+    }
+
+    /// Describe modifications of `type_name` with `verb`, e.g. "moved" for
+    /// "Position" turns "set Position of Actor 'bob' to (3, 3)" into
+    /// "moved Actor 'bob' from (2, 3) to (3, 3)"
+    pub fn with_verb(mut self, type_name: &str, verb: &str) -> Self {
+        self.verbs.insert(type_name.to_string(), verb.to_string());
+        self
+    }
+
+    /// Describe an entity by its kind and name, e.g. "Actor 'bob'"
+    pub fn describe_entity(&self, world: &World, entity: Entity) -> String {
+        self.labels(world, None).describe(entity)
+    }
+
+    /// Describe the changes of `update`, system by system, given the world
+    /// as it was before the update
+    pub fn narrate(&self, world: &World, update: &WorldUpdateDiff) -> Vec<String> {
+        let labels = self.labels(world, Some(update));
+        let mut sentences = Vec::new();
+        for (system_index, system_diff) in update.system_diffs().iter().enumerate() {
+            let system = system_name(world, system_index);
+            for operation in system_diff.world_operations() {
+                sentences.push(format!("{} {}", system, describe_operation(&labels, operation)));
+            }
+            for change in system_diff.component_changes() {
+                sentences.push(format!("{} {}", system, self.describe_change(&labels, change)));
+            }
+            if let Some(failure) = system_diff.failure() {
+                sentences.push(format!("{} failed: {}", system, failure));
+            }
+            if let Some(error) = system_diff.error() {
+                sentences.push(format!("{} reported an error: {}", system, error));
+            }
+        }
+        sentences
+    }
+
+    /// Collect the names and kinds of the world's entities and of those given
+    /// a name or kind in `update`
+    fn labels<'a>(&'a self, world: &'a World, update: Option<&WorldUpdateDiff>) -> EntityLabels<'a> {
+        let mut labels = EntityLabels {
+            world,
+            names: HashMap::new(),
+            kinds: HashMap::new(),
+        };
+        for entity in world.entities_with_component::<Name>() {
+            if let Some(name) = world.get_component::<Name>(entity) {
+                labels.names.insert(entity, name.name.clone());
+            }
+        }
+        for kind in self.kinds.iter().rev() {
+            let Some(registration) = world.component_registry().get(kind) else {
+                continue;
+            };
+            for (entity, _) in world.components.get(&registration.type_id()).into_iter().flatten() {
+                labels.kinds.insert(entity, kind);
+            }
+        }
+
+        let added = update
+            .into_iter()
+            .flat_map(|update| update.system_diffs())
+            .flat_map(|system_diff| system_diff.component_changes());
+        for change in added {
+            let DiffComponentChange::Added { entity, type_name, data } = change else {
+                continue;
+            };
+            if &**type_name == "Name" {
+                if let Some(Value::Map(fields)) = decode_value(data) {
+                    if let Some(Value::String(name)) = fields.get("name") {
+                        labels.names.insert(*entity, name.clone());
+                    }
+                }
+            }
+            if let Some(kind) = self.kinds.iter().find(|kind| kind.as_str() == &**type_name) {
+                labels.kinds.entry(*entity).or_insert(kind);
+            }
+        }
+        labels
+    }
+
+    fn describe_change(&self, labels: &EntityLabels, change: &DiffComponentChange) -> String {
+        match change {
+            DiffComponentChange::Added { entity, type_name, data } => {
+                let value = decode_value(data).map_or_else(|| data.to_string(), |value| format_value(&value));
+                if value.is_empty() {
+                    format!("added {} to {}", type_name, labels.describe(*entity))
+                } else {
+                    format!("added {} {} to {}", type_name, value, labels.describe(*entity))
+                }
+            }
+            DiffComponentChange::Modified { entity, type_name, diff } => {
+                let old = labels.world.reflect_component(*entity, type_name);
+                let new = old.as_ref().and_then(|old| applied_value(old, diff));
+                match (old, new, self.verbs.get(&**type_name)) {
+                    (Some(old), Some(new), Some(verb)) => format!(
+                        "{} {} from {} to {}",
+                        verb,
+                        labels.describe(*entity),
+                        format_value(&old),
+                        format_value(&new)
+                    ),
+                    (_, Some(new), None) => format!(
+                        "set {} of {} to {}",
+                        type_name,
+                        labels.describe(*entity),
+                        format_value(&new)
+                    ),
+                    _ => format!("changed {} of {}: {}", type_name, labels.describe(*entity), diff),
+                }
+            }
+            DiffComponentChange::Removed { entity, type_name } => {
+                format!("removed {} from {}", type_name, labels.describe(*entity))
+            }
+        }
+    }
+}
+
+impl EntityLabels<'_> {
+    fn describe(&self, entity: Entity) -> String {
+        match (self.kinds.get(&entity), self.names.get(&entity)) {
+            (Some(kind), Some(name)) => format!("{} '{}'", kind, name),
+            (None, Some(name)) => format!("'{}'", name),
+            (Some(kind), None) => format!("{} {}", kind, entity),
+            (None, None) => entity.to_string(),
+        }
+    }
+}
+
+fn describe_operation(labels: &EntityLabels, operation: &WorldOperation) -> String {
+    match operation {
+        WorldOperation::CreateEntity(entity) => format!("created {}", labels.describe(*entity)),
+        WorldOperation::RemoveEntity(entity) => format!("removed {}", labels.describe(*entity)),
+        WorldOperation::AddSystem(name) => format!("added system {}", short_system_name(name)),
+        WorldOperation::AddToGroup(entity, group) => {
+            format!("added {} to group '{}'", labels.describe(*entity), group)
+        }
+        WorldOperation::RemoveFromGroup(entity, group) => {
+            format!("removed {} from group '{}'", labels.describe(*entity), group)
+        }
+        WorldOperation::Relate(source, relation, target) => {
+            format!("related {} with {} to {}", labels.describe(*source), relation, labels.describe(*target))
+        }
+        WorldOperation::Unrelate(source, relation, target) => {
+            format!("unrelated {} with {} from {}", labels.describe(*source), relation, labels.describe(*target))
+        }
+        WorldOperation::DisableEntity(entity) => format!("disabled {}", labels.describe(*entity)),
+        WorldOperation::EnableEntity(entity) => format!("enabled {}", labels.describe(*entity)),
+        WorldOperation::ClearEntities => "removed all entities".to_string(),
+        WorldOperation::ClearAll => "cleared the world".to_string(),
+        WorldOperation::ResetHistory => "reset the recorded history".to_string(),
+        WorldOperation::CreateWorld(index) => format!("created world {}", index),
+        WorldOperation::RemoveWorld(index) => format!("removed world {}", index),
+    }
+}
+
+/// Short name of the system at `system_index`, e.g. "MovementSystem"
+fn system_name(world: &World, system_index: usize) -> String {
+    match world.systems.get(system_index) {
+        Some(system) => short_system_name(system.name()).to_string(),
+        None => format!("System {}", system_index),
+    }
+}
+
+/// Last path segment of a system type name, without generic arguments
+fn short_system_name(name: &str) -> &str {
+    let name = name.split('<').next().unwrap_or(name);
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+/// Format a value for a sentence: single fields by their value, structs of
+/// plain values as tuples like "(2, 3)" and markers as nothing
+fn format_value(value: &Value) -> String {
+    match value {
+        Value::Unit => String::new(),
+        Value::Map(fields) if fields.len() == 1 => format_value(fields.values().next().expect("one field")),
+        Value::Map(fields) if fields.values().all(|field| !matches!(field, Value::Map(_) | Value::List(_))) => {
+            let fields: Vec<String> = fields.values().map(|field| field.to_string()).collect();
+            format!("({})", fields.join(", "))
+        }
+        value => value.to_string(),
+    }
+}
+
+/// Decode recorded component data into a value
+#[cfg(feature = "serde")]
+fn decode_value(data: &str) -> Option<Value> {
+    ron::from_str::<ron::Value>(data).ok().map(crate::prefab::ron_to_value)
+}
+
+#[cfg(not(feature = "serde"))]
+fn decode_value(_data: &str) -> Option<Value> {
+    None
+}
+
+/// Apply a recorded diff to a component value
+#[cfg(feature = "serde")]
+fn applied_value(old: &Value, diff: &str) -> Option<Value> {
+    let diff = ron::from_str::<ron::Value>(diff).ok()?;
+    let mut new = old.clone();
+    crate::versioning::apply_recorded_diff(&mut new, diff);
+    Some(new)
+}
+
+#[cfg(not(feature = "serde"))]
+fn applied_value(_old: &Value, _diff: &str) -> Option<Value> {
+    None
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+    use crate::{Diff, Out, Reflect, ReplayPlayer, System, WorldView};
+
+    #[derive(Debug, Clone, PartialEq, Diff, Reflect, serde::Serialize, serde::Deserialize)]
+    struct Position {
+        x: i32,
+        y: i32,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Diff, Reflect, serde::Serialize, serde::Deserialize)]
+    struct WaitTimer {
+        remaining: i32,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Diff, Reflect, serde::Serialize, serde::Deserialize)]
+    struct Actor;
+
+    /// Spawns bob in the first update and moves him right afterwards
+    struct MovementSystem;
+
+    impl System for MovementSystem {
+        type InComponents = ();
+        type OutComponents = (Position, Name, Actor, WaitTimer);
+
+        fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+        fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+            let actors: Vec<Entity> = world
+                .query_components::<(Out<Position>,)>()
+                .into_iter()
+                .map(|(entity, _)| entity)
+                .collect();
+            if actors.is_empty() {
+                let bob = world.create_entity();
+                let (name, position) = (Name::new("bob"), Position { x: 2, y: 3 });
+                world.record_component_addition(bob, &name);
+                world.record_component_addition(bob, &Actor);
+                world.record_component_addition(bob, &position);
+                world.add_component(bob, name);
+                world.add_component(bob, Actor);
+                world.add_component(bob, position);
+            }
+            for actor in actors {
+                let old = world.get_component::<Position>(actor).unwrap().clone();
+                let new = Position { x: old.x + 1, ..old.clone() };
+                world.record_component_modification(actor, &old, &new);
+                *world.get_component_mut::<Position>(actor).unwrap() = new;
+                let timer = WaitTimer { remaining: 10 };
+                world.record_component_addition(actor, &timer);
+                world.add_component(actor, timer);
+            }
+        }
+
+        fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    }
+
+    fn new_world() -> World {
+        let mut world = World::new();
+        world.register_replay_component::<Position>();
+        world.register_replay_component::<WaitTimer>();
+        world.register_replay_component::<Actor>();
+        world.register_replay_component::<Name>();
+        world.register_system_factory(|| MovementSystem);
+        world
+    }
+
+    #[test]
+    fn test_narrate_recorded_frames() {
+        let mut world = new_world();
+        world.add_system(MovementSystem);
+        world.update();
+        world.update();
+
+        let narrator = Narrator::new().with_kind("Actor").with_verb("Position", "moved");
+        let mut player = ReplayPlayer::new(world.get_update_history().clone(), new_world).unwrap();
+        let mut frames = Vec::new();
+        while let Some(update) = player.history().updates().get(player.frame()).cloned() {
+            frames.push(narrator.narrate(player.world(), &update));
+            player.step();
+        }
+
+        assert_eq!(frames[0], vec!["System 0 added system MovementSystem"]);
+        assert_eq!(
+            frames[1],
+            vec![
+                "MovementSystem added Name \"bob\" to Actor 'bob'",
+                "MovementSystem added Actor to Actor 'bob'",
+                "MovementSystem added Position (2, 3) to Actor 'bob'",
+            ]
+        );
+        assert_eq!(
+            frames[2],
+            vec![
+                "MovementSystem moved Actor 'bob' from (2, 3) to (3, 3)",
+                "MovementSystem added WaitTimer 10 to Actor 'bob'",
+            ]
+        );
+
+        let bob = player.world().entities_with_component::<Name>()[0];
+        assert_eq!(Narrator::new().describe_entity(player.world(), bob), "'bob'");
+    }
+}
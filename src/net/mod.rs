@@ -0,0 +1,601 @@
+//! Network synchronization of world updates.
+//!
+//! A `DiffSender` streams the `WorldUpdateDiff` of every frame to a
+//! `DiffReceiver`, which applies it to a mirror world. Replication starts with a
+//! snapshot of the sender's entities and registered components, and every
+//! message carries a sequence number so receivers can detect lost or reordered
+//! messages on unreliable transports.
+//!
+//! Messages are serialized as JSON and can optionally be deflate-compressed.
+//! `TcpTransport` frames messages with a length prefix, `UdpTransport` sends one
+//! message per datagram. A `ReplicationConfig` limits what each connection
+//! receives.
+
+mod replication;
+
+pub use replication::{EntityLocator, Region, ReplicationConfig};
+
+use crate::{DiffComponentChange, Entity, SystemUpdateDiff, World, WorldOperation, WorldUpdateDiff};
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::any::{Any, TypeId};
+use std::collections::HashSet;
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs, UdpSocket};
+
+/// Header byte of an uncompressed message
+const FORMAT_JSON: u8 = 0;
+/// Header byte of a deflate-compressed message
+const FORMAT_JSON_DEFLATE: u8 = 1;
+/// Largest payload that fits into a single UDP datagram
+const MAX_DATAGRAM_SIZE: usize = 65_507;
+/// Largest message accepted from a peer, after decompression
+const MAX_MESSAGE_SIZE: usize = 64 * 1024 * 1024;
+
+/// Error for a message larger than `MAX_MESSAGE_SIZE`
+fn message_too_large(kind: io::ErrorKind, length: usize) -> io::Error {
+    io::Error::new(
+        kind,
+        format!("message of {} bytes exceeds the limit of {} bytes", length, MAX_MESSAGE_SIZE),
+    )
+}
+
+/// Moves encoded messages between a sender and a receiver
+pub trait Transport {
+    /// Send one complete message
+    fn send(&mut self, message: &[u8]) -> io::Result<()>;
+
+    /// Block until the next complete message arrives
+    fn receive(&mut self) -> io::Result<Vec<u8>>;
+}
+
+/// Reliable transport sending length-prefixed messages over a TCP stream
+pub struct TcpTransport {
+    stream: TcpStream,
+}
+
+impl TcpTransport {
+    /// Wrap an already connected stream
+    pub fn new(stream: TcpStream) -> Self {
+        Self { stream }
+    }
+
+    /// Connect to a listening receiver or sender
+    pub fn connect<A: ToSocketAddrs>(address: A) -> io::Result<Self> {
+        Ok(Self::new(TcpStream::connect(address)?))
+    }
+}
+
+impl Transport for TcpTransport {
+    fn send(&mut self, message: &[u8]) -> io::Result<()> {
+        if message.len() > MAX_MESSAGE_SIZE {
+            return Err(message_too_large(io::ErrorKind::InvalidInput, message.len()));
+        }
+        let length = message.len() as u32;
+        self.stream.write_all(&length.to_be_bytes())?;
+        self.stream.write_all(message)?;
+        self.stream.flush()
+    }
+
+    fn receive(&mut self) -> io::Result<Vec<u8>> {
+        let mut length = [0u8; 4];
+        self.stream.read_exact(&mut length)?;
+        let length = u32::from_be_bytes(length) as usize;
+        if length > MAX_MESSAGE_SIZE {
+            return Err(message_too_large(io::ErrorKind::InvalidData, length));
+        }
+        let mut message = vec![0u8; length];
+        self.stream.read_exact(&mut message)?;
+        Ok(message)
+    }
+}
+
+/// Unreliable transport sending one message per UDP datagram.
+///
+/// Datagrams may be lost or reordered; the receiver reports this through
+/// `ReceiveOutcome::Gap` and `ReceiveOutcome::Stale`.
+pub struct UdpTransport {
+    socket: UdpSocket,
+}
+
+impl UdpTransport {
+    /// Wrap a socket that has been connected to its peer
+    pub fn new(socket: UdpSocket) -> Self {
+        Self { socket }
+    }
+
+    /// Bind to `local` and connect to `peer`
+    pub fn connect<A: ToSocketAddrs, B: ToSocketAddrs>(local: A, peer: B) -> io::Result<Self> {
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(peer)?;
+        Ok(Self::new(socket))
+    }
+}
+
+impl Transport for UdpTransport {
+    fn send(&mut self, message: &[u8]) -> io::Result<()> {
+        if message.len() > MAX_DATAGRAM_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("message of {} bytes does not fit into a datagram", message.len()),
+            ));
+        }
+        self.socket.send(message)?;
+        Ok(())
+    }
+
+    fn receive(&mut self) -> io::Result<Vec<u8>> {
+        let mut buffer = vec![0u8; MAX_DATAGRAM_SIZE];
+        let length = self.socket.recv(&mut buffer)?;
+        buffer.truncate(length);
+        Ok(buffer)
+    }
+}
+
+/// A message exchanged between `DiffSender` and `DiffReceiver`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SyncMessage {
+    /// Full state of the sender: entity creations followed by component additions
+    Snapshot { sequence: u64, diff: WorldUpdateDiff },
+    /// Changes of one frame
+    Update { sequence: u64, diff: WorldUpdateDiff },
+}
+
+impl SyncMessage {
+    /// Get the sequence number of the message
+    pub fn sequence(&self) -> u64 {
+        match self {
+            SyncMessage::Snapshot { sequence, .. } | SyncMessage::Update { sequence, .. } => *sequence,
+        }
+    }
+
+    /// Serialize the message, optionally compressing it
+    pub fn encode(&self, compress: bool) -> io::Result<Vec<u8>> {
+        let json = serde_json::to_vec(self)?;
+        if !compress {
+            let mut message = Vec::with_capacity(json.len() + 1);
+            message.push(FORMAT_JSON);
+            message.extend_from_slice(&json);
+            return Ok(message);
+        }
+
+        let mut encoder = DeflateEncoder::new(vec![FORMAT_JSON_DEFLATE], Compression::default());
+        encoder.write_all(&json)?;
+        encoder.finish()
+    }
+
+    /// Deserialize a message produced by `encode`
+    pub fn decode(message: &[u8]) -> io::Result<Self> {
+        match message.split_first() {
+            Some((&FORMAT_JSON, json)) => Ok(serde_json::from_slice(json)?),
+            Some((&FORMAT_JSON_DEFLATE, compressed)) => {
+                let mut json = Vec::new();
+                // One byte more than allowed tells a too large message apart
+                DeflateDecoder::new(compressed)
+                    .take(MAX_MESSAGE_SIZE as u64 + 1)
+                    .read_to_end(&mut json)?;
+                if json.len() > MAX_MESSAGE_SIZE {
+                    return Err(message_too_large(io::ErrorKind::InvalidData, json.len()));
+                }
+                Ok(serde_json::from_slice(&json)?)
+            }
+            Some((format, _)) => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unknown message format {}", format),
+            )),
+            None => Err(io::Error::new(io::ErrorKind::InvalidData, "empty message")),
+        }
+    }
+}
+
+/// Component type included in snapshots
+pub(crate) struct SnapshotComponent {
+    type_id: TypeId,
+    type_name: &'static str,
+    describe: fn(&dyn Any) -> Option<String>,
+}
+
+impl SnapshotComponent {
+    /// Describe components of type C by their Debug form
+    pub(crate) fn of<C: std::fmt::Debug + 'static>() -> Self {
+        Self {
+            type_id: TypeId::of::<C>(),
+            type_name: crate::short_type_name::<C>(),
+            describe: describe_component::<C>,
+        }
+    }
+}
+
+fn describe_component<T: std::fmt::Debug + 'static>(component: &dyn Any) -> Option<String> {
+    component.downcast_ref::<T>().map(|component| format!("{:?}", component))
+}
+
+/// Add snapshot components to `components` unless a component of the same type is already there
+pub(crate) fn register_snapshot_component<C: std::fmt::Debug + 'static>(
+    components: &mut Vec<SnapshotComponent>,
+) {
+    let type_id = TypeId::of::<C>();
+    if !components.iter().any(|component| component.type_id == type_id) {
+        components.push(SnapshotComponent::of::<C>());
+    }
+}
+
+/// Entity creations followed by the components of `entities` whose type is
+/// accepted by `include`
+pub(crate) fn describe_entities(
+    world: &World,
+    components: &[SnapshotComponent],
+    entities: &[Entity],
+    include: impl Fn(&str) -> bool,
+) -> SystemUpdateDiff {
+    let mut system_diff = SystemUpdateDiff::new();
+    for entity in entities {
+        system_diff.record_world_operation(WorldOperation::CreateEntity(*entity));
+    }
+    for registered in components {
+        if !include(registered.type_name) {
+            continue;
+        }
+        let Some(stored) = world.components.get(&registered.type_id) else {
+            continue;
+        };
+        for (entity, component) in stored {
+            if !entities.contains(&entity) {
+                continue;
+            }
+            // Replayable types are sent in the form the mirror decodes
+            let data = world
+                .component_registry
+                .get_by_type_id(registered.type_id)
+                .and_then(|registration| registration.encode(component))
+                .or_else(|| (registered.describe)(component));
+            if let Some(data) = data {
+                system_diff.record_component_change(DiffComponentChange::Added {
+                    entity,
+                    type_name: registered.type_name.into(),
+                    data: data.into(),
+                });
+            }
+        }
+    }
+    system_diff
+}
+
+/// Sends world updates to a mirror world
+pub struct DiffSender<T: Transport> {
+    transport: T,
+    next_sequence: u64,
+    compression: bool,
+    components: Vec<SnapshotComponent>,
+    config: ReplicationConfig,
+    /// Entities the receiver currently knows about, when the config selects a subset
+    visible: HashSet<Entity>,
+    /// Number of history entries already sent by `send_new_updates`
+    sent_history_len: usize,
+}
+
+impl<T: Transport> DiffSender<T> {
+    /// Create a sender using the given transport
+    pub fn new(transport: T) -> Self {
+        Self {
+            transport,
+            next_sequence: 0,
+            compression: false,
+            components: Vec::new(),
+            config: ReplicationConfig::new(),
+            visible: HashSet::new(),
+            sent_history_len: 0,
+        }
+    }
+
+    /// Enable or disable deflate compression of outgoing messages
+    pub fn set_compression(&mut self, enabled: bool) {
+        self.compression = enabled;
+    }
+
+    /// Select the components and entities replicated over this connection.
+    /// Takes effect with the next snapshot.
+    pub fn set_replication_config(&mut self, config: ReplicationConfig) {
+        self.config = config;
+    }
+
+    /// Get the replication config of this connection
+    pub fn replication_config(&self) -> &ReplicationConfig {
+        &self.config
+    }
+
+    /// Include components of type C in snapshots.
+    ///
+    /// Components are sent in the same representation used by replay logs:
+    /// encoded if the type is registered with `register_replay_component`,
+    /// otherwise in their Debug form.
+    pub fn register_component<C: std::fmt::Debug + 'static>(&mut self) {
+        register_snapshot_component::<C>(&mut self.components);
+    }
+
+    /// Send the full state of `world` and continue with updates recorded after it.
+    /// Returns the sequence number of the snapshot.
+    pub fn send_snapshot(&mut self, world: &World) -> io::Result<u64> {
+        let entities: Vec<Entity> = world
+            .entities
+            .iter()
+            .copied()
+            .filter(|entity| self.config.is_entity_relevant(world, *entity))
+            .collect();
+        let mut diff = WorldUpdateDiff::new();
+        diff.record(self.describe_entities(world, &entities));
+
+        let sequence = self.next_sequence();
+        self.transport
+            .send(&SyncMessage::Snapshot { sequence, diff }.encode(self.compression)?)?;
+        self.visible = entities.into_iter().collect();
+        self.sent_history_len = world.get_update_history().len();
+        Ok(sequence)
+    }
+
+    /// Send the changes of one frame, filtered by the component types and
+    /// entities of the replication config. Returns the sequence number of the update.
+    pub fn send_update(&mut self, diff: &WorldUpdateDiff) -> io::Result<u64> {
+        let diff = self
+            .config
+            .filter_diff(diff, |entity| self.config.entity_filter().matches(entity));
+        self.send_message(diff)
+    }
+
+    /// Send every update recorded in the world's history since the last snapshot
+    /// or call. Returns the number of messages sent.
+    ///
+    /// When the replication config selects a subset of entities, updates only
+    /// contain changes of entities the receiver already knows. Entities that
+    /// became relevant are then sent in full and entities that stopped being
+    /// relevant are removed, in one additional message.
+    pub fn send_new_updates(&mut self, world: &World) -> io::Result<usize> {
+        let updates = world.get_update_history().updates();
+        let new_updates = updates.get(self.sent_history_len..).unwrap_or_default();
+        self.sent_history_len = updates.len();
+
+        let mut sent = 0;
+        for diff in new_updates {
+            let diff = if self.config.tracks_visibility() {
+                self.config
+                    .filter_diff(diff, |entity| self.visible.contains(&entity))
+            } else {
+                self.config.filter_diff(diff, |_| true)
+            };
+            self.send_message(diff)?;
+            sent += 1;
+        }
+
+        if self.config.tracks_visibility() {
+            if let Some(diff) = self.visibility_changes(world) {
+                self.send_message(diff)?;
+                sent += 1;
+            }
+        }
+        Ok(sent)
+    }
+
+    /// Diff creating newly relevant entities and removing no longer relevant ones
+    fn visibility_changes(&mut self, world: &World) -> Option<WorldUpdateDiff> {
+        let relevant: HashSet<Entity> = world
+            .entities
+            .iter()
+            .copied()
+            .filter(|entity| self.config.is_entity_relevant(world, *entity))
+            .collect();
+
+        let mut entered: Vec<Entity> = relevant.difference(&self.visible).copied().collect();
+        let mut left: Vec<Entity> = self.visible.difference(&relevant).copied().collect();
+        if entered.is_empty() && left.is_empty() {
+            return None;
+        }
+        entered.sort_by_key(|entity| entity.entity_index);
+        left.sort_by_key(|entity| entity.entity_index);
+
+        let mut system_diff = self.describe_entities(world, &entered);
+        for entity in left {
+            system_diff.record_world_operation(WorldOperation::RemoveEntity(entity));
+        }
+        self.visible = relevant;
+
+        let mut diff = WorldUpdateDiff::new();
+        diff.record(system_diff);
+        Some(diff)
+    }
+
+    /// Entity creations and registered components of the given entities
+    fn describe_entities(&self, world: &World, entities: &[Entity]) -> SystemUpdateDiff {
+        describe_entities(world, &self.components, entities, |type_name| {
+            self.config.replicates_component(type_name)
+        })
+    }
+
+    fn send_message(&mut self, diff: WorldUpdateDiff) -> io::Result<u64> {
+        let sequence = self.next_sequence();
+        let message = SyncMessage::Update { sequence, diff };
+        self.transport.send(&message.encode(self.compression)?)?;
+        Ok(sequence)
+    }
+
+    fn next_sequence(&mut self) -> u64 {
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+        sequence
+    }
+}
+
+/// Result of receiving one message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReceiveOutcome {
+    /// A snapshot was applied; updates are expected from `sequence + 1`
+    Snapshot { sequence: u64 },
+    /// An update was applied
+    Applied { sequence: u64 },
+    /// An update arrived before any snapshot and was ignored
+    AwaitingSnapshot { sequence: u64 },
+    /// An update older than the expected one (duplicate or reordered) was ignored
+    Stale { sequence: u64 },
+    /// Updates were lost. The update was ignored and the receiver waits for a
+    /// new snapshot before applying further updates.
+    Gap { expected: u64, received: u64 },
+}
+
+/// Receives world updates and applies them to a mirror world
+pub struct DiffReceiver<T: Transport> {
+    transport: T,
+    /// Sequence number of the next update to apply, None until a snapshot arrives
+    expected_sequence: Option<u64>,
+}
+
+impl<T: Transport> DiffReceiver<T> {
+    /// Create a receiver using the given transport
+    pub fn new(transport: T) -> Self {
+        Self {
+            transport,
+            expected_sequence: None,
+        }
+    }
+
+    /// Check if a snapshot has been applied and updates are being accepted
+    pub fn is_synchronized(&self) -> bool {
+        self.expected_sequence.is_some()
+    }
+
+    /// Block until the next message arrives and apply it to `mirror`
+    pub fn receive(&mut self, mirror: &mut World) -> io::Result<ReceiveOutcome> {
+        let message = SyncMessage::decode(&self.transport.receive()?)?;
+        Ok(self.apply(message, mirror))
+    }
+
+    /// Apply an already received message to `mirror`
+    pub fn apply(&mut self, message: SyncMessage, mirror: &mut World) -> ReceiveOutcome {
+        match message {
+            SyncMessage::Snapshot { sequence, diff } => {
+                mirror.apply_update_diff(&diff);
+                self.expected_sequence = Some(sequence + 1);
+                ReceiveOutcome::Snapshot { sequence }
+            }
+            SyncMessage::Update { sequence, diff } => match self.expected_sequence {
+                None => ReceiveOutcome::AwaitingSnapshot { sequence },
+                Some(expected) if sequence < expected => ReceiveOutcome::Stale { sequence },
+                Some(expected) if sequence > expected => {
+                    self.expected_sequence = None;
+                    ReceiveOutcome::Gap {
+                        expected,
+                        received: sequence,
+                    }
+                }
+                Some(_) => {
+                    mirror.apply_update_diff(&diff);
+                    mirror.world_update_history.record(diff);
+                    self.expected_sequence = Some(sequence + 1);
+                    ReceiveOutcome::Applied { sequence }
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_diff() -> WorldUpdateDiff {
+        let mut system_diff = SystemUpdateDiff::new();
+        system_diff.record_world_operation(WorldOperation::CreateEntity(Entity::new(0, 3)));
+        system_diff.record_component_change(DiffComponentChange::Modified {
+            entity: Entity::new(0, 3),
+            type_name: "Position".into(),
+            diff: "PositionDiff { x: Some(2), y: None }".into(),
+        });
+        let mut diff = WorldUpdateDiff::new();
+        diff.record(system_diff);
+        diff
+    }
+
+    #[test]
+    fn test_message_round_trip() {
+        for compress in [false, true] {
+            let message = SyncMessage::Update {
+                sequence: 7,
+                diff: sample_diff(),
+            };
+            let decoded = SyncMessage::decode(&message.encode(compress).unwrap()).unwrap();
+            assert_eq!(decoded.sequence(), 7);
+            let SyncMessage::Update { diff, .. } = decoded else {
+                panic!("expected an update");
+            };
+            let system_diff = &diff.system_diffs()[0];
+            assert_eq!(system_diff.world_operations().len(), 1);
+            assert_eq!(
+                system_diff.component_changes()[0].to_string(),
+                "MOD Entity(0, 3) Position PositionDiff { x: Some(2), y: None }"
+            );
+        }
+    }
+
+    #[test]
+    fn test_decode_rejects_unknown_format() {
+        assert!(SyncMessage::decode(&[]).is_err());
+        assert!(SyncMessage::decode(&[9, b'{', b'}']).is_err());
+    }
+
+    #[test]
+    fn test_oversized_messages_are_rejected() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut peer = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let mut transport = TcpTransport::new(listener.accept().unwrap().0);
+        peer.write_all(&u32::MAX.to_be_bytes()).unwrap();
+        assert_eq!(transport.receive().unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        // Compresses to a few kilobytes, but expands past the limit
+        let mut encoder = DeflateEncoder::new(vec![FORMAT_JSON_DEFLATE], Compression::default());
+        encoder.write_all(&vec![b' '; MAX_MESSAGE_SIZE + 1]).unwrap();
+        let bomb = encoder.finish().unwrap();
+        assert_eq!(SyncMessage::decode(&bomb).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_receiver_sequence_handling() {
+        /// Transport that is never used because messages are applied directly
+        struct NoTransport;
+        impl Transport for NoTransport {
+            fn send(&mut self, _message: &[u8]) -> io::Result<()> {
+                Ok(())
+            }
+            fn receive(&mut self) -> io::Result<Vec<u8>> {
+                Err(io::ErrorKind::UnexpectedEof.into())
+            }
+        }
+
+        let mut mirror = World::new();
+        let mut receiver = DiffReceiver::new(NoTransport);
+        let update = |sequence| SyncMessage::Update {
+            sequence,
+            diff: WorldUpdateDiff::new(),
+        };
+
+        assert_eq!(
+            receiver.apply(update(0), &mut mirror),
+            ReceiveOutcome::AwaitingSnapshot { sequence: 0 }
+        );
+        let snapshot = SyncMessage::Snapshot {
+            sequence: 1,
+            diff: WorldUpdateDiff::new(),
+        };
+        assert_eq!(receiver.apply(snapshot, &mut mirror), ReceiveOutcome::Snapshot { sequence: 1 });
+        assert_eq!(receiver.apply(update(2), &mut mirror), ReceiveOutcome::Applied { sequence: 2 });
+        assert_eq!(receiver.apply(update(2), &mut mirror), ReceiveOutcome::Stale { sequence: 2 });
+        assert_eq!(
+            receiver.apply(update(5), &mut mirror),
+            ReceiveOutcome::Gap {
+                expected: 3,
+                received: 5
+            }
+        );
+        assert!(!receiver.is_synchronized());
+        assert_eq!(mirror.get_update_history().len(), 1);
+    }
+}
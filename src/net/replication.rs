@@ -0,0 +1,218 @@
+//! Interest management for replication.
+//!
+//! A `ReplicationConfig` decides which component types and which entities a
+//! connection receives. Entities can be selected explicitly or by a spatial
+//! region; entities entering the region are sent in full, entities leaving it
+//! are removed from the mirror world.
+
+use crate::{ComponentSet, DiffComponentChange, DiffEntry, Entity, EntityFilter, SystemUpdateDiff, World, WorldOperation, WorldUpdateDiff};
+
+/// Axis-aligned rectangle used for spatial interest management (bounds inclusive)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Region {
+    pub min_x: f32,
+    pub min_y: f32,
+    pub max_x: f32,
+    pub max_y: f32,
+}
+
+impl Region {
+    /// Create a region from two opposite corners
+    pub fn new(min_x: f32, min_y: f32, max_x: f32, max_y: f32) -> Self {
+        Self {
+            min_x: min_x.min(max_x),
+            min_y: min_y.min(max_y),
+            max_x: min_x.max(max_x),
+            max_y: min_y.max(max_y),
+        }
+    }
+
+    /// Check if a point lies inside the region
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        x >= self.min_x && x <= self.max_x && y >= self.min_y && y <= self.max_y
+    }
+}
+
+/// Looks up the location of an entity for region filtering
+pub type EntityLocator = fn(&World, Entity) -> Option<(f32, f32)>;
+
+/// Per-connection selection of the replicated components and entities
+#[derive(Clone, Default)]
+pub struct ReplicationConfig {
+    /// Replicated component type names, None replicates every type
+    components: Option<Vec<&'static str>>,
+    entities: EntityFilter,
+    region: Option<(Region, EntityLocator)>,
+}
+
+impl std::fmt::Debug for ReplicationConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReplicationConfig")
+            .field("components", &self.components)
+            .field("entities", &self.entities)
+            .field("region", &self.region.map(|(region, _)| region))
+            .finish()
+    }
+}
+
+impl ReplicationConfig {
+    /// Create a configuration replicating every component of every entity
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only replicate the component types of the tuple C (may be called repeatedly)
+    pub fn with_components<C: ComponentSet>(mut self) -> Self {
+        self.components
+            .get_or_insert_with(Vec::new)
+            .extend(C::type_names());
+        self
+    }
+
+    /// Only replicate the entities accepted by `filter`
+    pub fn with_entities(mut self, filter: impl Into<EntityFilter>) -> Self {
+        self.entities = filter.into();
+        self
+    }
+
+    /// Only replicate entities that `locator` places inside `region`.
+    /// Entities without a location are not replicated.
+    pub fn with_region(mut self, region: Region, locator: EntityLocator) -> Self {
+        self.region = Some((region, locator));
+        self
+    }
+
+    /// Check if components with the given type name are replicated
+    pub fn replicates_component(&self, type_name: &str) -> bool {
+        match &self.components {
+            Some(components) => components.contains(&type_name),
+            None => true,
+        }
+    }
+
+    /// Get the explicit entity selection
+    pub fn entity_filter(&self) -> &EntityFilter {
+        &self.entities
+    }
+
+    /// Check if the config selects a subset of entities that may change over time
+    pub(crate) fn tracks_visibility(&self) -> bool {
+        self.region.is_some() || self.entities != EntityFilter::All
+    }
+
+    /// Check if an entity is replicated given the current state of `world`
+    pub fn is_entity_relevant(&self, world: &World, entity: Entity) -> bool {
+        if !self.entities.matches(entity) {
+            return false;
+        }
+        match self.region {
+            Some((region, locator)) => {
+                locator(world, entity).is_some_and(|(x, y)| region.contains(x, y))
+            }
+            None => true,
+        }
+    }
+
+    /// Keep only replicated component types and entities accepted by `relevant`
+    pub(crate) fn filter_diff(
+        &self,
+        diff: &WorldUpdateDiff,
+        relevant: impl Fn(Entity) -> bool,
+    ) -> WorldUpdateDiff {
+        let mut filtered = WorldUpdateDiff::new();
+        for system_diff in diff.system_diffs() {
+            let mut filtered_system = SystemUpdateDiff::new();
+            // Kept in the recorded order, so mirrors apply them in that order too
+            for entry in system_diff.entries() {
+                match entry {
+                    DiffEntry::Change(change) => {
+                        let (entity, type_name) = match change {
+                            DiffComponentChange::Added { entity, type_name, .. }
+                            | DiffComponentChange::Modified { entity, type_name, .. }
+                            | DiffComponentChange::Removed { entity, type_name } => (entity, type_name),
+                        };
+                        if relevant(*entity) && self.replicates_component(type_name) {
+                            filtered_system.record_component_change(change.clone());
+                        }
+                    }
+                    DiffEntry::Operation(operation) => {
+                        let keep = match operation {
+                            WorldOperation::CreateEntity(entity)
+                            | WorldOperation::RemoveEntity(entity)
+                            | WorldOperation::AddToGroup(entity, _)
+                            | WorldOperation::RemoveFromGroup(entity, _)
+                            | WorldOperation::DisableEntity(entity)
+                            | WorldOperation::EnableEntity(entity) => relevant(*entity),
+                            WorldOperation::Relate(source, _, target) | WorldOperation::Unrelate(source, _, target) => {
+                                relevant(*source) && relevant(*target)
+                            }
+                            _ => true,
+                        };
+                        if keep {
+                            filtered_system.record_world_operation(operation.clone());
+                        }
+                    }
+                }
+            }
+            filtered.record(filtered_system);
+        }
+        filtered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn locate_by_index(_world: &World, entity: Entity) -> Option<(f32, f32)> {
+        Some((entity.entity_index as f32, 0.0))
+    }
+
+    #[test]
+    fn test_region_contains() {
+        let region = Region::new(4.0, 4.0, 0.0, 0.0);
+        assert!(region.contains(0.0, 4.0));
+        assert!(region.contains(2.0, 2.0));
+        assert!(!region.contains(4.5, 2.0));
+    }
+
+    #[test]
+    fn test_filter_diff_by_component_and_entity() {
+        let config = ReplicationConfig::new()
+            .with_components::<(u32,)>()
+            .with_entities(Entity::new(0, 1));
+        assert!(config.replicates_component("u32"));
+        assert!(!config.replicates_component("i32"));
+
+        let mut system_diff = SystemUpdateDiff::new();
+        for (index, type_name) in [(0, "u32"), (1, "u32"), (1, "i32")] {
+            system_diff.record_component_change(DiffComponentChange::Removed {
+                entity: Entity::new(0, index),
+                type_name: type_name.into(),
+            });
+        }
+        system_diff.record_world_operation(WorldOperation::CreateEntity(Entity::new(0, 0)));
+        system_diff.record_world_operation(WorldOperation::CreateEntity(Entity::new(0, 1)));
+        let mut diff = WorldUpdateDiff::new();
+        diff.record(system_diff);
+
+        let filtered = config.filter_diff(&diff, |entity| config.entities.matches(entity));
+        let filtered_system = &filtered.system_diffs()[0];
+        assert_eq!(filtered_system.component_changes().len(), 1);
+        assert_eq!(
+            filtered_system.component_changes()[0].to_string(),
+            "REM Entity(0, 1) u32"
+        );
+        assert_eq!(filtered_system.world_operations().len(), 1);
+    }
+
+    #[test]
+    fn test_region_relevance() {
+        let world = World::new();
+        let config = ReplicationConfig::new().with_region(Region::new(0.0, 0.0, 2.0, 2.0), locate_by_index);
+        assert!(config.tracks_visibility());
+        assert!(config.is_entity_relevant(&world, Entity::new(0, 2)));
+        assert!(!config.is_entity_relevant(&world, Entity::new(0, 3)));
+        assert!(!ReplicationConfig::new().tracks_visibility());
+    }
+}
@@ -0,0 +1,252 @@
+//! Callbacks run when components of a type are added, written or removed.
+//!
+//! Observers react to single components right away instead of at the end of
+//! the frame, e.g. to keep a spatial grid in sync with positions without a
+//! dedicated system:
+//!
+//! ```ignore
+//! let grid = Rc::new(RefCell::new(SpatialGrid::new()));
+//! let observed = grid.clone();
+//! world.observe::<Position>(move |entity, old, new| {
+//!     observed.borrow_mut().update(entity, old, new);
+//! });
+//! ```
+//!
+//! The callback gets the old and the new value: `(None, Some(new))` when the
+//! component is added, `(Some(old), None)` when it is removed, and both when a
+//! write changed it. A write through `get_component_mut` or `Out<T>` is only
+//! done once the mutable reference is gone, so it is compared with the old
+//! value at the next access the framework hands out, when the writing system
+//! finishes, or when `flush_observers` is called. Writes that leave the value
+//! unchanged do not call the observer.
+
+use crate::{Diff, Entity, World};
+use std::any::TypeId;
+use std::collections::BTreeMap;
+
+/// Identifier of an observer registered with `World::observe`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ObserverId(usize);
+
+/// An observer of one component type, independent of the type
+pub(crate) trait ComponentObserver {
+    fn component_type(&self) -> TypeId;
+
+    /// Remember the value of a component that is about to be written
+    fn before_write(&mut self, world: &World, entity: Entity);
+
+    /// Report the remembered components whose value changed
+    fn flush(&mut self, world: &World);
+
+    fn added(&mut self, world: &World, entity: Entity);
+
+    /// Report a component that is about to be removed
+    fn removed(&mut self, world: &World, entity: Entity);
+}
+
+type ObserverCallback<T> = Box<dyn FnMut(Entity, Option<&T>, Option<&T>)>;
+
+struct Observer<T> {
+    callback: ObserverCallback<T>,
+    /// Values before the writes not reported yet, in entity order
+    pending: BTreeMap<Entity, T>,
+}
+
+impl<T: Diff + Clone + 'static> ComponentObserver for Observer<T> {
+    fn component_type(&self) -> TypeId {
+        TypeId::of::<T>()
+    }
+
+    fn before_write(&mut self, world: &World, entity: Entity) {
+        if self.pending.contains_key(&entity) {
+            return;
+        }
+        if let Some(component) = world.get_component::<T>(entity) {
+            self.pending.insert(entity, component.clone());
+        }
+    }
+
+    fn flush(&mut self, world: &World) {
+        for (entity, old) in std::mem::take(&mut self.pending) {
+            // Components removed since were reported by `removed`
+            let Some(new) = world.get_component::<T>(entity) else {
+                continue;
+            };
+            if old.diff(new).is_some() {
+                (self.callback)(entity, Some(&old), Some(new));
+            }
+        }
+    }
+
+    fn added(&mut self, world: &World, entity: Entity) {
+        if let Some(component) = world.get_component::<T>(entity) {
+            (self.callback)(entity, None, Some(component));
+        }
+    }
+
+    fn removed(&mut self, world: &World, entity: Entity) {
+        if let Some(component) = world.get_component::<T>(entity) {
+            (self.callback)(entity, Some(component), None);
+        }
+    }
+}
+
+impl World {
+    /// Call `callback` with the old and new value whenever a component of type T
+    /// is added, changed or removed
+    pub fn observe<T: Diff + Clone + 'static>(
+        &mut self,
+        callback: impl FnMut(Entity, Option<&T>, Option<&T>) + 'static,
+    ) -> ObserverId {
+        let id = ObserverId(self.next_observer_id);
+        self.next_observer_id += 1;
+        self.observers.push((
+            id,
+            Box::new(Observer::<T> {
+                callback: Box::new(callback),
+                pending: BTreeMap::new(),
+            }),
+        ));
+        id
+    }
+
+    /// Remove a previously added observer, returning whether it existed
+    pub fn remove_observer(&mut self, id: ObserverId) -> bool {
+        let initial_count = self.observers.len();
+        self.observers.retain(|(observer_id, _)| *observer_id != id);
+        self.observers.len() < initial_count
+    }
+
+    /// Report the writes made through mutable references handed out so far
+    pub fn flush_observers(&mut self) {
+        self.notify_observers(None, |observer, world| observer.flush(world));
+    }
+
+    /// Remember a component before handing out mutable access to it
+    pub(crate) fn observe_write(&mut self, type_id: TypeId, entity: Entity) {
+        self.notify_observers(Some(type_id), |observer, world| observer.before_write(world, entity));
+    }
+
+    /// Report a component that was just added
+    pub(crate) fn observe_added(&mut self, type_id: TypeId, entity: Entity) {
+        self.notify_observers(Some(type_id), |observer, world| observer.added(world, entity));
+    }
+
+    /// Report a component that is about to be removed, after any earlier writes
+    pub(crate) fn observe_removal(&mut self, type_id: TypeId, entity: Entity) {
+        if self.observers.is_empty() {
+            return;
+        }
+        self.flush_observers();
+        self.notify_observers(Some(type_id), |observer, world| observer.removed(world, entity));
+    }
+
+    /// Report the observed components of an entity that is about to be removed
+    pub(crate) fn observe_entity_removal(&mut self, entity: Entity) {
+        let observed: Vec<TypeId> = self
+            .observers
+            .iter()
+            .map(|(_, observer)| observer.component_type())
+            .collect();
+        for type_id in observed {
+            self.observe_removal(type_id, entity);
+        }
+    }
+
+    /// Run `notify` for the observers of a component type, or all observers
+    fn notify_observers(
+        &mut self,
+        type_id: Option<TypeId>,
+        mut notify: impl FnMut(&mut dyn ComponentObserver, &World),
+    ) {
+        if self.observers.is_empty() || self.observers_muted {
+            return;
+        }
+        let mut observers = std::mem::take(&mut self.observers);
+        for (_, observer) in &mut observers {
+            if type_id.is_none() || type_id == Some(observer.component_type()) {
+                notify(observer.as_mut(), self);
+            }
+        }
+        self.observers = observers;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Out, System, WorldView};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Position(i32);
+
+    impl Diff for Position {
+        type Diff = i32;
+
+        fn diff(&self, other: &Self) -> Option<i32> {
+            (self.0 != other.0).then_some(other.0)
+        }
+
+        fn apply_diff(&mut self, diff: &i32) {
+            self.0 = *diff;
+        }
+    }
+
+    /// Moves every position right, except the ones at 0
+    struct MoveSystem;
+
+    impl System for MoveSystem {
+        type InComponents = ();
+        type OutComponents = (Position,);
+
+        fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+        fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+            for (_, position) in world.query_components::<(Out<Position>,)>() {
+                if position.0 != 0 {
+                    position.0 += 1;
+                }
+            }
+        }
+
+        fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    }
+
+    #[test]
+    fn test_observers_see_additions_changes_and_removals() {
+        let mut world = World::new();
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let observed = seen.clone();
+        let id = world.observe::<Position>(move |entity, old, new| {
+            observed
+                .borrow_mut()
+                .push((entity, old.map(|p| p.0), new.map(|p| p.0)));
+        });
+
+        let still = world.create_entity();
+        let moving = world.create_entity();
+        world.add_component(still, Position(0));
+        world.add_component(moving, Position(5));
+        world.add_system(MoveSystem);
+        world.update();
+        world.get_component_mut::<Position>(still).unwrap().0 = 3;
+        world.remove_entity(still);
+
+        assert_eq!(
+            *seen.borrow(),
+            vec![
+                (still, None, Some(0)),
+                (moving, None, Some(5)),
+                (moving, Some(5), Some(6)),
+                (still, Some(0), Some(3)),
+                (still, Some(3), None),
+            ]
+        );
+
+        assert!(world.remove_observer(id));
+        world.update();
+        assert_eq!(seen.borrow().len(), 5);
+    }
+}
@@ -0,0 +1,150 @@
+//! Pausing a world, e.g. behind a pause menu.
+//!
+//! While a world is paused, `update` skips every system except the ones whose
+//! `run_when_paused` returns true, such as the renderer or the menu itself.
+//! Skipped systems record an empty diff like disabled ones, so frames keep
+//! counting and the system indices of the history stay aligned:
+//!
+//! ```ignore
+//! impl System for PauseMenuSystem {
+//!     fn run_when_paused(&self) -> bool {
+//!         true
+//!     }
+//!     // ...
+//! }
+//!
+//! world.pause();
+//! world.update(); // only PauseMenuSystem updates
+//! world.resume();
+//! ```
+//!
+//! `pause` and `resume` call the `on_pause` and `on_resume` hooks of every
+//! system, letting them stop sounds or reset timers. Changes the hooks make are
+//! recorded as one update of their own, like the ones of `initialize_systems`.
+
+use crate::{SystemUpdateDiff, World, WorldUpdateDiff};
+
+impl World {
+    /// Pause the world, calling the `on_pause` hook of every system. Does
+    /// nothing if the world is already paused.
+    pub fn pause(&mut self) {
+        if self.paused {
+            return;
+        }
+        self.paused = true;
+        ecs_info!("World {} paused at frame {}", self.world_index, self.frame_number);
+        self.run_pause_hooks(|system, world| system.on_pause(world));
+    }
+
+    /// Resume a paused world, calling the `on_resume` hook of every system.
+    /// Does nothing if the world is not paused.
+    pub fn resume(&mut self) {
+        if !self.paused {
+            return;
+        }
+        self.paused = false;
+        ecs_info!("World {} resumed at frame {}", self.world_index, self.frame_number);
+        self.run_pause_hooks(|system, world| system.on_resume(world));
+    }
+
+    /// Check if the world is paused
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Run a hook of every system, recording their changes
+    fn run_pause_hooks(&mut self, mut hook: impl FnMut(&mut dyn crate::SystemWrapper, &mut World) -> SystemUpdateDiff) {
+        let mut systems = std::mem::take(&mut self.systems);
+        let mut diff = WorldUpdateDiff::new();
+        for system in &mut systems {
+            diff.record(hook(system.as_mut(), self));
+        }
+        self.systems = systems;
+        self.record_lifecycle_update(diff);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Diff, Out, System, World, WorldView};
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Debug, Clone, Copy, PartialEq, Diff)]
+    struct Clock {
+        ticks: u32,
+    }
+
+    /// Ticks the clock, and notes its pause hooks
+    struct ClockSystem(Arc<Mutex<Vec<&'static str>>>);
+
+    impl System for ClockSystem {
+        type InComponents = ();
+        type OutComponents = (Clock,);
+
+        fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+        fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+            for (_, clock) in world.query_components::<(Out<Clock>,)>() {
+                clock.ticks += 1;
+            }
+        }
+
+        fn on_pause(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+            self.0.lock().unwrap().push("pause");
+        }
+
+        fn on_resume(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+            self.0.lock().unwrap().push("resume");
+        }
+
+        fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    }
+
+    /// Counts its updates, also while paused
+    struct MenuSystem(Arc<Mutex<Vec<&'static str>>>);
+
+    impl System for MenuSystem {
+        type InComponents = ();
+        type OutComponents = ();
+
+        fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+        fn update(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+            self.0.lock().unwrap().push("menu");
+        }
+
+        fn run_when_paused(&self) -> bool {
+            true
+        }
+
+        fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    }
+
+    #[test]
+    fn test_paused_world_only_runs_systems_marked_to() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut world = World::new();
+        let clock = world.create_entity();
+        world.add_component(clock, Clock { ticks: 0 });
+        world.add_system(ClockSystem(events.clone()));
+        world.add_system(MenuSystem(events.clone()));
+
+        world.update();
+        world.pause();
+        world.pause();
+        assert!(world.is_paused());
+        world.update();
+        world.update();
+        assert_eq!(world.get_component::<Clock>(clock), Some(&Clock { ticks: 1 }));
+        assert_eq!(world.frame_number(), 3);
+
+        world.resume();
+        world.resume();
+        world.update();
+        assert_eq!(world.get_component::<Clock>(clock), Some(&Clock { ticks: 2 }));
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec!["menu", "pause", "menu", "menu", "resume", "menu"]
+        );
+    }
+}
@@ -0,0 +1,104 @@
+//! Plugins bundle the systems, components, prefabs and resources of a
+//! feature so it can be added to a world in one call:
+//!
+//! ```ignore
+//! let mut world = World::new();
+//! world.add_plugin(GamePlugin::headless());
+//! world.initialize_systems();
+//! ```
+
+use crate::World;
+
+/// A cohesive piece of world setup
+pub trait Plugin: 'static {
+    /// Add the plugin's systems, components, prefabs and resources to the world
+    fn build(&self, world: &mut World);
+
+    /// Name of the plugin used in diagnostics
+    fn name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+            .rsplit("::")
+            .next()
+            .unwrap_or("Plugin")
+    }
+}
+
+impl World {
+    /// Build a plugin into the world. Each plugin type is added only once,
+    /// so plugins can add the plugins they depend on.
+    pub fn add_plugin<P: Plugin>(&mut self, plugin: P) -> &mut Self {
+        if self.has_plugin::<P>() {
+            ecs_warn!("Plugin {} was already added", plugin.name());
+            return self;
+        }
+        self.plugins.push((std::any::TypeId::of::<P>(), plugin.name()));
+        plugin.build(self);
+        ecs_info!("Added plugin {}", plugin.name());
+        self
+    }
+
+    /// Check if a plugin of type P was added
+    pub fn has_plugin<P: Plugin>(&self) -> bool {
+        self.plugins
+            .iter()
+            .any(|(type_id, _)| *type_id == std::any::TypeId::of::<P>())
+    }
+
+    /// Names of the added plugins, in the order they were added
+    pub fn plugin_names(&self) -> Vec<&'static str> {
+        self.plugins.iter().map(|(_, name)| *name).collect()
+    }
+}
+
+/// Writes every update to a replay log file
+#[cfg(feature = "fs")]
+pub struct ReplayLoggingPlugin {
+    pub config: crate::ReplayLogConfig,
+}
+
+#[cfg(feature = "fs")]
+impl Plugin for ReplayLoggingPlugin {
+    fn build(&self, world: &mut World) {
+        let config = crate::ReplayLogConfig {
+            enabled: true,
+            ..self.config.clone()
+        };
+        if let Err(e) = world.enable_replay_logging(config) {
+            ecs_error!("Failed to enable replay logging: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Counter(u32);
+
+    struct CounterPlugin;
+
+    impl Plugin for CounterPlugin {
+        fn build(&self, world: &mut World) {
+            world.insert_resource(Counter(0));
+        }
+    }
+
+    struct DependentPlugin;
+
+    impl Plugin for DependentPlugin {
+        fn build(&self, world: &mut World) {
+            world.add_plugin(CounterPlugin);
+            world.resource_mut::<Counter>().unwrap().0 += 1;
+        }
+    }
+
+    #[test]
+    fn test_plugins_are_added_once() {
+        let mut world = World::new();
+        world.add_plugin(DependentPlugin).add_plugin(CounterPlugin);
+
+        assert!(world.has_plugin::<CounterPlugin>());
+        assert_eq!(world.plugin_names(), vec!["DependentPlugin", "CounterPlugin"]);
+        assert_eq!(world.resource::<Counter>().unwrap().0, 1);
+    }
+}
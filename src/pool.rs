@@ -0,0 +1,159 @@
+//! Reusing entities for frequently spawned objects.
+//!
+//! Projectiles and short-lived effects are spawned and despawned all the
+//! time. An `EntityPool` keeps released entities instead of removing them:
+//! they are disabled, so queries skip them, and `acquire` hands one out again
+//! with its components replaced by a prefab's:
+//!
+//! ```ignore
+//! let mut bullets = EntityPool::new();
+//! let bullet = bullets.acquire(&mut world, &bullet_prefab)?;
+//! // ...
+//! bullets.release(&mut world, bullet);
+//! ```
+//!
+//! Entity identifiers have no generation, so the pool hands out
+//! `PooledEntity` handles that carry one. Releasing an entity bumps its
+//! generation, which makes handles to its previous use stale: `resolve`
+//! returns `None` for them and `release` ignores them.
+
+use crate::{Entity, Prefab, World};
+use std::collections::HashMap;
+
+/// An entity handed out by an `EntityPool`, valid until it is released
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PooledEntity {
+    entity: Entity,
+    generation: u32,
+}
+
+impl PooledEntity {
+    /// Get the pooled entity, which may be in use by a later acquire
+    pub fn entity(&self) -> Entity {
+        self.entity
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+}
+
+/// Released entities waiting to be acquired again
+#[derive(Debug, Default)]
+pub struct EntityPool {
+    /// Released entities, reused last released first
+    free: Vec<Entity>,
+    /// Current generation and whether the entity is in use, for every entity of the pool
+    entities: HashMap<Entity, (u32, bool)>,
+}
+
+impl EntityPool {
+    /// Create an empty pool
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Spawn an entity from a prefab, reusing a released entity if there is one.
+    /// Nothing changes if a component of the prefab fails to build.
+    pub fn acquire(&mut self, world: &mut World, prefab: &Prefab) -> Result<PooledEntity, String> {
+        world.register_prefab_types(prefab);
+        let components = world.prefab_components(prefab, &[])?;
+
+        // Entities removed from the world since their release cannot be reused
+        let reused = std::iter::from_fn(|| self.free.pop()).find(|entity| world.entity_exists(*entity));
+        let entity = match reused {
+            Some(entity) => {
+                world.clear_components(entity);
+                world.set_enabled(entity, true);
+                entity
+            }
+            None => world.create_entity(),
+        };
+        for (type_id, component) in components {
+            world.push_component_any(entity, type_id, component);
+        }
+
+        let (generation, in_use) = self.entities.entry(entity).or_insert((0, false));
+        *in_use = true;
+        Ok(PooledEntity {
+            entity,
+            generation: *generation,
+        })
+    }
+
+    /// Return an entity to the pool, disabling it. Returns false for stale handles.
+    pub fn release(&mut self, world: &mut World, pooled: PooledEntity) -> bool {
+        let Some((generation, in_use)) = self.entities.get_mut(&pooled.entity) else {
+            return false;
+        };
+        if !*in_use || *generation != pooled.generation {
+            return false;
+        }
+        *generation += 1;
+        *in_use = false;
+        world.set_enabled(pooled.entity, false);
+        self.free.push(pooled.entity);
+        true
+    }
+
+    /// Get the entity of a handle that was not released since it was acquired
+    pub fn resolve(&self, pooled: PooledEntity) -> Option<Entity> {
+        self.entities
+            .get(&pooled.entity)
+            .filter(|(generation, in_use)| *in_use && *generation == pooled.generation)
+            .map(|_| pooled.entity)
+    }
+
+    /// Number of released entities waiting to be reused
+    pub fn available(&self) -> usize {
+        self.free.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{In, Reflect, WorldView};
+
+    #[derive(Debug, Clone, PartialEq, Reflect)]
+    struct Bullet {
+        speed: i32,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Reflect)]
+    struct Burning {
+        ticks: i32,
+    }
+
+    #[test]
+    fn test_released_entities_are_reused_with_fresh_components() {
+        let mut world = World::new();
+        let mut pool = EntityPool::new();
+        let prefab = Prefab::new("bullet").with(Bullet { speed: 3 });
+
+        let first = pool.acquire(&mut world, &prefab).unwrap();
+        let second = pool.acquire(&mut world, &prefab).unwrap();
+        world.get_component_mut::<Bullet>(first.entity()).unwrap().speed = 0;
+        world.add_component(first.entity(), Burning { ticks: 2 });
+
+        assert!(pool.release(&mut world, first));
+        assert!(!pool.release(&mut world, first));
+        assert_eq!(pool.resolve(first), None);
+        let mut view = WorldView::<(Bullet,), ()>::new(&mut world);
+        let active: Vec<Entity> = view
+            .query_components::<(In<Bullet>,)>()
+            .into_iter()
+            .map(|(entity, _)| entity)
+            .collect();
+        assert_eq!(active, vec![second.entity()]);
+
+        let third = pool.acquire(&mut world, &prefab).unwrap();
+        assert_eq!(third.entity(), first.entity());
+        assert_ne!(third, first);
+        assert_eq!(pool.resolve(third), Some(first.entity()));
+        assert!(world.is_enabled(third.entity()));
+        assert_eq!(world.get_component::<Bullet>(third.entity()), Some(&Bullet { speed: 3 }));
+        assert_eq!(world.get_component::<Burning>(third.entity()), None);
+        assert_eq!(world.entity_count(), 2);
+    }
+}
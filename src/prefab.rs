@@ -0,0 +1,348 @@
+//! Named entity templates.
+//!
+//! A `Prefab` stores a set of components as reflected `Value`s under a name.
+//! `World::instantiate` spawns an entity from it, optionally overriding some
+//! fields. Prefabs are built in code or, with the `ron` feature, loaded from
+//! RON files mapping prefab names to components:
+//!
+//! ```text
+//! {
+//!     "home": {
+//!         "Position": (x: 1, y: 1),
+//!         "Home": (),
+//!         "Obstacle": (),
+//!     },
+//! }
+//! ```
+//!
+//! Components are looked up by short type name in the world's component
+//! registry, so their types must derive `Reflect` and be registered.
+
+use crate::reflect::ComponentRegistry;
+use crate::{Entity, Reflect, Value, World};
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+
+/// A component built from a prefab value, with its type
+pub(crate) type BuiltComponent = (TypeId, Box<dyn Any>);
+
+/// A named set of components entities can be spawned from
+#[derive(Debug, Clone)]
+pub struct Prefab {
+    name: String,
+    components: Vec<(String, Value)>,
+    /// Registrations of the component types added with `with`
+    registrations: Vec<fn(&mut ComponentRegistry)>,
+}
+
+impl Prefab {
+    /// Create an empty prefab
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            components: Vec::new(),
+            registrations: Vec::new(),
+        }
+    }
+
+    /// Add a component; its type is registered with the world together with the prefab
+    pub fn with<T: Reflect>(mut self, component: T) -> Self {
+        self.registrations.push(|registry| registry.register::<T>());
+        self.with_value(crate::short_type_name::<T>(), component.to_value())
+    }
+
+    /// Add a component of a registered type by name
+    pub fn with_value(mut self, type_name: impl Into<String>, value: Value) -> Self {
+        let type_name = type_name.into();
+        match self.components.iter_mut().find(|(name, _)| *name == type_name) {
+            Some((_, existing)) => *existing = value,
+            None => self.components.push((type_name, value)),
+        }
+        self
+    }
+
+    /// Get the name of the prefab
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get the components of the prefab, in the order they were added
+    pub fn components(&self) -> &[(String, Value)] {
+        &self.components
+    }
+}
+
+/// Prefabs registered with a world, looked up by name
+#[derive(Debug, Clone, Default)]
+pub struct PrefabRegistry {
+    prefabs: HashMap<String, Prefab>,
+}
+
+impl PrefabRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a prefab, replacing a prefab with the same name
+    pub fn register(&mut self, prefab: Prefab) {
+        self.prefabs.insert(prefab.name.clone(), prefab);
+    }
+
+    /// Get a prefab by name
+    pub fn get(&self, name: &str) -> Option<&Prefab> {
+        self.prefabs.get(name)
+    }
+
+    /// Get the names of all prefabs, sorted
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.prefabs.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Parse prefabs from a RON map of prefab names to component maps
+    #[cfg(feature = "ron")]
+    pub fn parse_ron(contents: &str) -> Result<Vec<Prefab>, String> {
+        let ron::Value::Map(prefabs) = ron::from_str::<ron::Value>(contents).map_err(|e| e.to_string())? else {
+            return Err("expected a map of prefab names to components".to_string());
+        };
+        prefabs
+            .into_iter()
+            .map(|(name, components)| {
+                let name = ron_key(name)?;
+                let ron::Value::Map(components) = components else {
+                    return Err(format!("prefab '{}' must be a map of component names to values", name));
+                };
+                components
+                    .into_iter()
+                    .try_fold(Prefab::new(name), |prefab, (type_name, value)| {
+                        Ok(prefab.with_value(ron_key(type_name)?, ron_to_value(value)))
+                    })
+            })
+            .collect()
+    }
+}
+
+/// Map key naming a prefab or component
+#[cfg(any(feature = "ron", feature = "serde"))]
+pub(crate) fn ron_key(key: ron::Value) -> Result<String, String> {
+    match key {
+        ron::Value::String(key) => Ok(key),
+        other => Err(format!("expected a name, found {:?}", other)),
+    }
+}
+
+/// Convert a parsed RON value; structs become maps and enum variants are written as strings
+#[cfg(any(feature = "ron", feature = "serde"))]
+pub(crate) fn ron_to_value(value: ron::Value) -> Value {
+    match value {
+        ron::Value::Bool(value) => Value::Bool(value),
+        ron::Value::Char(value) => Value::String(value.to_string()),
+        ron::Value::String(value) => Value::String(value),
+        ron::Value::Number(ron::Number::Integer(value)) => Value::Int(value),
+        ron::Value::Number(ron::Number::Float(value)) => Value::Float(value.get()),
+        ron::Value::Option(value) => value.map_or(Value::Unit, |value| ron_to_value(*value)),
+        ron::Value::Seq(items) => Value::List(items.into_iter().map(ron_to_value).collect()),
+        ron::Value::Unit => Value::Unit,
+        ron::Value::Map(map) => Value::Map(
+            map.into_iter()
+                .map(|(key, value)| {
+                    let key = match key {
+                        ron::Value::String(key) => key,
+                        other => format!("{:?}", other),
+                    };
+                    (key, ron_to_value(value))
+                })
+                .collect(),
+        ),
+    }
+}
+
+/// Apply overrides to a component value, merging maps field by field
+pub(crate) fn merge_value(base: &mut Value, overrides: &Value) {
+    match (base, overrides) {
+        (Value::Map(base), Value::Map(overrides)) => {
+            for (key, value) in overrides {
+                match base.get_mut(key) {
+                    Some(existing) => merge_value(existing, value),
+                    None => {
+                        base.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+        (base, overrides) => *base = overrides.clone(),
+    }
+}
+
+impl World {
+    /// Register a prefab and the component types added to it with `Prefab::with`
+    pub fn register_prefab(&mut self, prefab: Prefab) {
+        self.register_prefab_types(&prefab);
+        self.prefabs.register(prefab);
+    }
+
+    /// Register the component types added to a prefab with `Prefab::with`
+    pub(crate) fn register_prefab_types(&mut self, prefab: &Prefab) {
+        for register in &prefab.registrations {
+            register(&mut self.component_registry);
+        }
+    }
+
+    /// Get the registered prefabs
+    pub fn prefabs(&self) -> &PrefabRegistry {
+        &self.prefabs
+    }
+
+    /// Spawn an entity from a prefab.
+    ///
+    /// Each override names a component and a value merged into the prefab's
+    /// value, so `("Position", {x: 3})` only changes `x`. Components the
+    /// prefab lacks are added. Nothing is spawned if a component fails to build.
+    pub fn instantiate(&mut self, name: &str, overrides: &[(&str, Value)]) -> Result<Entity, String> {
+        let prefab = self.prefabs.get(name).ok_or_else(|| format!("Unknown prefab: {}", name))?;
+        let components = self.prefab_components(prefab, overrides)?;
+
+        let entity = self.create_entity();
+        for (type_id, component) in components {
+            self.push_component_any(entity, type_id, component);
+        }
+        Ok(entity)
+    }
+
+    /// Build the components of a prefab with overrides applied
+    pub(crate) fn prefab_components(
+        &self,
+        prefab: &Prefab,
+        overrides: &[(&str, Value)],
+    ) -> Result<Vec<BuiltComponent>, String> {
+        let name = &prefab.name;
+        let mut values = prefab.components.clone();
+        for (type_name, value) in overrides {
+            match values.iter_mut().find(|(name, _)| name == type_name) {
+                Some((_, base)) => merge_value(base, value),
+                None => values.push((type_name.to_string(), value.clone())),
+            }
+        }
+
+        let mut components = Vec::with_capacity(values.len());
+        for (type_name, value) in &values {
+            let registration = self
+                .component_registry
+                .get(type_name)
+                .ok_or_else(|| format!("Prefab {}: unknown component type {}", name, type_name))?;
+            let component = registration
+                .from_value(value)
+                .map_err(|e| format!("Prefab {}: {}", name, e))?;
+            components.push((registration.type_id(), component));
+        }
+        Ok(components)
+    }
+
+    /// Register every prefab in a RON string, returning how many were loaded
+    #[cfg(feature = "ron")]
+    pub fn load_prefabs_ron(&mut self, contents: &str) -> Result<usize, String> {
+        let prefabs = PrefabRegistry::parse_ron(contents)?;
+        let count = prefabs.len();
+        for prefab in prefabs {
+            self.register_prefab(prefab);
+        }
+        Ok(count)
+    }
+
+    /// Register every prefab in a RON file, returning how many were loaded
+    #[cfg(all(feature = "ron", feature = "fs"))]
+    pub fn load_prefabs<P: AsRef<std::path::Path>>(&mut self, path: P) -> Result<usize, String> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        self.load_prefabs_ron(&contents)
+            .map_err(|e| format!("Failed to load prefabs from {}: {}", path.display(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[derive(Debug, Clone, PartialEq, Reflect)]
+    struct Tree {
+        height: u32,
+        kind: String,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Reflect)]
+    struct Solid;
+
+    fn tree_prefab() -> Prefab {
+        Prefab::new("tree")
+            .with(Tree {
+                height: 3,
+                kind: "oak".to_string(),
+            })
+            .with(Solid)
+    }
+
+    #[test]
+    fn test_instantiate_with_overrides() {
+        let mut world = World::new();
+        world.register_prefab(tree_prefab());
+
+        let plain = world.instantiate("tree", &[]).unwrap();
+        let mut height = BTreeMap::new();
+        height.insert("height".to_string(), Value::Int(10));
+        let tall = world.instantiate("tree", &[("Tree", Value::Map(height))]).unwrap();
+
+        assert_ne!(plain, tall);
+        assert_eq!(world.get_component::<Tree>(plain).unwrap().height, 3);
+        let tall_tree = world.get_component::<Tree>(tall).unwrap();
+        assert_eq!((tall_tree.height, tall_tree.kind.as_str()), (10, "oak"));
+        assert!(world.get_component::<Solid>(tall).is_some());
+    }
+
+    #[test]
+    fn test_failed_instantiation_spawns_nothing() {
+        let mut world = World::new();
+        world.register_prefab(tree_prefab());
+        let entities = world.entities.len();
+
+        let mut height = BTreeMap::new();
+        height.insert("height".to_string(), Value::Int(-1));
+        assert!(world.instantiate("tree", &[("Tree", Value::Map(height))]).is_err());
+        assert!(world.instantiate("tree", &[("Unknown", Value::Unit)]).is_err());
+        assert_eq!(
+            world.instantiate("bush", &[]),
+            Err("Unknown prefab: bush".to_string())
+        );
+        assert_eq!(world.entities.len(), entities);
+    }
+
+    #[cfg(feature = "ron")]
+    #[test]
+    fn test_load_prefabs_from_ron() {
+        let mut world = World::new();
+        world.register_component::<Tree>();
+        world.register_component::<Solid>();
+        let loaded = world
+            .load_prefabs_ron(
+                r#"{
+                    "pine": {
+                        "Tree": (height: 12, kind: "pine"),
+                        "Solid": (),
+                    },
+                    "sapling": {
+                        "Tree": (height: 1, kind: "oak"),
+                    },
+                }"#,
+            )
+            .unwrap();
+
+        assert_eq!(loaded, 2);
+        assert_eq!(world.prefabs().names(), vec!["pine", "sapling"]);
+        let pine = world.instantiate("pine", &[]).unwrap();
+        assert_eq!(world.get_component::<Tree>(pine).unwrap().height, 12);
+        assert!(world.get_component::<Solid>(pine).is_some());
+        assert!(world.load_prefabs_ron("[1, 2]").is_err());
+    }
+}
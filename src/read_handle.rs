@@ -0,0 +1,299 @@
+//! Reading components from other threads while the world updates.
+//!
+//! The world itself is owned by the game thread. Tools like an inspector or
+//! a network sync thread read components of shared types through a
+//! `WorldReadHandle` instead. Each shared type is published into its own
+//! column behind a `RwLock` at the end of every frame, and readers borrow the
+//! components in place under the column's read lock:
+//!
+//! ```ignore
+//! world.share_component::<Position>();
+//! let handle = world.read_handle();
+//! std::thread::spawn(move || loop {
+//!     let positions = handle.components::<Position>().unwrap();
+//!     let positions = positions.read();
+//!     draw(positions.frame_number(), positions.iter());
+//! });
+//! ```
+//!
+//! Locking works per type:
+//!
+//! - The world takes a type's write lock only while publishing it after a
+//!   frame, and only clones the components written since it last did.
+//! - A read guard sees every component of its type as of one completed frame.
+//!   Guards of different types may come from different frames; compare their
+//!   `frame_number`.
+//! - A held guard blocks the world at the end of its next frame, so readers
+//!   should drop guards quickly.
+//!
+//! Changes made between updates are published with the next frame, or right
+//! away with `publish_shared_components`. Requires the `read-handle` feature.
+
+use crate::change_detection::ChangeTick;
+use crate::{Entity, World};
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock, RwLockReadGuard};
+
+/// Published components of one type, in entity order
+pub struct PublishedComponents<T> {
+    frame_number: usize,
+    components: Vec<(Entity, T)>,
+}
+
+impl<T> PublishedComponents<T> {
+    /// Get the number of completed frames when the components were published
+    pub fn frame_number(&self) -> usize {
+        self.frame_number
+    }
+
+    /// Get the component of an entity (if it has one)
+    pub fn get(&self, entity: Entity) -> Option<&T> {
+        self.components
+            .binary_search_by_key(&entity, |(e, _)| *e)
+            .ok()
+            .map(|index| &self.components[index].1)
+    }
+
+    /// Iterate over the entities and their components in entity order
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &T)> {
+        self.components.iter().map(|(entity, component)| (*entity, component))
+    }
+
+    pub fn len(&self) -> usize {
+        self.components.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.components.is_empty()
+    }
+}
+
+/// Column of one shared type, readable from any thread
+pub struct ComponentReader<T> {
+    column: Arc<RwLock<PublishedComponents<T>>>,
+}
+
+impl<T> ComponentReader<T> {
+    /// Lock the column for reading, waiting while the world publishes it
+    pub fn read(&self) -> RwLockReadGuard<'_, PublishedComponents<T>> {
+        // A panic while publishing leaves the column as it was
+        self.column.read().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl<T> Clone for ComponentReader<T> {
+    fn clone(&self) -> Self {
+        Self {
+            column: self.column.clone(),
+        }
+    }
+}
+
+/// Columns of the shared types by type
+type SharedColumns = HashMap<TypeId, Arc<dyn Any + Send + Sync>>;
+
+/// Thread-safe read-only access to the shared component types of a world
+#[derive(Clone)]
+pub struct WorldReadHandle {
+    columns: Arc<RwLock<SharedColumns>>,
+}
+
+impl WorldReadHandle {
+    /// Get the column of a shared type, None if `T` is not shared
+    pub fn components<T: Send + Sync + 'static>(&self) -> Option<ComponentReader<T>> {
+        let columns = self.columns.read().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let column = columns.get(&TypeId::of::<T>())?.clone();
+        let column = column.downcast::<RwLock<PublishedComponents<T>>>().ok()?;
+        Some(ComponentReader { column })
+    }
+
+    /// Get the component of an entity as of the latest published frame
+    pub fn get<T: Clone + Send + Sync + 'static>(&self, entity: Entity) -> Option<T> {
+        self.components::<T>()?.read().get(entity).cloned()
+    }
+}
+
+/// Publishes the components of one shared type
+type Publisher = fn(&World, &(dyn Any + Send + Sync), ChangeTick);
+
+/// Component types shared with read handles
+pub(crate) struct SharedComponents {
+    handle: WorldReadHandle,
+    publishers: Vec<(TypeId, Publisher)>,
+    /// Tick of the latest publication; components written at it or later
+    /// are published again
+    published_tick: ChangeTick,
+}
+
+impl Default for SharedComponents {
+    fn default() -> Self {
+        Self {
+            handle: WorldReadHandle {
+                columns: Arc::new(RwLock::new(HashMap::new())),
+            },
+            publishers: Vec::new(),
+            published_tick: ChangeTick::default(),
+        }
+    }
+}
+
+/// Update the published components of `T` to the world's current ones
+fn publish<T: Clone + Send + Sync + 'static>(world: &World, column: &(dyn Any + Send + Sync), since: ChangeTick) {
+    let Some(column) = column.downcast_ref::<RwLock<PublishedComponents<T>>>() else {
+        return;
+    };
+    let type_id = TypeId::of::<T>();
+    let mut published = column.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let mut previous = std::mem::take(&mut published.components).into_iter().peekable();
+    let mut components = Vec::new();
+    for (entity, component) in world.components.get(&type_id).into_iter().flatten() {
+        // Both are in entity order; skip the published components of removed entities
+        while previous.next_if(|(e, _)| *e < entity).is_some() {}
+        let unchanged = previous.next_if(|(e, _)| *e == entity).filter(|_| {
+            world
+                .component_ticks
+                .get(&(type_id, entity))
+                .is_some_and(|tick| since.is_newer_than(*tick))
+        });
+        match unchanged {
+            Some(unchanged) => components.push(unchanged),
+            None => {
+                if let Some(component) = component.downcast_ref::<T>() {
+                    components.push((entity, component.clone()));
+                }
+            }
+        }
+    }
+    published.components = components;
+    published.frame_number = world.frame_number;
+}
+
+impl World {
+    /// Publish the components of `T` to read handles after every frame
+    pub fn share_component<T: Clone + Send + Sync + 'static>(&mut self) {
+        let type_id = TypeId::of::<T>();
+        if self.shared_components.publishers.iter().any(|(shared, _)| *shared == type_id) {
+            return;
+        }
+        let column: Arc<dyn Any + Send + Sync> = Arc::new(RwLock::new(PublishedComponents::<T> {
+            frame_number: self.frame_number,
+            components: Vec::new(),
+        }));
+        publish::<T>(self, column.as_ref(), ChangeTick::default());
+        self.shared_components
+            .handle
+            .columns
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .insert(type_id, column);
+        self.shared_components.publishers.push((type_id, publish::<T>));
+    }
+
+    /// Get a handle reading the shared component types from any thread.
+    /// Types shared later are readable through existing handles too.
+    pub fn read_handle(&self) -> WorldReadHandle {
+        self.shared_components.handle.clone()
+    }
+
+    /// Publish the current components of the shared types, including changes
+    /// made since the last frame
+    pub fn publish_shared_components(&mut self) {
+        if self.shared_components.publishers.is_empty() {
+            return;
+        }
+        let columns = self
+            .shared_components
+            .handle
+            .columns
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone();
+        for (type_id, publish) in &self.shared_components.publishers {
+            if let Some(column) = columns.get(type_id) {
+                publish(self, column.as_ref(), self.shared_components.published_tick);
+            }
+        }
+        self.shared_components.published_tick = self.change_tick;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Out, System, WorldView};
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Counter(usize);
+
+    struct CountSystem;
+
+    impl System for CountSystem {
+        type InComponents = ();
+        type OutComponents = (Counter,);
+
+        fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+        fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+            for (_, counter) in world.query_components::<(Out<Counter>,)>() {
+                counter.0 += 1;
+            }
+        }
+
+        fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    }
+
+    #[test]
+    fn test_readers_see_whole_frames_while_the_world_updates() {
+        let mut world = World::new();
+        for _ in 0..20 {
+            let entity = world.create_entity();
+            world.add_component(entity, Counter(0));
+        }
+        world.add_system(CountSystem);
+        world.share_component::<Counter>();
+        let handle = world.read_handle();
+
+        let reader = std::thread::spawn(move || {
+            let counters = handle.components::<Counter>().unwrap();
+            let mut last_frame = 0;
+            while last_frame < 200 {
+                let counters = counters.read();
+                assert_eq!(counters.len(), 20);
+                assert!(counters.iter().all(|(_, counter)| counter.0 == counters.frame_number()));
+                assert!(counters.frame_number() >= last_frame);
+                last_frame = counters.frame_number();
+            }
+        });
+        for _ in 0..200 {
+            world.update();
+        }
+        reader.join().unwrap();
+    }
+
+    #[test]
+    fn test_changes_between_updates_are_published() {
+        let mut world = World::new();
+        let handle = world.read_handle();
+        assert!(handle.components::<Counter>().is_none());
+
+        let first = world.create_entity();
+        world.add_component(first, Counter(1));
+        world.share_component::<Counter>();
+        assert_eq!(handle.get::<Counter>(first), Some(Counter(1)));
+
+        let second = world.create_entity();
+        world.add_component(second, Counter(2));
+        world.get_component_mut::<Counter>(first).unwrap().0 = 5;
+        assert_eq!(handle.get::<Counter>(second), None);
+        world.publish_shared_components();
+        assert_eq!(handle.get::<Counter>(first), Some(Counter(5)));
+        assert_eq!(handle.get::<Counter>(second), Some(Counter(2)));
+
+        world.remove_entity(first);
+        world.update();
+        let counters = handle.components::<Counter>().unwrap();
+        assert_eq!(counters.read().iter().collect::<Vec<_>>(), vec![(second, &Counter(2))]);
+        assert_eq!(counters.read().frame_number(), 1);
+    }
+}
@@ -0,0 +1,193 @@
+//! Systems that can only read the world.
+//!
+//! Analysis and render systems declare no output components, but a
+//! `WorldView` would still let them create entities or add components and
+//! pollute the recorded diffs. A `ReadOnlySystem` gets a `ReadOnlyWorldView`
+//! instead, which has no mutation APIs at all:
+//!
+//! ```ignore
+//! impl ReadOnlySystem for RenderSystem {
+//!     type InComponents = (Position,);
+//!
+//!     fn update(&mut self, world: &ReadOnlyWorldView<Self::InComponents>) {
+//!         for (_, position) in world.query::<(In<Position>,)>() {
+//!             draw(position);
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! Every `ReadOnlySystem` is a `System` with `OutComponents = ()` and is added
+//! with `World::add_system` like any other.
+
+use crate::{ComponentAccess, ComponentSet, Entity, FallibleSystem, ReadOnlyQuery, SystemError, World, WorldView};
+
+/// Immutable access to the world for a `ReadOnlySystem` reading `I`
+pub struct ReadOnlyWorldView<'w, I> {
+    world: &'w World,
+    _input_phantom: std::marker::PhantomData<I>,
+}
+
+impl<'w, I> ReadOnlyWorldView<'w, I> {
+    /// Create a view of `world`
+    pub fn new(world: &'w World) -> Self {
+        Self {
+            world,
+            _input_phantom: std::marker::PhantomData,
+        }
+    }
+
+    /// Get a component for an entity (if it exists)
+    pub fn get_component<T: 'static>(&self, entity: Entity) -> Option<&'w T> {
+        self.world.check_access(|| vec![ComponentAccess::read::<T>()]);
+        self.world.profile_accesses(|| vec![ComponentAccess::read::<T>()], 1);
+        self.world.get_component::<T>(entity)
+    }
+
+    /// Query entities with multiple components, e.g.
+    /// `world.query::<(In<Position>, Without<Frozen>)>()`
+    pub fn query<Q: ReadOnlyQuery<'w>>(&self) -> Vec<(Entity, Q::Item)> {
+        self.world.check_access(Q::accesses);
+        self.world.select_slice_window(Q::accesses);
+        let results = self.world.query::<Q>();
+        if let Some(counts) = &self.world.query_counts {
+            counts.record(results.len());
+        }
+        self.world.profile_accesses(Q::accesses, results.len());
+        results
+    }
+
+    /// Get all entities that have a specific component type
+    pub fn entities_with_component<T: 'static>(&self) -> Vec<Entity> {
+        self.world.entities_with_component::<T>()
+    }
+
+    /// Get a resource by type
+    pub fn resource<R: 'static>(&self) -> Option<&'w R> {
+        self.world.resource::<R>()
+    }
+
+    /// Get the number of completed update frames
+    pub fn frame_number(&self) -> usize {
+        self.world.frame_number()
+    }
+
+    /// Check if the world is paused
+    pub fn is_paused(&self) -> bool {
+        self.world.is_paused()
+    }
+}
+
+/// A system that only reads components, such as an analysis or render system
+pub trait ReadOnlySystem {
+    /// Components that the system reads
+    type InComponents: ComponentSet;
+
+    /// Called once before the first update to initialize system state
+    fn initialize(&mut self, _world: &ReadOnlyWorldView<Self::InComponents>) {}
+
+    /// Called every frame to update the system
+    fn update(&mut self, world: &ReadOnlyWorldView<Self::InComponents>);
+
+    /// Called by `World::pause`
+    fn on_pause(&mut self, _world: &ReadOnlyWorldView<Self::InComponents>) {}
+
+    /// Called by `World::resume`
+    fn on_resume(&mut self, _world: &ReadOnlyWorldView<Self::InComponents>) {}
+
+    /// Whether the system keeps updating while the world is paused
+    fn run_when_paused(&self) -> bool {
+        false
+    }
+
+    /// Called when the system is being removed or the world is shutting down
+    fn deinitialize(&mut self, _world: &ReadOnlyWorldView<Self::InComponents>) {}
+}
+
+impl<S: ReadOnlySystem> FallibleSystem for S {
+    type InComponents = S::InComponents;
+    type OutComponents = ();
+
+    fn initialize(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+        ReadOnlySystem::initialize(self, &ReadOnlyWorldView::new(unsafe { world.world() }));
+    }
+
+    fn try_update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) -> Result<(), SystemError> {
+        ReadOnlySystem::update(self, &ReadOnlyWorldView::new(unsafe { world.world() }));
+        Ok(())
+    }
+
+    fn on_pause(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+        ReadOnlySystem::on_pause(self, &ReadOnlyWorldView::new(unsafe { world.world() }));
+    }
+
+    fn on_resume(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+        ReadOnlySystem::on_resume(self, &ReadOnlyWorldView::new(unsafe { world.world() }));
+    }
+
+    fn run_when_paused(&self) -> bool {
+        ReadOnlySystem::run_when_paused(self)
+    }
+
+    fn deinitialize(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+        ReadOnlySystem::deinitialize(self, &ReadOnlyWorldView::new(unsafe { world.world() }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{In, Out, System};
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Health(i32);
+
+    struct DamageSystem;
+
+    impl System for DamageSystem {
+        type InComponents = ();
+        type OutComponents = (Health,);
+
+        fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+        fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+            for (_, health) in world.query_components::<(Out<Health>,)>() {
+                health.0 -= 1;
+            }
+        }
+
+        fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    }
+
+    /// Sums the health of all entities every frame
+    struct HealthReportSystem(std::rc::Rc<std::cell::RefCell<Vec<i32>>>);
+
+    impl ReadOnlySystem for HealthReportSystem {
+        type InComponents = (Health,);
+
+        fn update(&mut self, world: &ReadOnlyWorldView<Self::InComponents>) {
+            let total = world.query::<(In<Health>,)>().into_iter().map(|(_, health)| health.0).sum();
+            self.0.borrow_mut().push(total);
+        }
+    }
+
+    #[test]
+    fn test_read_only_system_records_nothing() {
+        let mut world = World::new();
+        for health in [10, 20] {
+            let entity = world.create_entity();
+            world.add_component(entity, Health(health));
+        }
+        let totals = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        world.add_system(DamageSystem);
+        world.add_system(HealthReportSystem(totals.clone()));
+        world.set_system_stats(true);
+        world.update();
+        world.update();
+
+        assert_eq!(*totals.borrow(), vec![28, 26]);
+        let report = &world.get_update_history().updates().last().unwrap().system_diffs()[1];
+        assert!(report.component_changes().is_empty() && report.world_operations().is_empty());
+        assert_eq!(report.stats().map(|stats| stats.entities_matched), Some(2));
+    }
+}
@@ -0,0 +1,214 @@
+//! Read-only queries on a plain `&World`.
+//!
+//! Tools, tests and rendering often only read components. Instead of
+//! building a `WorldView` from a mutable world they can query the world
+//! directly, with the immutable query components `In`, `Changed`, `With`,
+//! `Without` and `IncludeDisabled`:
+//!
+//! ```ignore
+//! for (entity, (position, sprite)) in world.query::<(In<Position>, In<Sprite>)>() {
+//!     draw(sprite, position);
+//! }
+//! ```
+//!
+//! `Out` needs mutable access, so queries with it only compile on a `WorldView`.
+
+use crate::{Changed, ComponentAccess, Entity, In, IncludeDisabled, With, Without, World};
+use std::any::TypeId;
+
+/// Trait for multi-component queries with immutable access
+pub trait ReadOnlyQuery<'a> {
+    type Item;
+
+    /// Get all entities that have all the required components
+    fn query_read_only(world: &'a World) -> Vec<(Entity, Self::Item)>;
+
+    /// Components the query reads, checked in strict access mode
+    fn accesses() -> Vec<ComponentAccess> {
+        Vec::new()
+    }
+}
+
+/// Trait for query components that only read the world
+pub trait ReadOnlyQueryComponent<'a> {
+    type Item;
+
+    /// Whether the query also visits disabled entities, set by `IncludeDisabled`
+    const INCLUDES_DISABLED: bool = false;
+
+    /// Extract the component from the world for a specific entity
+    fn get_read_only_component(world: &'a World, entity: Entity) -> Option<Self::Item>;
+
+    /// Component read through this part of a query; filters access none
+    fn access() -> Option<ComponentAccess> {
+        None
+    }
+
+    /// Component type every entity the query matches has, see `MixedQueryComponent::required`
+    fn required() -> Option<TypeId> {
+        Self::access().map(|access| access.type_id)
+    }
+}
+
+impl<'a, T: 'static> ReadOnlyQueryComponent<'a> for In<T> {
+    type Item = &'a T;
+
+    fn get_read_only_component(world: &'a World, entity: Entity) -> Option<Self::Item> {
+        world.get_component::<T>(entity)
+    }
+
+    fn access() -> Option<ComponentAccess> {
+        Some(ComponentAccess::read::<T>())
+    }
+}
+
+impl<'a, T: 'static> ReadOnlyQueryComponent<'a> for Changed<T> {
+    type Item = &'a T;
+
+    fn get_read_only_component(world: &'a World, entity: Entity) -> Option<Self::Item> {
+        if !world.component_changed_since(TypeId::of::<T>(), entity, world.last_run_tick) {
+            return None;
+        }
+        world.get_component::<T>(entity)
+    }
+
+    fn access() -> Option<ComponentAccess> {
+        Some(ComponentAccess::read::<T>())
+    }
+}
+
+impl<'a, T: 'static> ReadOnlyQueryComponent<'a> for With<T> {
+    type Item = ();
+
+    fn get_read_only_component(world: &'a World, entity: Entity) -> Option<Self::Item> {
+        world.has_component_type(TypeId::of::<T>(), entity).then_some(())
+    }
+
+    fn required() -> Option<TypeId> {
+        Some(TypeId::of::<T>())
+    }
+}
+
+impl<'a, T: 'static> ReadOnlyQueryComponent<'a> for Without<T> {
+    type Item = ();
+
+    fn get_read_only_component(world: &'a World, entity: Entity) -> Option<Self::Item> {
+        (!world.has_component_type(TypeId::of::<T>(), entity)).then_some(())
+    }
+}
+
+impl<'a> ReadOnlyQueryComponent<'a> for IncludeDisabled {
+    type Item = ();
+    const INCLUDES_DISABLED: bool = true;
+
+    fn get_read_only_component(_world: &'a World, _entity: Entity) -> Option<Self::Item> {
+        Some(())
+    }
+}
+
+// Single component queries return the component itself, like `MixedMultiQuery`
+impl<'a, A: ReadOnlyQueryComponent<'a>> ReadOnlyQuery<'a> for (A,) {
+    type Item = A::Item;
+
+    fn accesses() -> Vec<ComponentAccess> {
+        A::access().into_iter().collect()
+    }
+
+    fn query_read_only(world: &'a World) -> Vec<(Entity, Self::Item)> {
+        world
+            .query_candidates(A::INCLUDES_DISABLED, &[A::required()])
+            .into_iter()
+            .filter_map(|entity| Some((entity, A::get_read_only_component(world, entity)?)))
+            .collect()
+    }
+}
+
+macro_rules! impl_read_only_query {
+    ($($component:ident),+) => {
+        impl<'a, $($component),+> ReadOnlyQuery<'a> for ($($component,)+)
+        where
+            $($component: ReadOnlyQueryComponent<'a>),+
+        {
+            type Item = ($($component::Item,)+);
+
+            fn accesses() -> Vec<ComponentAccess> {
+                [$($component::access()),+].into_iter().flatten().collect()
+            }
+
+            #[allow(non_snake_case)]
+            fn query_read_only(world: &'a World) -> Vec<(Entity, Self::Item)> {
+                let include_disabled = false $(|| $component::INCLUDES_DISABLED)+;
+                world
+                    .query_candidates(include_disabled, &[$($component::required()),+])
+                    .into_iter()
+                    .filter_map(|entity| {
+                        $(let $component = $component::get_read_only_component(world, entity)?;)+
+                        Some((entity, ($($component,)+)))
+                    })
+                    .collect()
+            }
+        }
+    };
+}
+
+impl_read_only_query!(A, B);
+impl_read_only_query!(A, B, C);
+impl_read_only_query!(A, B, C, D);
+impl_read_only_query!(A, B, C, D, E);
+impl_read_only_query!(A, B, C, D, E, F);
+impl_read_only_query!(A, B, C, D, E, F, G);
+impl_read_only_query!(A, B, C, D, E, F, G, H);
+impl_read_only_query!(A, B, C, D, E, F, G, H, I);
+impl_read_only_query!(A, B, C, D, E, F, G, H, I, J);
+impl_read_only_query!(A, B, C, D, E, F, G, H, I, J, K);
+impl_read_only_query!(A, B, C, D, E, F, G, H, I, J, K, L);
+impl_read_only_query!(A, B, C, D, E, F, G, H, I, J, K, L, M);
+impl_read_only_query!(A, B, C, D, E, F, G, H, I, J, K, L, M, N);
+impl_read_only_query!(A, B, C, D, E, F, G, H, I, J, K, L, M, N, O);
+impl_read_only_query!(A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P);
+
+impl World {
+    /// Query entities with multiple components without mutable access, e.g.
+    /// `world.query::<(In<Position>, In<Velocity>)>()`
+    ///
+    /// Results are in entity order, like those of `WorldView::query_components`.
+    pub fn query<'w, Q: ReadOnlyQuery<'w>>(&'w self) -> Vec<(Entity, Q::Item)> {
+        Q::query_read_only(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Position(i32);
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Frozen;
+
+    #[test]
+    fn test_query_plain_world() {
+        let mut world = World::new();
+        let entities: Vec<Entity> = (0..3)
+            .map(|x| {
+                let entity = world.create_entity();
+                world.add_component(entity, Position(x));
+                entity
+            })
+            .collect();
+        world.add_component(entities[1], Frozen);
+        world.set_enabled(entities[2], false);
+
+        let world = &world;
+        let moving: Vec<(Entity, &Position)> = world
+            .query::<(In<Position>, Without<Frozen>)>()
+            .into_iter()
+            .map(|(entity, (position, ()))| (entity, position))
+            .collect();
+        assert_eq!(moving, vec![(entities[0], &Position(0))]);
+        assert_eq!(world.query::<(In<Position>, With<Frozen>)>().len(), 1);
+        assert_eq!(world.query::<(In<Position>,)>().len(), 2);
+        assert_eq!(world.query::<(In<Position>, IncludeDisabled)>().len(), 3);
+    }
+}
@@ -0,0 +1,430 @@
+//! Selective recording for replay logs.
+//!
+//! `ReplayLogConfig` can leave component types and systems out of the log and
+//! record only every Nth frame. Sampled logs cannot be replayed diff by diff,
+//! so they should also write keyframes - full world states every few frames
+//! that a replay restores to get back in sync:
+//!
+//! ```ignore
+//! let config = ReplayLogConfig {
+//!     enabled: true,
+//!     components: RecordFilter::excluding(["Sprite"]),
+//!     systems: RecordFilter::excluding(["RenderSystem"]),
+//!     sampling: Some(FrameSampling { every: 10, keyframe_interval: 100 }),
+//!     ..ReplayLogConfig::default()
+//! };
+//! ```
+//!
+//! Component types that change every frame can also record fewer of their
+//! modifications with a `RecordingPolicy`, e.g. only once a position moved
+//! more than one cell:
+//!
+//! ```ignore
+//! world.set_recording_policy::<Position>(RecordingPolicy::on_threshold(1.0));
+//! ```
+//!
+//! Skipped modifications are not lost: the next recorded diff starts from the
+//! last recorded value, so replays reach the same state on recorded frames.
+//! Additions and removals are always recorded.
+
+use crate::reflect::{Reflect, Value};
+use crate::snapshot::WorldSnapshot;
+use crate::{Entity, SystemUpdateDiff, World, WorldUpdateDiff};
+use std::any::{Any, TypeId};
+
+/// Selects recorded component types or systems by name
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RecordFilter {
+    /// Names to record; all names not excluded are recorded if empty
+    pub include: Vec<String>,
+    /// Names never recorded
+    pub exclude: Vec<String>,
+}
+
+impl RecordFilter {
+    /// Record only the given names
+    pub fn only<S: Into<String>>(names: impl IntoIterator<Item = S>) -> Self {
+        Self {
+            include: names.into_iter().map(Into::into).collect(),
+            exclude: Vec::new(),
+        }
+    }
+
+    /// Record everything except the given names
+    pub fn excluding<S: Into<String>>(names: impl IntoIterator<Item = S>) -> Self {
+        Self {
+            include: Vec::new(),
+            exclude: names.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /// Check if the filter records everything
+    pub fn records_all(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    /// Check if a name is recorded. Full type names like `my_game::RenderSystem`
+    /// also match their short name.
+    pub fn records(&self, name: &str) -> bool {
+        let short_name = name.rsplit("::").next().unwrap_or(name);
+        let matches = |names: &[String]| names.iter().any(|n| n == name || n == short_name);
+        (self.include.is_empty() || matches(&self.include)) && !matches(&self.exclude)
+    }
+}
+
+/// Records only some frames of a session
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameSampling {
+    /// Record the first frame and then every Nth frame
+    pub every: usize,
+    /// Write the full world state after every Nth frame, 0 for never
+    pub keyframe_interval: usize,
+}
+
+impl FrameSampling {
+    /// Check if the diff of a 1-based frame number is recorded
+    #[allow(clippy::manual_is_multiple_of)]
+    pub fn records_frame(&self, frame: usize) -> bool {
+        self.every <= 1 || (frame - 1) % self.every == 0
+    }
+
+    /// Check if a keyframe is written after a 1-based frame number
+    #[allow(clippy::manual_is_multiple_of)]
+    pub fn writes_keyframe(&self, frame: usize) -> bool {
+        self.keyframe_interval > 0 && frame % self.keyframe_interval == 0
+    }
+}
+
+/// Which modifications of a component type are recorded
+#[derive(Debug, Clone, Copy, Default)]
+pub enum RecordingPolicy {
+    /// Record every modification
+    #[default]
+    Full,
+    /// Record modifications only in every Nth frame
+    EveryNthFrame(usize),
+    /// Record a modification once the distance from the last recorded value
+    /// reaches the threshold
+    OnThresholdChange {
+        threshold: f64,
+        distance: fn(&Value, &Value) -> f64,
+    },
+    /// Never record modifications
+    Never,
+}
+
+impl RecordingPolicy {
+    /// Record once the numeric fields moved `threshold` away from the last
+    /// recorded value, measured with `numeric_distance`
+    pub fn on_threshold(threshold: f64) -> Self {
+        Self::OnThresholdChange {
+            threshold,
+            distance: numeric_distance,
+        }
+    }
+}
+
+/// Euclidean distance between the numeric fields of two values, e.g. the
+/// `x` and `y` of a position. Other fields are ignored.
+pub fn numeric_distance(a: &Value, b: &Value) -> f64 {
+    fn squared(a: &Value, b: &Value) -> f64 {
+        match (a, b) {
+            (Value::Int(a), Value::Int(b)) => ((a - b) as f64).powi(2),
+            (Value::Float(a), Value::Float(b)) => (a - b).powi(2),
+            (Value::Map(a), Value::Map(b)) => a
+                .iter()
+                .filter_map(|(key, a)| Some(squared(a, b.get(key)?)))
+                .sum(),
+            (Value::List(a), Value::List(b)) => a.iter().zip(b).map(|(a, b)| squared(a, b)).sum(),
+            _ => 0.0,
+        }
+    }
+    squared(a, b).sqrt()
+}
+
+impl World {
+    /// Set which modifications of `T` systems record, registering it for
+    /// reflection if needed
+    pub fn set_recording_policy<T: Reflect>(&mut self, policy: RecordingPolicy) {
+        self.component_registry.register::<T>();
+        self.component_registry
+            .set_recording_policy(TypeId::of::<T>(), policy);
+    }
+
+    /// Decide if a modification of a `T` from `old` to `new` is recorded.
+    ///
+    /// Returns None if it is skipped. Otherwise returns the value the
+    /// recorded diff starts from, if earlier modifications were skipped.
+    #[allow(clippy::manual_is_multiple_of)]
+    pub(crate) fn recorded_modification_base<T: Clone + 'static>(
+        &mut self,
+        entity: Entity,
+        old: &T,
+        new: &T,
+    ) -> Option<Option<T>> {
+        let key = (TypeId::of::<T>(), entity);
+        let Some(registration) = self.component_registry.get_by_type_id(key.0) else {
+            return Some(None);
+        };
+        let record = match registration.recording_policy() {
+            RecordingPolicy::Full => true,
+            RecordingPolicy::Never => return None,
+            RecordingPolicy::EveryNthFrame(every) => every <= 1 || self.frame_number % every == 0,
+            RecordingPolicy::OnThresholdChange { threshold, distance } => {
+                let base: &dyn Any = match self.recording_bases.get(&key) {
+                    Some(base) => base.as_ref(),
+                    None => old,
+                };
+                match (registration.to_value(base), registration.to_value(new)) {
+                    (Some(base), Some(new)) => distance(&base, &new) >= threshold,
+                    _ => true,
+                }
+            }
+        };
+        if record {
+            Some(
+                self.recording_bases
+                    .remove(&key)
+                    .and_then(|base| base.downcast::<T>().ok())
+                    .map(|base| *base),
+            )
+        } else {
+            self.recording_bases
+                .entry(key)
+                .or_insert_with(|| Box::new(old.clone()));
+            None
+        }
+    }
+
+    /// Forget the last recorded value of a component after it was added again
+    pub(crate) fn forget_recording_base(&mut self, type_id: TypeId, entity: Entity) {
+        if !self.recording_bases.is_empty() {
+            self.recording_bases.remove(&(type_id, entity));
+        }
+    }
+}
+
+/// Copy of `update` without the changes of excluded component types and systems.
+///
+/// Excluded systems keep an empty entry, so system indices still match the
+/// world's systems. The world operations of recorded systems are always kept.
+pub(crate) fn filter_update(
+    update: &WorldUpdateDiff,
+    system_names: &[&str],
+    components: &RecordFilter,
+    systems: &RecordFilter,
+) -> WorldUpdateDiff {
+    let mut filtered = update.clone();
+    for (index, system_diff) in filtered.system_diffs.iter_mut().enumerate() {
+        if system_names.get(index).is_some_and(|name| !systems.records(name)) {
+            *system_diff = SystemUpdateDiff::new();
+        } else {
+            system_diff.retain_component_changes(|change| components.records(change.type_name()));
+            system_diff.causes.retain(|cause| components.records(&cause.component));
+        }
+    }
+    filtered
+}
+
+/// Copy of `snapshot` without the components and resources of excluded types
+pub(crate) fn filter_snapshot(snapshot: &WorldSnapshot, components: &RecordFilter) -> WorldSnapshot {
+    let mut filtered = snapshot.clone();
+    filtered
+        .diff
+        .retain_component_changes(|change| components.records(change.type_name()));
+    filtered.resources.retain(|(type_name, _)| components.records(type_name));
+    filtered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay_analysis::{parse_replay_log_str, validate_replay_log_str};
+    use crate::test_util::SharedBuffer;
+    use crate::{ReplayLogConfig, System, World, WorldView};
+
+    struct CountSystem;
+
+    impl System for CountSystem {
+        type InComponents = ();
+        type OutComponents = (u32, u8);
+
+        fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+        fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+            let entity = world.create_entity();
+            world.add_component(entity, 1u32);
+            world.add_component(entity, 2u8);
+            world.record_component_addition(entity, &1u32);
+            world.record_component_addition(entity, &2u8);
+        }
+
+        fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    }
+
+    fn record(config: ReplayLogConfig, frames: usize) -> String {
+        let buffer = SharedBuffer::default();
+        let mut world = World::new();
+        world.add_system(CountSystem);
+        world.enable_replay_logging_to(config, buffer.clone()).unwrap();
+        for _ in 0..frames {
+            world.update();
+        }
+        world.disable_replay_logging().unwrap();
+        buffer.contents()
+    }
+
+    #[test]
+    fn test_filters() {
+        assert!(RecordFilter::default().records("Position"));
+        assert!(RecordFilter::excluding(["RenderSystem"]).records("game::MovementSystem"));
+        assert!(!RecordFilter::excluding(["RenderSystem"]).records("game::RenderSystem"));
+        assert!(!RecordFilter::only(["Position"]).records("Sprite"));
+
+        let log = record(
+            ReplayLogConfig {
+                enabled: true,
+                components: RecordFilter::excluding(["u8"]),
+                ..ReplayLogConfig::default()
+            },
+            2,
+        );
+        assert!(log.contains("ADD Entity(0, 0) u32"), "{}", log);
+        assert!(!log.contains(" u8 "), "{}", log);
+
+        let log = record(
+            ReplayLogConfig {
+                enabled: true,
+                systems: RecordFilter::excluding(["CountSystem"]),
+                ..ReplayLogConfig::default()
+            },
+            2,
+        );
+        assert!(!log.contains("ADD Entity"));
+        assert_eq!(parse_replay_log_str(&log).len(), 2);
+    }
+
+    #[test]
+    fn test_sampled_log_with_keyframes() {
+        let log = record(
+            ReplayLogConfig {
+                enabled: true,
+                sampling: Some(FrameSampling {
+                    every: 3,
+                    keyframe_interval: 4,
+                }),
+                ..ReplayLogConfig::default()
+            },
+            8,
+        );
+        assert!(validate_replay_log_str(&log).is_empty(), "{:?}", validate_replay_log_str(&log));
+
+        // Frames 1, 4 and 7 are recorded, keyframes follow frames 4 and 8
+        let history = parse_replay_log_str(&log);
+        assert_eq!(history.len(), 3);
+        let keyframes: Vec<(usize, usize)> = history
+            .keyframes()
+            .iter()
+            .map(|keyframe| (keyframe.frame, keyframe.update_index))
+            .collect();
+        assert_eq!(keyframes, vec![(4, 2), (8, 3)]);
+        assert_eq!(history.keyframes()[1].state.diff.world_operations().len(), 9);
+    }
+
+    #[derive(Debug, Clone, PartialEq, crate::Diff, crate::Reflect)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    struct Position {
+        x: f32,
+        y: f32,
+    }
+
+    /// Spawns a position in the first update, then moves it 0.4 right every
+    /// update and 0.5 down in the second one
+    struct DriftSystem;
+
+    impl System for DriftSystem {
+        type InComponents = ();
+        type OutComponents = (Position,);
+
+        fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+        fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+            let entities: Vec<Entity> = world
+                .query_components::<(crate::Out<Position>,)>()
+                .into_iter()
+                .map(|(entity, _)| entity)
+                .collect();
+            if entities.is_empty() {
+                let entity = world.create_entity();
+                let position = Position { x: 0.0, y: 0.0 };
+                world.record_component_addition(entity, &position);
+                world.add_component(entity, position);
+            }
+            for entity in entities {
+                let old = world.get_component::<Position>(entity).unwrap().clone();
+                let y = if old.x == 0.4 { old.y + 0.5 } else { old.y };
+                let new = Position { x: old.x + 0.4, y };
+                world.record_component_modification(entity, &old, &new);
+                *world.get_component_mut::<Position>(entity).unwrap() = new;
+            }
+        }
+
+        fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    }
+
+    /// Run the drift system for 7 updates under `policy`
+    fn drift(policy: RecordingPolicy) -> World {
+        let mut world = World::new();
+        #[cfg(feature = "serde")]
+        world.register_replay_component::<Position>();
+        world.set_recording_policy::<Position>(policy);
+        world.add_system(DriftSystem);
+        for _ in 0..7 {
+            world.update();
+        }
+        world
+    }
+
+    /// Frames of the updates that recorded a modification
+    fn modified_frames(world: &World) -> Vec<usize> {
+        let updates = world.get_update_history().updates();
+        (0..updates.len())
+            .filter(|&frame| {
+                updates[frame].system_diffs().iter().any(|diff| {
+                    diff.component_changes()
+                        .iter()
+                        .any(|change| matches!(change, crate::DiffComponentChange::Modified { .. }))
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_recording_policies() {
+        // Update 0 adds the system and update 1 spawns the position
+        assert_eq!(modified_frames(&drift(RecordingPolicy::Full)), vec![2, 3, 4, 5, 6, 7]);
+        assert_eq!(modified_frames(&drift(RecordingPolicy::Never)), Vec::<usize>::new());
+        assert_eq!(modified_frames(&drift(RecordingPolicy::EveryNthFrame(3))), vec![4, 7]);
+        // Recorded once 1.0 away from the last recorded position
+        assert_eq!(modified_frames(&drift(RecordingPolicy::on_threshold(1.0))), vec![4, 7]);
+        assert_eq!(
+            numeric_distance(&Position { x: 0.0, y: 0.0 }.to_value(), &Position { x: 3.0, y: 4.0 }.to_value()),
+            5.0
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_skipped_modifications_are_included_in_the_next_diff() {
+        let world = drift(RecordingPolicy::on_threshold(1.0));
+        let mut replayed = World::new();
+        replayed.register_replay_component::<Position>();
+        for update in world.get_update_history().updates() {
+            replayed.apply_update_diff(update);
+        }
+        // The first recorded diff also holds the move down from a skipped frame
+        let entity = replayed.entities_with_component::<Position>()[0];
+        let position = replayed.get_component::<Position>(entity).unwrap();
+        assert!((position.x - 2.4).abs() < 1e-5 && position.y == 0.5, "{:?}", position);
+    }
+}
@@ -0,0 +1,824 @@
+//! Runtime reflection for components.
+//!
+//! Components implementing `Reflect` (usually through `#[derive(Reflect)]`)
+//! expose their fields by name as dynamically typed `Value`s and can be built
+//! from key/value maps. Registering a component type with the world makes it
+//! accessible by its short type name, so inspectors, scripts and replay can
+//! read, change and create components without knowing their Rust types.
+//!
+//! With the `serde` feature, types registered with
+//! `World::register_replay_component` also record their additions and typed
+//! diffs in RON, which replays decode and apply through `Diff::apply_diff`.
+
+use crate::{Entity, World};
+use std::any::{Any, TypeId};
+use std::collections::{BTreeMap, HashMap};
+
+/// Dynamically typed value of a reflected component or field
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Unit,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    List(Vec<Value>),
+    Map(BTreeMap<String, Value>),
+}
+
+/// Formats values like struct literals, e.g. `{x: 1, y: 2}`
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Unit => write!(f, "()"),
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::Int(value) => write!(f, "{}", value),
+            Value::Float(value) => write!(f, "{:?}", value),
+            Value::String(value) => write!(f, "{:?}", value),
+            Value::List(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            Value::Map(map) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in map.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", key, value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+/// Parses the text written by `Display`, e.g. when replaying recorded values
+impl std::str::FromStr for Value {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut parser = ValueParser { text, position: 0 };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        if parser.position < text.len() {
+            return Err(format!("unexpected `{}` after value", &text[parser.position..]));
+        }
+        Ok(value)
+    }
+}
+
+struct ValueParser<'a> {
+    text: &'a str,
+    position: usize,
+}
+
+impl<'a> ValueParser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.text[self.position..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.position = self.text.len() - self.rest().trim_start().len();
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        self.skip_whitespace();
+        if self.rest().starts_with(expected) {
+            self.position += expected.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        if self.eat(expected) {
+            Ok(())
+        } else {
+            Err(format!("expected `{}` at `{}`", expected, self.rest()))
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        self.skip_whitespace();
+        if self.eat('(') {
+            self.expect(')')?;
+            Ok(Value::Unit)
+        } else if self.eat('[') {
+            let mut items = Vec::new();
+            while !self.eat(']') {
+                if !items.is_empty() {
+                    self.expect(',')?;
+                }
+                items.push(self.value()?);
+            }
+            Ok(Value::List(items))
+        } else if self.eat('{') {
+            let mut map = BTreeMap::new();
+            while !self.eat('}') {
+                if !map.is_empty() {
+                    self.expect(',')?;
+                }
+                self.skip_whitespace();
+                let key_end = self.rest().find(':').ok_or_else(|| format!("expected `key: value` at `{}`", self.rest()))?;
+                let key = self.rest()[..key_end].trim().to_string();
+                self.position += key_end + 1;
+                map.insert(key, self.value()?);
+            }
+            Ok(Value::Map(map))
+        } else if self.rest().starts_with('"') {
+            self.string().map(Value::String)
+        } else {
+            self.scalar()
+        }
+    }
+
+    /// Parse a string escaped like `{:?}` writes it
+    fn string(&mut self) -> Result<String, String> {
+        let mut chars = self.rest().char_indices().skip(1);
+        let mut string = String::new();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.position += i + 1;
+                    return Ok(string);
+                }
+                '\\' => match chars.next().map(|(_, c)| c) {
+                    Some('n') => string.push('\n'),
+                    Some('r') => string.push('\r'),
+                    Some('t') => string.push('\t'),
+                    Some('0') => string.push('\0'),
+                    Some('u') => {
+                        let code: String = chars
+                            .by_ref()
+                            .map(|(_, c)| c)
+                            .skip_while(|c| *c == '{')
+                            .take_while(|c| *c != '}')
+                            .collect();
+                        let c = u32::from_str_radix(&code, 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| format!("invalid unicode escape `{}`", code))?;
+                        string.push(c);
+                    }
+                    Some(c) => string.push(c),
+                    None => break,
+                },
+                c => string.push(c),
+            }
+        }
+        Err(format!("unterminated string `{}`", self.rest()))
+    }
+
+    fn scalar(&mut self) -> Result<Value, String> {
+        let rest = self.rest();
+        let token = &rest[..rest.find([',', ']', '}', ')', ' ']).unwrap_or(rest.len())];
+        self.position += token.len();
+        match token {
+            "true" => Ok(Value::Bool(true)),
+            "false" => Ok(Value::Bool(false)),
+            "NaN" | "inf" | "-inf" => Ok(Value::Float(token.parse().unwrap())),
+            _ if token.contains(['.', 'e', 'E']) => {
+                token.parse().map(Value::Float).map_err(|_| format!("invalid number `{}`", token))
+            }
+            _ => token.parse().map(Value::Int).map_err(|_| format!("invalid value `{}`", token)),
+        }
+    }
+}
+
+/// Types whose fields can be read, written and constructed by name at runtime
+pub trait Reflect: 'static {
+    /// Names of the reflected fields, in declaration order
+    fn field_names() -> &'static [&'static str]
+    where
+        Self: Sized,
+    {
+        &[]
+    }
+
+    /// Get the value of a field
+    fn field(&self, _name: &str) -> Option<Value> {
+        None
+    }
+
+    /// Set the value of a field
+    fn set_field(&mut self, name: &str, _value: &Value) -> Result<(), String> {
+        Err(format!("no field '{}'", name))
+    }
+
+    /// Convert the whole value, fields become map entries
+    fn to_value(&self) -> Value;
+
+    /// Construct a value, e.g. from a map with one entry per field
+    fn from_value(value: &Value) -> Result<Self, String>
+    where
+        Self: Sized;
+}
+
+macro_rules! impl_reflect_int {
+    ($($type:ty),*) => {
+        $(
+            impl Reflect for $type {
+                fn to_value(&self) -> Value {
+                    Value::Int(*self as i64)
+                }
+
+                fn from_value(value: &Value) -> Result<Self, String> {
+                    match value {
+                        Value::Int(value) => <$type>::try_from(*value)
+                            .map_err(|_| format!("{} is out of range for {}", value, stringify!($type))),
+                        _ => Err(format!("expected an integer, found {}", value)),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_reflect_int!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+macro_rules! impl_reflect_float {
+    ($($type:ty),*) => {
+        $(
+            impl Reflect for $type {
+                fn to_value(&self) -> Value {
+                    Value::Float(*self as f64)
+                }
+
+                fn from_value(value: &Value) -> Result<Self, String> {
+                    match value {
+                        Value::Float(value) => Ok(*value as $type),
+                        Value::Int(value) => Ok(*value as $type),
+                        _ => Err(format!("expected a number, found {}", value)),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_reflect_float!(f32, f64);
+
+impl Reflect for bool {
+    fn to_value(&self) -> Value {
+        Value::Bool(*self)
+    }
+
+    fn from_value(value: &Value) -> Result<Self, String> {
+        match value {
+            Value::Bool(value) => Ok(*value),
+            _ => Err(format!("expected a boolean, found {}", value)),
+        }
+    }
+}
+
+impl Reflect for String {
+    fn to_value(&self) -> Value {
+        Value::String(self.clone())
+    }
+
+    fn from_value(value: &Value) -> Result<Self, String> {
+        match value {
+            Value::String(value) => Ok(value.clone()),
+            _ => Err(format!("expected a string, found {}", value)),
+        }
+    }
+}
+
+impl<T: Reflect> Reflect for Vec<T> {
+    fn to_value(&self) -> Value {
+        Value::List(self.iter().map(Reflect::to_value).collect())
+    }
+
+    fn from_value(value: &Value) -> Result<Self, String> {
+        match value {
+            Value::List(items) => items.iter().map(T::from_value).collect(),
+            _ => Err(format!("expected a list, found {}", value)),
+        }
+    }
+}
+
+impl<T: Reflect> Reflect for Option<T> {
+    fn to_value(&self) -> Value {
+        self.as_ref().map_or(Value::Unit, Reflect::to_value)
+    }
+
+    fn from_value(value: &Value) -> Result<Self, String> {
+        match value {
+            Value::Unit => Ok(None),
+            value => T::from_value(value).map(Some),
+        }
+    }
+}
+
+impl<V: Reflect> Reflect for BTreeMap<String, V> {
+    fn to_value(&self) -> Value {
+        Value::Map(self.iter().map(|(key, value)| (key.clone(), value.to_value())).collect())
+    }
+
+    fn from_value(value: &Value) -> Result<Self, String> {
+        match value {
+            Value::Map(entries) => entries
+                .iter()
+                .map(|(key, value)| Ok((key.clone(), V::from_value(value)?)))
+                .collect(),
+            _ => Err(format!("expected a map, found {}", value)),
+        }
+    }
+}
+
+/// Type-erased reflection functions of one registered component type
+#[derive(Debug, Clone)]
+pub struct ComponentRegistration {
+    type_id: TypeId,
+    type_name: &'static str,
+    field_names: &'static [&'static str],
+    to_value: fn(&dyn Any) -> Option<Value>,
+    field: fn(&dyn Any, &str) -> Option<Value>,
+    set_field: fn(&mut dyn Any, &str, &Value) -> Result<(), String>,
+    from_value: fn(&Value) -> Result<Box<dyn Any>, String>,
+    replay: Option<ReplayFunctions>,
+    /// Version of the serialized layout, starting at 1
+    version: u32,
+    /// Migrations from the layout of each older version to the next one
+    migrations: BTreeMap<u32, Migration>,
+    /// Which modifications systems record
+    recording_policy: crate::RecordingPolicy,
+    /// Storage components of the type get unless `World::register_storage` chose one
+    default_storage: crate::StorageKind,
+}
+
+/// Converts a component value from the layout of one version to the next
+pub type Migration = fn(Value) -> Result<Value, String>;
+
+/// Type-erased functions encoding recorded changes of a component type and
+/// applying them during replay
+#[derive(Debug, Clone, Copy)]
+struct ReplayFunctions {
+    encode: fn(&dyn Any) -> Option<String>,
+    /// Encode the diff from the first component to the second, None if they are equal
+    encode_diff: fn(&dyn Any, &dyn Any) -> Option<String>,
+    decode: fn(&str) -> Result<Box<dyn Any>, String>,
+    apply_diff: fn(&mut dyn Any, &str) -> Result<(), String>,
+}
+
+#[cfg(feature = "serde")]
+impl ReplayFunctions {
+    fn of<T>() -> Self
+    where
+        T: Reflect + crate::Diff + serde::Serialize + serde::de::DeserializeOwned,
+        T::Diff: serde::Serialize + serde::de::DeserializeOwned,
+    {
+        Self {
+            encode: |component| ron::to_string(component.downcast_ref::<T>()?).ok(),
+            encode_diff: |old, new| {
+                let diff = old.downcast_ref::<T>()?.diff(new.downcast_ref::<T>()?)?;
+                ron::to_string(&diff).ok()
+            },
+            decode: |data| {
+                ron::from_str::<T>(data)
+                    .map(|component| Box::new(component) as Box<dyn Any>)
+                    .map_err(|e| format!("Invalid {} data '{}': {}", crate::short_type_name::<T>(), data, e))
+            },
+            apply_diff: |component, data| {
+                let name = crate::short_type_name::<T>();
+                let component = component
+                    .downcast_mut::<T>()
+                    .ok_or_else(|| format!("component is not a {}", name))?;
+                let diff = ron::from_str::<T::Diff>(data)
+                    .map_err(|e| format!("Invalid {} diff '{}': {}", name, data, e))?;
+                component.apply_diff(&diff);
+                Ok(())
+            },
+        }
+    }
+}
+
+impl ComponentRegistration {
+    fn of<T: Reflect>() -> Self {
+        Self {
+            type_id: TypeId::of::<T>(),
+            type_name: crate::short_type_name::<T>(),
+            field_names: T::field_names(),
+            to_value: |component| component.downcast_ref::<T>().map(T::to_value),
+            field: |component, name| component.downcast_ref::<T>()?.field(name),
+            set_field: |component, name, value| match component.downcast_mut::<T>() {
+                Some(component) => component.set_field(name, value),
+                None => Err(format!("component is not a {}", crate::short_type_name::<T>())),
+            },
+            from_value: |value| T::from_value(value).map(|component| Box::new(component) as Box<dyn Any>),
+            replay: None,
+            version: 1,
+            migrations: BTreeMap::new(),
+            recording_policy: crate::RecordingPolicy::Full,
+            default_storage: crate::StorageKind::default_for::<T>(),
+        }
+    }
+
+    /// Get the TypeId of the component type
+    pub fn type_id(&self) -> TypeId {
+        self.type_id
+    }
+
+    /// Get the short type name the component is registered under
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    /// Get the names of the reflected fields
+    pub fn field_names(&self) -> &'static [&'static str] {
+        self.field_names
+    }
+
+    /// Convert a component of this type to a value
+    pub fn to_value(&self, component: &dyn Any) -> Option<Value> {
+        (self.to_value)(component)
+    }
+
+    /// Get a field of a component of this type
+    pub fn field(&self, component: &dyn Any, name: &str) -> Option<Value> {
+        (self.field)(component, name)
+    }
+
+    /// Set a field of a component of this type
+    pub fn set_field(&self, component: &mut dyn Any, name: &str, value: &Value) -> Result<(), String> {
+        (self.set_field)(component, name, value)
+    }
+
+    /// Construct a boxed component of this type from a value
+    pub fn from_value(&self, value: &Value) -> Result<Box<dyn Any>, String> {
+        (self.from_value)(value)
+    }
+
+    /// Get the version of the serialized layout, see `World::set_component_version`
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Upgrade a value recorded with the layout of `version` to the current
+    /// layout by running the migrations in order
+    pub fn migrate(&self, mut value: Value, version: u32) -> Result<Value, String> {
+        if version > self.version {
+            return Err(format!(
+                "{} data has version {}, newer than the supported version {}",
+                self.type_name, version, self.version
+            ));
+        }
+        for from in version.max(1)..self.version {
+            let migration = self
+                .migrations
+                .get(&from)
+                .ok_or_else(|| format!("No migration of {} from version {} to {}", self.type_name, from, from + 1))?;
+            value = migration(value).map_err(|e| format!("Migrating {} from version {}: {}", self.type_name, from, e))?;
+        }
+        Ok(value)
+    }
+
+    /// Get which modifications of this type systems record
+    pub fn recording_policy(&self) -> crate::RecordingPolicy {
+        self.recording_policy
+    }
+
+    /// Get the storage components of this type get unless another one is registered
+    pub fn default_storage(&self) -> crate::StorageKind {
+        self.default_storage
+    }
+
+    /// Check if changes of this type are recorded in a form replays can apply
+    pub fn is_replayable(&self) -> bool {
+        self.replay.is_some()
+    }
+
+    /// Encode a component of this type for replay logs
+    pub fn encode(&self, component: &dyn Any) -> Option<String> {
+        (self.replay?.encode)(component)
+    }
+
+    /// Encode the diff between two components of this type for replay logs
+    pub fn encode_diff(&self, old: &dyn Any, new: &dyn Any) -> Option<String> {
+        (self.replay?.encode_diff)(old, new)
+    }
+
+    /// Construct a boxed component of this type from its encoded form
+    pub fn decode(&self, data: &str) -> Result<Box<dyn Any>, String> {
+        (self.replay_functions()?.decode)(data)
+    }
+
+    /// Apply an encoded diff to a component of this type
+    pub fn apply_diff(&self, component: &mut dyn Any, diff: &str) -> Result<(), String> {
+        (self.replay_functions()?.apply_diff)(component, diff)
+    }
+
+    fn replay_functions(&self) -> Result<ReplayFunctions, String> {
+        self.replay
+            .ok_or_else(|| format!("Component type {} is not registered for replay", self.type_name))
+    }
+}
+
+/// Component types registered for reflection, looked up by short type name
+#[derive(Debug, Clone, Default)]
+pub struct ComponentRegistry {
+    registrations: Vec<ComponentRegistration>,
+    by_name: HashMap<&'static str, usize>,
+    /// Companions of component types, see `require`
+    pub(crate) requirements: crate::requirements::Requirements,
+}
+
+impl ComponentRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the component type T; registering a type twice has no effect
+    pub fn register<T: Reflect>(&mut self) {
+        if self.get_by_type_id(TypeId::of::<T>()).is_some() {
+            return;
+        }
+        let registration = ComponentRegistration::of::<T>();
+        self.by_name.insert(registration.type_name, self.registrations.len());
+        self.registrations.push(registration);
+    }
+
+    /// Register the component type T and record its changes so replays can apply them
+    #[cfg(feature = "serde")]
+    pub fn register_replayable<T>(&mut self)
+    where
+        T: Reflect + crate::Diff + serde::Serialize + serde::de::DeserializeOwned,
+        T::Diff: serde::Serialize + serde::de::DeserializeOwned,
+    {
+        self.register::<T>();
+        if let Some(registration) = self.registrations.iter_mut().find(|r| r.type_id == TypeId::of::<T>()) {
+            registration.replay = Some(ReplayFunctions::of::<T>());
+        }
+    }
+
+    /// Set the serialized layout version of a registered type
+    pub(crate) fn set_version(&mut self, type_id: TypeId, version: u32) {
+        if let Some(registration) = self.registrations.iter_mut().find(|r| r.type_id == type_id) {
+            registration.version = version;
+        }
+    }
+
+    /// Add the migration of a registered type from `from_version` to the next version
+    pub(crate) fn add_migration(&mut self, type_id: TypeId, from_version: u32, migration: Migration) {
+        if let Some(registration) = self.registrations.iter_mut().find(|r| r.type_id == type_id) {
+            registration.migrations.insert(from_version, migration);
+        }
+    }
+
+    /// Set which modifications of a registered type systems record
+    pub(crate) fn set_recording_policy(&mut self, type_id: TypeId, policy: crate::RecordingPolicy) {
+        if let Some(registration) = self.registrations.iter_mut().find(|r| r.type_id == type_id) {
+            registration.recording_policy = policy;
+        }
+    }
+
+    /// Get the registration of a component type by its short type name
+    pub fn get(&self, type_name: &str) -> Option<&ComponentRegistration> {
+        self.by_name.get(type_name).map(|index| &self.registrations[*index])
+    }
+
+    /// Get the registration of a component type by its TypeId
+    pub fn get_by_type_id(&self, type_id: TypeId) -> Option<&ComponentRegistration> {
+        self.registrations.iter().find(|registration| registration.type_id == type_id)
+    }
+
+    /// Iterate over all registrations in registration order
+    pub fn iter(&self) -> impl Iterator<Item = &ComponentRegistration> {
+        self.registrations.iter()
+    }
+}
+
+impl World {
+    /// Register a component type for reflection
+    pub fn register_component<T: Reflect>(&mut self) {
+        self.component_registry.register::<T>();
+    }
+
+    /// Register a component type for reflection and replay.
+    ///
+    /// Additions and modifications of registered types are recorded in RON,
+    /// which `apply_update_diff` decodes and applies through `Diff::apply_diff`.
+    /// Both the recording and the replaying world need the registration.
+    #[cfg(feature = "serde")]
+    pub fn register_replay_component<T>(&mut self)
+    where
+        T: Reflect + crate::Diff + serde::Serialize + serde::de::DeserializeOwned,
+        T::Diff: serde::Serialize + serde::de::DeserializeOwned,
+    {
+        self.component_registry.register_replayable::<T>();
+    }
+
+    /// Get the registry of reflected component types
+    pub fn component_registry(&self) -> &ComponentRegistry {
+        &self.component_registry
+    }
+
+    /// Get the registry of reflected component types for declaring requirements
+    pub fn component_registry_mut(&mut self) -> &mut ComponentRegistry {
+        &mut self.component_registry
+    }
+
+    /// Get a registered component of an entity as a value
+    pub fn reflect_component(&self, entity: Entity, type_name: &str) -> Option<Value> {
+        let registration = self.component_registry.get(type_name)?;
+        registration.to_value(self.component_any(registration.type_id, entity)?)
+    }
+
+    /// Get all registered components of an entity, in registration order
+    pub fn reflect_components(&self, entity: Entity) -> Vec<(&'static str, Value)> {
+        self.component_registry
+            .iter()
+            .filter_map(|registration| {
+                let component = self.component_any(registration.type_id, entity)?;
+                Some((registration.type_name, registration.to_value(component)?))
+            })
+            .collect()
+    }
+
+    /// Get one field of a registered component of an entity
+    pub fn component_field(&self, entity: Entity, type_name: &str, field: &str) -> Option<Value> {
+        let registration = self.component_registry.get(type_name)?;
+        registration.field(self.component_any(registration.type_id, entity)?, field)
+    }
+
+    /// Set one field of a registered component of an entity
+    pub fn set_component_field(
+        &mut self,
+        entity: Entity,
+        type_name: &str,
+        field: &str,
+        value: &Value,
+    ) -> Result<(), String> {
+        let registration = self
+            .component_registry
+            .get(type_name)
+            .ok_or_else(|| format!("Unknown component type: {}", type_name))?
+            .clone();
+        let component = self
+            .component_any_mut(registration.type_id, entity)
+            .ok_or_else(|| format!("{} has no {} component", entity, type_name))?;
+        registration.set_field(component, field, value)?;
+        self.flush_observers();
+        Ok(())
+    }
+
+    /// Construct a registered component from a value and add it to an entity,
+    /// replacing a component of the same type
+    pub fn insert_component_value(&mut self, entity: Entity, type_name: &str, value: &Value) -> Result<(), String> {
+        let registration = self
+            .component_registry
+            .get(type_name)
+            .ok_or_else(|| format!("Unknown component type: {}", type_name))?;
+        let component = registration.from_value(value)?;
+        self.insert_component_any(entity, registration.type_id, component);
+        Ok(())
+    }
+
+    /// Add a boxed component to an entity, replacing a component of the same type
+    pub(crate) fn insert_component_any(&mut self, entity: Entity, type_id: TypeId, component: Box<dyn Any>) {
+        if !self.has_component_type(type_id, entity) {
+            self.push_component_any(entity, type_id, component);
+            return;
+        }
+        self.observe_write(type_id, entity);
+        if let Some(components) = self.components.get_mut(&type_id) {
+            components.remove(entity);
+            components.insert(entity, component);
+        }
+        self.mark_component_changed(type_id, entity);
+        self.flush_observers();
+    }
+
+    /// Find the component of an entity in the world's storage
+    pub(crate) fn component_any(&self, type_id: TypeId, entity: Entity) -> Option<&dyn Any> {
+        self.components.get(&type_id)?.get(entity)
+    }
+
+    pub(crate) fn component_any_mut(&mut self, type_id: TypeId, entity: Entity) -> Option<&mut dyn Any> {
+        self.flush_observers();
+        self.observe_write(type_id, entity);
+        let component = self.components.get_mut(&type_id)?.get_mut(entity)?;
+        self.component_ticks.insert((type_id, entity), self.change_tick);
+        Some(component)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Reflect;
+
+    #[derive(Debug, Clone, PartialEq, Reflect)]
+    struct Stats {
+        health: i32,
+        speed: f32,
+        name: String,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Reflect)]
+    enum Mood {
+        Calm,
+        Angry,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Reflect)]
+    struct Marker;
+
+    #[test]
+    fn test_derived_fields() {
+        let mut stats = Stats {
+            health: 10,
+            speed: 1.5,
+            name: "orc".to_string(),
+        };
+        assert_eq!(Stats::field_names(), &["health", "speed", "name"]);
+        assert_eq!(stats.field("health"), Some(Value::Int(10)));
+        assert_eq!(stats.field("missing"), None);
+
+        stats.set_field("health", &Value::Int(7)).unwrap();
+        stats.set_field("speed", &Value::Int(2)).unwrap();
+        assert!(stats.set_field("name", &Value::Int(3)).is_err());
+        assert_eq!(stats.health, 7);
+        assert_eq!(stats.speed, 2.0);
+        assert_eq!(stats.to_value().to_string(), r#"{health: 7, name: "orc", speed: 2.0}"#);
+
+        assert_eq!(Mood::Angry.to_value(), Value::String("Angry".to_string()));
+        assert_eq!(Mood::from_value(&Value::String("Calm".to_string())), Ok(Mood::Calm));
+        assert!(Mood::from_value(&Value::String("Sad".to_string())).is_err());
+        assert_eq!(Marker::from_value(&Marker.to_value()), Ok(Marker));
+    }
+
+    #[test]
+    fn test_construct_from_map() {
+        let mut map = BTreeMap::new();
+        map.insert("health".to_string(), Value::Int(3));
+        map.insert("speed".to_string(), Value::Float(0.5));
+        assert_eq!(
+            Stats::from_value(&Value::Map(map.clone())),
+            Err("missing field Stats.name".to_string())
+        );
+
+        map.insert("name".to_string(), Value::String("elf".to_string()));
+        let stats = Stats::from_value(&Value::Map(map)).unwrap();
+        assert_eq!(stats.name, "elf");
+        assert_eq!(u8::from_value(&Value::Int(300)), Err("300 is out of range for u8".to_string()));
+
+        let counts: BTreeMap<String, u32> = [("wood".to_string(), 3)].into_iter().collect();
+        assert_eq!(counts.to_value().to_string(), "{wood: 3}");
+        assert_eq!(BTreeMap::<String, u32>::from_value(&counts.to_value()), Ok(counts));
+    }
+
+    #[test]
+    fn test_parse_displayed_values() {
+        let mut map = BTreeMap::new();
+        map.insert("name".to_string(), Value::String("say \"hi\"\n\u{7f}".to_string()));
+        map.insert("hits".to_string(), Value::List(vec![Value::Int(-3), Value::Float(1e-7), Value::Unit]));
+        map.insert("alive".to_string(), Value::Bool(true));
+        map.insert("empty".to_string(), Value::Map(BTreeMap::new()));
+        let value = Value::Map(map);
+        assert_eq!(value.to_string().parse(), Ok(value));
+        assert_eq!("inf".parse(), Ok(Value::Float(f64::INFINITY)));
+        assert!("{x: 1".parse::<Value>().is_err());
+        assert!("[1] 2".parse::<Value>().is_err());
+    }
+
+    #[test]
+    fn test_world_reflection() {
+        let mut world = World::new();
+        world.register_component::<Stats>();
+        world.register_component::<Mood>();
+        let entity = world.create_entity();
+        world.add_component(entity, Mood::Calm);
+
+        assert_eq!(world.component_registry().get("Stats").unwrap().field_names().len(), 3);
+        assert_eq!(world.reflect_component(entity, "Stats"), None);
+
+        let value = Stats {
+            health: 5,
+            speed: 1.0,
+            name: "elf".to_string(),
+        }
+        .to_value();
+        world.insert_component_value(entity, "Stats", &value).unwrap();
+        world.set_component_field(entity, "Stats", "health", &Value::Int(9)).unwrap();
+        world
+            .insert_component_value(entity, "Mood", &Value::String("Angry".to_string()))
+            .unwrap();
+
+        assert_eq!(world.get_component::<Stats>(entity).unwrap().health, 9);
+        assert_eq!(world.component_field(entity, "Stats", "health"), Some(Value::Int(9)));
+        assert_eq!(world.get_component::<Mood>(entity), Some(&Mood::Angry));
+        let names: Vec<&str> = world.reflect_components(entity).iter().map(|(name, _)| *name).collect();
+        assert_eq!(names, vec!["Stats", "Mood"]);
+        assert!(world.set_component_field(entity, "Unknown", "x", &Value::Int(1)).is_err());
+    }
+}
@@ -0,0 +1,221 @@
+//! Typed links between entities.
+//!
+//! A relation type is any type naming the link, e.g. `struct Targets;` or
+//! `struct AssignedTo;`. Relating two entities links the source to the
+//! target, and each source may have several targets:
+//!
+//! ```ignore
+//! world.relate::<Targets>(hunter, prey);
+//! assert_eq!(world.relations::<Targets>(hunter), vec![prey]);
+//! assert_eq!(world.relation_sources::<Targets>(prey), vec![hunter]);
+//! ```
+//!
+//! Relation changes are world operations, so they are recorded in the
+//! history and replay logs (`RELATE Entity(0, 1) Targets Entity(0, 2)`) and
+//! applied again by replays, which know relations by their type name.
+//! Removing either entity drops its relations without recording more
+//! operations, as replaying the removal drops them too.
+
+use crate::{short_type_name, Entity, World, WorldOperation, WorldView};
+use std::collections::BTreeSet;
+
+impl World {
+    /// Link the source entity to the target with the relation R, recording
+    /// the change in the history. Returns false if they were already linked
+    /// or either entity does not exist.
+    pub fn relate<R: 'static>(&mut self, source: Entity, target: Entity) -> bool {
+        let related = self.insert_relation(source, short_type_name::<R>(), target);
+        if related {
+            self.record_standalone_operation(WorldOperation::Relate(source, short_type_name::<R>().to_string(), target));
+        }
+        related
+    }
+
+    /// Unlink the source entity from the target, recording the change in the
+    /// history. Returns false if they were not linked.
+    pub fn unrelate<R: 'static>(&mut self, source: Entity, target: Entity) -> bool {
+        let unrelated = self.take_relation(source, short_type_name::<R>(), target);
+        if unrelated {
+            self.record_standalone_operation(WorldOperation::Unrelate(source, short_type_name::<R>().to_string(), target));
+        }
+        unrelated
+    }
+
+    /// Get the targets the source entity is linked to with the relation R, in entity order
+    pub fn relations<R: 'static>(&self, source: Entity) -> Vec<Entity> {
+        self.relations_named(short_type_name::<R>())
+            .filter(|(from, _)| *from == source)
+            .map(|(_, to)| to)
+            .collect()
+    }
+
+    /// Get the sources linked to the target entity with the relation R, in entity order
+    pub fn relation_sources<R: 'static>(&self, target: Entity) -> Vec<Entity> {
+        self.relations_named(short_type_name::<R>())
+            .filter(|(_, to)| *to == target)
+            .map(|(from, _)| from)
+            .collect()
+    }
+
+    /// Check if the source entity is linked to the target with the relation R
+    pub fn is_related<R: 'static>(&self, source: Entity, target: Entity) -> bool {
+        self.relations
+            .get(short_type_name::<R>())
+            .is_some_and(|links| links.contains(&(source, target)))
+    }
+
+    fn relations_named(&self, relation: &str) -> impl Iterator<Item = (Entity, Entity)> + '_ {
+        self.relations.get(relation).into_iter().flatten().copied()
+    }
+
+    /// Link two entities without recording it
+    pub(crate) fn insert_relation(&mut self, source: Entity, relation: &str, target: Entity) -> bool {
+        for entity in [source, target] {
+            if !self.entity_exists(entity) {
+                ecs_warn!("Cannot relate {} with {}: {} does not exist", source, relation, entity);
+                return false;
+            }
+        }
+        match self.relations.get_mut(relation) {
+            Some(links) => links.insert((source, target)),
+            None => {
+                self.relations.insert(relation.to_string(), BTreeSet::from([(source, target)]));
+                true
+            }
+        }
+    }
+
+    /// Unlink two entities without recording it
+    pub(crate) fn take_relation(&mut self, source: Entity, relation: &str, target: Entity) -> bool {
+        let Some(links) = self.relations.get_mut(relation) else {
+            return false;
+        };
+        let removed = links.remove(&(source, target));
+        if links.is_empty() {
+            self.relations.remove(relation);
+        }
+        removed
+    }
+
+    /// Drop the relations from and to a removed entity
+    pub(crate) fn drop_relations(&mut self, entity: Entity) {
+        self.relations.retain(|_, links| {
+            links.retain(|(source, target)| *source != entity && *target != entity);
+            !links.is_empty()
+        });
+    }
+}
+
+impl<I, O> WorldView<I, O> {
+    /// Link the source entity to the target, recording the change in the system's diff
+    pub fn relate<R: 'static>(&mut self, source: Entity, target: Entity) -> bool {
+        let related = unsafe { self.world_mut() }.insert_relation(source, short_type_name::<R>(), target);
+        if related {
+            self.system_diff
+                .record_world_operation(WorldOperation::Relate(source, short_type_name::<R>().to_string(), target));
+        }
+        related
+    }
+
+    /// Unlink the source entity from the target, recording the change in the system's diff
+    pub fn unrelate<R: 'static>(&mut self, source: Entity, target: Entity) -> bool {
+        let unrelated = unsafe { self.world_mut() }.take_relation(source, short_type_name::<R>(), target);
+        if unrelated {
+            self.system_diff
+                .record_world_operation(WorldOperation::Unrelate(source, short_type_name::<R>().to_string(), target));
+        }
+        unrelated
+    }
+
+    /// Get the targets the source entity is linked to with the relation R
+    pub fn relations<R: 'static>(&self, source: Entity) -> Vec<Entity> {
+        unsafe { self.world() }.relations::<R>(source)
+    }
+
+    /// Get the sources linked to the target entity with the relation R
+    pub fn relation_sources<R: 'static>(&self, target: Entity) -> Vec<Entity> {
+        unsafe { self.world() }.relation_sources::<R>(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay_analysis::{parse_replay_log_str, validate_replay_log_str, write_replay_log};
+    use crate::{In, System};
+
+    struct Hunter;
+    struct Targets;
+    struct Carries;
+
+    /// Hunters give up on prey that is carried by someone
+    struct GiveUpSystem;
+
+    impl System for GiveUpSystem {
+        type InComponents = ();
+        type OutComponents = ();
+
+        fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+        fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+            let hunters: Vec<Entity> = world.query_components::<(In<Hunter>,)>().into_iter().map(|(entity, _)| entity).collect();
+            for hunter in hunters {
+                for prey in world.relations::<Targets>(hunter) {
+                    if !world.relation_sources::<Carries>(prey).is_empty() {
+                        world.unrelate::<Targets>(hunter, prey);
+                    }
+                }
+            }
+        }
+
+        fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    }
+
+    #[test]
+    fn test_relations_are_recorded_and_replayed() {
+        let mut world = World::new();
+        let [hunter, prey, carrier, rabbit] = [(); 4].map(|_| world.create_entity());
+        world.add_component(hunter, Hunter);
+        assert!(world.relate::<Targets>(hunter, prey));
+        assert!(world.relate::<Targets>(hunter, rabbit));
+        assert!(!world.relate::<Targets>(hunter, prey));
+        world.relate::<Carries>(carrier, prey);
+        assert_eq!(world.relations::<Targets>(hunter), vec![prey, rabbit]);
+        assert_eq!(world.relation_sources::<Targets>(prey), vec![hunter]);
+        assert!(world.relations::<Carries>(hunter).is_empty());
+
+        world.add_system(GiveUpSystem);
+        world.update();
+        assert_eq!(world.relations::<Targets>(hunter), vec![rabbit]);
+        assert!(world.is_related::<Carries>(carrier, prey));
+
+        let mut log = Vec::new();
+        write_replay_log(world.get_update_history(), &mut log).unwrap();
+        let log = String::from_utf8(log).unwrap();
+        assert!(log.contains("UNRELATE Entity(0, 0) Targets Entity(0, 1)"), "{}", log);
+        assert!(validate_replay_log_str(&log).is_empty(), "{:?}", validate_replay_log_str(&log));
+
+        let mut replayed = World::new();
+        for _ in 0..4 {
+            replayed.create_entity();
+        }
+        replayed.add_component(hunter, Hunter);
+        for update in parse_replay_log_str(&log).updates() {
+            replayed.apply_update_diff(update);
+        }
+        assert_eq!(replayed.relations::<Targets>(hunter), vec![rabbit]);
+        assert_eq!(replayed.relation_sources::<Carries>(prey), vec![carrier]);
+    }
+
+    #[test]
+    fn test_removed_entities_lose_their_relations() {
+        let mut world = World::new();
+        let [hunter, prey, other] = [(); 3].map(|_| world.create_entity());
+        world.relate::<Targets>(hunter, prey);
+        world.relate::<Targets>(other, hunter);
+        world.remove_entity(hunter);
+        assert!(world.relation_sources::<Targets>(prey).is_empty());
+        assert!(world.relations::<Targets>(other).is_empty());
+        assert!(!world.relate::<Targets>(other, hunter));
+    }
+}
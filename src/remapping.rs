@@ -0,0 +1,203 @@
+//! Applying recorded entities to a world that already has its own.
+//!
+//! Recorded diffs name entities by the IDs they had in the recording world.
+//! Applied to a world that already has entities, those IDs point at the wrong
+//! ones and `apply_update_diff` silently mixes the recording into them. With
+//! an `EntityRemapper` set, every recorded entity gets a fresh entity of the
+//! target world the first time it appears, and the same one afterwards:
+//!
+//! ```ignore
+//! level.set_entity_remapper(EntityRemapper::new());
+//! for update in recording.updates() {
+//!     level.apply_update_diff(update);
+//! }
+//! let spawned = level.entity_remapper().unwrap().get(recorded_player);
+//! ```
+//!
+//! Entity operations and the targets of component changes are remapped.
+//! Entities stored inside component values, like `Parent`, keep their
+//! recorded IDs.
+
+use crate::{Entity, World, WorldOperation};
+use std::collections::HashMap;
+
+/// Recorded entities and the entities of the target world they were applied to
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EntityRemapper {
+    entities: HashMap<Entity, Entity>,
+}
+
+impl EntityRemapper {
+    /// Create a remapper with no entities mapped yet
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Map a recorded entity to an existing entity of the target world,
+    /// instead of a fresh one
+    pub fn insert(&mut self, recorded: Entity, target: Entity) {
+        self.entities.insert(recorded, target);
+    }
+
+    /// Get the entity a recorded entity was applied to
+    pub fn get(&self, recorded: Entity) -> Option<Entity> {
+        self.entities.get(&recorded).copied()
+    }
+
+    /// Get the mapped recorded and target entities, in recorded entity order
+    pub fn entities(&self) -> Vec<(Entity, Entity)> {
+        let mut entities: Vec<(Entity, Entity)> = self.entities.iter().map(|(recorded, target)| (*recorded, *target)).collect();
+        entities.sort();
+        entities
+    }
+
+    /// Get the number of mapped entities
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    /// Check if no entity is mapped
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+}
+
+impl World {
+    /// Map the entities of diffs applied with `apply_update_diff` to fresh
+    /// entities of this world, keeping the mapping across updates
+    pub fn set_entity_remapper(&mut self, remapper: EntityRemapper) {
+        self.entity_remapper = Some(remapper);
+    }
+
+    /// Get the entity mapping of applied diffs, if remapping is enabled
+    pub fn entity_remapper(&self) -> Option<&EntityRemapper> {
+        self.entity_remapper.as_ref()
+    }
+
+    /// Stop remapping applied entities and return the mapping
+    pub fn take_entity_remapper(&mut self) -> Option<EntityRemapper> {
+        self.entity_remapper.take()
+    }
+
+    /// Get the entity a recorded entity is applied to, creating a fresh one
+    /// the first time it appears
+    pub(crate) fn remap_entity(&mut self, recorded: Entity) -> Entity {
+        let Some(remapper) = &self.entity_remapper else {
+            return recorded;
+        };
+        if let Some(target) = remapper.get(recorded) {
+            return target;
+        }
+        let target = self.create_entity();
+        if let Some(remapper) = &mut self.entity_remapper {
+            remapper.insert(recorded, target);
+        }
+        target
+    }
+
+    /// Get a recorded operation with its entity remapped
+    pub(crate) fn remap_operation(&mut self, operation: &WorldOperation) -> WorldOperation {
+        match operation {
+            WorldOperation::CreateEntity(entity) => WorldOperation::CreateEntity(self.remap_entity(*entity)),
+            WorldOperation::RemoveEntity(entity) => WorldOperation::RemoveEntity(self.remap_entity(*entity)),
+            WorldOperation::AddToGroup(entity, group) => WorldOperation::AddToGroup(self.remap_entity(*entity), group.clone()),
+            WorldOperation::RemoveFromGroup(entity, group) => {
+                WorldOperation::RemoveFromGroup(self.remap_entity(*entity), group.clone())
+            }
+            WorldOperation::Relate(source, relation, target) => {
+                WorldOperation::Relate(self.remap_entity(*source), relation.clone(), self.remap_entity(*target))
+            }
+            WorldOperation::Unrelate(source, relation, target) => {
+                WorldOperation::Unrelate(self.remap_entity(*source), relation.clone(), self.remap_entity(*target))
+            }
+            WorldOperation::DisableEntity(entity) => WorldOperation::DisableEntity(self.remap_entity(*entity)),
+            WorldOperation::EnableEntity(entity) => WorldOperation::EnableEntity(self.remap_entity(*entity)),
+            WorldOperation::CreateWorld(_)
+            | WorldOperation::RemoveWorld(_)
+            | WorldOperation::AddSystem(_)
+            | WorldOperation::ClearEntities
+            | WorldOperation::ClearAll
+            | WorldOperation::ResetHistory => operation.clone(),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+    use crate::{Diff, Out, Reflect, System, WorldView};
+
+    #[derive(Debug, Clone, PartialEq, Diff, Reflect, serde::Serialize, serde::Deserialize)]
+    struct Health {
+        points: i32,
+    }
+
+    /// Spawns two entities in the first update and damages them afterwards
+    struct DamageSystem;
+
+    impl System for DamageSystem {
+        type InComponents = ();
+        type OutComponents = (Health,);
+
+        fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+        fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+            let entities: Vec<Entity> = world
+                .query_components::<(Out<Health>,)>()
+                .into_iter()
+                .map(|(entity, _)| entity)
+                .collect();
+            if entities.is_empty() {
+                for points in [10, 20] {
+                    let entity = world.create_entity();
+                    let health = Health { points };
+                    world.record_component_addition(entity, &health);
+                    world.add_component(entity, health);
+                }
+            }
+            for entity in entities {
+                let old = world.get_component::<Health>(entity).unwrap().clone();
+                let new = Health { points: old.points - 1 };
+                world.record_component_modification(entity, &old, &new);
+                *world.get_component_mut::<Health>(entity).unwrap() = new;
+            }
+        }
+
+        fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    }
+
+    #[test]
+    fn test_recorded_entities_are_applied_to_fresh_entities() {
+        let mut recording = World::new();
+        recording.register_replay_component::<Health>();
+        recording.add_system(DamageSystem);
+        recording.update();
+        recording.update();
+        let recorded = recording.entities_with_component::<Health>();
+
+        // The target already has entities with the recorded IDs
+        let mut level = World::new();
+        level.register_replay_component::<Health>();
+        level.register_system_factory(|| DamageSystem);
+        let existing: Vec<Entity> = (0..3).map(|_| level.create_entity()).collect();
+        for entity in &existing {
+            level.add_component(*entity, Health { points: 100 });
+        }
+        level.set_entity_remapper(EntityRemapper::new());
+        for update in recording.get_update_history().updates() {
+            level.apply_update_diff(update);
+        }
+
+        let remapper = level.take_entity_remapper().unwrap();
+        assert_eq!(remapper.len(), 2);
+        for (entity, points) in recorded.iter().zip([9, 19]) {
+            let target = remapper.get(*entity).unwrap();
+            assert!(!existing.contains(&target));
+            assert_eq!(level.get_component::<Health>(target), Some(&Health { points }));
+        }
+        for entity in &existing {
+            assert_eq!(level.get_component::<Health>(*entity), Some(&Health { points: 100 }));
+        }
+        assert_eq!(remapper.entities()[0], (recorded[0], remapper.get(recorded[0]).unwrap()));
+    }
+}
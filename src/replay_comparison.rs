@@ -0,0 +1,219 @@
+//! Side by side playback of two recorded sessions.
+//!
+//! To check that a refactor did not change the simulation, record a session
+//! before and after it and play both in lockstep. A `ReplayComparison` steps
+//! two `ReplayPlayer`s together and compares the states of their worlds after
+//! every frame:
+//!
+//! ```ignore
+//! let mut comparison = ReplayComparison::new(before, after, new_replay_world)?;
+//! if let Some(divergence) = comparison.find_divergence() {
+//!     println!("Sessions diverge at frame {}", divergence.frame);
+//!     for difference in &divergence.differences {
+//!         println!("  {}", difference);
+//!     }
+//! }
+//! ```
+//!
+//! States are compared through snapshots, so only component and resource
+//! types registered for replay take part. When one session is shorter, its
+//! world stays at its last frame while the other one keeps playing.
+
+use crate::snapshot::SnapshotDifference;
+use crate::{ReplayPlayer, World, WorldUpdateHistory};
+
+/// State differences between the two sessions after a frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameStateDifference {
+    pub frame: usize,
+    /// Differences from the first session's world to the second's
+    pub differences: Vec<SnapshotDifference>,
+}
+
+/// Plays two histories in lockstep
+pub struct ReplayComparison {
+    first: ReplayPlayer,
+    second: ReplayPlayer,
+}
+
+impl ReplayComparison {
+    /// Create a comparison positioned before the first frame of both sessions.
+    ///
+    /// `new_world` must register every component type and system factory
+    /// either history uses, like for `ReplayPlayer::new`.
+    pub fn new(
+        first: WorldUpdateHistory,
+        second: WorldUpdateHistory,
+        new_world: impl Fn() -> World + Clone + 'static,
+    ) -> Result<Self, String> {
+        Ok(Self {
+            first: ReplayPlayer::new(first, new_world.clone()).map_err(|e| format!("First session: {}", e))?,
+            second: ReplayPlayer::new(second, new_world).map_err(|e| format!("Second session: {}", e))?,
+        })
+    }
+
+    /// Get the player of the first session
+    pub fn first(&self) -> &ReplayPlayer {
+        &self.first
+    }
+
+    /// Get the player of the second session
+    pub fn second(&self) -> &ReplayPlayer {
+        &self.second
+    }
+
+    /// Number of frames applied so far
+    pub fn frame(&self) -> usize {
+        self.first.frame().max(self.second.frame())
+    }
+
+    /// Number of frames of the longer session
+    pub fn frame_count(&self) -> usize {
+        self.first.frame_count().max(self.second.frame_count())
+    }
+
+    /// Check if both sessions have been played to the end
+    pub fn is_finished(&self) -> bool {
+        self.first.is_finished() && self.second.is_finished()
+    }
+
+    /// Apply the next frame of both sessions, returning false if neither has one
+    pub fn step(&mut self) -> bool {
+        let first = self.first.step();
+        let second = self.second.step();
+        first || second
+    }
+
+    /// Bring both sessions to the state after `frame` frames
+    pub fn seek(&mut self, frame: usize) {
+        self.first.seek(frame);
+        self.second.seek(frame);
+    }
+
+    /// Compare the worlds of both sessions at the current frame
+    pub fn differences(&self) -> Vec<SnapshotDifference> {
+        self.first.world().snapshot().diff(&self.second.world().snapshot())
+    }
+
+    /// Play both sessions from the current frame until their states differ.
+    /// Returns None if they stay the same to the end.
+    pub fn find_divergence(&mut self) -> Option<FrameStateDifference> {
+        loop {
+            let differences = self.differences();
+            if !differences.is_empty() {
+                return Some(FrameStateDifference {
+                    frame: self.frame(),
+                    differences,
+                });
+            }
+            if !self.step() {
+                return None;
+            }
+        }
+    }
+
+    /// Play both sessions from the current frame to the end, collecting the
+    /// differences of every frame at which their states differ
+    pub fn all_differences(&mut self) -> Vec<FrameStateDifference> {
+        let mut frames = Vec::new();
+        loop {
+            let differences = self.differences();
+            if !differences.is_empty() {
+                frames.push(FrameStateDifference {
+                    frame: self.frame(),
+                    differences,
+                });
+            }
+            if !self.step() {
+                return frames;
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+    use crate::{Diff, Out, Reflect, System, WorldView};
+
+    #[derive(Debug, Clone, Copy, PartialEq, Diff, Reflect, serde::Serialize, serde::Deserialize)]
+    struct Counter {
+        value: i32,
+    }
+
+    /// Adds `step` to every counter, and `step + 1` from `changed_at` on
+    struct CountSystem {
+        step: i32,
+        changed_at: usize,
+        frame: usize,
+    }
+
+    impl System for CountSystem {
+        type InComponents = ();
+        type OutComponents = (Counter,);
+
+        fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+        fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+            self.frame += 1;
+            let step = if self.frame >= self.changed_at { self.step + 1 } else { self.step };
+            let mut changes = Vec::new();
+            for (entity, counter) in world.query_components::<(Out<Counter>,)>() {
+                let old = *counter;
+                counter.value += step;
+                changes.push((entity, old, *counter));
+            }
+            for (entity, old, new) in changes {
+                world.record_component_modification(entity, &old, &new);
+            }
+        }
+
+        fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    }
+
+    fn new_world() -> World {
+        let mut world = World::new();
+        world.register_replay_component::<Counter>();
+        world
+    }
+
+    fn record(changed_at: usize) -> WorldUpdateHistory {
+        let mut world = new_world();
+        let entity = world.create_entity();
+        world.add_component(entity, Counter { value: 0 });
+        let initial_state = world.snapshot();
+        world.add_system(CountSystem {
+            step: 1,
+            changed_at,
+            frame: 0,
+        });
+        world.reset_history();
+        for _ in 0..5 {
+            world.update();
+        }
+        let mut history = world.get_update_history().clone();
+        history.set_initial_state(initial_state);
+        history
+    }
+
+    #[test]
+    fn test_lockstep_playback_finds_divergence() {
+        let mut same = ReplayComparison::new(record(usize::MAX), record(usize::MAX), new_world).unwrap();
+        assert_eq!(same.find_divergence(), None);
+        assert!(same.is_finished());
+
+        let mut comparison = ReplayComparison::new(record(usize::MAX), record(3), new_world).unwrap();
+        let divergence = comparison.find_divergence().unwrap();
+        // The reset is the first frame, the third update the fourth
+        assert_eq!(divergence.frame, 4);
+        assert_eq!(
+            divergence.differences.iter().map(|d| d.to_string()).collect::<Vec<_>>(),
+            vec!["Entity(0, 0) Counter: (value:3) -> (value:4)"]
+        );
+        assert_eq!(comparison.all_differences().len(), 3);
+
+        comparison.seek(2);
+        assert_eq!(comparison.frame(), 2);
+        assert!(comparison.differences().is_empty());
+    }
+}
@@ -0,0 +1,197 @@
+//! Session metadata and annotations that make replay logs navigable.
+//!
+//! Metadata describes the build and settings a session was recorded with and
+//! is written to the header of replay logs. Annotations are timestamped
+//! markers added with `World::annotate_replay`, written into the frame they
+//! were added in:
+//!
+//! ```text
+//! # Game Version: 0.1.0
+//! # Seed: 42
+//! # Config Hash: 5b6e1c0a7f3d9e24
+//! # Component Version: Position 2
+//!
+//! UPDATE 12
+//!   ANNOTATION 5730ms wave 2 started
+//! SYSTEMS: 2
+//! ```
+
+use crate::World;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::time::Duration;
+
+/// Build and settings a session was recorded with
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReplayMetadata {
+    pub game_version: Option<String>,
+    /// Seed of the random number generator the session was played with
+    pub seed: Option<u64>,
+    /// Hash identifying the game configuration, see `hash_config`
+    pub config_hash: Option<String>,
+    /// Serialized layout versions of the component types above version 1,
+    /// see `World::set_component_version`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub component_versions: BTreeMap<String, u32>,
+}
+
+impl ReplayMetadata {
+    /// Check if no metadata is set
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Hash a configuration for `config_hash`. Uses FNV-1a, so the hash is
+    /// the same across builds and platforms.
+    pub fn hash_config(config: &str) -> String {
+        let hash = config.bytes().fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+        });
+        format!("{:016x}", hash)
+    }
+
+    /// Read a header comment line like "Seed: 42", returning false if it is not metadata
+    pub(crate) fn parse_header_line(&mut self, line: &str) -> bool {
+        let Some((key, value)) = line.split_once(": ") else {
+            return false;
+        };
+        match key {
+            "Game Version" => self.game_version = Some(value.to_string()),
+            "Seed" => match value.parse() {
+                Ok(seed) => self.seed = Some(seed),
+                Err(_) => return false,
+            },
+            "Config Hash" => self.config_hash = Some(value.to_string()),
+            "Component Version" => match value.split_once(' ').map(|(name, version)| (name, version.parse())) {
+                Some((name, Ok(version))) => {
+                    self.component_versions.insert(name.to_string(), version);
+                }
+                _ => return false,
+            },
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// Write the metadata as header comment lines of a replay log
+pub(crate) fn write_metadata_header<W: Write + ?Sized>(writer: &mut W, metadata: &ReplayMetadata) -> std::io::Result<()> {
+    if let Some(game_version) = &metadata.game_version {
+        writeln!(writer, "# Game Version: {}", game_version)?;
+    }
+    if let Some(seed) = metadata.seed {
+        writeln!(writer, "# Seed: {}", seed)?;
+    }
+    if let Some(config_hash) = &metadata.config_hash {
+        writeln!(writer, "# Config Hash: {}", config_hash)?;
+    }
+    for (type_name, version) in &metadata.component_versions {
+        writeln!(writer, "# Component Version: {} {}", type_name, version)?;
+    }
+    Ok(())
+}
+
+/// Marker added to a frame with `World::annotate_replay`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReplayAnnotation {
+    /// Time since the world was created
+    pub elapsed: Duration,
+    pub text: String,
+}
+
+impl std::fmt::Display for ReplayAnnotation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ANNOTATION {}ms {}", self.elapsed.as_millis(), self.text)
+    }
+}
+
+/// Parse an annotation from a string like "5730ms wave 2 started"
+pub(crate) fn parse_annotation(input: &str) -> Option<ReplayAnnotation> {
+    let (elapsed, text) = input.split_once(' ')?;
+    let elapsed = elapsed.strip_suffix("ms")?.parse::<u64>().ok()?;
+    Some(ReplayAnnotation {
+        elapsed: Duration::from_millis(elapsed),
+        text: text.to_string(),
+    })
+}
+
+impl World {
+    /// Add a marker to the current frame of the replay log, e.g. "wave 2 started".
+    ///
+    /// The marker is stored with the frame when it completes, so annotations
+    /// added between updates belong to the next frame.
+    pub fn annotate_replay(&mut self, text: impl Into<String>) {
+        let text = text.into().replace('\n', " ");
+        self.pending_annotations.push(ReplayAnnotation {
+            elapsed: self.now().saturating_sub(self.created_at),
+            text,
+        });
+    }
+
+    /// Set the metadata written to the header of replay logs. Must be set
+    /// before replay logging is enabled to appear in the log. The component
+    /// versions are always those of the world's registry.
+    pub fn set_replay_metadata(&mut self, mut metadata: ReplayMetadata) {
+        metadata.component_versions = self.component_versions();
+        if self.replay_logger.is_some() {
+            ecs_warn!("Replay metadata set after replay logging was enabled; it is not in the current log");
+        }
+        self.world_update_history.set_metadata(metadata);
+    }
+
+    /// Get the metadata written to the header of replay logs
+    pub fn replay_metadata(&self) -> &ReplayMetadata {
+        self.world_update_history.metadata()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay_analysis::{parse_replay_log_str, validate_replay_log_str, write_replay_log};
+
+    #[test]
+    fn test_annotations_and_metadata_survive_replay_log() {
+        let mut world = World::new();
+        world.set_replay_metadata(ReplayMetadata {
+            game_version: Some("1.2.0".to_string()),
+            seed: Some(42),
+            config_hash: Some(ReplayMetadata::hash_config("grid=10")),
+            ..ReplayMetadata::default()
+        });
+        world.update();
+        world.annotate_replay("wave 2\nstarted");
+        world.update();
+        world.update();
+
+        let history = world.get_update_history();
+        assert_eq!(history.annotations().len(), 1);
+        assert_eq!(history.annotations()[0].0, 1);
+
+        let mut log = Vec::new();
+        write_replay_log(history, &mut log).unwrap();
+        let log = String::from_utf8(log).unwrap();
+        assert!(log.contains("# Seed: 42"));
+        assert!(validate_replay_log_str(&log).is_empty());
+
+        let parsed = parse_replay_log_str(&log);
+        assert_eq!(parsed.metadata(), world.replay_metadata());
+        let annotations = parsed.annotations();
+        assert_eq!(annotations.len(), 1);
+        assert_eq!(annotations[0].0, 1);
+        assert_eq!(annotations[0].1.text, "wave 2 started");
+        assert_eq!(
+            annotations[0].1.elapsed.as_millis(),
+            history.annotations()[0].1.elapsed.as_millis()
+        );
+    }
+
+    #[test]
+    fn test_config_hash_is_stable() {
+        assert_eq!(ReplayMetadata::hash_config(""), "cbf29ce484222325");
+        assert_ne!(ReplayMetadata::hash_config("grid=10"), ReplayMetadata::hash_config("grid=11"));
+        assert!(ReplayMetadata::default().is_empty());
+    }
+}
@@ -0,0 +1,398 @@
+//! Frame by frame playback of a recorded session.
+//!
+//! A `ReplayPlayer` applies the updates of a `WorldUpdateHistory` to a world
+//! rebuilt from the history's initial state. It tracks the playback speed and
+//! whether playback is paused, steps one frame at a time and jumps to any
+//! frame; jumping backwards rebuilds the world and applies the frames up to
+//! the target again. Keyframes of sampled logs are restored when playback
+//! reaches them and serve as starting points for jumps. The caller drives
+//! playback and waits `frame_delay` between steps:
+//!
+//! ```ignore
+//! let mut player = ReplayPlayer::new(history, new_game_world)?;
+//! player.set_speed(ReplayPlayer::parse_speed("4x")?);
+//! while player.step() {
+//!     std::thread::sleep(player.frame_delay());
+//! }
+//! ```
+//!
+//! To take over from a recording, `branch_at` returns a live fork of the
+//! world at a frame. Its systems are created again by the factories
+//! `new_world` registered, so updating it continues the simulation from
+//! there while the player stays untouched:
+//!
+//! ```ignore
+//! let mut live = player.branch_at(120)?;
+//! live.get_component_mut::<Health>(boss).unwrap().current = 1;
+//! live.update();
+//! ```
+
+use crate::{Narrator, World, WorldUpdateHistory};
+use std::time::Duration;
+
+/// Time between frames at normal speed, the tick rate of the recorded game
+pub const DEFAULT_FRAME_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Plays back a recorded history on a world
+pub struct ReplayPlayer {
+    history: WorldUpdateHistory,
+    world: World,
+    /// Creates an empty world with the replayed types registered
+    new_world: Box<dyn Fn() -> World>,
+    /// Number of updates applied to `world`
+    frame: usize,
+    speed: f64,
+    paused: bool,
+    frame_interval: Duration,
+}
+
+impl ReplayPlayer {
+    /// Create a player positioned before the first frame.
+    ///
+    /// `new_world` creates the worlds the history is applied to; it must
+    /// register every component type and system factory the history uses.
+    pub fn new(history: WorldUpdateHistory, new_world: impl Fn() -> World + 'static) -> Result<Self, String> {
+        let mut world = new_world();
+        world.check_replay_registrations(&history)?;
+        world.set_replayed_component_versions(history.metadata().component_versions.clone());
+        let mut player = Self {
+            history,
+            world,
+            new_world: Box::new(new_world),
+            frame: 0,
+            speed: 1.0,
+            paused: false,
+            frame_interval: DEFAULT_FRAME_INTERVAL,
+        };
+        if let Some(snapshot) = player.history.initial_state() {
+            player.world.restore_snapshot(snapshot);
+        }
+        player.restore_keyframe();
+        Ok(player)
+    }
+
+    /// Parse a playback speed like "4x", "0.5x" or "2"
+    pub fn parse_speed(speed: &str) -> Result<f64, String> {
+        let value = speed.strip_suffix('x').unwrap_or(speed);
+        match value.parse::<f64>() {
+            Ok(speed) if speed > 0.0 && speed.is_finite() => Ok(speed),
+            _ => Err(format!("invalid playback speed '{}', expected e.g. 4x or 0.5x", speed)),
+        }
+    }
+
+    /// Get the world in the state after the applied frames
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    /// Get the history being played back
+    pub fn history(&self) -> &WorldUpdateHistory {
+        &self.history
+    }
+
+    /// Get the number of frames applied so far
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    /// Get the number of frames in the history
+    pub fn frame_count(&self) -> usize {
+        self.history.len()
+    }
+
+    /// Check if every frame has been applied
+    pub fn is_finished(&self) -> bool {
+        self.frame >= self.frame_count()
+    }
+
+    /// Apply the next frame, returning false if there is none
+    pub fn step(&mut self) -> bool {
+        let Some(update) = self.history.updates().get(self.frame) else {
+            return false;
+        };
+        self.world.apply_update_diff(update);
+        self.frame += 1;
+        self.restore_keyframe();
+        true
+    }
+
+    /// Describe the changes the next `step` applies as sentences
+    pub fn narrate_next(&self, narrator: &Narrator) -> Vec<String> {
+        self.history
+            .updates()
+            .get(self.frame)
+            .map_or_else(Vec::new, |update| narrator.narrate(&self.world, update))
+    }
+
+    /// Bring the world to the state after `frame` frames, clamped to the
+    /// history. Jumping backwards replays the history from the start or
+    /// from the last keyframe before `frame`.
+    pub fn seek(&mut self, frame: usize) {
+        let frame = frame.min(self.frame_count());
+        let keyframe = self
+            .history
+            .keyframes()
+            .iter()
+            .rfind(|keyframe| keyframe.update_index <= frame)
+            .map(|keyframe| keyframe.update_index);
+        if frame < self.frame || keyframe.is_some_and(|keyframe| keyframe > self.frame) {
+            self.world = self.new_replay_world();
+            match keyframe {
+                Some(keyframe) => self.frame = keyframe,
+                None => {
+                    if let Some(snapshot) = self.history.initial_state() {
+                        self.world.restore_snapshot(snapshot);
+                    }
+                    self.frame = 0;
+                }
+            }
+            self.restore_keyframe();
+        }
+        while self.frame < frame && self.step() {}
+    }
+
+    /// Seek to `frame` and return an independent live world in that state,
+    /// with the recording's metadata, see `World::fork`
+    pub fn branch_at(&mut self, frame: usize) -> Result<World, String> {
+        self.seek(frame);
+        let mut branch = self.world.fork()?;
+        branch.set_replay_metadata(self.history.metadata().clone());
+        Ok(branch)
+    }
+
+    /// Replace the world with the keyframe recorded at the current frame, if any
+    fn restore_keyframe(&mut self) {
+        let frame = self.frame;
+        let Some(keyframe) = self.history.keyframes().iter().rfind(|keyframe| keyframe.update_index == frame) else {
+            return;
+        };
+        self.world = self.new_replay_world();
+        self.world.restore_snapshot(&keyframe.state);
+    }
+
+    /// Create an empty world applying data with the recorded component versions
+    fn new_replay_world(&self) -> World {
+        let mut world = (self.new_world)();
+        world.set_replayed_component_versions(self.history.metadata().component_versions.clone());
+        world
+    }
+
+    /// Get the playback speed, 1.0 being the recorded speed
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    /// Set the playback speed; values that are not positive are ignored
+    pub fn set_speed(&mut self, speed: f64) {
+        if speed > 0.0 && speed.is_finite() {
+            self.speed = speed;
+        } else {
+            ecs_warn!("Ignoring invalid playback speed {}", speed);
+        }
+    }
+
+    /// Set the time between frames at normal speed
+    pub fn set_frame_interval(&mut self, interval: Duration) {
+        self.frame_interval = interval;
+    }
+
+    /// Get the time to wait between frames at the current speed
+    pub fn frame_delay(&self) -> Duration {
+        self.frame_interval.div_f64(self.speed)
+    }
+
+    /// Check if playback is paused
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    /// Pause if playing and resume if paused
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+    use crate::{Diff, Reflect};
+
+    #[derive(Debug, Clone, Copy, PartialEq, Diff, Reflect, serde::Serialize, serde::Deserialize)]
+    struct Counter {
+        value: i32,
+    }
+
+    fn new_world() -> World {
+        let mut world = World::new();
+        world.register_replay_component::<Counter>();
+        world
+    }
+
+    fn recorded_history() -> (WorldUpdateHistory, crate::Entity) {
+        let mut world = new_world();
+        let entity = world.create_entity();
+        world.add_component(entity, Counter { value: 0 });
+        let mut history = WorldUpdateHistory::new();
+        history.set_initial_state(world.snapshot());
+        for value in 1..=3 {
+            let mut system_diff = crate::SystemUpdateDiff::new();
+            system_diff.record_component_change(crate::DiffComponentChange::Modified {
+                entity,
+                type_name: "Counter".into(),
+                diff: world
+                    .component_registry
+                    .get("Counter")
+                    .unwrap()
+                    .encode_diff(&Counter { value: value - 1 }, &Counter { value })
+                    .unwrap()
+                    .into(),
+            });
+            let mut update = crate::WorldUpdateDiff::new();
+            update.record(system_diff);
+            history.record(update);
+        }
+        (history, entity)
+    }
+
+    #[test]
+    fn test_step_and_seek() {
+        let (history, entity) = recorded_history();
+        let mut player = ReplayPlayer::new(history, new_world).unwrap();
+        let value = |player: &ReplayPlayer| player.world().get_component::<Counter>(entity).unwrap().value;
+
+        assert_eq!(value(&player), 0);
+        assert!(player.step());
+        assert_eq!((player.frame(), value(&player)), (1, 1));
+
+        player.seek(10);
+        assert!(player.is_finished());
+        assert_eq!(value(&player), 3);
+        assert!(!player.step());
+
+        player.seek(1);
+        assert_eq!((player.frame(), value(&player)), (1, 1));
+        assert_eq!(player.world().entity_count(), 1);
+    }
+
+    #[test]
+    fn test_keyframes_resync_playback() {
+        let (mut history, entity) = recorded_history();
+        // A sampled log missing a frame: the keyframe after the second
+        // update holds the true state
+        let mut state = new_world();
+        let keyframe_entity = state.create_entity();
+        state.add_component(keyframe_entity, Counter { value: 10 });
+        let mut keyframes = WorldUpdateHistory::new();
+        for (index, update) in history.updates().iter().enumerate() {
+            if index == 2 {
+                keyframes.record_keyframe(2, state.snapshot());
+            }
+            keyframes.record(update.clone());
+        }
+        keyframes.set_initial_state(history.initial_state().unwrap().clone());
+        history = keyframes;
+
+        let mut player = ReplayPlayer::new(history, new_world).unwrap();
+        let value = |player: &ReplayPlayer| player.world().get_component::<Counter>(entity).unwrap().value;
+        player.step();
+        player.step();
+        assert_eq!(value(&player), 10);
+        player.step();
+        assert_eq!(value(&player), 3);
+
+        player.seek(0);
+        assert_eq!(value(&player), 0);
+        player.seek(2);
+        assert_eq!((player.frame(), value(&player)), (2, 10));
+    }
+
+    /// Counts up every update
+    struct CountSystem;
+
+    impl crate::System for CountSystem {
+        type InComponents = ();
+        type OutComponents = (Counter,);
+
+        fn initialize(&mut self, _world: &mut crate::WorldView<Self::InComponents, Self::OutComponents>) {}
+
+        fn update(&mut self, world: &mut crate::WorldView<Self::InComponents, Self::OutComponents>) {
+            let mut changes = Vec::new();
+            for (entity, counter) in world.query_components::<(crate::Out<Counter>,)>() {
+                let old = *counter;
+                counter.value += 1;
+                changes.push((entity, old, *counter));
+            }
+            for (entity, old, new) in changes {
+                world.record_component_modification(entity, &old, &new);
+            }
+        }
+
+        fn deinitialize(&mut self, _world: &mut crate::WorldView<Self::InComponents, Self::OutComponents>) {}
+    }
+
+    #[test]
+    fn test_branch_continues_live() {
+        let new_counting_world = || {
+            let mut world = new_world();
+            world.register_system_factory(|| CountSystem);
+            world
+        };
+        let mut world = new_counting_world();
+        world.set_replay_metadata(crate::replay_metadata::ReplayMetadata {
+            seed: Some(7),
+            ..Default::default()
+        });
+        let entity = world.create_entity();
+        world.add_component(entity, Counter { value: 0 });
+        let initial_state = world.snapshot();
+        world.add_system(CountSystem);
+        for _ in 0..3 {
+            world.update();
+        }
+        let mut history = world.get_update_history().clone();
+        history.set_initial_state(initial_state);
+
+        let mut player = ReplayPlayer::new(history, new_counting_world).unwrap();
+        // The system addition and the first update
+        let mut branch = player.branch_at(2).unwrap();
+        assert_eq!(branch.get_component::<Counter>(entity), Some(&Counter { value: 1 }));
+        assert_eq!(branch.replay_metadata().seed, Some(7));
+
+        branch.get_component_mut::<Counter>(entity).unwrap().value = 10;
+        branch.update();
+        assert_eq!(branch.get_component::<Counter>(entity), Some(&Counter { value: 11 }));
+        assert_eq!(player.frame(), 2);
+        assert_eq!(player.world().get_component::<Counter>(entity), Some(&Counter { value: 1 }));
+        player.step();
+        assert_eq!(player.world().get_component::<Counter>(entity), Some(&Counter { value: 2 }));
+    }
+
+    #[test]
+    fn test_speed_and_pause() {
+        let (history, _) = recorded_history();
+        let mut player = ReplayPlayer::new(history, new_world).unwrap();
+        player.set_speed(ReplayPlayer::parse_speed("4x").unwrap());
+        assert_eq!(player.frame_delay(), DEFAULT_FRAME_INTERVAL / 4);
+        player.set_speed(0.0);
+        assert_eq!(player.speed(), 4.0);
+        assert!(ReplayPlayer::parse_speed("fast").is_err());
+        assert!(ReplayPlayer::parse_speed("-2x").is_err());
+
+        player.toggle_pause();
+        assert!(player.is_paused());
+        player.resume();
+        assert!(!player.is_paused());
+
+        // Histories using unregistered types are rejected
+        let (history, _) = recorded_history();
+        assert!(ReplayPlayer::new(history, World::new).is_err());
+    }
+}
@@ -0,0 +1,322 @@
+//! Consistency checks of replay logs, on top of the structure checked by
+//! `replay_analysis::validate_replay_log_str`.
+//!
+//! Replaying a log from its initial state, every entity must be created
+//! before an entry refers to it and every component added before it is
+//! modified or removed:
+//!
+//! ```ignore
+//! for issue in World::validate_replay_log("replay_logs/session_0001.log")? {
+//!     println!("{}", issue); // line 14: MOD of Position on Entity(0, 3) before it was added
+//! }
+//! ```
+//!
+//! The entities and components alive are only known from an INITIAL_STATE
+//! or KEYFRAME section on, so logs without one are not checked until their
+//! first keyframe. Sampled logs skip frames and are not checked at all, and
+//! dropped updates stop the checks until the next keyframe.
+
+use crate::replay_analysis::ValidationIssue;
+use crate::{
+    parse_component_add, parse_component_mod, parse_component_rem, parse_world_operation, DiffComponentChange,
+    Entity, WorldOperation,
+};
+#[cfg(feature = "fs")]
+use crate::World;
+use std::collections::HashSet;
+
+/// Entries of the system or snapshot being read, checked once it ends
+#[derive(Default)]
+struct PendingEntries {
+    changes: Vec<(usize, DiffComponentChange)>,
+    operations: Vec<(usize, WorldOperation)>,
+    /// The ORDER line of the system, e.g. "C O C"
+    order: Option<String>,
+}
+
+/// An entry of a system with its line number
+enum Entry<'a> {
+    Change(usize, &'a DiffComponentChange),
+    Operation(usize, &'a WorldOperation),
+}
+
+impl PendingEntries {
+    /// The entries in the order they were recorded. Without a matching
+    /// ORDER line the operations came first.
+    fn entries(&self) -> Vec<Entry<'_>> {
+        let mut changes = self.changes.iter().map(|(line, change)| Entry::Change(*line, change));
+        let mut operations = self.operations.iter().map(|(line, operation)| Entry::Operation(*line, operation));
+        let order = self.order.as_deref().filter(|order| {
+            let entries: Vec<&str> = order.split_whitespace().collect();
+            entries.iter().filter(|entry| **entry == "C").count() == self.changes.len()
+                && entries.iter().filter(|entry| **entry == "O").count() == self.operations.len()
+                && entries.len() == self.changes.len() + self.operations.len()
+        });
+        match order {
+            Some(order) => order
+                .split_whitespace()
+                .filter_map(|entry| if entry == "C" { changes.next() } else { operations.next() })
+                .collect(),
+            None => operations.chain(changes).collect(),
+        }
+    }
+}
+
+/// Entities and components alive at the current point of the log
+#[derive(Default)]
+struct LogState {
+    entities: HashSet<Entity>,
+    removed: HashSet<Entity>,
+    components: HashSet<(Entity, String)>,
+}
+
+impl LogState {
+    /// Check that an entry refers to a live entity
+    fn refer(&self, entity: Entity, line: usize, keyword: &str, issues: &mut Vec<ValidationIssue>) -> bool {
+        if self.entities.contains(&entity) {
+            return true;
+        }
+        let when = if self.removed.contains(&entity) {
+            "after its removal"
+        } else {
+            "before its creation"
+        };
+        issues.push(ValidationIssue {
+            line,
+            message: format!("{} refers to {} {}", keyword, entity, when),
+        });
+        false
+    }
+
+    fn apply(&mut self, entry: Entry<'_>, issues: &mut Vec<ValidationIssue>) {
+        match entry {
+            Entry::Operation(line, operation) => self.apply_operation(line, operation, issues),
+            Entry::Change(line, change) => self.apply_change(line, change, issues),
+        }
+    }
+
+    fn apply_operation(&mut self, line: usize, operation: &WorldOperation, issues: &mut Vec<ValidationIssue>) {
+        match operation {
+            WorldOperation::CreateEntity(entity) => {
+                self.entities.insert(*entity);
+                self.removed.remove(entity);
+            }
+            WorldOperation::RemoveEntity(entity) => {
+                if self.refer(*entity, line, "REMOVE_ENTITY", issues) {
+                    self.entities.remove(entity);
+                    self.removed.insert(*entity);
+                    self.components.retain(|(owner, _)| owner != entity);
+                }
+            }
+            WorldOperation::DisableEntity(entity) => {
+                self.refer(*entity, line, "DISABLE_ENTITY", issues);
+            }
+            WorldOperation::EnableEntity(entity) => {
+                self.refer(*entity, line, "ENABLE_ENTITY", issues);
+            }
+            WorldOperation::AddToGroup(entity, _) => {
+                self.refer(*entity, line, "ADD_TO_GROUP", issues);
+            }
+            WorldOperation::RemoveFromGroup(entity, _) => {
+                self.refer(*entity, line, "REMOVE_FROM_GROUP", issues);
+            }
+            WorldOperation::Relate(source, _, target) => {
+                self.refer(*source, line, "RELATE", issues);
+                self.refer(*target, line, "RELATE", issues);
+            }
+            WorldOperation::Unrelate(source, _, target) => {
+                self.refer(*source, line, "UNRELATE", issues);
+                self.refer(*target, line, "UNRELATE", issues);
+            }
+            WorldOperation::ClearEntities | WorldOperation::ClearAll => {
+                self.removed.extend(self.entities.drain());
+                self.components.clear();
+            }
+            WorldOperation::CreateWorld(_)
+            | WorldOperation::RemoveWorld(_)
+            | WorldOperation::AddSystem(_)
+            | WorldOperation::ResetHistory => {}
+        }
+    }
+
+    fn apply_change(&mut self, line: usize, change: &DiffComponentChange, issues: &mut Vec<ValidationIssue>) {
+        let keyword = match change {
+            DiffComponentChange::Added { .. } => "ADD",
+            DiffComponentChange::Modified { .. } => "MOD",
+            DiffComponentChange::Removed { .. } => "REM",
+        };
+        let entity = change.entity();
+        if !self.refer(entity, line, keyword, issues) {
+            return;
+        }
+        let component = (entity, change.type_name().to_string());
+        let present = match change {
+            DiffComponentChange::Added { .. } => {
+                self.components.insert(component);
+                true
+            }
+            DiffComponentChange::Modified { .. } => self.components.contains(&component),
+            DiffComponentChange::Removed { .. } => self.components.remove(&component),
+        };
+        if !present {
+            issues.push(ValidationIssue {
+                line,
+                message: format!("{} of {} on {} before it was added", keyword, change.type_name(), entity),
+            });
+        }
+    }
+}
+
+/// Check that the entries of a replay log refer to entities after their
+/// creation and to components after their addition
+pub fn check_replay_log_consistency(contents: &str) -> Vec<ValidationIssue> {
+    let mut issues = Vec::new();
+    if contents.lines().any(|line| line.trim().starts_with("# Sampling:")) {
+        return issues;
+    }
+    // None until the entities alive are known from a snapshot section
+    let mut state: Option<LogState> = None;
+    let mut pending: Option<PendingEntries> = None;
+    fn finish(state: &mut Option<LogState>, pending: Option<PendingEntries>, issues: &mut Vec<ValidationIssue>) {
+        if let (Some(state), Some(pending)) = (state.as_mut(), pending) {
+            for entry in pending.entries() {
+                state.apply(entry, issues);
+            }
+        }
+    }
+
+    for (index, line) in contents.lines().enumerate() {
+        let number = index + 1;
+        let line = line.trim();
+        if line.starts_with("# DROPPED_UPDATES:") {
+            finish(&mut state, pending.take(), &mut issues);
+            state = None;
+            continue;
+        }
+        if line.starts_with('#') || line.is_empty() {
+            continue;
+        }
+        let (keyword, rest) = line.split_once(' ').unwrap_or((line, ""));
+        match keyword {
+            "INITIAL_STATE" | "KEYFRAME" => {
+                finish(&mut state, pending.take(), &mut issues);
+                // Snapshots list the whole world
+                state = Some(LogState::default());
+                pending = Some(PendingEntries::default());
+            }
+            "UPDATE" => finish(&mut state, pending.take(), &mut issues),
+            "SYSTEM" => {
+                finish(&mut state, pending.take(), &mut issues);
+                pending = Some(PendingEntries::default());
+            }
+            "ORDER:" => {
+                if let Some(pending) = pending.as_mut() {
+                    pending.order = Some(rest.to_string());
+                }
+            }
+            "ADD" | "MOD" | "REM" => {
+                let change = match keyword {
+                    "ADD" => parse_component_add(rest),
+                    "MOD" => parse_component_mod(rest),
+                    _ => parse_component_rem(rest),
+                };
+                if let (Some(change), Some(pending)) = (change, pending.as_mut()) {
+                    pending.changes.push((number, change));
+                }
+            }
+            _ => {
+                if let (Ok(operation), Some(pending)) = (parse_world_operation(line), pending.as_mut()) {
+                    pending.operations.push((number, operation));
+                }
+            }
+        }
+    }
+    finish(&mut state, pending.take(), &mut issues);
+    issues.sort_by_key(|issue| issue.line);
+    issues
+}
+
+#[cfg(feature = "fs")]
+impl World {
+    /// Check a replay log file for malformed lines, gaps in the frame
+    /// numbering, entries referring to entities before their creation and
+    /// components modified or removed before their addition. The issues are
+    /// sorted by line.
+    pub fn validate_replay_log(file_path: &str) -> Result<Vec<ValidationIssue>, std::io::Error> {
+        let contents = std::fs::read_to_string(file_path)?;
+        let mut issues = crate::replay_analysis::validate_replay_log_str(&contents);
+        issues.extend(check_replay_log_consistency(&contents));
+        issues.sort_by_key(|issue| issue.line);
+        Ok(issues)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(log: &str) -> Vec<String> {
+        check_replay_log_consistency(log).iter().map(|issue| issue.to_string()).collect()
+    }
+
+    #[test]
+    fn test_entries_before_creation_or_addition() {
+        let log = "\
+INITIAL_STATE
+  WORLD_OPERATIONS: 1
+    CREATE_ENTITY Entity(0, 0)
+  COMPONENT_CHANGES: 1
+    ADD Entity(0, 0) Health 3
+
+UPDATE 1
+SYSTEMS: 1
+  SYSTEM 0
+    COMPONENT_CHANGES: 3
+      MOD Entity(0, 0) Health 2
+      MOD Entity(0, 0) Armor 1
+      ADD Entity(0, 1) Health 5
+    WORLD_OPERATIONS: 1
+      ADD_TO_GROUP Entity(0, 2) enemies
+
+UPDATE 2
+SYSTEMS: 1
+  SYSTEM 0
+    COMPONENT_CHANGES: 2
+      ADD Entity(0, 3) Health 1
+      REM Entity(0, 0) Health
+    WORLD_OPERATIONS: 2
+      REMOVE_ENTITY Entity(0, 0)
+      CREATE_ENTITY Entity(0, 3)
+    ORDER: C O O C
+";
+        assert_eq!(
+            messages(log),
+            vec![
+                "line 12: MOD of Armor on Entity(0, 0) before it was added",
+                "line 13: ADD refers to Entity(0, 1) before its creation",
+                "line 15: ADD_TO_GROUP refers to Entity(0, 2) before its creation",
+                "line 21: ADD refers to Entity(0, 3) before its creation",
+                "line 22: REM refers to Entity(0, 0) after its removal",
+            ]
+        );
+
+        // Entities created in the same system before their components are fine
+        let log = log.replace("    ORDER: C O O C\n", "    ORDER: O O C C\n");
+        assert_eq!(messages(&log)[3..], ["line 22: REM refers to Entity(0, 0) after its removal"]);
+    }
+
+    #[test]
+    fn test_unknown_state_is_not_checked() {
+        let update = "UPDATE 1\nSYSTEMS: 1\n  SYSTEM 0\n    COMPONENT_CHANGES: 1\n      MOD Entity(0, 4) Health 2\n";
+        // Without a snapshot the entities alive are not known
+        assert!(check_replay_log_consistency(update).is_empty());
+
+        let snapshot = "INITIAL_STATE\n  WORLD_OPERATIONS: 1\n    CREATE_ENTITY Entity(0, 0)\n";
+        let log = format!("{}{}", snapshot, update);
+        assert_eq!(messages(&log), ["line 8: MOD refers to Entity(0, 4) before its creation"]);
+        let dropped = format!("{}# DROPPED_UPDATES: 3\n{}", snapshot, update);
+        assert!(check_replay_log_consistency(&dropped).is_empty());
+        let keyframe = format!("{}KEYFRAME 2\n    CREATE_ENTITY Entity(0, 4)\n    ADD Entity(0, 4) Health 1\n{}", snapshot, update);
+        assert!(check_replay_log_consistency(&keyframe).is_empty());
+    }
+}
@@ -0,0 +1,217 @@
+//! Components that need companions on the same entity.
+//!
+//! A `Target` means nothing without a `Position` to move from, and actors
+//! missing either are silently skipped by the game's MovementSystem.
+//! Declaring the dependency makes adding the component check for it:
+//!
+//! ```ignore
+//! world
+//!     .component_registry_mut()
+//!     .require::<Velocity, Acceleration>() // inserts Acceleration::default()
+//!     .require_existing::<Target, Position>(); // must be added first
+//! world.try_add_component(entity, Target { x: 1, y: 2 })?; // Err without a Position
+//! ```
+//!
+//! `add_component` inserts missing defaults right after the component and
+//! logs a warning for missing companions without one, while
+//! `try_add_component` fails and adds nothing. Requirements are checked when
+//! a component is added; removing a required component later is allowed.
+//!
+//! Replays insert the same defaults, so like registrations the replaying
+//! world needs the requirements declared too.
+
+use crate::{short_type_name, ComponentRegistry, Entity, World, WorldView};
+use std::any::TypeId;
+use std::collections::HashMap;
+
+/// A component type another component type needs on the same entity
+#[derive(Debug, Clone, Copy)]
+pub struct ComponentRequirement {
+    pub type_id: TypeId,
+    pub type_name: &'static str,
+    /// Adds the default of the required type, None if it must be added first
+    insert_default: Option<fn(&mut World, Entity)>,
+}
+
+impl ComponentRequirement {
+    /// Check if a missing component of this type is inserted with its default
+    pub fn has_default(&self) -> bool {
+        self.insert_default.is_some()
+    }
+}
+
+/// Requirements of the component types in a registry
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Requirements {
+    by_type: HashMap<TypeId, Vec<ComponentRequirement>>,
+}
+
+impl ComponentRegistry {
+    /// Require entities with a T component to have an R component, inserting
+    /// `R::default()` when T is added without one
+    pub fn require<T: 'static, R: Default + 'static>(&mut self) -> &mut Self {
+        self.add_requirement::<T, R>(Some(|world, entity| world.add_component(entity, R::default())))
+    }
+
+    /// Require an R component to be added to entities before their T component
+    pub fn require_existing<T: 'static, R: 'static>(&mut self) -> &mut Self {
+        self.add_requirement::<T, R>(None)
+    }
+
+    /// Get the component types required by the component type T, in declaration order
+    pub fn requirements<T: 'static>(&self) -> &[ComponentRequirement] {
+        self.requirements_of(TypeId::of::<T>())
+    }
+
+    pub(crate) fn requirements_of(&self, type_id: TypeId) -> &[ComponentRequirement] {
+        self.requirements.by_type.get(&type_id).map_or(&[], Vec::as_slice)
+    }
+
+    /// Add a requirement, replacing an earlier one of the same types
+    fn add_requirement<T: 'static, R: 'static>(&mut self, insert_default: Option<fn(&mut World, Entity)>) -> &mut Self {
+        if TypeId::of::<T>() == TypeId::of::<R>() {
+            ecs_warn!("Component {} cannot require itself", short_type_name::<T>());
+            return self;
+        }
+        let requirement = ComponentRequirement {
+            type_id: TypeId::of::<R>(),
+            type_name: short_type_name::<R>(),
+            insert_default,
+        };
+        let requirements = self.requirements.by_type.entry(TypeId::of::<T>()).or_default();
+        match requirements.iter_mut().find(|existing| existing.type_id == requirement.type_id) {
+            Some(existing) => *existing = requirement,
+            None => requirements.push(requirement),
+        }
+        self
+    }
+}
+
+impl World {
+    /// Add a component to an entity after checking that the components it
+    /// requires without a default are present; those with a default are
+    /// inserted. Nothing is added if a requirement is missing.
+    pub fn try_add_component<T: 'static>(&mut self, entity: Entity, component: T) -> Result<(), String> {
+        let missing = self.missing_requirements(TypeId::of::<T>(), entity);
+        if !missing.is_empty() {
+            return Err(format!("{} on {} requires {}", short_type_name::<T>(), entity, missing.join(", ")));
+        }
+        self.add_component(entity, component);
+        Ok(())
+    }
+
+    /// Get the names of the components without a default that a component
+    /// of the given type requires and the entity lacks
+    fn missing_requirements(&self, type_id: TypeId, entity: Entity) -> Vec<&'static str> {
+        self.component_registry
+            .requirements_of(type_id)
+            .iter()
+            .filter(|requirement| !requirement.has_default() && !self.has_component_type(requirement.type_id, entity))
+            .map(|requirement| requirement.type_name)
+            .collect()
+    }
+
+    /// Insert the defaults of the components a newly added component requires
+    pub(crate) fn insert_required_components(&mut self, type_id: TypeId, entity: Entity) {
+        let defaults: Vec<_> = self
+            .component_registry
+            .requirements_of(type_id)
+            .iter()
+            .filter_map(|requirement| Some((requirement.type_id, requirement.insert_default?)))
+            .collect();
+        for (required_type, insert_default) in defaults {
+            // Checked one by one, as inserting a default may insert others
+            if !self.has_component_type(required_type, entity) {
+                insert_default(self, entity);
+            }
+        }
+    }
+
+    /// Warn about components without a default that a component added with
+    /// `add_component` requires and the entity lacks
+    pub(crate) fn warn_missing_requirements(&self, type_id: TypeId, type_name: &str, entity: Entity) {
+        if self.component_registry.requirements_of(type_id).is_empty() {
+            return;
+        }
+        let missing = self.missing_requirements(type_id, entity);
+        if !missing.is_empty() {
+            ecs_warn!("{} added to {} without the required {}", type_name, entity, missing.join(", "));
+        }
+    }
+}
+
+impl<I, O> WorldView<I, O> {
+    /// Add a component to an entity if the components it requires are
+    /// present, see `World::try_add_component`
+    pub fn try_add_component<T: 'static>(&mut self, entity: Entity, component: T) -> Result<(), String> {
+        unsafe { self.world() }.check_access(|| vec![crate::ComponentAccess::write::<T>()]);
+        unsafe { self.world() }.profile_accesses(|| vec![crate::ComponentAccess::write::<T>()], 1);
+        unsafe { self.world_mut().try_add_component(entity, component) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Position(i32);
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Target(i32);
+
+    #[derive(Debug, Clone, PartialEq, Default)]
+    struct Velocity(i32);
+
+    #[derive(Debug, Clone, PartialEq, Default)]
+    struct Acceleration(i32);
+
+    #[test]
+    fn test_required_defaults_are_inserted() {
+        let mut world = World::new();
+        world
+            .component_registry_mut()
+            .require::<Position, Velocity>()
+            .require::<Velocity, Acceleration>()
+            // A cycle stops at components already present
+            .require::<Acceleration, Velocity>();
+        let entity = world.create_entity();
+        world.add_component(entity, Position(1));
+        assert_eq!(world.get_component::<Velocity>(entity), Some(&Velocity(0)));
+        assert_eq!(world.get_component::<Acceleration>(entity), Some(&Acceleration(0)));
+
+        // Present components are kept
+        let other = world.create_entity();
+        world.add_component(other, Velocity(5));
+        world.add_component(other, Position(2));
+        assert_eq!(world.get_component::<Velocity>(other), Some(&Velocity(5)));
+        assert_eq!(world.component_registry().requirements::<Position>().len(), 1);
+        assert!(world.component_registry().requirements::<Target>().is_empty());
+    }
+
+    #[test]
+    fn test_missing_requirements_fail_try_add_component() {
+        let mut world = World::new();
+        world
+            .component_registry_mut()
+            .require_existing::<Target, Position>()
+            .require::<Target, Velocity>();
+        let entity = world.create_entity();
+        assert_eq!(
+            world.try_add_component(entity, Target(3)),
+            Err(format!("Target on {} requires Position", entity))
+        );
+        assert!(world.get_component::<Target>(entity).is_none());
+        assert!(world.get_component::<Velocity>(entity).is_none());
+
+        world.add_component(entity, Position(0));
+        world.try_add_component(entity, Target(3)).unwrap();
+        assert_eq!(world.get_component::<Velocity>(entity), Some(&Velocity(0)));
+
+        // add_component only warns
+        let other = world.create_entity();
+        world.add_component(other, Target(4));
+        assert!(world.get_component::<Target>(other).is_some());
+        assert!(!world.component_registry().requirements::<Target>()[0].has_default());
+    }
+}
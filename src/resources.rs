@@ -0,0 +1,72 @@
+//! Resources: singleton values stored in the world by type.
+//!
+//! Resources hold state that belongs to no entity, like configuration or
+//! shared services installed by plugins. Unlike components, changes to
+//! resources are not recorded in the update history.
+
+use crate::{World, WorldView};
+use std::any::{Any, TypeId};
+
+impl World {
+    /// Insert a resource, returning the previous resource of the same type
+    pub fn insert_resource<R: 'static>(&mut self, resource: R) -> Option<R> {
+        self.resources
+            .insert(TypeId::of::<R>(), Box::new(resource))
+            .and_then(|previous| previous.downcast::<R>().ok())
+            .map(|previous| *previous)
+    }
+
+    /// Get a resource
+    pub fn resource<R: 'static>(&self) -> Option<&R> {
+        self.resources.get(&TypeId::of::<R>())?.downcast_ref::<R>()
+    }
+
+    /// Get a mutable resource
+    pub fn resource_mut<R: 'static>(&mut self) -> Option<&mut R> {
+        self.resources.get_mut(&TypeId::of::<R>())?.downcast_mut::<R>()
+    }
+
+    /// Remove a resource
+    pub fn remove_resource<R: 'static>(&mut self) -> Option<R> {
+        let resource: Box<dyn Any> = self.resources.remove(&TypeId::of::<R>())?;
+        resource.downcast::<R>().ok().map(|resource| *resource)
+    }
+
+    /// Check if a resource of type R exists
+    pub fn has_resource<R: 'static>(&self) -> bool {
+        self.resources.contains_key(&TypeId::of::<R>())
+    }
+}
+
+impl<I, O> WorldView<I, O> {
+    /// Get a resource of the world
+    pub fn resource<R: 'static>(&self) -> Option<&R> {
+        unsafe { self.world().resource::<R>() }
+    }
+
+    /// Get a mutable resource of the world
+    pub fn resource_mut<R: 'static>(&mut self) -> Option<&mut R> {
+        unsafe { self.world_mut().resource_mut::<R>() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Gravity(f32);
+
+    #[test]
+    fn test_resource_lifecycle() {
+        let mut world = World::new();
+        assert!(!world.has_resource::<Gravity>());
+        assert_eq!(world.insert_resource(Gravity(9.8)), None);
+        assert_eq!(world.insert_resource(Gravity(1.6)), Some(Gravity(9.8)));
+
+        world.resource_mut::<Gravity>().unwrap().0 = 3.7;
+        assert_eq!(world.resource::<Gravity>(), Some(&Gravity(3.7)));
+        assert_eq!(world.remove_resource::<Gravity>(), Some(Gravity(3.7)));
+        assert!(world.resource::<Gravity>().is_none());
+    }
+}
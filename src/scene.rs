@@ -0,0 +1,347 @@
+//! Resources and entities loaded from RON scene files.
+//!
+//! A scene describes the starting state of a world as data instead of setup
+//! code. Resources map type names to values; each entity maps component type
+//! names to values, or names a registered prefab with `prefab` and overrides
+//! some of its components:
+//!
+//! ```text
+//! (
+//!     resources: {
+//!         "Gravity": (strength: 3),
+//!     },
+//!     entities: [
+//!         { "Position": (x: 1, y: 1), "Home": () },
+//!         { "prefab": "actor", "Position": (x: 4) },
+//!     ],
+//! )
+//! ```
+//!
+//! Types are looked up by short type name in the world's component registry,
+//! so they must derive `Reflect` and be registered. Loading checks the whole
+//! file first: if any value fails to build, nothing is added to the world.
+//!
+//! `export_scene` writes the registered resources and components of a live
+//! world in the same format, to capture an interesting state as a starting
+//! scenario. Entity identities, groups and unregistered types are not part of
+//! a scene.
+
+use crate::prefab::{ron_key, ron_to_value, BuiltComponent};
+use crate::{Entity, EntityFilter, Prefab, Value, World};
+use std::any::TypeId;
+use std::fmt::Write;
+
+/// A resource or entity of a scene, built and ready to be added
+enum SceneItem {
+    Resource(TypeId, Box<dyn std::any::Any>),
+    Entity(Vec<BuiltComponent>),
+}
+
+impl World {
+    /// Insert the resources of a RON map of type names to values, returning how many were loaded
+    pub fn load_resources_ron(&mut self, contents: &str) -> Result<usize, String> {
+        let resources = parse_ron_map(ron::from_str(contents).map_err(|e| e.to_string())?, "resources")?;
+        let items = resources
+            .into_iter()
+            .map(|(type_name, value)| self.build_resource(&type_name, value))
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(self.add_scene_items(items).0)
+    }
+
+    /// Insert the resources of a RON file, returning how many were loaded
+    #[cfg(feature = "fs")]
+    pub fn load_resources_from_ron<P: AsRef<std::path::Path>>(&mut self, path: P) -> Result<usize, String> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        self.load_resources_ron(&contents)
+            .map_err(|e| format!("Failed to load resources from {}: {}", path.display(), e))
+    }
+
+    /// Insert the resources and spawn the entities of a RON scene, returning the new entities
+    pub fn load_scene_ron(&mut self, contents: &str) -> Result<Vec<Entity>, String> {
+        let scene = parse_ron_map(ron::from_str(contents).map_err(|e| e.to_string())?, "scene")?;
+        let mut items = Vec::new();
+        for (key, value) in scene {
+            match (key.as_str(), value) {
+                ("resources", resources) => {
+                    for (type_name, value) in parse_ron_map(resources, "resources")? {
+                        items.push(self.build_resource(&type_name, value)?);
+                    }
+                }
+                ("entities", ron::Value::Seq(entities)) => {
+                    for (index, entity) in entities.into_iter().enumerate() {
+                        let components = parse_ron_map(entity, "entity")?;
+                        items.push(
+                            self.build_scene_entity(components)
+                                .map_err(|e| format!("Entity {}: {}", index, e))?,
+                        );
+                    }
+                }
+                ("entities", _) => return Err("entities must be a list".to_string()),
+                (other, _) => return Err(format!("unknown scene section '{}'", other)),
+            }
+        }
+        Ok(self.add_scene_items(items).1)
+    }
+
+    /// Insert the resources and spawn the entities of a RON scene file, returning the new entities
+    #[cfg(feature = "fs")]
+    pub fn load_scene<P: AsRef<std::path::Path>>(&mut self, path: P) -> Result<Vec<Entity>, String> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        self.load_scene_ron(&contents)
+            .map_err(|e| format!("Failed to load scene from {}: {}", path.display(), e))
+    }
+
+    /// Write the registered resources and the registered components of the
+    /// entities accepted by the filter as a RON scene
+    pub fn export_scene_ron(&self, filter: impl Into<EntityFilter>) -> String {
+        let filter = filter.into();
+        let mut scene = String::from("(\n    resources: {\n");
+        for registration in self.component_registry.iter() {
+            let resource = self.resources.get(&registration.type_id());
+            if let Some(value) = resource.and_then(|resource| registration.to_value(resource.as_ref())) {
+                let _ = writeln!(scene, "        {:?}: {},", registration.type_name(), RonValue(&value));
+            }
+        }
+        scene.push_str("    },\n    entities: [\n");
+        for entity in self.entities.iter().filter(|entity| filter.matches(**entity)) {
+            scene.push_str("        {\n");
+            for (type_name, value) in self.reflect_components(*entity) {
+                let _ = writeln!(scene, "            {:?}: {},", type_name, RonValue(&value));
+            }
+            scene.push_str("        },\n");
+        }
+        scene.push_str("    ],\n)\n");
+        scene
+    }
+
+    /// Write a RON scene file of the world, returning how many entities it holds
+    #[cfg(feature = "fs")]
+    pub fn export_scene<P: AsRef<std::path::Path>>(&self, path: P, filter: impl Into<EntityFilter>) -> Result<usize, String> {
+        let path = path.as_ref();
+        let filter = filter.into();
+        let count = self.entities.iter().filter(|entity| filter.matches(**entity)).count();
+        std::fs::write(path, self.export_scene_ron(filter)).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        Ok(count)
+    }
+
+    fn build_resource(&self, type_name: &str, value: ron::Value) -> Result<SceneItem, String> {
+        let registration = self
+            .component_registry
+            .get(type_name)
+            .ok_or_else(|| format!("unknown resource type {}", type_name))?;
+        let resource = registration
+            .from_value(&ron_to_value(value))
+            .map_err(|e| format!("Resource {}: {}", type_name, e))?;
+        Ok(SceneItem::Resource(registration.type_id(), resource))
+    }
+
+    /// Build the components of an entity, starting from its prefab if it names one
+    fn build_scene_entity(&self, components: Vec<(String, ron::Value)>) -> Result<SceneItem, String> {
+        let mut prefab = None;
+        let mut values = Vec::new();
+        for (type_name, value) in components {
+            match (type_name.as_str(), value) {
+                ("prefab", ron::Value::String(name)) => prefab = Some(name),
+                ("prefab", _) => return Err("prefab must be a name".to_string()),
+                (_, value) => values.push((type_name, ron_to_value(value))),
+            }
+        }
+        let components = match prefab {
+            Some(name) => {
+                let prefab = self.prefabs.get(&name).ok_or_else(|| format!("Unknown prefab: {}", name))?;
+                let overrides: Vec<(&str, Value)> = values
+                    .iter()
+                    .map(|(type_name, value)| (type_name.as_str(), value.clone()))
+                    .collect();
+                self.prefab_components(prefab, &overrides)?
+            }
+            None => {
+                let prefab = values
+                    .into_iter()
+                    .fold(Prefab::new("scene entity"), |prefab, (type_name, value)| {
+                        prefab.with_value(type_name, value)
+                    });
+                self.prefab_components(&prefab, &[])?
+            }
+        };
+        Ok(SceneItem::Entity(components))
+    }
+
+    /// Add built resources and entities, returning the number of resources and the new entities
+    fn add_scene_items(&mut self, items: Vec<SceneItem>) -> (usize, Vec<Entity>) {
+        let mut resources = 0;
+        let mut entities = Vec::new();
+        for item in items {
+            match item {
+                SceneItem::Resource(type_id, resource) => {
+                    self.resources.insert(type_id, resource);
+                    resources += 1;
+                }
+                SceneItem::Entity(components) => {
+                    let entity = self.create_entity();
+                    for (type_id, component) in components {
+                        self.push_component_any(entity, type_id, component);
+                    }
+                    entities.push(entity);
+                }
+            }
+        }
+        (resources, entities)
+    }
+}
+
+/// Formats a value in RON: maps with field names become structs, e.g. `(x: 1, y: 2)`
+struct RonValue<'a>(&'a Value);
+
+impl std::fmt::Display for RonValue<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Value::List(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", RonValue(item))?;
+                }
+                write!(f, "]")
+            }
+            Value::Map(map) => {
+                let is_struct = map.keys().all(|key| {
+                    key.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+                });
+                write!(f, "{}", if is_struct { "(" } else { "{" })?;
+                for (i, (key, value)) in map.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    if is_struct {
+                        write!(f, "{}: {}", key, RonValue(value))?;
+                    } else {
+                        write!(f, "{:?}: {}", key, RonValue(value))?;
+                    }
+                }
+                write!(f, "{}", if is_struct { ")" } else { "}" })
+            }
+            value => write!(f, "{}", value),
+        }
+    }
+}
+
+/// Entries of a RON map with names as keys
+fn parse_ron_map(value: ron::Value, what: &str) -> Result<Vec<(String, ron::Value)>, String> {
+    let ron::Value::Map(map) = value else {
+        return Err(format!("expected {} to be a map of names to values", what));
+    };
+    map.into_iter()
+        .map(|(key, value)| Ok((ron_key(key)?, value)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Reflect;
+
+    #[derive(Debug, Clone, PartialEq, Reflect)]
+    struct Position {
+        x: i32,
+        y: i32,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Reflect)]
+    struct Home;
+
+    #[derive(Debug, Clone, PartialEq, Reflect)]
+    struct Gravity {
+        strength: i32,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Reflect)]
+    struct Label {
+        text: String,
+        scale: f32,
+    }
+
+    fn registered_world() -> World {
+        let mut world = World::new();
+        world.register_component::<Position>();
+        world.register_component::<Home>();
+        world.register_component::<Gravity>();
+        world.register_prefab(Prefab::new("actor").with(Position { x: 0, y: 0 }));
+        world
+    }
+
+    #[test]
+    fn test_load_scene_spawns_entities_and_resources() {
+        let mut world = registered_world();
+        let entities = world
+            .load_scene_ron(
+                r#"(
+                    resources: {
+                        "Gravity": (strength: 3),
+                    },
+                    entities: [
+                        { "Position": (x: 1, y: 1), "Home": () },
+                        { "prefab": "actor", "Position": (x: 4) },
+                    ],
+                )"#,
+            )
+            .unwrap();
+
+        assert_eq!(entities.len(), 2);
+        assert_eq!(world.resource::<Gravity>(), Some(&Gravity { strength: 3 }));
+        assert_eq!(world.get_component::<Position>(entities[0]), Some(&Position { x: 1, y: 1 }));
+        assert!(world.get_component::<Home>(entities[0]).is_some());
+        assert_eq!(world.get_component::<Position>(entities[1]), Some(&Position { x: 4, y: 0 }));
+
+        assert_eq!(world.load_resources_ron(r#"{ "Gravity": (strength: 9) }"#), Ok(1));
+        assert_eq!(world.resource::<Gravity>(), Some(&Gravity { strength: 9 }));
+    }
+
+    #[test]
+    fn test_invalid_scene_adds_nothing() {
+        let mut world = registered_world();
+        let error = world
+            .load_scene_ron(
+                r#"(
+                    resources: { "Gravity": (strength: 3) },
+                    entities: [
+                        { "Position": (x: 1, y: 1) },
+                        { "Unknown": () },
+                    ],
+                )"#,
+            )
+            .unwrap_err();
+
+        assert!(error.starts_with("Entity 1:"), "{}", error);
+        assert_eq!(world.entity_count(), 0);
+        assert_eq!(world.resource::<Gravity>(), None);
+    }
+
+    #[test]
+    fn test_exported_scene_loads_back() {
+        let mut world = registered_world();
+        world.register_component::<Label>();
+        world.insert_resource(Gravity { strength: 7 });
+        let entities: Vec<Entity> = (0..3).map(|_| world.create_entity()).collect();
+        world.add_component(entities[0], Position { x: 2, y: -1 });
+        world.add_component(entities[0], Home);
+        world.add_component(entities[1], Label { text: "gate \"north\"".to_string(), scale: 1.5 });
+        world.add_component(entities[2], Position { x: 9, y: 9 });
+
+        let scene = world.export_scene_ron(vec![entities[0], entities[1]]);
+        let mut loaded = registered_world();
+        loaded.register_component::<Label>();
+        let spawned = loaded.load_scene_ron(&scene).unwrap();
+
+        assert_eq!(spawned.len(), 2, "{}", scene);
+        assert_eq!(loaded.resource::<Gravity>(), Some(&Gravity { strength: 7 }));
+        for (original, copy) in entities.iter().zip(&spawned) {
+            assert_eq!(loaded.reflect_components(*copy), world.reflect_components(*original));
+        }
+    }
+}
@@ -0,0 +1,447 @@
+//! Systems whose update logic is written in Rhai scripts.
+//!
+//! A `ScriptSystem` runs a script's `update` function every frame (and its
+//! optional `init` function once). Scripts reach components by their type name
+//! through a per-system registry of component types; only registered types are
+//! visible, and only types registered as writable can be changed. Components
+//! are converted to script object maps with serde. Every change a script makes
+//! is recorded in the system's diff like changes made by Rust systems.
+//!
+//! Functions available to scripts:
+//!
+//! | Function | Description |
+//! |----------|-------------|
+//! | `query(["A", "B"])` | Entities having all listed components |
+//! | `has(entity, "A")` | Whether the entity has the component |
+//! | `get(entity, "A")` | The component as an object map, `()` if missing |
+//! | `set(entity, "A", value)` | Replace a writable component |
+//! | `add(entity, "A", value)` | Add a writable component |
+//! | `frame()` | Number of the current frame |
+
+use crate::reflect::ComponentRegistration;
+use crate::{DiffComponent, DiffComponentChange, Entity, System, SystemUpdateDiff, World, WorldView};
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Scope, AST};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::any::{Any, TypeId};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Component types that can be exposed to scripts
+pub trait ScriptComponent: DiffComponent + Clone + Serialize + DeserializeOwned {}
+
+impl<T: DiffComponent + Clone + Serialize + DeserializeOwned> ScriptComponent for T {}
+
+/// A component built from a script value and its recorded form
+type CreatedComponent = (Box<dyn Any>, String);
+
+/// Replaces a component with a script value, using the replay registration to record the diff
+type UpdateComponent = fn(&mut dyn Any, Dynamic, Option<&ComponentRegistration>) -> Result<Option<String>, String>;
+
+/// Type-erased access to one registered component type
+struct RegisteredComponent {
+    type_id: TypeId,
+    writable: bool,
+    to_script: fn(&dyn Any) -> Result<Dynamic, String>,
+    /// Replace the component with a script value, returning the diff if it changed
+    update: UpdateComponent,
+    /// Build a component from a script value, returning it and its recorded form
+    create: fn(Dynamic, Option<&ComponentRegistration>) -> Result<CreatedComponent, String>,
+}
+
+fn component_to_script<T: ScriptComponent>(component: &dyn Any) -> Result<Dynamic, String> {
+    let component = component
+        .downcast_ref::<T>()
+        .ok_or_else(|| format!("component is not a {}", crate::short_type_name::<T>()))?;
+    rhai::serde::to_dynamic(component).map_err(|e| e.to_string())
+}
+
+/// Changes of types registered for replay are recorded in their encoded form,
+/// like changes recorded through a `WorldView`
+fn update_component<T: ScriptComponent>(
+    component: &mut dyn Any,
+    value: Dynamic,
+    registration: Option<&ComponentRegistration>,
+) -> Result<Option<String>, String> {
+    let component = component
+        .downcast_mut::<T>()
+        .ok_or_else(|| format!("component is not a {}", crate::short_type_name::<T>()))?;
+    let new_value: T = rhai::serde::from_dynamic(&value).map_err(|e| e.to_string())?;
+    let diff = component.diff(&new_value).map(|diff| {
+        registration
+            .and_then(|registration| registration.encode_diff(&*component, &new_value))
+            .unwrap_or_else(|| T::diff_to_string(&diff))
+    });
+    *component = new_value;
+    Ok(diff)
+}
+
+fn create_component<T: ScriptComponent>(
+    value: Dynamic,
+    registration: Option<&ComponentRegistration>,
+) -> Result<CreatedComponent, String> {
+    let component: T = rhai::serde::from_dynamic(&value).map_err(|e| e.to_string())?;
+    let data = registration
+        .and_then(|registration| registration.encode(&component))
+        .unwrap_or_else(|| format!("{:?}", component));
+    Ok((Box::new(component), data))
+}
+
+/// State shared between a script system and the functions it registers
+#[derive(Default)]
+struct ScriptContext {
+    /// World being updated, only set while the script runs
+    world: Option<*mut World>,
+    components: HashMap<String, RegisteredComponent>,
+    diff: SystemUpdateDiff,
+}
+
+impl ScriptContext {
+    #[allow(clippy::mut_from_ref)]
+    fn world(&self) -> Result<&mut World, Box<EvalAltResult>> {
+        // The pointer is only set for the duration of a script call made with
+        // exclusive access to the world
+        self.world
+            .map(|world| unsafe { &mut *world })
+            .ok_or_else(|| "the world is only accessible while the system updates".into())
+    }
+
+    fn component(&self, type_name: &str) -> Result<&RegisteredComponent, Box<EvalAltResult>> {
+        self.components
+            .get(type_name)
+            .ok_or_else(|| format!("component type '{}' is not registered for this script", type_name).into())
+    }
+
+    fn writable_component(&self, type_name: &str) -> Result<&RegisteredComponent, Box<EvalAltResult>> {
+        let component = self.component(type_name)?;
+        if !component.writable {
+            return Err(format!("component type '{}' is read-only for this script", type_name).into());
+        }
+        Ok(component)
+    }
+}
+
+/// A system running the `update` function of a Rhai script
+pub struct ScriptSystem {
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    context: Rc<RefCell<ScriptContext>>,
+    /// Script file and its modification time when loaded from a file
+    #[cfg(feature = "fs")]
+    source_file: Option<(std::path::PathBuf, Option<std::time::SystemTime>)>,
+}
+
+impl ScriptSystem {
+    /// Compile a script system from source
+    pub fn new(source: &str) -> Result<Self, String> {
+        let context = Rc::new(RefCell::new(ScriptContext::default()));
+        let engine = Self::create_engine(&context);
+        let ast = engine.compile(source).map_err(|e| e.to_string())?;
+        Ok(Self {
+            engine,
+            ast,
+            scope: Scope::new(),
+            context,
+            #[cfg(feature = "fs")]
+            source_file: None,
+        })
+    }
+
+    /// Compile a script system from a file. The script is reloaded when the
+    /// file changes, so gameplay logic can be tweaked while the game runs.
+    #[cfg(feature = "fs")]
+    pub fn from_file<P: AsRef<std::path::Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref().to_path_buf();
+        let source = std::fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let mut system = Self::new(&source)?;
+        let modified = std::fs::metadata(&path).and_then(|metadata| metadata.modified()).ok();
+        system.source_file = Some((path, modified));
+        Ok(system)
+    }
+
+    /// Give the script read access to components of type T under their short type name
+    pub fn read<T: ScriptComponent>(self) -> Self {
+        self.register::<T>(false)
+    }
+
+    /// Give the script read and write access to components of type T
+    pub fn write<T: ScriptComponent>(self) -> Self {
+        self.register::<T>(true)
+    }
+
+    fn register<T: ScriptComponent>(self, writable: bool) -> Self {
+        let mut context = self.context.borrow_mut();
+        let component = context
+            .components
+            .entry(crate::short_type_name::<T>().to_string())
+            .or_insert(RegisteredComponent {
+                type_id: TypeId::of::<T>(),
+                writable,
+                to_script: component_to_script::<T>,
+                update: update_component::<T>,
+                create: create_component::<T>,
+            });
+        component.writable |= writable;
+        drop(context);
+        self
+    }
+
+    /// Replace the script with new source; the old script keeps running if the
+    /// new one fails to compile. Variables of the script scope are kept.
+    pub fn reload(&mut self, source: &str) -> Result<(), String> {
+        self.ast = self.engine.compile(source).map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    /// Reload the script file if it changed since it was loaded
+    #[cfg(feature = "fs")]
+    fn reload_if_changed(&mut self) {
+        let Some((path, loaded)) = self.source_file.as_mut() else {
+            return;
+        };
+        let modified = std::fs::metadata(&*path).and_then(|metadata| metadata.modified()).ok();
+        if modified == *loaded {
+            return;
+        }
+        *loaded = modified;
+
+        let path = path.clone();
+        match std::fs::read_to_string(&path) {
+            Ok(source) => match self.reload(&source) {
+                Ok(()) => ecs_info!("Reloaded script {}", path.display()),
+                Err(e) => ecs_error!("Failed to compile script {}: {}", path.display(), e),
+            },
+            Err(e) => ecs_error!("Failed to read script {}: {}", path.display(), e),
+        }
+    }
+
+    /// Call a script function with the world accessible, returning the changes it made
+    fn call(&mut self, function: &str, world: &mut World) -> SystemUpdateDiff {
+        let defined = self.ast.iter_functions().any(|f| f.name == function && f.params.is_empty());
+        if !defined {
+            return SystemUpdateDiff::new();
+        }
+
+        self.context.borrow_mut().world = Some(world as *mut World);
+        let result = self
+            .engine
+            .call_fn::<Dynamic>(&mut self.scope, &self.ast, function, ());
+        let mut context = self.context.borrow_mut();
+        context.world = None;
+        if let Err(e) = result {
+            ecs_error!("Script function '{}' failed: {}", function, e);
+        }
+        std::mem::take(&mut context.diff)
+    }
+
+    fn create_engine(context: &Rc<RefCell<ScriptContext>>) -> Engine {
+        let mut engine = Engine::new();
+        engine
+            .register_type_with_name::<Entity>("Entity")
+            .register_fn("to_string", |entity: &mut Entity| entity.to_string())
+            .register_fn("to_debug", |entity: &mut Entity| entity.to_string())
+            .register_fn("==", |a: Entity, b: Entity| a == b);
+
+        let ctx = context.clone();
+        engine.register_fn("frame", move || -> Result<i64, Box<EvalAltResult>> {
+            Ok(ctx.borrow().world()?.frame_number() as i64)
+        });
+
+        let ctx = context.clone();
+        engine.register_fn("query", move |type_names: Array| -> Result<Array, Box<EvalAltResult>> {
+            let context = ctx.borrow();
+            let mut type_ids = Vec::new();
+            for type_name in type_names {
+                let type_name = type_name.into_string().map_err(|_| "component type names must be strings")?;
+                type_ids.push(context.component(&type_name)?.type_id);
+            }
+            let world: &World = context.world()?;
+            let entities = world
+                .entities
+                .iter()
+                .filter(|entity| {
+                    type_ids
+                        .iter()
+                        .all(|type_id| world.component_any(*type_id, **entity).is_some())
+                })
+                .map(|entity| Dynamic::from(*entity))
+                .collect();
+            Ok(entities)
+        });
+
+        let ctx = context.clone();
+        engine.register_fn("has", move |entity: Entity, type_name: &str| -> Result<bool, Box<EvalAltResult>> {
+            let context = ctx.borrow();
+            let type_id = context.component(type_name)?.type_id;
+            Ok(context.world()?.component_any(type_id, entity).is_some())
+        });
+
+        let ctx = context.clone();
+        engine.register_fn("get", move |entity: Entity, type_name: &str| -> Result<Dynamic, Box<EvalAltResult>> {
+            let context = ctx.borrow();
+            let registered = context.component(type_name)?;
+            match context.world()?.component_any(registered.type_id, entity) {
+                Some(component) => Ok((registered.to_script)(component)?),
+                None => Ok(Dynamic::UNIT),
+            }
+        });
+
+        let ctx = context.clone();
+        engine.register_fn(
+            "set",
+            move |entity: Entity, type_name: &str, value: Dynamic| -> Result<(), Box<EvalAltResult>> {
+                let mut context = ctx.borrow_mut();
+                let registered = context.writable_component(type_name)?;
+                let (update, type_id) = (registered.update, registered.type_id);
+                let world = context.world()?;
+                let registration = world.component_registry.get_by_type_id(type_id).cloned();
+                let component = world.component_any_mut(type_id, entity)
+                    .ok_or_else(|| format!("{} has no {} component", entity, type_name))?;
+                if let Some(diff) = update(component, value, registration.as_ref())? {
+                    context.diff.record_component_change(DiffComponentChange::Modified {
+                        entity,
+                        type_name: type_name.into(),
+                        diff: diff.into(),
+                    });
+                }
+                Ok(())
+            },
+        );
+
+        let ctx = context.clone();
+        engine.register_fn(
+            "add",
+            move |entity: Entity, type_name: &str, value: Dynamic| -> Result<(), Box<EvalAltResult>> {
+                let mut context = ctx.borrow_mut();
+                let registered = context.writable_component(type_name)?;
+                let (create, type_id) = (registered.create, registered.type_id);
+                let world = context.world()?;
+                let (component, data) = create(value, world.component_registry.get_by_type_id(type_id))?;
+                if world.component_any(type_id, entity).is_some() {
+                    return Err(format!("{} already has a {} component", entity, type_name).into());
+                }
+                world.push_component_any(entity, type_id, component);
+                context.diff.record_component_change(DiffComponentChange::Added {
+                    entity,
+                    type_name: type_name.into(),
+                    data: data.into(),
+                });
+                Ok(())
+            },
+        );
+
+        engine
+    }
+}
+
+impl System for ScriptSystem {
+    type InComponents = ();
+    type OutComponents = ();
+
+    fn initialize(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+        // Changes made during initialization are not tracked, as for Rust systems
+        let _ = self.call("init", unsafe { world.world_mut() });
+    }
+
+    fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+        #[cfg(feature = "fs")]
+        self.reload_if_changed();
+
+        let diff = self.call("update", unsafe { world.world_mut() });
+        for change in diff.component_changes() {
+            world.system_diff.record_component_change(change.clone());
+        }
+    }
+
+    fn deinitialize(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+        let _ = self.call("deinit", unsafe { world.world_mut() });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Diff;
+
+    #[derive(Debug, Clone, PartialEq, Diff, serde::Serialize, serde::Deserialize)]
+    struct Counter {
+        value: i32,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Diff, serde::Serialize, serde::Deserialize)]
+    struct Step {
+        amount: i32,
+    }
+
+    fn counter_world() -> (World, Entity) {
+        let mut world = World::new();
+        let entity = world.create_entity();
+        world.add_component(entity, Counter { value: 1 });
+        world.add_component(entity, Step { amount: 2 });
+        (world, entity)
+    }
+
+    const COUNT_SCRIPT: &str = r#"
+        fn update() {
+            for entity in query(["Counter", "Step"]) {
+                let counter = get(entity, "Counter");
+                counter.value += get(entity, "Step").amount;
+                set(entity, "Counter", counter);
+            }
+        }
+    "#;
+
+    #[test]
+    fn test_script_changes_are_tracked() {
+        let (mut world, entity) = counter_world();
+        let system = ScriptSystem::new(COUNT_SCRIPT)
+            .unwrap()
+            .write::<Counter>()
+            .read::<Step>();
+        world.add_system(system);
+        world.initialize_systems();
+        world.update();
+        world.update();
+
+        assert_eq!(world.get_component::<Counter>(entity), Some(&Counter { value: 5 }));
+        let updates = world.get_update_history().updates();
+        let changes = updates.last().unwrap().system_diffs()[0].component_changes();
+        assert_eq!(changes.len(), 1);
+        assert!(changes[0].to_string().starts_with("MOD Entity(0, 0) Counter"));
+    }
+
+    #[test]
+    fn test_read_only_components_cannot_be_set() {
+        let (mut world, entity) = counter_world();
+        let system = ScriptSystem::new(
+            r#"fn update() { for e in query(["Step"]) { set(e, "Step", #{ amount: 9 }); } }"#,
+        )
+        .unwrap()
+        .read::<Step>();
+        world.add_system(system);
+        world.update();
+
+        assert_eq!(world.get_component::<Step>(entity), Some(&Step { amount: 2 }));
+    }
+
+    #[test]
+    fn test_reload_keeps_old_script_on_error() {
+        let (mut world, entity) = counter_world();
+        let mut system = ScriptSystem::new(COUNT_SCRIPT).unwrap().write::<Counter>().read::<Step>();
+        assert!(system.reload("fn update( {").is_err());
+        system
+            .reload(r#"fn update() { add(query(["Counter"])[0], "Step", #{ amount: 4 }); }"#)
+            .unwrap();
+        world.remove_component::<Step>(entity);
+        let mut system = system.write::<Step>();
+        let diff = system.call("update", &mut world);
+
+        assert_eq!(world.get_component::<Step>(entity), Some(&Step { amount: 4 }));
+        assert_eq!(
+            diff.component_changes()[0].to_string(),
+            "ADD Entity(0, 0) Step Step { amount: 4 }"
+        );
+    }
+}
@@ -0,0 +1,470 @@
+//! Headless server runner with remote inspection.
+//!
+//! A `Server` drives a `World` at a fixed tick rate without rendering and
+//! listens for control connections. Control clients send one text command per
+//! line and receive one JSON `ServerEvent` per line:
+//!
+//! | Command | Effect |
+//! |---------|--------|
+//! | `status` | Reply with the frame number, pause state and tick rate |
+//! | `pause` / `resume` | Stop or continue ticking |
+//! | `step [n]` | Run `n` updates (default 1) while paused |
+//! | `snapshot` | Reply with the entities and registered components |
+//! | `stream on` / `stream off` | Start or stop receiving the diff of every update |
+//! | `tick-rate <hz>` | Change the tick rate, 0 runs as fast as possible |
+//! | `shutdown` | Stop the server |
+
+use crate::net::{describe_entities, register_snapshot_component, SnapshotComponent};
+use crate::{World, WorldUpdateDiff};
+use serde::{Deserialize, Serialize};
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Longest time the server sleeps before checking for control commands
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Longest command line a control client may send, longer ones drop the client
+const MAX_COMMAND_LENGTH: usize = 4096;
+
+/// Longest time between updates, slower tick rates wait this long so the time
+/// of the next update cannot overflow
+const MAX_TICK_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Configuration of a headless server
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    /// Updates per second, 0 runs updates as fast as possible
+    pub tick_rate: f64,
+    /// Address to accept control connections on, None disables remote control
+    pub control_address: Option<String>,
+    /// Whether the server waits for a `resume` or `step` before the first update
+    pub start_paused: bool,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            tick_rate: 30.0,
+            control_address: Some("127.0.0.1:7878".to_string()),
+            start_paused: false,
+        }
+    }
+}
+
+/// A command sent by a control client
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ControlCommand {
+    Status,
+    Pause,
+    Resume,
+    Step(usize),
+    Snapshot,
+    Stream(bool),
+    TickRate(f64),
+    Shutdown,
+}
+
+impl ControlCommand {
+    /// Parse one line of the control protocol
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let mut words = line.split_whitespace();
+        let command = words.next().ok_or("empty command")?;
+        let argument = words.next();
+        if words.next().is_some() {
+            return Err(format!("too many arguments for '{}'", command));
+        }
+
+        match (command, argument) {
+            ("status", None) => Ok(ControlCommand::Status),
+            ("pause", None) => Ok(ControlCommand::Pause),
+            ("resume", None) => Ok(ControlCommand::Resume),
+            ("step", None) => Ok(ControlCommand::Step(1)),
+            ("step", Some(count)) => count
+                .parse()
+                .map(ControlCommand::Step)
+                .map_err(|_| format!("invalid step count '{}'", count)),
+            ("snapshot", None) => Ok(ControlCommand::Snapshot),
+            ("stream", Some("on")) => Ok(ControlCommand::Stream(true)),
+            ("stream", Some("off")) => Ok(ControlCommand::Stream(false)),
+            ("tick-rate", Some(rate)) => match rate.parse::<f64>() {
+                // The time between updates must fit a Duration
+                Ok(rate) if rate == 0.0 || (rate.is_finite() && rate > 0.0 && Duration::try_from_secs_f64(1.0 / rate).is_ok()) => {
+                    Ok(ControlCommand::TickRate(rate))
+                }
+                _ => Err(format!("invalid tick rate '{}'", rate)),
+            },
+            ("shutdown", None) => Ok(ControlCommand::Shutdown),
+            _ => Err(format!("unknown command '{}'", line.trim())),
+        }
+    }
+}
+
+/// A reply or streamed update sent to control clients, one JSON object per line
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ServerEvent {
+    /// The command was carried out
+    Ok { frame: usize },
+    /// The command could not be parsed or carried out
+    Error { message: String },
+    /// Reply to `status`
+    Status {
+        frame: usize,
+        paused: bool,
+        tick_rate: f64,
+        entities: usize,
+    },
+    /// Reply to `snapshot`: entity creations followed by component additions
+    Snapshot { frame: usize, diff: WorldUpdateDiff },
+    /// Changes of one update, sent to streaming clients
+    Update { frame: usize, diff: WorldUpdateDiff },
+}
+
+/// A connected control client
+struct ControlClient {
+    stream: TcpStream,
+    /// Received bytes not yet terminated by a newline
+    buffer: Vec<u8>,
+    streaming: bool,
+    connected: bool,
+}
+
+impl ControlClient {
+    fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nonblocking(true)?;
+        Ok(Self {
+            stream,
+            buffer: Vec::new(),
+            streaming: false,
+            connected: true,
+        })
+    }
+
+    /// Read every complete command line received so far
+    fn read_lines(&mut self) -> Vec<String> {
+        let mut chunk = [0u8; 1024];
+        // Lines beyond the limit are read by the next call
+        while self.buffer.len() <= MAX_COMMAND_LENGTH {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => {
+                    self.connected = false;
+                    break;
+                }
+                Ok(length) => self.buffer.extend_from_slice(&chunk[..length]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => {
+                    self.connected = false;
+                    break;
+                }
+            }
+        }
+
+        let mut lines = Vec::new();
+        while let Some(end) = self.buffer.iter().position(|byte| *byte == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line).trim().to_string();
+            if !line.is_empty() {
+                lines.push(line);
+            }
+        }
+        if self.buffer.len() > MAX_COMMAND_LENGTH {
+            ecs_warn!("Dropping control client: command longer than {} bytes", MAX_COMMAND_LENGTH);
+            self.buffer.clear();
+            self.connected = false;
+        }
+        lines
+    }
+
+    /// Send one event, dropping the client if the connection failed
+    fn send(&mut self, event: &ServerEvent) {
+        if let Err(e) = self.write_event(event) {
+            ecs_warn!("Dropping control client: {}", e);
+            self.connected = false;
+        }
+    }
+
+    fn write_event(&mut self, event: &ServerEvent) -> io::Result<()> {
+        let mut line = serde_json::to_vec(event)?;
+        line.push(b'\n');
+        // Block while writing so large snapshots are not cut short
+        self.stream.set_nonblocking(false)?;
+        let result = self.stream.write_all(&line);
+        self.stream.set_nonblocking(true)?;
+        result
+    }
+}
+
+/// Runs a world without a renderer at a fixed tick rate, controlled remotely
+pub struct Server {
+    world: World,
+    tick_rate: f64,
+    paused: bool,
+    listener: Option<TcpListener>,
+    clients: Vec<ControlClient>,
+    components: Vec<SnapshotComponent>,
+    running: Arc<AtomicBool>,
+}
+
+impl Server {
+    /// Create a server for `world`, binding the control address of `config`
+    pub fn new(world: World, config: ServerConfig) -> io::Result<Self> {
+        let listener = match config.control_address {
+            Some(ref address) => {
+                let listener = TcpListener::bind(address)?;
+                listener.set_nonblocking(true)?;
+                Some(listener)
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            world,
+            tick_rate: config.tick_rate,
+            paused: config.start_paused,
+            listener,
+            clients: Vec::new(),
+            components: Vec::new(),
+            running: Arc::new(AtomicBool::new(true)),
+        })
+    }
+
+    /// Get the address control clients connect to
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.listener.as_ref().and_then(|listener| listener.local_addr().ok())
+    }
+
+    /// Include components of type C in snapshots, in their Debug form
+    pub fn register_component<C: std::fmt::Debug + 'static>(&mut self) {
+        register_snapshot_component::<C>(&mut self.components);
+    }
+
+    /// Get the world driven by the server
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    /// Get mutable access to the world driven by the server
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
+    /// Stop the server and return its world
+    pub fn into_world(self) -> World {
+        self.world
+    }
+
+    /// Check if the server is paused
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Get the tick rate in updates per second
+    pub fn tick_rate(&self) -> f64 {
+        self.tick_rate
+    }
+
+    /// Get a flag that stops `run` when cleared, e.g. from a Ctrl+C handler
+    pub fn running_flag(&self) -> Arc<AtomicBool> {
+        self.running.clone()
+    }
+
+    /// Check if `run` continues
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    /// Update the world at the configured tick rate until a `shutdown`
+    /// command arrives or the running flag is cleared
+    pub fn run(&mut self) {
+        let mut next_tick = Instant::now();
+        while self.is_running() {
+            self.poll();
+            if !self.is_running() {
+                break;
+            }
+
+            let now = Instant::now();
+            if self.paused {
+                next_tick = now;
+                thread::sleep(POLL_INTERVAL);
+                continue;
+            }
+            // Fast-forwarding ticks as fast as the world updates
+            if now >= next_tick || self.world.is_fast_forwarding() {
+                self.tick();
+                let interval = self.tick_interval();
+                // Skip missed ticks instead of running them in a burst
+                next_tick = (next_tick + interval).max(now);
+                continue;
+            }
+            thread::sleep((next_tick - now).min(POLL_INTERVAL));
+        }
+        ecs_info!("Server stopped after frame {}", self.world.frame_number());
+    }
+
+    /// Accept new control clients and handle the commands they sent
+    pub fn poll(&mut self) {
+        if let Some(ref listener) = self.listener {
+            loop {
+                match listener.accept() {
+                    Ok((stream, address)) => match ControlClient::new(stream) {
+                        Ok(client) => {
+                            ecs_info!("Control client connected from {}", address);
+                            self.clients.push(client);
+                        }
+                        Err(e) => ecs_warn!("Failed to set up control client {}: {}", address, e),
+                    },
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                    Err(e) => {
+                        ecs_warn!("Failed to accept control client: {}", e);
+                        break;
+                    }
+                }
+            }
+        }
+
+        for index in 0..self.clients.len() {
+            for line in self.clients[index].read_lines() {
+                let reply = match ControlCommand::parse(&line) {
+                    Ok(command) => self.execute(index, command),
+                    Err(message) => ServerEvent::Error { message },
+                };
+                self.clients[index].send(&reply);
+            }
+        }
+        self.clients.retain(|client| client.connected);
+    }
+
+    /// Run one update and stream its diff to streaming clients
+    pub fn tick(&mut self) {
+        self.world.update();
+
+        let frame = self.world.frame_number();
+        let Some(diff) = self.world.get_update_history().updates().last() else {
+            return;
+        };
+        let event = ServerEvent::Update {
+            frame,
+            diff: diff.clone(),
+        };
+        for client in self.clients.iter_mut().filter(|client| client.streaming) {
+            client.send(&event);
+        }
+    }
+
+    /// Build the reply to `snapshot`
+    pub fn snapshot(&self) -> ServerEvent {
+        let mut entities = self.world.entities.to_vec();
+        entities.sort_by_key(|entity| entity.entity_index);
+        let mut diff = WorldUpdateDiff::new();
+        diff.record(describe_entities(&self.world, &self.components, &entities, |_| true));
+        ServerEvent::Snapshot {
+            frame: self.world.frame_number(),
+            diff,
+        }
+    }
+
+    fn execute(&mut self, client: usize, command: ControlCommand) -> ServerEvent {
+        match command {
+            ControlCommand::Status => {
+                return ServerEvent::Status {
+                    frame: self.world.frame_number(),
+                    paused: self.paused,
+                    tick_rate: self.tick_rate,
+                    entities: self.world.entity_count(),
+                }
+            }
+            ControlCommand::Snapshot => return self.snapshot(),
+            ControlCommand::Pause => self.paused = true,
+            ControlCommand::Resume => self.paused = false,
+            ControlCommand::Step(count) => {
+                if !self.paused {
+                    return ServerEvent::Error {
+                        message: "step requires the server to be paused".to_string(),
+                    };
+                }
+                for _ in 0..count {
+                    self.tick();
+                }
+            }
+            ControlCommand::Stream(enabled) => self.clients[client].streaming = enabled,
+            ControlCommand::TickRate(rate) => self.tick_rate = rate,
+            ControlCommand::Shutdown => self.running.store(false, Ordering::SeqCst),
+        }
+        ServerEvent::Ok {
+            frame: self.world.frame_number(),
+        }
+    }
+
+    fn tick_interval(&self) -> Duration {
+        if self.tick_rate > 0.0 {
+            Duration::try_from_secs_f64(1.0 / self.tick_rate)
+                .map_or(MAX_TICK_INTERVAL, |interval| interval.min(MAX_TICK_INTERVAL))
+        } else {
+            Duration::ZERO
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_commands() {
+        assert_eq!(ControlCommand::parse("status"), Ok(ControlCommand::Status));
+        assert_eq!(ControlCommand::parse("  step  "), Ok(ControlCommand::Step(1)));
+        assert_eq!(ControlCommand::parse("step 5"), Ok(ControlCommand::Step(5)));
+        assert_eq!(ControlCommand::parse("stream on"), Ok(ControlCommand::Stream(true)));
+        assert_eq!(ControlCommand::parse("tick-rate 0.5"), Ok(ControlCommand::TickRate(0.5)));
+        assert!(ControlCommand::parse("").is_err());
+        assert!(ControlCommand::parse("step -1").is_err());
+        assert!(ControlCommand::parse("tick-rate -3").is_err());
+        assert!(ControlCommand::parse("tick-rate 1e-310").is_err());
+        assert!(ControlCommand::parse("tick-rate inf").is_err());
+        assert!(ControlCommand::parse("tick-rate NaN").is_err());
+        assert!(ControlCommand::parse("stream maybe").is_err());
+        assert!(ControlCommand::parse("pause now").is_err());
+    }
+
+    #[test]
+    fn test_event_format() {
+        let json = serde_json::to_string(&ServerEvent::Ok { frame: 3 }).unwrap();
+        assert_eq!(json, r#"{"event":"ok","frame":3}"#);
+    }
+
+    #[test]
+    fn test_step_requires_pause() {
+        let config = ServerConfig {
+            control_address: None,
+            ..ServerConfig::default()
+        };
+        let mut server = Server::new(World::new(), config).unwrap();
+        assert!(matches!(server.execute(0, ControlCommand::Step(1)), ServerEvent::Error { .. }));
+        server.execute(0, ControlCommand::Pause);
+        assert!(matches!(server.execute(0, ControlCommand::Step(2)), ServerEvent::Ok { frame: 2 }));
+        server.execute(0, ControlCommand::Shutdown);
+        assert!(!server.is_running());
+    }
+
+    #[test]
+    fn test_overlong_commands_drop_the_client() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut sender = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let mut client = ControlClient::new(listener.accept().unwrap().0).unwrap();
+        sender.write_all(&[b'x'; MAX_COMMAND_LENGTH * 4]).unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while client.connected && Instant::now() < deadline {
+            assert!(client.read_lines().is_empty());
+            thread::sleep(POLL_INTERVAL);
+        }
+        assert!(!client.connected);
+        assert!(client.buffer.is_empty());
+    }
+}
@@ -0,0 +1,138 @@
+//! Shutting a world down cleanly, also when the process is interrupted.
+//!
+//! `World::shutdown` deinitializes the systems, writes the final state of the
+//! world to the replay log as a `KEYFRAME` section and finalizes the log, so
+//! the log ends with its end marker and can be replayed to the last frame.
+//!
+//! With the "shutdown-handler" feature, `install_shutdown_handler` makes
+//! Ctrl+C (SIGINT) and SIGTERM do the same instead of killing the process:
+//!
+//! ```ignore
+//! world.install_shutdown_handler()?;
+//! loop {
+//!     world.update();
+//! }
+//! ```
+//!
+//! The signal only sets a flag. The world shuts down when its current update
+//! ends, then the process exits, so a signal received between updates takes
+//! effect at the end of the next one. Every world of the process that
+//! installed the handler shuts down this way.
+
+use crate::World;
+
+#[cfg(feature = "shutdown-handler")]
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set by the signal handler, read by the worlds at the end of each update
+#[cfg(feature = "shutdown-handler")]
+static SHUTDOWN_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Result of installing the process-wide signal handler, which can only be done once
+#[cfg(feature = "shutdown-handler")]
+static SIGNAL_HANDLER: std::sync::OnceLock<Result<(), String>> = std::sync::OnceLock::new();
+
+impl World {
+    /// Deinitialize the systems, log the final state of the world and
+    /// finalize the replay log, see the module documentation
+    pub fn shutdown(&mut self) -> Result<(), std::io::Error> {
+        ecs_info!("Shutting down world {} after {} frames", self.world_index, self.frame_number);
+        self.deinitialize_systems();
+        if self.replay_logger.is_some() {
+            let snapshot = self.snapshot();
+            if let Some(logger) = self.replay_logger.as_mut() {
+                logger.log_keyframe(&snapshot)?;
+            }
+        }
+        self.disable_replay_logging()
+    }
+
+    /// Shut the world down and exit the process when it receives SIGINT or
+    /// SIGTERM, at the end of the update in progress
+    #[cfg(feature = "shutdown-handler")]
+    pub fn install_shutdown_handler(&mut self) -> Result<(), String> {
+        SIGNAL_HANDLER
+            .get_or_init(|| {
+                ctrlc::set_handler(|| SHUTDOWN_REQUESTED.store(true, Ordering::SeqCst))
+                    .map_err(|e| format!("Failed to install the shutdown handler: {}", e))
+            })
+            .clone()?;
+        self.shutdown_on_signal = true;
+        Ok(())
+    }
+
+    /// Check if a signal asked the process to shut down
+    #[cfg(feature = "shutdown-handler")]
+    pub fn shutdown_requested(&self) -> bool {
+        SHUTDOWN_REQUESTED.load(Ordering::SeqCst)
+    }
+
+    /// Shut down and exit if a signal arrived and the handler is installed
+    #[cfg(feature = "shutdown-handler")]
+    pub(crate) fn shut_down_on_signal(&mut self) {
+        if !self.shutdown_on_signal || !self.shutdown_requested() {
+            return;
+        }
+        ecs_info!("Received a shutdown signal");
+        let exit_code = match self.shutdown() {
+            Ok(()) => 0,
+            Err(e) => {
+                ecs_error!("Failed to finalize replay logging: {}", e);
+                1
+            }
+        };
+        std::process::exit(exit_code);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::replay_analysis::parse_replay_log_str;
+    use crate::test_util::SharedBuffer;
+    use crate::{Diff, ReplayLogConfig, System, World, WorldView};
+
+    #[derive(Debug, Clone, Copy, PartialEq, Diff)]
+    struct Saved;
+
+    /// Marks an entity as saved when deinitialized
+    struct SaveSystem;
+
+    impl System for SaveSystem {
+        type InComponents = ();
+        type OutComponents = (Saved,);
+
+        fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+        fn update(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+        fn deinitialize(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+            let entity = world.create_entity();
+            world.record_entity_creation(entity);
+            world.add_component(entity, Saved);
+            world.record_component_addition(entity, &Saved);
+        }
+    }
+
+    #[test]
+    fn test_shutdown_deinitializes_and_finalizes_the_log() {
+        let buffer = SharedBuffer::default();
+        let mut world = World::new();
+        world.add_system(SaveSystem);
+        let config = ReplayLogConfig {
+            enabled: true,
+            ..ReplayLogConfig::default()
+        };
+        world.enable_replay_logging_to(config, buffer.clone()).unwrap();
+        world.update();
+        world.shutdown().unwrap();
+
+        assert!(!world.is_replay_logging_enabled());
+        assert_eq!(world.entities_with_component::<Saved>().len(), 1);
+        let log = buffer.contents();
+        assert!(log.contains("KEYFRAME 2\n"), "{}", log);
+        assert!(log.ends_with("# End of replay log - Total updates: 2\n"), "{}", log);
+        let history = parse_replay_log_str(&log);
+        assert_eq!(history.len(), 2);
+        assert_eq!(history.keyframes().len(), 1);
+    }
+}
@@ -0,0 +1,266 @@
+//! Spreading the work of expensive systems over several frames.
+//!
+//! In very large worlds a system that visits every entity each frame makes
+//! frame times grow with the world. A sliced system processes only a portion
+//! of its entities per frame instead, continuing round-robin where the
+//! previous frame stopped:
+//!
+//! ```ignore
+//! // Each frame, AiSystem sees the next 500 entities with its components
+//! world.add_sliced_system(AiSystem::default(), 500);
+//! ```
+//!
+//! At the first query of an update, the next `entities_per_frame` entities
+//! after the cursor that have every component the query reads or writes are
+//! selected, wrapping around to the first entity. All queries of the update
+//! then only see entities in that range of entity order; `With`, `Without`
+//! and `Changed` filters apply afterwards, so a slice can hold fewer matches.
+//! The range ends at the cursor of the next update.
+//!
+//! The cursor is an entity, not a position, so entities added or removed
+//! between frames do not shift the slices. It is kept with the system, and
+//! the slices depend only on the entities of the world, so a re-simulation
+//! slices the same way as the recording.
+
+use crate::{ComponentAccess, Entity, System, World};
+use std::any::TypeId;
+
+/// Slicing configuration and round-robin position of one system
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SliceCursor {
+    entities_per_frame: usize,
+    /// Last entity of the previous slice
+    last: Option<Entity>,
+}
+
+impl SliceCursor {
+    pub(crate) fn new(entities_per_frame: usize) -> Self {
+        assert!(entities_per_frame > 0, "a slice needs at least one entity per frame");
+        Self {
+            entities_per_frame,
+            last: None,
+        }
+    }
+}
+
+/// Entities of the world a sliced system sees during one update
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SliceWindow {
+    /// Fewer entities than a slice, so the system sees all of them
+    All,
+    /// Entities after `after` up to and including `through`, in entity order,
+    /// wrapping past the last entity if `through` comes first
+    Range { after: Option<Entity>, through: Entity },
+}
+
+impl SliceWindow {
+    fn contains(self, entity: Entity) -> bool {
+        match self {
+            SliceWindow::All => true,
+            SliceWindow::Range { after: None, through } => entity <= through,
+            SliceWindow::Range { after: Some(after), through } if after < through => entity > after && entity <= through,
+            SliceWindow::Range { after: Some(after), through } => entity > after || entity <= through,
+        }
+    }
+}
+
+/// Slice of the sliced system being updated
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ActiveSlice {
+    cursor: SliceCursor,
+    /// Selected at the system's first query
+    window: Option<SliceWindow>,
+}
+
+impl World {
+    /// Add a system that processes at most `entities_per_frame` of its
+    /// entities per update, round-robin
+    pub fn add_sliced_system<S: System + 'static>(&mut self, system: S, entities_per_frame: usize) {
+        self.add_system(system);
+        if let Some(system) = self.systems.last_mut() {
+            system.set_slice(Some(SliceCursor::new(entities_per_frame)));
+        }
+    }
+
+    /// Slice the already added systems of type `S`, or process all their
+    /// entities again with None. Returns false if no such system was added.
+    pub fn set_system_slice<S: System + 'static>(&mut self, entities_per_frame: Option<usize>) -> bool {
+        let name = std::any::type_name::<S>();
+        let mut found = false;
+        for system in self.systems.iter_mut().filter(|system| system.name() == name) {
+            system.set_slice(entities_per_frame.map(SliceCursor::new));
+            found = true;
+        }
+        found
+    }
+
+    /// Get the number of entities per update of the systems of type `S`,
+    /// None if they are not sliced
+    pub fn system_slice<S: System + 'static>(&self) -> Option<usize> {
+        let name = std::any::type_name::<S>();
+        self.systems
+            .iter()
+            .filter(|system| system.name() == name)
+            .find_map(|system| system.slice())
+            .map(|cursor| cursor.entities_per_frame)
+    }
+
+    /// Run a system update limited to the next slice of its entities,
+    /// advancing the cursor past the entities it saw
+    pub(crate) fn run_sliced<R>(&mut self, slice: &mut Option<SliceCursor>, update: impl FnOnce(&mut World) -> R) -> R {
+        let Some(cursor) = *slice else {
+            return update(self);
+        };
+        self.active_slice.set(Some(ActiveSlice { cursor, window: None }));
+        let result = update(self);
+        if let Some(ActiveSlice { cursor, window }) = self.active_slice.take() {
+            let last = match window {
+                Some(SliceWindow::Range { through, .. }) => Some(through),
+                Some(SliceWindow::All) => None,
+                // Without queries the system saw no entities and keeps its place
+                None => cursor.last,
+            };
+            *slice = Some(SliceCursor { last, ..cursor });
+        }
+        result
+    }
+
+    /// Select the entities of the running sliced system's update at its
+    /// first query, from the entities with every accessed component
+    pub(crate) fn select_slice_window(&self, accesses: impl FnOnce() -> Vec<ComponentAccess>) {
+        let Some(ActiveSlice { cursor, window: None }) = self.active_slice.get() else {
+            return;
+        };
+        let type_ids: Vec<TypeId> = accesses().into_iter().map(|access| access.type_id).collect();
+        let candidates: Vec<Entity> = self
+            .query_entities(false)
+            .into_iter()
+            .filter(|entity| type_ids.iter().all(|type_id| self.has_component_type(*type_id, *entity)))
+            .collect();
+        let window = if candidates.len() <= cursor.entities_per_frame {
+            SliceWindow::All
+        } else {
+            // Continue after the previous slice, wrapping around to the first entity
+            let start = cursor.last.map_or(0, |last| candidates.partition_point(|entity| *entity <= last));
+            let end = (start + cursor.entities_per_frame - 1) % candidates.len();
+            SliceWindow::Range {
+                after: start.checked_sub(1).map(|index| candidates[index]),
+                through: candidates[end],
+            }
+        };
+        self.active_slice.set(Some(ActiveSlice {
+            cursor,
+            window: Some(window),
+        }));
+    }
+
+    /// Check if a query of the running system can see the entity
+    pub(crate) fn in_slice(&self, entity: Entity) -> bool {
+        match self.active_slice.get() {
+            Some(ActiveSlice { window: Some(window), .. }) => window.contains(entity),
+            _ => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{In, Out, ReadOnlySystem, ReadOnlyWorldView, WorldView};
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Visits(usize);
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Scenery;
+
+    struct VisitSystem;
+
+    impl System for VisitSystem {
+        type InComponents = ();
+        type OutComponents = (Visits,);
+
+        fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+        fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+            for (_, visits) in world.query_components::<(Out<Visits>,)>() {
+                visits.0 += 1;
+            }
+        }
+
+        fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    }
+
+    /// Counts the visits it can see
+    struct VisitReportSystem(std::rc::Rc<std::cell::Cell<usize>>);
+
+    impl ReadOnlySystem for VisitReportSystem {
+        type InComponents = (Visits,);
+
+        fn update(&mut self, world: &ReadOnlyWorldView<Self::InComponents>) {
+            self.0.set(world.query::<(In<Visits>,)>().len());
+        }
+    }
+
+    fn visits(world: &World) -> Vec<usize> {
+        world
+            .entities_with_component::<Visits>()
+            .into_iter()
+            .map(|entity| world.get_component::<Visits>(entity).unwrap().0)
+            .collect()
+    }
+
+    #[test]
+    fn test_sliced_system_visits_entities_round_robin() {
+        let mut world = World::new();
+        for index in 0..5 {
+            let entity = world.create_entity();
+            world.add_component(entity, Visits(0));
+            // Entities without the system's components do not count towards a slice
+            if index % 2 == 0 {
+                let scenery = world.create_entity();
+                world.add_component(scenery, Scenery);
+            }
+        }
+        world.add_sliced_system(VisitSystem, 2);
+        assert_eq!(world.system_slice::<VisitSystem>(), Some(2));
+
+        world.update();
+        assert_eq!(visits(&world), vec![1, 1, 0, 0, 0]);
+        world.update();
+        assert_eq!(visits(&world), vec![1, 1, 1, 1, 0]);
+        // Wraps around to the first entity
+        world.update();
+        assert_eq!(visits(&world), vec![2, 1, 1, 1, 1]);
+
+        // Removing the next entity does not skip the one after it
+        let removed = world.entities_with_component::<Visits>()[1];
+        world.remove_entity(removed);
+        world.update();
+        assert_eq!(visits(&world), vec![2, 2, 2, 1]);
+
+        assert!(world.set_system_slice::<VisitSystem>(None));
+        world.update();
+        assert_eq!(visits(&world), vec![3, 3, 3, 2]);
+    }
+
+    #[test]
+    fn test_small_worlds_are_not_sliced() {
+        let mut world = World::new();
+        let seen = std::rc::Rc::new(std::cell::Cell::new(0));
+        for _ in 0..3 {
+            let entity = world.create_entity();
+            world.add_component(entity, Visits(0));
+        }
+        world.add_sliced_system(VisitReportSystem(seen.clone()), 5);
+        world.update();
+        assert_eq!(seen.get(), 3);
+
+        assert!(world.set_system_slice::<VisitReportSystem>(Some(2)));
+        world.update();
+        assert_eq!(seen.get(), 2);
+        world.update();
+        assert_eq!(seen.get(), 2);
+        assert!(!world.set_system_slice::<VisitSystem>(Some(2)));
+    }
+}
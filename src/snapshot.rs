@@ -0,0 +1,799 @@
+//! Full world state written at the top of replay logs.
+//!
+//! Replay logs only contain diffs, so on their own they describe a session
+//! relative to whatever state the world had when logging started. The
+//! `INITIAL_STATE` section captures that state, so a replay can rebuild the
+//! world from the log alone:
+//!
+//! ```text
+//! INITIAL_STATE
+//!   WORLD_OPERATIONS: 2
+//!     CREATE_ENTITY Entity(0, 0)
+//!     ADD_SYSTEM my_game::MovementSystem
+//!   COMPONENT_CHANGES: 1
+//!     ADD Entity(0, 0) Position (x:1,y:1)
+//!   RESOURCES: 1
+//!     RESOURCE Gravity (9.8)
+//! ```
+//!
+//! Components and resources are only included if their type is registered
+//! with `World::register_replay_component`; systems are rebuilt from the
+//! factories registered with `World::register_system_factory`.
+//!
+//! Logs recorded with frame sampling also contain `KEYFRAME <frame>` sections
+//! in the same format, holding the state after that frame.
+//!
+//! Snapshots also serve to compare worlds: `WorldSnapshot::diff` lists the
+//! entities, systems, components and resources that differ between two.
+
+use crate::replay_metadata::ReplayMetadata;
+use crate::{ChangeTick, DiffComponentChange, Entity, SystemUpdateDiff, World, WorldOperation};
+use std::any::TypeId;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::Write;
+
+/// Entities, components, resources and systems of a world at one point in time
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WorldSnapshot {
+    /// Entity creations and system additions followed by component additions
+    pub diff: SystemUpdateDiff,
+    /// Encoded resources by short type name
+    pub resources: Vec<(String, String)>,
+    /// Serialized layout versions of the component types above version 1,
+    /// None if they are those of the replay the snapshot belongs to
+    #[cfg_attr(feature = "serde", serde(default = "crate::versioning::unversioned"))]
+    pub component_versions: Option<BTreeMap<String, u32>>,
+}
+
+impl WorldSnapshot {
+    /// Check if the snapshot holds no state at all
+    pub fn is_empty(&self) -> bool {
+        self.diff.world_operations().is_empty()
+            && self.diff.component_changes().is_empty()
+            && self.resources.is_empty()
+    }
+
+    /// Get the entities of the snapshot
+    pub fn entities(&self) -> Vec<Entity> {
+        self.diff
+            .world_operations()
+            .iter()
+            .filter_map(|operation| match operation {
+                WorldOperation::CreateEntity(entity) => Some(*entity),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Get the type names of the snapshot's systems in update order
+    pub fn systems(&self) -> Vec<&str> {
+        self.diff
+            .world_operations()
+            .iter()
+            .filter_map(|operation| match operation {
+                WorldOperation::AddSystem(name) => Some(name.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Get the encoded components of the snapshot with their entity and short type name
+    pub fn components(&self) -> Vec<(Entity, &str, &str)> {
+        self.diff
+            .component_changes()
+            .iter()
+            .filter_map(|change| match change {
+                DiffComponentChange::Added { entity, type_name, data } => {
+                    Some((*entity, type_name.as_str(), data.as_str()))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Hash the contents of the snapshot to check cheaply that two states
+    /// are identical. Entries are hashed in sorted order, so the same state
+    /// stored in a different order has the same checksum.
+    pub fn checksum(&self) -> String {
+        let mut entry = Vec::new();
+        write_snapshot_entry(&mut entry, "STATE", self).expect("writing to memory cannot fail");
+        let entry = String::from_utf8_lossy(&entry);
+        let mut lines: Vec<&str> = entry.lines().collect();
+        lines.sort_unstable();
+        ReplayMetadata::hash_config(&lines.join("\n"))
+    }
+
+    /// List the differences turning this snapshot into `other`.
+    ///
+    /// Components and resources are compared in their encoded form, so only
+    /// types registered for replay in both worlds are compared.
+    pub fn diff(&self, other: &WorldSnapshot) -> Vec<SnapshotDifference> {
+        let mut differences = Vec::new();
+
+        let entities: HashSet<Entity> = self.entities().into_iter().collect();
+        let other_entities = other.entities();
+        let other_entity_set: HashSet<Entity> = other_entities.iter().copied().collect();
+        for entity in self.entities() {
+            if !other_entity_set.contains(&entity) {
+                differences.push(SnapshotDifference::EntityRemoved(entity));
+            }
+        }
+        for entity in other_entities {
+            if !entities.contains(&entity) {
+                differences.push(SnapshotDifference::EntityAdded(entity));
+            }
+        }
+
+        let mut other_systems = other.systems();
+        for system in self.systems() {
+            match other_systems.iter().position(|other| *other == system) {
+                Some(index) => {
+                    other_systems.remove(index);
+                }
+                None => differences.push(SnapshotDifference::SystemRemoved(system.to_string())),
+            }
+        }
+        differences.extend(
+            other_systems
+                .into_iter()
+                .map(|system| SnapshotDifference::SystemAdded(system.to_string())),
+        );
+
+        let components: HashMap<(Entity, &str), &str> = self
+            .components()
+            .into_iter()
+            .map(|(entity, type_name, data)| ((entity, type_name), data))
+            .collect();
+        let other_components: HashMap<(Entity, &str), &str> = other
+            .components()
+            .into_iter()
+            .map(|(entity, type_name, data)| ((entity, type_name), data))
+            .collect();
+        for (entity, type_name, data) in self.components() {
+            match other_components.get(&(entity, type_name)) {
+                None => differences.push(SnapshotDifference::ComponentRemoved {
+                    entity,
+                    type_name: type_name.to_string(),
+                    data: data.to_string(),
+                }),
+                Some(other_data) if *other_data != data => differences.push(SnapshotDifference::ComponentChanged {
+                    entity,
+                    type_name: type_name.to_string(),
+                    from: data.to_string(),
+                    to: other_data.to_string(),
+                }),
+                Some(_) => {}
+            }
+        }
+        for (entity, type_name, data) in other.components() {
+            if !components.contains_key(&(entity, type_name)) {
+                differences.push(SnapshotDifference::ComponentAdded {
+                    entity,
+                    type_name: type_name.to_string(),
+                    data: data.to_string(),
+                });
+            }
+        }
+
+        for (type_name, data) in &self.resources {
+            match other.resources.iter().find(|(other, _)| other == type_name) {
+                None => differences.push(SnapshotDifference::ResourceRemoved {
+                    type_name: type_name.clone(),
+                    data: data.clone(),
+                }),
+                Some((_, other_data)) if other_data != data => differences.push(SnapshotDifference::ResourceChanged {
+                    type_name: type_name.clone(),
+                    from: data.clone(),
+                    to: other_data.clone(),
+                }),
+                Some(_) => {}
+            }
+        }
+        for (type_name, data) in &other.resources {
+            if !self.resources.iter().any(|(own, _)| own == type_name) {
+                differences.push(SnapshotDifference::ResourceAdded {
+                    type_name: type_name.clone(),
+                    data: data.clone(),
+                });
+            }
+        }
+
+        differences
+    }
+}
+
+/// A difference between two snapshots, from the snapshot `diff` is called on
+/// to the one it is compared with. Components and resources are encoded.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SnapshotDifference {
+    EntityAdded(Entity),
+    EntityRemoved(Entity),
+    SystemAdded(String),
+    SystemRemoved(String),
+    ComponentAdded {
+        entity: Entity,
+        type_name: String,
+        data: String,
+    },
+    ComponentRemoved {
+        entity: Entity,
+        type_name: String,
+        data: String,
+    },
+    ComponentChanged {
+        entity: Entity,
+        type_name: String,
+        from: String,
+        to: String,
+    },
+    ResourceAdded {
+        type_name: String,
+        data: String,
+    },
+    ResourceRemoved {
+        type_name: String,
+        data: String,
+    },
+    ResourceChanged {
+        type_name: String,
+        from: String,
+        to: String,
+    },
+}
+
+/// Formats the difference for reports, e.g. `Entity(0, 1) Position: (x:1,y:1) -> (x:2,y:1)`
+impl std::fmt::Display for SnapshotDifference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SnapshotDifference::EntityAdded(entity) => write!(f, "{} added", entity),
+            SnapshotDifference::EntityRemoved(entity) => write!(f, "{} removed", entity),
+            SnapshotDifference::SystemAdded(name) => write!(f, "system {} added", name),
+            SnapshotDifference::SystemRemoved(name) => write!(f, "system {} removed", name),
+            SnapshotDifference::ComponentAdded { entity, type_name, data } => {
+                write!(f, "{} {} added: {}", entity, type_name, data)
+            }
+            SnapshotDifference::ComponentRemoved { entity, type_name, data } => {
+                write!(f, "{} {} removed: {}", entity, type_name, data)
+            }
+            SnapshotDifference::ComponentChanged { entity, type_name, from, to } => {
+                write!(f, "{} {}: {} -> {}", entity, type_name, from, to)
+            }
+            SnapshotDifference::ResourceAdded { type_name, data } => {
+                write!(f, "resource {} added: {}", type_name, data)
+            }
+            SnapshotDifference::ResourceRemoved { type_name, data } => {
+                write!(f, "resource {} removed: {}", type_name, data)
+            }
+            SnapshotDifference::ResourceChanged { type_name, from, to } => {
+                write!(f, "resource {}: {} -> {}", type_name, from, to)
+            }
+        }
+    }
+}
+
+/// World state written between the updates of a sampled replay log
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Keyframe {
+    /// Frame number after which the state was captured
+    pub frame: usize,
+    /// Number of recorded updates preceding the keyframe in the history
+    pub update_index: usize,
+    pub state: WorldSnapshot,
+}
+
+/// Write a snapshot as a section of a replay log, headed `INITIAL_STATE` or `KEYFRAME <frame>`
+pub(crate) fn write_snapshot_entry<W: Write + ?Sized>(
+    writer: &mut W,
+    header: &str,
+    snapshot: &WorldSnapshot,
+) -> std::io::Result<()> {
+    writeln!(writer, "{}", header)?;
+    let operations = snapshot.diff.world_operations();
+    if !operations.is_empty() {
+        writeln!(writer, "  WORLD_OPERATIONS: {}", operations.len())?;
+        for operation in operations {
+            writeln!(writer, "    {}", operation)?;
+        }
+    }
+    let changes = snapshot.diff.component_changes();
+    if !changes.is_empty() {
+        writeln!(writer, "  COMPONENT_CHANGES: {}", changes.len())?;
+        for change in changes {
+            writeln!(writer, "    {}", change)?;
+        }
+    }
+    if !snapshot.resources.is_empty() {
+        writeln!(writer, "  RESOURCES: {}", snapshot.resources.len())?;
+        for (type_name, data) in &snapshot.resources {
+            writeln!(writer, "    RESOURCE {} {}", type_name, data)?;
+        }
+    }
+    writeln!(writer)
+}
+
+impl World {
+    /// Capture the current state of the world.
+    ///
+    /// Components and resources whose type is not registered for replay are
+    /// left out, since they could not be restored.
+    pub fn snapshot(&self) -> WorldSnapshot {
+        let skipped = self
+            .components
+            .iter()
+            .filter(|(type_id, components)| {
+                !components.is_empty()
+                    && !self
+                        .component_registry
+                        .get_by_type_id(**type_id)
+                        .is_some_and(|registration| registration.is_replayable())
+            })
+            .count();
+        if skipped > 0 {
+            ecs_warn!("Snapshot skips {} component types not registered for replay", skipped);
+        }
+        self.capture_state()
+    }
+
+    /// Capture the entities, systems, and the components and resources of
+    /// the types registered for replay
+    fn capture_state(&self) -> WorldSnapshot {
+        let mut diff = SystemUpdateDiff::new();
+        for entity in &self.entities {
+            diff.record_world_operation(WorldOperation::CreateEntity(*entity));
+        }
+        for system in &self.systems {
+            diff.record_world_operation(WorldOperation::AddSystem(system.name().to_string()));
+        }
+        for (group, members) in &self.groups {
+            for entity in members {
+                diff.record_world_operation(WorldOperation::AddToGroup(*entity, group.clone()));
+            }
+        }
+        for (relation, links) in &self.relations {
+            for (source, target) in links {
+                diff.record_world_operation(WorldOperation::Relate(*source, relation.clone(), *target));
+            }
+        }
+        for entity in &self.disabled_entities {
+            diff.record_world_operation(WorldOperation::DisableEntity(*entity));
+        }
+
+        let mut resources = Vec::new();
+        for registration in self.component_registry.iter().filter(|r| r.is_replayable()) {
+            for (entity, component) in self.components.get(&registration.type_id()).into_iter().flatten() {
+                if let Some(data) = registration.encode(component) {
+                    diff.record_component_change(DiffComponentChange::Added {
+                        entity,
+                        type_name: registration.type_name().into(),
+                        data: data.into(),
+                    });
+                }
+            }
+            if let Some(resource) = self.resources.get(&registration.type_id()) {
+                if let Some(data) = registration.encode(resource.as_ref()) {
+                    resources.push((registration.type_name().to_string(), data));
+                }
+            }
+        }
+
+        for (type_name, entity, value) in self.dynamic.iter() {
+            diff.record_component_change(DiffComponentChange::Added {
+                entity,
+                type_name: type_name.into(),
+                data: value.to_string().into(),
+            });
+        }
+
+        WorldSnapshot {
+            diff,
+            resources,
+            component_versions: Some(self.component_versions()),
+        }
+    }
+
+    /// Add the entities, components, resources and systems of a snapshot to
+    /// the world, migrating components saved with older versions
+    pub fn restore_snapshot(&mut self, snapshot: &WorldSnapshot) {
+        let replayed_versions = snapshot
+            .component_versions
+            .clone()
+            .map(|versions| self.replace_replayed_component_versions(Some(versions)));
+        self.apply_system_diff(&snapshot.diff);
+        for (type_name, data) in &snapshot.resources {
+            let resource = self
+                .replay_registration(type_name)
+                .and_then(|registration| Ok((registration.type_id(), registration.decode(data)?)));
+            match resource {
+                Ok((type_id, resource)) => {
+                    self.resources.insert(type_id, resource);
+                }
+                Err(e) => ecs_error!("Failed to restore resource: {}", e),
+            }
+        }
+        if let Some(versions) = replayed_versions {
+            self.replace_replayed_component_versions(versions);
+        }
+    }
+
+    /// Capture the state a system update is undone to in replay mode. The
+    /// observers are muted until the state is restored, so neither the
+    /// update nor its undo calls them.
+    pub(crate) fn system_snapshot(&mut self) -> SystemSnapshot {
+        let snapshot = SystemSnapshot {
+            state: self.capture_state(),
+            next_entity_id: self.next_entity_id,
+            component_ticks: self.component_ticks.clone(),
+            memberships: self.memberships.clone(),
+        };
+        self.observers_muted = true;
+        snapshot
+    }
+
+    /// Undo the changes made since the snapshot was captured to entities
+    /// and to the components and resources registered for replay. Change
+    /// ticks and memberships are put back as captured, so the undone update
+    /// leaves no changes behind for `Changed` queries or membership tracking.
+    ///
+    /// In debug builds, panics if the world's checksum differs from the
+    /// snapshot's afterwards.
+    pub(crate) fn restore_system_snapshot(&mut self, snapshot: SystemSnapshot) {
+        let current = self.capture_state();
+        // The undo refers to this world's own entities
+        let remapper = self.entity_remapper.take();
+        self.apply_system_diff(&undo_diff(&snapshot.state, &current));
+        self.entity_remapper = remapper;
+
+        let resources: HashMap<&str, &str> = snapshot
+            .state
+            .resources
+            .iter()
+            .map(|(type_name, data)| (type_name.as_str(), data.as_str()))
+            .collect();
+        for (type_name, _) in current.resources.iter().filter(|(type_name, _)| !resources.contains_key(type_name.as_str())) {
+            if let Ok(registration) = self.replay_registration(type_name) {
+                self.resources.remove(&registration.type_id());
+            }
+        }
+        for (type_name, data) in &snapshot.state.resources {
+            if current.resources.iter().any(|(current_type, current_data)| current_type == type_name && current_data == data) {
+                continue;
+            }
+            match self
+                .replay_registration(type_name)
+                .and_then(|registration| Ok((registration.type_id(), registration.decode(data)?)))
+            {
+                Ok((type_id, resource)) => {
+                    self.resources.insert(type_id, resource);
+                }
+                Err(e) => ecs_error!("Failed to restore resource: {}", e),
+            }
+        }
+        self.next_entity_id = snapshot.next_entity_id;
+        self.component_ticks = snapshot.component_ticks;
+        self.memberships = snapshot.memberships;
+        self.observers_muted = false;
+
+        debug_assert_eq!(
+            self.capture_state().checksum(),
+            snapshot.state.checksum(),
+            "world {} differs from its snapshot after restoring it: {:?}",
+            self.world_index,
+            snapshot.state.diff(&self.capture_state())
+        );
+    }
+}
+
+/// World state captured before a system update in replay mode
+pub(crate) struct SystemSnapshot {
+    state: WorldSnapshot,
+    next_entity_id: usize,
+    component_ticks: HashMap<(TypeId, Entity), ChangeTick>,
+    memberships: crate::membership::Memberships,
+}
+
+/// Build the entries turning the world captured in `current` back into `target`.
+/// Systems are left alone, they are not part of a system's update.
+fn undo_diff(target: &WorldSnapshot, current: &WorldSnapshot) -> SystemUpdateDiff {
+    let state_operations = |snapshot: &WorldSnapshot| -> Vec<WorldOperation> {
+        snapshot
+            .diff
+            .world_operations()
+            .iter()
+            .filter(|operation| !matches!(operation, WorldOperation::AddSystem(_)))
+            .cloned()
+            .collect()
+    };
+    let target_operations = state_operations(target);
+    let current_operations = state_operations(current);
+    let target_set: HashSet<&WorldOperation> = target_operations.iter().collect();
+    let current_set: HashSet<&WorldOperation> = current_operations.iter().collect();
+
+    let mut undo = SystemUpdateDiff::new();
+    // Removing an entity also drops its components, groups and relations
+    for operation in current_operations.iter().filter(|operation| !target_set.contains(operation)) {
+        let inverse = match operation {
+            WorldOperation::CreateEntity(entity) => WorldOperation::RemoveEntity(*entity),
+            WorldOperation::AddToGroup(entity, group) => WorldOperation::RemoveFromGroup(*entity, group.clone()),
+            WorldOperation::Relate(source, relation, target) => {
+                WorldOperation::Unrelate(*source, relation.clone(), *target)
+            }
+            WorldOperation::DisableEntity(entity) => WorldOperation::EnableEntity(*entity),
+            _ => continue,
+        };
+        undo.record_world_operation(inverse);
+    }
+    for operation in target_operations.iter().filter(|operation| !current_set.contains(operation)) {
+        undo.record_world_operation(operation.clone());
+    }
+
+    let target_components: HashMap<(Entity, &str), &str> = target
+        .components()
+        .into_iter()
+        .map(|(entity, type_name, data)| ((entity, type_name), data))
+        .collect();
+    let current_components: HashMap<(Entity, &str), &str> = current
+        .components()
+        .into_iter()
+        .map(|(entity, type_name, data)| ((entity, type_name), data))
+        .collect();
+    for (entity, type_name, _) in current.components() {
+        if !target_components.contains_key(&(entity, type_name)) {
+            undo.record_component_change(DiffComponentChange::Removed {
+                entity,
+                type_name: type_name.into(),
+            });
+        }
+    }
+    // Additions replace the components that were changed
+    for (entity, type_name, data) in target.components() {
+        if current_components.get(&(entity, type_name)) != Some(&data) {
+            undo.record_component_change(DiffComponentChange::Added {
+                entity,
+                type_name: type_name.into(),
+                data: data.into(),
+            });
+        }
+    }
+    undo
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+    use crate::replay_analysis::{parse_replay_log_str, write_replay_log};
+    use crate::{Diff, Out, Reflect, System, WorldUpdateHistory, WorldView};
+
+    #[derive(Debug, Clone, Copy, PartialEq, Diff, Reflect, serde::Serialize, serde::Deserialize)]
+    struct Health {
+        current: i32,
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Diff, Reflect, serde::Serialize, serde::Deserialize)]
+    struct Gravity {
+        strength: i32,
+    }
+
+    fn registered_world() -> World {
+        let mut world = World::new();
+        world.register_replay_component::<Health>();
+        world.register_replay_component::<Gravity>();
+        world
+    }
+
+    #[test]
+    fn test_snapshot_survives_replay_log() {
+        let mut world = registered_world();
+        let first = world.create_entity();
+        let second = world.create_entity();
+        world.add_component(second, Health { current: 7 });
+        world.add_component(first, 1u8);
+        world.insert_resource(Gravity { strength: 3 });
+
+        let mut history = WorldUpdateHistory::new();
+        history.set_initial_state(world.snapshot());
+        let mut log = Vec::new();
+        write_replay_log(&history, &mut log).unwrap();
+        let log = String::from_utf8(log).unwrap();
+        assert!(log.contains("ADD Entity(0, 1) Health (current:7)"));
+        assert!(log.contains("RESOURCE Gravity (strength:3)"));
+
+        let parsed = parse_replay_log_str(&log);
+        let mut restored = registered_world();
+        restored.restore_snapshot(parsed.initial_state().unwrap());
+
+        assert_eq!(restored.entity_count(), 2);
+        assert_eq!(restored.get_component::<Health>(second), Some(&Health { current: 7 }));
+        // Unregistered component types are not part of the snapshot
+        assert!(restored.get_component::<u8>(first).is_none());
+        assert_eq!(restored.resource::<Gravity>(), Some(&Gravity { strength: 3 }));
+        assert_eq!(restored.create_entity(), crate::Entity::new(0, 2));
+    }
+
+    #[test]
+    fn test_snapshot_diff() {
+        let mut world = registered_world();
+        let first = world.create_entity();
+        let second = world.create_entity();
+        world.add_component(first, Health { current: 7 });
+        world.add_component(second, Health { current: 3 });
+        world.insert_resource(Gravity { strength: 3 });
+        let before = world.snapshot();
+        assert!(before.diff(&world.snapshot()).is_empty());
+
+        world.get_component_mut::<Health>(first).unwrap().current = 5;
+        world.remove_component::<Health>(second);
+        let third = world.create_entity();
+        world.add_component(third, Health { current: 9 });
+        world.remove_resource::<Gravity>();
+
+        let differences = before.diff(&world.snapshot());
+        assert_eq!(
+            differences,
+            vec![
+                SnapshotDifference::EntityAdded(third),
+                SnapshotDifference::ComponentChanged {
+                    entity: first,
+                    type_name: "Health".to_string(),
+                    from: "(current:7)".to_string(),
+                    to: "(current:5)".to_string(),
+                },
+                SnapshotDifference::ComponentRemoved {
+                    entity: second,
+                    type_name: "Health".to_string(),
+                    data: "(current:3)".to_string(),
+                },
+                SnapshotDifference::ComponentAdded {
+                    entity: third,
+                    type_name: "Health".to_string(),
+                    data: "(current:9)".to_string(),
+                },
+                SnapshotDifference::ResourceRemoved {
+                    type_name: "Gravity".to_string(),
+                    data: "(strength:3)".to_string(),
+                },
+            ]
+        );
+        assert_eq!(differences[1].to_string(), "Entity(0, 0) Health: (current:7) -> (current:5)");
+    }
+
+    #[test]
+    fn test_checksum_ignores_storage_order() {
+        let mut world = registered_world();
+        let first = world.create_entity();
+        let second = world.create_entity();
+        world.add_component(first, Health { current: 7 });
+        world.add_component(second, Health { current: 3 });
+        let checksum = world.snapshot().checksum();
+
+        // Re-adding puts the component last in storage
+        world.remove_component::<Health>(first);
+        world.add_component(first, Health { current: 7 });
+        assert_eq!(world.snapshot().checksum(), checksum);
+        world.get_component_mut::<Health>(first).unwrap().current = 6;
+        assert_ne!(world.snapshot().checksum(), checksum);
+    }
+
+    /// Heals, spawns, despawns and changes gravity in one update
+    struct MeddlingSystem {
+        victim: Entity,
+    }
+
+    impl System for MeddlingSystem {
+        type InComponents = ();
+        type OutComponents = (Health,);
+
+        fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+        fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+            let mut changes = Vec::new();
+            for (entity, health) in world.query_components::<(Out<Health>,)>() {
+                let old = *health;
+                health.current += 1;
+                changes.push((entity, old, *health));
+            }
+            for (entity, old, new) in changes {
+                world.record_component_modification(entity, &old, &new);
+            }
+            let spawned = world.create_entity();
+            world.record_entity_creation(spawned);
+            world.add_component(spawned, Health { current: 1 });
+            world.commands().despawn(self.victim);
+            world.resource_mut::<Gravity>().unwrap().strength = 0;
+        }
+
+        fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    }
+
+    #[test]
+    fn test_replay_mode_restores_the_world_after_each_system() {
+        let mut world = registered_world();
+        let first = world.create_entity();
+        let victim = world.create_entity();
+        world.add_component(first, Health { current: 7 });
+        world.add_component(victim, Health { current: 3 });
+        world.add_to_group(victim, "enemies");
+        world.insert_resource(Gravity { strength: 3 });
+        world.add_system(MeddlingSystem { victim });
+        let before = world.snapshot();
+
+        world.enable_replay_mode();
+        world.update();
+        world.update();
+
+        assert!(before.diff(&world.snapshot()).is_empty());
+        assert_eq!(world.snapshot().checksum(), before.checksum());
+        assert!(world.is_in_group(victim, "enemies"));
+        // The systems' changes are still recorded
+        let update = world.get_update_history().updates().last().unwrap().clone();
+        let system_diff = &update.system_diffs()[0];
+        assert_eq!(system_diff.component_changes().len(), 2);
+        assert!(system_diff.world_operations().contains(&WorldOperation::RemoveEntity(victim)));
+        // Entities created afterwards get the ids they would have without the undone updates
+        assert_eq!(world.create_entity(), Entity::new(0, 2));
+    }
+
+    #[test]
+    fn test_undone_updates_leave_no_changes_behind() {
+        let mut world = registered_world();
+        let first = world.create_entity();
+        let victim = world.create_entity();
+        world.add_component(first, Health { current: 7 });
+        world.add_component(victim, Health { current: 3 });
+        world.insert_resource(Gravity { strength: 3 });
+        world.add_system(MeddlingSystem { victim });
+        let observed = std::rc::Rc::new(std::cell::Cell::new(0));
+        let calls = observed.clone();
+        world.observe::<Health>(move |_, _, _| calls.set(calls.get() + 1));
+        let ticks = [first, victim].map(|entity| world.component_change_tick::<Health>(entity));
+
+        world.enable_replay_mode();
+        world.update();
+        world.update();
+
+        assert_eq!(observed.get(), 0);
+        assert_eq!([first, victim].map(|entity| world.component_change_tick::<Health>(entity)), ticks);
+        // Observers are back once the update is undone
+        let spawned = world.create_entity();
+        world.add_component(spawned, Health { current: 1 });
+        assert_eq!(observed.get(), 1);
+    }
+
+    /// Panics after changing the health of every entity
+    struct PanickingSystem;
+
+    impl System for PanickingSystem {
+        type InComponents = ();
+        type OutComponents = (Health,);
+
+        fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+        fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+            for (_, health) in world.query_components::<(Out<Health>,)>() {
+                health.current += 1;
+            }
+            panic!("replayed system failed");
+        }
+
+        fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    }
+
+    #[test]
+    fn test_observers_survive_a_panicking_replayed_system() {
+        let mut world = registered_world();
+        let entity = world.create_entity();
+        world.add_component(entity, Health { current: 3 });
+        world.add_system(PanickingSystem);
+        let observed = std::rc::Rc::new(std::cell::Cell::new(0));
+        let calls = observed.clone();
+        world.observe::<Health>(move |_, _, _| calls.set(calls.get() + 1));
+
+        world.enable_replay_mode();
+        world.update();
+
+        let spawned = world.create_entity();
+        world.add_component(spawned, Health { current: 1 });
+        assert_eq!(observed.get(), 1);
+    }
+}
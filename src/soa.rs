@@ -0,0 +1,427 @@
+//! Query results as columns of f32 for batch math.
+//!
+//! Components stay boxed one by one in storage, so a column query gathers
+//! the fields of the components it visits into one `Vec<f32>` per field and
+//! writes the changed values back afterwards. Heavy numeric systems run
+//! their loops over plain slices the compiler can vectorize:
+//!
+//! ```ignore
+//! #[derive(Debug, Clone, Diff, Soa)]
+//! struct Position { x: f32, y: f32 }
+//!
+//! let mut batch = world_view.query_columns::<(Out<Position>, In<Velocity>)>();
+//! let (_entities, [x, y, dx, dy]) = batch.columns_mut();
+//! for i in 0..x.len() {
+//!     x[i] += dx[i];
+//!     y[i] += dy[i];
+//! }
+//! world_view.write_columns(&batch); // records the modified positions
+//! ```
+//!
+//! Only components deriving `Soa` (structs of f32 fields) take part, with
+//! one column per field in declaration order. Columns of `In` components
+//! are read only; changing them has no effect on the world.
+
+use crate::{short_type_name, ComponentAccess, Diff, Entity, In, Out, World, WorldView};
+use std::any::TypeId;
+use std::fmt::Debug;
+use std::marker::PhantomData;
+
+/// Component whose fields are f32 lanes, implemented by `#[derive(Soa)]`
+pub trait SoaComponent: Diff + Clone + Debug + 'static {
+    /// Names of the fields, one column each
+    fn field_names() -> &'static [&'static str];
+
+    /// Write the fields to `lanes`, one value per field
+    fn to_lanes(&self, lanes: &mut [f32]);
+
+    /// Set the fields from `lanes`, one value per field
+    fn set_lanes(&mut self, lanes: &[f32]);
+}
+
+/// Part of a column query, `In<T>` to read the columns of T and `Out<T>` to
+/// also write them back
+pub trait ColumnQueryComponent {
+    type Component: SoaComponent;
+
+    /// Whether `write_columns` writes the columns back
+    const WRITES: bool;
+
+    fn access() -> ComponentAccess {
+        if Self::WRITES {
+            ComponentAccess::write::<Self::Component>()
+        } else {
+            ComponentAccess::read::<Self::Component>()
+        }
+    }
+
+    /// Append the fields of the entity's component to its columns
+    fn gather(world: &World, entity: Entity, columns: &mut [Vec<f32>]) {
+        let mut lanes = vec![0.0; columns.len()];
+        if let Some(component) = world.get_component::<Self::Component>(entity) {
+            component.to_lanes(&mut lanes);
+        }
+        for (column, lane) in columns.iter_mut().zip(lanes) {
+            column.push(lane);
+        }
+    }
+
+    /// Write the components whose columns changed to the sink
+    fn scatter<S: ColumnSink>(sink: &mut S, entities: &[Entity], columns: &[Vec<f32>]) {
+        if !Self::WRITES {
+            return;
+        }
+        let mut lanes = vec![0.0; columns.len()];
+        let mut changed = Vec::new();
+        for (index, entity) in entities.iter().enumerate() {
+            // The batch may be older than the entity's last change
+            let Some(old) = sink.world().get_component::<Self::Component>(*entity) else {
+                continue;
+            };
+            old.to_lanes(&mut lanes);
+            let new_lanes: Vec<f32> = columns.iter().map(|column| column[index]).collect();
+            // Compared bitwise, so NaNs written back unchanged are not writes
+            if lanes.iter().zip(&new_lanes).all(|(a, b)| a.to_bits() == b.to_bits()) {
+                continue;
+            }
+            let mut new = old.clone();
+            new.set_lanes(&new_lanes);
+            changed.push((*entity, old.clone(), new));
+        }
+        for (entity, old, new) in changed {
+            sink.write_component(entity, old, new);
+        }
+    }
+}
+
+impl<T: SoaComponent> ColumnQueryComponent for In<T> {
+    type Component = T;
+    const WRITES: bool = false;
+}
+
+impl<T: SoaComponent> ColumnQueryComponent for Out<T> {
+    type Component = T;
+    const WRITES: bool = true;
+}
+
+/// Where `write_columns` writes the changed components, a world or a system's
+/// view recording the modifications
+pub trait ColumnSink {
+    fn world(&self) -> &World;
+
+    /// Replace the entity's component, old being the value it replaces
+    fn write_component<T: SoaComponent>(&mut self, entity: Entity, old: T, new: T);
+}
+
+impl ColumnSink for World {
+    fn world(&self) -> &World {
+        self
+    }
+
+    fn write_component<T: SoaComponent>(&mut self, entity: Entity, _old: T, new: T) {
+        if let Some(component) = self.get_component_mut::<T>(entity) {
+            *component = new;
+        }
+    }
+}
+
+impl<I, O> ColumnSink for WorldView<I, O> {
+    fn world(&self) -> &World {
+        unsafe { WorldView::world(self) }
+    }
+
+    fn write_component<T: SoaComponent>(&mut self, entity: Entity, old: T, new: T) {
+        if let Some(component) = unsafe { self.world_mut() }.get_component_mut::<T>(entity) {
+            *component = new.clone();
+            self.record_component_modification(entity, &old, &new);
+        }
+    }
+}
+
+/// Tuples of `In<T>` and `Out<T>` queried as columns
+pub trait ColumnQuery {
+    /// Names of the columns, e.g. "Velocity.dx"
+    fn column_names() -> Vec<String>;
+
+    /// Components the query reads and writes, checked in strict access mode
+    fn accesses() -> Vec<ComponentAccess>;
+
+    /// Check if the entity has all the components of the query
+    fn matches(world: &World, entity: Entity) -> bool;
+
+    /// Append the fields of the entity's components to the columns
+    fn gather(world: &World, entity: Entity, columns: &mut [Vec<f32>]);
+
+    /// Write the `Out` components whose columns changed to the sink
+    fn scatter<S: ColumnSink>(sink: &mut S, entities: &[Entity], columns: &[Vec<f32>]);
+}
+
+macro_rules! impl_column_query {
+    ($($T:ident),+) => {
+        impl<$($T: ColumnQueryComponent),+> ColumnQuery for ($($T,)+) {
+            fn column_names() -> Vec<String> {
+                let mut names = Vec::new();
+                $(
+                    let type_name = short_type_name::<$T::Component>();
+                    names.extend($T::Component::field_names().iter().map(|field| format!("{}.{}", type_name, field)));
+                )+
+                names
+            }
+
+            fn accesses() -> Vec<ComponentAccess> {
+                vec![$($T::access()),+]
+            }
+
+            fn matches(world: &World, entity: Entity) -> bool {
+                $(world.has_component_type(TypeId::of::<$T::Component>(), entity))&&+
+            }
+
+            fn gather(world: &World, entity: Entity, columns: &mut [Vec<f32>]) {
+                let mut offset = 0;
+                $(
+                    let count = $T::Component::field_names().len();
+                    $T::gather(world, entity, &mut columns[offset..offset + count]);
+                    offset += count;
+                )+
+                let _ = offset;
+            }
+
+            fn scatter<S: ColumnSink>(sink: &mut S, entities: &[Entity], columns: &[Vec<f32>]) {
+                let mut offset = 0;
+                $(
+                    let count = $T::Component::field_names().len();
+                    $T::scatter(sink, entities, &columns[offset..offset + count]);
+                    offset += count;
+                )+
+                let _ = offset;
+            }
+        }
+    };
+}
+
+impl_column_query!(A);
+impl_column_query!(A, B);
+impl_column_query!(A, B, C);
+impl_column_query!(A, B, C, D);
+impl_column_query!(A, B, C, D, E);
+impl_column_query!(A, B, C, D, E, F);
+impl_column_query!(A, B, C, D, E, F, G);
+impl_column_query!(A, B, C, D, E, F, G, H);
+
+/// Columns of the components of the entities a column query visited, in
+/// entity order
+pub struct ColumnBatch<Q> {
+    entities: Vec<Entity>,
+    names: Vec<String>,
+    columns: Vec<Vec<f32>>,
+    query: PhantomData<fn() -> Q>,
+}
+
+impl<Q: ColumnQuery> ColumnBatch<Q> {
+    fn gather(world: &World, candidates: Vec<Entity>) -> Self {
+        let names = Q::column_names();
+        let entities: Vec<Entity> = candidates.into_iter().filter(|entity| Q::matches(world, *entity)).collect();
+        let mut columns = vec![Vec::with_capacity(entities.len()); names.len()];
+        for entity in &entities {
+            Q::gather(world, *entity, &mut columns);
+        }
+        ColumnBatch {
+            entities,
+            names,
+            columns,
+            query: PhantomData,
+        }
+    }
+
+    /// Get the number of entities in the batch
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    /// Check if the query visited no entities
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    /// Get the entities, one per row of the columns
+    pub fn entities(&self) -> &[Entity] {
+        &self.entities
+    }
+
+    /// Get the names of the columns, e.g. "Position.x", in query order
+    pub fn column_names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Get a column by name, e.g. "Position.x"
+    pub fn column(&self, name: &str) -> Option<&[f32]> {
+        let index = self.names.iter().position(|column| column == name)?;
+        Some(&self.columns[index])
+    }
+
+    /// Get a mutable column by name, e.g. "Position.x"
+    pub fn column_mut(&mut self, name: &str) -> Option<&mut [f32]> {
+        let index = self.names.iter().position(|column| column == name)?;
+        Some(&mut self.columns[index])
+    }
+
+    /// Get the entities and all N columns at once, in query order.
+    /// Panics if the query does not have N columns.
+    pub fn columns_mut<const N: usize>(&mut self) -> (&[Entity], [&mut [f32]; N]) {
+        assert_eq!(
+            N,
+            self.columns.len(),
+            "query has {} columns: {}",
+            self.columns.len(),
+            self.names.join(", ")
+        );
+        let mut columns = self.columns.iter_mut();
+        let columns = std::array::from_fn(|_| columns.next().unwrap().as_mut_slice());
+        (&self.entities, columns)
+    }
+}
+
+impl World {
+    /// Gather the fields of the components of enabled entities having all
+    /// the query's components into columns, e.g.
+    /// `world.query_columns::<(Out<Position>, In<Velocity>)>()`
+    pub fn query_columns<Q: ColumnQuery>(&self) -> ColumnBatch<Q> {
+        ColumnBatch::gather(self, self.query_entities(false))
+    }
+
+    /// Write the columns of the batch's `Out` components back to the
+    /// components that changed
+    pub fn write_columns<Q: ColumnQuery>(&mut self, batch: &ColumnBatch<Q>) {
+        Q::scatter(self, &batch.entities, &batch.columns);
+    }
+}
+
+impl<I, O> WorldView<I, O> {
+    /// Query components as columns, see `World::query_columns`
+    pub fn query_columns<Q: ColumnQuery>(&mut self) -> ColumnBatch<Q> {
+        let world = unsafe { WorldView::world(self) };
+        world.check_access(Q::accesses);
+        world.select_slice_window(Q::accesses);
+        let batch = ColumnBatch::gather(world, world.query_entities(false));
+        world.profile_accesses(Q::accesses, batch.len());
+        if let Some(counts) = unsafe { self.world_mut() }.query_counts.as_mut() {
+            counts.record(batch.len());
+        }
+        if let Some(reads) = &mut self.reads {
+            let read: Vec<&'static str> = Q::accesses()
+                .into_iter()
+                .filter(|access| !access.write)
+                .map(|access| access.type_name)
+                .collect();
+            for entity in &batch.entities {
+                reads.record_reads(*entity, &read);
+            }
+        }
+        batch
+    }
+
+    /// Write the changed columns back, recording the modified components
+    pub fn write_columns<Q: ColumnQuery>(&mut self, batch: &ColumnBatch<Q>) {
+        unsafe { WorldView::world(self) }.check_access(Q::accesses);
+        Q::scatter(self, &batch.entities, &batch.columns);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Soa;
+
+    #[derive(Debug, Clone, PartialEq, Diff, Soa)]
+    struct Position {
+        x: f32,
+        y: f32,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Diff, Soa)]
+    struct Velocity {
+        dx: f32,
+        dy: f32,
+    }
+
+    fn spawn(world: &mut World, x: f32, dx: f32) -> Entity {
+        let entity = world.create_entity();
+        world.add_component(entity, Position { x, y: 0.0 });
+        world.add_component(entity, Velocity { dx, dy: 1.0 });
+        entity
+    }
+
+    #[test]
+    fn test_columns_are_gathered_and_written_back() {
+        let mut world = World::new();
+        let first = spawn(&mut world, 1.0, 2.0);
+        let second = spawn(&mut world, 5.0, -1.0);
+        let still = world.create_entity();
+        world.add_component(still, Position { x: 9.0, y: 9.0 });
+
+        let mut batch = world.query_columns::<(Out<Position>, In<Velocity>)>();
+        assert_eq!(batch.entities(), [first, second]);
+        assert_eq!(batch.column_names(), ["Position.x", "Position.y", "Velocity.dx", "Velocity.dy"]);
+        assert_eq!(batch.column("Position.x"), Some(&[1.0, 5.0][..]));
+        assert_eq!(batch.column("Position.z"), None);
+
+        let (_, [x, y, dx, dy]) = batch.columns_mut();
+        for i in 0..x.len() {
+            x[i] += dx[i];
+            y[i] += dy[i];
+            // Read only columns are not written back
+            dx[i] = 0.0;
+        }
+        world.write_columns(&batch);
+        assert_eq!(world.get_component::<Position>(first), Some(&Position { x: 3.0, y: 1.0 }));
+        assert_eq!(world.get_component::<Position>(second), Some(&Position { x: 4.0, y: 1.0 }));
+        assert_eq!(world.get_component::<Velocity>(first).unwrap().dx, 2.0);
+        assert_eq!(world.get_component::<Position>(still), Some(&Position { x: 9.0, y: 9.0 }));
+    }
+
+    struct MoveSystem;
+    impl crate::System for MoveSystem {
+        type InComponents = (Velocity,);
+        type OutComponents = (Position,);
+
+        fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+        fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+            let mut batch = world.query_columns::<(Out<Position>, In<Velocity>)>();
+            let (_, [x, _, dx, _]) = batch.columns_mut();
+            for (x, dx) in x.iter_mut().zip(dx.iter()) {
+                *x += dx;
+            }
+            world.write_columns(&batch);
+        }
+
+        fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    }
+
+    #[test]
+    fn test_systems_record_the_written_columns() {
+        let mut world = World::new();
+        let moving = spawn(&mut world, 0.0, 1.5);
+        spawn(&mut world, 0.0, 0.0);
+        world.add_system(MoveSystem);
+        world.update();
+        world.update();
+
+        assert_eq!(world.get_component::<Position>(moving).unwrap().x, 3.0);
+        let history = world.get_update_history();
+        // The system addition comes first, then only the moving entity changes
+        for update in &history.updates()[1..] {
+            let changes = update.system_diffs()[0].component_changes();
+            assert_eq!(changes.len(), 1);
+            assert_eq!(changes[0].entity(), moving);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "query has 2 columns: Position.x, Position.y")]
+    fn test_columns_mut_checks_the_column_count() {
+        let world = World::new();
+        let mut batch = world.query_columns::<(Out<Position>,)>();
+        let _ = batch.columns_mut::<3>();
+    }
+}
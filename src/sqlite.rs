@@ -0,0 +1,627 @@
+//! Replay storage in SQLite databases.
+//!
+//! Flat replay logs have to be read from the start to find anything. The
+//! store writes every frame's component changes and world operations into
+//! indexed tables instead, so questions like "all frames where entity 3's
+//! Position changed" are a single query:
+//!
+//! ```ignore
+//! let store = SqliteReplayStore::open("session.db")?;
+//! let frames = store.changed_frames(
+//!     &ChangeQuery::new().entity(Entity::new(0, 3)).component_type("Position"),
+//! )?;
+//! ```
+//!
+//! Frames are numbered from 0 in the order they were recorded, like the
+//! updates of a `WorldUpdateHistory`.
+
+use crate::{
+    DiffComponentChange, Entity, SystemUpdateDiff, World, WorldOperation, WorldUpdateDiff, WorldUpdateHistory,
+};
+use rusqlite::{params, Connection, OptionalExtension, Row, ToSql};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::Path;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS frames (
+        frame INTEGER PRIMARY KEY,
+        system_count INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS component_changes (
+        frame INTEGER NOT NULL,
+        system_index INTEGER NOT NULL,
+        position INTEGER NOT NULL,
+        operation TEXT NOT NULL,
+        world_index INTEGER NOT NULL,
+        entity_index INTEGER NOT NULL,
+        component_type TEXT NOT NULL,
+        data TEXT
+    );
+    CREATE INDEX IF NOT EXISTS component_changes_by_entity
+        ON component_changes (world_index, entity_index, frame);
+    CREATE INDEX IF NOT EXISTS component_changes_by_type ON component_changes (component_type, frame);
+    CREATE INDEX IF NOT EXISTS component_changes_by_frame ON component_changes (frame);
+    CREATE TABLE IF NOT EXISTS world_operations (
+        frame INTEGER NOT NULL,
+        system_index INTEGER NOT NULL,
+        position INTEGER NOT NULL,
+        operation TEXT NOT NULL,
+        world_index INTEGER,
+        entity_index INTEGER,
+        argument TEXT
+    );
+    CREATE INDEX IF NOT EXISTS world_operations_by_entity
+        ON world_operations (world_index, entity_index, frame);
+    CREATE INDEX IF NOT EXISTS world_operations_by_frame ON world_operations (frame);
+    CREATE TABLE IF NOT EXISTS system_failures (
+        frame INTEGER NOT NULL,
+        system_index INTEGER NOT NULL,
+        kind TEXT NOT NULL,
+        message TEXT NOT NULL
+    );
+";
+
+const TABLES: [&str; 4] = ["frames", "component_changes", "world_operations", "system_failures"];
+
+/// Filter for the changes returned by `SqliteReplayStore` queries.
+/// An empty query matches every change.
+#[derive(Debug, Clone, Default)]
+pub struct ChangeQuery {
+    entity: Option<Entity>,
+    component_type: Option<String>,
+    frames: Option<Range<usize>>,
+}
+
+impl ChangeQuery {
+    /// Create a query matching every change
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only match changes of this entity
+    pub fn entity(mut self, entity: Entity) -> Self {
+        self.entity = Some(entity);
+        self
+    }
+
+    /// Only match changes of components with this type name, e.g. `Position`
+    pub fn component_type(mut self, type_name: impl Into<String>) -> Self {
+        self.component_type = Some(type_name.into());
+        self
+    }
+
+    /// Only match changes in these frames
+    pub fn frames(mut self, frames: Range<usize>) -> Self {
+        self.frames = Some(frames);
+        self
+    }
+
+    /// SQL condition and its parameters; world operations have no component type
+    fn condition(&self, with_component_type: bool) -> (String, Vec<Box<dyn ToSql>>) {
+        let mut conditions = vec!["1".to_string()];
+        let mut parameters: Vec<Box<dyn ToSql>> = Vec::new();
+        if let Some(entity) = self.entity {
+            conditions.push("world_index = ? AND entity_index = ?".to_string());
+            parameters.push(Box::new(entity.world_index as i64));
+            parameters.push(Box::new(entity.entity_index as i64));
+        }
+        if let (Some(type_name), true) = (&self.component_type, with_component_type) {
+            conditions.push("component_type = ?".to_string());
+            parameters.push(Box::new(type_name.clone()));
+        }
+        if let Some(frames) = &self.frames {
+            conditions.push("frame >= ? AND frame < ?".to_string());
+            parameters.push(Box::new(frames.start as i64));
+            parameters.push(Box::new(frames.end.min(i64::MAX as usize) as i64));
+        }
+        (conditions.join(" AND "), parameters)
+    }
+}
+
+/// A component change together with where it was recorded
+#[derive(Debug, Clone)]
+pub struct RecordedChange {
+    pub frame: usize,
+    pub system_index: usize,
+    /// Position among the system's changes and operations, see `SystemUpdateDiff::sequences`
+    pub sequence: u64,
+    pub change: DiffComponentChange,
+}
+
+/// A world operation together with where it was recorded
+#[derive(Debug, Clone)]
+pub struct RecordedOperation {
+    pub frame: usize,
+    pub system_index: usize,
+    /// Position among the system's changes and operations, see `SystemUpdateDiff::sequences`
+    pub sequence: u64,
+    pub operation: WorldOperation,
+}
+
+/// Replay storage backed by a SQLite database
+pub struct SqliteReplayStore {
+    connection: Connection,
+}
+
+impl SqliteReplayStore {
+    /// Open or create a store in a database file
+    pub fn open<P: AsRef<Path>>(path: P) -> rusqlite::Result<Self> {
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    /// Create a store in an in-memory database
+    pub fn open_in_memory() -> rusqlite::Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(connection: Connection) -> rusqlite::Result<Self> {
+        connection.execute_batch(SCHEMA)?;
+        Ok(Self { connection })
+    }
+
+    /// Number of stored frames
+    pub fn frame_count(&self) -> rusqlite::Result<usize> {
+        let last: Option<i64> = self
+            .connection
+            .query_row("SELECT MAX(frame) FROM frames", [], |row| row.get(0))
+            .optional()?
+            .flatten();
+        Ok(last.map_or(0, |frame| frame as usize + 1))
+    }
+
+    /// Store a frame, replacing the frame and every later frame if they are
+    /// already stored (e.g. after the world was rewound)
+    pub fn record_frame(&mut self, frame: usize, update: &WorldUpdateDiff) -> rusqlite::Result<()> {
+        let transaction = self.connection.transaction()?;
+        for table in TABLES {
+            transaction.execute(&format!("DELETE FROM {} WHERE frame >= ?1", table), [frame as i64])?;
+        }
+        transaction.execute(
+            "INSERT INTO frames (frame, system_count) VALUES (?1, ?2)",
+            params![frame as i64, update.system_diffs().len() as i64],
+        )?;
+        {
+            let mut insert_change = transaction.prepare_cached(
+                "INSERT INTO component_changes
+                 (frame, system_index, position, operation, world_index, entity_index, component_type, data)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            )?;
+            let mut insert_operation = transaction.prepare_cached(
+                "INSERT INTO world_operations
+                 (frame, system_index, position, operation, world_index, entity_index, argument)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            )?;
+            for (system_index, system) in update.system_diffs().iter().enumerate() {
+                let failures = [("panic", system.failure()), ("error", system.error())];
+                for (kind, message) in failures {
+                    if let Some(message) = message {
+                        transaction.execute(
+                            "INSERT INTO system_failures (frame, system_index, kind, message) VALUES (?1, ?2, ?3, ?4)",
+                            params![frame as i64, system_index as i64, kind, message],
+                        )?;
+                    }
+                }
+                // Positions are sequence numbers, so the order across both tables is kept
+                let (change_sequence, operation_sequence) = system.sequences();
+                for (position, change) in change_sequence.into_iter().zip(system.component_changes()) {
+                    let (operation, entity, type_name, data) = match change {
+                        DiffComponentChange::Added { entity, type_name, data } => ("ADD", entity, type_name, Some(data)),
+                        DiffComponentChange::Modified { entity, type_name, diff } => ("MOD", entity, type_name, Some(diff)),
+                        DiffComponentChange::Removed { entity, type_name } => ("REM", entity, type_name, None),
+                    };
+                    insert_change.execute(params![
+                        frame as i64,
+                        system_index as i64,
+                        position as i64,
+                        operation,
+                        entity.world_index as i64,
+                        entity.entity_index as i64,
+                        type_name.as_str(),
+                        data.map(|data| data.as_str())
+                    ])?;
+                }
+                for (position, operation) in operation_sequence.into_iter().zip(system.world_operations()) {
+                    let relation_argument;
+                    let (name, world_index, entity_index, argument) = match operation {
+                        WorldOperation::CreateEntity(entity) => {
+                            ("CREATE_ENTITY", Some(entity.world_index), Some(entity.entity_index), None)
+                        }
+                        WorldOperation::RemoveEntity(entity) => {
+                            ("REMOVE_ENTITY", Some(entity.world_index), Some(entity.entity_index), None)
+                        }
+                        WorldOperation::CreateWorld(world_index) => ("CREATE_WORLD", Some(*world_index), None, None),
+                        WorldOperation::RemoveWorld(world_index) => ("REMOVE_WORLD", Some(*world_index), None, None),
+                        WorldOperation::AddSystem(system_type) => ("ADD_SYSTEM", None, None, Some(system_type)),
+                        WorldOperation::AddToGroup(entity, group) => {
+                            ("ADD_TO_GROUP", Some(entity.world_index), Some(entity.entity_index), Some(group))
+                        }
+                        WorldOperation::RemoveFromGroup(entity, group) => {
+                            ("REMOVE_FROM_GROUP", Some(entity.world_index), Some(entity.entity_index), Some(group))
+                        }
+                        WorldOperation::Relate(source, relation, target) => {
+                            relation_argument = format!("{} {}", relation, target);
+                            ("RELATE", Some(source.world_index), Some(source.entity_index), Some(&relation_argument))
+                        }
+                        WorldOperation::Unrelate(source, relation, target) => {
+                            relation_argument = format!("{} {}", relation, target);
+                            ("UNRELATE", Some(source.world_index), Some(source.entity_index), Some(&relation_argument))
+                        }
+                        WorldOperation::DisableEntity(entity) => {
+                            ("DISABLE_ENTITY", Some(entity.world_index), Some(entity.entity_index), None)
+                        }
+                        WorldOperation::EnableEntity(entity) => {
+                            ("ENABLE_ENTITY", Some(entity.world_index), Some(entity.entity_index), None)
+                        }
+                        WorldOperation::ClearEntities => ("CLEAR_ENTITIES", None, None, None),
+                        WorldOperation::ClearAll => ("CLEAR_ALL", None, None, None),
+                        WorldOperation::ResetHistory => ("RESET_HISTORY", None, None, None),
+                    };
+                    insert_operation.execute(params![
+                        frame as i64,
+                        system_index as i64,
+                        position as i64,
+                        name,
+                        world_index.map(|index| index as i64),
+                        entity_index.map(|index| index as i64),
+                        argument
+                    ])?;
+                }
+            }
+        }
+        transaction.commit()
+    }
+
+    /// Replace the stored frames with a whole history
+    pub fn save_history(&mut self, history: &WorldUpdateHistory) -> rusqlite::Result<()> {
+        if history.is_empty() {
+            for table in TABLES {
+                self.connection.execute(&format!("DELETE FROM {}", table), [])?;
+            }
+        }
+        for (frame, update) in history.updates().iter().enumerate() {
+            self.record_frame(frame, update)?;
+        }
+        Ok(())
+    }
+
+    /// Load every stored frame
+    pub fn load_history(&self) -> rusqlite::Result<WorldUpdateHistory> {
+        self.load_frames(0..usize::MAX)
+    }
+
+    /// Load the stored frames in a range as a history starting at `frames.start`
+    pub fn load_frames(&self, frames: Range<usize>) -> rusqlite::Result<WorldUpdateHistory> {
+        let query = ChangeQuery::new().frames(frames.clone());
+        let (condition, parameters) = query.condition(false);
+        let mut statement = self
+            .connection
+            .prepare(&format!("SELECT frame, system_count FROM frames WHERE {} ORDER BY frame", condition))?;
+        let stored: Vec<(usize, usize)> = statement
+            .query_map(rusqlite::params_from_iter(parameters.iter()), |row| {
+                Ok((row.get::<_, i64>(0)? as usize, row.get::<_, i64>(1)? as usize))
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+
+        let mut frame_systems: Vec<(usize, Vec<SystemUpdateDiff>)> = stored
+            .into_iter()
+            .map(|(frame, system_count)| (frame, vec![SystemUpdateDiff::new(); system_count]))
+            .collect();
+        let mut sequences: HashMap<(usize, usize), (Vec<u64>, Vec<u64>)> = HashMap::new();
+        for recorded in self.component_changes(&query)? {
+            if let Some(system) = stored_system(&mut frame_systems, recorded.frame, recorded.system_index) {
+                system.record_component_change(recorded.change);
+                sequences.entry((recorded.frame, recorded.system_index)).or_default().0.push(recorded.sequence);
+            }
+        }
+        for recorded in self.world_operations(&query)? {
+            if let Some(system) = stored_system(&mut frame_systems, recorded.frame, recorded.system_index) {
+                system.record_world_operation(recorded.operation);
+                sequences.entry((recorded.frame, recorded.system_index)).or_default().1.push(recorded.sequence);
+            }
+        }
+        for ((frame, system_index), (change_sequence, operation_sequence)) in sequences {
+            if let Some(system) = stored_system(&mut frame_systems, frame, system_index) {
+                system.set_sequences(change_sequence, operation_sequence);
+            }
+        }
+        let (condition, parameters) = query.condition(false);
+        let mut statement = self.connection.prepare(&format!(
+            "SELECT frame, system_index, kind, message FROM system_failures WHERE {}",
+            condition
+        ))?;
+        let failures = statement.query_map(rusqlite::params_from_iter(parameters.iter()), |row| {
+            Ok((
+                row.get::<_, i64>(0)? as usize,
+                row.get::<_, i64>(1)? as usize,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+            ))
+        })?;
+        for failure in failures {
+            let (frame, system_index, kind, message) = failure?;
+            if let Some(system) = stored_system(&mut frame_systems, frame, system_index) {
+                match kind.as_str() {
+                    "error" => system.record_error(message),
+                    _ => system.record_failure(message),
+                }
+            }
+        }
+
+        let mut history = WorldUpdateHistory::new();
+        for (_, systems) in frame_systems {
+            let mut update = WorldUpdateDiff::new();
+            for system in systems {
+                update.record(system);
+            }
+            history.record(update);
+        }
+        Ok(history)
+    }
+
+    /// Component changes matching a query, in recording order
+    pub fn component_changes(&self, query: &ChangeQuery) -> rusqlite::Result<Vec<RecordedChange>> {
+        let (condition, parameters) = query.condition(true);
+        let mut statement = self.connection.prepare(&format!(
+            "SELECT frame, system_index, operation, world_index, entity_index, component_type, data, position
+             FROM component_changes WHERE {} ORDER BY frame, system_index, position",
+            condition
+        ))?;
+        let changes = statement.query_map(rusqlite::params_from_iter(parameters.iter()), |row| {
+            let entity = Entity::new(row.get::<_, i64>(3)? as usize, row.get::<_, i64>(4)? as usize);
+            let type_name: String = row.get(5)?;
+            let data: Option<String> = row.get(6)?;
+            let change = match (row.get_ref(2)?.as_str()?, data) {
+                ("ADD", Some(data)) => DiffComponentChange::Added { entity, type_name: type_name.into(), data: data.into() },
+                ("MOD", Some(diff)) => DiffComponentChange::Modified { entity, type_name: type_name.into(), diff: diff.into() },
+                _ => DiffComponentChange::Removed { entity, type_name: type_name.into() },
+            };
+            Ok(RecordedChange {
+                frame: row.get::<_, i64>(0)? as usize,
+                system_index: row.get::<_, i64>(1)? as usize,
+                sequence: row.get::<_, i64>(7)? as u64,
+                change,
+            })
+        })?;
+        changes.collect()
+    }
+
+    /// World operations matching a query's entity and frames, in recording order
+    pub fn world_operations(&self, query: &ChangeQuery) -> rusqlite::Result<Vec<RecordedOperation>> {
+        let (condition, parameters) = query.condition(false);
+        let mut statement = self.connection.prepare(&format!(
+            "SELECT frame, system_index, operation, world_index, entity_index, argument, position
+             FROM world_operations WHERE {} ORDER BY frame, system_index, position",
+            condition
+        ))?;
+        let operations = statement.query_map(rusqlite::params_from_iter(parameters.iter()), |row| {
+            Ok(RecordedOperation {
+                frame: row.get::<_, i64>(0)? as usize,
+                system_index: row.get::<_, i64>(1)? as usize,
+                sequence: row.get::<_, i64>(6)? as u64,
+                operation: read_operation(row)?,
+            })
+        })?;
+        operations.collect()
+    }
+
+    /// Frames containing at least one component change matching a query
+    pub fn changed_frames(&self, query: &ChangeQuery) -> rusqlite::Result<Vec<usize>> {
+        let (condition, parameters) = query.condition(true);
+        let mut statement = self.connection.prepare(&format!(
+            "SELECT DISTINCT frame FROM component_changes WHERE {} ORDER BY frame",
+            condition
+        ))?;
+        let frames = statement.query_map(rusqlite::params_from_iter(parameters.iter()), |row| {
+            row.get::<_, i64>(0).map(|frame| frame as usize)
+        })?;
+        frames.collect()
+    }
+}
+
+/// Find a system of a loaded frame, frames are sorted by frame number
+fn stored_system(
+    frame_systems: &mut [(usize, Vec<SystemUpdateDiff>)],
+    frame: usize,
+    system_index: usize,
+) -> Option<&mut SystemUpdateDiff> {
+    let index = frame_systems.binary_search_by_key(&frame, |(stored, _)| *stored).ok()?;
+    frame_systems[index].1.get_mut(system_index)
+}
+
+/// Rebuild a world operation from the operation, world_index, entity_index and argument columns
+fn read_operation(row: &Row) -> rusqlite::Result<WorldOperation> {
+    let world_index = row.get::<_, Option<i64>>(3)?.unwrap_or(0) as usize;
+    let entity = || -> rusqlite::Result<Entity> {
+        Ok(Entity::new(world_index, row.get::<_, Option<i64>>(4)?.unwrap_or(0) as usize))
+    };
+    let argument = || -> rusqlite::Result<String> { Ok(row.get::<_, Option<String>>(5)?.unwrap_or_default()) };
+    Ok(match row.get_ref(2)?.as_str()? {
+        "CREATE_ENTITY" => WorldOperation::CreateEntity(entity()?),
+        "REMOVE_ENTITY" => WorldOperation::RemoveEntity(entity()?),
+        "CREATE_WORLD" => WorldOperation::CreateWorld(world_index),
+        "REMOVE_WORLD" => WorldOperation::RemoveWorld(world_index),
+        "ADD_TO_GROUP" => WorldOperation::AddToGroup(entity()?, argument()?),
+        "REMOVE_FROM_GROUP" => WorldOperation::RemoveFromGroup(entity()?, argument()?),
+        "RELATE" | "UNRELATE" => {
+            // The argument holds the relation and the target, "Targets Entity(0, 2)"
+            let argument = argument()?;
+            let (relation, target) = argument
+                .split_once(' ')
+                .and_then(|(relation, target)| Some((relation.to_string(), crate::parse_entity(target)?)))
+                .ok_or_else(|| rusqlite::Error::InvalidColumnType(5, "argument".to_string(), rusqlite::types::Type::Text))?;
+            if row.get_ref(2)?.as_str()? == "RELATE" {
+                WorldOperation::Relate(entity()?, relation, target)
+            } else {
+                WorldOperation::Unrelate(entity()?, relation, target)
+            }
+        }
+        "DISABLE_ENTITY" => WorldOperation::DisableEntity(entity()?),
+        "ENABLE_ENTITY" => WorldOperation::EnableEntity(entity()?),
+        "CLEAR_ENTITIES" => WorldOperation::ClearEntities,
+        "CLEAR_ALL" => WorldOperation::ClearAll,
+        "RESET_HISTORY" => WorldOperation::ResetHistory,
+        _ => WorldOperation::AddSystem(argument()?),
+    })
+}
+
+impl WorldUpdateHistory {
+    /// Load a history from a SQLite replay database
+    pub fn from_sqlite<P: AsRef<Path>>(path: P) -> rusqlite::Result<Self> {
+        SqliteReplayStore::open(path)?.load_history()
+    }
+
+    /// Write this history to a SQLite replay database, replacing its frames
+    pub fn save_to_sqlite<P: AsRef<Path>>(&self, path: P) -> rusqlite::Result<()> {
+        SqliteReplayStore::open(path)?.save_history(self)
+    }
+}
+
+impl World {
+    /// Store every completed frame in a SQLite replay database, alongside the
+    /// in-memory history and any replay log
+    pub fn enable_sqlite_replay_storage<P: AsRef<Path>>(&mut self, path: P) -> rusqlite::Result<()> {
+        self.sqlite_store = Some(SqliteReplayStore::open(path)?);
+        Ok(())
+    }
+
+    /// Stop storing frames in the SQLite replay database
+    pub fn disable_sqlite_replay_storage(&mut self) {
+        self.sqlite_store = None;
+    }
+
+    /// Get the SQLite replay store, if enabled
+    pub fn sqlite_replay_store(&self) -> Option<&SqliteReplayStore> {
+        self.sqlite_store.as_ref()
+    }
+
+    /// Store the frame just recorded in the history
+    pub(crate) fn store_frame_in_sqlite(&mut self, update: &WorldUpdateDiff) {
+        let frame = self.world_update_history.len().saturating_sub(1);
+        if let Some(store) = self.sqlite_store.as_mut() {
+            if let Err(e) = store.record_frame(frame, update) {
+                ecs_error!("Failed to store frame {} in SQLite: {}", frame, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_history() -> WorldUpdateHistory {
+        let entity = Entity::new(0, 3);
+        let mut history = WorldUpdateHistory::new();
+        for frame in 0..4 {
+            let mut system = SystemUpdateDiff::new();
+            if frame == 0 {
+                system.record_world_operation(WorldOperation::CreateEntity(entity));
+                system.record_world_operation(WorldOperation::AddSystem("MovementSystem".to_string()));
+                system.record_component_change(DiffComponentChange::Added {
+                    entity,
+                    type_name: "Position".into(),
+                    data: "Position { x: 0, y: 0 }".into(),
+                });
+            } else if frame % 2 == 1 {
+                system.record_component_change(DiffComponentChange::Modified {
+                    entity,
+                    type_name: "Position".into(),
+                    diff: format!("PositionDiff {{ x: Some({}), y: None }}", frame).into(),
+                });
+            }
+            let mut update = WorldUpdateDiff::new();
+            let mut idle = SystemUpdateDiff::new();
+            if frame == 2 {
+                idle.record_failure("index out of bounds");
+                system.record_error("missing resource Gravity");
+            }
+            update.record(idle);
+            update.record(system);
+            history.record(update);
+        }
+        history
+    }
+
+    fn describe(history: &WorldUpdateHistory) -> Vec<String> {
+        history
+            .updates()
+            .iter()
+            .map(|update| {
+                let systems: Vec<String> = update
+                    .system_diffs()
+                    .iter()
+                    .map(|system| {
+                        let changes = system.component_changes().iter().map(|change| change.to_string());
+                        let operations = system.world_operations().iter().map(|operation| operation.to_string());
+                        let failure = system.failure().map(|message| format!("FAILED: {}", message));
+                        let error = system.error().map(|message| format!("ERROR: {}", message));
+                        changes.chain(operations).chain(failure).chain(error).collect::<Vec<_>>().join("; ")
+                    })
+                    .collect();
+                systems.join(" | ")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_history_round_trip() {
+        let history = sample_history();
+        let mut store = SqliteReplayStore::open_in_memory().unwrap();
+        store.save_history(&history).unwrap();
+
+        assert_eq!(store.frame_count().unwrap(), 4);
+        assert_eq!(describe(&store.load_history().unwrap()), describe(&history));
+        assert_eq!(describe(&store.load_frames(1..3).unwrap()), describe(&history)[1..3]);
+
+        // Relations keep the relation and the target in the argument column
+        let mut history = WorldUpdateHistory::new();
+        let mut system = SystemUpdateDiff::new();
+        system.record_world_operation(WorldOperation::Relate(Entity::new(0, 3), "Targets".to_string(), Entity::new(0, 4)));
+        system.record_world_operation(WorldOperation::Unrelate(Entity::new(0, 3), "Targets".to_string(), Entity::new(0, 4)));
+        let mut update = WorldUpdateDiff::new();
+        update.record(system);
+        history.record(update);
+        let mut store = SqliteReplayStore::open_in_memory().unwrap();
+        store.save_history(&history).unwrap();
+        assert_eq!(describe(&store.load_history().unwrap()), describe(&history));
+    }
+
+    #[test]
+    fn test_indexed_queries() {
+        let mut store = SqliteReplayStore::open_in_memory().unwrap();
+        store.save_history(&sample_history()).unwrap();
+        let entity = Entity::new(0, 3);
+
+        let position_frames = store
+            .changed_frames(&ChangeQuery::new().entity(entity).component_type("Position"))
+            .unwrap();
+        assert_eq!(position_frames, vec![0, 1, 3]);
+        assert!(store
+            .changed_frames(&ChangeQuery::new().entity(Entity::new(0, 4)))
+            .unwrap()
+            .is_empty());
+
+        let changes = store
+            .component_changes(&ChangeQuery::new().component_type("Position").frames(1..4))
+            .unwrap();
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[1].frame, 3);
+        assert_eq!(changes[1].system_index, 1);
+
+        let operations = store.world_operations(&ChangeQuery::new().entity(entity)).unwrap();
+        assert_eq!(operations.len(), 1);
+        assert_eq!(operations[0].operation.to_string(), "CREATE_ENTITY Entity(0, 3)");
+    }
+
+    #[test]
+    fn test_rewritten_frames_replace_later_frames() {
+        let history = sample_history();
+        let mut store = SqliteReplayStore::open_in_memory().unwrap();
+        store.save_history(&history).unwrap();
+
+        store.record_frame(1, &WorldUpdateDiff::new()).unwrap();
+        assert_eq!(store.frame_count().unwrap(), 2);
+        assert_eq!(store.changed_frames(&ChangeQuery::new()).unwrap(), vec![0]);
+    }
+}
@@ -0,0 +1,584 @@
+//! Storage of the components of one type.
+//!
+//! Components are boxed and kept in entity order. Marker components - zero
+//! sized types like `struct Actor;` - carry no data, so their
+//! column is a set of the entities that have the marker, and the boxes of
+//! their values allocate nothing. Checking a marker with `With<T>` or
+//! `Without<T>` is a set lookup:
+//!
+//! ```ignore
+//! let homes = world_view.query_components::<(In<Position>, With<Home>, Without<Work>)>();
+//! ```
+//!
+//! The storage of a type can also be chosen with `World::register_storage`.
+//! `StorageKind::Dense` keeps the components in a vector sorted by entity,
+//! which is the fastest to iterate. `StorageKind::Sparse` keeps them in a hash
+//! map, so looking up, adding and removing the component of one entity take
+//! constant time, which suits components few entities have:
+//!
+//! ```ignore
+//! world.register_storage::<Burning>(StorageKind::Sparse)?;
+//! ```
+//!
+//! A query visits only the entities of the smallest column among the types
+//! it requires (`In`, `Out`, `Changed` and `With`), and looks the other
+//! components of each entity up in their columns. A query for a rare sparse or
+//! marker component therefore checks the few entities that have it rather
+//! than every entity of the world.
+
+use crate::{Entity, MixedQueryComponent, World};
+use std::any::{Any, TypeId};
+use std::collections::{BTreeSet, HashMap};
+
+/// How the components of one type are stored, see the module documentation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StorageKind {
+    /// Vector sorted by entity, fast to iterate. The default for types with data.
+    Dense,
+    /// Hash map by entity, fast to look up, add and remove single components
+    Sparse,
+    /// Set of entities, for zero sized types. The default for them.
+    Markers,
+}
+
+impl StorageKind {
+    /// Storage a type gets unless another one is registered
+    pub(crate) fn default_for<T: 'static>() -> Self {
+        if Self::is_marker::<T>() {
+            StorageKind::Markers
+        } else {
+            StorageKind::Dense
+        }
+    }
+
+    fn is_marker<T: 'static>() -> bool {
+        std::mem::size_of::<T>() == 0
+    }
+}
+
+/// Components of one type, in entity order
+pub(crate) enum ComponentColumn {
+    Boxed(Vec<(Entity, Box<dyn Any>)>),
+    Sparse(SparseMap),
+    Markers(MarkerSet),
+}
+
+/// Components of a sparse column, an entity has at most one
+pub(crate) struct SparseMap {
+    components: HashMap<Entity, Box<dyn Any>>,
+    /// The entities of `components` in entity order
+    order: BTreeSet<Entity>,
+}
+
+/// Entities that have a marker component
+pub(crate) struct MarkerSet {
+    /// The entities of `members` in entity order
+    entities: Vec<Entity>,
+    /// The marker of each entity, in a box that allocates nothing
+    members: HashMap<Entity, Box<dyn Any>>,
+}
+
+impl MarkerSet {
+    /// Remove a member from `entities`, which is sorted like on insertion
+    fn remove_entity(&mut self, entity: Entity) {
+        if let Ok(index) = self.entities.binary_search(&entity) {
+            self.entities.remove(index);
+        }
+    }
+}
+
+impl ComponentColumn {
+    /// Create the column for components of type T, holding `component`
+    pub(crate) fn new<T: 'static>(entity: Entity, component: Box<dyn Any>) -> Self {
+        Self::with_storage(StorageKind::default_for::<T>(), entity, component)
+    }
+
+    /// Create a column of the given kind, holding `component`
+    pub(crate) fn with_storage(kind: StorageKind, entity: Entity, component: Box<dyn Any>) -> Self {
+        match kind {
+            StorageKind::Dense => ComponentColumn::Boxed(vec![(entity, component)]),
+            StorageKind::Sparse => ComponentColumn::Sparse(SparseMap {
+                components: HashMap::from([(entity, component)]),
+                order: BTreeSet::from([entity]),
+            }),
+            StorageKind::Markers => ComponentColumn::Markers(MarkerSet {
+                entities: vec![entity],
+                members: HashMap::from([(entity, component)]),
+            }),
+        }
+    }
+
+    pub(crate) fn kind(&self) -> StorageKind {
+        match self {
+            ComponentColumn::Boxed(_) => StorageKind::Dense,
+            ComponentColumn::Sparse(_) => StorageKind::Sparse,
+            ComponentColumn::Markers(_) => StorageKind::Markers,
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        match self {
+            ComponentColumn::Boxed(components) => components.len(),
+            ComponentColumn::Sparse(sparse) => sparse.order.len(),
+            ComponentColumn::Markers(markers) => markers.entities.len(),
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Check if the entity has a component in the column
+    pub(crate) fn contains(&self, entity: Entity) -> bool {
+        match self {
+            ComponentColumn::Boxed(components) => Self::position(components, entity).is_some(),
+            ComponentColumn::Sparse(sparse) => sparse.components.contains_key(&entity),
+            ComponentColumn::Markers(markers) => markers.members.contains_key(&entity),
+        }
+    }
+
+    pub(crate) fn get(&self, entity: Entity) -> Option<&dyn Any> {
+        match self {
+            ComponentColumn::Boxed(components) => {
+                Self::position(components, entity).map(|index| components[index].1.as_ref())
+            }
+            ComponentColumn::Sparse(sparse) => sparse.components.get(&entity).map(|component| component.as_ref()),
+            ComponentColumn::Markers(markers) => markers.members.get(&entity).map(|component| component.as_ref()),
+        }
+    }
+
+    pub(crate) fn get_mut(&mut self, entity: Entity) -> Option<&mut dyn Any> {
+        match self {
+            ComponentColumn::Boxed(components) => {
+                Self::position(components, entity).map(|index| components[index].1.as_mut())
+            }
+            ComponentColumn::Sparse(sparse) => sparse.components.get_mut(&entity).map(|component| component.as_mut()),
+            ComponentColumn::Markers(markers) => markers.members.get_mut(&entity).map(|component| component.as_mut()),
+        }
+    }
+
+    /// Add a component, replacing the one the entity already has in the column
+    pub(crate) fn insert(&mut self, entity: Entity, component: Box<dyn Any>) {
+        match self {
+            ComponentColumn::Boxed(components) => {
+                let index = components.partition_point(|(e, _)| *e < entity);
+                match components.get_mut(index) {
+                    Some((e, old)) if *e == entity => *old = component,
+                    _ => components.insert(index, (entity, component)),
+                }
+            }
+            ComponentColumn::Sparse(sparse) => {
+                sparse.components.insert(entity, component);
+                sparse.order.insert(entity);
+            }
+            ComponentColumn::Markers(markers) => {
+                if markers.members.insert(entity, component).is_none() {
+                    let index = markers.entities.partition_point(|e| *e < entity);
+                    markers.entities.insert(index, entity);
+                }
+            }
+        }
+    }
+
+    /// Remove the component of the entity
+    pub(crate) fn remove(&mut self, entity: Entity) -> Option<Box<dyn Any>> {
+        match self {
+            ComponentColumn::Boxed(components) => {
+                Self::position(components, entity).map(|index| components.remove(index).1)
+            }
+            ComponentColumn::Sparse(sparse) => {
+                sparse.order.remove(&entity);
+                sparse.components.remove(&entity)
+            }
+            ComponentColumn::Markers(markers) => {
+                let component = markers.members.remove(&entity)?;
+                markers.remove_entity(entity);
+                Some(component)
+            }
+        }
+    }
+
+    /// Remove every component of the entity
+    pub(crate) fn remove_all(&mut self, entity: Entity) {
+        match self {
+            ComponentColumn::Boxed(components) => components.retain(|(e, _)| *e != entity),
+            ComponentColumn::Sparse(sparse) => {
+                sparse.order.remove(&entity);
+                sparse.components.remove(&entity);
+            }
+            ComponentColumn::Markers(markers) => {
+                if markers.members.remove(&entity).is_some() {
+                    markers.remove_entity(entity);
+                }
+            }
+        }
+    }
+
+    /// Iterate over the components in entity order
+    pub(crate) fn iter(&self) -> ColumnIter<'_> {
+        match self {
+            ComponentColumn::Boxed(components) => ColumnIter::Boxed(components.iter()),
+            ComponentColumn::Sparse(sparse) => ColumnIter::Sparse(sparse.order.iter(), &sparse.components),
+            ComponentColumn::Markers(markers) => ColumnIter::Markers(markers.entities.iter(), &markers.members),
+        }
+    }
+
+    /// Iterate over the entities that have components in the column, in
+    /// entity order and without looking the components up
+    pub(crate) fn entities(&self) -> Box<dyn Iterator<Item = Entity> + '_> {
+        match self {
+            ComponentColumn::Boxed(components) => Box::new(components.iter().map(|(entity, _)| *entity)),
+            ComponentColumn::Sparse(sparse) => Box::new(sparse.order.iter().copied()),
+            ComponentColumn::Markers(markers) => Box::new(markers.entities.iter().copied()),
+        }
+    }
+
+    /// Take the components out of the column in entity order
+    fn into_components(self) -> Vec<(Entity, Box<dyn Any>)> {
+        match self {
+            ComponentColumn::Boxed(components) => components,
+            ComponentColumn::Sparse(mut sparse) => sparse
+                .order
+                .iter()
+                .filter_map(|entity| Some((*entity, sparse.components.remove(entity)?)))
+                .collect(),
+            ComponentColumn::Markers(mut markers) => markers
+                .entities
+                .iter()
+                .filter_map(|entity| Some((*entity, markers.members.remove(entity)?)))
+                .collect(),
+        }
+    }
+
+    /// Index of the entity's component in a boxed column
+    fn position(components: &[(Entity, Box<dyn Any>)], entity: Entity) -> Option<usize> {
+        let index = components.partition_point(|(e, _)| *e < entity);
+        components.get(index).filter(|(e, _)| *e == entity).map(|_| index)
+    }
+}
+
+/// Iterator over the entities and components of a column
+pub(crate) enum ColumnIter<'a> {
+    Boxed(std::slice::Iter<'a, (Entity, Box<dyn Any>)>),
+    Sparse(std::collections::btree_set::Iter<'a, Entity>, &'a HashMap<Entity, Box<dyn Any>>),
+    Markers(std::slice::Iter<'a, Entity>, &'a HashMap<Entity, Box<dyn Any>>),
+}
+
+impl<'a> Iterator for ColumnIter<'a> {
+    type Item = (Entity, &'a dyn Any);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            ColumnIter::Boxed(components) => components.next().map(|(entity, component)| (*entity, component.as_ref())),
+            ColumnIter::Sparse(entities, components) => {
+                entities.next().map(|entity| (*entity, components[entity].as_ref()))
+            }
+            ColumnIter::Markers(entities, components) => {
+                entities.next().map(|entity| (*entity, components[entity].as_ref()))
+            }
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a ComponentColumn {
+    type Item = (Entity, &'a dyn Any);
+    type IntoIter = ColumnIter<'a>;
+
+    fn into_iter(self) -> ColumnIter<'a> {
+        self.iter()
+    }
+}
+
+/// Query filter matching entities that have a component of type T
+pub struct With<T>(std::marker::PhantomData<T>);
+
+/// Query filter matching entities without a component of type T
+pub struct Without<T>(std::marker::PhantomData<T>);
+
+impl<'a, T: 'static> MixedQueryComponent<'a> for With<T> {
+    type Item = ();
+
+    fn get_mixed_component(world: &'a mut World, entity: Entity) -> Option<Self::Item> {
+        world.has_component_type(TypeId::of::<T>(), entity).then_some(())
+    }
+
+    fn required() -> Option<TypeId> {
+        Some(TypeId::of::<T>())
+    }
+}
+
+impl<'a, T: 'static> MixedQueryComponent<'a> for Without<T> {
+    type Item = ();
+
+    fn get_mixed_component(world: &'a mut World, entity: Entity) -> Option<Self::Item> {
+        (!world.has_component_type(TypeId::of::<T>(), entity)).then_some(())
+    }
+}
+
+impl World {
+    /// Store the components of type T the given way, see the module
+    /// documentation. Components the world already has are moved to the new
+    /// storage. Fails for `StorageKind::Markers` if T is not a zero sized
+    /// type.
+    pub fn register_storage<T: 'static>(&mut self, kind: StorageKind) -> Result<(), String> {
+        if kind == StorageKind::Markers && !StorageKind::is_marker::<T>() {
+            return Err(format!(
+                "Cannot store {} as markers: it is not a zero sized type",
+                crate::short_type_name::<T>()
+            ));
+        }
+        let type_id = TypeId::of::<T>();
+        self.storages.insert(type_id, kind);
+        self.component_type_names.insert(type_id, crate::short_type_name::<T>());
+        if let Some(column) = self.components.remove(&type_id) {
+            let mut components = column.into_components().into_iter();
+            if let Some((entity, component)) = components.next() {
+                let mut column = ComponentColumn::with_storage(kind, entity, component);
+                for (entity, component) in components {
+                    column.insert(entity, component);
+                }
+                self.components.insert(type_id, column);
+            }
+        }
+        Ok(())
+    }
+
+    /// Get how the components of type T are stored, or None if the world
+    /// has never stored one and no storage is registered for the type
+    pub fn storage_kind<T: 'static>(&self) -> Option<StorageKind> {
+        let type_id = TypeId::of::<T>();
+        match self.components.get(&type_id) {
+            Some(column) => Some(column.kind()),
+            None => self.storages.get(&type_id).copied(),
+        }
+    }
+
+    /// Check if an entity has a component of the given type
+    pub(crate) fn has_component_type(&self, type_id: TypeId, entity: Entity) -> bool {
+        self.components.get(&type_id).is_some_and(|column| column.contains(entity))
+    }
+
+    /// Entities a query visits, in entity order: the entities of the smallest
+    /// column of the types it requires, or every entity if that column is not
+    /// smaller or the query requires no type. Columns and the entities of the
+    /// world are both kept in entity order, so either way gives the same order.
+    pub(crate) fn query_candidates(&self, include_disabled: bool, required: &[Option<TypeId>]) -> Vec<Entity> {
+        let mut columns = Vec::new();
+        for type_id in required.iter().flatten() {
+            match self.components.get(type_id) {
+                Some(column) => columns.push(column),
+                None => return Vec::new(),
+            }
+        }
+        let smallest = columns.into_iter().min_by_key(|column| column.len());
+        let Some(column) = smallest.filter(|column| column.len() < self.entities.len()) else {
+            return self.query_entities(include_disabled);
+        };
+        let sliced = self.active_slice.get().is_some();
+        let entities: Vec<Entity> = column
+            .entities()
+            .filter(|entity| self.entities.binary_search(entity).is_ok())
+            .filter(|entity| include_disabled || !self.disabled_entities.contains(entity))
+            .filter(|entity| !sliced || self.in_slice(*entity))
+            .collect();
+        entities
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{In, WorldView};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Actor;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Home;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Position(i32);
+
+    #[test]
+    fn test_markers_are_stored_as_entity_sets() {
+        let mut world = World::new();
+        let entities: Vec<Entity> = (0..4).map(|_| world.create_entity()).collect();
+        for entity in entities.iter().rev() {
+            world.add_component(*entity, Actor);
+            world.add_component(*entity, Position(entity.entity_index as i32));
+        }
+        assert!(matches!(world.components[&TypeId::of::<Actor>()], ComponentColumn::Markers(_)));
+        assert!(matches!(world.components[&TypeId::of::<Position>()], ComponentColumn::Boxed(_)));
+
+        assert_eq!(world.entities_with_component::<Actor>(), entities);
+        assert_eq!(world.get_component::<Actor>(entities[2]), Some(&Actor));
+        assert_eq!(world.remove_component::<Actor>(entities[2]), Some(Actor));
+        assert_eq!(world.remove_component::<Actor>(entities[2]), None);
+        world.remove_entity(entities[0]);
+        assert_eq!(world.entities_with_component::<Actor>(), vec![entities[1], entities[3]]);
+    }
+
+    #[derive(Debug, Clone, PartialEq, crate::Reflect)]
+    struct Guard;
+
+    #[test]
+    fn test_boxed_markers_get_the_default_storage() {
+        use crate::Reflect;
+
+        let mut world = World::new();
+        world.register_component::<Guard>();
+        let entity = world.create_entity();
+        world.insert_component_value(entity, "Guard", &Guard.to_value()).unwrap();
+        assert_eq!(world.storage_kind::<Guard>(), Some(StorageKind::Markers));
+        assert_eq!(world.get_component::<Guard>(entity), Some(&Guard));
+    }
+
+    #[test]
+    fn test_each_entity_owns_its_marker() {
+        static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+        /// A token that must not be duplicated
+        #[derive(Debug)]
+        struct Token;
+
+        impl Drop for Token {
+            fn drop(&mut self) {
+                DROPPED.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let mut world = World::new();
+        let entities: Vec<Entity> = (0..3).map(|_| world.create_entity()).collect();
+        for entity in &entities {
+            world.add_component(*entity, Token);
+        }
+        assert!(matches!(world.components[&TypeId::of::<Token>()], ComponentColumn::Markers(_)));
+
+        drop(world.remove_component::<Token>(entities[0]));
+        assert!(world.remove_component::<Token>(entities[0]).is_none());
+        assert_eq!(DROPPED.load(Ordering::SeqCst), 1);
+        world.remove_entity(entities[1]);
+        assert_eq!(DROPPED.load(Ordering::SeqCst), 2);
+        drop(world);
+        assert_eq!(DROPPED.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_adding_a_component_again_replaces_it_in_every_storage() {
+        #[derive(Debug, PartialEq)]
+        struct Flag(&'static str);
+
+        for kind in [StorageKind::Dense, StorageKind::Sparse] {
+            let mut world = World::new();
+            world.register_storage::<Flag>(kind).unwrap();
+            let entity = world.create_entity();
+            world.add_component(entity, Flag("old"));
+            world.add_component(entity, Flag("new"));
+            assert_eq!(world.get_component::<Flag>(entity), Some(&Flag("new")), "{:?}", kind);
+            assert_eq!(world.entities_with_component::<Flag>(), vec![entity], "{:?}", kind);
+            assert_eq!(world.remove_component::<Flag>(entity), Some(Flag("new")), "{:?}", kind);
+            assert_eq!(world.get_component::<Flag>(entity), None, "{:?}", kind);
+        }
+
+        let mut world = World::new();
+        let entity = world.create_entity();
+        world.add_component(entity, Actor);
+        world.add_component(entity, Actor);
+        assert_eq!(world.storage_kind::<Actor>(), Some(StorageKind::Markers));
+        assert_eq!(world.entities_with_component::<Actor>(), vec![entity]);
+        assert_eq!(world.remove_component::<Actor>(entity), Some(Actor));
+        assert_eq!(world.get_component::<Actor>(entity), None);
+    }
+
+    #[test]
+    fn test_column_queries_find_entities_created_after_applied_ones() {
+        let mut world = World::new();
+        let first = world.create_entity();
+        // An entity recorded in another world sorts after the ones created here
+        world.apply_world_operation(&crate::WorldOperation::CreateEntity(Entity::new(5, 0)));
+        let second = world.create_entity();
+        for entity in [first, Entity::new(5, 0), second] {
+            world.add_component(entity, Position(entity.entity_index as i32));
+        }
+        world.add_component(second, Home);
+
+        let required = [Some(TypeId::of::<Position>()), Some(TypeId::of::<Home>())];
+        assert_eq!(world.query_candidates(false, &required), vec![second]);
+        assert_eq!(world.query_candidates(false, &required[..1]), vec![first, second, Entity::new(5, 0)]);
+    }
+
+    #[test]
+    fn test_with_and_without_filters() {
+        let mut world = World::new();
+        let entities: Vec<Entity> = (0..4).map(|_| world.create_entity()).collect();
+        for entity in &entities {
+            world.add_component(*entity, Position(entity.entity_index as i32));
+        }
+        world.add_component(entities[1], Home);
+        world.add_component(entities[3], Home);
+
+        let mut view = WorldView::<(Position, Home), ()>::new(&mut world);
+        let at_home: Vec<i32> = view
+            .query_components::<(In<Position>, With<Home>)>()
+            .into_iter()
+            .map(|(_, (position, _))| position.0)
+            .collect();
+        assert_eq!(at_home, vec![1, 3]);
+        let away: Vec<Entity> = view
+            .query_components::<(In<Position>, Without<Home>)>()
+            .into_iter()
+            .map(|(entity, _)| entity)
+            .collect();
+        assert_eq!(away, vec![entities[0], entities[2]]);
+    }
+
+    #[test]
+    fn test_registered_storage_keeps_components_and_query_order() {
+        let mut world = World::new();
+        let entities: Vec<Entity> = (0..6).map(|_| world.create_entity()).collect();
+        for entity in &entities {
+            world.add_component(*entity, Position(entity.entity_index as i32));
+        }
+        for entity in [entities[4], entities[1]] {
+            world.add_component(entity, Home);
+        }
+        assert_eq!(world.storage_kind::<Position>(), Some(StorageKind::Dense));
+        assert_eq!(world.storage_kind::<Home>(), Some(StorageKind::Markers));
+        assert!(world.register_storage::<Position>(StorageKind::Markers).is_err());
+
+        // Existing components move to the new storage
+        world.register_storage::<Position>(StorageKind::Sparse).unwrap();
+        world.register_storage::<Home>(StorageKind::Sparse).unwrap();
+        assert!(matches!(world.components[&TypeId::of::<Position>()], ComponentColumn::Sparse(_)));
+        assert_eq!(world.get_component::<Home>(entities[4]), Some(&Home));
+        world.add_component(entities[2], Position(20));
+        assert_eq!(world.get_component::<Position>(entities[2]), Some(&Position(20)));
+        assert_eq!(world.remove_component::<Position>(entities[3]), Some(Position(3)));
+
+        // Queries visit the entities of the smallest column, still in entity order
+        assert_eq!(world.query_candidates(false, &[Some(TypeId::of::<Position>()), Some(TypeId::of::<Home>())]), vec![entities[1], entities[4]]);
+        let mut view = WorldView::<(Position, Home), ()>::new(&mut world);
+        let positions: Vec<i32> = view
+            .query_components::<(In<Position>,)>()
+            .into_iter()
+            .map(|(_, position)| position.0)
+            .collect();
+        assert_eq!(positions, vec![0, 1, 20, 4, 5]);
+        let at_home: Vec<i32> = view
+            .query_components::<(In<Position>, With<Home>)>()
+            .into_iter()
+            .map(|(_, (position, _))| position.0)
+            .collect();
+        assert_eq!(at_home, vec![1, 4]);
+
+        // Registered storage is used again once a column is created
+        let mut other = World::new();
+        other.register_storage::<Position>(StorageKind::Sparse).unwrap();
+        assert_eq!(other.storage_kind::<Position>(), Some(StorageKind::Sparse));
+        let entity = other.create_entity();
+        other.add_component(entity, Position(7));
+        assert!(matches!(other.components[&TypeId::of::<Position>()], ComponentColumn::Sparse(_)));
+        assert_eq!(other.storage_kind::<Actor>(), None);
+    }
+}
@@ -0,0 +1,209 @@
+//! Enforcing the components systems declare.
+//!
+//! Systems declare the components they read (`InComponents`) and write
+//! (`OutComponents`), and tools like the system graph rely on those
+//! declarations. With `World::set_strict_access(true)`, debug builds check
+//! every query and component access a system makes through its view during
+//! updates, and panic when it touches a component it did not declare:
+//!
+//! ```text
+//! Strict access: system game::MovementSystem wrote component Position without declaring it in OutComponents
+//! ```
+//!
+//! Reading requires the component in `InComponents` or `OutComponents`,
+//! writing or adding it requires `OutComponents`. `With` and `Without` filters
+//! only check whether a component is present and need no declaration. Like
+//! any panic in a system, the failure is recorded in the frame's diff and the
+//! other systems keep running. Release builds skip the checks.
+
+use crate::{ComponentSet, World};
+use std::any::TypeId;
+
+/// A component read or written by a system
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ComponentAccess {
+    pub type_id: TypeId,
+    /// Short type name of the component
+    pub type_name: &'static str,
+    pub write: bool,
+}
+
+impl ComponentAccess {
+    /// Immutable access to `T`
+    pub fn read<T: 'static>() -> Self {
+        Self {
+            type_id: TypeId::of::<T>(),
+            type_name: crate::short_type_name::<T>(),
+            write: false,
+        }
+    }
+
+    /// Mutable access to `T`
+    pub fn write<T: 'static>() -> Self {
+        Self {
+            write: true,
+            ..Self::read::<T>()
+        }
+    }
+}
+
+/// Declared components of the system being updated
+pub(crate) struct DeclaredAccess {
+    system: &'static str,
+    reads: Vec<TypeId>,
+    writes: Vec<TypeId>,
+}
+
+impl DeclaredAccess {
+    pub(crate) fn of<I: ComponentSet, O: ComponentSet>(system: &'static str) -> Self {
+        Self {
+            system,
+            reads: I::type_ids(),
+            writes: O::type_ids(),
+        }
+    }
+}
+
+impl World {
+    /// Check the components systems access against their declared In/Out
+    /// components in debug builds, panicking on undeclared access. Off by default.
+    pub fn set_strict_access(&mut self, strict: bool) {
+        self.strict_access = strict;
+    }
+
+    /// Check if system component access is checked
+    pub fn strict_access(&self) -> bool {
+        self.strict_access && cfg!(debug_assertions)
+    }
+
+    /// Run a system update with its declared components checked in strict mode
+    pub(crate) fn run_with_declared_access<R>(
+        &mut self,
+        declared: impl FnOnce() -> DeclaredAccess,
+        update: impl FnOnce(&mut World) -> R,
+    ) -> R {
+        if !self.strict_access() {
+            return update(self);
+        }
+        self.declared_access = Some(declared());
+        let result = update(self);
+        self.declared_access = None;
+        result
+    }
+
+    /// Panic if the running system did not declare the accessed components
+    pub(crate) fn check_access(&self, accesses: impl FnOnce() -> Vec<ComponentAccess>) {
+        let Some(declared) = &self.declared_access else {
+            return;
+        };
+        for access in accesses() {
+            let allowed = declared.writes.contains(&access.type_id)
+                || (!access.write && declared.reads.contains(&access.type_id));
+            if !allowed {
+                panic!(
+                    "Strict access: system {} {} component {} without declaring it in {}",
+                    declared.system,
+                    if access.write { "wrote" } else { "read" },
+                    access.type_name,
+                    if access.write { "OutComponents" } else { "InComponents or OutComponents" }
+                );
+            }
+        }
+    }
+}
+
+#[cfg(all(test, debug_assertions))]
+mod tests {
+    use super::*;
+    use crate::{In, Out, ReadOnlySystem, ReadOnlyWorldView, System, With, Without, WorldView};
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Position(i32);
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Velocity(i32);
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Frozen;
+
+    /// Declares it only reads velocities, but writes positions
+    struct SneakyMovementSystem;
+
+    impl System for SneakyMovementSystem {
+        type InComponents = (Velocity,);
+        type OutComponents = ();
+
+        fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+        fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+            for (_, (velocity, position)) in world.query_components::<(In<Velocity>, Out<Position>)>() {
+                position.0 += velocity.0;
+            }
+        }
+
+        fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    }
+
+    struct MovementSystem;
+
+    impl System for MovementSystem {
+        type InComponents = (Velocity,);
+        type OutComponents = (Position,);
+
+        fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+        fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+            for (_, (velocity, position, ())) in world.query_components::<(In<Velocity>, Out<Position>, Without<Frozen>)>() {
+                position.0 += velocity.0;
+            }
+        }
+
+        fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    }
+
+    /// Reads velocities without declaring them
+    struct SpeedReportSystem;
+
+    impl ReadOnlySystem for SpeedReportSystem {
+        type InComponents = (Position,);
+
+        fn update(&mut self, world: &ReadOnlyWorldView<Self::InComponents>) {
+            world.query::<(In<Position>, With<Frozen>)>();
+            world.query::<(In<Velocity>,)>();
+        }
+    }
+
+    fn new_world() -> World {
+        let mut world = World::new();
+        let entity = world.create_entity();
+        world.add_component(entity, Position(0));
+        world.add_component(entity, Velocity(1));
+        world.set_strict_access(true);
+        world
+    }
+
+    #[test]
+    fn test_undeclared_write_fails_the_system() {
+        let mut world = new_world();
+        world.add_system(MovementSystem);
+        world.add_system(SneakyMovementSystem);
+        world.add_system(SpeedReportSystem);
+        world.update();
+
+        let update = world.get_update_history().updates().last().unwrap();
+        let failures: Vec<Option<&str>> = update.system_diffs().iter().map(|diff| diff.failure()).collect();
+        assert_eq!(failures[0], None);
+        assert!(failures[1].unwrap().contains("SneakyMovementSystem wrote component Position without declaring it in OutComponents"));
+        assert!(failures[2].unwrap().contains("SpeedReportSystem read component Velocity"));
+    }
+
+    #[test]
+    fn test_undeclared_access_is_allowed_when_not_strict() {
+        let mut world = new_world();
+        world.set_strict_access(false);
+        world.add_system(SneakyMovementSystem);
+        world.update();
+        assert!(world.get_update_history().updates().last().unwrap().system_diffs()[0].failure().is_none());
+        assert_eq!(world.query::<(In<Position>,)>()[0].1, &Position(1));
+    }
+}
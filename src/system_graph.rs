@@ -0,0 +1,394 @@
+//! Graphviz export of the system dependency graph and access analysis.
+//!
+//! Systems declare the components they read (`InComponents`) and write
+//! (`OutComponents`). This module turns those declarations into a DOT graph so
+//! the data flow between systems can be visualized with `dot -Tsvg`, and into
+//! a `SystemAccessReport` listing who reads and writes each component, the
+//! systems that could never run at the same time, and a suggested split of the
+//! systems into stages that could.
+
+use crate::World;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+/// Declared component access of one system, in execution order
+struct SystemNode {
+    name: &'static str,
+    reads: Vec<&'static str>,
+    writes: Vec<&'static str>,
+}
+
+impl SystemNode {
+    fn accesses(&self, component: &str) -> bool {
+        self.reads.contains(&component) || self.writes.contains(&component)
+    }
+
+    /// Components that make the relative order of two systems observable:
+    /// everything one of them writes and the other reads or writes
+    fn conflicts_with(&self, other: &SystemNode) -> BTreeSet<&'static str> {
+        let mut shared = BTreeSet::new();
+        for component in &self.writes {
+            if other.accesses(component) {
+                shared.insert(*component);
+            }
+        }
+        for component in &other.writes {
+            if self.accesses(component) {
+                shared.insert(*component);
+            }
+        }
+        shared
+    }
+}
+
+/// Escape a label for use inside a quoted DOT string
+fn escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl World {
+    /// Render the registered systems and their declared component access as a
+    /// Graphviz DOT graph.
+    ///
+    /// Systems are boxes and components are ellipses. Solid edges go from a
+    /// component to the systems reading it and from a system to the components
+    /// it writes. Dashed edges connect systems whose relative order matters
+    /// because one writes a component the other accesses; they point in
+    /// execution order and are labelled with the shared components.
+    pub fn system_graph_dot(&self) -> String {
+        let mut dot = String::new();
+        self.write_system_graph_dot(&mut dot)
+            .expect("writing to a String cannot fail");
+        dot
+    }
+
+    /// Declared component access of the systems, in execution order
+    fn system_nodes(&self) -> Vec<SystemNode> {
+        self.systems
+            .iter()
+            .map(|system| SystemNode {
+                name: system.name().rsplit("::").next().unwrap_or(system.name()),
+                reads: system.in_components(),
+                writes: system.out_components(),
+            })
+            .collect()
+    }
+
+    fn write_system_graph_dot(&self, out: &mut String) -> std::fmt::Result {
+        let systems = self.system_nodes();
+
+        let components: BTreeSet<&'static str> = systems
+            .iter()
+            .flat_map(|system| system.reads.iter().chain(&system.writes).copied())
+            .collect();
+
+        writeln!(out, "digraph systems {{")?;
+        writeln!(out, "    rankdir=LR;")?;
+
+        writeln!(out)?;
+        writeln!(out, "    // Systems in execution order")?;
+        for (index, system) in systems.iter().enumerate() {
+            writeln!(
+                out,
+                "    \"system_{}\" [label=\"{}. {}\", shape=box, style=filled, fillcolor=lightblue];",
+                index,
+                index + 1,
+                escape(system.name)
+            )?;
+        }
+
+        writeln!(out)?;
+        writeln!(out, "    // Components")?;
+        for component in &components {
+            writeln!(
+                out,
+                "    \"component_{}\" [label=\"{}\", shape=ellipse];",
+                escape(component),
+                escape(component)
+            )?;
+        }
+
+        writeln!(out)?;
+        writeln!(out, "    // Reads and writes")?;
+        for (index, system) in systems.iter().enumerate() {
+            for component in &system.reads {
+                writeln!(
+                    out,
+                    "    \"component_{}\" -> \"system_{}\" [label=\"read\"];",
+                    escape(component),
+                    index
+                )?;
+            }
+            for component in &system.writes {
+                writeln!(
+                    out,
+                    "    \"system_{}\" -> \"component_{}\" [label=\"write\", color=red];",
+                    index,
+                    escape(component)
+                )?;
+            }
+        }
+
+        writeln!(out)?;
+        writeln!(out, "    // Ordering conflicts")?;
+        for (first_index, first) in systems.iter().enumerate() {
+            for (second_index, second) in systems.iter().enumerate().skip(first_index + 1) {
+                let shared = first.conflicts_with(second);
+                if shared.is_empty() {
+                    continue;
+                }
+                let label = shared.into_iter().collect::<Vec<_>>().join(", ");
+                writeln!(
+                    out,
+                    "    \"system_{}\" -> \"system_{}\" [label=\"{}\", style=dashed, color=orange, constraint=false];",
+                    first_index,
+                    second_index,
+                    escape(&label)
+                )?;
+            }
+        }
+
+        writeln!(out, "}}")
+    }
+
+    /// Analyze the declared component access of the registered systems
+    pub fn analyze_system_access(&self) -> SystemAccessReport {
+        let systems = self.system_nodes();
+
+        let mut components: BTreeMap<&'static str, ComponentAccessSummary> = BTreeMap::new();
+        for system in &systems {
+            for (component, write) in system.reads.iter().map(|c| (c, false)).chain(system.writes.iter().map(|c| (c, true))) {
+                let summary = components.entry(component).or_insert_with(|| ComponentAccessSummary {
+                    component,
+                    readers: Vec::new(),
+                    writers: Vec::new(),
+                });
+                let systems = if write { &mut summary.writers } else { &mut summary.readers };
+                if !systems.contains(&system.name) {
+                    systems.push(system.name);
+                }
+            }
+        }
+
+        let mut write_conflicts = Vec::new();
+        for (first_index, first) in systems.iter().enumerate() {
+            for second in &systems[first_index + 1..] {
+                let components: Vec<&'static str> = first
+                    .writes
+                    .iter()
+                    .filter(|component| second.writes.contains(component))
+                    .copied()
+                    .collect();
+                if !components.is_empty() {
+                    write_conflicts.push(WriteConflict {
+                        first: first.name,
+                        second: second.name,
+                        components,
+                    });
+                }
+            }
+        }
+
+        // Each system goes to the stage after the last one holding a system
+        // it conflicts with, so conflicting systems keep their order
+        let mut stage_of: Vec<usize> = Vec::with_capacity(systems.len());
+        for (index, system) in systems.iter().enumerate() {
+            let stage = systems[..index]
+                .iter()
+                .zip(&stage_of)
+                .filter(|(earlier, _)| !earlier.conflicts_with(system).is_empty())
+                .map(|(_, stage)| stage + 1)
+                .max()
+                .unwrap_or(0);
+            stage_of.push(stage);
+        }
+        let mut stages: Vec<Vec<&'static str>> = vec![Vec::new(); stage_of.iter().max().map_or(0, |last| last + 1)];
+        for (system, stage) in systems.iter().zip(stage_of) {
+            stages[stage].push(system.name);
+        }
+
+        SystemAccessReport {
+            components: components.into_values().collect(),
+            write_conflicts,
+            stages,
+        }
+    }
+
+    /// Print the system access analysis to stdout
+    pub fn print_system_access(&self) {
+        print!("{}", self.analyze_system_access());
+    }
+}
+
+/// Systems declaring access to one component type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComponentAccessSummary {
+    pub component: &'static str,
+    /// Systems reading the component, in execution order
+    pub readers: Vec<&'static str>,
+    /// Systems writing the component, in execution order
+    pub writers: Vec<&'static str>,
+}
+
+/// Two systems writing the same components, which forces them to run one
+/// after the other in a parallel scheduler
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WriteConflict {
+    /// The system running first
+    pub first: &'static str,
+    pub second: &'static str,
+    /// Components both systems write
+    pub components: Vec<&'static str>,
+}
+
+/// Declared component access of all systems, returned by `World::analyze_system_access`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SystemAccessReport {
+    /// Access per component type, sorted by component name
+    pub components: Vec<ComponentAccessSummary>,
+    pub write_conflicts: Vec<WriteConflict>,
+    /// Suggested stages in execution order. Systems of a stage access no
+    /// component another one of them writes, so they could run in parallel.
+    pub stages: Vec<Vec<&'static str>>,
+}
+
+impl std::fmt::Display for SystemAccessReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "System Access Analysis")?;
+        writeln!(f, "======================")?;
+        writeln!(f)?;
+        writeln!(f, "Components:")?;
+        for summary in &self.components {
+            writeln!(f, "  {}", summary.component)?;
+            if !summary.readers.is_empty() {
+                writeln!(f, "    read by:    {}", summary.readers.join(", "))?;
+            }
+            if !summary.writers.is_empty() {
+                writeln!(f, "    written by: {}", summary.writers.join(", "))?;
+            }
+        }
+        writeln!(f)?;
+        writeln!(f, "Write-Write Conflicts:")?;
+        if self.write_conflicts.is_empty() {
+            writeln!(f, "  none")?;
+        }
+        for conflict in &self.write_conflicts {
+            writeln!(
+                f,
+                "  {} and {} both write {}",
+                conflict.first,
+                conflict.second,
+                conflict.components.join(", ")
+            )?;
+        }
+        writeln!(f)?;
+        writeln!(f, "Suggested Stages:")?;
+        for (index, stage) in self.stages.iter().enumerate() {
+            writeln!(f, "  {}. {}", index + 1, stage.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_quotes_and_backslashes() {
+        assert_eq!(escape("a\"b\\c"), "a\\\"b\\\\c");
+    }
+
+    /// Declares component access without touching the world
+    macro_rules! declared_system {
+        ($name:ident, $in:ty, $out:ty) => {
+            struct $name;
+
+            impl crate::System for $name {
+                type InComponents = $in;
+                type OutComponents = $out;
+
+                fn initialize(&mut self, _world: &mut crate::WorldView<Self::InComponents, Self::OutComponents>) {}
+
+                fn update(&mut self, _world: &mut crate::WorldView<Self::InComponents, Self::OutComponents>) {}
+
+                fn deinitialize(&mut self, _world: &mut crate::WorldView<Self::InComponents, Self::OutComponents>) {}
+            }
+        };
+    }
+
+    struct Position;
+    struct Velocity;
+    struct Health;
+
+    declared_system!(InputSystem, (), (Velocity,));
+    declared_system!(RegenerationSystem, (), (Health,));
+    declared_system!(MovementSystem, (Velocity,), (Position,));
+    declared_system!(KnockbackSystem, (), (Position, Velocity));
+    declared_system!(RenderSystem, (Position, Health), ());
+
+    #[test]
+    fn test_analyze_system_access() {
+        let mut world = World::new();
+        world.add_system(InputSystem);
+        world.add_system(RegenerationSystem);
+        world.add_system(MovementSystem);
+        world.add_system(KnockbackSystem);
+        world.add_system(RenderSystem);
+
+        let report = world.analyze_system_access();
+        let position = report.components.iter().find(|summary| summary.component == "Position").unwrap();
+        assert_eq!(position.readers, vec!["RenderSystem"]);
+        assert_eq!(position.writers, vec!["MovementSystem", "KnockbackSystem"]);
+        assert_eq!(
+            report.write_conflicts,
+            vec![
+                WriteConflict {
+                    first: "InputSystem",
+                    second: "KnockbackSystem",
+                    components: vec!["Velocity"],
+                },
+                WriteConflict {
+                    first: "MovementSystem",
+                    second: "KnockbackSystem",
+                    components: vec!["Position"],
+                },
+            ]
+        );
+        assert_eq!(
+            report.stages,
+            vec![
+                vec!["InputSystem", "RegenerationSystem"],
+                vec!["MovementSystem"],
+                vec!["KnockbackSystem"],
+                vec!["RenderSystem"],
+            ]
+        );
+        assert!(report.to_string().contains("  1. InputSystem, RegenerationSystem\n"));
+    }
+
+    #[test]
+    fn test_conflicts_require_a_writer() {
+        let reader = SystemNode {
+            name: "Reader",
+            reads: vec!["Position"],
+            writes: vec![],
+        };
+        let other_reader = SystemNode {
+            name: "OtherReader",
+            reads: vec!["Position"],
+            writes: vec![],
+        };
+        let writer = SystemNode {
+            name: "Writer",
+            reads: vec![],
+            writes: vec!["Position", "Velocity"],
+        };
+
+        assert!(reader.conflicts_with(&other_reader).is_empty());
+        assert_eq!(
+            reader.conflicts_with(&writer).into_iter().collect::<Vec<_>>(),
+            vec!["Position"]
+        );
+    }
+}
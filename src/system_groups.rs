@@ -0,0 +1,272 @@
+//! Named groups of systems that are enabled and ordered together.
+//!
+//! Systems added with `add_system_to_group` belong to a group such as
+//! "simulation", "rendering" or "debug". Whole groups can be switched off,
+//! e.g. for headless runs or replay verification, and ordered relative to
+//! each other:
+//!
+//! ```ignore
+//! world.add_system_to_group("rendering", RenderSystem);
+//! world.add_system_to_group("simulation", MovementSystem);
+//! world.order_system_groups("simulation", "rendering")?;
+//! world.set_group_enabled("rendering", false);
+//! ```
+//!
+//! Systems of a disabled group are skipped like systems disabled after a
+//! panic: they record an empty diff, so the system indices of the history stay
+//! aligned. Group order takes effect at the start of the next update, which
+//! moves systems of later groups after those of earlier groups and keeps
+//! everything else in the order it was added.
+
+use crate::{System, World};
+use std::collections::{HashMap, HashSet};
+
+/// Group membership, disabled groups and ordering constraints of a world
+#[derive(Clone, Default)]
+pub(crate) struct SystemGroups {
+    /// Group of each grouped system, by system index
+    members: HashMap<usize, String>,
+    disabled: HashSet<String>,
+    /// Pairs of groups where the first runs before the second
+    order: Vec<(String, String)>,
+    /// Whether the systems must be reordered before the next update
+    order_changed: bool,
+}
+
+impl SystemGroups {
+    /// Check if the system at `system_index` belongs to a disabled group
+    pub(crate) fn is_disabled(&self, system_index: usize) -> bool {
+        self.members
+            .get(&system_index)
+            .is_some_and(|group| self.disabled.contains(group))
+    }
+
+    /// Get the group of the system at `system_index`
+    pub(crate) fn group_of(&self, system_index: usize) -> Option<&str> {
+        self.members.get(&system_index).map(String::as_str)
+    }
+
+    /// Check if `first` has to run before `second`, directly or through other groups
+    fn runs_before(&self, first: &str, second: &str) -> bool {
+        let mut pending = vec![first];
+        let mut visited = HashSet::new();
+        while let Some(group) = pending.pop() {
+            if !visited.insert(group) {
+                continue;
+            }
+            for (before, after) in &self.order {
+                if before == group {
+                    if after == second {
+                        return true;
+                    }
+                    pending.push(after);
+                }
+            }
+        }
+        false
+    }
+
+    /// Check if the system at `first` has to run before the system at `second`
+    fn must_precede(&self, first: usize, second: usize) -> bool {
+        match (self.members.get(&first), self.members.get(&second)) {
+            (Some(first), Some(second)) => self.order.iter().any(|(before, after)| before == first && after == second),
+            _ => false,
+        }
+    }
+}
+
+impl World {
+    /// Add a system to the world as a member of `group`
+    pub fn add_system_to_group<S: System + 'static>(&mut self, group: &str, system: S) {
+        self.add_system(system);
+        self.system_groups
+            .members
+            .insert(self.systems.len() - 1, group.to_string());
+        self.system_groups.order_changed = true;
+    }
+
+    /// Move the already added systems of type `S` to `group`, e.g. systems
+    /// added by a plugin. Returns false if no such system was added.
+    pub fn set_system_group<S: System + 'static>(&mut self, group: &str) -> bool {
+        let name = std::any::type_name::<S>();
+        let indices: Vec<usize> = self
+            .systems
+            .iter()
+            .enumerate()
+            .filter(|(_, system)| system.name() == name)
+            .map(|(index, _)| index)
+            .collect();
+        for &index in &indices {
+            self.system_groups.members.insert(index, group.to_string());
+        }
+        self.system_groups.order_changed |= !indices.is_empty();
+        !indices.is_empty()
+    }
+
+    /// Get the names of the systems in `group`, in execution order
+    pub fn systems_in_group(&self, group: &str) -> Vec<&'static str> {
+        self.systems
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| self.system_groups.members.get(index).map(String::as_str) == Some(group))
+            .map(|(_, system)| system.name())
+            .collect()
+    }
+
+    /// Enable or disable all systems of `group`. Groups are enabled by default.
+    pub fn set_group_enabled(&mut self, group: &str, enabled: bool) {
+        if enabled {
+            self.system_groups.disabled.remove(group);
+        } else {
+            self.system_groups.disabled.insert(group.to_string());
+        }
+    }
+
+    /// Check if the systems of `group` run
+    pub fn is_group_enabled(&self, group: &str) -> bool {
+        !self.system_groups.disabled.contains(group)
+    }
+
+    /// Run all systems of group `before` ahead of those of group `after`,
+    /// starting with the next update
+    pub fn order_system_groups(&mut self, before: &str, after: &str) -> Result<(), String> {
+        if before == after {
+            return Err(format!("Group {} cannot run before itself", before));
+        }
+        if self.system_groups.runs_before(after, before) {
+            return Err(format!("Group {} already runs before group {}", after, before));
+        }
+        self.system_groups
+            .order
+            .push((before.to_string(), after.to_string()));
+        self.system_groups.order_changed = true;
+        Ok(())
+    }
+
+    /// Reorder the systems to follow the group order, if it changed
+    pub(crate) fn apply_system_group_order(&mut self) {
+        if !std::mem::take(&mut self.system_groups.order_changed) {
+            return;
+        }
+        // Stable topological sort: always take the earliest system none of
+        // whose predecessors is still waiting
+        let count = self.systems.len();
+        let mut order = Vec::with_capacity(count);
+        let mut placed = vec![false; count];
+        while order.len() < count {
+            let next = (0..count)
+                .find(|&index| {
+                    !placed[index]
+                        && (0..count).all(|other| placed[other] || !self.system_groups.must_precede(other, index))
+                })
+                .expect("group order constraints are acyclic");
+            placed[next] = true;
+            order.push(next);
+        }
+        if order.iter().enumerate().all(|(position, &index)| position == index) {
+            return;
+        }
+
+        let mut new_index = vec![0; count];
+        for (position, &index) in order.iter().enumerate() {
+            new_index[index] = position;
+        }
+        let mut systems: Vec<_> = std::mem::take(&mut self.systems).into_iter().map(Some).collect();
+        self.systems = order
+            .iter()
+            .map(|&index| systems[index].take().expect("every system is placed once"))
+            .collect();
+        self.system_groups.members = std::mem::take(&mut self.system_groups.members)
+            .into_iter()
+            .map(|(index, group)| (new_index[index], group))
+            .collect();
+        self.disabled_systems = self.disabled_systems.iter().map(|&index| new_index[index]).collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::WorldView;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    type RunLog = Rc<RefCell<Vec<&'static str>>>;
+
+    macro_rules! logging_system {
+        ($name:ident) => {
+            struct $name(RunLog);
+
+            impl System for $name {
+                type InComponents = ();
+                type OutComponents = ();
+
+                fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+                fn update(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+                    self.0.borrow_mut().push(stringify!($name));
+                }
+
+                fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+            }
+        };
+    }
+
+    logging_system!(RenderSystem);
+    logging_system!(PhysicsSystem);
+    logging_system!(DebugSystem);
+    logging_system!(InputSystem);
+
+    fn run(world: &mut World, log: &RunLog) -> Vec<&'static str> {
+        log.borrow_mut().clear();
+        world.update();
+        log.borrow().clone()
+    }
+
+    #[test]
+    fn test_disabled_group_is_skipped() {
+        let log = RunLog::default();
+        let mut world = World::new();
+        world.add_system_to_group("simulation", PhysicsSystem(log.clone()));
+        world.add_system_to_group("debug", DebugSystem(log.clone()));
+        world.add_system(InputSystem(log.clone()));
+
+        world.set_group_enabled("debug", false);
+        assert!(!world.is_group_enabled("debug"));
+        assert_eq!(run(&mut world, &log), vec!["PhysicsSystem", "InputSystem"]);
+        // Skipped systems keep their slot in the frame's diff
+        assert_eq!(world.get_update_history().updates().last().unwrap().system_diffs().len(), 3);
+
+        world.set_group_enabled("debug", true);
+        assert_eq!(run(&mut world, &log), vec!["PhysicsSystem", "DebugSystem", "InputSystem"]);
+    }
+
+    #[test]
+    fn test_group_order() {
+        let log = RunLog::default();
+        let mut world = World::new();
+        world.add_system_to_group("rendering", RenderSystem(log.clone()));
+        world.add_system(InputSystem(log.clone()));
+        world.add_system_to_group("simulation", PhysicsSystem(log.clone()));
+        world.add_system_to_group("debug", DebugSystem(log.clone()));
+
+        world.order_system_groups("simulation", "rendering").unwrap();
+        world.order_system_groups("rendering", "debug").unwrap();
+        assert!(world.order_system_groups("debug", "simulation").is_err());
+        assert!(world.order_system_groups("debug", "debug").is_err());
+
+        assert_eq!(
+            run(&mut world, &log),
+            vec!["InputSystem", "PhysicsSystem", "RenderSystem", "DebugSystem"]
+        );
+        assert_eq!(world.systems_in_group("rendering"), vec![std::any::type_name::<RenderSystem>()]);
+
+        // Groups stay enabled and disabled by name after reordering
+        world.set_group_enabled("rendering", false);
+        assert_eq!(run(&mut world, &log), vec!["InputSystem", "PhysicsSystem", "DebugSystem"]);
+
+        assert!(world.set_system_group::<InputSystem>("debug"));
+        assert!(!world.set_system_group::<crate::TransformPropagationSystem>("debug"));
+        assert_eq!(run(&mut world, &log), vec!["PhysicsSystem", "InputSystem", "DebugSystem"]);
+    }
+}
@@ -0,0 +1,205 @@
+//! Factories for the systems of replayed `AddSystem` operations.
+//!
+//! Replay logs only record the type name of an added system. Registering a
+//! factory under that name lets `World::apply_update_diff` construct the
+//! system again, so the core library needs no knowledge of concrete systems:
+//!
+//! ```ignore
+//! world.register_system_factory(|| MovementSystem);
+//! ```
+
+use crate::{System, World, WorldOperation, WorldUpdateHistory};
+use std::collections::{BTreeSet, HashMap};
+use std::rc::Rc;
+
+/// Adds a newly constructed system to a world
+type SystemFactory = Rc<dyn Fn(&mut World)>;
+
+/// System factories keyed by the full type name recorded in `AddSystem` operations
+#[derive(Clone, Default)]
+pub struct SystemRegistry {
+    factories: HashMap<String, SystemFactory>,
+}
+
+impl std::fmt::Debug for SystemRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SystemRegistry")
+            .field("systems", &self.names())
+            .finish()
+    }
+}
+
+impl SystemRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a factory for systems of type S, replacing an earlier one
+    pub fn register<S: System + 'static>(&mut self, factory: impl Fn() -> S + 'static) {
+        self.factories.insert(
+            std::any::type_name::<S>().to_string(),
+            Rc::new(move |world: &mut World| world.add_system_internal(factory())),
+        );
+    }
+
+    /// Check if a factory is registered under a system type name
+    pub fn contains(&self, type_name: &str) -> bool {
+        self.factories.contains_key(type_name)
+    }
+
+    /// Get the type names of all registered systems, sorted
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.factories.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    pub(crate) fn get(&self, type_name: &str) -> Option<SystemFactory> {
+        self.factories.get(type_name).cloned()
+    }
+}
+
+impl World {
+    /// Register how to construct systems of type S when a replay adds one
+    pub fn register_system_factory<S: System + 'static>(&mut self, factory: impl Fn() -> S + 'static) {
+        self.system_registry.register(factory);
+    }
+
+    /// Get the registered system factories
+    pub fn system_registry(&self) -> &SystemRegistry {
+        &self.system_registry
+    }
+
+    /// Check that every system added and every component changed in `history`,
+    /// including its initial state and keyframes, is registered, so replaying it reconstructs the recorded world. The
+    /// error lists all missing registrations.
+    pub fn check_replay_registrations(&self, history: &WorldUpdateHistory) -> Result<(), String> {
+        let mut systems = BTreeSet::new();
+        let mut components = BTreeSet::new();
+        let replayable = |type_name: &str| {
+            self.component_registry
+                .get(type_name)
+                .is_some_and(|registration| registration.is_replayable())
+        };
+
+        let snapshots: Vec<_> = history
+            .initial_state()
+            .into_iter()
+            .chain(history.keyframes().iter().map(|keyframe| &keyframe.state))
+            .collect();
+        let updates = history.updates().iter().flat_map(|update| update.system_diffs());
+        for system_diff in snapshots.iter().map(|snapshot| &snapshot.diff).chain(updates) {
+            for operation in system_diff.world_operations() {
+                if let WorldOperation::AddSystem(type_name) = operation {
+                    if !self.system_registry.contains(type_name) {
+                        systems.insert(type_name.as_str());
+                    }
+                }
+            }
+            for change in system_diff.component_changes() {
+                if !replayable(change.type_name()) {
+                    components.insert(change.type_name());
+                }
+            }
+        }
+        // Resources are registered like components
+        for (type_name, _) in snapshots.iter().flat_map(|snapshot| &snapshot.resources) {
+            if !replayable(type_name) {
+                components.insert(type_name.as_str());
+            }
+        }
+
+        let mut missing = Vec::new();
+        if !systems.is_empty() {
+            missing.push(format!(
+                "systems without a factory (register_system_factory): {}",
+                systems.into_iter().collect::<Vec<_>>().join(", ")
+            ));
+        }
+        if !components.is_empty() {
+            missing.push(format!(
+                "components not registered for replay (register_replay_component): {}",
+                components.into_iter().collect::<Vec<_>>().join(", ")
+            ));
+        }
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("Replay contains unregistered types - {}", missing.join("; ")))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SystemUpdateDiff, WorldOperation, WorldUpdateDiff, WorldView};
+
+    struct TickSystem {
+        step: u32,
+    }
+
+    impl System for TickSystem {
+        type InComponents = ();
+        type OutComponents = (u32,);
+
+        fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+        fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+            let entity = world.create_entity();
+            world.add_component(entity, self.step);
+        }
+
+        fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    }
+
+    fn add_system_update(type_name: &str) -> WorldUpdateDiff {
+        let mut system_diff = SystemUpdateDiff::new();
+        system_diff.record_world_operation(WorldOperation::AddSystem(type_name.to_string()));
+        let mut update = WorldUpdateDiff::new();
+        update.record(system_diff);
+        update
+    }
+
+    #[test]
+    fn test_replayed_system_is_constructed_by_factory() {
+        let mut world = World::new();
+        world.register_system_factory(|| TickSystem { step: 3 });
+        assert!(world.system_registry().contains(std::any::type_name::<TickSystem>()));
+
+        world.apply_update_diff(&add_system_update(std::any::type_name::<TickSystem>()));
+        world.apply_update_diff(&add_system_update("my_game::UnknownSystem"));
+        world.update();
+
+        let entities = world.entities_with_component::<u32>();
+        assert_eq!(entities.len(), 1);
+        assert_eq!(world.get_component::<u32>(entities[0]), Some(&3));
+    }
+
+    #[test]
+    fn test_check_lists_unregistered_types() {
+        let mut history = WorldUpdateHistory::new();
+        history.record(add_system_update("my_game::AiSystem"));
+        history.record(add_system_update(std::any::type_name::<TickSystem>()));
+        history.record(add_system_update("my_game::AiSystem"));
+        let mut system_diff = SystemUpdateDiff::new();
+        system_diff.record_component_change(crate::DiffComponentChange::Removed {
+            entity: crate::Entity::new(0, 0),
+            type_name: "Health".into(),
+        });
+        let mut update = WorldUpdateDiff::new();
+        update.record(system_diff);
+        history.record(update);
+
+        let mut world = World::new();
+        world.register_system_factory(|| TickSystem { step: 1 });
+        assert_eq!(
+            world.check_replay_registrations(&history),
+            Err("Replay contains unregistered types - systems without a factory (register_system_factory): \
+                 my_game::AiSystem; components not registered for replay (register_replay_component): Health"
+                .to_string())
+        );
+        assert_eq!(world.check_replay_registrations(&WorldUpdateHistory::new()), Ok(()));
+    }
+}
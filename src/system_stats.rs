@@ -0,0 +1,252 @@
+//! Per-system execution statistics recorded with the history.
+//!
+//! With `World::set_system_stats(true)` every system update records how long
+//! it took and how many entities its queries matched. Replay logs keep them
+//! on a `STATS` line of the system, so performance can be analyzed offline
+//! with `analyze_replay_history` / `print_replay_analysis`:
+//!
+//! ```text
+//!   SYSTEM 0
+//!     STATS: time_us=120 queries=2 matched=340 allocs=12
+//! ```
+//!
+//! Allocations are only counted when the application installs
+//! `CountingAllocator` as its global allocator:
+//!
+//! ```ignore
+//! #[global_allocator]
+//! static ALLOCATOR: rust_ecs::system_stats::CountingAllocator = rust_ecs::system_stats::CountingAllocator;
+//! ```
+
+use crate::World;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Duration;
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static COUNTING: AtomicBool = AtomicBool::new(false);
+
+/// Global allocator counting allocations for the `allocs` statistic
+pub struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        COUNTING.store(true, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        COUNTING.store(true, Ordering::Relaxed);
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+/// Number of allocations so far, if `CountingAllocator` is the global allocator
+pub fn allocation_count() -> Option<u64> {
+    COUNTING
+        .load(Ordering::Relaxed)
+        .then(|| ALLOCATIONS.load(Ordering::Relaxed))
+}
+
+/// Execution statistics of one system update
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SystemStats {
+    /// Wall-clock time spent in the update
+    pub duration: Duration,
+    /// Number of queries the system ran
+    pub queries: usize,
+    /// Number of entities matched by all of its queries
+    pub entities_matched: usize,
+    /// Number of allocations, if counted by `CountingAllocator`
+    pub allocations: Option<u64>,
+}
+
+/// Formats the statistics the way they appear in replay logs, e.g. `time_us=120 queries=2 matched=340`
+impl std::fmt::Display for SystemStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "time_us={} queries={} matched={}",
+            self.duration.as_micros(),
+            self.queries,
+            self.entities_matched
+        )?;
+        if let Some(allocations) = self.allocations {
+            write!(f, " allocs={}", allocations)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for SystemStats {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut stats = SystemStats::default();
+        for field in text.split_whitespace() {
+            let (key, value) = field
+                .split_once('=')
+                .ok_or_else(|| format!("expected key=value, found {}", field))?;
+            let value: u64 = value.parse().map_err(|_| format!("invalid {} value {}", key, value))?;
+            match key {
+                "time_us" => stats.duration = Duration::from_micros(value),
+                "queries" => stats.queries = value as usize,
+                "matched" => stats.entities_matched = value as usize,
+                "allocs" => stats.allocations = Some(value),
+                // Statistics added by later versions are skipped
+                _ => {}
+            }
+        }
+        Ok(stats)
+    }
+}
+
+/// Queries run by the current system while statistics are recorded.
+/// Counted through a shared reference, so read-only views can record too.
+#[derive(Debug, Default)]
+pub(crate) struct QueryCounts {
+    queries: Cell<usize>,
+    entities_matched: Cell<usize>,
+}
+
+impl QueryCounts {
+    pub(crate) fn record(&self, matched: usize) {
+        self.queries.set(self.queries.get() + 1);
+        self.entities_matched.set(self.entities_matched.get() + matched);
+    }
+}
+
+/// Statistics of the system update in progress
+pub(crate) struct SystemStatsStart {
+    started: Duration,
+    allocations: Option<u64>,
+}
+
+impl World {
+    /// Record execution statistics of every system update in the history
+    /// and replay logs. Off by default.
+    pub fn set_system_stats(&mut self, enabled: bool) {
+        self.query_counts = enabled.then(QueryCounts::default);
+    }
+
+    /// Check if system execution statistics are recorded
+    pub fn system_stats_enabled(&self) -> bool {
+        self.query_counts.is_some()
+    }
+
+    /// Start measuring a system update, if statistics are recorded
+    pub(crate) fn begin_system_stats(&mut self) -> Option<SystemStatsStart> {
+        let counts = self.query_counts.as_mut()?;
+        *counts = QueryCounts::default();
+        Some(SystemStatsStart {
+            started: self.now(),
+            allocations: allocation_count(),
+        })
+    }
+
+    /// Finish measuring a system update started with `begin_system_stats`
+    pub(crate) fn finish_system_stats(&mut self, start: SystemStatsStart) -> SystemStats {
+        let counts = self.query_counts.as_mut().map(std::mem::take).unwrap_or_default();
+        SystemStats {
+            duration: self.now().saturating_sub(start.started),
+            queries: counts.queries.get(),
+            entities_matched: counts.entities_matched.get(),
+            allocations: start
+                .allocations
+                .zip(allocation_count())
+                .map(|(before, after)| after.saturating_sub(before)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay_analysis::{analyze_replay_history, parse_replay_log_str, validate_replay_log_str, write_replay_log};
+    use crate::{In, Out, System, WorldView};
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Position(i32);
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Velocity(i32);
+
+    struct MovementSystem;
+
+    impl System for MovementSystem {
+        type InComponents = (Velocity,);
+        type OutComponents = (Position,);
+
+        fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+        fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+            for (_, (velocity, position)) in world.query_components::<(In<Velocity>, Out<Position>)>() {
+                position.0 += velocity.0;
+            }
+            world.query_components::<(In<Position>,)>();
+        }
+
+        fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    }
+
+    #[test]
+    fn test_stats_format_round_trip() {
+        let stats = SystemStats {
+            duration: Duration::from_micros(120),
+            queries: 2,
+            entities_matched: 340,
+            allocations: Some(12),
+        };
+        assert_eq!(stats.to_string(), "time_us=120 queries=2 matched=340 allocs=12");
+        assert_eq!(stats.to_string().parse(), Ok(stats));
+        assert!("time_us=fast".parse::<SystemStats>().is_err());
+    }
+
+    #[test]
+    fn test_system_stats_are_logged_and_aggregated() {
+        let mut world = World::new();
+        for velocity in [1, 2, 3] {
+            let entity = world.create_entity();
+            world.add_component(entity, Position(0));
+            if velocity > 1 {
+                world.add_component(entity, Velocity(velocity));
+            }
+        }
+        world.add_system(MovementSystem);
+        world.update();
+        assert!(world.get_update_history().updates().last().unwrap().system_diffs()[0].stats().is_none());
+
+        world.set_system_stats(true);
+        world.update();
+        world.update();
+        let stats = *world.get_update_history().updates().last().unwrap().system_diffs()[0].stats().unwrap();
+        assert_eq!((stats.queries, stats.entities_matched), (2, 5));
+
+        let mut log = Vec::new();
+        write_replay_log(world.get_update_history(), &mut log).unwrap();
+        let log = String::from_utf8(log).unwrap();
+        assert!(log.contains("STATS: time_us="), "{}", log);
+        assert!(validate_replay_log_str(&log).is_empty(), "{:?}", validate_replay_log_str(&log));
+
+        let history = parse_replay_log_str(&log);
+        assert_eq!(history.updates().last().unwrap().system_diffs()[0].stats().map(|s| s.entities_matched), Some(5));
+        let analysis = analyze_replay_history(&history);
+        assert_eq!(analysis.system_stats.len(), 1);
+        assert_eq!(analysis.system_stats[0].executions, 2);
+        assert_eq!(analysis.system_stats[0].queries, 4);
+        assert_eq!(analysis.system_stats[0].entities_matched, 10);
+    }
+}
@@ -0,0 +1,238 @@
+//! Background tasks for computations that take longer than a frame.
+//!
+//! Systems spawn work such as pathfinding over a large map or procedural
+//! generation on the `TaskPool` resource and attach the returned
+//! `AsyncTask<T>` to an entity. `TaskCompletionSystem<T>` checks the tasks
+//! every frame and, once one is done, replaces it with its result `T`:
+//!
+//! ```ignore
+//! world.insert_resource(TaskPool::default());
+//! world.add_system(PathRequestSystem);
+//! world.add_system(TaskCompletionSystem::<Path>::new());
+//!
+//! // In PathRequestSystem::update
+//! let task = world.resource::<TaskPool>().unwrap().spawn(move || find_path(&map, start, goal));
+//! world.add_component(actor, task);
+//! ```
+//!
+//! Results are recorded as component additions, so replays apply them in the
+//! frame the task finished without running it again. The tasks themselves
+//! are not recorded.
+
+use crate::{Entity, Out, System, World, WorldView};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Worker threads running spawned tasks in the order they were spawned
+pub struct TaskPool {
+    jobs: Option<Sender<Job>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl TaskPool {
+    /// Create a pool with `threads` worker threads (at least one)
+    pub fn new(threads: usize) -> Self {
+        let (jobs, queue) = mpsc::channel::<Job>();
+        let queue = Arc::new(Mutex::new(queue));
+        let workers = (0..threads.max(1))
+            .map(|_| {
+                let queue = Arc::clone(&queue);
+                std::thread::spawn(move || loop {
+                    let job = match queue.lock() {
+                        Ok(queue) => queue.recv(),
+                        Err(_) => return,
+                    };
+                    match job {
+                        // A panicking task drops its result sender, which
+                        // fails the task instead of the worker
+                        Ok(job) => {
+                            let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(job));
+                        }
+                        Err(_) => return,
+                    }
+                })
+            })
+            .collect();
+        Self {
+            jobs: Some(jobs),
+            workers,
+        }
+    }
+
+    /// Get the number of worker threads
+    pub fn thread_count(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Run `task` on a worker thread
+    pub fn spawn<T: Send + 'static>(&self, task: impl FnOnce() -> T + Send + 'static) -> AsyncTask<T> {
+        let (result, receiver) = mpsc::channel();
+        let job: Job = Box::new(move || {
+            let _ = result.send(task());
+        });
+        if let Some(jobs) = &self.jobs {
+            // Workers only stop once the pool is dropped
+            let _ = jobs.send(job);
+        }
+        AsyncTask { result: receiver }
+    }
+}
+
+/// A pool with one worker per available CPU
+impl Default for TaskPool {
+    fn default() -> Self {
+        Self::new(std::thread::available_parallelism().map_or(1, |threads| threads.get()))
+    }
+}
+
+/// Lets the workers finish the queued tasks and waits for them
+impl Drop for TaskPool {
+    fn drop(&mut self) {
+        self.jobs = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Component holding the pending result of a task spawned on a `TaskPool`
+pub struct AsyncTask<T> {
+    result: Receiver<T>,
+}
+
+impl<T> AsyncTask<T> {
+    /// Take the result if the task is done. Returns an error if the task
+    /// panicked, and None while it is still running.
+    pub fn poll(&mut self) -> Option<Result<T, String>> {
+        match self.result.try_recv() {
+            Ok(result) => Some(Ok(result)),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err("task panicked".to_string())),
+        }
+    }
+}
+
+/// Replaces every finished `AsyncTask<T>` with its result component `T`.
+/// Tasks that panicked are removed with a warning.
+pub struct TaskCompletionSystem<T> {
+    _result: std::marker::PhantomData<T>,
+}
+
+impl<T> TaskCompletionSystem<T> {
+    pub fn new() -> Self {
+        Self {
+            _result: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<T> Default for TaskCompletionSystem<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Send + std::fmt::Debug + 'static> System for TaskCompletionSystem<T> {
+    type InComponents = ();
+    type OutComponents = (AsyncTask<T>, T);
+
+    fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+    fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+        let finished: Vec<(Entity, Result<T, String>)> = world
+            .query_components::<(Out<AsyncTask<T>>,)>()
+            .into_iter()
+            .filter_map(|(entity, task)| Some((entity, task.poll()?)))
+            .collect();
+        for (entity, result) in finished {
+            unsafe { world.world_mut() }.remove_component::<AsyncTask<T>>(entity);
+            match result {
+                Ok(component) => {
+                    world.record_component_addition(entity, &component);
+                    world.add_component(entity, component);
+                }
+                Err(message) => ecs_warn!(
+                    "Task for {} of {:?} failed: {}",
+                    std::any::type_name::<T>(),
+                    entity,
+                    message
+                ),
+            }
+        }
+    }
+
+    fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+}
+
+impl World {
+    /// Get the number of entities still waiting for a task with result `T`
+    pub fn pending_tasks<T: 'static>(&self) -> usize {
+        self.entities_with_component::<AsyncTask<T>>().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DiffComponentChange;
+    use std::time::Duration;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Path(Vec<i32>);
+
+    fn run_until_done(world: &mut World) {
+        for _ in 0..500 {
+            world.update();
+            if world.pending_tasks::<Path>() == 0 {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(2));
+        }
+        panic!("tasks did not finish");
+    }
+
+    #[test]
+    fn test_finished_task_becomes_component() {
+        let mut world = World::new();
+        world.insert_resource(TaskPool::new(2));
+        world.add_system(TaskCompletionSystem::<Path>::new());
+        let actor = world.create_entity();
+        let task = world.resource::<TaskPool>().unwrap().spawn(|| Path((0..4).collect()));
+        world.add_component(actor, task);
+        assert_eq!(world.pending_tasks::<Path>(), 1);
+
+        run_until_done(&mut world);
+        assert_eq!(world.get_component::<Path>(actor), Some(&Path(vec![0, 1, 2, 3])));
+        let changes = world.get_update_history().updates().last().unwrap().system_diffs()[0].component_changes();
+        assert!(matches!(
+            changes,
+            [DiffComponentChange::Added { entity, type_name, .. }] if *entity == actor && &**type_name == "Path"
+        ));
+    }
+
+    #[test]
+    fn test_panicking_task_is_removed() {
+        let mut world = World::new();
+        world.insert_resource(TaskPool::new(1));
+        world.add_system(TaskCompletionSystem::<Path>::new());
+        let actor = world.create_entity();
+        let task = world
+            .resource::<TaskPool>()
+            .unwrap()
+            .spawn(|| -> Path { panic!("no path") });
+        world.add_component(actor, task);
+
+        run_until_done(&mut world);
+        assert_eq!(world.get_component::<Path>(actor), None);
+
+        // The worker survives the panic
+        let other = world.create_entity();
+        let task = world.resource::<TaskPool>().unwrap().spawn(|| Path(vec![7]));
+        world.add_component(other, task);
+        run_until_done(&mut world);
+        assert_eq!(world.get_component::<Path>(other), Some(&Path(vec![7])));
+    }
+}
@@ -0,0 +1,307 @@
+//! Structured diagnostics for the ECS runtime.
+//!
+//! With the `tracing` feature enabled, frames and system updates run inside
+//! spans and every recorded component change is emitted as an event, so ECS
+//! telemetry can be routed through any `tracing` subscriber. Without the
+//! feature, informational messages go to stdout and problems to stderr as
+//! before, and spans and change events compile to nothing.
+//!
+//! With the `metrics` feature enabled, frame, system and replay log statistics
+//! are reported through the `metrics` facade (see REPLAY_LOGGING.md for the
+//! list of metrics).
+
+use crate::{DiffComponentChange, SystemTiming, World, WorldOperation, WorldUpdateDiff};
+
+/// Whether system updates must be timed for metrics
+pub(crate) const METRICS_ENABLED: bool = cfg!(feature = "metrics");
+
+/// Report progress information (stdout without the `tracing` feature)
+macro_rules! ecs_info {
+    ($($arg:tt)*) => {{
+        #[cfg(feature = "tracing")]
+        tracing::info!($($arg)*);
+        #[cfg(not(feature = "tracing"))]
+        println!($($arg)*);
+    }};
+}
+
+/// Report a recoverable problem (stderr without the `tracing` feature)
+macro_rules! ecs_warn {
+    ($($arg:tt)*) => {{
+        #[cfg(feature = "tracing")]
+        tracing::warn!($($arg)*);
+        #[cfg(not(feature = "tracing"))]
+        eprintln!($($arg)*);
+    }};
+}
+
+/// Report a failed operation (stderr without the `tracing` feature)
+macro_rules! ecs_error {
+    ($($arg:tt)*) => {{
+        #[cfg(feature = "tracing")]
+        tracing::error!($($arg)*);
+        #[cfg(not(feature = "tracing"))]
+        eprintln!($($arg)*);
+    }};
+}
+
+/// Guard keeping a span entered until it is dropped
+#[cfg(feature = "tracing")]
+pub(crate) type SpanGuard = tracing::span::EnteredSpan;
+#[cfg(not(feature = "tracing"))]
+pub(crate) struct SpanGuard;
+
+/// Enter the span covering one world update
+#[cfg(feature = "tracing")]
+pub(crate) fn frame_span(world_index: usize, frame: usize) -> SpanGuard {
+    tracing::info_span!("ecs_frame", world = world_index, frame).entered()
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn frame_span(_world_index: usize, _frame: usize) -> SpanGuard {
+    SpanGuard
+}
+
+/// Enter the span covering a single system update
+#[cfg(feature = "tracing")]
+pub(crate) fn system_span(system: &str) -> SpanGuard {
+    tracing::debug_span!("ecs_system", system).entered()
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn system_span(_system: &str) -> SpanGuard {
+    SpanGuard
+}
+
+/// Emit events for the changes recorded by a system update
+#[cfg(feature = "tracing")]
+pub(crate) fn record_changes(changes: &[DiffComponentChange], operations: &[WorldOperation]) {
+    for change in changes {
+        match change {
+            DiffComponentChange::Added { entity, type_name, data } => tracing::trace!(
+                entity = %entity, component = %type_name, data = %data, "component added"
+            ),
+            DiffComponentChange::Modified { entity, type_name, diff } => tracing::trace!(
+                entity = %entity, component = %type_name, diff = %diff, "component modified"
+            ),
+            DiffComponentChange::Removed { entity, type_name } => tracing::trace!(
+                entity = %entity, component = %type_name, "component removed"
+            ),
+        }
+    }
+    for operation in operations {
+        tracing::trace!(operation = %operation, "world operation");
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn record_changes(_changes: &[DiffComponentChange], _operations: &[WorldOperation]) {}
+
+/// Report the timing and diff size of one system update
+#[cfg(feature = "metrics")]
+pub(crate) fn record_system_metrics(timing: &SystemTiming) {
+    metrics::histogram!("ecs_system_duration_seconds", "system" => timing.system_name.clone())
+        .record(timing.duration.as_secs_f64());
+    metrics::histogram!("ecs_system_diff_size", "system" => timing.system_name.clone())
+        .record(timing.change_count as f64);
+}
+
+#[cfg(not(feature = "metrics"))]
+pub(crate) fn record_system_metrics(_timing: &SystemTiming) {}
+
+/// Report world statistics after a completed frame
+#[cfg(feature = "metrics")]
+pub(crate) fn record_frame_metrics(world: &World, diff: &WorldUpdateDiff) {
+    let world_label = world.world_index.to_string();
+    let component_count: usize = world.components.values().map(|components| components.len()).sum();
+    let diff_size: usize = diff
+        .system_diffs()
+        .iter()
+        .map(|system_diff| system_diff.component_changes().len() + system_diff.world_operations().len())
+        .sum();
+
+    metrics::counter!("ecs_frames_total", "world" => world_label.clone()).increment(1);
+    metrics::gauge!("ecs_entities", "world" => world_label.clone()).set(world.entity_count() as f64);
+    metrics::gauge!("ecs_components", "world" => world_label.clone()).set(component_count as f64);
+    metrics::histogram!("ecs_frame_diff_size", "world" => world_label).record(diff_size as f64);
+}
+
+#[cfg(not(feature = "metrics"))]
+pub(crate) fn record_frame_metrics(_world: &World, _diff: &WorldUpdateDiff) {}
+
+/// Report bytes written to a replay log
+#[cfg(feature = "metrics")]
+pub(crate) fn replay_log_bytes_written(bytes: usize) {
+    metrics::counter!("ecs_replay_log_bytes_written_total").increment(bytes as u64);
+}
+
+#[cfg(not(feature = "metrics"))]
+pub(crate) fn replay_log_bytes_written(_bytes: usize) {}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use crate::{Out, System, World, WorldView};
+    use std::sync::{Arc, Mutex};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    /// Records the names of created spans and events
+    struct RecordingSubscriber {
+        names: Arc<Mutex<Vec<String>>>,
+        next_id: Mutex<u64>,
+    }
+
+    impl Subscriber for RecordingSubscriber {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            self.names.lock().unwrap().push(span.metadata().name().to_string());
+            let mut next_id = self.next_id.lock().unwrap();
+            *next_id += 1;
+            Id::from_u64(*next_id)
+        }
+
+        fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            self.names.lock().unwrap().push(event.metadata().name().to_string());
+        }
+
+        fn enter(&self, _span: &Id) {}
+
+        fn exit(&self, _span: &Id) {}
+    }
+
+    #[derive(Debug, Clone, PartialEq, crate::Diff)]
+    struct Counter {
+        value: i32,
+    }
+
+    struct CountSystem;
+    impl System for CountSystem {
+        type InComponents = ();
+        type OutComponents = (Counter,);
+
+        fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+        fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+            let mut changes = Vec::new();
+            for (entity, counter) in world.query_components::<(Out<Counter>,)>() {
+                let old = counter.clone();
+                counter.value += 1;
+                changes.push((entity, old, counter.clone()));
+            }
+            for (entity, old, new) in changes {
+                world.record_component_modification(entity, &old, &new);
+            }
+        }
+
+        fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    }
+
+    #[test]
+    fn test_update_emits_spans_and_change_events() {
+        let names = Arc::new(Mutex::new(Vec::new()));
+        let subscriber = RecordingSubscriber {
+            names: names.clone(),
+            next_id: Mutex::new(0),
+        };
+
+        tracing::subscriber::with_default(subscriber, || {
+            let mut world = World::new();
+            let entity = world.create_entity();
+            world.add_component(entity, Counter { value: 0 });
+            world.add_system(CountSystem);
+            world.update();
+        });
+
+        let names = names.lock().unwrap();
+        assert!(names.iter().any(|name| name == "ecs_frame"));
+        assert!(names.iter().any(|name| name == "ecs_system"));
+        assert!(names.iter().any(|name| name.contains("telemetry.rs")));
+    }
+}
+
+#[cfg(all(test, feature = "metrics"))]
+mod metrics_tests {
+    use crate::{Out, System, World, WorldView};
+    use metrics::{Counter, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SharedString, Unit};
+    use std::sync::Mutex;
+
+    /// Records the names of registered metrics
+    #[derive(Default)]
+    struct RecordingRecorder {
+        names: Mutex<Vec<String>>,
+    }
+
+    impl RecordingRecorder {
+        fn register(&self, key: &Key) {
+            self.names.lock().unwrap().push(key.name().to_string());
+        }
+    }
+
+    impl Recorder for RecordingRecorder {
+        fn describe_counter(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+        fn describe_gauge(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+        fn describe_histogram(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+
+        fn register_counter(&self, key: &Key, _metadata: &Metadata<'_>) -> Counter {
+            self.register(key);
+            Counter::noop()
+        }
+
+        fn register_gauge(&self, key: &Key, _metadata: &Metadata<'_>) -> Gauge {
+            self.register(key);
+            Gauge::noop()
+        }
+
+        fn register_histogram(&self, key: &Key, _metadata: &Metadata<'_>) -> Histogram {
+            self.register(key);
+            Histogram::noop()
+        }
+    }
+
+    struct IdleSystem;
+    impl System for IdleSystem {
+        type InComponents = ();
+        type OutComponents = (i32,);
+
+        fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+        fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+            for (_entity, value) in world.query_components::<(Out<i32>,)>() {
+                *value += 1;
+            }
+        }
+
+        fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    }
+
+    #[test]
+    fn test_update_reports_metrics() {
+        let recorder = RecordingRecorder::default();
+        metrics::with_local_recorder(&recorder, || {
+            let mut world = World::new();
+            let entity = world.create_entity();
+            world.add_component(entity, 0i32);
+            world.add_system(IdleSystem);
+            world.update();
+        });
+
+        let names = recorder.names.lock().unwrap();
+        for expected in [
+            "ecs_frames_total",
+            "ecs_entities",
+            "ecs_components",
+            "ecs_frame_diff_size",
+            "ecs_system_duration_seconds",
+            "ecs_system_diff_size",
+        ] {
+            assert!(names.iter().any(|name| name == expected), "missing {}", expected);
+        }
+    }
+}
@@ -0,0 +1,27 @@
+//! Helpers shared by the unit tests.
+
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+/// Sink keeping a log in memory, shared with the test. Clones write to the
+/// same buffer, also from the thread of a background log writer.
+#[derive(Clone, Default)]
+pub(crate) struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl SharedBuffer {
+    /// Get what was written so far
+    pub(crate) fn contents(&self) -> String {
+        String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,432 @@
+//! Test helpers: deterministic test worlds and golden replay regression tests.
+//!
+//! A `TestWorld` reads the time from a mock clock that advances by a fixed
+//! tick per frame, records a fixed seed in its replay metadata and steps
+//! updates on demand, so simulation tests need only a few lines:
+//!
+//! ```ignore
+//! let mut world = TestWorld::new();
+//! let player = world.create_entity();
+//! world.add_component(player, Position { x: 0, y: 0 });
+//! world.add_component(player, Velocity { dx: 1, dy: 0 });
+//! world.add_system(MovementSystem);
+//! world.step(3);
+//! world.assert_component_eq(player, Position { x: 3, y: 0 });
+//! ```
+//!
+//! A golden log is a replay log recorded from a known good build. Tests run
+//! the same simulation again and compare it with the golden log frame by
+//! frame, so any change in behavior fails with the frames and components that
+//! drifted:
+//!
+//! ```ignore
+//! #[test]
+//! fn simulation_matches_golden_log() {
+//!     let world = my_game::initial_world(42);
+//!     assert_replay_matches(world, |world| world.add_system(MovementSystem), "tests/golden/movement.log");
+//! }
+//! ```
+//!
+//! Golden logs are created with `record_golden_log`. Running the tests with
+//! `RUST_ECS_UPDATE_GOLDEN=1` rewrites the existing golden logs after an
+//! intended change in behavior. Simulations must be deterministic - seed
+//! random number generators and avoid wall clock time.
+
+use crate::clock::MockClock;
+use crate::replay_analysis::{compare_histories, parse_replay_log_str, write_replay_log};
+use crate::replay_player::DEFAULT_FRAME_INTERVAL;
+use crate::snapshot::WorldSnapshot;
+use crate::{Entity, ReplayMetadata, World, WorldUpdateHistory};
+use std::fmt::Write as _;
+use std::time::Duration;
+
+/// Environment variable that makes `assert_replay_matches` rewrite golden logs
+pub const UPDATE_GOLDEN_VAR: &str = "RUST_ECS_UPDATE_GOLDEN";
+
+/// Seed of a `TestWorld` created with `TestWorld::new`
+pub const DEFAULT_TEST_SEED: u64 = 0;
+
+/// World wrapper for deterministic tests.
+///
+/// The world's time comes from a mock clock that starts at zero and advances
+/// by `tick` after every frame. The seed is stored in the replay metadata,
+/// where plugins such as `GamePlugin` pick it up when they have no seed of
+/// their own. All `World` methods are available through `Deref`.
+pub struct TestWorld {
+    world: World,
+    clock: MockClock,
+    seed: u64,
+    tick: Duration,
+    initialized: bool,
+}
+
+impl Default for TestWorld {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TestWorld {
+    /// Create an empty test world with the default seed
+    pub fn new() -> Self {
+        Self::with_seed(DEFAULT_TEST_SEED)
+    }
+
+    /// Create an empty test world with the given seed
+    pub fn with_seed(seed: u64) -> Self {
+        let clock = MockClock::new();
+        let mut world = World::new();
+        world.set_mock_clock(clock.clone());
+        world.set_replay_metadata(ReplayMetadata {
+            seed: Some(seed),
+            ..ReplayMetadata::default()
+        });
+        Self {
+            world,
+            clock,
+            seed,
+            tick: DEFAULT_FRAME_INTERVAL,
+            initialized: false,
+        }
+    }
+
+    /// Set the time the clock advances by per frame
+    pub fn with_tick(mut self, tick: Duration) -> Self {
+        self.tick = tick;
+        self
+    }
+
+    /// Get the seed of the world
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Get the mock clock, e.g. to advance it within a frame
+    pub fn clock(&self) -> &MockClock {
+        &self.clock
+    }
+
+    /// Take the wrapped world
+    pub fn into_world(self) -> World {
+        self.world
+    }
+
+    /// Run `frames` updates, advancing the clock by the tick after each.
+    /// The first step initializes the systems added so far.
+    pub fn step(&mut self, frames: usize) -> &mut Self {
+        if !self.initialized {
+            self.world.initialize_systems();
+            self.initialized = true;
+        }
+        for _ in 0..frames {
+            self.world.update();
+            self.clock.advance(self.tick);
+        }
+        self
+    }
+
+    /// Panic unless the entity has a component equal to `expected`
+    #[track_caller]
+    pub fn assert_component_eq<T: PartialEq + std::fmt::Debug + 'static>(&self, entity: Entity, expected: T) {
+        match self.world.get_component::<T>(entity) {
+            Some(actual) => assert!(
+                *actual == expected,
+                "{} {} after {} frames: expected {:?}, got {:?}",
+                entity,
+                std::any::type_name::<T>(),
+                self.world.frame_number(),
+                expected,
+                actual
+            ),
+            None => panic!(
+                "{} has no {} after {} frames, expected {:?}",
+                entity,
+                std::any::type_name::<T>(),
+                self.world.frame_number(),
+                expected
+            ),
+        }
+    }
+
+    /// Panic unless the world has `expected` entities
+    #[track_caller]
+    pub fn assert_entity_count(&self, expected: usize) {
+        let actual = self.world.entity_count();
+        assert!(
+            actual == expected,
+            "expected {} entities after {} frames, got {}",
+            expected,
+            self.world.frame_number(),
+            actual
+        );
+    }
+}
+
+impl std::ops::Deref for TestWorld {
+    type Target = World;
+
+    fn deref(&self) -> &World {
+        &self.world
+    }
+}
+
+impl std::ops::DerefMut for TestWorld {
+    fn deref_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+}
+
+/// Add the systems to `world` and record its initial state and `frames` updates
+pub fn record_session(mut world: World, systems: impl FnOnce(&mut World), frames: usize) -> WorldUpdateHistory {
+    systems(&mut world);
+    world.initialize_systems();
+
+    let mut history = WorldUpdateHistory::new();
+    history.set_metadata(world.replay_metadata().clone());
+    history.set_initial_state(world.snapshot());
+    let start = world.get_update_history().len();
+    for _ in 0..frames {
+        world.update();
+    }
+    for update in &world.get_update_history().updates()[start..] {
+        history.record(update.clone());
+    }
+    history
+}
+
+/// Write a history as a replay log string
+fn to_log(history: &WorldUpdateHistory) -> String {
+    let mut log = Vec::new();
+    write_replay_log(history, &mut log).expect("writing to memory does not fail");
+    String::from_utf8(log).expect("replay logs are UTF-8")
+}
+
+/// Entries of a snapshot in the replay log format
+fn snapshot_entries(snapshot: Option<&WorldSnapshot>) -> Vec<String> {
+    let Some(snapshot) = snapshot else {
+        return Vec::new();
+    };
+    let operations = snapshot.diff.world_operations().iter().map(|operation| operation.to_string());
+    let changes = snapshot.diff.component_changes().iter().map(|change| change.to_string());
+    let resources = snapshot
+        .resources
+        .iter()
+        .map(|(type_name, data)| format!("RESOURCE {} {}", type_name, data));
+    operations.chain(changes).chain(resources).collect()
+}
+
+/// Split an ADD or MOD entry into the changed component and its data
+fn component_change(entry: &str) -> Option<(&str, &str)> {
+    if !entry.starts_with("ADD ") && !entry.starts_with("MOD ") {
+        return None;
+    }
+    let end = entry.find(") ")? + 1;
+    let (type_name, data) = entry[end..].trim_start().split_once(' ')?;
+    Some((&entry[..end + 1 + type_name.len()], data))
+}
+
+/// Describe entries only in the golden log (`expected`) or only in the run
+/// (`actual`), pairing changes of the same component
+fn describe_entries(report: &mut String, expected: &[String], actual: &[String]) {
+    let mut actual: Vec<&String> = actual.iter().collect();
+    for entry in expected {
+        let paired = component_change(entry).and_then(|(component, expected_data)| {
+            let index = actual
+                .iter()
+                .position(|other| component_change(other).is_some_and(|(other, _)| other == component))?;
+            let (_, actual_data) = component_change(actual.remove(index))?;
+            Some((component, expected_data, actual_data))
+        });
+        match paired {
+            Some((component, expected_data, actual_data)) => {
+                let _ = writeln!(report, "  {}: expected {}, got {}", component, expected_data, actual_data);
+            }
+            None => {
+                let _ = writeln!(report, "  - {}", entry);
+            }
+        }
+    }
+    for entry in actual {
+        let _ = writeln!(report, "  + {}", entry);
+    }
+}
+
+/// Compare a run with a golden log, returning a report of the differences.
+///
+/// Both are compared in the replay log format. Lines starting with `-` are
+/// only in the golden log, lines starting with `+` only in the run.
+pub fn diff_replays(golden: &WorldUpdateHistory, actual: &WorldUpdateHistory) -> Option<String> {
+    let actual = parse_replay_log_str(&to_log(actual));
+    let mut report = String::new();
+
+    let mut expected = snapshot_entries(golden.initial_state());
+    let mut only_in_run = Vec::new();
+    for entry in snapshot_entries(actual.initial_state()) {
+        match expected.iter().position(|e| *e == entry) {
+            Some(index) => {
+                expected.remove(index);
+            }
+            None => only_in_run.push(entry),
+        }
+    }
+    if !expected.is_empty() || !only_in_run.is_empty() {
+        report.push_str("initial state:\n");
+        describe_entries(&mut report, &expected, &only_in_run);
+    }
+
+    let differences = compare_histories(golden, &actual);
+    for difference in &differences {
+        let _ = writeln!(report, "frame {}:", difference.frame);
+        describe_entries(&mut report, &difference.only_in_first, &difference.only_in_second);
+    }
+    if golden.len() != actual.len() {
+        let _ = writeln!(report, "golden log has {} frames, the run has {}", golden.len(), actual.len());
+    }
+
+    if report.is_empty() {
+        None
+    } else {
+        Some(format!(
+            "{} of {} frames differ\n{}",
+            differences.len(),
+            golden.len().max(actual.len()),
+            report
+        ))
+    }
+}
+
+/// Run the simulation and write it as a golden log
+#[cfg(feature = "fs")]
+pub fn record_golden_log(
+    initial_state: World,
+    systems: impl FnOnce(&mut World),
+    frames: usize,
+    golden_log: impl AsRef<std::path::Path>,
+) -> std::io::Result<()> {
+    let golden_log = golden_log.as_ref();
+    if let Some(directory) = golden_log.parent() {
+        std::fs::create_dir_all(directory)?;
+    }
+    let history = record_session(initial_state, systems, frames);
+    std::fs::write(golden_log, to_log(&history))
+}
+
+/// Run the simulation for as many frames as the golden log has and panic
+/// with a frame and component level report if it behaves differently.
+///
+/// With `RUST_ECS_UPDATE_GOLDEN` set the golden log is rewritten instead.
+#[cfg(feature = "fs")]
+#[track_caller]
+pub fn assert_replay_matches(
+    initial_state: World,
+    systems: impl FnOnce(&mut World),
+    golden_log: impl AsRef<std::path::Path>,
+) {
+    let golden_log = golden_log.as_ref();
+    let golden = match std::fs::read_to_string(golden_log) {
+        Ok(contents) => parse_replay_log_str(&contents),
+        Err(e) => panic!(
+            "Failed to read golden log {}: {}; record it with record_golden_log",
+            golden_log.display(),
+            e
+        ),
+    };
+
+    if std::env::var_os(UPDATE_GOLDEN_VAR).is_some() {
+        if let Err(e) = record_golden_log(initial_state, systems, golden.len(), golden_log) {
+            panic!("Failed to update golden log {}: {}", golden_log.display(), e);
+        }
+        ecs_info!("Updated golden log {}", golden_log.display());
+        return;
+    }
+
+    let actual = record_session(initial_state, systems, golden.len());
+    if let Some(report) = diff_replays(&golden, &actual) {
+        panic!(
+            "Replay drifted from golden log {}: {}(set {}=1 to accept the new behavior)",
+            golden_log.display(),
+            report,
+            UPDATE_GOLDEN_VAR
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Out, System, WorldView};
+
+    struct GrowSystem {
+        step: u32,
+    }
+
+    impl System for GrowSystem {
+        type InComponents = ();
+        type OutComponents = (u32,);
+
+        fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+        fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+            let values: Vec<(Entity, u32)> = world
+                .query_components::<(Out<u32>,)>()
+                .into_iter()
+                .map(|(entity, value)| {
+                    *value += self.step;
+                    (entity, *value)
+                })
+                .collect();
+            for (entity, new) in values {
+                world.record_component_modification(entity, &(new - self.step), &new);
+            }
+        }
+
+        fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    }
+
+    fn session(step: u32) -> WorldUpdateHistory {
+        let mut world = World::new();
+        let entity = world.create_entity();
+        world.add_component(entity, 0u32);
+        record_session(world, |world| world.add_system(GrowSystem { step }), 3)
+    }
+
+    #[test]
+    fn test_identical_runs_match() {
+        let golden = parse_replay_log_str(&to_log(&session(1)));
+        assert_eq!(golden.len(), 3);
+        assert_eq!(diff_replays(&golden, &session(1)), None);
+    }
+
+    #[test]
+    fn test_drift_is_reported_per_component() {
+        let golden = parse_replay_log_str(&to_log(&session(1)));
+        let report = diff_replays(&golden, &session(2)).unwrap();
+        assert!(report.starts_with("3 of 3 frames differ"), "{}", report);
+        assert!(report.contains("frame 0:\n  MOD Entity(0, 0) u32: expected 1, got 2\n"), "{}", report);
+    }
+
+    #[test]
+    fn test_test_world_steps_with_mock_time() {
+        let mut world = TestWorld::with_seed(3).with_tick(Duration::from_millis(20));
+        let entity = world.create_entity();
+        world.add_component(entity, 0u32);
+        world.add_system(GrowSystem { step: 2 });
+        world.annotate_replay("start");
+
+        world.step(3).assert_component_eq(entity, 6u32);
+        world.assert_entity_count(1);
+        assert_eq!(world.now(), Duration::from_millis(60));
+        assert_eq!(world.replay_metadata().seed, Some(3));
+        assert_eq!(world.get_update_history().annotations()[0].1.elapsed, Duration::ZERO);
+    }
+
+    #[test]
+    #[should_panic(expected = "Entity(0, 0) u32 after 1 frames: expected 5, got 1")]
+    fn test_component_mismatch_is_reported() {
+        let mut world = TestWorld::new();
+        let entity = world.create_entity();
+        world.add_component(entity, 0u32);
+        world.add_system(GrowSystem { step: 1 });
+        world.step(1).assert_component_eq(entity, 5u32);
+    }
+}
@@ -0,0 +1,460 @@
+//! Time-travel debugging support.
+//!
+//! Watches let `World::debug_update` pause in the middle of a frame as soon as a
+//! system changes a watched component, and rewind tracking records the inverse
+//! of every frame's component diffs so `World::rewind` can step the world back.
+
+use crate::{short_type_name, ChangeTick, Diff, Entity, World};
+use std::any::TypeId;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// Number of frames kept for rewinding unless configured otherwise
+pub const DEFAULT_REWIND_CAPACITY: usize = 256;
+
+/// Selects the entities a per-entity feature (such as a watch) applies to
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum EntityFilter {
+    /// Every entity in the world
+    #[default]
+    All,
+    /// Only the listed entities
+    Entities(Vec<Entity>),
+}
+
+impl EntityFilter {
+    /// Check whether the filter accepts the given entity
+    pub fn matches(&self, entity: Entity) -> bool {
+        match self {
+            EntityFilter::All => true,
+            EntityFilter::Entities(entities) => entities.contains(&entity),
+        }
+    }
+}
+
+impl From<Entity> for EntityFilter {
+    fn from(entity: Entity) -> Self {
+        EntityFilter::Entities(vec![entity])
+    }
+}
+
+impl From<Vec<Entity>> for EntityFilter {
+    fn from(entities: Vec<Entity>) -> Self {
+        EntityFilter::Entities(entities)
+    }
+}
+
+/// Identifier of a watch registered with `World::add_watch`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WatchId(usize);
+
+/// A watched component change that paused `World::debug_update`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatchHit {
+    /// The watch that triggered
+    pub watch_id: WatchId,
+    /// Frame number the change happened in
+    pub frame: usize,
+    /// Type name of the system that made the change
+    pub system_name: String,
+    /// Entity whose component changed
+    pub entity: Entity,
+    /// Type name of the changed component
+    pub type_name: String,
+    /// Value before the system ran (None if the component was added)
+    pub old_value: Option<String>,
+    /// Value after the system ran (None if the component was removed)
+    pub new_value: Option<String>,
+}
+
+/// Before and after values of one component between a capture and now
+struct TrackedChange<T> {
+    entity: Entity,
+    old: Option<T>,
+    new: Option<T>,
+}
+
+/// Remembers every component of type T so later state can be compared against it
+struct ComponentTracker<T> {
+    filter: EntityFilter,
+    before: HashMap<Entity, T>,
+    /// Change tick of the last capture; components not written since are still in `before`
+    captured_at: Option<ChangeTick>,
+}
+
+impl<T: Diff + Clone + 'static> ComponentTracker<T> {
+    fn new(filter: EntityFilter) -> Self {
+        Self {
+            filter,
+            before: HashMap::new(),
+            captured_at: None,
+        }
+    }
+
+    /// Components of type T accepted by the filter
+    fn tracked<'w>(&'w self, world: &'w World) -> impl Iterator<Item = (Entity, &'w T)> + 'w {
+        world
+            .components
+            .get(&TypeId::of::<T>())
+            .into_iter()
+            .flatten()
+            .filter(|(entity, _)| self.filter.matches(*entity))
+            .filter_map(|(entity, component)| component.downcast_ref::<T>().map(|c| (entity, c)))
+    }
+
+    /// Check if a component may differ from its captured value
+    fn written_since_capture(&self, world: &World, entity: Entity) -> bool {
+        match self.captured_at {
+            Some(tick) => world.component_changed_since(TypeId::of::<T>(), entity, tick),
+            None => true,
+        }
+    }
+
+    /// Remember the current components, copying only those written since the last capture
+    fn capture(&mut self, world: &World) {
+        let mut present = HashSet::new();
+        let mut updated = Vec::new();
+        for (entity, component) in self.tracked(world) {
+            present.insert(entity);
+            if !self.before.contains_key(&entity) || self.written_since_capture(world, entity) {
+                updated.push((entity, component.clone()));
+            }
+        }
+        self.before.retain(|entity, _| present.contains(entity));
+        self.before.extend(updated);
+        self.captured_at = Some(world.change_tick());
+    }
+
+    /// Changes since the last capture, sorted by entity for stable reporting
+    fn changes(&self, world: &World) -> Vec<TrackedChange<T>> {
+        let mut changes = Vec::new();
+        let mut present = HashSet::new();
+
+        for (entity, new) in self.tracked(world) {
+            present.insert(entity);
+            match self.before.get(&entity) {
+                // Components nobody wrote cannot differ from the capture
+                Some(_) if !self.written_since_capture(world, entity) => {}
+                Some(old) => {
+                    if old.diff(new).is_some() {
+                        changes.push(TrackedChange {
+                            entity,
+                            old: Some(old.clone()),
+                            new: Some(new.clone()),
+                        });
+                    }
+                }
+                None => changes.push(TrackedChange {
+                    entity,
+                    old: None,
+                    new: Some(new.clone()),
+                }),
+            }
+        }
+        for (entity, old) in &self.before {
+            if !present.contains(entity) {
+                changes.push(TrackedChange {
+                    entity: *entity,
+                    old: Some(old.clone()),
+                    new: None,
+                });
+            }
+        }
+
+        changes.sort_by_key(|change| (change.entity.world_index, change.entity.entity_index));
+        changes
+    }
+}
+
+/// Type-erased watch over one component type
+pub(crate) trait Watch {
+    fn capture(&mut self, world: &World);
+    /// Changes since the last capture as (entity, old value, new value) in Debug form
+    fn detect(&self, world: &World) -> Vec<(Entity, Option<String>, Option<String>)>;
+    fn type_name(&self) -> &'static str;
+}
+
+impl<T: Diff + Clone + std::fmt::Debug + 'static> Watch for ComponentTracker<T> {
+    fn capture(&mut self, world: &World) {
+        ComponentTracker::capture(self, world);
+    }
+
+    fn detect(&self, world: &World) -> Vec<(Entity, Option<String>, Option<String>)> {
+        self.changes(world)
+            .into_iter()
+            .map(|change| {
+                (
+                    change.entity,
+                    change.old.map(|old| format!("{:?}", old)),
+                    change.new.map(|new| format!("{:?}", new)),
+                )
+            })
+            .collect()
+    }
+
+    fn type_name(&self) -> &'static str {
+        short_type_name::<T>()
+    }
+}
+
+/// Type-erased rewind tracker over one component type
+pub(crate) trait RewindTracker {
+    fn capture(&mut self, world: &World);
+    /// Records that undo every change since the last capture
+    fn undo_records(&self, world: &World) -> Vec<Box<dyn UndoRecord>>;
+}
+
+impl<T: Diff + Clone + 'static> RewindTracker for ComponentTracker<T> {
+    fn capture(&mut self, world: &World) {
+        ComponentTracker::capture(self, world);
+    }
+
+    fn undo_records(&self, world: &World) -> Vec<Box<dyn UndoRecord>> {
+        self.changes(world)
+            .into_iter()
+            .filter_map(|change| {
+                let undo = match (change.old, change.new) {
+                    (Some(old), Some(new)) => {
+                        ComponentUndo::Revert(old.invert_diff(&old.diff(&new)?)?)
+                    }
+                    (Some(old), None) => ComponentUndo::Restore(old),
+                    (None, Some(_)) => ComponentUndo::Remove,
+                    (None, None) => return None,
+                };
+                Some(Box::new((change.entity, undo)) as Box<dyn UndoRecord>)
+            })
+            .collect()
+    }
+}
+
+/// Reverses a single recorded component change
+pub(crate) trait UndoRecord {
+    fn undo(&self, world: &mut World);
+}
+
+enum ComponentUndo<T: Diff> {
+    /// Apply the inverse of the diff the frame produced
+    Revert(T::Diff),
+    /// The component was added during the frame
+    Remove,
+    /// The component was removed during the frame
+    Restore(T),
+}
+
+impl<T: Diff + Clone + 'static> UndoRecord for (Entity, ComponentUndo<T>) {
+    fn undo(&self, world: &mut World) {
+        let (entity, undo) = self;
+        match undo {
+            ComponentUndo::Revert(inverse) => {
+                if let Some(component) = world.get_component_mut::<T>(*entity) {
+                    component.apply_diff(inverse);
+                }
+            }
+            ComponentUndo::Remove => {
+                world.remove_component::<T>(*entity);
+            }
+            ComponentUndo::Restore(old) => {
+                world.remove_component::<T>(*entity);
+                world.add_component(*entity, old.clone());
+            }
+        }
+    }
+}
+
+/// Everything needed to undo one completed frame
+pub(crate) struct RewindFrame {
+    history_len: usize,
+    entities: Vec<Entity>,
+    next_entity_id: usize,
+    undo: Vec<Box<dyn UndoRecord>>,
+}
+
+/// State captured when a frame starts, turned into a `RewindFrame` when it ends
+pub(crate) struct RewindCapture {
+    history_len: usize,
+    entities: Vec<Entity>,
+    next_entity_id: usize,
+}
+
+/// A frame that `World::debug_update` paused in the middle of
+pub(crate) struct FrameInProgress {
+    pub(crate) diff: crate::WorldUpdateDiff,
+    pub(crate) next_system: usize,
+    pub(crate) pending_hits: VecDeque<WatchHit>,
+    pub(crate) rewind: Option<RewindCapture>,
+    /// Per-system timings, only collected when a frame budget is set
+    pub(crate) system_timings: Vec<crate::SystemTiming>,
+    /// Names of the systems that reported an error, with their messages
+    pub(crate) system_errors: Vec<(&'static str, String)>,
+}
+
+impl World {
+    /// Watch components of type T on the entities accepted by `filter`.
+    ///
+    /// `debug_update` pauses right after the system that changes a watched
+    /// component and returns a `WatchHit`. Watched types are also tracked for
+    /// rewinding.
+    pub fn add_watch<T: Diff + Clone + std::fmt::Debug + 'static>(
+        &mut self,
+        filter: impl Into<EntityFilter>,
+    ) -> WatchId {
+        let id = WatchId(self.next_watch_id);
+        self.next_watch_id += 1;
+        self.watches
+            .push((id, Box::new(ComponentTracker::<T>::new(filter.into()))));
+        self.track_rewind::<T>();
+        id
+    }
+
+    /// Remove a previously added watch, returning whether it existed
+    pub fn remove_watch(&mut self, id: WatchId) -> bool {
+        let initial_count = self.watches.len();
+        self.watches.retain(|(watch_id, _)| *watch_id != id);
+        self.watches.len() < initial_count
+    }
+
+    /// Record inverse diffs of component type T every frame so `rewind` can undo them
+    pub fn track_rewind<T: Diff + Clone + 'static>(&mut self) {
+        let type_id = TypeId::of::<T>();
+        if !self.rewind_trackers.iter().any(|(id, _)| *id == type_id) {
+            self.rewind_trackers
+                .push((type_id, Box::new(ComponentTracker::<T>::new(EntityFilter::All))));
+        }
+    }
+
+    /// Set how many frames are kept for rewinding (oldest frames are dropped first)
+    pub fn set_rewind_capacity(&mut self, frames: usize) {
+        self.rewind_capacity = frames;
+        while self.rewind_frames.len() > frames {
+            self.rewind_frames.pop_front();
+        }
+    }
+
+    /// Number of completed frames that can currently be rewound
+    pub fn rewindable_frames(&self) -> usize {
+        self.rewind_frames.len()
+    }
+
+    /// Check if `debug_update` stopped in the middle of a frame
+    pub fn is_paused_mid_frame(&self) -> bool {
+        self.frame_in_progress.is_some()
+    }
+
+    /// Step the world back by up to `n_frames` completed frames.
+    ///
+    /// Tracked component types are restored by applying inverted diffs, the
+    /// entity list is restored and the rewound frames are dropped from the
+    /// update history. A frame paused by `debug_update` is undone first without
+    /// counting towards `n_frames`. Returns the number of frames rewound.
+    pub fn rewind(&mut self, n_frames: usize) -> usize {
+        if let Some(frame) = self.frame_in_progress.take() {
+            if let Some(capture) = frame.rewind {
+                let partial = self.complete_rewind_frame(capture);
+                self.undo_rewind_frame(partial);
+            }
+        }
+
+        let mut rewound = 0;
+        while rewound < n_frames {
+            let Some(frame) = self.rewind_frames.pop_back() else {
+                break;
+            };
+            self.undo_rewind_frame(frame);
+            self.frame_number = self.frame_number.saturating_sub(1);
+            rewound += 1;
+        }
+
+        // Trackers compare against the state at the start of the next frame
+        self.capture_rewind_trackers();
+        rewound
+    }
+
+    pub(crate) fn begin_rewind_capture(&mut self) -> Option<RewindCapture> {
+        if self.rewind_trackers.is_empty() {
+            return None;
+        }
+        self.capture_rewind_trackers();
+        Some(RewindCapture {
+            history_len: self.world_update_history.len(),
+            entities: self.entities.clone(),
+            next_entity_id: self.next_entity_id,
+        })
+    }
+
+    pub(crate) fn finish_rewind_capture(&mut self, capture: RewindCapture) {
+        let frame = self.complete_rewind_frame(capture);
+        self.rewind_frames.push_back(frame);
+        while self.rewind_frames.len() > self.rewind_capacity {
+            self.rewind_frames.pop_front();
+        }
+    }
+
+    fn capture_rewind_trackers(&mut self) {
+        let mut trackers = std::mem::take(&mut self.rewind_trackers);
+        for (_, tracker) in &mut trackers {
+            tracker.capture(self);
+        }
+        self.rewind_trackers = trackers;
+    }
+
+    fn complete_rewind_frame(&self, capture: RewindCapture) -> RewindFrame {
+        let undo = self
+            .rewind_trackers
+            .iter()
+            .flat_map(|(_, tracker)| tracker.undo_records(self))
+            .collect();
+        RewindFrame {
+            history_len: capture.history_len,
+            entities: capture.entities,
+            next_entity_id: capture.next_entity_id,
+            undo,
+        }
+    }
+
+    fn undo_rewind_frame(&mut self, frame: RewindFrame) {
+        for record in frame.undo.iter().rev() {
+            record.undo(self);
+        }
+
+        // Drop entities created during the frame along with their components
+        let created: Vec<Entity> = self
+            .entities
+            .iter()
+            .filter(|entity| !frame.entities.contains(entity))
+            .copied()
+            .collect();
+        for entity in created {
+            self.remove_entity(entity);
+        }
+        self.entities = frame.entities;
+        self.next_entity_id = frame.next_entity_id;
+        self.world_update_history.truncate(frame.history_len);
+    }
+
+    /// Capture every watch before a system runs
+    pub(crate) fn capture_watches(&mut self) {
+        let mut watches = std::mem::take(&mut self.watches);
+        for (_, watch) in &mut watches {
+            watch.capture(self);
+        }
+        self.watches = watches;
+    }
+
+    /// Collect watch hits caused by the system that just ran
+    pub(crate) fn detect_watch_hits(&self, system_name: &str) -> Vec<WatchHit> {
+        let mut hits = Vec::new();
+        for (watch_id, watch) in &self.watches {
+            for (entity, old_value, new_value) in watch.detect(self) {
+                hits.push(WatchHit {
+                    watch_id: *watch_id,
+                    frame: self.frame_number,
+                    system_name: system_name.to_string(),
+                    entity,
+                    type_name: watch.type_name().to_string(),
+                    old_value,
+                    new_value,
+                });
+            }
+        }
+        hits
+    }
+}
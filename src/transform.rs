@@ -0,0 +1,215 @@
+//! Positions relative to a parent entity.
+//!
+//! An entity with a `Parent` component is attached to the parent: its
+//! `LocalPosition` is an offset from the parent, and
+//! `TransformPropagationSystem` computes the world-space `GlobalPosition` of
+//! every entity with a `LocalPosition` down the hierarchy. A tool carried by an
+//! actor follows the actor without any system moving it:
+//!
+//! ```ignore
+//! world.add_component(tool, LocalPosition { x: 0.5, y: 0.0 });
+//! world.add_component(tool, Parent { entity: actor });
+//! world.add_system(TransformPropagationSystem);
+//! ```
+//!
+//! Entities without a parent, or whose parent has no position, are roots and
+//! their global position equals their local one. Add the system after the
+//! systems that move entities, so global positions are up to date in the same
+//! frame.
+
+use crate::{Diff, Entity, In, System, World, WorldView};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// Entity this entity is attached to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Diff)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Parent {
+    pub entity: Entity,
+}
+
+/// Position relative to the parent, or to the world for entities without one
+#[derive(Debug, Clone, Copy, PartialEq, Default, Diff)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LocalPosition {
+    pub x: f32,
+    pub y: f32,
+}
+
+/// World-space position computed by `TransformPropagationSystem`
+#[derive(Debug, Clone, Copy, PartialEq, Default, Diff)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GlobalPosition {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl World {
+    /// Get the entities attached to `entity` with a `Parent` component, in entity order
+    pub fn children(&self, entity: Entity) -> Vec<Entity> {
+        self.entities_with_component::<Parent>()
+            .into_iter()
+            .filter(|child| self.get_component::<Parent>(*child).is_some_and(|parent| parent.entity == entity))
+            .collect()
+    }
+}
+
+/// Computes the `GlobalPosition` of every entity with a `LocalPosition`,
+/// adding the component where it is missing
+pub struct TransformPropagationSystem;
+
+impl System for TransformPropagationSystem {
+    type InComponents = (LocalPosition, Parent);
+    type OutComponents = (GlobalPosition,);
+
+    fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+    fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+        let locals: HashMap<Entity, LocalPosition> = world
+            .query_components::<(In<LocalPosition>,)>()
+            .into_iter()
+            .map(|(entity, local)| (entity, *local))
+            .collect();
+        let parents: HashMap<Entity, Entity> = world
+            .query_components::<(In<Parent>,)>()
+            .into_iter()
+            .map(|(entity, parent)| (entity, parent.entity))
+            .collect();
+        let globals = propagate(&locals, &parents);
+
+        let mut added = Vec::new();
+        let mut changes = Vec::new();
+        for (entity, global) in globals {
+            match world.get_component_mut::<GlobalPosition>(entity) {
+                Some(current) => {
+                    if *current != global {
+                        changes.push((entity, *current, global));
+                        *current = global;
+                    }
+                }
+                None => added.push((entity, global)),
+            }
+        }
+        for (entity, old, new) in changes {
+            world.record_component_modification(entity, &old, &new);
+        }
+        for (entity, global) in added {
+            world.add_component(entity, global);
+            world.record_component_addition(entity, &global);
+        }
+    }
+
+    fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+}
+
+/// Compute the global positions of the entities with a local position
+fn propagate(locals: &HashMap<Entity, LocalPosition>, parents: &HashMap<Entity, Entity>) -> BTreeMap<Entity, GlobalPosition> {
+    let mut globals = BTreeMap::new();
+    let mut entities: Vec<Entity> = locals.keys().copied().collect();
+    entities.sort();
+    for entity in entities {
+        if globals.contains_key(&entity) {
+            continue;
+        }
+        // Walk up to a root or an entity already computed, then back down
+        let mut chain = vec![entity];
+        let mut visited = HashSet::from([entity]);
+        let mut origin = GlobalPosition::default();
+        while let Some(parent) = parents.get(chain.last().unwrap()) {
+            if let Some(global) = globals.get(parent) {
+                origin = *global;
+                break;
+            }
+            if !locals.contains_key(parent) {
+                break;
+            }
+            if !visited.insert(*parent) {
+                ecs_warn!("Parent cycle through {}, treating it as a root", parent);
+                break;
+            }
+            chain.push(*parent);
+        }
+        for entity in chain.into_iter().rev() {
+            let local = locals[&entity];
+            origin = GlobalPosition {
+                x: origin.x + local.x,
+                y: origin.y + local.y,
+            };
+            globals.insert(entity, origin);
+        }
+    }
+    globals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Moves every root one unit right
+    struct MoveRootsSystem;
+
+    impl System for MoveRootsSystem {
+        type InComponents = (Parent,);
+        type OutComponents = (LocalPosition,);
+
+        fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+        fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+            let roots: Vec<Entity> = world
+                .query_components::<(In<LocalPosition>, crate::Without<Parent>)>()
+                .into_iter()
+                .map(|(entity, _)| entity)
+                .collect();
+            for entity in roots {
+                world.get_component_mut::<LocalPosition>(entity).unwrap().x += 1.0;
+            }
+        }
+
+        fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    }
+
+    fn spawn(world: &mut World, x: f32, parent: Option<Entity>) -> Entity {
+        let entity = world.create_entity();
+        world.add_component(entity, LocalPosition { x, y: 1.0 });
+        if let Some(parent) = parent {
+            world.add_component(entity, Parent { entity: parent });
+        }
+        entity
+    }
+
+    #[test]
+    fn test_children_follow_their_parents() {
+        let mut world = World::new();
+        // Created before its parent, so the hierarchy is not in entity order
+        let gem = world.create_entity();
+        let actor = spawn(&mut world, 10.0, None);
+        let tool = spawn(&mut world, 0.5, Some(actor));
+        world.add_component(gem, LocalPosition { x: 0.25, y: 1.0 });
+        world.add_component(gem, Parent { entity: tool });
+        world.add_system(MoveRootsSystem);
+        world.add_system(TransformPropagationSystem);
+
+        world.update();
+        assert_eq!(world.children(actor), vec![tool]);
+        assert_eq!(world.get_component::<GlobalPosition>(actor), Some(&GlobalPosition { x: 11.0, y: 1.0 }));
+        assert_eq!(world.get_component::<GlobalPosition>(tool), Some(&GlobalPosition { x: 11.5, y: 2.0 }));
+        assert_eq!(world.get_component::<GlobalPosition>(gem), Some(&GlobalPosition { x: 11.75, y: 3.0 }));
+
+        world.update();
+        assert_eq!(world.get_component::<GlobalPosition>(gem), Some(&GlobalPosition { x: 12.75, y: 3.0 }));
+    }
+
+    #[test]
+    fn test_parent_cycles_are_treated_as_roots() {
+        let mut locals = HashMap::new();
+        let mut parents = HashMap::new();
+        let (a, b) = (Entity::new(0, 0), Entity::new(0, 1));
+        locals.insert(a, LocalPosition { x: 1.0, y: 0.0 });
+        locals.insert(b, LocalPosition { x: 2.0, y: 0.0 });
+        parents.insert(a, b);
+        parents.insert(b, a);
+
+        let globals = propagate(&locals, &parents);
+        assert_eq!(globals[&b], GlobalPosition { x: 2.0, y: 0.0 });
+        assert_eq!(globals[&a], GlobalPosition { x: 3.0, y: 0.0 });
+    }
+}
@@ -0,0 +1,368 @@
+//! Versioned component layouts, so older replays and snapshots stay readable.
+//!
+//! Replay logs and snapshots store components in the serialized layout of the
+//! build that recorded them. When a component struct changes, its version is
+//! raised and a migration upgrades values of the previous layout:
+//!
+//! ```ignore
+//! // Version 1 stored `Position { x, y }`, version 2 stores `Position { pos: (x, y) }`
+//! world.register_replay_component::<Position>();
+//! world.set_component_version::<Position>(2);
+//! world.add_component_migration::<Position>(1, |value| {
+//!     let Value::Map(mut fields) = value else {
+//!         return Err("expected a map".to_string());
+//!     };
+//!     let x = fields.remove("x").unwrap_or(Value::Float(0.0));
+//!     let y = fields.remove("y").unwrap_or(Value::Float(0.0));
+//!     Ok(Value::Map([("pos".to_string(), Value::List(vec![x, y]))].into()))
+//! });
+//! ```
+//!
+//! Versions above 1 are written to the header of replay logs as
+//! `# Component Version: Position 2` and stored in snapshots. Logs and
+//! snapshots without them were recorded with version 1 of every type.
+//! `ReplayPlayer` applies histories with the versions they were recorded
+//! with; other replays set them with `set_replayed_component_versions`.
+//!
+//! Outdated additions are decoded into a `Value`, migrated step by step and
+//! built with `Reflect::from_value`. Outdated diffs are applied to the value
+//! in the recorded layout, which is migrated again afterwards.
+
+#[cfg(feature = "serde")]
+use crate::reflect::ComponentRegistration;
+use crate::reflect::{Migration, Reflect, Value};
+use crate::{Entity, World};
+use std::collections::{BTreeMap, HashMap};
+
+/// Component versions of the data a world applies
+#[derive(Default)]
+pub(crate) struct ReplayVersions {
+    /// Versions the data was recorded with, None if they are the registered ones
+    versions: Option<BTreeMap<String, u32>>,
+    /// Values of components added from outdated data, in their recorded
+    /// layout, by entity and short type name
+    recorded: HashMap<(Entity, String), Value>,
+}
+
+impl ReplayVersions {
+    /// Forget the recorded values of an entity's components
+    pub(crate) fn forget_entity(&mut self, entity: Entity) {
+        if !self.recorded.is_empty() {
+            self.recorded.retain(|(owner, _), _| *owner != entity);
+        }
+    }
+
+    /// Forget the recorded value of one component
+    pub(crate) fn forget(&mut self, entity: Entity, type_name: &str) {
+        if !self.recorded.is_empty() {
+            self.recorded.remove(&(entity, type_name.to_string()));
+        }
+    }
+}
+
+/// Component versions of snapshots saved before versions were stored
+#[cfg(feature = "serde")]
+pub(crate) fn unversioned() -> Option<BTreeMap<String, u32>> {
+    Some(BTreeMap::new())
+}
+
+impl World {
+    /// Set the version of the serialized layout of `T`, registering it for
+    /// reflection if needed. Types start at version 1; raise it whenever
+    /// the layout changes and add a migration from the previous version.
+    pub fn set_component_version<T: Reflect>(&mut self, version: u32) {
+        assert!(version >= 1, "component versions start at 1");
+        self.component_registry.register::<T>();
+        self.component_registry
+            .set_version(std::any::TypeId::of::<T>(), version);
+        if self.replay_logger.is_some() {
+            ecs_warn!("Component version set after replay logging was enabled; it is not in the current log");
+        }
+        let mut metadata = self.world_update_history.metadata().clone();
+        metadata.component_versions = self.component_versions();
+        self.world_update_history.set_metadata(metadata);
+    }
+
+    /// Add the migration upgrading values of `T` recorded with `from_version`
+    /// to the layout of the next version
+    pub fn add_component_migration<T: Reflect>(&mut self, from_version: u32, migration: Migration) {
+        self.component_registry.register::<T>();
+        self.component_registry
+            .add_migration(std::any::TypeId::of::<T>(), from_version, migration);
+    }
+
+    /// Get the versions of the registered component types above version 1,
+    /// by short type name
+    pub fn component_versions(&self) -> BTreeMap<String, u32> {
+        self.component_registry
+            .iter()
+            .filter(|registration| registration.version() > 1)
+            .map(|registration| (registration.type_name().to_string(), registration.version()))
+            .collect()
+    }
+
+    /// Set the component versions the diffs applied with `apply_update_diff`
+    /// were recorded with, e.g. from `ReplayMetadata::component_versions`.
+    /// Types not listed are version 1.
+    pub fn set_replayed_component_versions(&mut self, versions: BTreeMap<String, u32>) {
+        self.replace_replayed_component_versions(Some(versions));
+    }
+
+    /// Replace the versions of the applied data, returning the previous ones
+    pub(crate) fn replace_replayed_component_versions(
+        &mut self,
+        versions: Option<BTreeMap<String, u32>>,
+    ) -> Option<BTreeMap<String, u32>> {
+        std::mem::replace(&mut self.replay_versions.versions, versions)
+    }
+
+    /// Get the version applied data of a type was recorded with, if it is
+    /// not the registered one
+    #[cfg(feature = "serde")]
+    pub(crate) fn outdated_version(&self, registration: &ComponentRegistration) -> Option<u32> {
+        let versions = self.replay_versions.versions.as_ref()?;
+        let version = versions.get(registration.type_name()).copied().unwrap_or(1);
+        (version != registration.version()).then_some(version)
+    }
+
+    /// Add a component from data recorded with an older version
+    #[cfg(feature = "serde")]
+    pub(crate) fn apply_outdated_addition(
+        &mut self,
+        entity: Entity,
+        registration: &ComponentRegistration,
+        version: u32,
+        data: &str,
+    ) -> Result<(), String> {
+        let recorded = ron::from_str::<ron::Value>(data)
+            .map(crate::prefab::ron_to_value)
+            .map_err(|e| format!("Invalid {} data '{}': {}", registration.type_name(), data, e))?;
+        self.insert_migrated(entity, registration, version, recorded)
+    }
+
+    /// Apply a diff recorded with an older version to a component added from
+    /// data of that version
+    #[cfg(feature = "serde")]
+    pub(crate) fn apply_outdated_modification(
+        &mut self,
+        entity: Entity,
+        registration: &ComponentRegistration,
+        version: u32,
+        diff: &str,
+    ) -> Result<(), String> {
+        let type_name = registration.type_name();
+        if !self.has_component_type(registration.type_id(), entity) {
+            return Err(format!(
+                "Cannot modify {} component that doesn't exist on entity {:?}",
+                type_name, entity
+            ));
+        }
+        let diff = ron::from_str::<ron::Value>(diff)
+            .map_err(|e| format!("Invalid {} diff '{}': {}", type_name, diff, e))?;
+        let mut recorded = self
+            .replay_versions
+            .recorded
+            .get(&(entity, type_name.to_string()))
+            .cloned()
+            .ok_or_else(|| {
+                format!(
+                    "No {} data of version {} on entity {:?} to apply the diff to",
+                    type_name, version, entity
+                )
+            })?;
+        apply_recorded_diff(&mut recorded, diff);
+        self.insert_migrated(entity, registration, version, recorded)
+    }
+
+    /// Migrate a recorded value, replace the component with it and keep the
+    /// recorded value for later diffs
+    #[cfg(feature = "serde")]
+    fn insert_migrated(
+        &mut self,
+        entity: Entity,
+        registration: &ComponentRegistration,
+        version: u32,
+        recorded: Value,
+    ) -> Result<(), String> {
+        let component = registration.from_value(&registration.migrate(recorded.clone(), version)?)?;
+        self.insert_component_any(entity, registration.type_id(), component);
+        self.replay_versions
+            .recorded
+            .insert((entity, registration.type_name().to_string()), recorded);
+        Ok(())
+    }
+}
+
+/// Apply a RON diff of a derived `Diff` type to a value: fields set to None
+/// are unchanged and nested diffs are applied field by field
+#[cfg(feature = "serde")]
+pub(crate) fn apply_recorded_diff(value: &mut Value, diff: ron::Value) {
+    match (value, diff) {
+        (Value::Map(fields), ron::Value::Map(changes)) => {
+            for (name, change) in changes {
+                let Ok(name) = crate::prefab::ron_key(name) else {
+                    continue;
+                };
+                match change {
+                    ron::Value::Option(None) => {}
+                    ron::Value::Option(Some(change)) => match fields.get_mut(&name) {
+                        Some(field) => apply_recorded_diff(field, *change),
+                        None => {
+                            fields.insert(name, crate::prefab::ron_to_value(*change));
+                        }
+                    },
+                    change => {
+                        fields.insert(name, crate::prefab::ron_to_value(change));
+                    }
+                }
+            }
+        }
+        (value, diff) => *value = crate::prefab::ron_to_value(diff),
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+    use crate::replay_analysis::{parse_replay_log_str, write_replay_log};
+    use crate::{Diff, Out, Reflect, ReplayPlayer, System, WorldView};
+
+    /// Version 1 of the position layout
+    mod v1 {
+        use crate::{Diff, Reflect};
+
+        #[derive(Debug, Clone, PartialEq, Diff, Reflect, serde::Serialize, serde::Deserialize)]
+        pub struct Position {
+            pub x: i32,
+            pub y: i32,
+        }
+    }
+
+    /// Version 2 stores the coordinates under new names and adds a floor
+    #[derive(Debug, Clone, PartialEq, Diff, Reflect, serde::Serialize, serde::Deserialize)]
+    struct Position {
+        column: i32,
+        row: i32,
+        floor: i32,
+    }
+
+    fn migrate_position(value: Value) -> Result<Value, String> {
+        let Value::Map(mut fields) = value else {
+            return Err(format!("expected a map, found {}", value));
+        };
+        let column = fields.remove("x").ok_or("missing x")?;
+        let row = fields.remove("y").ok_or("missing y")?;
+        Ok(Value::Map(
+            [
+                ("column".to_string(), column),
+                ("row".to_string(), row),
+                ("floor".to_string(), Value::Int(0)),
+            ]
+            .into(),
+        ))
+    }
+
+    /// Spawns a position in the first update and moves it right afterwards
+    struct MoveRightSystem;
+
+    impl System for MoveRightSystem {
+        type InComponents = ();
+        type OutComponents = (v1::Position,);
+
+        fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+        fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+            let entities: Vec<Entity> = world
+                .query_components::<(Out<v1::Position>,)>()
+                .into_iter()
+                .map(|(entity, _)| entity)
+                .collect();
+            if entities.is_empty() {
+                let entity = world.create_entity();
+                let position = v1::Position { x: 1, y: 5 };
+                world.record_component_addition(entity, &position);
+                world.add_component(entity, position);
+            }
+            for entity in entities {
+                let old = world.get_component::<v1::Position>(entity).unwrap().clone();
+                let new = v1::Position { x: old.x + 1, ..old.clone() };
+                world.record_component_modification(entity, &old, &new);
+                *world.get_component_mut::<v1::Position>(entity).unwrap() = new;
+            }
+        }
+
+        fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    }
+
+    fn new_world() -> World {
+        let mut world = World::new();
+        world.register_replay_component::<Position>();
+        world.set_component_version::<Position>(2);
+        world.add_component_migration::<Position>(1, migrate_position);
+        world.register_system_factory(|| MoveRightSystem);
+        world
+    }
+
+    #[test]
+    fn test_old_replay_log_is_migrated() {
+        // Record a session with the old layout
+        let mut old = World::new();
+        old.register_replay_component::<v1::Position>();
+        old.add_system(MoveRightSystem);
+        old.update();
+        old.update();
+        old.update();
+        let entity = old.entities_with_component::<v1::Position>()[0];
+        let mut log = Vec::new();
+        write_replay_log(old.get_update_history(), &mut log).unwrap();
+        let log = String::from_utf8(log).unwrap();
+        assert!(!log.contains("Component Version"));
+
+        let history = parse_replay_log_str(&log);
+        let mut player = ReplayPlayer::new(history, new_world).unwrap();
+        player.seek(4);
+        assert_eq!(
+            player.world().get_component::<Position>(entity),
+            Some(&Position { column: 3, row: 5, floor: 0 })
+        );
+    }
+
+    #[test]
+    fn test_versions_are_written_to_the_log() {
+        let mut world = new_world();
+        world.update();
+        assert_eq!(world.component_versions(), BTreeMap::from([("Position".to_string(), 2)]));
+        let mut log = Vec::new();
+        write_replay_log(world.get_update_history(), &mut log).unwrap();
+        let log = String::from_utf8(log).unwrap();
+        assert!(log.contains("# Component Version: Position 2"));
+        assert_eq!(parse_replay_log_str(&log).metadata().component_versions, world.component_versions());
+    }
+
+    #[test]
+    fn test_old_snapshot_is_migrated() {
+        let mut old = World::new();
+        old.register_replay_component::<v1::Position>();
+        let entity = old.create_entity();
+        old.add_component(entity, v1::Position { x: 4, y: 2 });
+        let snapshot = old.snapshot();
+        assert_eq!(snapshot.component_versions, Some(BTreeMap::new()));
+
+        let mut world = new_world();
+        world.restore_snapshot(&snapshot);
+        assert_eq!(
+            world.get_component::<Position>(entity),
+            Some(&Position { column: 4, row: 2, floor: 0 })
+        );
+
+        // Without a migration the data cannot be loaded
+        let mut unmigrated = World::new();
+        unmigrated.register_replay_component::<Position>();
+        unmigrated.set_component_version::<Position>(2);
+        unmigrated.restore_snapshot(&snapshot);
+        assert_eq!(unmigrated.get_component::<Position>(entity), None);
+        let registration = unmigrated.component_registry().get("Position").unwrap();
+        assert!(registration.migrate(Value::Unit, 1).unwrap_err().contains("No migration of Position"));
+        assert!(registration.migrate(Value::Unit, 3).is_err());
+    }
+}
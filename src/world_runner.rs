@@ -0,0 +1,439 @@
+//! Several independent root worlds updated together on worker threads.
+//!
+//! A `WorldRunner` hosts worlds such as the shards of a server or a
+//! simulation world next to a UI world. Each world is built on the worker
+//! thread that keeps it, so worlds need not be `Send`, and `update` runs one
+//! frame of every world, the worlds of different workers in parallel:
+//!
+//! ```ignore
+//! let mut runner = WorldRunner::new(2);
+//! runner.add_world("simulation", build_simulation_world)?;
+//! runner.add_world("ui", build_ui_world)?;
+//! runner.connect::<Score>("simulation", "ui")?;
+//! runner.enable_replay_logging("simulation", ReplayLogConfig::default())?;
+//! for _ in 0..100 {
+//!     runner.update()?;
+//! }
+//! let entities = runner.with_world("ui", |world| world.entity_count())?;
+//! ```
+//!
+//! Connected worlds exchange messages through the `WorldOutbox<M>` and
+//! `WorldInbox<M>` resources. Messages sent during a frame are delivered to
+//! the inbox when the next frame starts, ordered by the world that sent them
+//! in the order the worlds were added, so what a world receives does not
+//! depend on thread timing. Each world keeps its own history and replay
+//! logger.
+
+use crate::World;
+use std::any::Any;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::JoinHandle;
+
+type Build = Box<dyn FnOnce() -> World + Send>;
+type Run = Box<dyn FnOnce(&mut HostedWorld) + Send>;
+type Delivery = Box<dyn FnMut(&mut World)>;
+
+/// Work sent to a worker thread
+enum Job {
+    Host(String, Build),
+    Run(String, Run),
+    /// Deliver the messages sent in the last frame, done before any world updates
+    Deliver(Sender<Result<(), String>>),
+    Update(Sender<Result<(), String>>),
+}
+
+/// A world kept by a worker thread
+struct HostedWorld {
+    world: World,
+    /// Move the messages sent to the world into its inboxes
+    deliveries: Vec<Delivery>,
+}
+
+struct Worker {
+    jobs: Option<Sender<Job>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+/// Updates several independent root worlds on a pool of worker threads
+pub struct WorldRunner {
+    workers: Vec<Worker>,
+    /// Names of the worlds in the order they were added, with their worker
+    worlds: Vec<(String, usize)>,
+    frame: usize,
+}
+
+impl WorldRunner {
+    /// Create a runner with `threads` worker threads (at least one)
+    pub fn new(threads: usize) -> Self {
+        let workers = (0..threads.max(1))
+            .map(|_| {
+                let (jobs, queue) = mpsc::channel();
+                Worker {
+                    jobs: Some(jobs),
+                    thread: Some(std::thread::spawn(move || work(queue))),
+                }
+            })
+            .collect();
+        Self {
+            workers,
+            worlds: Vec::new(),
+            frame: 0,
+        }
+    }
+
+    /// Build a world on the next worker thread and update it with the others
+    pub fn add_world(&mut self, name: &str, build: impl FnOnce() -> World + Send + 'static) -> Result<(), String> {
+        if self.worker_of(name).is_ok() {
+            return Err(format!("A world named {} was already added", name));
+        }
+        let worker = self.worlds.len() % self.workers.len();
+        self.send(worker, Job::Host(name.to_string(), Box::new(build)))?;
+        self.worlds.push((name.to_string(), worker));
+        // Report a world that failed to build now rather than at the next update
+        let built = self.with_world(name, |_| ());
+        if built.is_err() {
+            self.worlds.pop();
+        }
+        built
+    }
+
+    /// Get the names of the worlds in the order they were added
+    pub fn world_names(&self) -> Vec<&str> {
+        self.worlds.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    /// Get the number of frames run with `update`
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    /// Run one frame of every world and wait for all of them to finish
+    pub fn update(&mut self) -> Result<(), String> {
+        let mut errors = self.on_every_worker(Job::Deliver)?;
+        errors.extend(self.on_every_worker(Job::Update)?);
+        self.frame += 1;
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("Frame {} failed: {}", self.frame, errors.join("; ")))
+        }
+    }
+
+    /// Run `f` on a world on its worker thread and return the result
+    pub fn with_world<R: Send + 'static>(
+        &self,
+        name: &str,
+        f: impl FnOnce(&mut World) -> R + Send + 'static,
+    ) -> Result<R, String> {
+        self.run(name, move |hosted| f(&mut hosted.world))
+    }
+
+    /// Let `from` send messages of type M to `to`, see the module documentation
+    pub fn connect<M: Send + 'static>(&mut self, from: &str, to: &str) -> Result<(), String> {
+        let sender_index = self
+            .worlds
+            .iter()
+            .position(|(name, _)| name == from)
+            .ok_or_else(|| format!("No world named {}", from))?;
+        let sender = self.run(to, |hosted| {
+            if hosted.world.resource::<WorldInbox<M>>().is_none() {
+                let (sender, receiver) = mpsc::channel();
+                hosted.world.insert_resource(WorldInbox::<M> {
+                    sender,
+                    receiver,
+                    messages: Vec::new(),
+                });
+                hosted.deliveries.push(Box::new(|world: &mut World| {
+                    if let Some(inbox) = world.resource_mut::<WorldInbox<M>>() {
+                        inbox.deliver();
+                    }
+                }));
+            }
+            hosted.world.resource::<WorldInbox<M>>().map(|inbox| inbox.sender.clone())
+        })?;
+        let sender = sender.ok_or_else(|| format!("World {} has no inbox", to))?;
+        let to = to.to_string();
+        self.with_world(from, move |world| {
+            if world.resource::<WorldOutbox<M>>().is_none() {
+                world.insert_resource(WorldOutbox::<M> {
+                    sender_index,
+                    destinations: Vec::new(),
+                });
+            }
+            if let Some(outbox) = world.resource_mut::<WorldOutbox<M>>() {
+                outbox.destinations.retain(|(name, _)| *name != to);
+                outbox.destinations.push((to, sender));
+            }
+        })
+    }
+
+    /// Enable replay logging of a world, with the world's name appended to
+    /// the file prefix so every world writes its own log
+    #[cfg(feature = "fs")]
+    pub fn enable_replay_logging(&mut self, name: &str, mut config: crate::ReplayLogConfig) -> Result<(), String> {
+        config.file_prefix = format!("{}_{}", config.file_prefix, name);
+        self.with_world(name, move |world| world.enable_replay_logging(config))?
+            .map_err(|e| format!("Failed to enable replay logging of world {}: {}", name, e))
+    }
+
+    fn run<R: Send + 'static>(&self, name: &str, f: impl FnOnce(&mut HostedWorld) -> R + Send + 'static) -> Result<R, String> {
+        let worker = self.worker_of(name)?;
+        let (result, receiver) = mpsc::channel();
+        self.send(
+            worker,
+            Job::Run(
+                name.to_string(),
+                Box::new(move |hosted| {
+                    let _ = result.send(f(hosted));
+                }),
+            ),
+        )?;
+        receiver
+            .recv()
+            .map_err(|_| format!("World {} is not running, it failed to build or panicked", name))
+    }
+
+    /// Send a job to every worker and wait for all of them, returning their errors
+    fn on_every_worker(&self, job: fn(Sender<Result<(), String>>) -> Job) -> Result<Vec<String>, String> {
+        let (done, results) = mpsc::channel();
+        for worker in 0..self.workers.len() {
+            self.send(worker, job(done.clone()))?;
+        }
+        drop(done);
+        Ok((0..self.workers.len())
+            .map(|_| results.recv().unwrap_or_else(|_| Err("a worker thread stopped".to_string())))
+            .filter_map(Result::err)
+            .collect())
+    }
+
+    fn worker_of(&self, name: &str) -> Result<usize, String> {
+        self.worlds
+            .iter()
+            .find(|(world, _)| world == name)
+            .map(|(_, worker)| *worker)
+            .ok_or_else(|| format!("No world named {}", name))
+    }
+
+    fn send(&self, worker: usize, job: Job) -> Result<(), String> {
+        self.workers[worker]
+            .jobs
+            .as_ref()
+            .and_then(|jobs| jobs.send(job).ok())
+            .ok_or_else(|| format!("Worker thread {} stopped", worker))
+    }
+}
+
+impl Default for WorldRunner {
+    /// A runner with a worker thread per available core
+    fn default() -> Self {
+        Self::new(std::thread::available_parallelism().map_or(1, |threads| threads.get()))
+    }
+}
+
+impl Drop for WorldRunner {
+    /// Drop the worlds on their threads and wait for the threads to finish
+    fn drop(&mut self) {
+        for worker in &mut self.workers {
+            worker.jobs = None;
+        }
+        for worker in &mut self.workers {
+            if let Some(thread) = worker.thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+/// Run the jobs of one worker thread until the runner is dropped
+fn work(jobs: Receiver<Job>) {
+    let mut worlds: Vec<(String, HostedWorld)> = Vec::new();
+    for job in jobs {
+        match job {
+            Job::Host(name, build) => match std::panic::catch_unwind(std::panic::AssertUnwindSafe(build)) {
+                Ok(world) => worlds.push((
+                    name,
+                    HostedWorld {
+                        world,
+                        deliveries: Vec::new(),
+                    },
+                )),
+                Err(payload) => ecs_error!("Building world {} panicked: {}", name, panic_message(payload)),
+            },
+            Job::Run(name, run) => {
+                if let Some((_, hosted)) = worlds.iter_mut().find(|(world, _)| *world == name) {
+                    // A panic drops the result sender, which fails the call
+                    let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| run(hosted)));
+                }
+            }
+            Job::Deliver(done) => {
+                for (_, hosted) in &mut worlds {
+                    for deliver in &mut hosted.deliveries {
+                        deliver(&mut hosted.world);
+                    }
+                }
+                let _ = done.send(Ok(()));
+            }
+            Job::Update(done) => {
+                let errors: Vec<String> = worlds
+                    .iter_mut()
+                    .filter_map(|(name, hosted)| {
+                        std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| hosted.world.update()))
+                        .err()
+                        .map(|payload| format!("world {} panicked: {}", name, panic_message(payload)))
+                    })
+                    .collect();
+                let _ = done.send(if errors.is_empty() { Ok(()) } else { Err(errors.join("; ")) });
+            }
+        }
+    }
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "non-string panic payload".to_string())
+}
+
+/// Sends messages of type M to the worlds connected with `WorldRunner::connect`
+pub struct WorldOutbox<M> {
+    /// Position of the sending world in the runner, orders deliveries
+    sender_index: usize,
+    destinations: Vec<(String, Sender<(usize, M)>)>,
+}
+
+impl<M> WorldOutbox<M> {
+    /// Send a message to a connected world, delivered when the next frame starts
+    pub fn send(&self, to: &str, message: M) -> Result<(), String> {
+        let (_, sender) = self
+            .destinations
+            .iter()
+            .find(|(name, _)| name == to)
+            .ok_or_else(|| format!("Not connected to world {}", to))?;
+        sender
+            .send((self.sender_index, message))
+            .map_err(|_| format!("World {} was dropped", to))
+    }
+
+    /// Get the names of the worlds messages can be sent to
+    pub fn destinations(&self) -> Vec<&str> {
+        self.destinations.iter().map(|(name, _)| name.as_str()).collect()
+    }
+}
+
+/// Messages of type M received from other worlds of a `WorldRunner`
+pub struct WorldInbox<M> {
+    sender: Sender<(usize, M)>,
+    receiver: Receiver<(usize, M)>,
+    messages: Vec<M>,
+}
+
+impl<M> WorldInbox<M> {
+    /// Take the messages delivered so far
+    pub fn drain(&mut self) -> Vec<M> {
+        std::mem::take(&mut self.messages)
+    }
+
+    /// Get the messages delivered so far without taking them
+    pub fn messages(&self) -> &[M] {
+        &self.messages
+    }
+
+    /// Move the messages sent before the frame into `messages`
+    fn deliver(&mut self) {
+        let mut received: Vec<(usize, M)> = self.receiver.try_iter().collect();
+        // Stable, so messages of one world keep the order they were sent in
+        received.sort_by_key(|(sender_index, _)| *sender_index);
+        self.messages.extend(received.into_iter().map(|(_, message)| message));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{System, WorldView};
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Score(u32);
+
+    /// Counts its updates and reports them to the ui world
+    struct ScoreSystem(u32);
+
+    impl System for ScoreSystem {
+        type InComponents = ();
+        type OutComponents = ();
+
+        fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+        fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+            self.0 += 1;
+            world.resource::<WorldOutbox<Score>>().unwrap().send("ui", Score(self.0)).unwrap();
+        }
+
+        fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    }
+
+    /// Shows the scores it received, keeping them in a resource
+    struct ScoreboardSystem;
+
+    impl System for ScoreboardSystem {
+        type InComponents = ();
+        type OutComponents = ();
+
+        fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+        fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+            let scores = world.resource_mut::<WorldInbox<Score>>().unwrap().drain();
+            world.resource_mut::<Vec<Score>>().unwrap().push(*scores.last().unwrap_or(&Score(0)));
+        }
+
+        fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    }
+
+    fn scoring_world() -> World {
+        let mut world = World::new();
+        world.add_system(ScoreSystem(0));
+        world
+    }
+
+    #[test]
+    fn test_worlds_exchange_messages_between_frames() {
+        let mut runner = WorldRunner::new(2);
+        runner.add_world("left", scoring_world).unwrap();
+        runner.add_world("right", scoring_world).unwrap();
+        runner
+            .add_world("ui", || {
+                let mut world = World::new();
+                world.insert_resource(Vec::<Score>::new());
+                world.add_system(ScoreboardSystem);
+                world
+            })
+            .unwrap();
+        runner.connect::<Score>("left", "ui").unwrap();
+        runner.connect::<Score>("right", "ui").unwrap();
+        assert!(runner.add_world("ui", World::new).is_err());
+        assert!(runner.connect::<Score>("left", "missing").is_err());
+
+        for _ in 0..3 {
+            runner.update().unwrap();
+        }
+        assert_eq!(runner.frame(), 3);
+        // Scores arrive a frame later, the last from the world added last
+        let shown = runner.with_world("ui", |world| world.resource::<Vec<Score>>().unwrap().clone()).unwrap();
+        assert_eq!(shown, vec![Score(0), Score(1), Score(2)]);
+        let history = runner.with_world("left", |world| world.get_update_history().len()).unwrap();
+        assert_eq!(history, 4);
+        assert_eq!(runner.world_names(), vec!["left", "right", "ui"]);
+    }
+
+    #[test]
+    fn test_failures_are_reported() {
+        let mut runner = WorldRunner::new(1);
+        assert!(runner.add_world("broken", || panic!("no world today")).is_err());
+        runner.add_world("empty", World::new).unwrap();
+        runner.update().unwrap();
+        assert!(runner.with_world("empty", |_| panic!("inspection failed")).is_err());
+        assert_eq!(runner.with_world("empty", |world| world.entity_count()), Ok(0));
+    }
+}
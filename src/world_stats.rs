@@ -0,0 +1,146 @@
+//! Summary of the contents of a world, for demos, tests and debug output.
+//!
+//! ```ignore
+//! println!("{}", world.stats());
+//! // World 0: 3 entities (1 disabled), 2 systems, 8 updates recorded, replay logging off
+//! //   Health: 3
+//! //   Position: 2
+//! ```
+
+use crate::World;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Counts of the entities, components and systems of a world and its child worlds
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorldStats {
+    pub world_index: usize,
+    pub entity_count: usize,
+    pub disabled_entity_count: usize,
+    /// Number of components of each type by short type name, including
+    /// dynamic components by schema name
+    pub component_counts: BTreeMap<String, usize>,
+    pub system_count: usize,
+    /// Number of updates in the history
+    pub history_length: usize,
+    pub replay_logging: bool,
+    pub child_worlds: Vec<WorldStats>,
+}
+
+impl WorldStats {
+    /// Get the number of components of all types
+    pub fn component_count(&self) -> usize {
+        self.component_counts.values().sum()
+    }
+
+    /// Get the number of components of a type, 0 if there are none
+    pub fn component_count_of(&self, type_name: &str) -> usize {
+        self.component_counts.get(type_name).copied().unwrap_or(0)
+    }
+
+    fn fmt_indented(&self, f: &mut fmt::Formatter<'_>, indent: usize) -> fmt::Result {
+        let pad = "  ".repeat(indent);
+        write!(f, "{}World {}: {} entities", pad, self.world_index, self.entity_count)?;
+        if self.disabled_entity_count > 0 {
+            write!(f, " ({} disabled)", self.disabled_entity_count)?;
+        }
+        writeln!(
+            f,
+            ", {} systems, {} updates recorded, replay logging {}",
+            self.system_count,
+            self.history_length,
+            if self.replay_logging { "on" } else { "off" }
+        )?;
+        for (type_name, count) in &self.component_counts {
+            writeln!(f, "{}  {}: {}", pad, type_name, count)?;
+        }
+        for child in &self.child_worlds {
+            child.fmt_indented(f, indent + 1)?;
+        }
+        Ok(())
+    }
+}
+
+/// One line for the world, then one per component type and the child worlds indented
+impl fmt::Display for WorldStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_indented(f, 0)
+    }
+}
+
+impl World {
+    /// Count the entities, components and systems of the world and its child worlds
+    pub fn stats(&self) -> WorldStats {
+        let mut component_counts = BTreeMap::new();
+        for (type_id, column) in &self.components {
+            if !column.is_empty() {
+                *component_counts.entry(self.component_type_name(*type_id).to_string()).or_insert(0) += column.len();
+            }
+        }
+        for (name, _, _) in self.dynamic.iter() {
+            *component_counts.entry(name.to_string()).or_insert(0) += 1;
+        }
+        WorldStats {
+            world_index: self.world_index,
+            entity_count: self.entities.len(),
+            disabled_entity_count: self.disabled_entities.len(),
+            component_counts,
+            system_count: self.systems.len(),
+            history_length: self.world_update_history.len(),
+            replay_logging: self.is_replay_logging_enabled(),
+            child_worlds: self.child_worlds.iter().map(World::stats).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{System, WorldView};
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Position(i32);
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Health(i32);
+
+    struct IdleSystem;
+    impl System for IdleSystem {
+        type InComponents = ();
+        type OutComponents = ();
+
+        fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+        fn update(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+        fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    }
+
+    #[test]
+    fn test_stats_summarize_the_world() {
+        let mut world = World::new();
+        let entities: Vec<_> = (0..3).map(|_| world.create_entity()).collect();
+        for entity in &entities {
+            world.add_component(*entity, Health(3));
+        }
+        world.add_component(entities[0], Position(1));
+        world.add_component(entities[1], Position(2));
+        world.remove_component::<Position>(entities[1]);
+        world.set_enabled(entities[2], false);
+        world.add_system(IdleSystem);
+        world.update();
+        let child = world.create_child_world();
+
+        let stats = world.stats();
+        assert_eq!(stats.entity_count, 3);
+        assert_eq!(stats.component_count(), 4);
+        assert_eq!(stats.component_count_of("Position"), 1);
+        assert_eq!(stats.component_count_of("Velocity"), 0);
+        assert_eq!(stats.child_worlds.len(), 1);
+        assert_eq!(
+            stats.to_string(),
+            format!(
+                "World 0: 3 entities (1 disabled), 1 systems, {} updates recorded, replay logging off\n  Health: 3\n  Position: 1\n  World {}: 0 entities, 0 systems, 0 updates recorded, replay logging off\n",
+                stats.history_length, child
+            )
+        );
+    }
+}
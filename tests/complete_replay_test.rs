@@ -1,3 +1,4 @@
+#![cfg(feature = "game")]
 use rust_ecs::*;
 use std::fs;
 use std::path::Path;
@@ -39,4 +40,48 @@ fn test_complete_replay_workflow() {
     
     // Clean up
     let _ = fs::remove_dir_all("test_replay_logs");
-}
\ No newline at end of file
+}
+#[test]
+fn test_logged_component_changes_round_trip() {
+    let _ = fs::remove_dir_all("test_round_trip_logs");
+
+    // Record one frame with a component change and an entity creation by hand
+    let entity = Entity::new(0, 0);
+    let mut system_diff = SystemUpdateDiff::new();
+    system_diff.record_component_change(DiffComponentChange::Modified {
+        entity,
+        type_name: "Position".into(),
+        diff: "PositionDiff { x: Some(3), y: None }".into(),
+    });
+    system_diff.record_world_operation(WorldOperation::CreateEntity(entity));
+    let mut update = WorldUpdateDiff::new();
+    update.record(system_diff);
+
+    let mut logger = AutoReplayLogger::new(ReplayLogConfig {
+        enabled: true,
+        log_directory: "test_round_trip_logs".to_string(),
+        file_prefix: "round_trip".to_string(),
+        flush_interval: 1,
+        include_component_details: true,
+        ..ReplayLogConfig::default()
+    });
+    logger.initialize().unwrap();
+    logger.log_update(&update).unwrap();
+    let bytes_written = logger.bytes_written();
+    logger.finalize().unwrap();
+
+    // Entities must be written in the same form the parser reads back
+    let log_file = format!("test_round_trip_logs/round_trip_{}.log", logger.session_id());
+    let content = fs::read_to_string(&log_file).unwrap();
+    let footer = "# End of replay log - Total updates: 1\n";
+    assert_eq!(bytes_written as usize, content.len() - footer.len());
+    assert!(content.contains("MOD Entity(0, 0) Position PositionDiff { x: Some(3), y: None }"));
+    assert!(content.contains("CREATE_ENTITY Entity(0, 0)"));
+
+    let history = World::parse_replay_log_file(&log_file).unwrap();
+    let system_diff = &history.updates()[0].system_diffs()[0];
+    assert_eq!(system_diff.component_changes().len(), 1);
+    assert_eq!(system_diff.world_operations().len(), 1);
+
+    let _ = fs::remove_dir_all("test_round_trip_logs");
+}
@@ -380,6 +380,27 @@ fn visualize_world_history(history: &WorldUpdateHistory) {
                     WorldOperation::AddSystem(system_type) => {
                         println!("      Added system {}", system_type);
                     }
+                    WorldOperation::AddToGroup(entity, group) => {
+                        println!("      Added entity {:?} to group {}", entity, group);
+                    }
+                    WorldOperation::RemoveFromGroup(entity, group) => {
+                        println!("      Removed entity {:?} from group {}", entity, group);
+                    }
+                    WorldOperation::Relate(source, relation, target) => {
+                        println!("      Related entity {:?} with {} to {:?}", source, relation, target);
+                    }
+                    WorldOperation::Unrelate(source, relation, target) => {
+                        println!("      Unrelated entity {:?} with {} from {:?}", source, relation, target);
+                    }
+                    WorldOperation::DisableEntity(entity) => {
+                        println!("      Disabled entity {:?}", entity);
+                    }
+                    WorldOperation::EnableEntity(entity) => {
+                        println!("      Enabled entity {:?}", entity);
+                    }
+                    WorldOperation::ClearEntities => println!("      Cleared entities"),
+                    WorldOperation::ClearAll => println!("      Cleared world"),
+                    WorldOperation::ResetHistory => println!("      Reset history"),
                 }
             }
         }
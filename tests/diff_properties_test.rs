@@ -0,0 +1,164 @@
+//! Property-based tests of the Diff laws for arbitrary values.
+//!
+//! For any values a and b:
+//! - `a.diff(&b)` is None exactly when a equals b
+//! - applying `a.diff(&b)` to a gives b
+//! - applying the inverse of the diff afterwards gives a again
+
+use proptest::prelude::*;
+use rust_ecs::{Diff, DiffComponent};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Debug;
+
+#[derive(Debug, Clone, Copy, PartialEq, Diff)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+enum Mood {
+    Calm,
+    Angry,
+    Sleepy,
+}
+
+#[derive(Debug, Clone, PartialEq, Diff)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Villager {
+    name: String,
+    age: u32,
+    mood: Mood,
+    inventory: Vec<String>,
+    skills: HashMap<String, i32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Diff)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Village {
+    villagers: Vec<Villager>,
+    stock: BTreeMap<String, u32>,
+}
+
+/// Check the diff laws for a and b
+fn check_laws<T: Diff + Clone + PartialEq + Debug>(a: &T, b: &T) -> Result<(), TestCaseError> {
+    let diff = a.diff(b);
+    prop_assert_eq!(diff.is_none(), a == b, "diff {:?}", diff);
+    let Some(diff) = diff else {
+        return Ok(());
+    };
+
+    let mut applied = a.clone();
+    applied.apply_diff(&diff);
+    prop_assert_eq!(&applied, b, "applying {:?}", diff);
+
+    let inverse = a.invert_diff(&diff).expect("a non-empty diff changes the value");
+    applied.apply_diff(&inverse);
+    prop_assert_eq!(&applied, a, "inverting {:?} with {:?}", diff, inverse);
+    Ok(())
+}
+
+fn mood() -> impl Strategy<Value = Mood> {
+    prop_oneof![Just(Mood::Calm), Just(Mood::Angry), Just(Mood::Sleepy)]
+}
+
+fn villager() -> impl Strategy<Value = Villager> {
+    (
+        "[a-c]{0,3}",
+        0u32..4,
+        mood(),
+        prop::collection::vec("[a-c]{1,2}", 0..4),
+        prop::collection::hash_map("[a-c]", -2i32..2, 0..3),
+    )
+        .prop_map(|(name, age, mood, inventory, skills)| Villager {
+            name,
+            age,
+            mood,
+            inventory,
+            skills,
+        })
+}
+
+fn village() -> impl Strategy<Value = Village> {
+    (
+        prop::collection::vec(villager(), 0..4),
+        prop::collection::btree_map("[a-c]", 0u32..3, 0..3),
+    )
+        .prop_map(|(villagers, stock)| Village { villagers, stock })
+}
+
+proptest! {
+    #[test]
+    fn test_primitives(a: i32, b: i32, c: u32, d: u32, e: usize, f: usize, g in ".{0,4}", h in ".{0,4}") {
+        check_laws(&a, &b)?;
+        check_laws(&c, &d)?;
+        check_laws(&e, &f)?;
+        check_laws(&g, &h)?;
+    }
+
+    /// Floats equal within `f32::EPSILON` have no diff
+    #[test]
+    fn test_floats(a in -1e6f32..1e6, b in -1e6f32..1e6) {
+        match a.diff(&b) {
+            Some(diff) => {
+                let mut applied = a;
+                applied.apply_diff(&diff);
+                prop_assert_eq!(applied, b);
+            }
+            None => prop_assert!((a - b).abs() <= f32::EPSILON),
+        }
+    }
+
+    #[test]
+    fn test_vecs(a in prop::collection::vec(-3i32..3, 0..8), b in prop::collection::vec(-3i32..3, 0..8)) {
+        check_laws(&a, &b)?;
+    }
+
+    #[test]
+    fn test_nested_vecs(
+        a in prop::collection::vec(prop::collection::vec(0i32..3, 0..3), 0..5),
+        b in prop::collection::vec(prop::collection::vec(0i32..3, 0..3), 0..5),
+    ) {
+        check_laws(&a, &b)?;
+    }
+
+    #[test]
+    fn test_hash_maps(
+        a in prop::collection::hash_map(0u8..6, prop::collection::vec(0i32..3, 0..3), 0..5),
+        b in prop::collection::hash_map(0u8..6, prop::collection::vec(0i32..3, 0..3), 0..5),
+    ) {
+        check_laws(&a, &b)?;
+    }
+
+    #[test]
+    fn test_btree_maps(
+        a in prop::collection::btree_map("[a-d]", 0u32..3, 0..5),
+        b in prop::collection::btree_map("[a-d]", 0u32..3, 0..5),
+    ) {
+        check_laws(&a, &b)?;
+    }
+
+    #[test]
+    fn test_enums(a in mood(), b in mood()) {
+        check_laws(&a, &b)?;
+    }
+
+    #[test]
+    fn test_derived_structs(a in villager(), b in villager()) {
+        check_laws(&a, &b)?;
+    }
+
+    #[test]
+    fn test_nested_derived_structs(a in village(), b in village()) {
+        check_laws(&a, &b)?;
+    }
+
+    /// A diff applied to the value it was computed from, then diffed back,
+    /// needs no further change
+    #[test]
+    fn test_diffs_compose_along_a_chain(a in village(), b in village(), c in village()) {
+        let mut value = a.clone();
+        for next in [&b, &c] {
+            if let Some(diff) = value.diff(next) {
+                value.apply_diff(&diff);
+            }
+            prop_assert_eq!(&value, next);
+        }
+        prop_assert!(value.diff(&c).is_none());
+    }
+}
@@ -0,0 +1,124 @@
+//! Tests for the `ecs-replay` command line tool.
+#![cfg(feature = "replay-cli")]
+
+use std::fs;
+use std::process::{Command, Output};
+
+const LOG: &str = "# ECS Replay Log
+
+UPDATE 1
+SYSTEMS: 1
+  SYSTEM 0
+    WORLD_OPERATIONS: 1
+      CREATE_ENTITY Entity(0, 0)
+
+UPDATE 2
+SYSTEMS: 1
+  SYSTEM 0
+    COMPONENT_CHANGES: 1
+      ADD Entity(0, 0) Position Position { x: 1, y: 2 }
+
+UPDATE 3
+SYSTEMS: 1
+  SYSTEM 0
+    COMPONENT_CHANGES: 1
+      MOD Entity(0, 0) Position PositionDiff { x: Some(2), y: None }
+";
+
+fn ecs_replay(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_ecs-replay"))
+        .args(args)
+        .output()
+        .expect("Failed to run ecs-replay")
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn test_convert_trim_and_diff_logs() {
+    let directory = std::env::temp_dir().join(format!("rust_ecs_replay_cli_{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    let log = directory.join("session.log");
+    fs::write(&log, LOG).unwrap();
+    let path = |name: &str| directory.join(name).to_str().unwrap().to_string();
+    let log = log.to_str().unwrap();
+
+    let output = ecs_replay(&["validate", log]);
+    assert!(output.status.success(), "{}", stdout(&output));
+    let info = stdout(&ecs_replay(&["info", log]));
+    assert!(info.contains("Total Component Changes: 2"));
+    assert!(info.contains("3 unique strings"), "{}", info);
+
+    // Text -> binary -> JSON keeps every change
+    assert!(ecs_replay(&["convert", log, "--to", "binary"]).status.success());
+    assert!(fs::read(path("session.bin")).unwrap().starts_with(b"ECSR"));
+    let json = path("session.json");
+    assert!(ecs_replay(&["convert", &path("session.bin"), "--to", "json", "--output", &json]).status.success());
+    let output = ecs_replay(&["diff", log, &json]);
+    assert!(output.status.success(), "{}", stdout(&output));
+
+    let output = ecs_replay(&["trim", log, "--frames", "1..=2"]);
+    assert!(output.status.success());
+    let trimmed = path("session_frames_1-3.log");
+    assert!(stdout(&ecs_replay(&["validate", &trimmed])).contains("(2 updates)"));
+
+    let output = ecs_replay(&["diff", log, &trimmed]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stdout(&output).contains("  - CREATE_ENTITY Entity(0, 0)"));
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn test_validate_reports_malformed_lines() {
+    let directory = std::env::temp_dir().join(format!("rust_ecs_replay_cli_invalid_{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    let log = directory.join("broken.log");
+    fs::write(&log, LOG.replace("CREATE_ENTITY Entity(0, 0)", "CREATE_ENTITY 0")).unwrap();
+
+    let output = ecs_replay(&["validate", log.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stdout(&output).contains("line 7: malformed CREATE_ENTITY entry '0'"));
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn test_validate_reports_components_used_before_their_addition() {
+    let directory = std::env::temp_dir().join(format!("rust_ecs_replay_cli_inconsistent_{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    let log = directory.join("edited.log");
+    let edited = LOG
+        .replace("# ECS Replay Log\n", "# ECS Replay Log\nINITIAL_STATE\n")
+        .replace("ADD Entity(0, 0)", "ADD Entity(0, 1)");
+    fs::write(&log, edited).unwrap();
+
+    let output = ecs_replay(&["validate", log.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(1));
+    let output = stdout(&output);
+    assert!(output.contains("line 14: ADD refers to Entity(0, 1) before its creation\n"), "{}", output);
+    assert!(output.contains("line 20: MOD of Position on Entity(0, 0) before it was added\n"), "{}", output);
+    assert!(output.contains("2 issues found"), "{}", output);
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn test_recover_saves_the_complete_updates_of_a_crashed_log() {
+    let directory = std::env::temp_dir().join(format!("rust_ecs_replay_cli_crashed_{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    let log = directory.join("crashed.log");
+    // The session crashed while the third update was written
+    fs::write(&log, &LOG[..LOG.find("PositionDiff").unwrap()]).unwrap();
+
+    let output = ecs_replay(&["recover", log.to_str().unwrap()]);
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert!(stdout(&output).contains("Recovered 2 updates from an unfinished log, discarding 5 lines"));
+    let recovered = directory.join("crashed_recovered.log");
+    let output = stdout(&ecs_replay(&["validate", recovered.to_str().unwrap()]));
+    assert!(output.contains("(2 updates)"), "{}", output);
+
+    fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,83 @@
+//! Tests for systems reporting recoverable errors through `World::try_update`.
+
+use rust_ecs::{FallibleSystem, System, SystemError, World, WorldView};
+
+struct Gravity(f32);
+
+/// Needs the Gravity resource, which the test inserts only later
+struct FallSystem {
+    speed: f32,
+}
+
+impl FallibleSystem for FallSystem {
+    type InComponents = ();
+    type OutComponents = ();
+
+    fn try_update(&mut self, world: &mut WorldView<(), ()>) -> Result<(), SystemError> {
+        let gravity = world
+            .resource::<Gravity>()
+            .ok_or_else(|| SystemError::new("missing resource Gravity"))?;
+        self.speed += gravity.0;
+        Ok(())
+    }
+}
+
+/// A regular system in the same world
+struct TickSystem;
+
+impl System for TickSystem {
+    type InComponents = ();
+    type OutComponents = ();
+
+    fn initialize(&mut self, _world: &mut WorldView<(), ()>) {}
+
+    fn update(&mut self, world: &mut WorldView<(), ()>) {
+        if let Some(ticks) = world.resource_mut::<u32>() {
+            *ticks += 1;
+        }
+    }
+
+    fn deinitialize(&mut self, _world: &mut WorldView<(), ()>) {}
+}
+
+#[test]
+fn test_errors_are_returned_and_flagged_in_history() {
+    let mut world = World::new();
+    world.insert_resource(0u32);
+    world.add_system(FallSystem { speed: 0.0 });
+    world.add_system(TickSystem);
+
+    let error = world.try_update().unwrap_err();
+    assert_eq!(error.frame, 0);
+    assert_eq!(error.errors.len(), 1);
+    assert!(error.errors[0].0.ends_with("FallSystem"));
+    assert_eq!(error.errors[0].1, "missing resource Gravity");
+    // The other systems still ran
+    assert_eq!(world.resource::<u32>(), Some(&1));
+
+    world.insert_resource(Gravity(9.8));
+    assert!(world.try_update().is_ok());
+    world.update();
+
+    let history = world.get_update_history();
+    let first_frame = history.len() - 3;
+    assert_eq!(history.error_frames(), vec![first_frame]);
+    assert_eq!(
+        history.updates()[first_frame].system_diffs()[0].error(),
+        Some("missing resource Gravity")
+    );
+}
+
+#[test]
+fn test_errors_name_the_system_that_reported_them() {
+    let mut world = World::new();
+    world.insert_resource(0u32);
+    world.add_system(TickSystem);
+    world.add_system(FallSystem { speed: 0.0 });
+    world.add_system(TickSystem);
+
+    let error = world.try_update().unwrap_err();
+    assert_eq!(error.errors.len(), 1);
+    assert!(error.errors[0].0.ends_with("FallSystem"), "{}", error);
+    assert_eq!(world.resource::<u32>(), Some(&2));
+}
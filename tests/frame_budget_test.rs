@@ -0,0 +1,120 @@
+//! Tests for the frame-budget watchdog.
+#![cfg(feature = "game")]
+
+use rust_ecs::game::Position;
+use rust_ecs::{Out, ReplayLogConfig, System, World, WorldView};
+use std::time::Duration;
+
+/// Moves every positioned entity and then stalls for a while
+struct SlowSystem {
+    delay: Duration,
+}
+
+impl System for SlowSystem {
+    type InComponents = ();
+    type OutComponents = (Position,);
+
+    fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+    fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+        let mut changes = Vec::new();
+        for (entity, position) in world.query_components::<(Out<Position>,)>() {
+            let old_position = *position;
+            position.x += 1;
+            changes.push((entity, old_position, *position));
+        }
+        for (entity, old_position, new_position) in changes {
+            world.record_component_modification(entity, &old_position, &new_position);
+        }
+        std::thread::sleep(self.delay);
+    }
+
+    fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+}
+
+fn setup_world() -> World {
+    let mut world = World::new();
+    for i in 0..3 {
+        let entity = world.create_entity();
+        world.add_component(entity, Position { x: i, y: 0 });
+    }
+    world.add_system(SlowSystem {
+        delay: Duration::from_millis(5),
+    });
+    world
+}
+
+#[test]
+fn test_no_slow_frames_without_budget() {
+    let mut world = setup_world();
+    world.update();
+    assert_eq!(world.frame_budget(), None);
+    assert!(world.slow_frames().is_empty());
+}
+
+#[test]
+fn test_frames_within_budget_are_not_captured() {
+    let mut world = setup_world();
+    world.set_frame_budget(Some(Duration::from_secs(60)));
+    world.update();
+    assert!(world.slow_frames().is_empty());
+}
+
+#[test]
+fn test_slow_frame_is_captured() {
+    let mut world = setup_world();
+    world.update();
+    world.set_frame_budget(Some(Duration::from_millis(1)));
+    world.update();
+
+    let slow_frames = world.slow_frames();
+    assert_eq!(slow_frames.len(), 1);
+    let record = &slow_frames[0];
+    assert_eq!(record.frame, 1);
+    assert!(record.duration >= Duration::from_millis(5));
+    assert_eq!(record.budget, Duration::from_millis(1));
+    assert_eq!(record.entity_count, 3);
+    assert_eq!(record.component_count, 3);
+    assert_eq!(record.system_timings.len(), 1);
+    assert!(record.system_timings[0].system_name.ends_with("SlowSystem"));
+    assert_eq!(record.system_timings[0].change_count, 3);
+    assert_eq!(record.largest_diffs, record.system_timings);
+
+    world.clear_slow_frames();
+    assert!(world.slow_frames().is_empty());
+}
+
+#[test]
+fn test_slow_frame_is_written_to_replay_log() {
+    let log_directory = "test_logs_frame_budget";
+    let mut world = setup_world();
+    world
+        .enable_replay_logging(ReplayLogConfig {
+            enabled: true,
+            log_directory: log_directory.to_string(),
+            file_prefix: "frame_budget".to_string(),
+            flush_interval: 1,
+            include_component_details: true,
+            ..ReplayLogConfig::default()
+        })
+        .expect("Failed to enable logging");
+    world.set_frame_budget(Some(Duration::from_millis(1)));
+    world.update();
+    world.update();
+
+    let session_id = world.replay_session_id().unwrap().to_string();
+    world.disable_replay_logging().expect("Failed to disable logging");
+    let log_path = format!("{}/frame_budget_{}.log", log_directory, session_id);
+    let content = std::fs::read_to_string(&log_path).expect("Failed to read log");
+
+    assert!(content.contains("# SLOW_FRAME 0 "));
+    assert!(content.contains("# SLOW_FRAME 1 "));
+    assert!(content.contains("#   SYSTEM_TIME "));
+    assert!(content.contains("#   LARGEST_DIFF "));
+
+    // Slow-frame records do not disturb replay parsing
+    let history = World::parse_replay_log_file(&log_path).expect("Failed to parse log");
+    assert_eq!(history.len(), 2);
+
+    let _ = std::fs::remove_dir_all(log_directory);
+}
@@ -1,218 +1,14 @@
+#![cfg(feature = "game")]
 use rust_ecs::*;
 use rust_ecs::game::game::*;
 use std::fs;
 use std::path::Path;
-use std::collections::HashMap;
-
-/// Helper function to create a comprehensive snapshot of world state for comparison
-#[derive(Debug, Clone, PartialEq)]
-struct WorldSnapshot {
-    entity_count: usize,
-    positions: HashMap<Entity, Position>,
-    targets: HashMap<Entity, Target>,
-    wait_timers: HashMap<Entity, WaitTimer>,
-    actor_states: HashMap<Entity, ActorState>,
-    actors: Vec<Entity>,
-    homes: Vec<Entity>,
-    works: Vec<Entity>,
-    obstacles: Vec<Entity>,
-}
-
-impl WorldSnapshot {
-    fn capture(world: &World) -> Self {
-        let mut positions = HashMap::new();
-        let mut targets = HashMap::new();
-        let mut wait_timers = HashMap::new();
-        let mut actor_states = HashMap::new();
-
-        // Capture all Position components
-        for entity in world.entities_with_component::<Position>() {
-            if let Some(pos) = world.get_component::<Position>(entity) {
-                positions.insert(entity, *pos);
-            }
-        }
-
-        // Capture all Target components
-        for entity in world.entities_with_component::<Target>() {
-            if let Some(target) = world.get_component::<Target>(entity) {
-                targets.insert(entity, *target);
-            }
-        }
-
-        // Capture all WaitTimer components
-        for entity in world.entities_with_component::<WaitTimer>() {
-            if let Some(timer) = world.get_component::<WaitTimer>(entity) {
-                wait_timers.insert(entity, *timer);
-            }
-        }
-
-        // Capture all ActorState components
-        for entity in world.entities_with_component::<ActorState>() {
-            if let Some(state) = world.get_component::<ActorState>(entity) {
-                actor_states.insert(entity, *state);
-            }
-        }
-
-        // Capture entity lists by component type
-        let actors = world.entities_with_component::<Actor>();
-        let homes = world.entities_with_component::<Home>();
-        let works = world.entities_with_component::<Work>();
-        let obstacles = world.entities_with_component::<Obstacle>();
-
-        WorldSnapshot {
-            entity_count: world.entity_count(),
-            positions,
-            targets,
-            wait_timers,
-            actor_states,
-            actors,
-            homes,
-            works,
-            obstacles,
-        }
-    }
-
-    fn compare(&self, other: &WorldSnapshot) -> Vec<String> {
-        let mut differences = Vec::new();
-
-        // Compare entity counts
-        if self.entity_count != other.entity_count {
-            differences.push(format!(
-                "Entity count differs: {} vs {}",
-                self.entity_count, other.entity_count
-            ));
-        }
-
-        // Compare component counts
-        if self.positions.len() != other.positions.len() {
-            differences.push(format!(
-                "Position component count differs: {} vs {}",
-                self.positions.len(), other.positions.len()
-            ));
-        }
-
-        if self.targets.len() != other.targets.len() {
-            differences.push(format!(
-                "Target component count differs: {} vs {}",
-                self.targets.len(), other.targets.len()
-            ));
-        }
-
-        if self.wait_timers.len() != other.wait_timers.len() {
-            differences.push(format!(
-                "WaitTimer component count differs: {} vs {}",
-                self.wait_timers.len(), other.wait_timers.len()
-            ));
-        }
-
-        if self.actor_states.len() != other.actor_states.len() {
-            differences.push(format!(
-                "ActorState component count differs: {} vs {}",
-                self.actor_states.len(), other.actor_states.len()
-            ));
-        }
-
-        // Compare entity lists
-        if self.actors.len() != other.actors.len() {
-            differences.push(format!(
-                "Actor count differs: {} vs {}",
-                self.actors.len(), other.actors.len()
-            ));
-        }
-
-        if self.homes.len() != other.homes.len() {
-            differences.push(format!(
-                "Home count differs: {} vs {}",
-                self.homes.len(), other.homes.len()
-            ));
-        }
-
-        if self.works.len() != other.works.len() {
-            differences.push(format!(
-                "Work count differs: {} vs {}",
-                self.works.len(), other.works.len()
-            ));
-        }
-
-        if self.obstacles.len() != other.obstacles.len() {
-            differences.push(format!(
-                "Obstacle count differs: {} vs {}",
-                self.obstacles.len(), other.obstacles.len()
-            ));
-        }
-
-        // Compare component values for each entity
-        for (entity, pos1) in &self.positions {
-            if let Some(pos2) = other.positions.get(entity) {
-                if pos1 != pos2 {
-                    differences.push(format!(
-                        "Position differs for entity {:?}: ({}, {}) vs ({}, {})",
-                        entity, pos1.x, pos1.y, pos2.x, pos2.y
-                    ));
-                }
-            } else {
-                differences.push(format!(
-                    "Entity {:?} has Position in first world but not in second",
-                    entity
-                ));
-            }
-        }
-
-        for (entity, target1) in &self.targets {
-            if let Some(target2) = other.targets.get(entity) {
-                if target1 != target2 {
-                    differences.push(format!(
-                        "Target differs for entity {:?}: ({}, {}) vs ({}, {})",
-                        entity, target1.x, target1.y, target2.x, target2.y
-                    ));
-                }
-            } else {
-                differences.push(format!(
-                    "Entity {:?} has Target in first world but not in second",
-                    entity
-                ));
-            }
-        }
-
-        for (entity, timer1) in &self.wait_timers {
-            if let Some(timer2) = other.wait_timers.get(entity) {
-                if timer1 != timer2 {
-                    differences.push(format!(
-                        "WaitTimer differs for entity {:?}: {} vs {}",
-                        entity, timer1.ticks, timer2.ticks
-                    ));
-                }
-            } else {
-                differences.push(format!(
-                    "Entity {:?} has WaitTimer in first world but not in second",
-                    entity
-                ));
-            }
-        }
-
-        for (entity, state1) in &self.actor_states {
-            if let Some(state2) = other.actor_states.get(entity) {
-                if state1 != state2 {
-                    differences.push(format!(
-                        "ActorState differs for entity {:?}: {:?} vs {:?}",
-                        entity, state1, state2
-                    ));
-                }
-            } else {
-                differences.push(format!(
-                    "Entity {:?} has ActorState in first world but not in second",
-                    entity
-                ));
-            }
-        }
-
-        differences
-    }
-}
 
 /// Create a deterministic version of the game initialization for testing
 fn initialize_deterministic_game() -> World {
     let mut world = World::new();
+    // Registered components are captured by World::snapshot
+    register_game_components(&mut world);
 
     // Create home entity
     let home_entity = world.create_entity();
@@ -258,7 +54,7 @@ fn initialize_deterministic_game() -> World {
     // Add systems - same for both normal and replay modes
     world.add_system(MovementSystem);
     world.add_system(WaitSystem);
-    world.add_system(RenderSystem::default());
+    world.add_system(RenderSystem);
 
     // Initialize systems
     world.initialize_systems();
@@ -282,8 +78,8 @@ fn test_game_replay_integration() {
     
     // Enable replay logging and capture initial state
     normal_world.enable_replay_logging_simple(test_log_dir, "integration_test", 1).unwrap();
-    let initial_snapshot = WorldSnapshot::capture(&normal_world);
-    println!("Initial world state captured: {} entities", initial_snapshot.entity_count);
+    let initial_snapshot = normal_world.snapshot();
+    println!("Initial world state captured: {} entities", initial_snapshot.entities().len());
     
     // Run the game for several updates
     let num_updates = 5; // Reduced for more predictable testing
@@ -293,8 +89,8 @@ fn test_game_replay_integration() {
     }
     
     // Capture final state of normal game
-    let normal_final_snapshot = WorldSnapshot::capture(&normal_world);
-    println!("Normal game final state captured: {} entities", normal_final_snapshot.entity_count);
+    let normal_final_snapshot = normal_world.snapshot();
+    println!("Normal game final state captured: {} entities", normal_final_snapshot.entities().len());
     
     // Finalize logging
     let session_id = normal_world.replay_session_id().unwrap().to_string();
@@ -317,8 +113,8 @@ fn test_game_replay_integration() {
     let mut replay_world = initialize_deterministic_game();
     
     // Verify initial states match (deterministic initialization)
-    let replay_initial_snapshot = WorldSnapshot::capture(&replay_world);
-    let initial_differences = initial_snapshot.compare(&replay_initial_snapshot);
+    let replay_initial_snapshot = replay_world.snapshot();
+    let initial_differences = initial_snapshot.diff(&replay_initial_snapshot);
     assert!(initial_differences.is_empty(), 
         "Initial states should be identical with deterministic initialization, but found differences: {:?}", initial_differences);
     println!("✅ Deterministic initialization produces identical initial states");
@@ -332,18 +128,14 @@ fn test_game_replay_integration() {
     // Step 4: Compare final world states  
     println!("Step 3: Comparing final world states");
     
-    let replay_final_snapshot = WorldSnapshot::capture(&replay_world);
-    let differences = normal_final_snapshot.compare(&replay_final_snapshot);
+    let replay_final_snapshot = replay_world.snapshot();
+    let differences = normal_final_snapshot.diff(&replay_final_snapshot);
     
     if differences.is_empty() {
         println!("✅ SUCCESS: Final world states are identical!");
         println!("   This demonstrates that with deterministic initialization:");
-        println!("   - Entity count: {}", normal_final_snapshot.entity_count);
-        println!("   - Position components: {}", normal_final_snapshot.positions.len());
-        println!("   - Target components: {}", normal_final_snapshot.targets.len());
-        println!("   - WaitTimer components: {}", normal_final_snapshot.wait_timers.len());
-        println!("   - ActorState components: {}", normal_final_snapshot.actor_states.len());
-        println!("   - Actors: {}", normal_final_snapshot.actors.len());
+        println!("   - Entity count: {}", normal_final_snapshot.entities().len());
+        println!("   - Components: {}", normal_final_snapshot.components().len());
         println!("   - Both worlds produce identical final states");
     } else {
         println!("ℹ️  Note: Found {} differences between normal and replay worlds", differences.len());
@@ -0,0 +1,18 @@
+//! Regression test comparing the simulation game with a recorded golden log.
+#![cfg(feature = "game")]
+
+use rust_ecs::game::GamePlugin;
+use rust_ecs::testing::assert_replay_matches;
+use rust_ecs::World;
+
+fn add_game(world: &mut World) {
+    world.add_plugin(GamePlugin {
+        render: false,
+        seed: Some(7),
+    });
+}
+
+#[test]
+fn test_headless_game_matches_golden_log() {
+    assert_replay_matches(World::new(), add_game, "tests/golden/headless_game_seed_7.log");
+}
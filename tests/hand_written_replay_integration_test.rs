@@ -1,3 +1,4 @@
+#![cfg(feature = "game")]
 use rust_ecs::*;
 use rust_ecs::game::game::*;
 use std::fs;
@@ -0,0 +1,170 @@
+//! Tests for invariant assertions checked during updates.
+#![cfg(feature = "game")]
+
+use rust_ecs::game::{Actor, Position};
+use rust_ecs::{replay_analysis, In, Out, System, World, WorldView};
+
+/// Moves every positioned entity one cell to the right
+struct StepSystem;
+impl System for StepSystem {
+    type InComponents = ();
+    type OutComponents = (Position,);
+
+    fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+    fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+        let mut changes = Vec::new();
+        for (entity, position) in world.query_components::<(Out<Position>,)>() {
+            let old_position = *position;
+            position.x += 1;
+            changes.push((entity, old_position, *position));
+        }
+        for (entity, old_position, new_position) in changes {
+            world.record_component_modification(entity, &old_position, &new_position);
+        }
+    }
+
+    fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+}
+
+/// Does nothing, used to check attribution to the right system
+struct IdleSystem;
+impl System for IdleSystem {
+    type InComponents = ();
+    type OutComponents = ();
+
+    fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    fn update(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+    fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+}
+
+/// Asserts that positions stay in bounds without stopping anything
+struct BoundsCheckSystem;
+impl System for BoundsCheckSystem {
+    type InComponents = (Position,);
+    type OutComponents = ();
+
+    fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+    fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+        let in_bounds = world.query_components::<(In<Position>,)>().iter().all(|(_, position)| position.x < 3);
+        world.debug_assert_invariant("positions_in_bounds", in_bounds);
+        world.debug_assert_invariant("positions_in_bounds", in_bounds);
+    }
+
+    fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+}
+
+fn positions_in_bounds(world: &World) -> bool {
+    world
+        .entities_with_component::<Position>()
+        .iter()
+        .all(|entity| world.get_component::<Position>(*entity).is_some_and(|p| p.x < 3))
+}
+
+#[test]
+fn test_violation_reports_offending_system_and_frame() {
+    let mut world = World::new();
+    let entity = world.create_entity();
+    world.add_component(entity, Position { x: 0, y: 0 });
+    world.add_system(IdleSystem);
+    world.add_system(StepSystem);
+    world.add_invariant("positions_in_bounds", positions_in_bounds);
+
+    for _ in 0..5 {
+        world.update();
+    }
+
+    // Reported once when x reaches 3, not again while it stays broken
+    let violations = world.invariant_violations();
+    assert_eq!(violations.len(), 1);
+    let violation = &violations[0];
+    assert_eq!(violation.invariant, "positions_in_bounds");
+    assert_eq!(violation.frame, 2);
+    assert!(violation.system_name.as_deref().unwrap().ends_with("StepSystem"));
+    let diff = violation.system_diff.as_ref().unwrap();
+    assert_eq!(diff.component_changes().len(), 1);
+    assert!(violation.to_string().contains("violated in frame 2 by"));
+}
+
+#[test]
+fn test_violation_outside_system_updates() {
+    let mut world = World::new();
+    let entity = world.create_entity();
+    world.add_component(entity, Position { x: 0, y: 0 });
+    world.add_system(IdleSystem);
+    world.add_invariant("every_position_has_actor", |world| {
+        world
+            .entities_with_component::<Position>()
+            .iter()
+            .all(|entity| world.get_component::<Actor>(*entity).is_some())
+    });
+
+    // Broken from the start, so nothing new to report
+    world.update();
+    assert!(world.invariant_violations().is_empty());
+
+    world.add_component(entity, Actor);
+    world.update();
+    world.remove_component::<Actor>(entity);
+    world.update();
+
+    let violations = world.invariant_violations();
+    assert_eq!(violations.len(), 1);
+    assert_eq!(violations[0].system_name, None);
+    assert!(violations[0].to_string().contains("outside of system updates"));
+
+    world.clear_invariant_violations();
+    assert!(world.remove_invariant("every_position_has_actor"));
+    assert!(!world.remove_invariant("every_position_has_actor"));
+}
+
+#[test]
+fn test_failed_assertions_are_recorded_in_the_frame() {
+    let mut world = World::new();
+    let entity = world.create_entity();
+    world.add_component(entity, Position { x: 0, y: 0 });
+    world.add_system(StepSystem);
+    world.add_system(BoundsCheckSystem);
+    for _ in 0..4 {
+        world.update();
+    }
+
+    // The first update of the history adds StepSystem, the next BoundsCheckSystem
+    let history = world.get_update_history();
+    assert_eq!(history.assertion_frames(), vec![4, 5]);
+    let failed = &history.updates()[4].system_diffs()[1];
+    assert_eq!(failed.failed_assertions(), ["positions_in_bounds".to_string()]);
+    assert!(history.error_frames().is_empty());
+
+    let mut log = Vec::new();
+    replay_analysis::write_replay_log(history, &mut log).unwrap();
+    let log = String::from_utf8(log).unwrap();
+    assert!(log.contains("    ASSERT: positions_in_bounds\n"), "{}", log);
+    let parsed = replay_analysis::parse_replay_log_str(&log);
+    assert_eq!(parsed.assertion_frames(), vec![4, 5]);
+    assert!(replay_analysis::compare_histories(history, &parsed).is_empty());
+}
+
+#[test]
+fn test_replayed_systems_are_checked_before_their_update_is_undone() {
+    let mut world = World::new();
+    // Only components registered for replay are undone
+    world.register_replay_component::<Position>();
+    let entity = world.create_entity();
+    world.add_component(entity, Position { x: 2, y: 0 });
+    world.add_system(StepSystem);
+    world.add_system(IdleSystem);
+    world.add_invariant("positions_in_bounds", positions_in_bounds);
+    world.enable_replay_mode();
+
+    world.update();
+    world.update();
+
+    // Each frame's undone step breaks the bounds again
+    let violations = world.invariant_violations();
+    assert_eq!(violations.len(), 2);
+    assert!(violations.iter().all(|violation| violation.system_name.as_deref().unwrap().ends_with("StepSystem")));
+    assert_eq!(violations[1].frame, 1);
+    assert_eq!(world.get_component::<Position>(entity), Some(&Position { x: 2, y: 0 }));
+}
@@ -0,0 +1,162 @@
+//! Tests for replicating world updates to a mirror world over the network.
+#![cfg(all(feature = "net", feature = "game"))]
+
+use rust_ecs::game::{register_game_components, Actor, MovementSystem, Position, Target};
+use rust_ecs::net::{
+    DiffReceiver, DiffSender, ReceiveOutcome, Region, ReplicationConfig, TcpTransport, UdpTransport,
+};
+use rust_ecs::{Entity, World};
+use std::net::TcpListener;
+
+fn create_sender_world() -> World {
+    let mut world = create_mirror_world();
+    let actor = world.create_entity();
+    world.add_component(actor, Position { x: 2, y: 3 });
+    world.add_component(actor, Actor);
+    world.add_component(actor, Target { x: 9, y: 9 });
+    world.add_system(MovementSystem);
+    world
+}
+
+/// Both worlds need the components registered for replay
+fn create_mirror_world() -> World {
+    let mut world = World::new();
+    register_game_components(&mut world);
+    world
+}
+
+fn actor_position(world: &World) -> Option<Position> {
+    let actor = world.entities_with_component::<Actor>()[0];
+    world.get_component::<Position>(actor).copied()
+}
+
+fn register_components<T: rust_ecs::net::Transport>(sender: &mut DiffSender<T>) {
+    sender.register_component::<Position>();
+    sender.register_component::<Actor>();
+    sender.register_component::<Target>();
+}
+
+#[test]
+fn test_tcp_mirror_follows_sender() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+
+    let sender_thread = std::thread::spawn(move || {
+        let mut world = create_sender_world();
+        let mut sender = DiffSender::new(TcpTransport::connect(address).unwrap());
+        sender.set_compression(true);
+        register_components(&mut sender);
+
+        sender.send_snapshot(&world).unwrap();
+        for _ in 0..3 {
+            world.update();
+        }
+        assert_eq!(sender.send_new_updates(&world).unwrap(), 3);
+        actor_position(&world)
+    });
+
+    let (stream, _) = listener.accept().unwrap();
+    let mut receiver = DiffReceiver::new(TcpTransport::new(stream));
+    let mut mirror = create_mirror_world();
+
+    assert_eq!(
+        receiver.receive(&mut mirror).unwrap(),
+        ReceiveOutcome::Snapshot { sequence: 0 }
+    );
+    assert_eq!(mirror.entity_count(), 1);
+    assert_eq!(actor_position(&mirror), Some(Position { x: 2, y: 3 }));
+
+    for sequence in 1..=3 {
+        assert_eq!(
+            receiver.receive(&mut mirror).unwrap(),
+            ReceiveOutcome::Applied { sequence }
+        );
+    }
+
+    let sender_position = sender_thread.join().unwrap();
+    assert_ne!(sender_position, Some(Position { x: 2, y: 3 }));
+    assert_eq!(actor_position(&mirror), sender_position);
+    assert_eq!(mirror.get_update_history().len(), 3);
+}
+
+#[test]
+fn test_udp_mirror_follows_sender() {
+    let sender_socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let receiver_socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    sender_socket.connect(receiver_socket.local_addr().unwrap()).unwrap();
+    receiver_socket.connect(sender_socket.local_addr().unwrap()).unwrap();
+
+    let mut world = create_sender_world();
+    let mut sender = DiffSender::new(UdpTransport::new(sender_socket));
+    register_components(&mut sender);
+    let mut receiver = DiffReceiver::new(UdpTransport::new(receiver_socket));
+    let mut mirror = create_mirror_world();
+
+    sender.send_snapshot(&world).unwrap();
+    assert!(matches!(
+        receiver.receive(&mut mirror).unwrap(),
+        ReceiveOutcome::Snapshot { .. }
+    ));
+    assert!(receiver.is_synchronized());
+
+    world.update();
+    sender.send_new_updates(&world).unwrap();
+    assert!(matches!(
+        receiver.receive(&mut mirror).unwrap(),
+        ReceiveOutcome::Applied { .. }
+    ));
+    assert_eq!(actor_position(&mirror), actor_position(&world));
+}
+
+fn locate_by_position(world: &World, entity: Entity) -> Option<(f32, f32)> {
+    world
+        .get_component::<Position>(entity)
+        .map(|position| (position.x as f32, position.y as f32))
+}
+
+#[test]
+fn test_region_interest_management() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let sender_transport = TcpTransport::connect(listener.local_addr().unwrap()).unwrap();
+    let (stream, _) = listener.accept().unwrap();
+
+    // The first actor starts inside the region and walks out of it
+    let mut world = create_sender_world();
+    let far_actor = world.create_entity();
+    world.add_component(far_actor, Position { x: 8, y: 8 });
+    world.add_component(far_actor, Actor);
+
+    let mut sender = DiffSender::new(sender_transport);
+    register_components(&mut sender);
+    sender.set_replication_config(
+        ReplicationConfig::new()
+            .with_components::<(Position, Actor)>()
+            .with_region(Region::new(0.0, 0.0, 4.0, 4.0), locate_by_position),
+    );
+    let mut receiver = DiffReceiver::new(TcpTransport::new(stream));
+    let mut mirror = create_mirror_world();
+
+    sender.send_snapshot(&world).unwrap();
+    receiver.receive(&mut mirror).unwrap();
+    assert_eq!(mirror.entity_count(), 1);
+    assert_eq!(actor_position(&mirror), Some(Position { x: 2, y: 3 }));
+    // Target is not replicated
+    assert!(mirror.entities_with_component::<Target>().is_empty());
+
+    // One step keeps the actor inside the region
+    world.update();
+    assert_eq!(sender.send_new_updates(&world).unwrap(), 1);
+    receiver.receive(&mut mirror).unwrap();
+    assert_eq!(actor_position(&mirror), actor_position(&world));
+
+    // Walking on towards (9, 9) leaves the region and removes the actor
+    for _ in 0..6 {
+        world.update();
+    }
+    let sent = sender.send_new_updates(&world).unwrap();
+    assert_eq!(sent, 7);
+    for _ in 0..sent {
+        receiver.receive(&mut mirror).unwrap();
+    }
+    assert_eq!(mirror.entity_count(), 0);
+}
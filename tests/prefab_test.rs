@@ -0,0 +1,47 @@
+//! Tests for spawning the game entities from prefabs.
+#![cfg(feature = "game")]
+
+use rust_ecs::game::{register_game_prefabs, Actor, ActorState, Home, Obstacle, Position, Target, WaitTimer};
+use rust_ecs::{Reflect, World};
+
+#[test]
+fn test_actor_prefab_with_position_override() {
+    let mut world = World::new();
+    register_game_prefabs(&mut world);
+
+    let actor = world
+        .instantiate("actor", &[("Position", Position { x: 4, y: 2 }.to_value())])
+        .unwrap();
+    let home = world.instantiate("home", &[]).unwrap();
+
+    assert_eq!(world.get_component::<Position>(actor), Some(&Position { x: 4, y: 2 }));
+    assert_eq!(world.get_component::<Target>(actor), Some(&Target { x: 6, y: 8 }));
+    assert_eq!(world.get_component::<WaitTimer>(actor), Some(&WaitTimer { ticks: 0 }));
+    assert_eq!(world.get_component::<ActorState>(actor), Some(&ActorState::MovingToWork));
+    assert!(world.get_component::<Actor>(actor).is_some());
+    assert!(world.get_component::<Home>(home).is_some());
+    assert!(world.get_component::<Obstacle>(home).is_some());
+}
+
+#[cfg(feature = "ron")]
+#[test]
+fn test_prefab_file_matches_game_prefabs() {
+    let mut code = World::new();
+    register_game_prefabs(&mut code);
+    // Registers the component types; the loaded prefabs replace the ones built in code
+    let mut file = World::new();
+    register_game_prefabs(&mut file);
+    let loaded = file
+        .load_prefabs(concat!(env!("CARGO_MANIFEST_DIR"), "/src/game/prefabs.ron"))
+        .unwrap();
+    assert_eq!(loaded, 3);
+
+    for name in code.prefabs().names() {
+        let sorted = |world: &World| {
+            let mut components = world.prefabs().get(name).unwrap().components().to_vec();
+            components.sort_by(|a, b| a.0.cmp(&b.0));
+            components
+        };
+        assert_eq!(sorted(&code), sorted(&file), "prefab {}", name);
+    }
+}
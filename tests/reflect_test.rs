@@ -0,0 +1,51 @@
+//! Tests for runtime reflection of the game components.
+#![cfg(feature = "game")]
+
+use rust_ecs::game::{ActorState, Position, Target, WaitTimer};
+use rust_ecs::{Value, World};
+use std::collections::BTreeMap;
+
+fn registered_world() -> World {
+    let mut world = World::new();
+    world.register_component::<Position>();
+    world.register_component::<Target>();
+    world.register_component::<WaitTimer>();
+    world.register_component::<ActorState>();
+    world
+}
+
+#[test]
+fn test_inspect_and_edit_game_components_by_name() {
+    let mut world = registered_world();
+    let actor = world.create_entity();
+    world.add_component(actor, Position { x: 1, y: 2 });
+    world.add_component(actor, ActorState::MovingToWork);
+
+    let components: Vec<String> = world
+        .reflect_components(actor)
+        .iter()
+        .map(|(name, value)| format!("{} {}", name, value))
+        .collect();
+    assert_eq!(components, vec!["Position {x: 1, y: 2}", "ActorState \"MovingToWork\""]);
+
+    world.set_component_field(actor, "Position", "y", &Value::Int(5)).unwrap();
+    assert_eq!(world.get_component::<Position>(actor), Some(&Position { x: 1, y: 5 }));
+    assert!(world.set_component_field(actor, "Position", "z", &Value::Int(5)).is_err());
+    assert!(world.set_component_field(actor, "Target", "x", &Value::Int(5)).is_err());
+}
+
+#[test]
+fn test_construct_game_components_from_maps() {
+    let mut world = registered_world();
+    let actor = world.create_entity();
+
+    let mut fields = BTreeMap::new();
+    fields.insert("ticks".to_string(), Value::Int(3));
+    world.insert_component_value(actor, "WaitTimer", &Value::Map(fields)).unwrap();
+    assert_eq!(world.get_component::<WaitTimer>(actor), Some(&WaitTimer { ticks: 3 }));
+
+    let mut fields = BTreeMap::new();
+    fields.insert("ticks".to_string(), Value::Int(-1));
+    assert!(world.insert_component_value(actor, "WaitTimer", &Value::Map(fields)).is_err());
+    assert_eq!(world.component_field(actor, "WaitTimer", "ticks"), Some(Value::Int(3)));
+}
@@ -0,0 +1,140 @@
+//! Tests for applying recorded changes of registered components during replay.
+#![cfg(feature = "game")]
+
+use rust_ecs::game::{
+    initialize_headless_game, register_game_components, world_from_replay, Actor, GamePlugin, MovementSystem, Position,
+    Target,
+};
+use rust_ecs::replay_analysis::{parse_replay_log_str, write_replay_log};
+use rust_ecs::{DiffComponentChange, Entity, SystemUpdateDiff, World, WorldUpdateDiff};
+
+fn spawn_actor(world: &mut World) -> Entity {
+    let actor = world.create_entity();
+    world.add_component(actor, Position { x: 0, y: 0 });
+    world.add_component(actor, Actor);
+    world.add_component(actor, Target { x: 5, y: 3 });
+    actor
+}
+
+#[test]
+fn test_recorded_diffs_replay_through_text_log() {
+    let mut world = World::new();
+    register_game_components(&mut world);
+    let actor = spawn_actor(&mut world);
+    world.add_system(MovementSystem);
+    world.initialize_systems();
+    let recorded = world.get_update_history().len();
+    for _ in 0..4 {
+        world.update();
+    }
+
+    // Diffs are recorded as typed values, not Debug strings
+    let history = world.get_update_history();
+    let first_change = history.updates()[recorded].system_diffs()[0].component_changes()[0].to_string();
+    assert_eq!(first_change, "MOD Entity(0, 0) Position (x:Some(1),y:None)");
+
+    let mut log = Vec::new();
+    write_replay_log(history, &mut log).unwrap();
+    let parsed = parse_replay_log_str(&String::from_utf8(log).unwrap());
+
+    let mut replay = World::new();
+    register_game_components(&mut replay);
+    let replayed_actor = spawn_actor(&mut replay);
+    for update in &parsed.updates()[recorded..] {
+        replay.apply_update_diff(update);
+    }
+
+    assert_ne!(world.get_component::<Position>(actor), Some(&Position { x: 0, y: 0 }));
+    assert_eq!(
+        replay.get_component::<Position>(replayed_actor),
+        world.get_component::<Position>(actor)
+    );
+}
+
+#[test]
+fn test_additions_and_removals_replay() {
+    let entity = Entity::new(0, 0);
+    let mut system_diff = SystemUpdateDiff::new();
+    system_diff.record_component_change(DiffComponentChange::Added {
+        entity,
+        type_name: "Target".into(),
+        data: "(x:2,y:7)".into(),
+    });
+    system_diff.record_component_change(DiffComponentChange::Added {
+        entity,
+        type_name: "Actor".into(),
+        data: "()".into(),
+    });
+    system_diff.record_component_change(DiffComponentChange::Removed {
+        entity,
+        type_name: "Actor".into(),
+    });
+    let mut update = WorldUpdateDiff::new();
+    update.record(system_diff);
+
+    let mut replay = World::new();
+    register_game_components(&mut replay);
+    let replayed = replay.create_entity();
+    replay.apply_update_diff(&update);
+
+    assert_eq!(replay.get_component::<Target>(replayed), Some(&Target { x: 2, y: 7 }));
+    assert!(replay.get_component::<Actor>(replayed).is_none());
+
+    // Without the registration nothing can be decoded
+    let mut unregistered = World::new();
+    let entity = unregistered.create_entity();
+    unregistered.apply_update_diff(&update);
+    assert!(unregistered.get_component::<Target>(entity).is_none());
+}
+
+#[test]
+fn test_world_is_rebuilt_from_log_alone() {
+    let directory = std::env::temp_dir().join(format!("rust_ecs_initial_state_{}", std::process::id()));
+    let mut world = initialize_headless_game();
+    world
+        .enable_replay_logging_simple(directory.to_str().unwrap(), "session", 1)
+        .unwrap();
+    for _ in 0..5 {
+        world.update();
+    }
+    let session_id = world.replay_session_id().unwrap().to_string();
+    world.disable_replay_logging().unwrap();
+
+    let log_path = directory.join(format!("session_{}.log", session_id));
+    let history = World::parse_replay_log_file(log_path.to_str().unwrap()).unwrap();
+    let mut replay = world_from_replay(&history).unwrap();
+    for update in history.updates() {
+        replay.apply_update_diff(update);
+    }
+
+    // The randomly placed actors end up where they did in the recorded session
+    let actors = world.entities_with_component::<Actor>();
+    assert_eq!(actors.len(), 3);
+    assert_eq!(replay.entity_count(), world.entity_count());
+    for actor in actors {
+        assert_eq!(replay.get_component::<Position>(actor), world.get_component::<Position>(actor));
+    }
+
+    std::fs::remove_dir_all(&directory).unwrap();
+    assert!(world_from_replay(&rust_ecs::WorldUpdateHistory::new()).is_err());
+}
+
+#[test]
+fn test_game_records_its_seed() {
+    let build = |seed| {
+        let mut world = World::new();
+        world.add_plugin(GamePlugin {
+            render: false,
+            seed: Some(seed),
+        });
+        world
+    };
+    let (first, second) = (build(7), build(7));
+
+    assert_eq!(first.replay_metadata().seed, Some(7));
+    assert_eq!(first.replay_metadata(), second.replay_metadata());
+    assert!(first.replay_metadata().game_version.is_some());
+    for actor in first.entities_with_component::<Actor>() {
+        assert_eq!(first.get_component::<Position>(actor), second.get_component::<Position>(actor));
+    }
+}
@@ -1,3 +1,4 @@
+#![cfg(feature = "game")]
 use rust_ecs::{World, ReplayLogConfig, replay_analysis};
 
 #[test]
@@ -12,6 +13,7 @@ fn test_complete_replay_logging_workflow() {
         file_prefix: "integration_test".to_string(),
         flush_interval: 5,
         include_component_details: true,
+        ..ReplayLogConfig::default()
     };
     
     // Enable logging
@@ -22,8 +24,8 @@ fn test_complete_replay_logging_workflow() {
     assert!(world.replay_session_id().is_some());
     
     // Create some entities and run updates
-    let entity1 = world.create_entity();
-    let entity2 = world.create_entity();
+    let _entity1 = world.create_entity();
+    let _entity2 = world.create_entity();
     
     // Run some updates to generate history
     for i in 0..10 {
@@ -40,7 +42,7 @@ fn test_complete_replay_logging_workflow() {
     
     // Verify the analysis results
     assert_eq!(stats.total_updates, 10);
-    assert!(stats.total_system_executions >= 0); // May be 0 if no systems added
+    assert_eq!(stats.total_system_executions, 0); // No systems were added
     
     // Test anomaly detection (should find no anomalies in uniform data)
     let anomalous = replay_analysis::find_anomalous_frames(history, 2.0);
@@ -99,4 +101,77 @@ fn test_replay_analysis_with_activity() {
     }
     
     println!("✅ Replay analysis with activity test passed");
-}
\ No newline at end of file
+}
+#[test]
+fn test_background_replay_log_file() {
+    let log_directory = "test_background_logs";
+    let _ = std::fs::remove_dir_all(log_directory);
+
+    let mut world = World::new();
+    world
+        .enable_replay_logging(ReplayLogConfig {
+            enabled: true,
+            log_directory: log_directory.to_string(),
+            file_prefix: "background".to_string(),
+            flush_interval: 1,
+            background: Some(rust_ecs::BackgroundWriting::default()),
+            ..ReplayLogConfig::default()
+        })
+        .expect("Failed to enable logging");
+    world.create_entity();
+    for _ in 0..25 {
+        world.update();
+    }
+    let session_id = world.replay_session_id().unwrap().to_string();
+    world.disable_replay_logging().expect("Failed to disable logging");
+
+    // Disabling waits for the writer thread, so the file is complete
+    let log_file = format!("{}/background_{}.log", log_directory, session_id);
+    let content = std::fs::read_to_string(&log_file).unwrap();
+    assert!(content.ends_with("# End of replay log - Total updates: 25\n"));
+    assert!(replay_analysis::validate_replay_log_str(&content).is_empty());
+    assert_eq!(World::parse_replay_log_file(&log_file).unwrap().len(), 25);
+
+    let _ = std::fs::remove_dir_all(log_directory);
+}
+
+#[test]
+fn test_recorded_game_logs_are_consistent() {
+    assert_eq!(World::validate_replay_log("tests/golden/headless_game_seed_7.log").unwrap(), Vec::new());
+
+    let log_directory = "test_consistent_logs";
+    let _ = std::fs::remove_dir_all(log_directory);
+    let mut world = World::new();
+    // Fighters die and their weapons are despawned with them
+    world.add_plugin(rust_ecs::game::CombatPlugin {
+        render: false,
+        ..Default::default()
+    });
+    world.initialize_systems();
+    // The initial state lists the fighters spawned by the plugin
+    world
+        .enable_replay_logging(ReplayLogConfig {
+            enabled: true,
+            log_directory: log_directory.to_string(),
+            file_prefix: "combat".to_string(),
+            ..ReplayLogConfig::default()
+        })
+        .expect("Failed to enable logging");
+    world.update_n(40);
+    let session_id = world.replay_session_id().unwrap().to_string();
+    world.disable_replay_logging().expect("Failed to disable logging");
+
+    let log_file = format!("{}/combat_{}.log", log_directory, session_id);
+    let content = std::fs::read_to_string(&log_file).unwrap();
+    assert!(content.contains("REMOVE_ENTITY"));
+    assert_eq!(World::validate_replay_log(&log_file).unwrap(), Vec::new());
+
+    // A hand-edited log modifying a component that was never added
+    let edited = content.replacen("MOD Entity(0, ", "MOD Entity(9, ", 1);
+    std::fs::write(&log_file, &edited).unwrap();
+    let issues = World::validate_replay_log(&log_file).unwrap();
+    assert_eq!(issues.len(), 1, "{:?}", issues);
+    assert!(issues[0].message.starts_with("MOD refers to Entity(9, "), "{}", issues[0]);
+
+    let _ = std::fs::remove_dir_all(log_directory);
+}
@@ -0,0 +1,124 @@
+//! Property-based tests of the replay log parser on malformed input, the
+//! same checks the `fuzz/` target runs with cargo-fuzz.
+//!
+//! For any text:
+//! - parsing and validating never panic
+//! - every parse error points at a line of the text
+//! - the parsed history is written as a log that parses without errors
+
+use proptest::prelude::*;
+use rust_ecs::replay_analysis;
+
+/// Log of a world with entities, components, groups and failures
+const RECORDED_LOG: &str = "\
+# ECS Replay Log
+# Seed: 7
+INITIAL_STATE
+  WORLD_OPERATIONS: 1
+    CREATE_ENTITY Entity(0, 0)
+  COMPONENT_CHANGES: 1
+    ADD Entity(0, 0) u32 7
+  RESOURCES: 1
+    RESOURCE Score 3
+
+UPDATE 1
+  ANNOTATION 120ms wave 2 started
+SYSTEMS: 2
+  SYSTEM 0
+    COMPONENT_CHANGES: 2
+      MOD Entity(0, 0) u32 8
+      ADD Entity(0, 1) Name \"scout\"
+    WORLD_OPERATIONS: 2
+      CREATE_ENTITY Entity(0, 1)
+      ADD_TO_GROUP Entity(0, 1) scouts
+    ORDER: O C O C
+    STATS: time_us=120 queries=2 matched=3
+    CAUSE: Entity(0, 0) u32 <- u32
+  SYSTEM 1
+    FAILED: index out of bounds
+KEYFRAME 1
+  WORLD_OPERATIONS: 1
+    CREATE_ENTITY Entity(0, 0)
+
+UPDATE 2
+SYSTEMS: 1
+  SYSTEM 0
+    COMPONENT_CHANGES: 1
+      REM Entity(0, 1) Name
+    WORLD_OPERATIONS: 3
+      REMOVE_FROM_GROUP Entity(0, 1) scouts
+      DISABLE_ENTITY Entity(0, 0)
+      CLEAR_ENTITIES
+    ERROR: out of scouts
+# End of replay log - Total updates: 2
+";
+
+fn check_parser(contents: &str) -> Result<(), TestCaseError> {
+    let (history, errors) = replay_analysis::parse_replay_log_with_errors(contents);
+    let lines = contents.lines().count();
+    for error in &errors {
+        prop_assert!((1..=lines).contains(&error.line), "{} of {} lines", error, lines);
+    }
+    replay_analysis::validate_replay_log_str(contents);
+
+    let mut log = Vec::new();
+    replay_analysis::write_replay_log(&history, &mut log).unwrap();
+    let log = String::from_utf8(log).unwrap();
+    let (reparsed, errors) = replay_analysis::parse_replay_log_with_errors(&log);
+    prop_assert!(errors.is_empty(), "{:?} in\n{}", errors, log);
+    prop_assert!(replay_analysis::compare_histories(&history, &reparsed).is_empty(), "{}", log);
+    Ok(())
+}
+
+/// Lines starting with a log keyword followed by arbitrary text
+fn log_line() -> impl Strategy<Value = String> {
+    let keyword = prop::sample::select(vec![
+        "UPDATE", "SYSTEMS:", "SYSTEM", "INITIAL_STATE", "KEYFRAME", "RESOURCES:", "RESOURCE", "ANNOTATION",
+        "COMPONENT_CHANGES:", "WORLD_OPERATIONS:", "ADD", "MOD", "REM", "FAILED:", "ERROR:", "ASSERT:", "STATS:", "ORDER:",
+        "CAUSE:", "CREATE_ENTITY", "REMOVE_ENTITY", "DISABLE_ENTITY", "ENABLE_ENTITY", "CREATE_WORLD",
+        "REMOVE_WORLD", "ADD_SYSTEM", "ADD_TO_GROUP", "REMOVE_FROM_GROUP", "CLEAR_ALL", "#",
+    ]);
+    let argument = prop_oneof![
+        Just(String::new()),
+        "[0-9]{1,3}".prop_map(|n| format!(" {}", n)),
+        "[0-9]{1,2}".prop_map(|n| format!(" Entity(0, {}) u32 {}", n, n)),
+        "[0-9]{1,2}".prop_map(|n| format!(" Entity(0, {})", n)),
+        " ?[ -~]{0,20}",
+        " ?\\PC{0,8}",
+    ];
+    (keyword, argument).prop_map(|(keyword, argument)| format!("{}{}", keyword, argument))
+}
+
+proptest! {
+    #[test]
+    fn test_arbitrary_text(contents in "(?s)\\PC{0,200}") {
+        check_parser(&contents)?;
+    }
+
+    #[test]
+    fn test_arbitrary_log_lines(lines in prop::collection::vec(log_line(), 0..30)) {
+        check_parser(&lines.join("\n"))?;
+    }
+
+    #[test]
+    fn test_truncated_logs(end in 0..RECORDED_LOG.len()) {
+        check_parser(&RECORDED_LOG[..end])?;
+    }
+
+    #[test]
+    fn test_logs_with_a_changed_line(index in 0..42usize, line in log_line()) {
+        let mut lines: Vec<&str> = RECORDED_LOG.lines().collect();
+        let index = index % lines.len();
+        lines[index] = &line;
+        check_parser(&lines.join("\n"))?;
+    }
+}
+
+#[test]
+fn test_recorded_log_parses_without_errors() {
+    let history = replay_analysis::try_parse_replay_log_str(RECORDED_LOG).unwrap();
+    assert_eq!(history.len(), 2);
+    assert_eq!(history.keyframes().len(), 1);
+    assert_eq!(history.metadata().seed, Some(7));
+    assert!(replay_analysis::validate_replay_log_str(RECORDED_LOG).is_empty());
+}
@@ -0,0 +1,59 @@
+//! Tests for systems scripted in Rhai.
+#![cfg(all(feature = "scripting", feature = "game"))]
+
+use rust_ecs::game::{Actor, Position};
+use rust_ecs::scripting::ScriptSystem;
+use rust_ecs::World;
+use std::fs;
+
+fn write_script(path: &std::path::Path, step: i32) {
+    let source = format!(
+        r#"
+        fn update() {{
+            for actor in query(["Actor", "Position"]) {{
+                let position = get(actor, "Position");
+                position.x += {};
+                set(actor, "Position", position);
+            }}
+        }}
+        "#,
+        step
+    );
+    fs::write(path, source).expect("Failed to write script");
+}
+
+#[test]
+fn test_script_file_is_reloaded_when_changed() {
+    let directory = std::env::temp_dir().join(format!("rust_ecs_scripting_{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    let script_path = directory.join("move_right.rhai");
+    write_script(&script_path, 1);
+
+    let mut world = World::new();
+    let actor = world.create_entity();
+    world.add_component(actor, Position { x: 0, y: 0 });
+    world.add_component(actor, Actor);
+    let system = ScriptSystem::from_file(&script_path)
+        .unwrap()
+        .read::<Actor>()
+        .write::<Position>();
+    world.add_system(system);
+    world.initialize_systems();
+
+    world.update();
+    assert_eq!(world.get_component::<Position>(actor), Some(&Position { x: 1, y: 0 }));
+
+    // Make sure the modification time differs on file systems with coarse timestamps
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    write_script(&script_path, 10);
+    world.update();
+    assert_eq!(world.get_component::<Position>(actor), Some(&Position { x: 11, y: 0 }));
+
+    // Script changes are recorded like changes of Rust systems
+    let last_update = world.get_update_history().updates().last().unwrap();
+    let changes = last_update.system_diffs()[0].component_changes();
+    assert_eq!(changes.len(), 1);
+    assert!(changes[0].to_string().starts_with("MOD Entity(0, 0) Position"));
+
+    fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,115 @@
+//! Tests for controlling a headless server over its control socket.
+#![cfg(all(feature = "server", feature = "game"))]
+
+use rust_ecs::game::{Actor, MovementSystem, Position, Target};
+use rust_ecs::server::{Server, ServerConfig, ServerEvent};
+use rust_ecs::World;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::sync::mpsc;
+
+fn create_server_world() -> World {
+    let mut world = World::new();
+    let actor = world.create_entity();
+    world.add_component(actor, Position { x: 2, y: 3 });
+    world.add_component(actor, Actor);
+    world.add_component(actor, Target { x: 9, y: 9 });
+    world.add_system(MovementSystem);
+    world.initialize_systems();
+    world
+}
+
+struct ControlConnection {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl ControlConnection {
+    fn send(&mut self, command: &str) -> ServerEvent {
+        writeln!(self.writer, "{}", command).unwrap();
+        self.next_event()
+    }
+
+    fn next_event(&mut self) -> ServerEvent {
+        let mut line = String::new();
+        self.reader.read_line(&mut line).unwrap();
+        serde_json::from_str(&line).unwrap()
+    }
+}
+
+#[test]
+fn test_remote_pause_step_snapshot_and_stream() {
+    let (address_tx, address_rx) = mpsc::channel();
+    let server_thread = std::thread::spawn(move || {
+        let config = ServerConfig {
+            tick_rate: 0.0,
+            control_address: Some("127.0.0.1:0".to_string()),
+            start_paused: true,
+        };
+        let mut server = Server::new(create_server_world(), config).unwrap();
+        server.register_component::<Position>();
+        address_tx.send(server.local_addr().unwrap()).unwrap();
+        server.run();
+        server.world().frame_number()
+    });
+
+    let stream = TcpStream::connect(address_rx.recv().unwrap()).unwrap();
+    let mut control = ControlConnection {
+        reader: BufReader::new(stream.try_clone().unwrap()),
+        writer: stream,
+    };
+
+    match control.send("status") {
+        ServerEvent::Status { frame, paused, entities, .. } => {
+            assert_eq!(frame, 0);
+            assert!(paused);
+            assert_eq!(entities, 1);
+        }
+        event => panic!("unexpected reply {:?}", event),
+    }
+
+    assert!(matches!(control.send("step 2"), ServerEvent::Ok { frame: 2 }));
+
+    match control.send("snapshot") {
+        ServerEvent::Snapshot { frame, diff } => {
+            assert_eq!(frame, 2);
+            let system_diff = &diff.system_diffs()[0];
+            assert_eq!(system_diff.world_operations().len(), 1);
+            assert_eq!(system_diff.component_changes().len(), 1);
+            assert_eq!(
+                system_diff.component_changes()[0].to_string(),
+                "ADD Entity(0, 0) Position Position { x: 4, y: 5 }"
+            );
+        }
+        event => panic!("unexpected reply {:?}", event),
+    }
+
+    assert!(matches!(control.send("stream on"), ServerEvent::Ok { .. }));
+    assert!(matches!(control.send("bogus"), ServerEvent::Error { .. }));
+    writeln!(control.writer, "step").unwrap();
+    match control.next_event() {
+        ServerEvent::Update { frame, diff } => {
+            assert_eq!(frame, 3);
+            assert_eq!(diff.system_diffs()[0].component_changes().len(), 1);
+        }
+        event => panic!("unexpected event {:?}", event),
+    }
+    assert!(matches!(control.next_event(), ServerEvent::Ok { frame: 3 }));
+
+    // Resumed at an unlimited tick rate, updates keep streaming in
+    assert!(matches!(control.send("resume"), ServerEvent::Ok { .. }));
+    let mut last_frame = 3;
+    for _ in 0..3 {
+        match control.next_event() {
+            ServerEvent::Update { frame, .. } => {
+                assert!(frame > last_frame);
+                last_frame = frame;
+            }
+            event => panic!("unexpected event {:?}", event),
+        }
+    }
+
+    writeln!(control.writer, "shutdown").unwrap();
+    let final_frame = server_thread.join().unwrap();
+    assert!(final_frame >= last_frame);
+}
@@ -1,4 +1,5 @@
-use rust_ecs::{World, game::game::{MovementSystem, WaitSystem, RenderSystem}};
+#![cfg(feature = "game")]
+use rust_ecs::{World, game::game::{register_game_systems, MovementSystem, WaitSystem, RenderSystem}};
 
 #[test]
 fn test_system_addition_recording_and_replay() {
@@ -31,6 +32,7 @@ fn test_system_addition_recording_and_replay() {
     
     // Now test replay: create a fresh world and apply the history
     let mut replay_world = World::new();
+    register_game_systems(&mut replay_world);
     
     // Apply the recorded operations
     for update in history.updates() {
@@ -39,7 +41,8 @@ fn test_system_addition_recording_and_replay() {
     
     // Check how many updates we have so far (should be 0 - replaying doesn't record history)
     let replay_history_before_new_update = replay_world.get_update_history();
-    println!("Replay world has {} updates after applying recorded operations", replay_history_before_new_update.len());
+    print!("{}", replay_world.stats());
+    assert_eq!(replay_world.stats().system_count, original_world.stats().system_count);
     assert_eq!(replay_history_before_new_update.len(), 0, "Replay world should have 0 updates after applying recorded operations (replay doesn't re-record)");
     
     // The replay world should now have the same systems as the original
@@ -48,12 +51,12 @@ fn test_system_addition_recording_and_replay() {
     
     // The replay world should now have 1 update: the new update after replay
     let replay_history = replay_world.get_update_history();
-    println!("Replay world has {} updates after calling update()", replay_history.len());
+    print!("{}", replay_world.stats());
     assert_eq!(replay_history.len(), 1, "Replay world should have 1 update after replay + 1 update call");
     
     // The update should have system diffs from the replayed system
     let update = &replay_history.updates()[0];
-    assert!(update.system_diffs().len() >= 1, "Update should have at least 1 system diff from the replayed MovementSystem");
+    assert!(!update.system_diffs().is_empty(), "Update should have at least 1 system diff from the replayed MovementSystem");
     
     println!("✅ System addition recording and replay test passed");
 }
@@ -66,7 +69,7 @@ fn test_multiple_system_additions_replay() {
     // Add multiple systems
     original_world.add_system(MovementSystem);
     original_world.add_system(WaitSystem);
-    original_world.add_system(RenderSystem::default());
+    original_world.add_system(RenderSystem);
     
     // Run some updates
     original_world.update();
@@ -78,6 +81,7 @@ fn test_multiple_system_additions_replay() {
     
     // Create a fresh world for replay
     let mut replay_world = World::new();
+    register_game_systems(&mut replay_world);
     
     // Apply all the recorded operations
     for update in history.updates() {
@@ -111,6 +115,7 @@ fn test_empty_world_replay() {
     
     // Create a completely fresh world (simulating the problem statement requirement)
     let mut fresh_world = World::new();
+    register_game_systems(&mut fresh_world);
     
     // Replay everything from the beginning
     for update in complete_history.updates() {
@@ -0,0 +1,46 @@
+//! Tests for exporting the system dependency graph.
+#![cfg(feature = "game")]
+
+use rust_ecs::game::{MovementSystem, RenderSystem, WaitSystem};
+use rust_ecs::{ComponentSet, World};
+
+#[test]
+fn test_component_set_type_names() {
+    assert!(<() as ComponentSet>::type_names().is_empty());
+    assert_eq!(
+        <(rust_ecs::game::Position, rust_ecs::game::Target) as ComponentSet>::type_names(),
+        vec!["Position", "Target"]
+    );
+}
+
+#[test]
+fn test_system_graph_dot_for_game_systems() {
+    let mut world = World::new();
+    world.add_system(MovementSystem);
+    world.add_system(WaitSystem);
+    world.add_system(RenderSystem);
+
+    let dot = world.system_graph_dot();
+    assert!(dot.starts_with("digraph systems {"));
+    assert!(dot.trim_end().ends_with('}'));
+
+    // Systems in execution order and every declared component
+    assert!(dot.contains("\"system_0\" [label=\"1. MovementSystem\""));
+    assert!(dot.contains("\"system_1\" [label=\"2. WaitSystem\""));
+    assert!(dot.contains("\"system_2\" [label=\"3. RenderSystem\""));
+    for component in ["Actor", "Position", "Target", "WaitTimer"] {
+        assert!(dot.contains(&format!("\"component_{}\" [label=\"{}\"", component, component)));
+    }
+
+    // MovementSystem writes Position, which WaitSystem reads
+    assert!(dot.contains("\"system_0\" -> \"component_Position\" [label=\"write\""));
+    assert!(dot.contains("\"component_Position\" -> \"system_1\" [label=\"read\"]"));
+    assert!(dot.contains("\"system_0\" -> \"system_1\" [label=\"Position"));
+}
+
+#[test]
+fn test_system_graph_dot_empty_world() {
+    let world = World::new();
+    let dot = world.system_graph_dot();
+    assert!(!dot.contains("->"));
+}
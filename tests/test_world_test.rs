@@ -0,0 +1,29 @@
+//! Tests of the game written with `TestWorld`.
+
+#![cfg(feature = "game")]
+
+use rust_ecs::game::{GamePlugin, Position};
+use rust_ecs::testing::TestWorld;
+use rust_ecs::Entity;
+
+fn actor_positions(world: &TestWorld) -> Vec<Position> {
+    (2..5)
+        .map(|id| world.get_component::<Position>(Entity::new(0, id)).copied().unwrap())
+        .collect()
+}
+
+#[test]
+fn test_game_is_deterministic_in_test_world() {
+    let mut first = TestWorld::with_seed(11);
+    first.add_plugin(GamePlugin::headless());
+    first.step(10);
+
+    first.assert_entity_count(5);
+    first.assert_component_eq(Entity::new(0, 0), Position { x: 1, y: 1 });
+    assert_eq!(first.replay_metadata().seed, Some(11));
+
+    let mut second = TestWorld::with_seed(11);
+    second.add_plugin(GamePlugin::headless());
+    second.step(10);
+    assert_eq!(actor_positions(&first), actor_positions(&second));
+}
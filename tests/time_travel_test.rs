@@ -0,0 +1,161 @@
+//! Tests for component watches and rewinding.
+#![cfg(feature = "game")]
+
+use rust_ecs::game::{Position, WaitTimer};
+use rust_ecs::{Out, System, World, WorldView};
+
+/// Moves every positioned entity one cell to the right
+struct StepSystem;
+impl System for StepSystem {
+    type InComponents = ();
+    type OutComponents = (Position,);
+
+    fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+    fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+        for (_entity, position) in world.query_components::<(Out<Position>,)>() {
+            position.x += 1;
+        }
+    }
+
+    fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+}
+
+/// Counts down every wait timer
+struct TickSystem;
+impl System for TickSystem {
+    type InComponents = ();
+    type OutComponents = (WaitTimer,);
+
+    fn initialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+
+    fn update(&mut self, world: &mut WorldView<Self::InComponents, Self::OutComponents>) {
+        for (_entity, timer) in world.query_components::<(Out<WaitTimer>,)>() {
+            timer.ticks = timer.ticks.saturating_sub(1);
+        }
+    }
+
+    fn deinitialize(&mut self, _world: &mut WorldView<Self::InComponents, Self::OutComponents>) {}
+}
+
+fn setup_world() -> (World, rust_ecs::Entity, rust_ecs::Entity) {
+    let mut world = World::new();
+    let first = world.create_entity();
+    world.add_component(first, Position { x: 0, y: 0 });
+    world.add_component(first, WaitTimer { ticks: 5 });
+    let second = world.create_entity();
+    world.add_component(second, Position { x: 3, y: 3 });
+    world.add_system(TickSystem);
+    world.add_system(StepSystem);
+    (world, first, second)
+}
+
+#[test]
+fn test_watch_pauses_after_changing_system() {
+    let (mut world, first, _second) = setup_world();
+    let watch = world.add_watch::<Position>(first);
+
+    let hit = world.debug_update().expect("watched position should change");
+    assert_eq!(hit.watch_id, watch);
+    assert_eq!(hit.frame, 0);
+    assert_eq!(hit.entity, first);
+    assert_eq!(hit.type_name, "Position");
+    assert!(hit.system_name.ends_with("StepSystem"));
+    assert_eq!(hit.old_value.as_deref(), Some("Position { x: 0, y: 0 }"));
+    assert_eq!(hit.new_value.as_deref(), Some("Position { x: 1, y: 0 }"));
+    assert!(world.is_paused_mid_frame());
+    assert_eq!(world.frame_number(), 0);
+
+    // Resuming finishes the frame
+    assert!(world.debug_update().is_none());
+    assert!(!world.is_paused_mid_frame());
+    assert_eq!(world.frame_number(), 1);
+}
+
+#[test]
+fn test_watch_filter_and_removal() {
+    let (mut world, first, second) = setup_world();
+    let watch = world.add_watch::<WaitTimer>(second);
+
+    // Only the first entity has a timer, so the watch never triggers
+    assert!(world.debug_update().is_none());
+
+    assert!(world.remove_watch(watch));
+    assert!(!world.remove_watch(watch));
+    world.add_watch::<WaitTimer>(first);
+    let hit = world.debug_update().expect("timer should tick");
+    assert_eq!(hit.entity, first);
+    assert!(hit.system_name.ends_with("TickSystem"));
+    assert_eq!(hit.new_value.as_deref(), Some("WaitTimer { ticks: 3 }"));
+}
+
+#[test]
+fn test_update_ignores_watches() {
+    let (mut world, first, _second) = setup_world();
+    world.add_watch::<Position>(first);
+
+    world.update();
+    assert!(!world.is_paused_mid_frame());
+    assert_eq!(world.get_component::<Position>(first), Some(&Position { x: 1, y: 0 }));
+}
+
+#[test]
+fn test_rewind_restores_components_and_history() {
+    let (mut world, first, second) = setup_world();
+    world.track_rewind::<Position>();
+    world.track_rewind::<WaitTimer>();
+    let history_before = world.get_update_history().len();
+
+    for _ in 0..3 {
+        world.update();
+    }
+    assert_eq!(world.rewindable_frames(), 3);
+    assert_eq!(world.get_component::<Position>(first), Some(&Position { x: 3, y: 0 }));
+
+    assert_eq!(world.rewind(2), 2);
+    assert_eq!(world.frame_number(), 1);
+    assert_eq!(world.get_component::<Position>(first), Some(&Position { x: 1, y: 0 }));
+    assert_eq!(world.get_component::<Position>(second), Some(&Position { x: 4, y: 3 }));
+    assert_eq!(world.get_component::<WaitTimer>(first), Some(&WaitTimer { ticks: 4 }));
+    assert_eq!(world.get_update_history().len(), history_before + 1);
+
+    // Rewinding past the recorded frames stops at the oldest one
+    assert_eq!(world.rewind(5), 1);
+    assert_eq!(world.get_component::<Position>(first), Some(&Position { x: 0, y: 0 }));
+
+    // Running again after a rewind records fresh frames
+    world.update();
+    assert_eq!(world.get_component::<Position>(first), Some(&Position { x: 1, y: 0 }));
+    assert_eq!(world.rewindable_frames(), 1);
+}
+
+#[test]
+fn test_rewind_discards_paused_frame() {
+    let (mut world, first, _second) = setup_world();
+    world.add_watch::<Position>(first);
+    world.track_rewind::<WaitTimer>();
+    world.update();
+
+    assert!(world.debug_update().is_some());
+    assert_eq!(world.get_component::<Position>(first), Some(&Position { x: 2, y: 0 }));
+
+    // The partial frame is undone without counting towards the requested frames
+    assert_eq!(world.rewind(0), 0);
+    assert!(!world.is_paused_mid_frame());
+    assert_eq!(world.get_component::<Position>(first), Some(&Position { x: 1, y: 0 }));
+    assert_eq!(world.get_component::<WaitTimer>(first), Some(&WaitTimer { ticks: 4 }));
+}
+
+#[test]
+fn test_rewind_capacity_drops_oldest_frames() {
+    let (mut world, first, _second) = setup_world();
+    world.track_rewind::<Position>();
+    world.set_rewind_capacity(2);
+
+    for _ in 0..4 {
+        world.update();
+    }
+    assert_eq!(world.rewindable_frames(), 2);
+    assert_eq!(world.rewind(3), 2);
+    assert_eq!(world.get_component::<Position>(first), Some(&Position { x: 2, y: 0 }));
+}